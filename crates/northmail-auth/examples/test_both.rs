@@ -1,5 +1,5 @@
 use northmail_auth::{AuthManager, GoaAuthType};
-use northmail_imap::{ImapClient, SimpleImapClient};
+use northmail_imap::ImapClient;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tokio::runtime::Builder::new_current_thread()
@@ -20,13 +20,13 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
         
         match account.auth_type {
             GoaAuthType::OAuth2 if account.provider_type == "google" => {
-                // Test Gmail with SimpleImapClient
+                // Test Gmail with ImapClient
                 match auth_manager.get_xoauth2_token_for_goa(&account.id).await {
                     Ok((email, token)) => {
                         println!("  Got OAuth2 token");
                         
-                        // Use SimpleImapClient for Gmail
-                        let mut client = SimpleImapClient::new();
+                        // Use ImapClient for Gmail
+                        let mut client = ImapClient::new();
                         match client.connect_gmail(&email, &token).await {
                             Ok(_) => {
                                 println!("  Connected to Gmail!");
@@ -57,10 +57,10 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
                         let (tx, rx) = std::sync::mpsc::channel();
                         std::thread::spawn(move || {
                             let result = async_std::task::block_on(async {
-                                let mut client = ImapClient::new(&host, 993);
-                                match client.authenticate_login(&username, &password).await {
+                                let mut client = ImapClient::new();
+                                match client.connect_login(&host, 993, &username, &password).await {
                                     Ok(_) => {
-                                        match client.select_folder("INBOX").await {
+                                        match client.select("INBOX").await {
                                             Ok(folder) => {
                                                 let count = folder.message_count.unwrap_or(0);
                                                 let _ = client.logout().await;
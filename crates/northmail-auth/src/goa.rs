@@ -48,6 +48,9 @@ trait GoaMailInterface {
     #[zbus(property)]
     fn imap_use_ssl(&self) -> zbus::Result<bool>;
 
+    #[zbus(property)]
+    fn imap_use_tls(&self) -> zbus::Result<bool>;
+
     #[zbus(property)]
     fn imap_user_name(&self) -> zbus::Result<String>;
 
@@ -94,6 +97,12 @@ pub enum GoaAccountEvent {
     AccountAdded,
     /// An account with Mail interface was removed
     AccountRemoved,
+    /// An account's credentials or mail settings were updated, e.g. after
+    /// the user signed in again in Settings
+    CredentialsChanged {
+        /// D-Bus object path of the account
+        object_path: String,
+    },
 }
 
 /// Represents a mail-enabled GOA account
@@ -115,6 +124,8 @@ pub struct GoaAccount {
     pub imap_host: Option<String>,
     /// IMAP username if available
     pub imap_username: Option<String>,
+    /// Connect with STARTTLS instead of implicit TLS
+    pub imap_starttls: bool,
     /// SMTP host if available
     pub smtp_host: Option<String>,
     /// Authentication type
@@ -229,6 +240,9 @@ impl GoaManager {
             let email = mail_proxy.email_address().await.unwrap_or_default();
             let imap_host = mail_proxy.imap_host().await.ok();
             let imap_username = mail_proxy.imap_user_name().await.ok();
+            // GOA's "STARTTLS after connecting" choice; ImapUseSsl means implicit TLS
+            let imap_starttls = !mail_proxy.imap_use_ssl().await.unwrap_or(true)
+                && mail_proxy.imap_use_tls().await.unwrap_or(false);
             let smtp_host = mail_proxy.smtp_host().await.ok();
 
             if email.is_empty() {
@@ -259,6 +273,7 @@ impl GoaManager {
                 mail_enabled: true,
                 imap_host,
                 imap_username,
+                imap_starttls,
                 smtp_host,
                 auth_type,
                 presentation_identity,
@@ -420,7 +435,24 @@ impl GoaManager {
                 .unwrap_or(false);
             (GoaAccountEvent::AccountRemoved, has_mail)
         });
-        let mut merged = futures::stream::select(added, removed);
+
+        // GOA reports re-authentication as AttentionNeeded going false on the
+        // Account interface; edited server settings change the Mail interface
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface("org.freedesktop.DBus.Properties")
+            .and_then(|b| b.member("PropertiesChanged"))
+            .and_then(|b| b.path_namespace("/org/gnome/OnlineAccounts/Accounts"))
+            .map_err(|e| AuthError::DbusError(e.to_string()))?
+            .build();
+        let changed_stream = zbus::MessageStream::for_match_rule(rule, &conn, None)
+            .await
+            .map_err(|e| AuthError::DbusError(e.to_string()))?;
+        let changed = changed_stream.filter_map(|message| {
+            futures::future::ready(message.ok().and_then(credentials_changed))
+        });
+
+        let mut merged = futures::stream::select(futures::stream::select(added, removed), changed);
 
         info!("GOA account watcher started, listening for changes...");
 
@@ -437,3 +469,21 @@ impl GoaManager {
         Ok(())
     }
 }
+
+/// The event for a PropertiesChanged signal that means an account's
+/// connections should log in again, if it is one
+fn credentials_changed(message: zbus::Message) -> Option<(GoaAccountEvent, bool)> {
+    let object_path = message.header().path()?.to_string();
+    let signal = zbus::fdo::PropertiesChanged::from_message(message)?;
+    let args = signal.args().ok()?;
+    let relevant = match args.interface_name().as_str() {
+        "org.gnome.OnlineAccounts.Account" => args
+            .changed_properties()
+            .get("AttentionNeeded")
+            .and_then(|value| bool::try_from(value).ok())
+            == Some(false),
+        "org.gnome.OnlineAccounts.Mail" => true,
+        _ => false,
+    };
+    relevant.then_some((GoaAccountEvent::CredentialsChanged { object_path }, true))
+}
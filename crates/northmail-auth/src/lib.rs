@@ -13,7 +13,7 @@ mod xoauth2;
 pub use error::{AuthError, AuthResult};
 pub use goa::{GoaAccount, GoaAccountEvent, GoaAuthType, GoaManager};
 pub use oauth2::{OAuth2Config, OAuth2Flow, OAuth2Provider, TokenPair};
-pub use secrets::SecretStore;
+pub use secrets::{set_profile as set_secrets_profile, JmapCredentials, SecretStore};
 pub use xoauth2::XOAuth2Token;
 
 /// Gmail OAuth2 configuration
@@ -55,6 +55,8 @@ pub enum AuthMethod {
     Goa { account_id: String },
     /// Standalone OAuth2 with tokens in libsecret
     OAuth2 { email: String },
+    /// JMAP server, with a token or password in libsecret
+    Jmap { email: String },
 }
 
 impl AuthMethod {
@@ -62,6 +64,7 @@ impl AuthMethod {
         match self {
             AuthMethod::Goa { account_id } => account_id,
             AuthMethod::OAuth2 { email } => email,
+            AuthMethod::Jmap { email } => email,
         }
     }
 }
@@ -168,6 +171,45 @@ impl AuthManager {
 
                 Ok(XOAuth2Token::new(email, &tokens.access_token))
             }
+            AuthMethod::Jmap { email } => Err(AuthError::InvalidConfig(format!(
+                "{} is a JMAP account and has no XOAUTH2 token",
+                email
+            ))),
         }
     }
+
+    /// Store the credentials of a JMAP account
+    pub async fn store_jmap_credentials(
+        &self,
+        email: &str,
+        credentials: &JmapCredentials,
+    ) -> AuthResult<()> {
+        self.secret_store.store_jmap_credentials(email, credentials).await
+    }
+
+    /// Retrieve the credentials of a JMAP account
+    pub async fn get_jmap_credentials(&self, email: &str) -> AuthResult<JmapCredentials> {
+        self.secret_store
+            .get_jmap_credentials(email)
+            .await?
+            .ok_or_else(|| AuthError::TokenNotFound(email.to_string()))
+    }
+
+    /// Store the password of the SMTP server an identity sends through
+    pub async fn store_smtp_password(&self, email: &str, password: &str) -> AuthResult<()> {
+        self.secret_store.store_smtp_password(email, password).await
+    }
+
+    /// Retrieve the password of the SMTP server an identity sends through
+    pub async fn get_smtp_password(&self, email: &str) -> AuthResult<String> {
+        self.secret_store
+            .get_smtp_password(email)
+            .await?
+            .ok_or_else(|| AuthError::TokenNotFound(email.to_string()))
+    }
+
+    /// Delete the SMTP password of an identity
+    pub async fn delete_smtp_password(&self, email: &str) -> AuthResult<()> {
+        self.secret_store.delete_smtp_password(email).await
+    }
 }
@@ -1,14 +1,43 @@
 //! Secure credential storage using libsecret
 //!
-//! Stores OAuth2 tokens in the system keyring via libsecret.
+//! Stores OAuth2 tokens and JMAP credentials in the system keyring via
+//! libsecret.
 
 use crate::{AuthError, AuthResult, TokenPair};
 use std::collections::HashMap;
+use std::sync::OnceLock;
 use tracing::{debug, info};
 
 /// Schema for storing NorthMail credentials
 const SCHEMA_NAME: &str = "com.petrariu.NorthMail.Credentials";
 
+/// Profile whose credentials this process uses, when not the default one
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Keep this process's credentials apart from other profiles'. Lookups
+/// match on the schema name, so each named profile gets its own schema;
+/// the default profile keeps the original one. Call once, before any
+/// [`SecretStore`] is created.
+pub fn set_profile(name: &str) {
+    let _ = PROFILE.set(name.to_string());
+}
+
+/// Credentials for a JMAP account: an API token, or a username and
+/// password when `username` is set
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct JmapCredentials {
+    pub username: Option<String>,
+    pub secret: String,
+}
+
+impl std::fmt::Debug for JmapCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JmapCredentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Manages secure storage of credentials
 pub struct SecretStore {
     schema: libsecret::Schema,
@@ -21,8 +50,12 @@ impl SecretStore {
         attributes.insert("type", libsecret::SchemaAttributeType::String);
         attributes.insert("email", libsecret::SchemaAttributeType::String);
 
+        let schema_name = match PROFILE.get() {
+            Some(profile) => format!("{}.{}", SCHEMA_NAME, profile),
+            None => SCHEMA_NAME.to_string(),
+        };
         let schema = libsecret::Schema::new(
-            SCHEMA_NAME,
+            &schema_name,
             libsecret::SchemaFlags::NONE,
             attributes,
         );
@@ -93,6 +126,152 @@ impl SecretStore {
         info!("Deleted OAuth2 tokens for {}", email);
         Ok(())
     }
+
+    /// Store the credentials of a JMAP account
+    pub async fn store_jmap_credentials(
+        &self,
+        email: &str,
+        credentials: &JmapCredentials,
+    ) -> AuthResult<()> {
+        let json = serde_json::to_string(credentials).map_err(|e| {
+            AuthError::SecretError(format!("Failed to serialize credentials: {}", e))
+        })?;
+
+        let attributes = std::collections::HashMap::from([
+            ("type", "jmap_credentials"),
+            ("email", email),
+        ]);
+
+        libsecret::password_store_future(
+            Some(&self.schema),
+            attributes,
+            Some(libsecret::COLLECTION_DEFAULT),
+            &format!("NorthMail JMAP credentials for {}", email),
+            &json,
+        )
+        .await
+        .map_err(|e| AuthError::SecretError(e.to_string()))?;
+
+        info!("Stored JMAP credentials for {}", email);
+        Ok(())
+    }
+
+    /// Retrieve the credentials of a JMAP account
+    pub async fn get_jmap_credentials(&self, email: &str) -> AuthResult<Option<JmapCredentials>> {
+        let attributes = std::collections::HashMap::from([
+            ("type", "jmap_credentials"),
+            ("email", email),
+        ]);
+
+        let secret = libsecret::password_lookup_future(Some(&self.schema), attributes)
+            .await
+            .map_err(|e| AuthError::SecretError(e.to_string()))?;
+
+        secret
+            .map(|json| {
+                serde_json::from_str(&json).map_err(|e| {
+                    AuthError::SecretError(format!("Failed to parse credentials: {}", e))
+                })
+            })
+            .transpose()
+    }
+
+    /// Delete the credentials of a JMAP account
+    pub async fn delete_jmap_credentials(&self, email: &str) -> AuthResult<()> {
+        let attributes = std::collections::HashMap::from([
+            ("type", "jmap_credentials"),
+            ("email", email),
+        ]);
+
+        libsecret::password_clear_future(Some(&self.schema), attributes)
+            .await
+            .map_err(|e| AuthError::SecretError(e.to_string()))?;
+
+        info!("Deleted JMAP credentials for {}", email);
+        Ok(())
+    }
+
+    /// Store the password of the SMTP server an identity sends through
+    pub async fn store_smtp_password(&self, email: &str, password: &str) -> AuthResult<()> {
+        let attributes = std::collections::HashMap::from([
+            ("type", "smtp_password"),
+            ("email", email),
+        ]);
+
+        libsecret::password_store_future(
+            Some(&self.schema),
+            attributes,
+            Some(libsecret::COLLECTION_DEFAULT),
+            &format!("NorthMail SMTP password for {}", email),
+            password,
+        )
+        .await
+        .map_err(|e| AuthError::SecretError(e.to_string()))?;
+
+        info!("Stored SMTP password for {}", email);
+        Ok(())
+    }
+
+    /// Retrieve the password of the SMTP server an identity sends through
+    pub async fn get_smtp_password(&self, email: &str) -> AuthResult<Option<String>> {
+        let attributes = std::collections::HashMap::from([
+            ("type", "smtp_password"),
+            ("email", email),
+        ]);
+
+        libsecret::password_lookup_future(Some(&self.schema), attributes)
+            .await
+            .map(|password| password.map(|password| password.to_string()))
+            .map_err(|e| AuthError::SecretError(e.to_string()))
+    }
+
+    /// Delete the SMTP password of an identity
+    pub async fn delete_smtp_password(&self, email: &str) -> AuthResult<()> {
+        let attributes = std::collections::HashMap::from([
+            ("type", "smtp_password"),
+            ("email", email),
+        ]);
+
+        libsecret::password_clear_future(Some(&self.schema), attributes)
+            .await
+            .map_err(|e| AuthError::SecretError(e.to_string()))?;
+
+        info!("Deleted SMTP password for {}", email);
+        Ok(())
+    }
+
+    /// Retrieve the key the local mail cache is encrypted with
+    pub async fn get_database_key(&self) -> AuthResult<Option<String>> {
+        let attributes = std::collections::HashMap::from([("type", "database_key")]);
+
+        libsecret::password_lookup_future(Some(&self.schema), attributes)
+            .await
+            .map(|key| key.map(|key| key.to_string()))
+            .map_err(|e| AuthError::SecretError(e.to_string()))
+    }
+
+    /// Generate a random 256-bit key for the local mail cache and store it,
+    /// returning it in hex
+    pub async fn create_database_key(&self) -> AuthResult<String> {
+        let key: String = rand::random::<[u8; 32]>()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let attributes = std::collections::HashMap::from([("type", "database_key")]);
+
+        libsecret::password_store_future(
+            Some(&self.schema),
+            attributes,
+            Some(libsecret::COLLECTION_DEFAULT),
+            "NorthMail mail cache key",
+            &key,
+        )
+        .await
+        .map_err(|e| AuthError::SecretError(e.to_string()))?;
+
+        info!("Stored new mail cache key");
+        Ok(key)
+    }
 }
 
 impl Default for SecretStore {
@@ -14,6 +14,10 @@ pub struct AccountConfig {
     pub smtp_host: String,
     /// SMTP server port
     pub smtp_port: u16,
+    /// JMAP session URL, or the server's host name, for JMAP accounts,
+    /// which have no IMAP or SMTP server (see [`crate::jmap`])
+    #[serde(default)]
+    pub jmap_session_url: Option<String>,
 }
 
 impl AccountConfig {
@@ -24,6 +28,7 @@ impl AccountConfig {
             imap_port: 993,
             smtp_host: "smtp.gmail.com".to_string(),
             smtp_port: 587,
+            jmap_session_url: None,
         }
     }
 
@@ -34,6 +39,18 @@ impl AccountConfig {
             imap_port: 993,
             smtp_host: "smtp.office365.com".to_string(),
             smtp_port: 587,
+            jmap_session_url: None,
+        }
+    }
+
+    /// iCloud Mail configuration (see [`crate::icloud`])
+    pub fn icloud() -> Self {
+        Self {
+            imap_host: crate::icloud::IMAP_HOST.to_string(),
+            imap_port: crate::icloud::IMAP_PORT,
+            smtp_host: crate::icloud::SMTP_HOST.to_string(),
+            smtp_port: crate::icloud::SMTP_PORT,
+            jmap_session_url: None,
         }
     }
 }
@@ -79,4 +96,23 @@ impl Account {
             config: AccountConfig::gmail(),
         }
     }
+
+    /// Create a new JMAP account. `session_url` is the session URL or the
+    /// server's host name.
+    pub fn jmap(email: String, session_url: String) -> Self {
+        Self {
+            id: format!("jmap:{}", email.to_lowercase()),
+            email: email.clone(),
+            display_name: None,
+            provider: crate::jmap::PROVIDER.to_string(),
+            auth_method: AuthMethod::Jmap { email },
+            config: AccountConfig {
+                imap_host: String::new(),
+                imap_port: 0,
+                smtp_host: String::new(),
+                smtp_port: 0,
+                jmap_session_url: Some(session_url),
+            },
+        }
+    }
 }
@@ -0,0 +1,367 @@
+//! Address list parsing (RFC 5322 §3.4)
+//!
+//! Header values and stored recipient lists look like
+//! `"Doe, Jane" <jane@example.com>, Team: a@x.org, b@y.org;`. Splitting on
+//! commas breaks quoted names and groups, so the message list, the header
+//! panel and reply-all all go through [`parse_address_list`].
+
+/// A single mailbox from an address list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    /// Display name, unquoted (`None` when only an address was given)
+    pub name: Option<String>,
+    /// The address itself; raw text if the entry has no `@`
+    pub email: String,
+}
+
+impl Address {
+    pub fn new(name: Option<&str>, email: &str) -> Self {
+        Self {
+            name: name
+                .map(str::trim)
+                .filter(|n| !n.is_empty() && *n != email)
+                .map(str::to_string),
+            email: email.trim().to_string(),
+        }
+    }
+
+    /// Name if there is one, otherwise the address
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.email)
+    }
+
+    /// Header form, quoting the name when needed: `"Doe, Jane" <jane@example.com>`
+    pub fn to_header(&self) -> String {
+        match &self.name {
+            Some(name) if needs_quoting(name) => {
+                let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("\"{}\" <{}>", escaped, self.email)
+            }
+            Some(name) => format!("{} <{}>", name, self.email),
+            None => self.email.clone(),
+        }
+    }
+}
+
+/// Parse an address list into mailboxes.
+///
+/// Groups are flattened into their members, comments are dropped (or used as
+/// the name when there is no other), and entries that don't parse are kept
+/// as raw text so nothing silently disappears.
+pub fn parse_address_list(input: &str) -> Vec<Address> {
+    let mut addresses = Vec::new();
+    let mut parser = Parser::new(input);
+
+    loop {
+        parser.skip_cfws();
+        if parser.at_end() {
+            break;
+        }
+        match parser.peek() {
+            Some(',') | Some(';') => {
+                parser.bump();
+                parser.comment = None;
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(address) = parser.mailbox() {
+            addresses.push(address);
+        }
+    }
+
+    addresses
+}
+
+/// Join mailboxes into a header value, e.g. for storing recipients
+pub fn format_address_list(addresses: &[Address]) -> String {
+    addresses
+        .iter()
+        .map(Address::to_header)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Just the addresses from a list, in order
+pub fn emails_in(input: &str) -> Vec<String> {
+    parse_address_list(input)
+        .into_iter()
+        .map(|a| a.email)
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+fn needs_quoting(name: &str) -> bool {
+    name.chars()
+        .any(|c| matches!(c, ',' | ';' | ':' | '<' | '>' | '@' | '"' | '(' | ')' | '[' | ']' | '\\'))
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    /// Text of the last comment seen, used as a fallback name
+    comment: Option<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            comment: None,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        self.chars.next()
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.chars.peek().is_none()
+    }
+
+    /// Skip whitespace and comments, remembering the last comment
+    fn skip_cfws(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '(' {
+                let comment = self.comment_text();
+                if !comment.trim().is_empty() {
+                    self.comment = Some(comment.trim().to_string());
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consume a (possibly nested) comment, returning its text
+    fn comment_text(&mut self) -> String {
+        let mut text = String::new();
+        let mut depth = 0;
+        while let Some(c) = self.bump() {
+            match c {
+                '(' => {
+                    if depth > 0 {
+                        text.push(c);
+                    }
+                    depth += 1;
+                }
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    text.push(c);
+                }
+                '\\' => {
+                    if let Some(escaped) = self.bump() {
+                        text.push(escaped);
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+        text
+    }
+
+    fn quoted_string(&mut self) -> String {
+        let mut text = String::new();
+        self.bump(); // opening quote
+        while let Some(c) = self.bump() {
+            match c {
+                '"' => break,
+                '\\' => {
+                    if let Some(escaped) = self.bump() {
+                        text.push(escaped);
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+        text
+    }
+
+    /// Parse one mailbox, or the next member of a group after `Name:`.
+    /// Stops before the `,` or `;` that ends it.
+    fn mailbox(&mut self) -> Option<Address> {
+        let mut phrase = String::new();
+        let mut angle: Option<String> = None;
+
+        loop {
+            self.skip_cfws();
+            match self.peek() {
+                None | Some(',') | Some(';') => break,
+                Some('"') => {
+                    let quoted = self.quoted_string();
+                    push_word(&mut phrase, &quoted);
+                }
+                Some('<') => {
+                    self.bump();
+                    let mut addr = String::new();
+                    while let Some(c) = self.peek() {
+                        if c == '(' {
+                            self.comment_text();
+                            continue;
+                        }
+                        self.bump();
+                        if c == '>' {
+                            break;
+                        }
+                        if !c.is_whitespace() {
+                            addr.push(c);
+                        }
+                    }
+                    angle = Some(addr);
+                }
+                Some(':') if angle.is_none() => {
+                    // Group display name; members follow
+                    self.bump();
+                    phrase.clear();
+                    self.comment = None;
+                }
+                Some(_) => {
+                    let mut word = String::new();
+                    while let Some(c) = self.peek() {
+                        if c.is_whitespace() || matches!(c, '"' | '<' | '(' | ',' | ';' | ':') {
+                            break;
+                        }
+                        word.push(c);
+                        self.bump();
+                    }
+                    if word.is_empty() {
+                        // Stray ':' after an angle address
+                        self.bump();
+                    } else {
+                        push_word(&mut phrase, &word);
+                    }
+                }
+            }
+        }
+
+        let comment = self.comment.take();
+        match angle {
+            Some(email) => {
+                let name = if phrase.is_empty() { comment } else { Some(phrase) };
+                Some(Address::new(name.as_deref(), &email))
+            }
+            None if phrase.is_empty() => None,
+            None => Some(Address::new(comment.as_deref(), &phrase)),
+        }
+    }
+}
+
+fn push_word(phrase: &mut String, word: &str) {
+    if !phrase.is_empty() {
+        phrase.push(' ');
+    }
+    phrase.push_str(word);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(input: &str) -> Vec<(Option<String>, String)> {
+        parse_address_list(input)
+            .into_iter()
+            .map(|a| (a.name, a.email))
+            .collect()
+    }
+
+    fn named(name: &str, email: &str) -> (Option<String>, String) {
+        (Some(name.to_string()), email.to_string())
+    }
+
+    fn bare(email: &str) -> (Option<String>, String) {
+        (None, email.to_string())
+    }
+
+    #[test]
+    fn test_simple_lists() {
+        assert_eq!(pairs("jdoe@example.org"), vec![bare("jdoe@example.org")]);
+        assert_eq!(
+            pairs("John Doe <jdoe@machine.example>, mary@example.net"),
+            vec![named("John Doe", "jdoe@machine.example"), bare("mary@example.net")]
+        );
+        assert_eq!(pairs("<boss@nil.test>"), vec![bare("boss@nil.test")]);
+        assert!(pairs("").is_empty());
+        assert!(pairs(" , ,").is_empty());
+    }
+
+    #[test]
+    fn test_quoted_names() {
+        assert_eq!(
+            pairs("\"Joe Q. Public\" <john.q.public@example.com>"),
+            vec![named("Joe Q. Public", "john.q.public@example.com")]
+        );
+        assert_eq!(
+            pairs("\"Doe, Jane\" <jane@example.com>, \"Smith, Bob\" <bob@example.com>"),
+            vec![named("Doe, Jane", "jane@example.com"), named("Smith, Bob", "bob@example.com")]
+        );
+        assert_eq!(
+            pairs(r#""Giant; \"Big\" Box" <sysservices@example.net>"#),
+            vec![named("Giant; \"Big\" Box", "sysservices@example.net")]
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        // RFC 5322 Appendix A.1.3
+        assert_eq!(
+            pairs("A Group:Ed Jones <c@a.test>,joe@where.test,John <jdoe@one.test>;"),
+            vec![named("Ed Jones", "c@a.test"), bare("joe@where.test"), named("John", "jdoe@one.test")]
+        );
+        assert!(pairs("Undisclosed recipients:;").is_empty());
+        assert_eq!(
+            pairs("Team: a@x.org, b@y.org;, carol@z.org"),
+            vec![bare("a@x.org"), bare("b@y.org"), bare("carol@z.org")]
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        // RFC 5322 Appendix A.5
+        assert_eq!(
+            pairs("Pete(A nice \\) chap) <pete(his account)@silly.test(his host)>"),
+            vec![named("Pete", "pete@silly.test")]
+        );
+        assert_eq!(pairs("jdoe@example.org (John Doe)"), vec![named("John Doe", "jdoe@example.org")]);
+        assert_eq!(
+            pairs("(Nested (comment)) ann@example.com, bob@example.com"),
+            vec![named("Nested (comment)", "ann@example.com"), bare("bob@example.com")]
+        );
+    }
+
+    #[test]
+    fn test_unparseable_entries_are_kept() {
+        assert_eq!(pairs("nobody"), vec![bare("nobody")]);
+        assert_eq!(
+            pairs("Ann <ann@example.com>, nobody"),
+            vec![named("Ann", "ann@example.com"), bare("nobody")]
+        );
+        assert_eq!(pairs("Broken <ann@example.com"), vec![named("Broken", "ann@example.com")]);
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let list = vec![
+            Address::new(Some("Doe, Jane"), "jane@example.com"),
+            Address::new(Some("Bob"), "bob@example.com"),
+            Address::new(None, "carol@example.com"),
+        ];
+        let formatted = format_address_list(&list);
+        assert_eq!(
+            formatted,
+            "\"Doe, Jane\" <jane@example.com>, Bob <bob@example.com>, carol@example.com"
+        );
+        assert_eq!(parse_address_list(&formatted), list);
+        assert_eq!(
+            emails_in(&formatted),
+            vec!["jane@example.com", "bob@example.com", "carol@example.com"]
+        );
+    }
+}
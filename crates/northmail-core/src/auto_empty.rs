@@ -0,0 +1,83 @@
+//! Emptying Trash and Junk automatically
+//!
+//! An account can have the sync engine delete messages for good once they
+//! have sat in its Trash or Junk folder for a number of days. Servers don't
+//! record when a message was moved into a folder, so a message's age is
+//! counted from when the cache first saw it there: mail already in the
+//! folder when this computer first synced it gets the full period too,
+//! and messages the cache never saw are left alone.
+
+/// Day counts offered; `None` never empties the folder
+pub const AUTO_EMPTY_DAYS: [Option<u32>; 5] = [None, Some(7), Some(14), Some(30), Some(60)];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// An account's auto-empty settings. The default keeps everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AutoEmptyPolicy {
+    /// Days a message stays in Trash
+    pub trash_days: Option<u32>,
+    /// Days a message stays in Junk
+    pub junk_days: Option<u32>,
+}
+
+impl AutoEmptyPolicy {
+    /// Whether the policy ever deletes anything
+    pub fn is_enabled(&self) -> bool {
+        self.trash_days.is_some() || self.junk_days.is_some()
+    }
+
+    /// Days messages stay in a folder of `folder_type` (as stored in the
+    /// cache), if it's emptied at all
+    pub fn days_for(&self, folder_type: &str) -> Option<u32> {
+        match folder_type {
+            "trash" => self.trash_days,
+            "spam" => self.junk_days,
+            _ => None,
+        }
+    }
+
+    /// Date (Unix seconds) before which messages that reached a folder of
+    /// `folder_type` are deleted at `now`
+    pub fn cutoff(&self, folder_type: &str, now: i64) -> Option<i64> {
+        self.days_for(folder_type)
+            .map(|days| now.saturating_sub(i64::from(days) * SECONDS_PER_DAY))
+    }
+}
+
+/// Messages of one folder due to be deleted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiredMessages {
+    pub folder_id: i64,
+    pub full_path: String,
+    pub uids: Vec<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cutoff() {
+        let now = 1_700_000_000;
+        let policy = AutoEmptyPolicy {
+            trash_days: Some(30),
+            junk_days: Some(7),
+        };
+        assert_eq!(policy.cutoff("trash", now), Some(now - 30 * 86_400));
+        assert_eq!(policy.cutoff("spam", now), Some(now - 7 * 86_400));
+        assert_eq!(policy.cutoff("inbox", now), None);
+        assert_eq!(AutoEmptyPolicy::default().cutoff("trash", now), None);
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        assert!(!AutoEmptyPolicy::default().is_enabled());
+        let policy = AutoEmptyPolicy {
+            trash_days: None,
+            junk_days: Some(14),
+        };
+        assert!(policy.is_enabled());
+        assert_eq!(policy.days_for("trash"), None);
+    }
+}
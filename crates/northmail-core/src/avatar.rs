@@ -0,0 +1,309 @@
+//! Sender images
+//!
+//! The avatar next to a message is the sender's photo in GNOME Contacts if
+//! there is one, else the first image found among the [`AvatarSource`]s the
+//! user has on: their Gravatar, the BIMI logo their domain publishes, or
+//! their domain's website icon. Without any of those it's their initials.
+//!
+//! Fetched images, and the lookups that found nothing, are kept in an
+//! [`AvatarCache`] on disk: images for [`FOUND_TTL`], misses for
+//! [`MISSING_TTL`], so a sender who sets up a Gravatar gets it within a day
+//! and nobody is looked up on every start.
+
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How long a fetched image is used before it's fetched again
+pub const FOUND_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How long a lookup that found nothing is trusted
+pub const MISSING_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Images larger than this are not used
+pub const MAX_IMAGE_BYTES: usize = 512 * 1024;
+
+/// Pixel size images are asked for
+pub const IMAGE_SIZE: u32 = 128;
+
+/// Where sender images come from, besides contact photos, in the order
+/// they're tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AvatarSource {
+    /// The image the sender registered with Gravatar for their address
+    Gravatar,
+    /// The brand logo the sender's domain publishes in a BIMI DNS record
+    Bimi,
+    /// The sender's domain's website icon
+    Favicon,
+}
+
+impl AvatarSource {
+    pub const ALL: [AvatarSource; 3] = [
+        AvatarSource::Gravatar,
+        AvatarSource::Bimi,
+        AvatarSource::Favicon,
+    ];
+
+    /// Identifier in the `avatar-sources` setting
+    pub fn id(self) -> &'static str {
+        match self {
+            AvatarSource::Gravatar => "gravatar",
+            AvatarSource::Bimi => "bimi",
+            AvatarSource::Favicon => "favicon",
+        }
+    }
+
+    /// The sources named in the `avatar-sources` setting, in the order
+    /// they're tried
+    pub fn from_ids<S: AsRef<str>>(ids: &[S]) -> Vec<AvatarSource> {
+        Self::ALL
+            .into_iter()
+            .filter(|source| ids.iter().any(|id| id.as_ref() == source.id()))
+            .collect()
+    }
+}
+
+/// Gravatar image for `email`, which answers 404 when there is none
+pub fn gravatar_url(email: &str) -> String {
+    format!(
+        "https://gravatar.com/avatar/{}?s={}&d=404",
+        sha256_hex(&email.trim().to_lowercase()),
+        IMAGE_SIZE
+    )
+}
+
+/// DNS-over-HTTPS query for the BIMI record of `domain`, answered as JSON
+pub fn bimi_query_url(domain: &str) -> String {
+    format!(
+        "https://dns.google/resolve?name=default._bimi.{}&type=TXT",
+        domain.trim_end_matches('.')
+    )
+}
+
+/// TXT records in a DNS-over-HTTPS JSON answer, with the strings of each
+/// record joined
+pub fn parse_txt_answer(json: &str) -> Vec<String> {
+    let Ok(answer) = serde_json::from_str::<serde_json::Value>(json) else {
+        return Vec::new();
+    };
+    let Some(records) = answer.get("Answer").and_then(|a| a.as_array()) else {
+        return Vec::new();
+    };
+    records
+        .iter()
+        // Type 16 is TXT; CNAMEs on the way there are listed too
+        .filter(|record| record.get("type").and_then(|t| t.as_u64()) == Some(16))
+        .filter_map(|record| record.get("data")?.as_str())
+        .map(join_txt_strings)
+        .collect()
+}
+
+/// `"v=BIMI1; l=ht" "tps://…"` → `v=BIMI1; l=https://…`; unquoted data is
+/// taken as it is
+fn join_txt_strings(data: &str) -> String {
+    if !data.trim_start().starts_with('"') {
+        return data.to_string();
+    }
+    data.split('"')
+        .skip(1)
+        .step_by(2)
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+/// Logo address in a BIMI record (`v=BIMI1; l=https://…/logo.svg; a=…`).
+/// Only HTTPS logos count.
+pub fn bimi_logo_url(record: &str) -> Option<String> {
+    let mut tags = record
+        .split(';')
+        .filter_map(|tag| tag.split_once('='))
+        .map(|(name, value)| (name.trim(), value.trim()));
+    if !tags
+        .next()
+        .is_some_and(|(name, value)| name == "v" && value.eq_ignore_ascii_case("BIMI1"))
+    {
+        return None;
+    }
+    tags.find(|(name, _)| *name == "l")
+        .map(|(_, value)| value)
+        .filter(|url| url.starts_with("https://"))
+        .map(str::to_string)
+}
+
+/// What the cache knows about an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cached {
+    Found(Vec<u8>),
+    /// Looked up lately, and there was none
+    Missing,
+}
+
+/// Sender images on disk, one file per source and address or domain,
+/// named by a hash so addresses don't show in file names
+pub struct AvatarCache {
+    dir: PathBuf,
+}
+
+impl AvatarCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// What's cached for `source` and `subject` (an address or domain) and
+    /// hasn't expired at `now`
+    pub fn get(&self, source: AvatarSource, subject: &str, now: SystemTime) -> Option<Cached> {
+        let (image, missing) = self.paths(source, subject);
+        if let Some(age) = age(&missing, now) {
+            return (age < MISSING_TTL).then_some(Cached::Missing);
+        }
+        if age(&image, now)? >= FOUND_TTL {
+            return None;
+        }
+        fs::read(&image)
+            .ok()
+            .filter(|bytes| !bytes.is_empty())
+            .map(Cached::Found)
+    }
+
+    /// Remember the image found for `source` and `subject`, or that there
+    /// was none
+    pub fn put(&self, source: AvatarSource, subject: &str, image: Option<&[u8]>) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.dir, fs::Permissions::from_mode(0o700))?;
+        }
+        let (image_path, missing_path) = self.paths(source, subject);
+        match image {
+            Some(bytes) => {
+                fs::write(&image_path, bytes)?;
+                remove_if_exists(&missing_path)
+            }
+            None => {
+                fs::write(&missing_path, b"")?;
+                remove_if_exists(&image_path)
+            }
+        }
+    }
+
+    fn paths(&self, source: AvatarSource, subject: &str) -> (PathBuf, PathBuf) {
+        let hash = sha256_hex(&subject.trim().to_lowercase());
+        let name = format!("{}-{}", source.id(), &hash[..32]);
+        (
+            self.dir.join(format!("{}.img", name)),
+            self.dir.join(format!("{}.none", name)),
+        )
+    }
+}
+
+/// How long ago the file at `path` was written, if it exists. A time in
+/// the future counts as just now.
+fn age(path: &Path, now: SystemTime) -> Option<Duration> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(now.duration_since(modified).unwrap_or_default())
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gravatar_url() {
+        // Gravatar's documented example
+        assert_eq!(
+            gravatar_url(" MyEmailAddress@example.com "),
+            "https://gravatar.com/avatar/84059b07d4be67b806386c0aad8070a23f18836bbaae342275dc0a83414c32ee?s=128&d=404"
+        );
+    }
+
+    #[test]
+    fn test_parse_txt_answer() {
+        let json = r#"{"Status":0,"Answer":[
+            {"name":"default._bimi.example.com.","type":5,"data":"bimi.example.net."},
+            {"name":"bimi.example.net.","type":16,"data":"\"v=BIMI1; l=https://example.com/lo\" \"go.svg; a=;\""}
+        ]}"#;
+        assert_eq!(
+            parse_txt_answer(json),
+            vec!["v=BIMI1; l=https://example.com/logo.svg; a=;".to_string()]
+        );
+        assert!(parse_txt_answer(r#"{"Status":3}"#).is_empty());
+        assert!(parse_txt_answer("not json").is_empty());
+    }
+
+    #[test]
+    fn test_bimi_logo_url() {
+        assert_eq!(
+            bimi_logo_url("v=BIMI1; l=https://example.com/logo.svg; a=https://example.com/vmc.pem"),
+            Some("https://example.com/logo.svg".to_string())
+        );
+        assert_eq!(
+            bimi_logo_url("v=BIMI1; l=http://example.com/logo.svg"),
+            None
+        );
+        // A declined record has an empty logo
+        assert_eq!(bimi_logo_url("v=BIMI1; l=; a=;"), None);
+        assert_eq!(bimi_logo_url("v=spf1 include:example.com ~all"), None);
+    }
+
+    #[test]
+    fn test_from_ids() {
+        assert_eq!(
+            AvatarSource::from_ids(&["favicon", "gravatar", "unknown"]),
+            vec![AvatarSource::Gravatar, AvatarSource::Favicon]
+        );
+        assert!(AvatarSource::from_ids::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("northmail-avatar-test-{}", std::process::id()));
+        let cache = AvatarCache::new(&dir);
+        let now = SystemTime::now();
+        let source = AvatarSource::Gravatar;
+        // Files are written a moment after `now`
+        const MINUTE: Duration = Duration::from_secs(60);
+
+        assert_eq!(cache.get(source, "ana@example.com", now), None);
+        cache.put(source, "ana@example.com", Some(b"png")).unwrap();
+        assert_eq!(
+            cache.get(source, "Ana@Example.com", now),
+            Some(Cached::Found(b"png".to_vec()))
+        );
+        assert_eq!(cache.get(AvatarSource::Bimi, "ana@example.com", now), None);
+        assert_eq!(
+            cache.get(source, "ana@example.com", now + FOUND_TTL + MINUTE),
+            None
+        );
+
+        cache.put(source, "ana@example.com", None).unwrap();
+        assert_eq!(
+            cache.get(source, "ana@example.com", now),
+            Some(Cached::Missing)
+        );
+        assert_eq!(
+            cache.get(source, "ana@example.com", now + MISSING_TTL + MINUTE),
+            None
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
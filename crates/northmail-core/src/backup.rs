@@ -0,0 +1,447 @@
+//! Full backups of the mail store
+//!
+//! [`create`] writes a backup directory holding a snapshot of the database,
+//! which has the cached mail, attachment data, local folders and account
+//! settings; whatever configuration files the caller hands in; and a
+//! `manifest.json` with the size and SHA-256 of each file. [`verify`]
+//! checks a backup against its manifest, and [`restore`] doesn't touch the
+//! data directory unless that check passes.
+//!
+//! Passwords and tokens live in the keyring and are left out, unless the
+//! caller passes them along with a passphrase. They are then written to a
+//! SQLCipher database keyed with that passphrase, so only builds with
+//! SQLCipher can include them. An encrypted mail cache can't be read after
+//! a restore without its key, so that belongs among them when moving to
+//! another machine.
+
+use crate::{CoreError, CoreResult, Database};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+use sqlx::{ConnectOptions, Connection, Row};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Version of the backup layout; backups from a newer one are refused
+pub const FORMAT_VERSION: u32 = 1;
+
+/// File listing everything else in a backup, written last
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const DATABASE_FILE: &str = "mail.db";
+const SECRETS_FILE: &str = "secrets.db";
+const CONFIG_DIR: &str = "config";
+
+/// What a backup holds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// [`FORMAT_VERSION`] of the NorthMail that wrote it
+    pub version: u32,
+    /// Unix time it was made
+    pub created_at: i64,
+    /// Every file but the manifest, by path inside the backup
+    pub files: Vec<BackupFile>,
+}
+
+impl Manifest {
+    /// Whether passwords and tokens were saved, encrypted with a passphrase
+    pub fn has_secrets(&self) -> bool {
+        self.files.iter().any(|f| f.name == SECRETS_FILE)
+    }
+
+    /// Names of the configuration files saved
+    pub fn config_names(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter_map(|f| f.name.strip_prefix(CONFIG_DIR)?.strip_prefix('/'))
+    }
+}
+
+/// A file in a backup and what it has to match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// Path inside the backup, with `/` between components
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    /// SHA-256 of the contents, in lowercase hex
+    pub sha256: String,
+}
+
+/// A configuration file saved alongside the database, such as a profile's
+/// settings. The caller decides what goes in and where it goes back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFile {
+    /// File name, without directories
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+/// A password, token or key from the keyring
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret {
+    /// What it is, such as `oauth_tokens` or `database_key`
+    pub kind: String,
+    /// Account it belongs to, if any
+    pub account: Option<String>,
+    pub value: String,
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secret")
+            .field("kind", &self.kind)
+            .field("account", &self.account)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What goes into a backup besides the database
+#[derive(Debug, Clone, Default)]
+pub struct BackupOptions {
+    pub config: Vec<ConfigFile>,
+    /// Saved only with a passphrase
+    pub secrets: Vec<Secret>,
+    pub passphrase: Option<String>,
+}
+
+/// What [`restore`] did and brought back
+#[derive(Debug)]
+pub struct Restored {
+    pub manifest: Manifest,
+    /// Where the database that was replaced went, if there was one
+    pub previous_database: Option<PathBuf>,
+    /// Configuration files, for the caller to put back
+    pub config: Vec<ConfigFile>,
+    /// Passwords and tokens, for the caller to put back in the keyring;
+    /// empty unless the backup has them and a passphrase was given
+    pub secrets: Vec<Secret>,
+}
+
+/// Back up `db` to a new directory at `path`. The backup is put together
+/// in `<path>.partial` and only renamed into place once its manifest is
+/// written, so an interrupted backup never looks complete.
+pub async fn create(db: &Database, path: &Path, options: &BackupOptions) -> CoreResult<Manifest> {
+    if path.exists() {
+        return Err(CoreError::StorageError(format!("{} already exists", path.display())));
+    }
+    for file in &options.config {
+        check_config_name(&file.name)?;
+    }
+    let passphrase = match (&options.passphrase, options.secrets.is_empty()) {
+        (_, true) => None,
+        (Some(passphrase), false) if !passphrase.is_empty() => {
+            if !Database::encryption_supported() {
+                return Err(CoreError::EncryptionUnsupported(SECRETS_FILE.to_string()));
+            }
+            Some(passphrase.as_str())
+        }
+        _ => return Err(CoreError::StorageError("Passwords are only saved with a passphrase".to_string())),
+    };
+
+    let staging = partial_path(path);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let result = write_backup(db, &staging, options, passphrase).await;
+    let manifest = match result {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+    fs::rename(&staging, path)?;
+
+    info!("Backed up {} files to {}", manifest.files.len(), path.display());
+    Ok(manifest)
+}
+
+async fn write_backup(
+    db: &Database,
+    dir: &Path,
+    options: &BackupOptions,
+    passphrase: Option<&str>,
+) -> CoreResult<Manifest> {
+    let mut names = vec![DATABASE_FILE.to_string()];
+    db.snapshot_to(&dir.join(DATABASE_FILE)).await?;
+
+    if !options.config.is_empty() {
+        fs::create_dir(dir.join(CONFIG_DIR))?;
+    }
+    for file in &options.config {
+        let name = format!("{}/{}", CONFIG_DIR, file.name);
+        fs::write(dir.join(&name), &file.contents)?;
+        names.push(name);
+    }
+
+    if let Some(passphrase) = passphrase {
+        write_secrets(&dir.join(SECRETS_FILE), passphrase, &options.secrets).await?;
+        names.push(SECRETS_FILE.to_string());
+    }
+
+    let mut files = Vec::with_capacity(names.len());
+    for name in names {
+        files.push(describe(dir, name)?);
+    }
+    let manifest = Manifest {
+        version: FORMAT_VERSION,
+        created_at: chrono::Utc::now().timestamp(),
+        files,
+    };
+    let json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| CoreError::StorageError(format!("Failed to write manifest: {}", e)))?;
+    fs::write(dir.join(MANIFEST_FILE), json)?;
+    for file in manifest.files.iter().map(|f| f.name.as_str()).chain([MANIFEST_FILE]) {
+        fs::File::open(dir.join(file))?.sync_all()?;
+    }
+    Ok(manifest)
+}
+
+/// Check every file in the backup at `path` against its manifest,
+/// returning the manifest if they all match
+pub fn verify(path: &Path) -> CoreResult<Manifest> {
+    let json = match fs::read(path.join(MANIFEST_FILE)) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(CoreError::BackupInvalid(format!("{} has no {}", path.display(), MANIFEST_FILE)));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let manifest: Manifest = serde_json::from_slice(&json)
+        .map_err(|e| CoreError::BackupInvalid(format!("unreadable manifest: {}", e)))?;
+    if manifest.version > FORMAT_VERSION {
+        return Err(CoreError::BackupInvalid(format!(
+            "made by a newer NorthMail (format {})",
+            manifest.version
+        )));
+    }
+    if !manifest.files.iter().any(|f| f.name == DATABASE_FILE) {
+        return Err(CoreError::BackupInvalid(format!("no {}", DATABASE_FILE)));
+    }
+
+    for file in &manifest.files {
+        check_file_name(&file.name)?;
+        let found = match describe(path, file.name.clone()) {
+            Ok(found) => found,
+            Err(CoreError::IoError(e)) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(CoreError::BackupInvalid(format!("{} is missing", file.name)));
+            }
+            Err(e) => return Err(e),
+        };
+        if found != *file {
+            return Err(CoreError::BackupInvalid(format!("{} is damaged", file.name)));
+        }
+    }
+    Ok(manifest)
+}
+
+/// Verify the backup at `path`, then put its database in place of the one
+/// at `database_path`, which must not be open. The database it replaces is
+/// kept next to it (see [`Database::replace`]). Saved passwords come back
+/// only with the `passphrase` they were saved with; a wrong one fails
+/// before anything is replaced.
+pub async fn restore(path: &Path, database_path: &Path, passphrase: Option<&str>) -> CoreResult<Restored> {
+    let manifest = verify(path)?;
+
+    let secrets = match passphrase {
+        Some(passphrase) if manifest.has_secrets() => {
+            if !Database::encryption_supported() {
+                return Err(CoreError::EncryptionUnsupported(SECRETS_FILE.to_string()));
+            }
+            read_secrets(&path.join(SECRETS_FILE), passphrase).await?
+        }
+        _ => Vec::new(),
+    };
+    let config = manifest
+        .config_names()
+        .map(|name| {
+            Ok(ConfigFile {
+                name: name.to_string(),
+                contents: fs::read(path.join(CONFIG_DIR).join(name))?,
+            })
+        })
+        .collect::<CoreResult<Vec<_>>>()?;
+
+    let previous_database = Database::replace(database_path, &path.join(DATABASE_FILE))?;
+
+    info!("Restored backup from {}", path.display());
+    Ok(Restored {
+        manifest,
+        previous_database,
+        config,
+        secrets,
+    })
+}
+
+/// Size and SHA-256 of the file `name` inside `dir`
+fn describe(dir: &Path, name: String) -> CoreResult<BackupFile> {
+    let mut file = fs::File::open(dir.join(&name))?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+    let sha256 = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(BackupFile { name, size, sha256 })
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+/// Configuration files are written under their own name, so a name must
+/// not lead anywhere else
+fn check_config_name(name: &str) -> CoreResult<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(CoreError::StorageError(format!("Invalid configuration file name: {:?}", name)));
+    }
+    Ok(())
+}
+
+/// A manifest only names files inside the backup
+fn check_file_name(name: &str) -> CoreResult<()> {
+    let valid = match name.split_once('/') {
+        None => name == DATABASE_FILE || name == SECRETS_FILE,
+        Some((CONFIG_DIR, rest)) => check_config_name(rest).is_ok(),
+        Some(_) => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(CoreError::BackupInvalid(format!("unexpected file {:?}", name)))
+    }
+}
+
+/// SQLCipher derives the key from a passphrase given as a string literal
+fn secrets_options(path: &Path, passphrase: &str) -> SqliteConnectOptions {
+    SqliteConnectOptions::new()
+        .filename(path)
+        .journal_mode(SqliteJournalMode::Delete)
+        .pragma("key", format!("'{}'", passphrase.replace('\'', "''")))
+}
+
+// Errors from the secrets file are mapped here rather than through
+// `From<sqlx::Error>`, which would take a wrong passphrase for a damaged
+// mail cache
+
+async fn write_secrets(path: &Path, passphrase: &str, secrets: &[Secret]) -> CoreResult<()> {
+    let failed = |e: sqlx::Error| CoreError::StorageError(format!("Failed to save passwords: {}", e));
+    let mut conn = secrets_options(path, passphrase)
+        .create_if_missing(true)
+        .connect()
+        .await
+        .map_err(failed)?;
+    sqlx::query("CREATE TABLE secrets (kind TEXT NOT NULL, account TEXT, value TEXT NOT NULL)")
+        .execute(&mut conn)
+        .await
+        .map_err(failed)?;
+    for secret in secrets {
+        sqlx::query("INSERT INTO secrets (kind, account, value) VALUES (?, ?, ?)")
+            .bind(&secret.kind)
+            .bind(&secret.account)
+            .bind(&secret.value)
+            .execute(&mut conn)
+            .await
+            .map_err(failed)?;
+    }
+    conn.close().await.map_err(failed)
+}
+
+async fn read_secrets(path: &Path, passphrase: &str) -> CoreResult<Vec<Secret>> {
+    let unreadable = |_| CoreError::BackupInvalid("wrong passphrase for the saved passwords".to_string());
+    let mut conn = secrets_options(path, passphrase)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(unreadable)?;
+    let rows = sqlx::query("SELECT kind, account, value FROM secrets")
+        .fetch_all(&mut conn)
+        .await
+        .map_err(unreadable)?;
+    let _ = conn.close().await;
+    Ok(rows
+        .iter()
+        .map(|row| Secret {
+            kind: row.get("kind"),
+            account: row.get("account"),
+            value: row.get("value"),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("northmail-backup-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_manifest(dir: &Path, files: Vec<BackupFile>) {
+        let manifest = Manifest { version: FORMAT_VERSION, created_at: 0, files };
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_vec(&manifest).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_verify_accepts_matching_files() {
+        let dir = test_dir("ok");
+        fs::write(dir.join(DATABASE_FILE), b"database").unwrap();
+        fs::create_dir(dir.join(CONFIG_DIR)).unwrap();
+        fs::write(dir.join("config/settings.ini"), b"[General]\n").unwrap();
+        let files = vec![
+            describe(&dir, DATABASE_FILE.to_string()).unwrap(),
+            describe(&dir, "config/settings.ini".to_string()).unwrap(),
+        ];
+        assert_eq!(files[0].size, 8);
+        assert_eq!(files[0].sha256, "3549b0028b75d981cdda2e573e9cb49dedc200185876df299f912b79f69dabd8");
+        write_manifest(&dir, files);
+
+        let manifest = verify(&dir).unwrap();
+        assert!(!manifest.has_secrets());
+        assert_eq!(manifest.config_names().collect::<Vec<_>>(), vec!["settings.ini"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_changed_and_missing_files() {
+        let dir = test_dir("damaged");
+        fs::write(dir.join(DATABASE_FILE), b"database").unwrap();
+        write_manifest(&dir, vec![describe(&dir, DATABASE_FILE.to_string()).unwrap()]);
+
+        fs::write(dir.join(DATABASE_FILE), b"databasf").unwrap();
+        assert!(matches!(verify(&dir), Err(CoreError::BackupInvalid(_))));
+
+        fs::remove_file(dir.join(DATABASE_FILE)).unwrap();
+        assert!(matches!(verify(&dir), Err(CoreError::BackupInvalid(_))));
+
+        fs::remove_file(dir.join(MANIFEST_FILE)).unwrap();
+        assert!(matches!(verify(&dir), Err(CoreError::BackupInvalid(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_paths_outside_backup() {
+        let dir = test_dir("paths");
+        fs::write(dir.join(DATABASE_FILE), b"database").unwrap();
+        let database = describe(&dir, DATABASE_FILE.to_string()).unwrap();
+        let outside = BackupFile { name: "config/../../etc/passwd".to_string(), ..database.clone() };
+        write_manifest(&dir, vec![database, outside]);
+        assert!(matches!(verify(&dir), Err(CoreError::BackupInvalid(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_names() {
+        assert!(check_config_name("settings.ini").is_ok());
+        assert!(check_config_name("").is_err());
+        assert!(check_config_name("..").is_err());
+        assert!(check_config_name("a/b").is_err());
+    }
+}
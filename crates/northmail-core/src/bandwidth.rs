@@ -0,0 +1,97 @@
+//! Bandwidth policy for sync
+//!
+//! On a metered or slow connection NorthMail keeps traffic down: header
+//! syncs leave out the body structure, bodies aren't prefetched, the periodic
+//! check runs less often, and inline images are left on the server with the
+//! attachments, to be downloaded when clicked. Low-bandwidth mode follows NetworkManager's
+//! metered flag by default and can be forced on or off.
+
+/// How often the periodic check runs in low-bandwidth mode, as a multiple of
+/// the configured interval
+const LOW_BANDWIDTH_POLL_FACTOR: u32 = 4;
+
+/// The `low-bandwidth-mode` setting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LowBandwidthMode {
+    /// On while the connection is metered
+    Auto,
+    Always,
+    Never,
+}
+
+impl LowBandwidthMode {
+    /// Parse the settings value; unknown values mean `Auto`
+    pub fn from_setting(value: &str) -> Self {
+        match value {
+            "always" => Self::Always,
+            "never" => Self::Never,
+            _ => Self::Auto,
+        }
+    }
+}
+
+/// What sync may download right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BandwidthPolicy {
+    /// Low-bandwidth mode is in effect
+    pub low_bandwidth: bool,
+    /// Header syncs include BODYSTRUCTURE (used to spot attachments)
+    pub fetch_body_structure: bool,
+    /// Bodies are prefetched in the background
+    pub prefetch_bodies: bool,
+    /// Inline images are downloaded along with the message text
+    pub fetch_inline_resources: bool,
+}
+
+impl BandwidthPolicy {
+    /// Policy for the current connection. `prefetch_on_metered` keeps body
+    /// prefetch on while metered in `Auto` mode.
+    pub fn new(mode: LowBandwidthMode, metered: bool, prefetch_on_metered: bool) -> Self {
+        let low_bandwidth = match mode {
+            LowBandwidthMode::Auto => metered,
+            LowBandwidthMode::Always => true,
+            LowBandwidthMode::Never => false,
+        };
+        let prefetch_bodies = match mode {
+            LowBandwidthMode::Auto => !metered || prefetch_on_metered,
+            _ => !low_bandwidth,
+        };
+        Self {
+            low_bandwidth,
+            fetch_body_structure: !low_bandwidth,
+            prefetch_bodies,
+            fetch_inline_resources: !low_bandwidth,
+        }
+    }
+
+    /// Minutes between periodic checks, given the configured interval
+    pub fn poll_interval(&self, minutes: u32) -> u32 {
+        if self.low_bandwidth {
+            minutes.saturating_mul(LOW_BANDWIDTH_POLL_FACTOR)
+        } else {
+            minutes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let unmetered = BandwidthPolicy::new(LowBandwidthMode::Auto, false, false);
+        assert!(!unmetered.low_bandwidth && unmetered.prefetch_bodies && unmetered.fetch_body_structure);
+        assert_eq!(unmetered.poll_interval(5), 5);
+
+        let metered = BandwidthPolicy::new(LowBandwidthMode::Auto, true, false);
+        assert!(metered.low_bandwidth && !metered.prefetch_bodies && !metered.fetch_inline_resources);
+        assert_eq!(metered.poll_interval(5), 20);
+        assert!(BandwidthPolicy::new(LowBandwidthMode::Auto, true, true).prefetch_bodies);
+
+        assert!(BandwidthPolicy::new(LowBandwidthMode::Always, false, true).low_bandwidth);
+        assert!(!BandwidthPolicy::new(LowBandwidthMode::Always, false, true).prefetch_bodies);
+        assert!(!BandwidthPolicy::new(LowBandwidthMode::Never, true, false).low_bandwidth);
+        assert_eq!(LowBandwidthMode::from_setting("bogus"), LowBandwidthMode::Auto);
+    }
+}
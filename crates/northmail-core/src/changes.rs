@@ -0,0 +1,318 @@
+//! Cache change notifications
+//!
+//! The database announces what it writes as [`Change`]s on its
+//! [`ChangeBus`], so everything showing cached mail in the process can stay
+//! up to date without polling. Only one process can have the cache open, so
+//! others (the shell search provider, a sync daemon) hear about changes
+//! over D-Bus: the app sends local changes out as `Changed` signals on
+//! [`DBUS_INTERFACE`] in their [`Change::to_wire`] form, and puts the ones
+//! other processes send on the bus as remote changes, which aren't sent out
+//! again. Each profile has its own cache, so its signals go out on its own
+//! [`dbus_path`] and those of other profiles are never heard.
+//!
+//! Listeners that fall behind lose changes (the receiver reports how many);
+//! they should reload what they show.
+
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+
+/// D-Bus interface the `Changed` signal is sent on
+pub const DBUS_INTERFACE: &str = "com.petrariu.NorthMail.Changes";
+
+/// Object path the default profile's `Changed` signal is sent from
+pub const DBUS_PATH: &str = "/com/petrariu/NorthMail/Changes";
+
+/// Name of the D-Bus signal, with arguments `(s kind, ax ids, t count)`
+pub const DBUS_SIGNAL: &str = "Changed";
+
+/// Changes kept for a listener that hasn't caught up
+const CAPACITY: usize = 1024;
+
+/// Object path for the `Changed` signals of `profile`, `None` being the
+/// default profile. Characters an object path can't hold are escaped as
+/// `_xx`, `_` included, so different names never share a path.
+pub fn dbus_path(profile: Option<&str>) -> String {
+    let Some(profile) = profile else {
+        return DBUS_PATH.to_string();
+    };
+    let mut path = format!("{}/", DBUS_PATH);
+    for byte in profile.bytes() {
+        if byte.is_ascii_alphanumeric() {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    path
+}
+
+/// Something the database changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Headers cached in a folder, new or updated
+    MessagesInserted { folder_id: i64, count: u64 },
+    /// Messages removed from a folder
+    MessagesRemoved { folder_id: i64 },
+    /// Read, starred or other flags of messages, by row id
+    FlagsChanged { message_ids: Vec<i64> },
+    /// Message or unread counts of folders
+    FolderCountsChanged { folder_ids: Vec<i64> },
+}
+
+impl Change {
+    /// The change as `Changed` signal arguments: its kind, the folder or
+    /// message ids, and a count
+    pub fn to_wire(&self) -> (String, Vec<i64>, u64) {
+        let (kind, ids, count) = match self {
+            Change::MessagesInserted { folder_id, count } => {
+                ("messages-inserted", vec![*folder_id], *count)
+            }
+            Change::MessagesRemoved { folder_id } => ("messages-removed", vec![*folder_id], 0),
+            Change::FlagsChanged { message_ids } => ("flags-changed", message_ids.clone(), 0),
+            Change::FolderCountsChanged { folder_ids } => {
+                ("folder-counts-changed", folder_ids.clone(), 0)
+            }
+        };
+        (kind.to_string(), ids, count)
+    }
+
+    /// The change sent as `Changed` signal arguments; `None` for a kind
+    /// this version doesn't know
+    pub fn from_wire(kind: &str, ids: Vec<i64>, count: u64) -> Option<Change> {
+        let folder_id = || ids.first().copied();
+        match kind {
+            "messages-inserted" => Some(Change::MessagesInserted {
+                folder_id: folder_id()?,
+                count,
+            }),
+            "messages-removed" => Some(Change::MessagesRemoved {
+                folder_id: folder_id()?,
+            }),
+            "flags-changed" => Some(Change::FlagsChanged { message_ids: ids }),
+            "folder-counts-changed" => Some(Change::FolderCountsChanged { folder_ids: ids }),
+            _ => None,
+        }
+    }
+
+    /// Whether the change touches folder `folder_id`. Flag changes name
+    /// messages, not folders, so they might touch any.
+    pub fn affects_folder(&self, folder_id: i64) -> bool {
+        match self {
+            Change::MessagesInserted { folder_id: id, .. }
+            | Change::MessagesRemoved { folder_id: id } => *id == folder_id,
+            Change::FolderCountsChanged { folder_ids } => folder_ids.contains(&folder_id),
+            Change::FlagsChanged { .. } => true,
+        }
+    }
+}
+
+/// Merge a burst of changes: counts of inserts into a folder add up, and
+/// ids of flag and count changes are joined, each listed once
+pub fn coalesce(changes: Vec<Change>) -> Vec<Change> {
+    let mut inserted: BTreeMap<i64, u64> = BTreeMap::new();
+    let mut removed: Vec<i64> = Vec::new();
+    let mut message_ids: Vec<i64> = Vec::new();
+    let mut folder_ids: Vec<i64> = Vec::new();
+    let add = |ids: &mut Vec<i64>, new: Vec<i64>| {
+        for id in new {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    };
+    for change in changes {
+        match change {
+            Change::MessagesInserted { folder_id, count } => {
+                *inserted.entry(folder_id).or_default() += count;
+            }
+            Change::MessagesRemoved { folder_id } => add(&mut removed, vec![folder_id]),
+            Change::FlagsChanged { message_ids: ids } => add(&mut message_ids, ids),
+            Change::FolderCountsChanged { folder_ids: ids } => add(&mut folder_ids, ids),
+        }
+    }
+
+    let mut merged: Vec<Change> = inserted
+        .into_iter()
+        .map(|(folder_id, count)| Change::MessagesInserted { folder_id, count })
+        .collect();
+    merged.extend(
+        removed
+            .into_iter()
+            .map(|folder_id| Change::MessagesRemoved { folder_id }),
+    );
+    if !message_ids.is_empty() {
+        merged.push(Change::FlagsChanged { message_ids });
+    }
+    if !folder_ids.is_empty() {
+        merged.push(Change::FolderCountsChanged { folder_ids });
+    }
+    merged
+}
+
+/// A change as listeners get it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub change: Change,
+    /// Made by another process and heard over D-Bus
+    pub remote: bool,
+}
+
+/// Where the database announces its changes. Clones share one channel.
+#[derive(Debug, Clone)]
+pub struct ChangeBus {
+    sender: broadcast::Sender<ChangeEvent>,
+}
+
+impl ChangeBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+
+    /// Announce a change made in this process
+    pub fn send(&self, change: Change) {
+        // Nobody listening is fine
+        let _ = self.sender.send(ChangeEvent {
+            change,
+            remote: false,
+        });
+    }
+
+    /// Announce a change another process made
+    pub fn send_remote(&self, change: Change) {
+        let _ = self.sender.send(ChangeEvent {
+            change,
+            remote: true,
+        });
+    }
+
+    /// Listen for changes announced from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for ChangeBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_round_trip() {
+        let changes = [
+            Change::MessagesInserted {
+                folder_id: 3,
+                count: 50,
+            },
+            Change::MessagesRemoved { folder_id: 4 },
+            Change::FlagsChanged {
+                message_ids: vec![1, 2],
+            },
+            Change::FolderCountsChanged {
+                folder_ids: vec![3, 4],
+            },
+        ];
+        for change in changes {
+            let (kind, ids, count) = change.to_wire();
+            assert_eq!(Change::from_wire(&kind, ids, count), Some(change));
+        }
+        assert_eq!(Change::from_wire("messages-moved", vec![1], 0), None);
+        assert_eq!(Change::from_wire("messages-inserted", Vec::new(), 1), None);
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let merged = coalesce(vec![
+            Change::MessagesInserted {
+                folder_id: 2,
+                count: 50,
+            },
+            Change::FlagsChanged {
+                message_ids: vec![7],
+            },
+            Change::FolderCountsChanged {
+                folder_ids: vec![2],
+            },
+            Change::MessagesInserted {
+                folder_id: 2,
+                count: 20,
+            },
+            Change::FlagsChanged {
+                message_ids: vec![7, 8],
+            },
+            Change::FolderCountsChanged {
+                folder_ids: vec![5, 2],
+            },
+        ]);
+        assert_eq!(
+            merged,
+            vec![
+                Change::MessagesInserted {
+                    folder_id: 2,
+                    count: 70
+                },
+                Change::FlagsChanged {
+                    message_ids: vec![7, 8]
+                },
+                Change::FolderCountsChanged {
+                    folder_ids: vec![2, 5]
+                },
+            ]
+        );
+        assert!(coalesce(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_affects_folder() {
+        assert!(Change::MessagesRemoved { folder_id: 1 }.affects_folder(1));
+        assert!(!Change::MessagesInserted {
+            folder_id: 1,
+            count: 1
+        }
+        .affects_folder(2));
+        assert!(Change::FolderCountsChanged {
+            folder_ids: vec![1, 2]
+        }
+        .affects_folder(2));
+        assert!(Change::FlagsChanged {
+            message_ids: vec![9]
+        }
+        .affects_folder(2));
+    }
+
+    #[test]
+    fn test_dbus_path() {
+        assert_eq!(dbus_path(None), DBUS_PATH);
+        assert_eq!(dbus_path(Some("work")), "/com/petrariu/NorthMail/Changes/work");
+        assert_eq!(
+            dbus_path(Some("my-work_2")),
+            "/com/petrariu/NorthMail/Changes/my_2dwork_5f2"
+        );
+        assert_ne!(dbus_path(Some("a-b")), dbus_path(Some("a_2db")));
+    }
+
+    #[test]
+    fn test_bus() {
+        let bus = ChangeBus::new();
+        // Sending with nobody listening is fine
+        bus.send(Change::MessagesRemoved { folder_id: 1 });
+
+        let mut receiver = bus.clone().subscribe();
+        bus.send(Change::MessagesRemoved { folder_id: 2 });
+        bus.send_remote(Change::MessagesRemoved { folder_id: 3 });
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ChangeEvent {
+                change: Change::MessagesRemoved { folder_id: 2 },
+                remote: false
+            }
+        );
+        assert!(receiver.try_recv().unwrap().remote);
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -1,8 +1,34 @@
 //! Database storage using SQLite
 
-use crate::{CoreError, CoreResult};
+use crate::duplicates::{self, DuplicateGroup, DuplicateScope, MessageCopy};
+use crate::eml::MessageSource;
+use crate::flag_merge::{Flag, FlagConflict, FlagPolicy, FlagUpdate};
+use crate::address::Address;
+use crate::auto_empty::{AutoEmptyPolicy, ExpiredMessages};
+use crate::changes::{Change, ChangeBus};
+use crate::identity::{Identity, Signature, SignatureImage, SmtpOverride};
+use crate::import::{LocalMessage, LOCAL_FOLDER_TYPE};
+use crate::maildir::{self, CachedAttachment, CachedMessage, ExportCounts};
+use crate::maintenance::{self, MaintenanceReport};
+use crate::invite::{Attendee, Invite, Rsvp, StoredInvite};
+use crate::mdn::{ReceiptPolicy, ReceiptRequest, RequestedReceipt, ReturnedReceipt};
+use crate::migrations;
+use crate::outbox::{OutboxItem, OutboxStatus};
+use crate::recipients::{self, Recipient};
+use crate::offline::OfflineCandidate;
+use crate::purge::{self, PurgeProgress, PurgeStage, PurgedAccount};
+use crate::retention::{FolderCacheSize, PruneReport, RetentionPolicy};
+use crate::search::{SearchQuery, SqlParam};
+use crate::sync_policy::{FolderSyncPolicy, ScheduledFolder};
+use crate::thread::{self, ThreadMessageRef, ThreadSummary};
+use crate::unified::{self, UnifiedInbox, UnifiedPage, UnifiedQuery};
+use crate::{wipe, CoreError, CoreResult};
+use northmail_imap::{decode_mailbox_name, ImapClient, MessageFlags};
 use sqlx::{sqlite::{SqliteConnectOptions, SqlitePoolOptions}, Pool, Row, Sqlite};
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use tracing::{debug, info, warn};
 
 /// Prepare a user query for FTS5 search with prefix matching
@@ -27,6 +53,146 @@ fn prepare_fts_query(query: &str) -> String {
         .join(" ")
 }
 
+/// Characters of preview text list queries return until the app sets its own
+const DEFAULT_SNIPPET_LENGTH: usize = 200;
+
+/// Full-text index over the searchable text of cached messages. It is an
+/// external content table, so text is not stored twice. Bodies are indexed as
+/// they are saved: the update trigger fires only when one of the indexed
+/// columns changes, not on flag changes.
+const FTS_SCHEMA: &str = r#"
+    CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+        subject,
+        from_address,
+        from_name,
+        to_addresses,
+        cc_addresses,
+        snippet,
+        body_text,
+        content=messages,
+        content_rowid=id
+    );
+
+    CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
+        INSERT INTO messages_fts(rowid, subject, from_address, from_name,
+                                 to_addresses, cc_addresses, snippet, body_text)
+        VALUES (new.id, new.subject, new.from_address, new.from_name,
+                new.to_addresses, new.cc_addresses, new.snippet, new.body_text);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
+        INSERT INTO messages_fts(messages_fts, rowid, subject, from_address, from_name,
+                                 to_addresses, cc_addresses, snippet, body_text)
+        VALUES ('delete', old.id, old.subject, old.from_address, old.from_name,
+                old.to_addresses, old.cc_addresses, old.snippet, old.body_text);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS messages_au
+    AFTER UPDATE OF subject, from_address, from_name, to_addresses, cc_addresses, snippet, body_text
+    ON messages BEGIN
+        INSERT INTO messages_fts(messages_fts, rowid, subject, from_address, from_name,
+                                 to_addresses, cc_addresses, snippet, body_text)
+        VALUES ('delete', old.id, old.subject, old.from_address, old.from_name,
+                old.to_addresses, old.cc_addresses, old.snippet, old.body_text);
+        INSERT INTO messages_fts(rowid, subject, from_address, from_name,
+                                 to_addresses, cc_addresses, snippet, body_text)
+        VALUES (new.id, new.subject, new.from_address, new.from_name,
+                new.to_addresses, new.cc_addresses, new.snippet, new.body_text);
+    END;
+"#;
+
+/// Statements linking messages to their tags. `source` selects the
+/// messages' `id`, `tags` and `gmail_labels`; their user keywords and
+/// Gmail labels not yet in `tags` are added to it first. Duplicates are
+/// filtered out rather than left to `OR IGNORE`, which the upserts firing
+/// the triggers would override.
+fn tag_link_sql(source: &str) -> String {
+    let keywords = "json_each(CASE WHEN json_valid(s.tags) THEN s.tags END) j";
+    let labels = "json_each(CASE WHEN json_valid(s.gmail_labels) THEN s.gmail_labels END) j";
+    format!(
+        r#"INSERT INTO tags (name)
+                SELECT tag_name FROM (
+                    SELECT j.value AS tag_name FROM ({source}) s, {keywords} WHERE {user_keyword}
+                    UNION
+                    SELECT j.value FROM ({source}) s, {labels} WHERE substr(j.value, 1, 1) <> '\'
+                )
+                WHERE NOT EXISTS (SELECT 1 FROM tags t WHERE t.name = tag_name)
+                GROUP BY tag_name COLLATE NOCASE;
+            INSERT INTO message_tags (message_id, tag_id)
+                SELECT message_id, tag_id FROM (
+                    SELECT s.id AS message_id, t.id AS tag_id
+                        FROM ({source}) s, {keywords} JOIN tags t ON t.name = j.value
+                    UNION
+                    SELECT s.id, t.id FROM ({source}) s, {labels} JOIN tags t ON t.name = j.value
+                ) l
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM message_tags mt
+                    WHERE mt.message_id = l.message_id AND mt.tag_id = l.tag_id
+                );"#,
+        user_keyword = crate::tags::user_tag_sql("j.value"),
+    )
+}
+
+/// Messages a search looks through
+#[derive(Debug, Clone, Copy)]
+pub enum SearchScope<'a> {
+    /// Every cached message
+    All,
+    /// Every folder of one account
+    Account(&'a str),
+    Folder(i64),
+    /// Inbox folders of every account, less those left out of the unified
+    /// inbox
+    Inbox,
+    /// Starred messages of every account
+    Starred,
+    StarredInAccount(&'a str),
+    /// Snoozed messages of every account
+    Snoozed,
+    /// Messages of every account carrying a tag
+    Tagged(i64),
+    /// Messages of every account marked to reply to later
+    ReplyLater,
+}
+
+impl SearchScope<'_> {
+    /// Condition selecting the scope's messages, and the folder or account
+    /// id it binds, if any
+    fn condition(&self) -> (&'static str, Option<SearchBind<'_>>) {
+        match *self {
+            SearchScope::All => ("1", None),
+            SearchScope::Account(account_id) => (
+                "m.folder_id IN (SELECT id FROM folders WHERE account_id = ?)",
+                Some(SearchBind::Text(account_id)),
+            ),
+            SearchScope::Folder(folder_id) => (
+                "m.folder_id = ? AND m.snoozed_until IS NULL",
+                Some(SearchBind::Id(folder_id)),
+            ),
+            SearchScope::Inbox => (
+                "m.folder_id IN (SELECT id FROM folders WHERE folder_type = 'inbox' AND unified_inbox = 1) AND m.snoozed_until IS NULL",
+                None,
+            ),
+            SearchScope::Starred => ("m.is_starred = 1", None),
+            SearchScope::StarredInAccount(account_id) => (
+                "m.is_starred = 1 AND m.folder_id IN (SELECT id FROM folders WHERE account_id = ?)",
+                Some(SearchBind::Text(account_id)),
+            ),
+            SearchScope::Snoozed => ("m.snoozed_until IS NOT NULL", None),
+            SearchScope::Tagged(tag_id) => (
+                "m.id IN (SELECT message_id FROM message_tags WHERE tag_id = ?) AND m.snoozed_until IS NULL",
+                Some(SearchBind::Id(tag_id)),
+            ),
+            SearchScope::ReplyLater => ("m.reply_later_at IS NOT NULL", None),
+        }
+    }
+}
+
+enum SearchBind<'a> {
+    Id(i64),
+    Text(&'a str),
+}
+
 /// Database folder record
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct DbFolder {
@@ -39,6 +205,16 @@ pub struct DbFolder {
     pub uid_next: Option<i64>,
     pub message_count: Option<i64>,
     pub unread_count: Option<i64>,
+    /// False for `\Noselect` / `\NonExistent` containers that only hold children
+    pub is_selectable: bool,
+    /// Full path for display (decoded from modified UTF-7); `full_path`
+    /// keeps the raw form used in IMAP commands
+    pub display_path: String,
+    /// Whether the user is subscribed to the folder (LSUB); true when the
+    /// server has no subscription information
+    pub is_subscribed: bool,
+    /// An inbox shows in the unified inbox (see [`crate::sync_policy`])
+    pub unified_inbox: bool,
 }
 
 /// Attachment metadata from database
@@ -90,6 +266,49 @@ pub struct DbMessage {
     pub body_text: Option<String>,
     /// Cached HTML body
     pub body_html: Option<String>,
+    /// Gmail labels as a JSON array (see [`crate::gmail`])
+    #[sqlx(default)]
+    pub gmail_labels: Option<String>,
+    /// Gmail conversation id (X-GM-THRID)
+    #[sqlx(default)]
+    pub gmail_thread_id: Option<i64>,
+    /// Mention keyword found in the message (see [`crate::mention`])
+    #[sqlx(default)]
+    pub mention: Option<String>,
+    /// IMAP keywords as a JSON array (see [`crate::tags`]); `None` when
+    /// the server wasn't asked
+    #[sqlx(default)]
+    pub tags: Option<String>,
+    /// When a snoozed message comes back, as a Unix timestamp (see
+    /// [`crate::snooze`])
+    #[sqlx(default)]
+    pub snoozed_until: Option<i64>,
+    /// When the reply to the message is due, as a Unix timestamp (see
+    /// [`crate::reply_later`])
+    #[sqlx(default)]
+    pub reply_later_at: Option<i64>,
+}
+
+/// A tag from the `tags` table (see [`crate::tags`])
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DbTag {
+    pub id: i64,
+    /// Keyword or Gmail label, as stored on the server
+    pub name: String,
+    /// `#rrggbb`; `None` for the tag's default colour
+    pub color: Option<String>,
+    /// Cached messages carrying the tag
+    #[sqlx(default)]
+    pub message_count: i64,
+}
+
+impl DbTag {
+    /// The tag's colour, or its default one
+    pub fn color(&self) -> &str {
+        self.color
+            .as_deref()
+            .unwrap_or_else(|| crate::tags::default_color(&self.name))
+    }
 }
 
 /// Filter parameters for message queries
@@ -138,15 +357,410 @@ impl MessageFilter {
     }
 }
 
+/// Bytes of a message's cached header fields, as counted against a cache
+/// size limit
+const HEADER_BYTES: &str = "COALESCE(LENGTH(CAST(m.subject AS BLOB)), 0) \
+     + COALESCE(LENGTH(CAST(m.from_address AS BLOB)), 0) \
+     + COALESCE(LENGTH(CAST(m.from_name AS BLOB)), 0) \
+     + COALESCE(LENGTH(CAST(m.to_addresses AS BLOB)), 0) \
+     + COALESCE(LENGTH(CAST(m.cc_addresses AS BLOB)), 0) \
+     + COALESCE(LENGTH(CAST(m.snippet AS BLOB)), 0)";
+
+/// Bytes of a message's cached body
+const BODY_BYTES: &str = "COALESCE(LENGTH(CAST(m.body_text AS BLOB)), 0) \
+     + COALESCE(LENGTH(CAST(m.body_html AS BLOB)), 0)";
+
+/// Outbox item from a row of the `outbox` table
+fn outbox_item(row: &sqlx::sqlite::SqliteRow) -> OutboxItem {
+    OutboxItem {
+        id: row.get("id"),
+        account_id: row.get("account_id"),
+        subject: row.get("subject"),
+        recipients: row.get("recipients"),
+        status: OutboxStatus::parse(row.get("status")),
+        send_at: row.get("send_at"),
+        error: row.get("error"),
+        attempts: row.get("attempts"),
+        draft_uid: row
+            .get::<Option<i64>, _>("draft_uid")
+            .and_then(|uid| u32::try_from(uid).ok()),
+    }
+}
+
+/// Retention policy from an account's `retention_body_days` and
+/// `retention_max_bytes` columns
+fn folder_sync_policy(
+    (interval, days, fetch_bodies, unified_inbox): (Option<i64>, Option<i64>, bool, bool),
+) -> FolderSyncPolicy {
+    FolderSyncPolicy {
+        interval_minutes: interval.and_then(|m| u32::try_from(m).ok()),
+        sync_days: days.and_then(|d| u32::try_from(d).ok()),
+        fetch_bodies,
+        unified_inbox,
+    }
+}
+
+fn retention_policy((days, bytes): (Option<i64>, Option<i64>)) -> RetentionPolicy {
+    RetentionPolicy {
+        body_days: days.and_then(|d| u32::try_from(d).ok()),
+        max_bytes: bytes.and_then(|b| u64::try_from(b).ok()),
+    }
+}
+
+/// Auto-empty policy from an account's `auto_empty_trash_days` and
+/// `auto_empty_junk_days` columns
+fn auto_empty_policy((trash, junk): (Option<i64>, Option<i64>)) -> AutoEmptyPolicy {
+    AutoEmptyPolicy {
+        trash_days: trash.and_then(|d| u32::try_from(d).ok()),
+        junk_days: junk.and_then(|d| u32::try_from(d).ok()),
+    }
+}
+
+/// Columns of an `identities` row, in the order [`IDENTITY_COLUMNS`] lists them
+type IdentityRow = (
+    i64,
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<Vec<u8>>,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
+);
+
+const IDENTITY_COLUMNS: &str = "id, account_id, name, email, signature, signature_html, signature_image, \
+     signature_image_type, smtp_host, smtp_port, smtp_username";
+
+fn identity_from_row(
+    (id, account_id, name, email, text, html, image, image_type, host, port, username): IdentityRow,
+) -> Identity {
+    let smtp = match (host, port.and_then(|p| u16::try_from(p).ok()), username) {
+        (Some(host), Some(port), Some(username)) => Some(SmtpOverride { host, port, username }),
+        _ => None,
+    };
+    let image = match (image, image_type) {
+        (Some(data), Some(mime_type)) => Some(SignatureImage { mime_type, data }),
+        _ => None,
+    };
+    let signature = Signature {
+        text: text.unwrap_or_default(),
+        html,
+        image,
+    };
+    let signature = (!signature.is_empty()).then_some(signature);
+    Identity {
+        id,
+        account_id,
+        name,
+        email,
+        signature,
+        smtp,
+    }
+}
+
+/// How much space the cache takes and what's in it
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseStats {
+    /// Size of the database file, as SQLite counts its pages
+    pub total_bytes: u64,
+    /// Bytes in free pages, which a vacuum gives back
+    pub free_bytes: u64,
+    /// Bytes of full-text search index data
+    pub fts_bytes: u64,
+    pub accounts: Vec<AccountStats>,
+    /// What the last maintenance run found, if it has run
+    pub last_maintenance: Option<MaintenanceReport>,
+}
+
+/// Cached rows of one account
+#[derive(Debug, Clone, Default)]
+pub struct AccountStats {
+    pub account_id: String,
+    pub folders: i64,
+    pub messages: i64,
+    /// Messages with a cached body
+    pub bodies: i64,
+    pub attachments: i64,
+}
+
+/// A step of [`Database::optimize`], reported as it starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizeStep {
+    /// Copy the write-ahead log into the database file
+    Checkpoint,
+    /// Merge the search index's segments
+    OptimizeIndex,
+    /// Refresh the query planner's statistics
+    Analyze,
+    /// Rebuild the file without its free pages
+    Vacuum,
+}
+
+impl OptimizeStep {
+    pub const ALL: [OptimizeStep; 4] = [
+        OptimizeStep::Checkpoint,
+        OptimizeStep::OptimizeIndex,
+        OptimizeStep::Analyze,
+        OptimizeStep::Vacuum,
+    ];
+}
+
+/// Position in a message list just after the last message shown, where
+/// the next page starts (see [`Database::get_messages`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub date_epoch: Option<i64>,
+    /// Row id, which breaks date ties in lists spanning folders
+    pub id: i64,
+    /// IMAP UID, which breaks date ties within a folder; unlike the row id
+    /// it is known for messages that came straight from the server
+    pub uid: i64,
+}
+
+/// Messages a list pages through. Folder and inbox lists leave out
+/// snoozed messages.
+#[derive(Debug, Clone, Copy)]
+enum PageScope<'a> {
+    Folder(i64),
+    /// Inbox folders of every account, less those left out of the unified
+    /// inbox
+    Inbox,
+    /// Starred messages of every account
+    Starred,
+    StarredInAccount(&'a str),
+    /// Snoozed messages of every account
+    Snoozed,
+    /// Messages of every account carrying a tag
+    Tagged(i64),
+    /// Messages of every account marked to reply to later
+    ReplyLater,
+}
+
+impl PageScope<'_> {
+    /// Condition selecting the scope's messages; binds the folder or
+    /// account id, if any
+    fn condition(&self) -> &'static str {
+        match self {
+            PageScope::Folder(_) => "m.folder_id = ? AND m.snoozed_until IS NULL",
+            PageScope::Inbox => {
+                "m.folder_id IN (SELECT id FROM folders WHERE folder_type = 'inbox' AND unified_inbox = 1) AND m.snoozed_until IS NULL"
+            }
+            PageScope::Starred => "m.is_starred = 1",
+            PageScope::StarredInAccount(_) => {
+                "m.is_starred = 1 AND m.folder_id IN (SELECT id FROM folders WHERE account_id = ?)"
+            }
+            PageScope::Snoozed => "m.snoozed_until IS NOT NULL",
+            PageScope::ReplyLater => "m.reply_later_at IS NOT NULL",
+            PageScope::Tagged(_) => {
+                "m.id IN (SELECT message_id FROM message_tags WHERE tag_id = ?) AND m.snoozed_until IS NULL"
+            }
+        }
+    }
+
+    /// Index holding the scope's messages in display order
+    fn index(&self) -> &'static str {
+        match self {
+            PageScope::Folder(_) => "idx_messages_folder_order",
+            PageScope::Inbox => "idx_messages_inbox_order",
+            PageScope::Starred | PageScope::StarredInAccount(_) => "idx_messages_starred_order",
+            PageScope::Snoozed => "idx_messages_snoozed_order",
+            PageScope::ReplyLater => "idx_messages_reply_later_order",
+            // Every message newest first; the tag's links are checked on the way
+            PageScope::Tagged(_) => "idx_messages_inbox_order",
+        }
+    }
+
+    /// Column ordering messages with the same date
+    fn tiebreak(&self) -> &'static str {
+        match self {
+            PageScope::Folder(_) => "uid",
+            _ => "id",
+        }
+    }
+
+    fn cursor_key(&self, cursor: &PageCursor) -> i64 {
+        match self {
+            PageScope::Folder(_) => cursor.uid,
+            _ => cursor.id,
+        }
+    }
+}
+
+/// Pool options shared by file and in-memory databases. Both hooks apply
+/// the current secure delete setting: `after_connect` to new connections,
+/// `before_acquire` to idle ones handed out again. The pragma is per
+/// connection and costs no I/O, so setting it on every acquire is cheaper
+/// than tracking which connections are up to date.
+fn pool_options(max_connections: u32, secure_delete: &Arc<AtomicBool>) -> SqlitePoolOptions {
+    let on_connect = secure_delete.clone();
+    let on_acquire = secure_delete.clone();
+    SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .after_connect(move |conn, _meta| {
+            let pragma = secure_delete_pragma(&on_connect);
+            Box::pin(async move { sqlx::query(pragma).execute(conn).await.map(|_| ()) })
+        })
+        .before_acquire(move |conn, _meta| {
+            let pragma = secure_delete_pragma(&on_acquire);
+            Box::pin(async move { sqlx::query(pragma).execute(conn).await.map(|_| true) })
+        })
+}
+
+/// Set once a query finds the database file damaged, see
+/// [`Database::is_corrupt`]. One process opens one database, so this
+/// doesn't need to live in it.
+static CORRUPTION_DETECTED: AtomicBool = AtomicBool::new(false);
+
+/// Set while an open upgrades the database's schema, see
+/// [`Database::is_migrating`]
+static MIGRATING: AtomicBool = AtomicBool::new(false);
+
+/// Marks a schema upgrade as running until dropped
+struct Migrating;
+
+impl Migrating {
+    fn start() -> Self {
+        MIGRATING.store(true, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for Migrating {
+    fn drop(&mut self) {
+        MIGRATING.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Whether an error means the database file is damaged: SQLITE_CORRUPT or
+/// SQLITE_NOTADB, or one of their extended codes
+pub(crate) fn is_corruption_error(e: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db_err) = e else {
+        return false;
+    };
+    db_err
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 11 | 26))
+}
+
+pub(crate) fn note_corruption(e: &sqlx::Error) {
+    if !CORRUPTION_DETECTED.swap(true, Ordering::Relaxed) {
+        tracing::error!("Database corruption detected: {}", e);
+    }
+}
+
+/// Take the exclusive lock on `<database>.lock`, failing with
+/// [`CoreError::DatabaseLocked`] if another process holds it
+fn lock_database(path: &Path) -> CoreResult<std::fs::File> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)?;
+    if let Err(e) = lock.try_lock() {
+        return Err(match e {
+            std::fs::TryLockError::WouldBlock => CoreError::DatabaseLocked(path.display().to_string()),
+            std::fs::TryLockError::Error(e) => e.into(),
+        });
+    }
+    Ok(lock)
+}
+
+/// File whose presence makes the next [`Database::open_or_recover`] move
+/// the database aside
+fn recovery_marker(path: &Path) -> PathBuf {
+    let mut marker = path.as_os_str().to_owned();
+    marker.push(".recover");
+    PathBuf::from(marker)
+}
+
+/// Second name for a plain database while encrypting it, see
+/// [`Database::convert`]
+fn plaintext_path(path: &Path) -> PathBuf {
+    let mut plain = path.as_os_str().to_owned();
+    plain.push(".plaintext");
+    PathBuf::from(plain)
+}
+
+/// Start of every plain SQLite database file. SQLCipher encrypts the file
+/// from its first byte, so an encrypted database never starts with it.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether the database file at `path` is encrypted. A missing or empty
+/// file isn't.
+fn is_encrypted_file(path: &Path) -> CoreResult<bool> {
+    use std::io::Read;
+
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut header = Vec::with_capacity(SQLITE_HEADER.len());
+    file.by_ref().take(SQLITE_HEADER.len() as u64).read_to_end(&mut header)?;
+    Ok(!header.is_empty() && header != SQLITE_HEADER)
+}
+
+/// Keys are 256-bit raw keys in hex, handed to SQLCipher as `x'...'` so it
+/// uses them as they are instead of deriving a key from a passphrase
+fn check_key(key: &str) -> CoreResult<()> {
+    if key.len() == 64 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(CoreError::StorageError("Database key must be 64 hex digits".to_string()))
+    }
+}
+
+fn raw_key(key: &str) -> String {
+    format!("x'{}'", key)
+}
+
+fn secure_delete_pragma(enabled: &AtomicBool) -> &'static str {
+    if enabled.load(Ordering::Relaxed) {
+        "PRAGMA secure_delete = ON"
+    } else {
+        "PRAGMA secure_delete = OFF"
+    }
+}
+
 /// Database connection pool
 pub struct Database {
     pool: Pool<Sqlite>,
+    /// Whether deleted content is overwritten (see [`Self::set_secure_delete`])
+    secure_delete: Arc<AtomicBool>,
+    /// Characters of preview text list queries return (see
+    /// [`Self::set_snippet_length`])
+    snippet_length: Arc<AtomicUsize>,
+    /// Where writes to messages and folder counts are announced
+    changes: ChangeBus,
+    /// Exclusive lock on `<database>.lock`, held while the database is
+    /// open so a second process can't write to it too
+    _lock: Option<std::fs::File>,
 }
 
 impl Database {
+    /// Whether this build links SQLCipher, so the database can be encrypted
+    pub fn encryption_supported() -> bool {
+        cfg!(feature = "sqlcipher")
+    }
+
+    /// Whether the database file at `path` is encrypted with SQLCipher
+    pub fn is_encrypted(path: impl AsRef<Path>) -> CoreResult<bool> {
+        is_encrypted_file(path.as_ref())
+    }
+
     /// Open or create a database at the given path
     pub async fn open(path: impl AsRef<Path>) -> CoreResult<Self> {
-        let path = path.as_ref();
+        Self::open_with_key(path.as_ref(), None).await
+    }
+
+    /// Open or create a database, encrypted with `key` if given
+    async fn open_with_key(path: &Path, key: Option<&str>) -> CoreResult<Self> {
 
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
@@ -155,39 +769,423 @@ impl Database {
 
         info!("Opening database at {}", path.display());
 
-        let connect_options = SqliteConnectOptions::new()
+        // SQLite copes with several writers, but two NorthMail processes
+        // syncing the same cache would duplicate work and fight over it
+        let lock = lock_database(path)?;
+
+        let mut connect_options = SqliteConnectOptions::new()
             .filename(path)
             .create_if_missing(true)
             .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            // New databases give free pages back in idle-time maintenance;
+            // existing ones switch over with the next full vacuum
+            .auto_vacuum(sqlx::sqlite::SqliteAutoVacuum::Incremental)
             .busy_timeout(std::time::Duration::from_secs(30));
+        if let Some(key) = key {
+            // sqlx sends the key pragma before any other statement
+            connect_options = connect_options.pragma("key", format!("\"{}\"", raw_key(key)));
+        }
 
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
+        let secure_delete = Arc::new(AtomicBool::new(false));
+        let pool = pool_options(5, &secure_delete)
             .connect_with(connect_options)
             .await?;
 
-        let db = Self { pool };
+        let db = Self {
+            pool,
+            secure_delete,
+            snippet_length: Arc::new(AtomicUsize::new(DEFAULT_SNIPPET_LENGTH)),
+            changes: ChangeBus::new(),
+            _lock: Some(lock),
+        };
 
-        db.initialize().await?;
+        if let Err(e) = db.initialize(Some(path)).await {
+            db.pool.close().await;
+            return Err(e);
+        }
 
         Ok(db)
     }
 
+    /// Open the database like [`Self::open`], but if it is damaged, or a
+    /// previous run asked for it with [`Self::schedule_recovery`], move it
+    /// aside and start over with an empty one. Returns where the damaged
+    /// file went, if it was moved.
+    ///
+    /// With `encrypt` the database is encrypted with `key` using SQLCipher,
+    /// otherwise it is left in plain text; a database in the other state is
+    /// converted first. `key` is also what an encrypted database is read
+    /// with, so pass the stored key even when turning encryption off. An
+    /// encrypted database that can't be read without a key is moved aside.
+    /// Encrypting removes the plain file and the copies kept next to it
+    /// (see [`Self::aside_copies`]), wiping them when `secure`.
+    pub async fn open_or_recover(
+        path: impl AsRef<Path>,
+        key: Option<&str>,
+        encrypt: bool,
+        secure: bool,
+    ) -> CoreResult<(Self, Option<PathBuf>)> {
+        let path = path.as_ref();
+        let marker = recovery_marker(path);
+
+        if let Some(key) = key {
+            check_key(key)?;
+        }
+        let wanted = match (encrypt, key) {
+            (false, _) => None,
+            (true, Some(key)) => Some(key),
+            (true, None) => {
+                return Err(CoreError::StorageError("No key to encrypt the database with".to_string()))
+            }
+        };
+        if wanted.is_some() && !Self::encryption_supported() {
+            return Err(CoreError::EncryptionUnsupported(path.display().to_string()));
+        }
+
+        if !marker.exists() {
+            match Self::open_converted(path, key, wanted, secure).await {
+                Err(CoreError::DatabaseCorrupt(reason)) => {
+                    warn!("Database at {} is damaged, rebuilding it: {}", path.display(), reason);
+                }
+                result => return result.map(|db| (db, None)),
+            }
+        }
+
+        let moved = Self::move_aside(path)?;
+        if let Err(e) = std::fs::remove_file(&marker) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        CORRUPTION_DETECTED.store(false, Ordering::Relaxed);
+        let db = Self::open_with_key(path, wanted).await?;
+        Ok((db, Some(moved)))
+    }
+
+    /// Open the database with `wanted` as its key, first encrypting or
+    /// decrypting it if it is stored the other way
+    async fn open_converted(
+        path: &Path,
+        key: Option<&str>,
+        wanted: Option<&str>,
+        secure: bool,
+    ) -> CoreResult<Self> {
+        // The plain file an encryption stopped before wiping. While the
+        // database is still plain it may be another name for it.
+        let plain = plaintext_path(path);
+        if plain.exists() {
+            if is_encrypted_file(path)? {
+                wipe::remove_file(&plain, true)?;
+            } else {
+                std::fs::remove_file(&plain)?;
+            }
+        }
+
+        let current = if is_encrypted_file(path)? {
+            if !Self::encryption_supported() {
+                return Err(CoreError::EncryptionUnsupported(path.display().to_string()));
+            }
+            // Without the key nothing can read it again
+            Some(key.ok_or_else(|| CoreError::DatabaseCorrupt("encrypted with a key that is no longer stored".to_string()))?)
+        } else {
+            None
+        };
+
+        if current != wanted {
+            match Self::convert(path, current, wanted, secure).await {
+                Ok(()) => {}
+                Err(e @ CoreError::DatabaseCorrupt(_)) => return Err(e),
+                Err(e) => {
+                    // Keep using it as it is; the next start tries again
+                    warn!("Failed to convert database at {}: {}", path.display(), e);
+                    return Self::open_with_key(path, current).await;
+                }
+            }
+        }
+        Self::open_with_key(path, wanted).await
+    }
+
+    /// Rewrite the database with SQLCipher's `sqlcipher_export` into a copy
+    /// encrypted with `to`, or a plain one when `to` is `None`, and put the
+    /// copy in its place. The original stays as it was if this fails.
+    /// Encrypting also removes the copies kept next to the database, which
+    /// are in plain text, and with `secure` wipes what it removes.
+    async fn convert(path: &Path, from: Option<&str>, to: Option<&str>, secure: bool) -> CoreResult<()> {
+        let mut target = path.as_os_str().to_owned();
+        target.push(".converting");
+        let target = PathBuf::from(target);
+        match std::fs::remove_file(&target) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        info!(
+            "{} database at {}",
+            if to.is_some() { "Encrypting" } else { "Decrypting" },
+            path.display()
+        );
+
+        let Self { pool, _lock: lock, .. } = Self::open_with_key(path, from).await?;
+        let exported = async {
+            // ATTACH and the export have to run on the same connection
+            let mut conn = pool.acquire().await?;
+            sqlx::query("ATTACH DATABASE ? AS converted KEY ?")
+                .bind(target.to_string_lossy().into_owned())
+                .bind(to.map(raw_key).unwrap_or_default())
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("SELECT sqlcipher_export('converted')")
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query("DETACH DATABASE converted")
+                .execute(&mut *conn)
+                .await?;
+            Ok::<_, sqlx::Error>(())
+        }
+        .await;
+        // Closing the last connection checkpoints the WAL into the file
+        pool.close().await;
+        if let Err(e) = exported {
+            let _ = std::fs::remove_file(&target);
+            return Err(e.into());
+        }
+
+        // Plain text left on disk is what encrypting is meant to get rid of
+        let wipe_plain = secure && to.is_some();
+        for suffix in ["-wal", "-shm"] {
+            let mut stale = path.as_os_str().to_owned();
+            stale.push(suffix);
+            wipe::remove_file(Path::new(&stale), wipe_plain)?;
+        }
+        if wipe_plain {
+            // Keep a second name for the plain file, so it can be wiped once
+            // the encrypted copy has taken its place
+            std::fs::hard_link(path, plaintext_path(path))?;
+        }
+        std::fs::rename(&target, path)?;
+        if wipe_plain {
+            wipe::wipe_file(&plaintext_path(path))?;
+        }
+        // A backup from before a migration is still in the old format, and
+        // databases moved aside are left in plain text
+        let stale = if to.is_some() {
+            Self::aside_copies(path)?
+        } else {
+            vec![migrations::backup_path(path)]
+        };
+        for copy in stale {
+            wipe::remove_file(&copy, wipe_plain)?;
+            debug!("Removed {}", copy.display());
+        }
+        drop(lock);
+        Ok(())
+    }
+
+    /// Ask the next [`Self::open_or_recover`] of `path` to move the database
+    /// aside, for a damaged database found while it is open
+    pub fn schedule_recovery(path: impl AsRef<Path>) -> CoreResult<()> {
+        std::fs::write(recovery_marker(path.as_ref()), b"")?;
+        Ok(())
+    }
+
+    /// Whether a query has found the database damaged since it was opened
+    pub fn is_corrupt(&self) -> bool {
+        CORRUPTION_DETECTED.load(Ordering::Relaxed)
+    }
+
+    /// Whether an open is upgrading the database's schema, which can take
+    /// a while on a large cache, so a caller waiting on it can tell a slow
+    /// upgrade from a stuck open
+    pub fn is_migrating() -> bool {
+        MIGRATING.load(Ordering::Relaxed)
+    }
+
+    /// Write a consistent copy of the database to `target` with SQLite's
+    /// `VACUUM INTO`, which leaves out free pages. The database stays
+    /// usable meanwhile; writes wait until the copy is done.
+    pub async fn snapshot_to(&self, target: &Path) -> CoreResult<()> {
+        if target.exists() {
+            return Err(CoreError::StorageError(format!("{} already exists", target.display())));
+        }
+        sqlx::query("VACUUM INTO ?")
+            .bind(target.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Put a copy of the database file at `from` in place of the one at
+    /// `path`, which must not be open. The database it replaces is renamed
+    /// to `<name>.before-restore-<unix time>` with its WAL and
+    /// shared-memory files, and that path is returned if there was one.
+    pub fn replace(path: &Path, from: &Path) -> CoreResult<Option<PathBuf>> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let lock = lock_database(path)?;
+
+        let mut staged = path.as_os_str().to_owned();
+        staged.push(".restoring");
+        let staged = PathBuf::from(staged);
+        std::fs::copy(from, &staged)?;
+        std::fs::File::open(&staged)?.sync_all()?;
+
+        let moved = if path.exists() {
+            Some(Self::move_aside_as(path, "before-restore")?)
+        } else {
+            None
+        };
+        // Neither a pending recovery nor a pre-migration copy is about the
+        // database that comes back
+        for stale in [recovery_marker(path), migrations::backup_path(path)] {
+            match std::fs::remove_file(&stale) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        std::fs::rename(&staged, path)?;
+        drop(lock);
+
+        info!("Restored database at {}", path.display());
+        Ok(moved)
+    }
+
+    /// Rename the database and its WAL and shared-memory files to
+    /// `<name>.corrupt-<unix time>`, returning the new database path
+    fn move_aside(path: &Path) -> CoreResult<PathBuf> {
+        let target = Self::move_aside_as(path, "corrupt")?;
+        warn!("Moved damaged database to {}", target.display());
+        Ok(target)
+    }
+
+    /// Rename the database and its WAL and shared-memory files to
+    /// `<name>.<label>-<unix time>`, returning the new database path
+    fn move_aside_as(path: &Path, label: &str) -> CoreResult<PathBuf> {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut target = path.as_os_str().to_owned();
+        target.push(format!(".{}-{}", label, stamp));
+        let target = PathBuf::from(target);
+
+        for suffix in ["", "-wal", "-shm"] {
+            let mut from = path.as_os_str().to_owned();
+            from.push(suffix);
+            let mut to = target.as_os_str().to_owned();
+            to.push(suffix);
+            match std::fs::rename(&from, &to) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(target)
+    }
+
+    /// Copies of the database at `path` kept next to it: the backup from
+    /// before a migration and databases moved aside by a recovery or a
+    /// restore, with their WAL and shared-memory files
+    pub fn aside_copies(path: &Path) -> CoreResult<Vec<PathBuf>> {
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+            return Ok(Vec::new());
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let prefixes = [
+            format!("{}.pre-migration", name),
+            format!("{}.corrupt-", name),
+            format!("{}.before-restore-", name),
+        ];
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut copies = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if prefixes.iter().any(|prefix| file_name.starts_with(prefix.as_str())) {
+                copies.push(entry.path());
+            }
+        }
+        copies.sort();
+        Ok(copies)
+    }
+
     /// Open an in-memory database (for testing)
     pub async fn open_memory() -> CoreResult<Self> {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(1)
+        let secure_delete = Arc::new(AtomicBool::new(false));
+        let pool = pool_options(1, &secure_delete)
             .connect("sqlite::memory:")
             .await?;
 
-        let db = Self { pool };
-        db.initialize().await?;
+        let db = Self {
+            pool,
+            secure_delete,
+            snippet_length: Arc::new(AtomicUsize::new(DEFAULT_SNIPPET_LENGTH)),
+            changes: ChangeBus::new(),
+            _lock: None,
+        };
+        db.initialize(None).await?;
 
         Ok(db)
     }
 
-    /// Initialize the database schema
-    async fn initialize(&self) -> CoreResult<()> {
+    /// Overwrite deleted content instead of just unlinking it, so purged
+    /// mail doesn't linger in free pages of the database file. Takes effect
+    /// on the next query of every pooled connection.
+    pub fn set_secure_delete(&self, enabled: bool) {
+        self.secure_delete.store(enabled, Ordering::Relaxed);
+    }
+
+    /// How much preview text message list pages and searches return: the
+    /// stored snippet, or the start of the cached body when there is none,
+    /// cut to `chars`. Zero leaves previews out.
+    pub fn set_snippet_length(&self, chars: usize) {
+        self.snippet_length.store(chars, Ordering::Relaxed);
+    }
+
+    /// Where the database announces the messages, flags and folder counts
+    /// it changes (see [`crate::changes`])
+    pub fn changes(&self) -> &ChangeBus {
+        &self.changes
+    }
+
+    /// Select-list expression for the `snippet` column of list queries
+    fn snippet_column(&self) -> String {
+        match self.snippet_length.load(Ordering::Relaxed) {
+            0 => "NULL AS snippet".to_string(),
+            chars => format!(
+                "substr(COALESCE(NULLIF(m.snippet, ''), m.body_text), 1, {}) AS snippet",
+                chars
+            ),
+        }
+    }
+
+    /// After a purge with secure delete on, checkpoint the WAL and truncate
+    /// it, so the zeroed pages reach the database file and no copy of the
+    /// old ones is left in the log
+    async fn finish_purge(&self) -> CoreResult<()> {
+        if self.secure_delete.load(Ordering::Relaxed) {
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Initialize the database schema. `path` is the database file, backed
+    /// up before any migration (see [`migrations`]); `None` in memory.
+    async fn initialize(&self, path: Option<&Path>) -> CoreResult<()> {
         debug!("Initializing database schema");
 
         sqlx::query(
@@ -213,6 +1211,10 @@ impl Database {
                 uid_next INTEGER,
                 message_count INTEGER DEFAULT 0,
                 unread_count INTEGER DEFAULT 0,
+                is_selectable INTEGER NOT NULL DEFAULT 1,
+                is_subscribed INTEGER NOT NULL DEFAULT 1,
+                display_path TEXT,
+                is_idle INTEGER NOT NULL DEFAULT 0,
                 created_at TEXT DEFAULT (datetime('now')),
                 updated_at TEXT DEFAULT (datetime('now')),
                 UNIQUE(account_id, full_path)
@@ -248,34 +1250,6 @@ impl Database {
             CREATE INDEX IF NOT EXISTS idx_messages_message_id ON messages(message_id);
             CREATE INDEX IF NOT EXISTS idx_folders_account ON folders(account_id);
 
-            -- Full-text search for messages
-            CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
-                subject,
-                from_address,
-                from_name,
-                snippet,
-                content=messages,
-                content_rowid=id
-            );
-
-            -- Triggers to keep FTS in sync
-            CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
-                INSERT INTO messages_fts(rowid, subject, from_address, from_name, snippet)
-                VALUES (new.id, new.subject, new.from_address, new.from_name, new.snippet);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
-                INSERT INTO messages_fts(messages_fts, rowid, subject, from_address, from_name, snippet)
-                VALUES ('delete', old.id, old.subject, old.from_address, old.from_name, old.snippet);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS messages_au AFTER UPDATE ON messages BEGIN
-                INSERT INTO messages_fts(messages_fts, rowid, subject, from_address, from_name, snippet)
-                VALUES ('delete', old.id, old.subject, old.from_address, old.from_name, old.snippet);
-                INSERT INTO messages_fts(rowid, subject, from_address, from_name, snippet)
-                VALUES (new.id, new.subject, new.from_address, new.from_name, new.snippet);
-            END;
-
             -- Attachment metadata cache (data fetched from IMAP on demand)
             CREATE TABLE IF NOT EXISTS attachments (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -306,21 +1280,148 @@ impl Database {
         // Migration: Add graph_folder_id and graph_message_id columns
         self.migrate_add_graph_ids().await?;
 
+        // Migration: Add is_selectable column for container folders
+        self.migrate_add_folder_selectable().await?;
+
+        // Migration: Add is_subscribed column for subscription-aware listing
+        self.migrate_add_folder_subscribed().await?;
+
+        // Migration: Add display_path column for decoded folder names
+        self.migrate_add_folder_display_path().await?;
+
+        // Migration: Add is_idle column for folders watched with IDLE
+        self.migrate_add_folder_idle().await?;
+
+        // Migration: Add graph_delta_link column for Graph API change tracking
+        self.migrate_add_folder_delta_link().await?;
+
+        // Migration: Add per-account TLS trust columns (custom CA, pinned certificate)
+        self.migrate_add_account_tls().await?;
+        self.migrate_add_account_proxy().await?;
+
+        // Migration: Add Gmail label and conversation id columns
+        self.migrate_add_gmail_attributes().await?;
+
+        // Migration: Add mention column for messages matching a mention keyword
+        self.migrate_add_message_mention().await?;
+
+        // Migration: Add tags column for IMAP keywords
+        self.migrate_add_message_tags().await?;
+
+        // Migration: Add indexes for paging through message lists
+        self.migrate_add_page_indexes().await?;
+
+        // Migration: Add snoozed_until column and its indexes
+        self.migrate_add_message_snooze().await?;
+
+        // Migration: Add rules table for client-side message rules
+        self.migrate_add_rules().await?;
+
+        // Migration: Add the tag catalog and message-to-tag links
+        self.migrate_add_tag_links().await?;
+
+        // Migration: Add reply_later_at column and its indexes
+        self.migrate_add_reply_later().await?;
+
+        // Migration: Add per-account cache retention settings
+        self.migrate_add_account_retention().await?;
+
+        // Migration: Add the outbox of messages waiting to be sent
+        self.migrate_add_outbox().await?;
+
+        // Migration: Index recipients and body text for full-text search.
+        // Runs after the column migrations, as its triggers read those columns.
+        self.migrate_fts_columns().await?;
+
         // Migration: Rebuild FTS index to ensure all messages are indexed
         self.migrate_rebuild_fts().await?;
 
+        // Everything above is the baseline; later changes are numbered
+        self.run_migrations(path).await?;
+
         info!("Database schema initialized");
         Ok(())
     }
 
-    /// Add body_text and body_html columns if they don't exist
-    async fn migrate_add_body_columns(&self) -> CoreResult<()> {
-        // Check if columns exist by trying to select them
-        let result = sqlx::query("SELECT body_text FROM messages LIMIT 1")
-            .fetch_optional(&self.pool)
-            .await;
-
-        if result.is_err() {
+    /// Record the baseline schema, then apply the numbered migrations the
+    /// database hasn't had, each in a transaction with its `schema_version`
+    /// row, after backing up the database file at `path`
+    async fn run_migrations(&self, path: Option<&Path>) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TEXT DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("INSERT OR IGNORE INTO schema_version (version, name) VALUES (?, 'baseline')")
+            .bind(migrations::BASELINE_VERSION)
+            .execute(&self.pool)
+            .await?;
+
+        let current: i64 = sqlx::query_scalar("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&self.pool)
+            .await?;
+        if current > migrations::latest_version() {
+            warn!(
+                "Database schema version {} is newer than this build knows ({}); it was opened by a newer NorthMail",
+                current,
+                migrations::latest_version()
+            );
+            return Ok(());
+        }
+        let pending = migrations::pending(current);
+        if pending.is_empty() {
+            return Ok(());
+        }
+        // Copying the file first takes a while on a large cache
+        let _migrating = Migrating::start();
+
+        if let Some(path) = path {
+            // The WAL has to be in the file for the copy to hold everything
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(&self.pool)
+                .await?;
+            let backup = migrations::backup_path(path);
+            std::fs::copy(path, &backup)?;
+            info!("Backed up database to {} before migrating it", backup.display());
+        }
+
+        for migration in pending {
+            info!("Migrating database to schema version {}: {}", migration.version, migration.name);
+            let mut tx = self.pool.begin().await?;
+            let applied = async {
+                sqlx::query(migration.sql).execute(&mut *tx).await?;
+                sqlx::query("INSERT INTO schema_version (version, name) VALUES (?, ?)")
+                    .bind(migration.version)
+                    .bind(migration.name)
+                    .execute(&mut *tx)
+                    .await
+            }
+            .await;
+            if let Err(e) = applied {
+                // Dropping the transaction rolls the migration back
+                warn!("Migration to schema version {} failed: {}", migration.version, e);
+                return Err(e.into());
+            }
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Add body_text and body_html columns if they don't exist
+    async fn migrate_add_body_columns(&self) -> CoreResult<()> {
+        // Check if columns exist by trying to select them
+        let result = sqlx::query("SELECT body_text FROM messages LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
             // Columns don't exist, add them
             debug!("Migrating database: adding body_text and body_html columns");
             if let Err(e) = sqlx::query("ALTER TABLE messages ADD COLUMN body_text TEXT")
@@ -449,1437 +1550,4326 @@ impl Database {
         Ok(())
     }
 
-    /// Rebuild FTS index to ensure all messages are indexed
-    /// This is needed because messages inserted before the FTS table existed won't be in the index
-    async fn migrate_rebuild_fts(&self) -> CoreResult<()> {
-        // Check if there are messages not in FTS by comparing counts
-        let msg_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
-            .fetch_one(&self.pool)
-            .await
-            .unwrap_or(0);
+    /// Add is_selectable column to folders if it doesn't exist
+    async fn migrate_add_folder_selectable(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT is_selectable FROM folders LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
 
-        let fts_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages_fts")
-            .fetch_one(&self.pool)
+        if result.is_err() {
+            debug!("Migrating database: adding is_selectable column to folders");
+            if let Err(e) = sqlx::query(
+                "ALTER TABLE folders ADD COLUMN is_selectable INTEGER NOT NULL DEFAULT 1",
+            )
+            .execute(&self.pool)
             .await
-            .unwrap_or(0);
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding is_selectable column: {}", e);
+                }
+            }
+        }
 
-        if msg_count > fts_count {
-            info!(
-                "FTS index incomplete ({} messages, {} indexed). Rebuilding...",
-                msg_count, fts_count
-            );
+        Ok(())
+    }
 
-            // Rebuild FTS index by re-inserting all messages
-            // First delete all FTS entries
-            if let Err(e) = sqlx::query("DELETE FROM messages_fts")
-                .execute(&self.pool)
-                .await
+    /// Add is_subscribed column to folders if it doesn't exist
+    async fn migrate_add_folder_subscribed(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT is_subscribed FROM folders LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding is_subscribed column to folders");
+            if let Err(e) = sqlx::query(
+                "ALTER TABLE folders ADD COLUMN is_subscribed INTEGER NOT NULL DEFAULT 1",
+            )
+            .execute(&self.pool)
+            .await
             {
-                warn!("Failed to clear FTS index: {}", e);
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding is_subscribed column: {}", e);
+                }
             }
+        }
 
-            // Then repopulate from messages table
-            sqlx::query(
-                r#"
-                INSERT INTO messages_fts(rowid, subject, from_address, from_name, snippet)
-                SELECT id, subject, from_address, from_name, snippet FROM messages
-                "#,
+        Ok(())
+    }
+
+    /// Add is_idle column to folders if it doesn't exist
+    async fn migrate_add_folder_idle(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT is_idle FROM folders LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding is_idle column to folders");
+            if let Err(e) = sqlx::query(
+                "ALTER TABLE folders ADD COLUMN is_idle INTEGER NOT NULL DEFAULT 0",
             )
             .execute(&self.pool)
-            .await?;
+            .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding is_idle column: {}", e);
+                }
+            }
+        }
 
-            info!("FTS index rebuilt with {} messages", msg_count);
+        Ok(())
+    }
+
+    async fn migrate_add_folder_delta_link(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT graph_delta_link FROM folders LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding graph_delta_link column to folders");
+            if let Err(e) = sqlx::query("ALTER TABLE folders ADD COLUMN graph_delta_link TEXT")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding graph_delta_link column: {}", e);
+                }
+            }
         }
 
         Ok(())
     }
 
-    /// Insert or update an account
-    pub async fn upsert_account(&self, account: &crate::Account) -> CoreResult<()> {
-        let auth_method = serde_json::to_string(&account.auth_method)
-            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
-        let config_json = serde_json::to_string(&account.config)
-            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+    async fn migrate_add_account_tls(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT tls_ca_file FROM accounts LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
 
-        sqlx::query(
-            r#"
-            INSERT INTO accounts (id, email_address, display_name, provider, auth_method, config_json)
-            VALUES (?, ?, ?, ?, ?, ?)
-            ON CONFLICT(id) DO UPDATE SET
-                email_address = excluded.email_address,
-                display_name = excluded.display_name,
-                provider = excluded.provider,
-                auth_method = excluded.auth_method,
-                config_json = excluded.config_json,
-                updated_at = datetime('now')
-            "#,
-        )
-        .bind(&account.id)
-        .bind(&account.email)
-        .bind(&account.display_name)
-        .bind(&account.provider)
-        .bind(&auth_method)
-        .bind(&config_json)
-        .execute(&self.pool)
-        .await?;
+        if result.is_err() {
+            debug!("Migrating database: adding TLS columns to accounts");
+            for column in ["tls_ca_file", "tls_pinned_fingerprint"] {
+                if let Err(e) = sqlx::query(&format!("ALTER TABLE accounts ADD COLUMN {} TEXT", column))
+                    .execute(&self.pool)
+                    .await
+                {
+                    if !e.to_string().contains("duplicate column") {
+                        warn!("Migration error adding {} column: {}", column, e);
+                    }
+                }
+            }
+        }
 
         Ok(())
     }
 
-    /// Get all accounts
-    pub async fn get_accounts(&self) -> CoreResult<Vec<crate::Account>> {
-        #[derive(sqlx::FromRow)]
-        struct AccountRow {
-            id: String,
-            email_address: String,
-            display_name: Option<String>,
-            provider: String,
-            auth_method: String,
-            config_json: String,
+    async fn migrate_add_account_proxy(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT proxy_url FROM accounts LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding proxy_url column to accounts");
+            if let Err(e) = sqlx::query("ALTER TABLE accounts ADD COLUMN proxy_url TEXT")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding proxy_url column: {}", e);
+                }
+            }
         }
 
-        let rows: Vec<AccountRow> =
-            sqlx::query_as("SELECT id, email_address, display_name, provider, auth_method, config_json FROM accounts")
-                .fetch_all(&self.pool)
-                .await?;
+        Ok(())
+    }
 
-        let mut accounts = Vec::new();
-        for row in rows {
-            let auth_method: northmail_auth::AuthMethod = serde_json::from_str(&row.auth_method)
-                .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
-            let config: crate::AccountConfig = serde_json::from_str(&row.config_json)
-                .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+    /// Add the retention columns to accounts if they don't exist (see
+    /// [`crate::retention`])
+    async fn migrate_add_account_retention(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT retention_body_days FROM accounts LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
 
-            accounts.push(crate::Account {
-                id: row.id,
-                email: row.email_address,
-                display_name: row.display_name,
-                provider: row.provider,
-                auth_method,
-                config,
-            });
+        if result.is_err() {
+            debug!("Migrating database: adding retention columns to accounts");
+            for column in ["retention_body_days", "retention_max_bytes"] {
+                if let Err(e) = sqlx::query(&format!("ALTER TABLE accounts ADD COLUMN {} INTEGER", column))
+                    .execute(&self.pool)
+                    .await
+                {
+                    if !e.to_string().contains("duplicate column") {
+                        warn!("Migration error adding {} column: {}", column, e);
+                    }
+                }
+            }
         }
 
-        Ok(accounts)
+        Ok(())
     }
 
-    /// Delete an account
-    pub async fn delete_account(&self, account_id: &str) -> CoreResult<()> {
-        sqlx::query("DELETE FROM accounts WHERE id = ?")
-            .bind(account_id)
+    /// Add gmail_labels and gmail_thread_id columns to messages if they don't exist
+    async fn migrate_add_gmail_attributes(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT gmail_thread_id FROM messages LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding Gmail label columns to messages");
+            for column in ["gmail_labels TEXT", "gmail_thread_id INTEGER"] {
+                if let Err(e) = sqlx::query(&format!("ALTER TABLE messages ADD COLUMN {}", column))
+                    .execute(&self.pool)
+                    .await
+                {
+                    if !e.to_string().contains("duplicate column") {
+                        warn!("Migration error adding {} column: {}", column, e);
+                    }
+                }
+            }
+        }
+
+        if let Err(e) = sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_gmail_thread ON messages(gmail_thread_id)")
             .execute(&self.pool)
-            .await?;
+            .await
+        {
+            warn!("Migration error creating gmail_thread_id index: {}", e);
+        }
+
         Ok(())
     }
 
-    /// Insert or update a folder
-    pub async fn upsert_folder(
-        &self,
-        account_id: &str,
-        name: &str,
-        full_path: &str,
-        folder_type: &str,
-    ) -> CoreResult<i64> {
-        self.upsert_folder_with_counts(account_id, name, full_path, folder_type, None, None)
-            .await
+    /// Add mention column to messages if it doesn't exist
+    async fn migrate_add_message_mention(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT mention FROM messages LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding mention column to messages");
+            if let Err(e) = sqlx::query("ALTER TABLE messages ADD COLUMN mention TEXT")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding mention column: {}", e);
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    /// Insert or update a folder with message/unread counts
-    pub async fn upsert_folder_with_counts(
-        &self,
-        account_id: &str,
-        name: &str,
-        full_path: &str,
-        folder_type: &str,
-        message_count: Option<i64>,
-        unread_count: Option<i64>,
-    ) -> CoreResult<i64> {
-        let result = sqlx::query(
-            r#"
-            INSERT INTO folders (account_id, name, full_path, folder_type, message_count, unread_count)
+    /// Add tags column to messages if it doesn't exist
+    async fn migrate_add_message_tags(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT tags FROM messages LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding tags column to messages");
+            if let Err(e) = sqlx::query("ALTER TABLE messages ADD COLUMN tags TEXT")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding tags column: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add snoozed_until column to messages if it doesn't exist, with
+    /// indexes for the Snoozed list and for finding the next one due
+    async fn migrate_add_message_snooze(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT snoozed_until FROM messages LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding snoozed_until column to messages");
+            if let Err(e) = sqlx::query("ALTER TABLE messages ADD COLUMN snoozed_until INTEGER")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding snoozed_until column: {}", e);
+                }
+            }
+        }
+
+        for statement in [
+            "CREATE INDEX IF NOT EXISTS idx_messages_snoozed_order
+             ON messages(date_epoch DESC, id DESC) WHERE snoozed_until IS NOT NULL",
+            "CREATE INDEX IF NOT EXISTS idx_messages_snoozed_due
+             ON messages(snoozed_until) WHERE snoozed_until IS NOT NULL",
+        ] {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Add reply_later_at column to messages if it doesn't exist, with
+    /// indexes for the Reply Later list and for finding the next one due
+    async fn migrate_add_reply_later(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT reply_later_at FROM messages LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding reply_later_at column to messages");
+            if let Err(e) = sqlx::query("ALTER TABLE messages ADD COLUMN reply_later_at INTEGER")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding reply_later_at column: {}", e);
+                }
+            }
+        }
+
+        for statement in [
+            "CREATE INDEX IF NOT EXISTS idx_messages_reply_later_order
+             ON messages(date_epoch DESC, id DESC) WHERE reply_later_at IS NOT NULL",
+            "CREATE INDEX IF NOT EXISTS idx_messages_reply_later_due
+             ON messages(reply_later_at) WHERE reply_later_at IS NOT NULL",
+        ] {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Add the table of outgoing messages waiting to be sent (see
+    /// [`crate::outbox`])
+    async fn migrate_add_outbox(&self) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id TEXT NOT NULL,
+                subject TEXT NOT NULL DEFAULT '',
+                recipients TEXT NOT NULL DEFAULT '',
+                message_json TEXT NOT NULL,
+                draft_uid INTEGER,
+                status TEXT NOT NULL DEFAULT 'queued',
+                send_at INTEGER NOT NULL,
+                error TEXT,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT DEFAULT (datetime('now'))
+            );
+            CREATE INDEX IF NOT EXISTS idx_outbox_due ON outbox(status, send_at);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Add the table of client-side message rules (see [`crate::rules`])
+    async fn migrate_add_rules(&self) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS rules (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                account_id TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                match_mode TEXT NOT NULL DEFAULT 'all',
+                conditions_json TEXT NOT NULL DEFAULT '[]',
+                actions_json TEXT NOT NULL DEFAULT '[]',
+                stop_processing INTEGER NOT NULL DEFAULT 0,
+                position INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Add the tag catalog and the links from messages to tags (see
+    /// [`crate::tags`]). Triggers keep the links in step with each message's
+    /// keywords and Gmail labels, registering tags not seen before; they are
+    /// recreated on every start so they skip the current list of bookkeeping
+    /// keywords. New link tables are filled from the cache.
+    async fn migrate_add_tag_links(&self) -> CoreResult<()> {
+        let existed = sqlx::query("SELECT 1 FROM message_tags LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await
+            .is_ok();
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                color TEXT
+            );
+            CREATE TABLE IF NOT EXISTS message_tags (
+                message_id INTEGER NOT NULL,
+                tag_id INTEGER NOT NULL,
+                PRIMARY KEY (message_id, tag_id)
+            ) WITHOUT ROWID;
+            CREATE INDEX IF NOT EXISTS idx_message_tags_tag ON message_tags(tag_id, message_id);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let triggers = format!(
+            r#"
+            DROP TRIGGER IF EXISTS message_tags_ai;
+            CREATE TRIGGER message_tags_ai AFTER INSERT ON messages BEGIN
+                {link}
+            END;
+
+            DROP TRIGGER IF EXISTS message_tags_au;
+            CREATE TRIGGER message_tags_au AFTER UPDATE OF tags, gmail_labels ON messages
+            WHEN old.tags IS NOT new.tags OR old.gmail_labels IS NOT new.gmail_labels
+            BEGIN
+                DELETE FROM message_tags WHERE message_id = old.id;
+                {link}
+            END;
+
+            DROP TRIGGER IF EXISTS message_tags_ad;
+            CREATE TRIGGER message_tags_ad AFTER DELETE ON messages BEGIN
+                DELETE FROM message_tags WHERE message_id = old.id;
+            END;
+            "#,
+            link = tag_link_sql("SELECT new.id AS id, new.tags AS tags, new.gmail_labels AS gmail_labels"),
+        );
+        sqlx::query(&triggers).execute(&self.pool).await?;
+
+        if !existed {
+            debug!("Migrating database: linking cached messages to tags");
+            sqlx::query(&tag_link_sql("SELECT id, tags, gmail_labels FROM messages"))
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Add the indexes behind [`Self::get_page`]: each list's messages in
+    /// display order, plus inbox folders by type. The unified inbox index
+    /// carries the folder and read state so a page is found without
+    /// touching the table until the rows to return are known.
+    async fn migrate_add_page_indexes(&self) -> CoreResult<()> {
+        for statement in [
+            "CREATE INDEX IF NOT EXISTS idx_folders_type ON folders(folder_type, id)",
+            "CREATE INDEX IF NOT EXISTS idx_messages_inbox_order
+             ON messages(date_epoch DESC, id DESC, folder_id, is_read)",
+            "CREATE INDEX IF NOT EXISTS idx_messages_folder_order
+             ON messages(folder_id, date_epoch DESC, uid DESC)",
+            "CREATE INDEX IF NOT EXISTS idx_messages_starred_order
+             ON messages(date_epoch DESC, id DESC) WHERE is_starred = 1",
+        ] {
+            sqlx::query(statement).execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    /// Add display_path column to folders if it doesn't exist
+    async fn migrate_add_folder_display_path(&self) -> CoreResult<()> {
+        let result = sqlx::query("SELECT display_path FROM folders LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await;
+
+        if result.is_err() {
+            debug!("Migrating database: adding display_path column to folders");
+            if let Err(e) = sqlx::query("ALTER TABLE folders ADD COLUMN display_path TEXT")
+                .execute(&self.pool)
+                .await
+            {
+                if !e.to_string().contains("duplicate column") {
+                    warn!("Migration error adding display_path column: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the full-text index, replacing one from before recipients and
+    /// body text were indexed
+    async fn migrate_fts_columns(&self) -> CoreResult<()> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'messages_fts'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let current = exists
+            && sqlx::query("SELECT body_text FROM messages_fts LIMIT 0")
+                .execute(&self.pool)
+                .await
+                .is_ok();
+        if current {
+            return Ok(());
+        }
+        // Reindexing every cached body takes a while on a large cache
+        let _migrating = Migrating::start();
+
+        if exists {
+            debug!("Migrating database: indexing recipients and body text");
+            sqlx::query(
+                r#"
+                DROP TRIGGER IF EXISTS messages_ai;
+                DROP TRIGGER IF EXISTS messages_ad;
+                DROP TRIGGER IF EXISTS messages_au;
+                DROP TABLE messages_fts;
+                "#,
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        sqlx::query(FTS_SCHEMA).execute(&self.pool).await?;
+        // Index what is already cached; a new database has nothing to index
+        sqlx::query("INSERT INTO messages_fts(messages_fts) VALUES ('rebuild')")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Rebuild FTS index to ensure all messages are indexed
+    /// This is needed because messages inserted before the FTS table existed won't be in the index
+    async fn migrate_rebuild_fts(&self) -> CoreResult<()> {
+        // Check if there are messages not in FTS by comparing counts
+        let msg_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&self.pool)
+            .await
+            .unwrap_or(0);
+
+        let fts_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages_fts")
+            .fetch_one(&self.pool)
+            .await
+            .unwrap_or(0);
+
+        if msg_count > fts_count {
+            info!(
+                "FTS index incomplete ({} messages, {} indexed). Rebuilding...",
+                msg_count, fts_count
+            );
+
+            // Rebuild FTS index by re-inserting all messages
+            // First delete all FTS entries
+            if let Err(e) = sqlx::query("DELETE FROM messages_fts")
+                .execute(&self.pool)
+                .await
+            {
+                warn!("Failed to clear FTS index: {}", e);
+            }
+
+            // Then repopulate from messages table
+            sqlx::query(
+                r#"
+                INSERT INTO messages_fts(rowid, subject, from_address, from_name,
+                                         to_addresses, cc_addresses, snippet, body_text)
+                SELECT id, subject, from_address, from_name,
+                       to_addresses, cc_addresses, snippet, body_text FROM messages
+                "#,
+            )
+            .execute(&self.pool)
+            .await?;
+
+            info!("FTS index rebuilt with {} messages", msg_count);
+        }
+
+        Ok(())
+    }
+
+    /// Insert or update an account
+    pub async fn upsert_account(&self, account: &crate::Account) -> CoreResult<()> {
+        let auth_method = serde_json::to_string(&account.auth_method)
+            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+        let config_json = serde_json::to_string(&account.config)
+            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, email_address, display_name, provider, auth_method, config_json)
             VALUES (?, ?, ?, ?, ?, ?)
-            ON CONFLICT(account_id, full_path) DO UPDATE SET
-                name = excluded.name,
-                folder_type = excluded.folder_type,
-                message_count = COALESCE(excluded.message_count, folders.message_count),
-                unread_count = COALESCE(excluded.unread_count, folders.unread_count),
+            ON CONFLICT(id) DO UPDATE SET
+                email_address = excluded.email_address,
+                display_name = excluded.display_name,
+                provider = excluded.provider,
+                auth_method = excluded.auth_method,
+                config_json = excluded.config_json,
                 updated_at = datetime('now')
-            RETURNING id
+            "#,
+        )
+        .bind(&account.id)
+        .bind(&account.email)
+        .bind(&account.display_name)
+        .bind(&account.provider)
+        .bind(&auth_method)
+        .bind(&config_json)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// TLS trust settings for an account: (custom CA file, pinned certificate fingerprint)
+    pub async fn get_account_tls(&self, account_id: &str) -> CoreResult<(Option<String>, Option<String>)> {
+        let row: Option<(Option<String>, Option<String>)> =
+            sqlx::query_as("SELECT tls_ca_file, tls_pinned_fingerprint FROM accounts WHERE id = ?")
+                .bind(account_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(row.unwrap_or_default())
+    }
+
+    /// Set or clear the PEM file of extra CA certificates trusted for an account
+    pub async fn set_account_ca_file(&self, account_id: &str, ca_file: Option<&str>) -> CoreResult<()> {
+        sqlx::query("UPDATE accounts SET tls_ca_file = ? WHERE id = ?")
+            .bind(ca_file)
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Pin (or unpin) the server certificate for an account by SHA-256 fingerprint
+    pub async fn set_account_pinned_fingerprint(
+        &self,
+        account_id: &str,
+        fingerprint: Option<&str>,
+    ) -> CoreResult<()> {
+        sqlx::query("UPDATE accounts SET tls_pinned_fingerprint = ? WHERE id = ?")
+            .bind(fingerprint)
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Proxy override for an account: `None` to use the default proxy,
+    /// an empty string to connect directly, otherwise a proxy URL
+    pub async fn get_account_proxy(&self, account_id: &str) -> CoreResult<Option<String>> {
+        let row: Option<(Option<String>,)> = sqlx::query_as("SELECT proxy_url FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.and_then(|(proxy,)| proxy))
+    }
+
+    /// Set or clear an account's proxy override (see [`Self::get_account_proxy`])
+    pub async fn set_account_proxy(&self, account_id: &str, proxy_url: Option<&str>) -> CoreResult<()> {
+        sqlx::query("UPDATE accounts SET proxy_url = ? WHERE id = ?")
+            .bind(proxy_url)
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// An account's cache retention settings
+    pub async fn get_account_retention(&self, account_id: &str) -> CoreResult<RetentionPolicy> {
+        let row: Option<(Option<i64>, Option<i64>)> = sqlx::query_as(
+            "SELECT retention_body_days, retention_max_bytes FROM accounts WHERE id = ?",
+        )
+        .bind(account_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(retention_policy).unwrap_or_default())
+    }
+
+    /// Retention settings of every account that limits its cache
+    pub async fn get_limited_retentions(&self) -> CoreResult<Vec<(String, RetentionPolicy)>> {
+        let rows: Vec<(String, Option<i64>, Option<i64>)> = sqlx::query_as(
+            "SELECT id, retention_body_days, retention_max_bytes FROM accounts \
+             WHERE retention_body_days IS NOT NULL OR retention_max_bytes IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, days, bytes)| (id, retention_policy((days, bytes))))
+            .collect())
+    }
+
+    /// Set an account's cache retention settings
+    pub async fn set_account_retention(&self, account_id: &str, policy: &RetentionPolicy) -> CoreResult<()> {
+        sqlx::query("UPDATE accounts SET retention_body_days = ?, retention_max_bytes = ? WHERE id = ?")
+            .bind(policy.body_days.map(i64::from))
+            .bind(policy.max_bytes.map(|bytes| bytes.min(i64::MAX as u64) as i64))
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// An account's Trash and Junk auto-empty settings
+    pub async fn get_account_auto_empty(&self, account_id: &str) -> CoreResult<AutoEmptyPolicy> {
+        let row: Option<(Option<i64>, Option<i64>)> = sqlx::query_as(
+            "SELECT auto_empty_trash_days, auto_empty_junk_days FROM accounts WHERE id = ?",
+        )
+        .bind(account_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(auto_empty_policy).unwrap_or_default())
+    }
+
+    /// Auto-empty settings of every account that empties Trash or Junk
+    pub async fn get_auto_empty_policies(&self) -> CoreResult<Vec<(String, AutoEmptyPolicy)>> {
+        let rows: Vec<(String, Option<i64>, Option<i64>)> = sqlx::query_as(
+            "SELECT id, auto_empty_trash_days, auto_empty_junk_days FROM accounts \
+             WHERE auto_empty_trash_days IS NOT NULL OR auto_empty_junk_days IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, trash, junk)| (id, auto_empty_policy((trash, junk))))
+            .collect())
+    }
+
+    /// Set an account's Trash and Junk auto-empty settings
+    pub async fn set_account_auto_empty(&self, account_id: &str, policy: &AutoEmptyPolicy) -> CoreResult<()> {
+        sqlx::query("UPDATE accounts SET auto_empty_trash_days = ?, auto_empty_junk_days = ? WHERE id = ?")
+            .bind(policy.trash_days.map(i64::from))
+            .bind(policy.junk_days.map(i64::from))
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Every account's sending identities, by account then address
+    pub async fn get_identities(&self) -> CoreResult<Vec<Identity>> {
+        let rows: Vec<IdentityRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM identities ORDER BY account_id, email COLLATE NOCASE",
+            IDENTITY_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(identity_from_row).collect())
+    }
+
+    /// The identity of an account that sends from `email`
+    pub async fn identity_by_email(&self, account_id: &str, email: &str) -> CoreResult<Option<Identity>> {
+        let row: Option<IdentityRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM identities WHERE account_id = ? AND email = ? COLLATE NOCASE",
+            IDENTITY_COLUMNS
+        ))
+        .bind(account_id)
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(identity_from_row))
+    }
+
+    /// Add an identity (`id` 0) or update one, returning its ID
+    pub async fn save_identity(&self, identity: &Identity) -> CoreResult<i64> {
+        let smtp = identity.smtp.as_ref();
+        let signature = identity.signature.as_ref();
+        let image = signature.and_then(|s| s.image.as_ref());
+        let query = if identity.id == 0 {
+            sqlx::query(
+                "INSERT INTO identities (account_id, name, email, signature, signature_html, signature_image, \
+                 signature_image_type, smtp_host, smtp_port, smtp_username) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+        } else {
+            sqlx::query(
+                "UPDATE identities SET account_id = ?, name = ?, email = ?, signature = ?, signature_html = ?, \
+                 signature_image = ?, signature_image_type = ?, smtp_host = ?, smtp_port = ?, smtp_username = ? \
+                 WHERE id = ?",
+            )
+        };
+        let query = query
+            .bind(&identity.account_id)
+            .bind(&identity.name)
+            .bind(identity.email.trim())
+            .bind(signature.map(|s| s.text.as_str()).filter(|text| !text.is_empty()))
+            .bind(signature.and_then(|s| s.html.as_deref()))
+            .bind(image.map(|i| i.data.as_slice()))
+            .bind(image.map(|i| i.mime_type.as_str()))
+            .bind(smtp.map(|s| s.host.as_str()))
+            .bind(smtp.map(|s| i64::from(s.port)))
+            .bind(smtp.map(|s| s.username.as_str()));
+        if identity.id == 0 {
+            Ok(query.execute(&self.pool).await?.last_insert_rowid())
+        } else {
+            query.bind(identity.id).execute(&self.pool).await?;
+            Ok(identity.id)
+        }
+    }
+
+    pub async fn delete_identity(&self, id: i64) -> CoreResult<()> {
+        sqlx::query("DELETE FROM identities WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Cached messages of an account's Trash and Junk folders that `policy`
+    /// has due for deletion at `now`, by folder
+    pub async fn get_auto_empty_expired(
+        &self,
+        account_id: &str,
+        policy: &AutoEmptyPolicy,
+        now: i64,
+    ) -> CoreResult<Vec<ExpiredMessages>> {
+        let folders: Vec<(i64, String, String)> = sqlx::query_as(
+            "SELECT id, full_path, folder_type FROM folders \
+             WHERE account_id = ? AND folder_type IN ('trash', 'spam')",
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut expired = Vec::new();
+        for (folder_id, full_path, folder_type) in folders {
+            let Some(cutoff) = policy.cutoff(&folder_type, now) else {
+                continue;
+            };
+            let uids: Vec<i64> = sqlx::query_scalar(
+                "SELECT uid FROM messages WHERE folder_id = ? AND created_at < datetime(?, 'unixepoch') \
+                 ORDER BY uid",
+            )
+            .bind(folder_id)
+            .bind(cutoff)
+            .fetch_all(&self.pool)
+            .await?;
+            let uids: Vec<u32> = uids.into_iter().filter_map(|uid| u32::try_from(uid).ok()).collect();
+            if !uids.is_empty() {
+                expired.push(ExpiredMessages {
+                    folder_id,
+                    full_path,
+                    uids,
+                });
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Get all accounts
+    pub async fn get_accounts(&self) -> CoreResult<Vec<crate::Account>> {
+        #[derive(sqlx::FromRow)]
+        struct AccountRow {
+            id: String,
+            email_address: String,
+            display_name: Option<String>,
+            provider: String,
+            auth_method: String,
+            config_json: String,
+        }
+
+        let rows: Vec<AccountRow> =
+            sqlx::query_as("SELECT id, email_address, display_name, provider, auth_method, config_json FROM accounts")
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut accounts = Vec::new();
+        for row in rows {
+            let auth_method: northmail_auth::AuthMethod = serde_json::from_str(&row.auth_method)
+                .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+            let config: crate::AccountConfig = serde_json::from_str(&row.config_json)
+                .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+
+            accounts.push(crate::Account {
+                id: row.id,
+                email: row.email_address,
+                display_name: row.display_name,
+                provider: row.provider,
+                auth_method,
+                config,
+            });
+        }
+
+        Ok(accounts)
+    }
+
+    /// Delete an account
+    pub async fn delete_account(&self, account_id: &str) -> CoreResult<()> {
+        sqlx::query("DELETE FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        self.finish_purge().await?;
+        Ok(())
+    }
+
+    /// Remove everything the cache holds for an account: its mail a folder
+    /// at a time, reporting each to `progress`, then its outbox and rules
+    /// and the account itself, with what cascades from it. The recipient
+    /// suggestions are reset, to be indexed again from the mail that
+    /// remains, as they don't record which account an address came from.
+    /// See [`crate::purge`] for removing the rest of the account.
+    pub async fn purge_account(
+        &self,
+        account_id: &str,
+        mut progress: impl FnMut(PurgeProgress) + Send,
+    ) -> CoreResult<PurgedAccount> {
+        let email: String = sqlx::query_scalar("SELECT email_address FROM accounts WHERE id = ?")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+        let identities: Vec<(String, bool)> =
+            sqlx::query_as("SELECT email, smtp_host IS NOT NULL FROM identities WHERE account_id = ?")
+                .bind(account_id)
+                .fetch_all(&self.pool)
+                .await?;
+        let others: Vec<String> = sqlx::query_scalar(
+            "SELECT email_address FROM accounts WHERE id != ?1 \
+             UNION SELECT email FROM identities WHERE account_id != ?1",
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let folders: Vec<i64> = sqlx::query_scalar("SELECT id FROM folders WHERE account_id = ?")
+            .bind(account_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut messages = 0;
+        for (done, folder_id) in folders.iter().enumerate() {
+            progress(PurgeProgress::new(PurgeStage::Mail, done, folders.len()));
+            messages += sqlx::query("DELETE FROM messages WHERE folder_id = ?")
+                .bind(folder_id)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+        }
+
+        progress(PurgeProgress::new(PurgeStage::Records, 0, 1));
+        let mut tx = self.pool.begin().await?;
+        for statement in [
+            "DELETE FROM outbox WHERE account_id = ?",
+            "DELETE FROM rules WHERE account_id = ?",
+            "DELETE FROM accounts WHERE id = ?",
+        ] {
+            sqlx::query(statement).bind(account_id).execute(&mut *tx).await?;
+        }
+        sqlx::query(
+            "DELETE FROM recipients; DELETE FROM recipient_messages; \
+             UPDATE recipients_indexed SET last_message_id = 0 WHERE id = 1",
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.finish_purge().await?;
+
+        info!("Purged {} messages of account {} from the cache", messages, email);
+        Ok(PurgedAccount {
+            secret_addresses: purge::secret_addresses(&email, &identities, &others),
+            email,
+            messages,
+        })
+    }
+
+    /// Insert or update a folder
+    pub async fn upsert_folder(
+        &self,
+        account_id: &str,
+        name: &str,
+        full_path: &str,
+        folder_type: &str,
+    ) -> CoreResult<i64> {
+        self.upsert_folder_with_counts(account_id, name, full_path, folder_type, None, None)
+            .await
+    }
+
+    /// Insert or update a folder with message/unread counts
+    pub async fn upsert_folder_with_counts(
+        &self,
+        account_id: &str,
+        name: &str,
+        full_path: &str,
+        folder_type: &str,
+        message_count: Option<i64>,
+        unread_count: Option<i64>,
+    ) -> CoreResult<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO folders (account_id, name, full_path, folder_type, message_count, unread_count)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(account_id, full_path) DO UPDATE SET
+                name = excluded.name,
+                folder_type = excluded.folder_type,
+                message_count = COALESCE(excluded.message_count, folders.message_count),
+                unread_count = COALESCE(excluded.unread_count, folders.unread_count),
+                updated_at = datetime('now')
+            RETURNING id
+            "#,
+        )
+        .bind(account_id)
+        .bind(name)
+        .bind(full_path)
+        .bind(folder_type)
+        .bind(message_count)
+        .bind(unread_count)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let folder_id = result.get::<i64, _>("id");
+        if message_count.is_some() || unread_count.is_some() {
+            self.changes.send(Change::FolderCountsChanged {
+                folder_ids: vec![folder_id],
+            });
+        }
+        Ok(folder_id)
+    }
+
+    /// Get folders for an account
+    pub async fn get_folders(&self, account_id: &str) -> CoreResult<Vec<DbFolder>> {
+        let folders = sqlx::query_as::<_, DbFolder>(
+            "SELECT id, account_id, name, full_path, folder_type, uidvalidity, uid_next, message_count, unread_count, is_selectable, is_subscribed, unified_inbox, COALESCE(display_path, full_path) AS display_path FROM folders WHERE account_id = ? ORDER BY folder_type, name",
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(folders)
+    }
+
+    /// Update folder sync state
+    pub async fn update_folder_sync(
+        &self,
+        folder_id: i64,
+        uidvalidity: i64,
+        uid_next: i64,
+        message_count: i64,
+        unread_count: i64,
+    ) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE folders SET
+                uidvalidity = ?,
+                uid_next = ?,
+                message_count = ?,
+                unread_count = ?,
+                updated_at = datetime('now')
+            WHERE id = ?
+            "#,
+        )
+        .bind(uidvalidity)
+        .bind(uid_next)
+        .bind(message_count)
+        .bind(unread_count)
+        .bind(folder_id)
+        .execute(&self.pool)
+        .await?;
+
+        self.changes.send(Change::FolderCountsChanged {
+            folder_ids: vec![folder_id],
+        });
+        Ok(())
+    }
+
+    /// Upsert a folder with Graph API folder ID
+    pub async fn upsert_folder_graph(
+        &self,
+        account_id: &str,
+        name: &str,
+        full_path: &str,
+        folder_type: &str,
+        message_count: Option<i64>,
+        unread_count: Option<i64>,
+        graph_folder_id: &str,
+    ) -> CoreResult<i64> {
+        let id = self
+            .upsert_folder_with_counts(
+                account_id,
+                name,
+                full_path,
+                folder_type,
+                message_count,
+                unread_count,
+            )
+            .await?;
+
+        // Update the graph_folder_id
+        sqlx::query("UPDATE folders SET graph_folder_id = ? WHERE id = ?")
+            .bind(graph_folder_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Get graph_message_id for a message by folder_id and uid
+    pub async fn get_graph_message_id(&self, folder_id: i64, uid: i64) -> CoreResult<Option<String>> {
+        let result = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT graph_message_id FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.flatten())
+    }
+
+    /// Get graph_folder_id for a folder by its DB id
+    pub async fn get_graph_folder_id(&self, folder_id: i64) -> CoreResult<Option<String>> {
+        let result = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT graph_folder_id FROM folders WHERE id = ?",
+        )
+        .bind(folder_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.flatten())
+    }
+
+    /// Get the Graph API delta link that returns a folder's changes since
+    /// its last sync
+    pub async fn get_graph_delta_link(&self, folder_id: i64) -> CoreResult<Option<String>> {
+        let result = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT graph_delta_link FROM folders WHERE id = ?",
+        )
+        .bind(folder_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.flatten())
+    }
+
+    /// Store (or with `None`, forget) a folder's Graph API delta link
+    pub async fn set_graph_delta_link(&self, folder_id: i64, delta_link: Option<&str>) -> CoreResult<()> {
+        sqlx::query("UPDATE folders SET graph_delta_link = ? WHERE id = ?")
+            .bind(delta_link)
+            .bind(folder_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete messages of a folder by Graph message ID, returning the UIDs
+    /// they had
+    pub async fn delete_messages_by_graph_ids(
+        &self,
+        folder_id: i64,
+        graph_message_ids: &[String],
+    ) -> CoreResult<Vec<i64>> {
+        let mut uids = Vec::new();
+        for graph_message_id in graph_message_ids {
+            let uid: Option<i64> = sqlx::query_scalar(
+                "DELETE FROM messages WHERE folder_id = ? AND graph_message_id = ? RETURNING uid",
+            )
+            .bind(folder_id)
+            .bind(graph_message_id)
+            .fetch_optional(&self.pool)
+            .await?;
+            uids.extend(uid);
+        }
+        Ok(uids)
+    }
+
+    /// Upsert a message with a Graph message ID
+    pub async fn upsert_message_graph(
+        &self,
+        folder_id: i64,
+        msg: &DbMessage,
+        graph_message_id: &str,
+    ) -> CoreResult<i64> {
+        let id = self.upsert_message(folder_id, msg).await?;
+
+        sqlx::query("UPDATE messages SET graph_message_id = ? WHERE id = ?")
+            .bind(graph_message_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Batch upsert messages with Graph message IDs
+    pub async fn upsert_messages_batch_graph(
+        &self,
+        folder_id: i64,
+        messages: &[(DbMessage, String)], // (message, graph_message_id)
+    ) -> CoreResult<usize> {
+        let mut count = 0;
+
+        // Process in chunks to avoid holding write lock too long
+        for chunk in messages.chunks(50) {
+            let mut tx = self.pool.begin().await?;
+
+            for (msg, graph_id) in chunk {
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO messages (
+                        folder_id, uid, message_id, subject, from_address, from_name,
+                        to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
+                        has_attachments, size, maildir_path, graph_message_id
+                    )
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(folder_id, uid) DO UPDATE SET
+                        message_id = excluded.message_id,
+                        subject = excluded.subject,
+                        from_address = excluded.from_address,
+                        from_name = excluded.from_name,
+                        to_addresses = excluded.to_addresses,
+                        cc_addresses = excluded.cc_addresses,
+                        date_sent = excluded.date_sent,
+                        date_epoch = excluded.date_epoch,
+                        snippet = excluded.snippet,
+                        is_read = excluded.is_read,
+                        is_starred = excluded.is_starred,
+                        has_attachments = excluded.has_attachments,
+                        size = excluded.size,
+                        maildir_path = excluded.maildir_path,
+                        graph_message_id = excluded.graph_message_id,
+                        thread_key = CASE WHEN excluded.subject IS messages.subject
+                            THEN messages.thread_key END,
+                        updated_at = datetime('now')
+                    "#,
+                )
+                .bind(folder_id)
+                .bind(msg.uid)
+                .bind(&msg.message_id)
+                .bind(&msg.subject)
+                .bind(&msg.from_address)
+                .bind(&msg.from_name)
+                .bind(&msg.to_addresses)
+                .bind(&msg.cc_addresses)
+                .bind(&msg.date_sent)
+                .bind(msg.date_epoch)
+                .bind(&msg.snippet)
+                .bind(msg.is_read)
+                .bind(msg.is_starred)
+                .bind(msg.has_attachments)
+                .bind(msg.size)
+                .bind(&msg.maildir_path)
+                .bind(graph_id)
+                .execute(&mut *tx)
+                .await;
+
+                match result {
+                    Ok(_) => count += 1,
+                    Err(e) => {
+                        tracing::warn!("Failed to upsert graph message uid={}: {}", msg.uid, e);
+                    }
+                }
+            }
+
+            tx.commit().await?;
+        }
+
+        if count > 0 {
+            self.changes.send(Change::MessagesInserted {
+                folder_id,
+                count: count as u64,
+            });
+        }
+        Ok(count)
+    }
+
+    /// Look up the Graph message ID for a message by its UID hash
+    pub async fn get_graph_message_id_by_uid(&self, uid: i64) -> CoreResult<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            "SELECT graph_message_id FROM messages WHERE uid = ? AND graph_message_id IS NOT NULL LIMIT 1",
+        )
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| r.0))
+    }
+
+    /// Look up the Graph message ID for a message by account, folder, and UID
+    pub async fn get_graph_message_id_for_folder_uid(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+        uid: i64,
+    ) -> CoreResult<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"
+            SELECT m.graph_message_id FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND f.full_path = ? AND m.uid = ?
+            AND m.graph_message_id IS NOT NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder_path)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| r.0))
+    }
+
+    /// Insert or update messages in a batch (wrapped in a transaction for performance)
+    pub async fn upsert_messages_batch(
+        &self,
+        folder_id: i64,
+        messages: &[DbMessage],
+    ) -> CoreResult<usize> {
+        let mut count = 0;
+
+        // Process in chunks to avoid holding write lock too long
+        for chunk in messages.chunks(50) {
+            let mut tx = self.pool.begin().await?;
+
+            for msg in chunk {
+                let result = sqlx::query(
+                    r#"
+                    INSERT INTO messages (
+                        folder_id, uid, message_id, subject, from_address, from_name,
+                        to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
+                        has_attachments, size, maildir_path, gmail_labels, gmail_thread_id, tags
+                    )
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(folder_id, uid) DO UPDATE SET
+                        message_id = excluded.message_id,
+                        subject = excluded.subject,
+                        from_address = excluded.from_address,
+                        from_name = excluded.from_name,
+                        to_addresses = excluded.to_addresses,
+                        cc_addresses = excluded.cc_addresses,
+                        date_sent = excluded.date_sent,
+                        date_epoch = excluded.date_epoch,
+                        snippet = excluded.snippet,
+                        is_read = excluded.is_read,
+                        is_starred = excluded.is_starred,
+                        has_attachments = excluded.has_attachments,
+                        size = excluded.size,
+                        maildir_path = excluded.maildir_path,
+                        gmail_labels = COALESCE(excluded.gmail_labels, messages.gmail_labels),
+                        gmail_thread_id = COALESCE(excluded.gmail_thread_id, messages.gmail_thread_id),
+                        tags = COALESCE(excluded.tags, messages.tags),
+                        thread_key = CASE WHEN excluded.subject IS messages.subject
+                            AND COALESCE(excluded.gmail_thread_id, messages.gmail_thread_id) IS messages.gmail_thread_id
+                            THEN messages.thread_key END,
+                        updated_at = datetime('now')
+                    "#,
+                )
+                .bind(folder_id)
+                .bind(msg.uid)
+                .bind(&msg.message_id)
+                .bind(&msg.subject)
+                .bind(&msg.from_address)
+                .bind(&msg.from_name)
+                .bind(&msg.to_addresses)
+                .bind(&msg.cc_addresses)
+                .bind(&msg.date_sent)
+                .bind(msg.date_epoch)
+                .bind(&msg.snippet)
+                .bind(msg.is_read)
+                .bind(msg.is_starred)
+                .bind(msg.has_attachments)
+                .bind(msg.size)
+                .bind(&msg.maildir_path)
+                .bind(&msg.gmail_labels)
+                .bind(msg.gmail_thread_id)
+                .bind(&msg.tags)
+                .execute(&mut *tx)
+                .await;
+
+                match result {
+                    Ok(_) => count += 1,
+                    Err(e) => {
+                        tracing::warn!("Failed to upsert message uid={}: {}", msg.uid, e);
+                    }
+                }
+            }
+
+            tx.commit().await?;
+        }
+
+        if count > 0 {
+            self.changes.send(Change::MessagesInserted {
+                folder_id,
+                count: count as u64,
+            });
+        }
+        Ok(count)
+    }
+
+    /// Insert or update a message
+    pub async fn upsert_message(&self, folder_id: i64, msg: &DbMessage) -> CoreResult<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO messages (
+                folder_id, uid, message_id, subject, from_address, from_name,
+                to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
+                has_attachments, size, maildir_path, gmail_labels, gmail_thread_id, tags
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(folder_id, uid) DO UPDATE SET
+                message_id = excluded.message_id,
+                subject = excluded.subject,
+                from_address = excluded.from_address,
+                from_name = excluded.from_name,
+                to_addresses = excluded.to_addresses,
+                cc_addresses = excluded.cc_addresses,
+                date_sent = excluded.date_sent,
+                date_epoch = excluded.date_epoch,
+                snippet = excluded.snippet,
+                is_read = excluded.is_read,
+                is_starred = excluded.is_starred,
+                has_attachments = excluded.has_attachments,
+                size = excluded.size,
+                maildir_path = excluded.maildir_path,
+                gmail_labels = COALESCE(excluded.gmail_labels, messages.gmail_labels),
+                gmail_thread_id = COALESCE(excluded.gmail_thread_id, messages.gmail_thread_id),
+                tags = COALESCE(excluded.tags, messages.tags),
+                thread_key = CASE WHEN excluded.subject IS messages.subject
+                    AND COALESCE(excluded.gmail_thread_id, messages.gmail_thread_id) IS messages.gmail_thread_id
+                    THEN messages.thread_key END,
+                updated_at = datetime('now')
+            RETURNING id
+            "#,
+        )
+        .bind(folder_id)
+        .bind(msg.uid)
+        .bind(&msg.message_id)
+        .bind(&msg.subject)
+        .bind(&msg.from_address)
+        .bind(&msg.from_name)
+        .bind(&msg.to_addresses)
+        .bind(&msg.cc_addresses)
+        .bind(&msg.date_sent)
+        .bind(msg.date_epoch)
+        .bind(&msg.snippet)
+        .bind(msg.is_read)
+        .bind(msg.is_starred)
+        .bind(msg.has_attachments)
+        .bind(msg.size)
+        .bind(&msg.maildir_path)
+        .bind(&msg.gmail_labels)
+        .bind(msg.gmail_thread_id)
+        .bind(&msg.tags)
+        .fetch_one(&self.pool)
+        .await?;
+
+        self.changes.send(Change::MessagesInserted { folder_id, count: 1 });
+        Ok(result.get::<i64, _>("id"))
+    }
+
+    /// Get a page of messages for a folder, newest first: the first page for
+    /// `None`, else the one after the cursor from the last message shown
+    pub async fn get_messages(
+        &self,
+        folder_id: i64,
+        limit: i64,
+        after: Option<PageCursor>,
+    ) -> CoreResult<Vec<DbMessage>> {
+        self.get_page(PageScope::Folder(folder_id), limit, after, &MessageFilter::default())
+            .await
+    }
+
+    /// Get message body by folder and UID
+    pub async fn get_message_body(
+        &self,
+        folder_id: i64,
+        uid: i64,
+    ) -> CoreResult<Option<(Option<String>, Option<String>)>> {
+        let result = sqlx::query(
+            "SELECT body_text, body_html FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|row| {
+            (
+                row.get::<Option<String>, _>("body_text"),
+                row.get::<Option<String>, _>("body_html"),
+            )
+        }))
+    }
+
+    /// Save message body
+    pub async fn save_message_body(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        body_text: Option<&str>,
+        body_html: Option<&str>,
+    ) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE messages
+            SET body_text = ?, body_html = ?, updated_at = datetime('now')
+            WHERE folder_id = ? AND uid = ?
+            "#,
+        )
+        .bind(body_text)
+        .bind(body_html)
+        .bind(folder_id)
+        .bind(uid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get attachment metadata for a message
+    pub async fn get_message_attachments(
+        &self,
+        folder_id: i64,
+        uid: i64,
+    ) -> CoreResult<Vec<AttachmentMetadata>> {
+        // First get the message id
+        let msg_id: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some((message_id,)) = msg_id else {
+            return Ok(Vec::new());
+        };
+
+        let attachments = sqlx::query_as::<_, AttachmentMetadata>(
+            "SELECT id, message_id, filename, mime_type, size, content_id, is_inline, data FROM attachments WHERE message_id = ?",
+        )
+        .bind(message_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(attachments)
+    }
+
+    /// Store downloaded data for an attachment that was cached without it
+    pub async fn save_attachment_data(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        filename: &str,
+        data: &[u8],
+    ) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE attachments SET data = ?
+            WHERE filename = ? AND data IS NULL
+              AND message_id = (SELECT id FROM messages WHERE folder_id = ? AND uid = ?)
+            "#,
+        )
+        .bind(data)
+        .bind(filename)
+        .bind(folder_id)
+        .bind(uid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Save attachment metadata for a message (replaces existing)
+    pub async fn save_message_attachments(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        attachments: &[AttachmentInfo],
+    ) -> CoreResult<()> {
+        // First get the message id
+        let msg_id: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some((message_id,)) = msg_id else {
+            return Ok(());
+        };
+
+        // Delete existing attachments
+        sqlx::query("DELETE FROM attachments WHERE message_id = ?")
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+
+        // Insert new attachments
+        for att in attachments {
+            sqlx::query(
+                r#"
+                INSERT INTO attachments (message_id, filename, mime_type, size, content_id, is_inline, data)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(message_id)
+            .bind(&att.filename)
+            .bind(&att.mime_type)
+            .bind(att.size as i64)
+            .bind(&att.content_id)
+            .bind(att.is_inline)
+            .bind(if att.data.is_empty() { None } else { Some(&att.data) })
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get message UIDs that need body prefetch (no cached body, within last N days)
+    /// Returns (uid, is_unread) pairs, prioritizing unread messages
+    pub async fn get_messages_needing_body_prefetch(
+        &self,
+        folder_id: i64,
+        days: i64,
+        limit: i64,
+    ) -> CoreResult<Vec<(i64, bool)>> {
+        let cutoff_epoch = chrono::Utc::now().timestamp() - (days * 24 * 60 * 60);
+
+        let results: Vec<(i64, bool)> = sqlx::query_as(
+            r#"
+            SELECT uid, is_read = 0 as is_unread
+            FROM messages
+            WHERE folder_id = ?
+              AND (body_text IS NULL AND body_html IS NULL)
+              AND (date_epoch IS NULL OR date_epoch >= ?)
+            ORDER BY is_read ASC, date_epoch DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(folder_id)
+        .bind(cutoff_epoch)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(results)
+    }
+
+    /// Messages of a folder not fully cached: with no body, or with
+    /// attachments whose data is still on the server (see
+    /// [`crate::offline`])
+    pub async fn get_offline_candidates(&self, folder_id: i64) -> CoreResult<Vec<OfflineCandidate>> {
+        let rows: Vec<(i64, Option<i64>, i64)> = sqlx::query_as(
+            r#"
+            SELECT m.uid, m.date_epoch, COALESCE(m.size, 0)
+            FROM messages m
+            WHERE m.folder_id = ?
+              AND ((m.body_text IS NULL AND m.body_html IS NULL)
+                   OR EXISTS (SELECT 1 FROM attachments a WHERE a.message_id = m.id AND a.data IS NULL)
+                   OR (m.has_attachments = 1
+                       AND NOT EXISTS (SELECT 1 FROM attachments a WHERE a.message_id = m.id)))
+            "#,
+        )
+        .bind(folder_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(uid, date_epoch, size)| OfflineCandidate {
+                uid: uid as u32,
+                date_epoch,
+                size: size.max(0) as u64,
+            })
+            .collect())
+    }
+
+    /// Export a folder into the Maildir at `path` (see [`crate::maildir`]).
+    /// The cache only keeps decoded bodies, so each message is fetched whole
+    /// over IMAP with `client`. Messages the server no longer has, or all of
+    /// them without a client, are rebuilt from the cache if their body is
+    /// cached and counted as missing if not. Messages an earlier export
+    /// wrote are skipped, so an export that stopped part way can simply be
+    /// run again.
+    pub async fn export_maildir(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+        path: &Path,
+        mut client: Option<&mut ImapClient>,
+    ) -> CoreResult<ExportCounts> {
+        let folder = self
+            .get_folder_by_path(account_id, folder_path)
+            .await?
+            .ok_or_else(|| CoreError::FolderNotFound(folder_path.to_string()))?;
+        maildir::create_dirs(path)?;
+        let exported = maildir::exported_names(path)?;
+
+        let messages: Vec<(i64, Option<i64>, bool, bool)> = sqlx::query_as(
+            "SELECT uid, date_epoch, is_read, is_starred FROM messages WHERE folder_id = ? ORDER BY uid",
+        )
+        .bind(folder.id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if let Some(client) = client.as_deref_mut() {
+            client.examine(folder_path).await?;
+        }
+
+        let boundary = format!("northmail-{}", uuid::Uuid::new_v4().simple());
+        let mut counts = ExportCounts::default();
+        for (uid, date_epoch, is_read, is_starred) in messages {
+            let unique = maildir::unique_name(date_epoch, uid);
+            if exported.contains(&unique) {
+                counts.skipped += 1;
+                continue;
+            }
+
+            let mut data = match client.as_deref_mut() {
+                Some(client) => maildir::to_unix_line_endings(&client.fetch_raw(uid as u32).await?),
+                None => Vec::new(),
+            };
+            if data.is_empty() {
+                match self.rebuild_cached_message(folder.id, uid, &boundary).await? {
+                    Some(rebuilt) => {
+                        data = rebuilt;
+                        counts.rebuilt += 1;
+                    }
+                    None => {
+                        counts.missing += 1;
+                        continue;
+                    }
+                }
+            }
+
+            maildir::deliver(path, &maildir::file_name(&unique, is_read, is_starred), &data)?;
+            counts.exported += 1;
+        }
+
+        Ok(counts)
+    }
+
+    /// A message rebuilt from what the cache has of it, if its body is
+    /// cached. Attachments whose data was never downloaded are left out.
+    async fn rebuild_cached_message(&self, folder_id: i64, uid: i64, boundary: &str) -> CoreResult<Option<Vec<u8>>> {
+        let message = sqlx::query_as::<_, DbMessage>(
+            r#"
+            SELECT id, folder_id, uid, message_id, subject, from_address, from_name,
+                   to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read,
+                   is_starred, has_attachments, size, maildir_path, body_text, body_html
+            FROM messages WHERE folder_id = ? AND uid = ?
+            "#,
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(message) = message.filter(|m| m.body_text.is_some() || m.body_html.is_some()) else {
+            return Ok(None);
+        };
+
+        let attachments = self.get_message_attachments(folder_id, uid).await?;
+        let cached = CachedMessage {
+            message_id: message.message_id.as_deref(),
+            date: message.date_sent.as_deref(),
+            date_epoch: message.date_epoch,
+            from_name: message.from_name.as_deref(),
+            from_address: message.from_address.as_deref(),
+            to: message.to_addresses.as_deref(),
+            cc: message.cc_addresses.as_deref(),
+            subject: message.subject.as_deref(),
+            body_text: message.body_text.as_deref(),
+            body_html: message.body_html.as_deref(),
+            attachments: attachments
+                .iter()
+                .filter_map(|a| {
+                    Some(CachedAttachment {
+                        filename: &a.filename,
+                        mime_type: &a.mime_type,
+                        data: a.data.as_deref()?,
+                    })
+                })
+                .collect(),
+        };
+        Ok(Some(maildir::rebuild_message(&cached, boundary)))
+    }
+
+    /// A message's RFC 822 source, for saving as an `.eml` file: from the
+    /// server over `client`, or rebuilt from the cache when there's no
+    /// client, the server no longer has it, or it's in a local folder.
+    /// `None` if neither has it.
+    pub async fn message_source(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+        uid: u32,
+        client: Option<&mut ImapClient>,
+    ) -> CoreResult<Option<MessageSource>> {
+        let folder = self
+            .get_folder_by_path(account_id, folder_path)
+            .await?
+            .ok_or_else(|| CoreError::FolderNotFound(folder_path.to_string()))?;
+
+        if let Some(client) = client.filter(|_| folder.folder_type != LOCAL_FOLDER_TYPE) {
+            client.examine(folder_path).await?;
+            let data = client.fetch_raw(uid).await?;
+            if !data.is_empty() {
+                return Ok(Some(MessageSource { data, rebuilt: false }));
+            }
+        }
+
+        let boundary = format!("northmail-{}", uuid::Uuid::new_v4().simple());
+        Ok(self
+            .rebuild_cached_message(folder.id, uid as i64, &boundary)
+            .await?
+            .map(|data| MessageSource { data, rebuilt: true }))
+    }
+
+    /// Full-text search of subject, sender, recipients, snippet and cached
+    /// body text within `scope`, newest first. Each word of `query` matches
+    /// as a prefix; operators, phrases and boolean terms are compiled by
+    /// [`SearchQuery`].
+    pub async fn search_messages(
+        &self,
+        query: &str,
+        scope: SearchScope<'_>,
+        limit: i64,
+    ) -> CoreResult<Vec<DbMessage>> {
+        let Some(parsed) = SearchQuery::parse(query) else {
+            return Ok(Vec::new());
+        };
+        if !parsed.is_plain_words() {
+            return self.search_messages_parsed(&parsed, scope, limit).await;
+        }
+
+        let fts_query = prepare_fts_query(query);
+        debug!("FTS search: '{}' -> '{}' ({:?})", query, fts_query, scope);
+
+        if fts_query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (condition, bind) = scope.condition();
+        let query_str = format!(
+            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
+                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, {snippet},
+                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
+                   m.body_text, m.body_html, m.gmail_labels, m.gmail_thread_id, m.mention, m.tags,
+                   m.snoozed_until, m.reply_later_at
+            FROM messages m
+            JOIN messages_fts fts ON m.id = fts.rowid
+            WHERE messages_fts MATCH ? AND {}
+            ORDER BY m.date_epoch DESC
+            LIMIT ?"#,
+            condition,
+            snippet = self.snippet_column(),
+        );
+        let mut query = sqlx::query_as::<_, DbMessage>(&query_str).bind(&fts_query);
+        query = match bind {
+            Some(SearchBind::Id(id)) => query.bind(id),
+            Some(SearchBind::Text(text)) => query.bind(text),
+            None => query,
+        };
+        let messages = query.bind(limit).fetch_all(&self.pool).await?;
+
+        Ok(messages)
+    }
+
+    /// Search with a query using operators: its compiled condition selects
+    /// the messages instead of a join on the full-text index
+    async fn search_messages_parsed(
+        &self,
+        parsed: &SearchQuery,
+        scope: SearchScope<'_>,
+        limit: i64,
+    ) -> CoreResult<Vec<DbMessage>> {
+        let offset = *chrono::Local::now().offset();
+        let filter = parsed.to_sql(offset);
+        debug!("Query search: {:?} -> {} ({:?})", parsed, filter.condition, scope);
+
+        let (condition, bind) = scope.condition();
+        let query_str = format!(
+            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
+                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, {snippet},
+                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
+                   m.body_text, m.body_html, m.gmail_labels, m.gmail_thread_id, m.mention, m.tags,
+                   m.snoozed_until, m.reply_later_at
+            FROM messages m
+            WHERE {} AND {}
+            ORDER BY m.date_epoch DESC
+            LIMIT ?"#,
+            filter.condition,
+            condition,
+            snippet = self.snippet_column(),
+        );
+        let mut query = sqlx::query_as::<_, DbMessage>(&query_str);
+        for param in filter.params {
+            query = match param {
+                SqlParam::Text(text) => query.bind(text),
+                SqlParam::Int(value) => query.bind(value),
+            };
+        }
+        query = match bind {
+            Some(SearchBind::Id(id)) => query.bind(id),
+            Some(SearchBind::Text(text)) => query.bind(text),
+            None => query,
+        };
+        let messages = query.bind(limit).fetch_all(&self.pool).await?;
+
+        Ok(messages)
+    }
+
+    /// Update message read status
+    pub async fn set_message_read(&self, message_id: i64, is_read: bool) -> CoreResult<()> {
+        let folder_id: Option<i64> = sqlx::query_scalar(
+            "UPDATE messages SET is_read = ?, updated_at = datetime('now') WHERE id = ? RETURNING folder_id",
+        )
+        .bind(is_read)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        // Update the folder's unread_count
+        let delta: i64 = if is_read { -1 } else { 1 };
+        sqlx::query(
+            r#"
+            UPDATE folders SET unread_count = MAX(0, COALESCE(unread_count, 0) + ?)
+            WHERE id = (SELECT folder_id FROM messages WHERE id = ?)
+            "#,
+        )
+        .bind(delta)
+        .bind(message_id)
+        .execute(&self.pool)
+        .await?;
+
+        if let Some(folder_id) = folder_id {
+            self.record_pending_flags(&[message_id], Flag::Read, is_read)
+                .await?;
+            self.changes.send(Change::FlagsChanged {
+                message_ids: vec![message_id],
+            });
+            self.changes.send(Change::FolderCountsChanged {
+                folder_ids: vec![folder_id],
+            });
+        }
+        Ok(())
+    }
+
+    /// Update read status by folder_id + UID (for Graph messages where DB id may be 0)
+    pub async fn set_message_read_by_uid(&self, folder_id: i64, uid: i64, is_read: bool) -> CoreResult<()> {
+        let message_ids: Vec<i64> = sqlx::query_scalar(
+            "UPDATE messages SET is_read = ?, updated_at = datetime('now') WHERE folder_id = ? AND uid = ? RETURNING id",
+        )
+        .bind(is_read)
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Update the folder's unread_count
+        let delta: i64 = if is_read { -1 } else { 1 };
+        sqlx::query(
+            "UPDATE folders SET unread_count = MAX(0, COALESCE(unread_count, 0) + ?) WHERE id = ?",
+        )
+        .bind(delta)
+        .bind(folder_id)
+        .execute(&self.pool)
+        .await?;
+
+        self.record_pending_flags(&message_ids, Flag::Read, is_read)
+            .await?;
+        self.changes.send(Change::FlagsChanged { message_ids });
+        self.changes.send(Change::FolderCountsChanged {
+            folder_ids: vec![folder_id],
+        });
+        Ok(())
+    }
+
+    /// Update message starred status
+    pub async fn set_message_starred(&self, message_id: i64, is_starred: bool) -> CoreResult<()> {
+        sqlx::query(
+            "UPDATE messages SET is_starred = ?, updated_at = datetime('now') WHERE id = ?",
+        )
+        .bind(is_starred)
+        .bind(message_id)
+        .execute(&self.pool)
+        .await?;
+        self.record_pending_flags(&[message_id], Flag::Starred, is_starred)
+            .await?;
+        self.changes.send(Change::FlagsChanged {
+            message_ids: vec![message_id],
+        });
+        Ok(())
+    }
+
+    /// Remember local flag changes until the server confirms them, so a
+    /// sync can tell them from the server's (see [`crate::flag_merge`])
+    async fn record_pending_flags(
+        &self,
+        message_ids: &[i64],
+        flag: Flag,
+        value: bool,
+    ) -> CoreResult<()> {
+        if message_ids.is_empty() {
+            return Ok(());
+        }
+        let ids_json = serde_json::to_string(message_ids)
+            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+        sqlx::query(
+            r#"
+            INSERT INTO pending_flags (message_id, flag, value, changed_at)
+            SELECT id, ?, ?, ? FROM messages WHERE id IN (SELECT value FROM json_each(?))
+            ON CONFLICT(message_id, flag) DO UPDATE SET
+                value = excluded.value,
+                changed_at = excluded.changed_at
+            "#,
+        )
+        .bind(flag.as_str())
+        .bind(value)
+        .bind(chrono::Utc::now().timestamp())
+        .bind(&ids_json)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Forget pending changes of `flag` to `value` once the server has
+    /// them. A change made again since, to the other value, stays pending.
+    pub async fn confirm_pending_flags(
+        &self,
+        folder_id: i64,
+        uids: &[u32],
+        flag: Flag,
+        value: bool,
+    ) -> CoreResult<()> {
+        let uids_json =
+            serde_json::to_string(uids).map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+        sqlx::query(
+            r#"
+            DELETE FROM pending_flags
+            WHERE flag = ? AND value = ? AND message_id IN (
+                SELECT id FROM messages
+                WHERE folder_id = ? AND uid IN (SELECT value FROM json_each(?))
+            )
+            "#,
+        )
+        .bind(flag.as_str())
+        .bind(value)
+        .bind(folder_id)
+        .bind(&uids_json)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Set a message's tags (IMAP keywords)
+    pub async fn set_message_tags(&self, message_id: i64, keywords: &[String]) -> CoreResult<()> {
+        sqlx::query("UPDATE messages SET tags = ?, updated_at = datetime('now') WHERE id = ?")
+            .bind(crate::tags::encode_tags(keywords))
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        self.changes.send(Change::FlagsChanged {
+            message_ids: vec![message_id],
+        });
+        Ok(())
+    }
+
+    /// Tag a message with the mention keyword found in it
+    pub async fn set_message_mention(&self, message_id: i64, keyword: &str) -> CoreResult<()> {
+        sqlx::query("UPDATE messages SET mention = ?, updated_at = datetime('now') WHERE id = ?")
+            .bind(keyword)
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Update message has_attachments flag (corrected after body parsing)
+    pub async fn set_message_has_attachments_by_uid(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        has_attachments: bool,
+    ) -> CoreResult<()> {
+        sqlx::query(
+            "UPDATE messages SET has_attachments = ?, updated_at = datetime('now') WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(has_attachments)
+        .bind(folder_id)
+        .bind(uid)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the has_attachments flag for a message
+    pub async fn get_message_has_attachments(
+        &self,
+        folder_id: i64,
+        uid: i64,
+    ) -> CoreResult<bool> {
+        let result: Option<(bool,)> = sqlx::query_as(
+            "SELECT has_attachments FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|(v,)| v).unwrap_or(false))
+    }
+
+    /// Delete a single message by ID
+    pub async fn delete_message(&self, message_id: i64) -> CoreResult<()> {
+        let folder_id: Option<i64> =
+            sqlx::query_scalar("DELETE FROM messages WHERE id = ? RETURNING folder_id")
+                .bind(message_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        if let Some(folder_id) = folder_id {
+            self.changes.send(Change::MessagesRemoved { folder_id });
+        }
+        Ok(())
+    }
+
+    /// Delete a single message by folder_id and IMAP UID
+    /// More reliable than delete_message() since the UID is always known from IMAP
+    pub async fn delete_message_by_uid(&self, folder_id: i64, uid: i64) -> CoreResult<()> {
+        // Check if message is unread before deleting, to update folder count
+        let is_unread: bool = sqlx::query_scalar(
+            "SELECT CASE WHEN is_read = 0 THEN 1 ELSE 0 END FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?
+        .unwrap_or(false);
+
+        let result = sqlx::query("DELETE FROM messages WHERE folder_id = ? AND uid = ?")
+            .bind(folder_id)
+            .bind(uid)
+            .execute(&self.pool)
+            .await?;
+
+        // Decrement unread count if the deleted message was unread
+        if is_unread {
+            sqlx::query(
+                "UPDATE folders SET unread_count = MAX(0, COALESCE(unread_count, 0) - 1) WHERE id = ?",
+            )
+            .bind(folder_id)
+            .execute(&self.pool)
+            .await?;
+            self.changes.send(Change::FolderCountsChanged {
+                folder_ids: vec![folder_id],
+            });
+        }
+        if result.rows_affected() > 0 {
+            self.changes.send(Change::MessagesRemoved { folder_id });
+        }
+
+        Ok(())
+    }
+
+    /// Delete messages of a folder by IMAP UID, keeping its unread count
+    /// right. Returns how many were cached.
+    pub async fn delete_messages_by_uids(&self, folder_id: i64, uids: &[u32]) -> CoreResult<u64> {
+        if uids.is_empty() {
+            return Ok(0);
+        }
+        let uids_json =
+            serde_json::to_string(uids).map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "DELETE FROM messages WHERE folder_id = ? AND uid IN (SELECT value FROM json_each(?))",
+        )
+        .bind(folder_id)
+        .bind(&uids_json)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE folders SET unread_count = \
+             (SELECT COUNT(*) FROM messages WHERE folder_id = folders.id AND is_read = 0) WHERE id = ?",
+        )
+        .bind(folder_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        if result.rows_affected() > 0 {
+            self.changes.send(Change::MessagesRemoved { folder_id });
+            self.changes.send(Change::FolderCountsChanged {
+                folder_ids: vec![folder_id],
+            });
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Delete messages by UID (for sync)
+    pub async fn delete_messages_not_in_uids(
+        &self,
+        folder_id: i64,
+        uids: &[i64],
+    ) -> CoreResult<u64> {
+        if uids.is_empty() {
+            let result = sqlx::query("DELETE FROM messages WHERE folder_id = ?")
+                .bind(folder_id)
+                .execute(&self.pool)
+                .await?;
+            if result.rows_affected() > 0 {
+                self.changes.send(Change::MessagesRemoved { folder_id });
+            }
+            return Ok(result.rows_affected());
+        }
+
+        // Use a temp table within a single connection to avoid SQLite's variable limit
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query("CREATE TEMP TABLE IF NOT EXISTS _valid_uids (uid INTEGER PRIMARY KEY)")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM _valid_uids")
+            .execute(&mut *conn)
+            .await?;
+
+        // Insert UIDs in chunks of 500
+        for chunk in uids.chunks(500) {
+            let placeholders: String = chunk.iter().map(|_| "(?)").collect::<Vec<_>>().join(",");
+            let query = format!("INSERT OR IGNORE INTO _valid_uids (uid) VALUES {}", placeholders);
+            let mut q = sqlx::query(&query);
+            for uid in chunk {
+                q = q.bind(uid);
+            }
+            q.execute(&mut *conn).await?;
+        }
+
+        let result = sqlx::query(
+            "DELETE FROM messages WHERE folder_id = ? AND uid NOT IN (SELECT uid FROM _valid_uids)"
+        )
+        .bind(folder_id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query("DELETE FROM _valid_uids").execute(&mut *conn).await?;
+
+        if result.rows_affected() > 0 {
+            self.changes.send(Change::MessagesRemoved { folder_id });
+        }
+        Ok(result.rows_affected())
+    }
+
+    /// Get a folder by account ID and path
+    pub async fn get_folder_by_path(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+    ) -> CoreResult<Option<DbFolder>> {
+        let folder = sqlx::query_as::<_, DbFolder>(
+            r#"
+            SELECT id, account_id, name, full_path, folder_type, uidvalidity,
+                   uid_next, message_count, unread_count, is_selectable, is_subscribed,
+                   unified_inbox, COALESCE(display_path, full_path) AS display_path
+            FROM folders
+            WHERE account_id = ? AND full_path = ?
             "#,
         )
         .bind(account_id)
-        .bind(name)
+        .bind(folder_path)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(folder)
+    }
+
+    /// Get folder ID by path, creating the folder if it doesn't exist
+    pub async fn get_or_create_folder_id(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+    ) -> CoreResult<i64> {
+        // Try to find existing folder
+        if let Some(folder) = self.get_folder_by_path(account_id, folder_path).await? {
+            return Ok(folder.id);
+        }
+
+        // Create new folder entry
+        let folder_name = folder_path.rsplit('/').next().unwrap_or(folder_path);
+        let folder_type = Self::guess_folder_type(folder_path);
+        self.upsert_folder(account_id, folder_name, folder_path, &folder_type)
+            .await
+    }
+
+    /// Delete a folder and all its messages from the database
+    pub async fn delete_folder_by_path(
+        &self,
+        account_id: &str,
+        full_path: &str,
+    ) -> CoreResult<()> {
+        // Delete the folder and all child folders (messages cascade via FK)
+        sqlx::query(
+            "DELETE FROM folders WHERE account_id = ? AND (full_path = ? OR full_path LIKE ? OR full_path LIKE ?)",
+        )
+        .bind(account_id)
         .bind(full_path)
-        .bind(folder_type)
-        .bind(message_count)
-        .bind(unread_count)
-        .fetch_one(&self.pool)
+        .bind(format!("{}/%", full_path))
+        .bind(format!("{}.%", full_path))
+        .execute(&self.pool)
         .await?;
 
-        Ok(result.get::<i64, _>("id"))
+        debug!("Deleted folder {} (and children) for account {}", full_path, account_id);
+        Ok(())
     }
 
-    /// Get folders for an account
-    pub async fn get_folders(&self, account_id: &str) -> CoreResult<Vec<DbFolder>> {
-        let folders = sqlx::query_as::<_, DbFolder>(
-            "SELECT id, account_id, name, full_path, folder_type, uidvalidity, uid_next, message_count, unread_count FROM folders WHERE account_id = ? ORDER BY folder_type, name",
+    /// Get the ID of the local folder `name`, creating it if it doesn't
+    /// exist. Fails if a folder synced from the server has that name.
+    pub async fn create_local_folder(&self, account_id: &str, name: &str) -> CoreResult<i64> {
+        if let Some(folder) = self.get_folder_by_path(account_id, name).await? {
+            if folder.folder_type != LOCAL_FOLDER_TYPE {
+                return Err(CoreError::SyncError(format!(
+                    "The account already has a folder named {}",
+                    name
+                )));
+            }
+            return Ok(folder.id);
+        }
+        self.upsert_folder(account_id, name, name, LOCAL_FOLDER_TYPE)
+            .await
+    }
+
+    /// Message-IDs of the messages in a folder
+    pub async fn get_folder_message_ids(&self, folder_id: i64) -> CoreResult<Vec<String>> {
+        let ids: Vec<String> = sqlx::query_scalar(
+            "SELECT message_id FROM messages WHERE folder_id = ? AND message_id IS NOT NULL",
+        )
+        .bind(folder_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Add messages to a local folder, with their bodies and attachments,
+    /// after the ones it already holds, and recount it
+    pub async fn store_local_messages(
+        &self,
+        folder_id: i64,
+        messages: &[LocalMessage],
+    ) -> CoreResult<()> {
+        let last_uid: Option<i64> = sqlx::query_scalar("SELECT MAX(uid) FROM messages WHERE folder_id = ?")
+            .bind(folder_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        let first_uid = last_uid.unwrap_or(0) + 1;
+        let headers: Vec<DbMessage> = (first_uid..)
+            .zip(messages)
+            .map(|(uid, message)| DbMessage {
+                folder_id,
+                uid,
+                ..message.header.clone()
+            })
+            .collect();
+        self.upsert_messages_batch(folder_id, &headers).await?;
+
+        for (header, message) in headers.iter().zip(messages) {
+            self.save_message_body(
+                folder_id,
+                header.uid,
+                message.body_text.as_deref(),
+                message.body_html.as_deref(),
+            )
+            .await?;
+            if !message.attachments.is_empty() {
+                self.save_message_attachments(folder_id, header.uid, &message.attachments)
+                    .await?;
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE folders SET
+                message_count = (SELECT COUNT(*) FROM messages WHERE folder_id = folders.id),
+                unread_count = (SELECT COUNT(*) FROM messages WHERE folder_id = folders.id AND is_read = 0),
+                updated_at = datetime('now')
+            WHERE id = ?
+            "#,
+        )
+        .bind(folder_id)
+        .execute(&self.pool)
+        .await?;
+        self.changes.send(Change::FolderCountsChanged {
+            folder_ids: vec![folder_id],
+        });
+        Ok(())
+    }
+
+    /// Delete all messages in a folder (by account_id and folder path), keeping the folder itself
+    pub async fn delete_messages_in_folder(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+    ) -> CoreResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM messages WHERE folder_id IN (
+                SELECT id FROM folders WHERE account_id = ? AND full_path = ?
+            )
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder_path)
+        .execute(&self.pool)
+        .await?;
+
+        // Reset folder counts to 0
+        let folder_ids: Vec<i64> = sqlx::query_scalar(
+            "UPDATE folders SET message_count = 0, unread_count = 0 WHERE account_id = ? AND full_path = ? RETURNING id",
         )
         .bind(account_id)
+        .bind(folder_path)
         .fetch_all(&self.pool)
         .await?;
+        if !folder_ids.is_empty() {
+            for &folder_id in &folder_ids {
+                self.changes.send(Change::MessagesRemoved { folder_id });
+            }
+            self.changes.send(Change::FolderCountsChanged { folder_ids });
+        }
 
-        Ok(folders)
+        debug!("Deleted {} messages in folder {} for account {}", result.rows_affected(), folder_path, account_id);
+        Ok(result.rows_affected())
+    }
+
+    /// Check if a message is unread (by folder_id + uid)
+    pub async fn is_message_unread(&self, folder_id: i64, uid: i64) -> CoreResult<bool> {
+        let is_unread: bool = sqlx::query_scalar(
+            "SELECT CASE WHEN is_read = 0 THEN 1 ELSE 0 END FROM messages WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(folder_id)
+        .bind(uid)
+        .fetch_optional(&self.pool)
+        .await?
+        .unwrap_or(false);
+        Ok(is_unread)
+    }
+
+    /// Increment unread count for a folder (used when moving an unread message into a folder)
+    pub async fn increment_folder_unread(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+    ) -> CoreResult<()> {
+        let folder_ids: Vec<i64> = sqlx::query_scalar(
+            "UPDATE folders SET unread_count = COALESCE(unread_count, 0) + 1 WHERE account_id = ? AND full_path = ? RETURNING id",
+        )
+        .bind(account_id)
+        .bind(folder_path)
+        .fetch_all(&self.pool)
+        .await?;
+        if !folder_ids.is_empty() {
+            self.changes.send(Change::FolderCountsChanged { folder_ids });
+        }
+        Ok(())
+    }
+
+    /// Mark a folder as selectable or as a `\Noselect` container
+    pub async fn set_folder_selectable(
+        &self,
+        account_id: &str,
+        full_path: &str,
+        is_selectable: bool,
+    ) -> CoreResult<()> {
+        sqlx::query("UPDATE folders SET is_selectable = ? WHERE account_id = ? AND full_path = ?")
+            .bind(is_selectable)
+            .bind(account_id)
+            .bind(full_path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark a single folder as subscribed or unsubscribed
+    pub async fn set_folder_subscribed(
+        &self,
+        account_id: &str,
+        full_path: &str,
+        is_subscribed: bool,
+    ) -> CoreResult<()> {
+        sqlx::query("UPDATE folders SET is_subscribed = ? WHERE account_id = ? AND full_path = ?")
+            .bind(is_subscribed)
+            .bind(account_id)
+            .bind(full_path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Opt a folder in or out of IDLE push notifications
+    pub async fn set_folder_idle(
+        &self,
+        account_id: &str,
+        full_path: &str,
+        is_idle: bool,
+    ) -> CoreResult<()> {
+        sqlx::query("UPDATE folders SET is_idle = ? WHERE account_id = ? AND full_path = ?")
+            .bind(is_idle)
+            .bind(account_id)
+            .bind(full_path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Paths of the folders opted into IDLE for an account (INBOX is always
+    /// watched and not included unless set explicitly)
+    pub async fn get_idle_folders(&self, account_id: &str) -> CoreResult<Vec<String>> {
+        let paths: Vec<(String,)> = sqlx::query_as(
+            "SELECT full_path FROM folders WHERE account_id = ? AND is_idle = 1 AND is_selectable = 1 ORDER BY full_path",
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(paths.into_iter().map(|(p,)| p).collect())
+    }
+
+    /// A folder's sync policy; the default for a folder not in the cache
+    pub async fn get_folder_sync_policy(
+        &self,
+        account_id: &str,
+        full_path: &str,
+    ) -> CoreResult<FolderSyncPolicy> {
+        let row: Option<(Option<i64>, Option<i64>, bool, bool)> = sqlx::query_as(
+            "SELECT sync_interval_minutes, sync_days, fetch_bodies, unified_inbox \
+             FROM folders WHERE account_id = ? AND full_path = ?",
+        )
+        .bind(account_id)
+        .bind(full_path)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(folder_sync_policy).unwrap_or_default())
     }
 
-    /// Update folder sync state
-    pub async fn update_folder_sync(
+    /// Set a folder's sync policy
+    pub async fn set_folder_sync_policy(
         &self,
-        folder_id: i64,
-        uidvalidity: i64,
-        uid_next: i64,
-        message_count: i64,
-        unread_count: i64,
+        account_id: &str,
+        full_path: &str,
+        policy: &FolderSyncPolicy,
     ) -> CoreResult<()> {
         sqlx::query(
-            r#"
-            UPDATE folders SET
-                uidvalidity = ?,
-                uid_next = ?,
-                message_count = ?,
-                unread_count = ?,
-                updated_at = datetime('now')
-            WHERE id = ?
-            "#,
+            "UPDATE folders SET sync_interval_minutes = ?, sync_days = ?, fetch_bodies = ?, unified_inbox = ? \
+             WHERE account_id = ? AND full_path = ?",
         )
-        .bind(uidvalidity)
-        .bind(uid_next)
-        .bind(message_count)
-        .bind(unread_count)
-        .bind(folder_id)
+        .bind(policy.interval_minutes.map(i64::from))
+        .bind(policy.sync_days.map(i64::from))
+        .bind(policy.fetch_bodies)
+        .bind(policy.unified_inbox)
+        .bind(account_id)
+        .bind(full_path)
         .execute(&self.pool)
         .await?;
+        Ok(())
+    }
+
+    /// Folders with a background sync interval, local folders aside
+    pub async fn get_scheduled_folders(&self) -> CoreResult<Vec<ScheduledFolder>> {
+        let rows = sqlx::query(
+            "SELECT id, account_id, full_path, sync_interval_minutes, sync_days, fetch_bodies, \
+                    unified_inbox, synced_at \
+             FROM folders \
+             WHERE sync_interval_minutes IS NOT NULL AND is_selectable = 1 AND folder_type != ?",
+        )
+        .bind(LOCAL_FOLDER_TYPE)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ScheduledFolder {
+                folder_id: row.get("id"),
+                account_id: row.get("account_id"),
+                full_path: row.get("full_path"),
+                policy: folder_sync_policy((
+                    row.get("sync_interval_minutes"),
+                    row.get("sync_days"),
+                    row.get("fetch_bodies"),
+                    row.get("unified_inbox"),
+                )),
+                synced_at: row.get("synced_at"),
+            })
+            .collect())
+    }
 
+    /// Record when the sync engine last synced a folder on schedule
+    pub async fn set_folder_synced_at(&self, folder_id: i64, synced_at: i64) -> CoreResult<()> {
+        sqlx::query("UPDATE folders SET synced_at = ? WHERE id = ?")
+            .bind(synced_at)
+            .bind(folder_id)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
-    /// Upsert a folder with Graph API folder ID
-    pub async fn upsert_folder_graph(
+    /// Set the display forms of a folder's leaf name and full path
+    pub async fn set_folder_display_names(
         &self,
         account_id: &str,
-        name: &str,
         full_path: &str,
-        folder_type: &str,
-        message_count: Option<i64>,
-        unread_count: Option<i64>,
-        graph_folder_id: &str,
-    ) -> CoreResult<i64> {
-        let id = self
-            .upsert_folder_with_counts(
-                account_id,
-                name,
-                full_path,
-                folder_type,
-                message_count,
-                unread_count,
-            )
+        name: &str,
+        display_path: &str,
+    ) -> CoreResult<()> {
+        sqlx::query("UPDATE folders SET name = ?, display_path = ? WHERE account_id = ? AND full_path = ?")
+            .bind(name)
+            .bind(display_path)
+            .bind(account_id)
+            .bind(full_path)
+            .execute(&self.pool)
             .await?;
+        Ok(())
+    }
 
-        // Update the graph_folder_id
-        sqlx::query("UPDATE folders SET graph_folder_id = ? WHERE id = ?")
-            .bind(graph_folder_id)
-            .bind(id)
-            .execute(&self.pool)
+    /// Mark exactly the given folders as subscribed for an account
+    pub async fn set_subscribed_folders(
+        &self,
+        account_id: &str,
+        subscribed_paths: &[String],
+    ) -> CoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE folders SET is_subscribed = 0 WHERE account_id = ?")
+            .bind(account_id)
+            .execute(&mut *tx)
             .await?;
+        for path in subscribed_paths {
+            sqlx::query("UPDATE folders SET is_subscribed = 1 WHERE account_id = ? AND full_path = ?")
+                .bind(account_id)
+                .bind(path)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
 
-        Ok(id)
+    /// Delete folders not in the given set of paths (cleanup stale folders
+    /// after sync). Local folders aren't on the server, so they stay.
+    pub async fn delete_stale_folders(
+        &self,
+        account_id: &str,
+        valid_paths: &[String],
+    ) -> CoreResult<u64> {
+        if valid_paths.is_empty() {
+            return Ok(0);
+        }
+        // Build a query with placeholders for the valid paths
+        let placeholders: Vec<&str> = valid_paths.iter().map(|_| "?").collect();
+        let query = format!(
+            "DELETE FROM folders WHERE account_id = ? AND folder_type != ? AND full_path NOT IN ({})",
+            placeholders.join(", ")
+        );
+        let mut q = sqlx::query(&query).bind(account_id).bind(LOCAL_FOLDER_TYPE);
+        for path in valid_paths {
+            q = q.bind(path);
+        }
+        let result = q.execute(&self.pool).await?;
+        Ok(result.rows_affected())
     }
 
-    /// Get graph_message_id for a message by folder_id and uid
-    pub async fn get_graph_message_id(&self, folder_id: i64, uid: i64) -> CoreResult<Option<String>> {
-        let result = sqlx::query_scalar::<_, Option<String>>(
-            "SELECT graph_message_id FROM messages WHERE folder_id = ? AND uid = ?",
+    /// Rename a folder path in the database, updating child folder paths too
+    pub async fn rename_folder_path(
+        &self,
+        account_id: &str,
+        old_path: &str,
+        new_path: &str,
+    ) -> CoreResult<()> {
+        let new_name = new_path.rsplit('/').next()
+            .or_else(|| new_path.rsplit('.').next())
+            .unwrap_or(new_path);
+
+        // Update the folder itself
+        sqlx::query(
+            "UPDATE folders SET full_path = ?, name = ?, display_path = ? WHERE account_id = ? AND full_path = ?",
         )
-        .bind(folder_id)
-        .bind(uid)
-        .fetch_optional(&self.pool)
+        .bind(new_path)
+        .bind(decode_mailbox_name(new_name))
+        .bind(decode_mailbox_name(new_path))
+        .bind(account_id)
+        .bind(old_path)
+        .execute(&self.pool)
         .await?;
 
-        Ok(result.flatten())
+        // Update child folders whose paths start with old_path + delimiter
+        // Try both "/" and "." delimiters
+        for delim in &["/", "."] {
+            let old_prefix = format!("{}{}", old_path, delim);
+            let new_prefix = format!("{}{}", new_path, delim);
+
+            // Find child folders
+            let children: Vec<(i64, String)> = sqlx::query_as(
+                "SELECT id, full_path FROM folders WHERE account_id = ? AND full_path LIKE ?",
+            )
+            .bind(account_id)
+            .bind(format!("{}%", old_prefix))
+            .fetch_all(&self.pool)
+            .await?;
+
+            for (child_id, child_path) in children {
+                let updated_path = format!("{}{}", new_prefix, &child_path[old_prefix.len()..]);
+                let child_name = updated_path.rsplit(*delim).next().unwrap_or(&updated_path);
+                sqlx::query("UPDATE folders SET full_path = ?, name = ?, display_path = ? WHERE id = ?")
+                    .bind(&updated_path)
+                    .bind(decode_mailbox_name(child_name))
+                    .bind(decode_mailbox_name(&updated_path))
+                    .bind(child_id)
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        debug!(
+            "Renamed folder {} -> {} for account {}",
+            old_path, new_path, account_id
+        );
+        Ok(())
     }
 
-    /// Get graph_folder_id for a folder by its DB id
-    pub async fn get_graph_folder_id(&self, folder_id: i64) -> CoreResult<Option<String>> {
+    /// Get the graph_folder_id for a folder identified by account_id and full_path
+    pub async fn get_graph_folder_id_by_path(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+    ) -> CoreResult<Option<String>> {
         let result = sqlx::query_scalar::<_, Option<String>>(
-            "SELECT graph_folder_id FROM folders WHERE id = ?",
+            "SELECT graph_folder_id FROM folders WHERE account_id = ? AND full_path = ?",
         )
-        .bind(folder_id)
+        .bind(account_id)
+        .bind(folder_path)
         .fetch_optional(&self.pool)
         .await?;
 
         Ok(result.flatten())
     }
 
-    /// Upsert a message with a Graph message ID
-    pub async fn upsert_message_graph(
-        &self,
-        folder_id: i64,
-        msg: &DbMessage,
-        graph_message_id: &str,
-    ) -> CoreResult<i64> {
-        let id = self.upsert_message(folder_id, msg).await?;
-
-        sqlx::query("UPDATE messages SET graph_message_id = ? WHERE id = ?")
-            .bind(graph_message_id)
-            .bind(id)
-            .execute(&self.pool)
+    /// Get all message UIDs in a folder (for sync comparison)
+    pub async fn get_message_uids(&self, folder_id: i64) -> CoreResult<Vec<i64>> {
+        let rows = sqlx::query("SELECT uid FROM messages WHERE folder_id = ?")
+            .bind(folder_id)
+            .fetch_all(&self.pool)
             .await?;
 
-        Ok(id)
-    }
-
-    /// Batch upsert messages with Graph message IDs
-    pub async fn upsert_messages_batch_graph(
-        &self,
-        folder_id: i64,
-        messages: &[(DbMessage, String)], // (message, graph_message_id)
-    ) -> CoreResult<usize> {
-        let mut count = 0;
-
-        // Process in chunks to avoid holding write lock too long
-        for chunk in messages.chunks(50) {
-            let mut tx = self.pool.begin().await?;
-
-            for (msg, graph_id) in chunk {
-                let result = sqlx::query(
-                    r#"
-                    INSERT INTO messages (
-                        folder_id, uid, message_id, subject, from_address, from_name,
-                        to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
-                        has_attachments, size, maildir_path, graph_message_id
-                    )
-                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                    ON CONFLICT(folder_id, uid) DO UPDATE SET
-                        message_id = excluded.message_id,
-                        subject = excluded.subject,
-                        from_address = excluded.from_address,
-                        from_name = excluded.from_name,
-                        to_addresses = excluded.to_addresses,
-                        cc_addresses = excluded.cc_addresses,
-                        date_sent = excluded.date_sent,
-                        date_epoch = excluded.date_epoch,
-                        snippet = excluded.snippet,
-                        is_read = excluded.is_read,
-                        is_starred = excluded.is_starred,
-                        has_attachments = excluded.has_attachments,
-                        size = excluded.size,
-                        maildir_path = excluded.maildir_path,
-                        graph_message_id = excluded.graph_message_id,
-                        updated_at = datetime('now')
-                    "#,
-                )
-                .bind(folder_id)
-                .bind(msg.uid)
-                .bind(&msg.message_id)
-                .bind(&msg.subject)
-                .bind(&msg.from_address)
-                .bind(&msg.from_name)
-                .bind(&msg.to_addresses)
-                .bind(&msg.cc_addresses)
-                .bind(&msg.date_sent)
-                .bind(msg.date_epoch)
-                .bind(&msg.snippet)
-                .bind(msg.is_read)
-                .bind(msg.is_starred)
-                .bind(msg.has_attachments)
-                .bind(msg.size)
-                .bind(&msg.maildir_path)
-                .bind(graph_id)
-                .execute(&mut *tx)
-                .await;
-
-                match result {
-                    Ok(_) => count += 1,
-                    Err(e) => {
-                        tracing::warn!("Failed to upsert graph message uid={}: {}", msg.uid, e);
-                    }
-                }
-            }
-
-            tx.commit().await?;
-        }
-
-        Ok(count)
+        Ok(rows.iter().map(|r| r.get::<i64, _>("uid")).collect())
     }
 
-    /// Look up the Graph message ID for a message by its UID hash
-    pub async fn get_graph_message_id_by_uid(&self, uid: i64) -> CoreResult<Option<String>> {
-        let row: Option<(String,)> = sqlx::query_as(
-            "SELECT graph_message_id FROM messages WHERE uid = ? AND graph_message_id IS NOT NULL LIMIT 1",
+    /// Get message count for a folder
+    pub async fn get_message_count(&self, folder_id: i64) -> CoreResult<i64> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) as count FROM messages WHERE folder_id = ? AND snoozed_until IS NULL",
         )
-        .bind(uid)
-        .fetch_optional(&self.pool)
+        .bind(folder_id)
+        .fetch_one(&self.pool)
         .await?;
-        Ok(row.map(|r| r.0))
+
+        Ok(row.get::<i64, _>("count"))
     }
 
-    /// Look up the Graph message ID for a message by account, folder, and UID
-    pub async fn get_graph_message_id_for_folder_uid(
-        &self,
-        account_id: &str,
-        folder_path: &str,
-        uid: i64,
-    ) -> CoreResult<Option<String>> {
-        let row: Option<(String,)> = sqlx::query_as(
+    /// Guess folder type from path
+    fn guess_folder_type(folder_path: &str) -> String {
+        let path_lower = folder_path.to_lowercase();
+        if path_lower == "inbox" {
+            "inbox".to_string()
+        } else if path_lower.contains("sent") {
+            "sent".to_string()
+        } else if path_lower.contains("draft") {
+            "drafts".to_string()
+        } else if path_lower.contains("trash") || path_lower.contains("deleted") {
+            "trash".to_string()
+        } else if path_lower.contains("spam") || path_lower.contains("junk") {
+            "spam".to_string()
+        } else if path_lower.contains("archive") {
+            "archive".to_string()
+        } else {
+            "other".to_string()
+        }
+    }
+
+    /// Get total message count for an account (across all folders)
+    pub async fn get_account_message_count(&self, account_id: &str) -> CoreResult<i64> {
+        let row = sqlx::query(
             r#"
-            SELECT m.graph_message_id FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE f.account_id = ? AND f.full_path = ? AND m.uid = ?
-            AND m.graph_message_id IS NOT NULL
-            LIMIT 1
+            SELECT COUNT(*) as count FROM messages m
+            INNER JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ?
             "#,
         )
         .bind(account_id)
-        .bind(folder_path)
-        .bind(uid)
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
-        Ok(row.map(|r| r.0))
-    }
-
-    /// Insert or update messages in a batch (wrapped in a transaction for performance)
-    pub async fn upsert_messages_batch(
-        &self,
-        folder_id: i64,
-        messages: &[DbMessage],
-    ) -> CoreResult<usize> {
-        let mut count = 0;
 
-        // Process in chunks to avoid holding write lock too long
-        for chunk in messages.chunks(50) {
-            let mut tx = self.pool.begin().await?;
+        Ok(row.get::<i64, _>("count"))
+    }
 
-            for msg in chunk {
-                let result = sqlx::query(
-                    r#"
-                    INSERT INTO messages (
-                        folder_id, uid, message_id, subject, from_address, from_name,
-                        to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
-                        has_attachments, size, maildir_path
-                    )
-                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-                    ON CONFLICT(folder_id, uid) DO UPDATE SET
-                        message_id = excluded.message_id,
-                        subject = excluded.subject,
-                        from_address = excluded.from_address,
-                        from_name = excluded.from_name,
-                        to_addresses = excluded.to_addresses,
-                        cc_addresses = excluded.cc_addresses,
-                        date_sent = excluded.date_sent,
-                        date_epoch = excluded.date_epoch,
-                        snippet = excluded.snippet,
-                        is_read = excluded.is_read,
-                        is_starred = excluded.is_starred,
-                        has_attachments = excluded.has_attachments,
-                        size = excluded.size,
-                        maildir_path = excluded.maildir_path,
-                        updated_at = datetime('now')
-                    "#,
-                )
-                .bind(folder_id)
-                .bind(msg.uid)
-                .bind(&msg.message_id)
-                .bind(&msg.subject)
-                .bind(&msg.from_address)
-                .bind(&msg.from_name)
-                .bind(&msg.to_addresses)
-                .bind(&msg.cc_addresses)
-                .bind(&msg.date_sent)
-                .bind(msg.date_epoch)
-                .bind(&msg.snippet)
-                .bind(msg.is_read)
-                .bind(msg.is_starred)
-                .bind(msg.has_attachments)
-                .bind(msg.size)
-                .bind(&msg.maildir_path)
-                .execute(&mut *tx)
-                .await;
+    /// Get total cached body count for an account
+    pub async fn get_account_body_count(&self, account_id: &str) -> CoreResult<i64> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM messages m
+            INNER JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND (m.body_text IS NOT NULL OR m.body_html IS NOT NULL)
+            "#,
+        )
+        .bind(account_id)
+        .fetch_one(&self.pool)
+        .await?;
 
-                match result {
-                    Ok(_) => count += 1,
-                    Err(e) => {
-                        tracing::warn!("Failed to upsert message uid={}: {}", msg.uid, e);
-                    }
-                }
-            }
+        Ok(row.get::<i64, _>("count"))
+    }
 
-            tx.commit().await?;
-        }
+    /// Get inbox message count for a specific account
+    pub async fn get_inbox_message_count_for_account(&self, account_id: &str) -> CoreResult<i64> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM messages m
+            INNER JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND f.folder_type = 'inbox'
+            "#,
+        )
+        .bind(account_id)
+        .fetch_one(&self.pool)
+        .await?;
 
-        Ok(count)
+        Ok(row.get::<i64, _>("count"))
     }
 
-    /// Insert or update a message
-    pub async fn upsert_message(&self, folder_id: i64, msg: &DbMessage) -> CoreResult<i64> {
-        let result = sqlx::query(
+    /// Get the latest inbox message for a specific account (for notifications)
+    pub async fn get_latest_inbox_message(&self, account_id: &str) -> CoreResult<Option<DbMessage>> {
+        let message = sqlx::query_as::<_, DbMessage>(
             r#"
-            INSERT INTO messages (
-                folder_id, uid, message_id, subject, from_address, from_name,
-                to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
-                has_attachments, size, maildir_path
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            ON CONFLICT(folder_id, uid) DO UPDATE SET
-                message_id = excluded.message_id,
-                subject = excluded.subject,
-                from_address = excluded.from_address,
-                from_name = excluded.from_name,
-                to_addresses = excluded.to_addresses,
-                cc_addresses = excluded.cc_addresses,
-                date_sent = excluded.date_sent,
-                date_epoch = excluded.date_epoch,
-                snippet = excluded.snippet,
-                is_read = excluded.is_read,
-                is_starred = excluded.is_starred,
-                has_attachments = excluded.has_attachments,
-                size = excluded.size,
-                maildir_path = excluded.maildir_path,
-                updated_at = datetime('now')
-            RETURNING id
+            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
+                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
+                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
+                   m.body_text, m.body_html, m.gmail_labels, m.gmail_thread_id, m.mention, m.tags,
+                   m.snoozed_until, m.reply_later_at
+            FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND f.folder_type = 'inbox'
+            ORDER BY m.date_epoch DESC, m.uid DESC
+            LIMIT 1
             "#,
         )
-        .bind(folder_id)
-        .bind(msg.uid)
-        .bind(&msg.message_id)
-        .bind(&msg.subject)
-        .bind(&msg.from_address)
-        .bind(&msg.from_name)
-        .bind(&msg.to_addresses)
-        .bind(&msg.cc_addresses)
-        .bind(&msg.date_sent)
-        .bind(msg.date_epoch)
-        .bind(&msg.snippet)
-        .bind(msg.is_read)
-        .bind(msg.is_starred)
-        .bind(msg.has_attachments)
-        .bind(msg.size)
-        .bind(&msg.maildir_path)
-        .fetch_one(&self.pool)
+        .bind(account_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(result.get::<i64, _>("id"))
+        Ok(message)
     }
 
-    /// Get messages for a folder
-    pub async fn get_messages(
-        &self,
-        folder_id: i64,
-        limit: i64,
-        offset: i64,
-    ) -> CoreResult<Vec<DbMessage>> {
+    /// Get the newest `limit` inbox messages for an account
+    pub async fn get_latest_inbox_messages(&self, account_id: &str, limit: i64) -> CoreResult<Vec<DbMessage>> {
         let messages = sqlx::query_as::<_, DbMessage>(
             r#"
-            SELECT id, folder_id, uid, message_id, subject, from_address, from_name,
-                   to_addresses, cc_addresses, date_sent, date_epoch, snippet, is_read, is_starred,
-                   has_attachments, size, maildir_path, body_text, body_html
-            FROM messages
-            WHERE folder_id = ?
-            ORDER BY date_epoch DESC, uid DESC
-            LIMIT ? OFFSET ?
+            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
+                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
+                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
+                   m.body_text, m.body_html, m.gmail_labels, m.gmail_thread_id, m.mention, m.tags,
+                   m.snoozed_until, m.reply_later_at
+            FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND f.folder_type = 'inbox'
+            ORDER BY m.date_epoch DESC, m.uid DESC
+            LIMIT ?
             "#,
         )
-        .bind(folder_id)
+        .bind(account_id)
         .bind(limit)
-        .bind(offset)
         .fetch_all(&self.pool)
         .await?;
 
         Ok(messages)
     }
 
-    /// Get message body by folder and UID
-    pub async fn get_message_body(
-        &self,
-        folder_id: i64,
-        uid: i64,
-    ) -> CoreResult<Option<(Option<String>, Option<String>)>> {
-        let result = sqlx::query(
-            "SELECT body_text, body_html FROM messages WHERE folder_id = ? AND uid = ?",
+    /// Get total unread count across all accounts (for window badge)
+    pub async fn get_total_unread_count(&self) -> CoreResult<i64> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM messages m
+            INNER JOIN folders f ON m.folder_id = f.id
+            WHERE f.folder_type = 'inbox' AND m.is_read = 0
+            "#,
         )
-        .bind(folder_id)
-        .bind(uid)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(result.map(|row| {
-            (
-                row.get::<Option<String>, _>("body_text"),
-                row.get::<Option<String>, _>("body_html"),
-            )
-        }))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get::<i64, _>("count"))
     }
 
-    /// Save message body
-    pub async fn save_message_body(
-        &self,
-        folder_id: i64,
-        uid: i64,
-        body_text: Option<&str>,
-        body_html: Option<&str>,
-    ) -> CoreResult<()> {
+    /// Clear all cached data for an account. Local folders have no copy on
+    /// the server, so they're kept.
+    pub async fn clear_account_cache(&self, account_id: &str) -> CoreResult<()> {
+        // Delete messages first (foreign key constraint)
         sqlx::query(
             r#"
-            UPDATE messages
-            SET body_text = ?, body_html = ?, updated_at = datetime('now')
-            WHERE folder_id = ? AND uid = ?
+            DELETE FROM messages WHERE folder_id IN (
+                SELECT id FROM folders WHERE account_id = ? AND folder_type != ?
+            )
             "#,
         )
-        .bind(body_text)
-        .bind(body_html)
-        .bind(folder_id)
-        .bind(uid)
+        .bind(account_id)
+        .bind(LOCAL_FOLDER_TYPE)
         .execute(&self.pool)
         .await?;
 
+        // Delete folders
+        sqlx::query("DELETE FROM folders WHERE account_id = ? AND folder_type != ?")
+            .bind(account_id)
+            .bind(LOCAL_FOLDER_TYPE)
+            .execute(&self.pool)
+            .await?;
+
+        self.finish_purge().await?;
+        info!("Cleared cache for account {}", account_id);
         Ok(())
     }
 
-    /// Get attachment metadata for a message
-    pub async fn get_message_attachments(
-        &self,
-        folder_id: i64,
-        uid: i64,
-    ) -> CoreResult<Vec<AttachmentMetadata>> {
-        // First get the message id
-        let msg_id: Option<(i64,)> = sqlx::query_as(
-            "SELECT id FROM messages WHERE folder_id = ? AND uid = ?",
-        )
-        .bind(folder_id)
-        .bind(uid)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        let Some((message_id,)) = msg_id else {
-            return Ok(Vec::new());
-        };
+    /// A page of the unified inbox, with the colour and counts of every
+    /// inbox in it (see [`crate::unified`])
+    pub async fn unified_inbox(&self, query: &UnifiedQuery) -> CoreResult<UnifiedPage> {
+        let messages = self
+            .get_page(PageScope::Inbox, query.limit, query.after, &query.filter)
+            .await?;
 
-        let attachments = sqlx::query_as::<_, AttachmentMetadata>(
-            "SELECT id, message_id, filename, mime_type, size, content_id, is_inline, data FROM attachments WHERE message_id = ?",
+        let rows = sqlx::query(
+            r#"
+            SELECT f.id, f.account_id, COUNT(m.id) AS total,
+                   COALESCE(SUM(m.is_read = 0), 0) AS unread
+            FROM folders f
+            LEFT JOIN messages m ON m.folder_id = f.id AND m.snoozed_until IS NULL
+            WHERE f.folder_type = 'inbox' AND f.unified_inbox = 1
+            GROUP BY f.id
+            ORDER BY f.account_id, f.id
+            "#,
         )
-        .bind(message_id)
         .fetch_all(&self.pool)
         .await?;
+        let account_ids: Vec<String> = rows.iter().map(|row| row.get("account_id")).collect();
+        let colors = unified::account_colors(account_ids.iter().map(String::as_str));
+        let inboxes: Vec<UnifiedInbox> = rows
+            .iter()
+            .map(|row| {
+                let account_id: String = row.get("account_id");
+                UnifiedInbox {
+                    folder_id: row.get("id"),
+                    color: colors[&account_id],
+                    account_id,
+                    total: row.get("total"),
+                    unread: row.get("unread"),
+                }
+            })
+            .collect();
 
-        Ok(attachments)
+        let total = if query.filter.is_active() {
+            self.get_inbox_messages_filtered_count(&query.filter).await?
+        } else {
+            inboxes.iter().map(|inbox| inbox.total).sum()
+        };
+        let unread = inboxes.iter().map(|inbox| inbox.unread).sum();
+        Ok(UnifiedPage {
+            messages,
+            inboxes,
+            total,
+            unread,
+        })
     }
 
-    /// Save attachment metadata for a message (replaces existing)
-    pub async fn save_message_attachments(
+    /// A page of `scope`, newest first, starting after `after` (or at the
+    /// top). Messages without a date come last.
+    ///
+    /// Pages are keyed on the last message shown rather than an offset, so
+    /// each one is a seek into the scope's order index however deep the
+    /// user has scrolled, and nothing sorts the whole list: a page of a
+    /// 100k-message cache takes well under a millisecond. The SQL only
+    /// changes with the scope and which filters are on, so sqlx prepares
+    /// each shape once per connection and reuses it from the statement
+    /// cache.
+    async fn get_page(
         &self,
-        folder_id: i64,
-        uid: i64,
-        attachments: &[AttachmentInfo],
-    ) -> CoreResult<()> {
-        // First get the message id
-        let msg_id: Option<(i64,)> = sqlx::query_as(
-            "SELECT id FROM messages WHERE folder_id = ? AND uid = ?",
-        )
-        .bind(folder_id)
-        .bind(uid)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        let Some((message_id,)) = msg_id else {
-            return Ok(());
+        scope: PageScope<'_>,
+        limit: i64,
+        after: Option<PageCursor>,
+        filter: &MessageFilter,
+    ) -> CoreResult<Vec<DbMessage>> {
+        let key = scope.tiebreak();
+        let dated = format!("(m.date_epoch, m.{}) < (?, ?)", key);
+        let undated = format!("m.date_epoch IS NULL AND m.{} < ?", key);
+
+        // Dated messages first, then the ones without a date
+        let (mut messages, undated_after) = match after {
+            None => (
+                self.query_page(scope, "m.date_epoch IS NOT NULL", None, None, limit, filter)
+                    .await?,
+                i64::MAX,
+            ),
+            Some(cursor @ PageCursor { date_epoch: Some(date), .. }) => (
+                self.query_page(scope, &dated, Some(date), Some(scope.cursor_key(&cursor)), limit, filter)
+                    .await?,
+                i64::MAX,
+            ),
+            Some(cursor) => (Vec::new(), scope.cursor_key(&cursor)),
         };
 
-        // Delete existing attachments
-        sqlx::query("DELETE FROM attachments WHERE message_id = ?")
-            .bind(message_id)
-            .execute(&self.pool)
-            .await?;
-
-        // Insert new attachments
-        for att in attachments {
-            sqlx::query(
-                r#"
-                INSERT INTO attachments (message_id, filename, mime_type, size, content_id, is_inline, data)
-                VALUES (?, ?, ?, ?, ?, ?, ?)
-                "#,
-            )
-            .bind(message_id)
-            .bind(&att.filename)
-            .bind(&att.mime_type)
-            .bind(att.size as i64)
-            .bind(&att.content_id)
-            .bind(att.is_inline)
-            .bind(if att.data.is_empty() { None } else { Some(&att.data) })
-            .execute(&self.pool)
-            .await?;
+        let remaining = limit - messages.len() as i64;
+        if remaining > 0 {
+            let rest = self
+                .query_page(scope, &undated, None, Some(undated_after), remaining, filter)
+                .await?;
+            messages.extend(rest);
         }
-
-        Ok(())
+        Ok(messages)
     }
 
-    /// Get message UIDs that need body prefetch (no cached body, within last N days)
-    /// Returns (uid, is_unread) pairs, prioritizing unread messages
-    pub async fn get_messages_needing_body_prefetch(
+    /// One range of a list for [`Self::get_page`]
+    async fn query_page(
         &self,
-        folder_id: i64,
-        days: i64,
+        scope: PageScope<'_>,
+        keyset: &str,
+        date: Option<i64>,
+        key: Option<i64>,
         limit: i64,
-    ) -> CoreResult<Vec<(i64, bool)>> {
-        let cutoff_epoch = chrono::Utc::now().timestamp() - (days * 24 * 60 * 60);
+        filter: &MessageFilter,
+    ) -> CoreResult<Vec<DbMessage>> {
+        let mut conditions = vec![scope.condition().to_string(), keyset.to_string()];
+        conditions.extend(filter.build_conditions());
+        // Left to itself the planner may pick idx_messages_folder and then
+        // sort every message in scope; walking the order index is what
+        // makes the page cheap
+        let query_str = format!(
+            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
+                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, {snippet},
+                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
+                   m.body_text, m.body_html, m.gmail_labels, m.gmail_thread_id, m.mention, m.tags,
+                   m.snoozed_until, m.reply_later_at
+            FROM messages m INDEXED BY {}
+            WHERE {}
+            ORDER BY m.date_epoch DESC, m.{} DESC
+            LIMIT ?"#,
+            scope.index(),
+            conditions.join(" AND "),
+            scope.tiebreak(),
+            snippet = self.snippet_column(),
+        );
+        let mut query = sqlx::query_as::<_, DbMessage>(&query_str);
+        match scope {
+            PageScope::Folder(id) | PageScope::Tagged(id) => query = query.bind(id),
+            PageScope::StarredInAccount(account_id) => query = query.bind(account_id),
+            PageScope::Inbox | PageScope::Starred | PageScope::Snoozed | PageScope::ReplyLater => {}
+        }
+        if let Some(date) = date {
+            query = query.bind(date);
+        }
+        if let Some(key) = key {
+            query = query.bind(key);
+        }
+        if !filter.from_contains.is_empty() {
+            let pattern = format!("%{}%", filter.from_contains);
+            query = query.bind(pattern.clone()).bind(pattern);
+        }
+        if let Some(after) = filter.date_after {
+            query = query.bind(after);
+        }
+        if let Some(before) = filter.date_before {
+            query = query.bind(before);
+        }
+        let messages = query.bind(limit).fetch_all(&self.pool).await?;
+        Ok(messages)
+    }
 
-        let results: Vec<(i64, bool)> = sqlx::query_as(
+    /// Get total message count across all inbox folders
+    pub async fn get_inbox_message_count(&self) -> CoreResult<i64> {
+        let row = sqlx::query(
             r#"
-            SELECT uid, is_read = 0 as is_unread
-            FROM messages
-            WHERE folder_id = ?
-              AND (body_text IS NULL AND body_html IS NULL)
-              AND (date_epoch IS NULL OR date_epoch >= ?)
-            ORDER BY is_read ASC, date_epoch DESC
-            LIMIT ?
+            SELECT COUNT(*) as count FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.folder_type = 'inbox' AND f.unified_inbox = 1 AND m.snoozed_until IS NULL
             "#,
         )
-        .bind(folder_id)
-        .bind(cutoff_epoch)
-        .bind(limit)
-        .fetch_all(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
 
-        Ok(results)
+        Ok(row.get::<i64, _>("count"))
     }
 
-    /// Search messages using FTS
-    pub async fn search_messages(&self, query: &str, limit: i64) -> CoreResult<Vec<DbMessage>> {
-        let fts_query = prepare_fts_query(query);
-        debug!("FTS search: '{}' -> '{}'", query, fts_query);
-
-        if fts_query.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let messages = sqlx::query_as::<_, DbMessage>(
+    /// Get a folder by its ID (for resolving folder→account mapping in unified inbox)
+    pub async fn get_folder_by_id(&self, folder_id: i64) -> CoreResult<Option<DbFolder>> {
+        let folder = sqlx::query_as::<_, DbFolder>(
             r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN messages_fts fts ON m.id = fts.rowid
-            WHERE messages_fts MATCH ?
-            ORDER BY rank
-            LIMIT ?
+            SELECT id, account_id, name, full_path, folder_type, uidvalidity,
+                   uid_next, message_count, unread_count, is_selectable, is_subscribed,
+                   unified_inbox, COALESCE(display_path, full_path) AS display_path
+            FROM folders
+            WHERE id = ?
             "#,
         )
-        .bind(&fts_query)
-        .bind(limit)
-        .fetch_all(&self.pool)
+        .bind(folder_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(messages)
+        Ok(folder)
     }
 
-    /// Search messages using FTS within a specific folder
-    pub async fn search_messages_in_folder(
+    /// Get messages for a folder with filters applied
+    pub async fn get_messages_filtered(
         &self,
         folder_id: i64,
-        query: &str,
         limit: i64,
+        after: Option<PageCursor>,
+        filter: &MessageFilter,
     ) -> CoreResult<Vec<DbMessage>> {
-        let fts_query = prepare_fts_query(query);
-        debug!("FTS folder search: '{}' -> '{}' (folder_id={})", query, fts_query, folder_id);
+        self.get_page(PageScope::Folder(folder_id), limit, after, filter).await
+    }
 
-        if fts_query.is_empty() {
-            return Ok(Vec::new());
+    /// Get message count for a folder with filters applied
+    pub async fn get_messages_filtered_count(
+        &self,
+        folder_id: i64,
+        filter: &MessageFilter,
+    ) -> CoreResult<i64> {
+        let mut conditions = vec!["m.folder_id = ?".to_string(), "m.snoozed_until IS NULL".to_string()];
+        conditions.extend(filter.build_conditions());
+        let where_clause = conditions.join(" AND ");
+        let query_str = format!(
+            "SELECT COUNT(*) as count FROM messages m WHERE {}",
+            where_clause
+        );
+        let mut query = sqlx::query(&query_str).bind(folder_id);
+        if !filter.from_contains.is_empty() {
+            let pattern = format!("%{}%", filter.from_contains);
+            query = query.bind(pattern.clone()).bind(pattern);
+        }
+        if let Some(after) = filter.date_after {
+            query = query.bind(after);
+        }
+        if let Some(before) = filter.date_before {
+            query = query.bind(before);
         }
+        let row = query.fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>("count"))
+    }
 
-        let messages = sqlx::query_as::<_, DbMessage>(
-            r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN messages_fts fts ON m.id = fts.rowid
-            WHERE messages_fts MATCH ? AND m.folder_id = ?
-            ORDER BY m.date_epoch DESC
-            LIMIT ?
-            "#,
+    /// Get message count across all inbox folders with filters applied
+    pub async fn get_inbox_messages_filtered_count(
+        &self,
+        filter: &MessageFilter,
+    ) -> CoreResult<i64> {
+        let mut conditions = vec![
+            "f.folder_type = 'inbox' AND f.unified_inbox = 1".to_string(),
+            "m.snoozed_until IS NULL".to_string(),
+        ];
+        conditions.extend(filter.build_conditions());
+        let where_clause = conditions.join(" AND ");
+        let query_str = format!(
+            r#"SELECT COUNT(*) as count FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE {}"#,
+            where_clause
+        );
+        let mut query = sqlx::query(&query_str);
+        if !filter.from_contains.is_empty() {
+            let pattern = format!("%{}%", filter.from_contains);
+            query = query.bind(pattern.clone()).bind(pattern);
+        }
+        if let Some(after) = filter.date_after {
+            query = query.bind(after);
+        }
+        if let Some(before) = filter.date_before {
+            query = query.bind(before);
+        }
+        let row = query.fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>("count"))
+    }
+
+    /// Get the Drafts folder path for an account
+    pub async fn get_drafts_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
+        let row = sqlx::query(
+            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'drafts' LIMIT 1",
         )
-        .bind(&fts_query)
-        .bind(folder_id)
-        .bind(limit)
-        .fetch_all(&self.pool)
+        .bind(account_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(messages)
+        Ok(row.map(|r| r.get::<String, _>("full_path")))
     }
 
-    /// Update message read status
-    pub async fn set_message_read(&self, message_id: i64, is_read: bool) -> CoreResult<()> {
-        sqlx::query("UPDATE messages SET is_read = ?, updated_at = datetime('now') WHERE id = ?")
-            .bind(is_read)
-            .bind(message_id)
-            .execute(&self.pool)
-            .await?;
-
-        // Update the folder's unread_count
-        let delta: i64 = if is_read { -1 } else { 1 };
-        sqlx::query(
-            r#"
-            UPDATE folders SET unread_count = MAX(0, COALESCE(unread_count, 0) + ?)
-            WHERE id = (SELECT folder_id FROM messages WHERE id = ?)
-            "#,
+    /// Get the trash folder path for an account
+    pub async fn get_trash_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
+        let row = sqlx::query(
+            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'trash' LIMIT 1",
         )
-        .bind(delta)
-        .bind(message_id)
-        .execute(&self.pool)
+        .bind(account_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(())
+        Ok(row.map(|r| r.get::<String, _>("full_path")))
     }
 
-    /// Update read status by folder_id + UID (for Graph messages where DB id may be 0)
-    pub async fn set_message_read_by_uid(&self, folder_id: i64, uid: i64, is_read: bool) -> CoreResult<()> {
-        sqlx::query("UPDATE messages SET is_read = ?, updated_at = datetime('now') WHERE folder_id = ? AND uid = ?")
-            .bind(is_read)
-            .bind(folder_id)
-            .bind(uid)
-            .execute(&self.pool)
-            .await?;
-
-        // Update the folder's unread_count
-        let delta: i64 = if is_read { -1 } else { 1 };
-        sqlx::query(
-            "UPDATE folders SET unread_count = MAX(0, COALESCE(unread_count, 0) + ?) WHERE id = ?",
+    /// Get the archive folder path for an account
+    pub async fn get_archive_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
+        let row = sqlx::query(
+            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'archive' LIMIT 1",
         )
-        .bind(delta)
-        .bind(folder_id)
-        .execute(&self.pool)
+        .bind(account_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(())
+        Ok(row.map(|r| r.get::<String, _>("full_path")))
     }
 
-    /// Update message starred status
-    pub async fn set_message_starred(&self, message_id: i64, is_starred: bool) -> CoreResult<()> {
-        sqlx::query(
-            "UPDATE messages SET is_starred = ?, updated_at = datetime('now') WHERE id = ?",
+    /// Get the Sent folder path for an account
+    pub async fn get_sent_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
+        let row = sqlx::query(
+            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'sent' LIMIT 1",
         )
-        .bind(is_starred)
-        .bind(message_id)
-        .execute(&self.pool)
+        .bind(account_id)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(())
+
+        Ok(row.map(|r| r.get::<String, _>("full_path")))
     }
 
-    /// Update message has_attachments flag (corrected after body parsing)
-    pub async fn set_message_has_attachments_by_uid(
-        &self,
-        folder_id: i64,
-        uid: i64,
-        has_attachments: bool,
-    ) -> CoreResult<()> {
-        sqlx::query(
-            "UPDATE messages SET has_attachments = ?, updated_at = datetime('now') WHERE folder_id = ? AND uid = ?",
+    /// Get the spam/junk folder path for an account
+    pub async fn get_spam_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
+        let row = sqlx::query(
+            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'spam' LIMIT 1",
         )
-        .bind(has_attachments)
-        .bind(folder_id)
-        .bind(uid)
-        .execute(&self.pool)
+        .bind(account_id)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(())
+
+        Ok(row.map(|r| r.get::<String, _>("full_path")))
     }
 
-    /// Get the has_attachments flag for a message
-    pub async fn get_message_has_attachments(
-        &self,
-        folder_id: i64,
-        uid: i64,
-    ) -> CoreResult<bool> {
-        let result: Option<(bool,)> = sqlx::query_as(
-            "SELECT has_attachments FROM messages WHERE folder_id = ? AND uid = ?",
+    /// Get the distinct recipient domains of cached Sent messages (send history)
+    pub async fn get_sent_recipient_domains(&self) -> CoreResult<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT m.to_addresses, m.cc_addresses FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.folder_type = 'sent'
+            "#,
         )
-        .bind(folder_id)
-        .bind(uid)
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(result.map(|(v,)| v).unwrap_or(false))
+        let mut domains: Vec<String> = Vec::new();
+        for row in rows {
+            for column in ["to_addresses", "cc_addresses"] {
+                if let Some(list) = row.get::<Option<String>, _>(column) {
+                    for domain in crate::recipient_check::domains_in(&list) {
+                        if !domains.contains(&domain) {
+                            domains.push(domain);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(domains)
     }
 
-    /// Delete a single message by ID
-    pub async fn delete_message(&self, message_id: i64) -> CoreResult<()> {
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(message_id)
-            .execute(&self.pool)
+    /// Count the messages cached since the last call into the recipient
+    /// frecency table (see [`crate::recipients`]), a batch per transaction.
+    /// Returns how many messages were looked at; the first call after the
+    /// table was added goes through the whole cache.
+    pub async fn index_recipients(&self) -> CoreResult<usize> {
+        const BATCH: i64 = 2000;
+        let now = chrono::Utc::now().timestamp();
+        let mut indexed = 0;
+        loop {
+            let mut tx = self.pool.begin().await?;
+            let last: i64 =
+                sqlx::query_scalar("SELECT last_message_id FROM recipients_indexed WHERE id = 1")
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let rows = sqlx::query(
+                r#"
+                SELECT m.id, m.message_id, m.from_address, m.from_name, m.to_addresses,
+                       m.cc_addresses, m.date_epoch, f.folder_type, a.email_address
+                FROM messages m
+                JOIN folders f ON m.folder_id = f.id
+                LEFT JOIN accounts a ON a.id = f.account_id
+                WHERE m.id > ?
+                ORDER BY m.id
+                LIMIT ?
+                "#,
+            )
+            .bind(last)
+            .bind(BATCH)
+            .fetch_all(&mut *tx)
             .await?;
-        Ok(())
-    }
+            let Some(last_row) = rows.last() else {
+                break;
+            };
+            let last_id: i64 = last_row.get("id");
+
+            for row in &rows {
+                let folder_type: String = row.get("folder_type");
+                let own_address: Option<String> = row.get("email_address");
+                let from_address: Option<String> = row.get("from_address");
+                let from_name: Option<String> = row.get("from_name");
+                let to: Option<String> = row.get("to_addresses");
+                let cc: Option<String> = row.get("cc_addresses");
+                let Some(correspondence) = recipients::correspondence(
+                    &folder_type,
+                    own_address.as_deref(),
+                    from_address.as_deref(),
+                    from_name.as_deref(),
+                    to.as_deref(),
+                    cc.as_deref(),
+                ) else {
+                    continue;
+                };
+                // Counted already from another folder
+                if let Some(message_id) = row.get::<Option<String>, _>("message_id") {
+                    let inserted = sqlx::query(
+                        "INSERT OR IGNORE INTO recipient_messages (key) VALUES (?)",
+                    )
+                    .bind(recipients::message_key(&message_id))
+                    .execute(&mut *tx)
+                    .await?;
+                    if inserted.rows_affected() == 0 {
+                        continue;
+                    }
+                }
 
-    /// Delete a single message by folder_id and IMAP UID
-    /// More reliable than delete_message() since the UID is always known from IMAP
-    pub async fn delete_message_by_uid(&self, folder_id: i64, uid: i64) -> CoreResult<()> {
-        // Check if message is unread before deleting, to update folder count
-        let is_unread: bool = sqlx::query_scalar(
-            "SELECT CASE WHEN is_read = 0 THEN 1 ELSE 0 END FROM messages WHERE folder_id = ? AND uid = ?",
-        )
-        .bind(folder_id)
-        .bind(uid)
-        .fetch_optional(&self.pool)
-        .await?
-        .unwrap_or(false);
+                let used = row
+                    .get::<Option<i64>, _>("date_epoch")
+                    .map_or(0, |date| date.min(now));
+                let (sent, received) = if correspondence.sent { (1, 0) } else { (0, 1) };
+                for address in correspondence.addresses {
+                    sqlx::query(
+                        r#"
+                        INSERT INTO recipients (email, name, sent_count, received_count, last_used)
+                        VALUES (?, ?, ?, ?, ?)
+                        ON CONFLICT(email) DO UPDATE SET
+                            name = CASE WHEN excluded.last_used >= last_used
+                                THEN COALESCE(excluded.name, name)
+                                ELSE COALESCE(name, excluded.name) END,
+                            sent_count = sent_count + excluded.sent_count,
+                            received_count = received_count + excluded.received_count,
+                            last_used = MAX(last_used, excluded.last_used)
+                        "#,
+                    )
+                    .bind(&address.email)
+                    .bind(&address.name)
+                    .bind(sent)
+                    .bind(received)
+                    .bind(used)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
 
-        sqlx::query("DELETE FROM messages WHERE folder_id = ? AND uid = ?")
-            .bind(folder_id)
-            .bind(uid)
-            .execute(&self.pool)
-            .await?;
+            sqlx::query("UPDATE recipients_indexed SET last_message_id = ? WHERE id = 1")
+                .bind(last_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            indexed += rows.len();
+            if (rows.len() as i64) < BATCH {
+                break;
+            }
+        }
+        if indexed > 0 {
+            debug!("Indexed recipients of {} messages", indexed);
+        }
+        Ok(indexed)
+    }
 
-        // Decrement unread count if the deleted message was unread
-        if is_unread {
-            sqlx::query(
-                "UPDATE folders SET unread_count = MAX(0, COALESCE(unread_count, 0) - 1) WHERE id = ?",
-            )
-            .bind(folder_id)
-            .execute(&self.pool)
-            .await?;
+    /// People whose address, or a word of whose name, starts with `prefix`,
+    /// the `limit` most recently in touch; rank them with
+    /// [`recipients::merge_suggestions`]
+    pub async fn suggest_recipients(&self, prefix: &str, limit: i64) -> CoreResult<Vec<Recipient>> {
+        let prefix = prefix.trim();
+        if prefix.is_empty() {
+            return Ok(Vec::new());
         }
+        let escaped = prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let starts = format!("{}%", escaped);
+        let word_starts = format!("% {}%", escaped);
+        let rows = sqlx::query(
+            r#"
+            SELECT email, name, sent_count, received_count, last_used
+            FROM recipients
+            WHERE email LIKE ?1 ESCAPE '\' OR name LIKE ?1 ESCAPE '\' OR name LIKE ?2 ESCAPE '\'
+            ORDER BY last_used DESC
+            LIMIT ?3
+            "#,
+        )
+        .bind(&starts)
+        .bind(&word_starts)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
 
-        Ok(())
+        Ok(rows
+            .iter()
+            .map(|row| Recipient {
+                email: row.get("email"),
+                name: row.get("name"),
+                sent_count: row.get("sent_count"),
+                received_count: row.get("received_count"),
+                last_used: row.get("last_used"),
+            })
+            .collect())
     }
 
-    /// Delete messages by UID (for sync)
-    pub async fn delete_messages_not_in_uids(
+    /// The receipt policy the user chose for `email`, if any
+    pub async fn receipt_policy(&self, email: &str) -> CoreResult<Option<ReceiptPolicy>> {
+        let policy: Option<String> =
+            sqlx::query_scalar("SELECT policy FROM receipt_policies WHERE email = ?")
+                .bind(email.trim())
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(policy.as_deref().map(ReceiptPolicy::parse))
+    }
+
+    /// Set the receipt policy for `email`, or with `None` go back to the
+    /// default
+    pub async fn set_receipt_policy(
         &self,
-        folder_id: i64,
-        uids: &[i64],
-    ) -> CoreResult<u64> {
-        if uids.is_empty() {
-            let result = sqlx::query("DELETE FROM messages WHERE folder_id = ?")
-                .bind(folder_id)
+        email: &str,
+        policy: Option<ReceiptPolicy>,
+    ) -> CoreResult<()> {
+        match policy {
+            Some(policy) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO receipt_policies (email, policy) VALUES (?, ?)
+                    ON CONFLICT(email) DO UPDATE SET policy = excluded.policy
+                    "#,
+                )
+                .bind(email.trim())
+                .bind(policy.as_str())
                 .execute(&self.pool)
                 .await?;
-            return Ok(result.rows_affected());
-        }
-
-        // Use a temp table within a single connection to avoid SQLite's variable limit
-        let mut conn = self.pool.acquire().await?;
-
-        sqlx::query("CREATE TEMP TABLE IF NOT EXISTS _valid_uids (uid INTEGER PRIMARY KEY)")
-            .execute(&mut *conn)
-            .await?;
-        sqlx::query("DELETE FROM _valid_uids")
-            .execute(&mut *conn)
-            .await?;
-
-        // Insert UIDs in chunks of 500
-        for chunk in uids.chunks(500) {
-            let placeholders: String = chunk.iter().map(|_| "(?)").collect::<Vec<_>>().join(",");
-            let query = format!("INSERT OR IGNORE INTO _valid_uids (uid) VALUES {}", placeholders);
-            let mut q = sqlx::query(&query);
-            for uid in chunk {
-                q = q.bind(uid);
             }
-            q.execute(&mut *conn).await?;
+            None => {
+                sqlx::query("DELETE FROM receipt_policies WHERE email = ?")
+                    .bind(email.trim())
+                    .execute(&self.pool)
+                    .await?;
+            }
         }
+        Ok(())
+    }
 
-        let result = sqlx::query(
-            "DELETE FROM messages WHERE folder_id = ? AND uid NOT IN (SELECT uid FROM _valid_uids)"
+    /// Remember that a cached message asks for a receipt. A request already
+    /// known, answered or not, is left alone.
+    pub async fn save_receipt_request(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        request: &ReceiptRequest,
+    ) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO receipt_requests (message_id, notify_to, return_path_matches)
+            SELECT id, ?, ? FROM messages WHERE folder_id = ? AND uid = ?
+            "#,
         )
+        .bind(&request.notify_to)
+        .bind(request.return_path_matches)
         .bind(folder_id)
-        .execute(&mut *conn)
+        .bind(uid)
+        .execute(&self.pool)
         .await?;
-
-        sqlx::query("DELETE FROM _valid_uids").execute(&mut *conn).await?;
-
-        Ok(result.rows_affected())
+        Ok(())
     }
 
-    /// Get a folder by account ID and path
-    pub async fn get_folder_by_path(
+    /// The receipt a cached message asks for, unless it was sent or declined
+    pub async fn pending_receipt_request(
         &self,
-        account_id: &str,
-        folder_path: &str,
-    ) -> CoreResult<Option<DbFolder>> {
-        let folder = sqlx::query_as::<_, DbFolder>(
+        folder_id: i64,
+        uid: i64,
+    ) -> CoreResult<Option<ReceiptRequest>> {
+        let row = sqlx::query(
             r#"
-            SELECT id, account_id, name, full_path, folder_type, uidvalidity,
-                   uid_next, message_count, unread_count
-            FROM folders
-            WHERE account_id = ? AND full_path = ?
+            SELECT r.notify_to, r.return_path_matches FROM receipt_requests r
+            JOIN messages m ON m.id = r.message_id
+            WHERE m.folder_id = ? AND m.uid = ? AND r.status = 'pending'
             "#,
         )
-        .bind(account_id)
-        .bind(folder_path)
+        .bind(folder_id)
+        .bind(uid)
         .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(folder)
-    }
-
-    /// Get folder ID by path, creating the folder if it doesn't exist
-    pub async fn get_or_create_folder_id(
-        &self,
-        account_id: &str,
-        folder_path: &str,
-    ) -> CoreResult<i64> {
-        // Try to find existing folder
-        if let Some(folder) = self.get_folder_by_path(account_id, folder_path).await? {
-            return Ok(folder.id);
-        }
+        .await?;
+        Ok(row.map(|row| ReceiptRequest {
+            notify_to: row.get("notify_to"),
+            return_path_matches: row.get("return_path_matches"),
+        }))
+    }
 
-        // Create new folder entry
-        let folder_name = folder_path.rsplit('/').next().unwrap_or(folder_path);
-        let folder_type = Self::guess_folder_type(folder_path);
-        self.upsert_folder(account_id, folder_name, folder_path, &folder_type)
-            .await
+    /// Record that the receipt a message asks for was sent, or declined, so
+    /// the user isn't asked again
+    pub async fn finish_receipt_request(&self, folder_id: i64, uid: i64, sent: bool) -> CoreResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE receipt_requests SET status = ?
+            WHERE message_id = (SELECT id FROM messages WHERE folder_id = ? AND uid = ?)
+            "#,
+        )
+        .bind(if sent { "sent" } else { "declined" })
+        .bind(folder_id)
+        .bind(uid)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
     }
 
-    /// Delete a folder and all its messages from the database
-    pub async fn delete_folder_by_path(
+    /// Remember that a message sent with `message_id` asked for a receipt
+    pub async fn track_requested_receipt(
         &self,
         account_id: &str,
-        full_path: &str,
+        message_id: &str,
+        subject: &str,
+        recipients: &str,
+        sent_at: i64,
     ) -> CoreResult<()> {
-        // Delete the folder and all child folders (messages cascade via FK)
         sqlx::query(
-            "DELETE FROM folders WHERE account_id = ? AND (full_path = ? OR full_path LIKE ? OR full_path LIKE ?)",
+            r#"
+            INSERT OR REPLACE INTO requested_receipts (message_id, account_id, subject, recipients, sent_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
         )
+        .bind(message_id)
         .bind(account_id)
-        .bind(full_path)
-        .bind(format!("{}/%", full_path))
-        .bind(format!("{}.%", full_path))
+        .bind(subject)
+        .bind(recipients)
+        .bind(sent_at)
         .execute(&self.pool)
         .await?;
-
-        debug!("Deleted folder {} (and children) for account {}", full_path, account_id);
         Ok(())
     }
 
-    /// Delete all messages in a folder (by account_id and folder path), keeping the folder itself
-    pub async fn delete_messages_in_folder(
-        &self,
-        account_id: &str,
-        folder_path: &str,
-    ) -> CoreResult<u64> {
+    /// Match a receipt that came back to the message it is for. The first
+    /// one back counts; returns whether it was for a message being tracked.
+    pub async fn record_returned_receipt(&self, receipt: &ReturnedReceipt, now: i64) -> CoreResult<bool> {
         let result = sqlx::query(
             r#"
-            DELETE FROM messages WHERE folder_id IN (
-                SELECT id FROM folders WHERE account_id = ? AND full_path = ?
-            )
+            UPDATE requested_receipts SET returned_at = ?, disposition = ?
+            WHERE message_id = ? AND returned_at IS NULL
             "#,
         )
-        .bind(account_id)
-        .bind(folder_path)
+        .bind(now)
+        .bind(&receipt.disposition)
+        .bind(&receipt.original_message_id)
         .execute(&self.pool)
         .await?;
+        Ok(result.rows_affected() > 0)
+    }
 
-        // Reset folder counts to 0
+    /// The receipt asked for by the sent message with `message_id`, if it
+    /// asked for one
+    pub async fn requested_receipt(&self, message_id: &str) -> CoreResult<Option<RequestedReceipt>> {
+        let row = sqlx::query(
+            r#"
+            SELECT message_id, recipients, sent_at, returned_at, disposition
+            FROM requested_receipts WHERE message_id = ?
+            "#,
+        )
+        .bind(message_id.trim())
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| RequestedReceipt {
+            message_id: row.get("message_id"),
+            recipients: row.get("recipients"),
+            sent_at: row.get("sent_at"),
+            returned_at: row.get("returned_at"),
+            disposition: row.get("disposition"),
+        }))
+    }
+
+    /// Keep the invitation a cached message carries. Saving it again, when
+    /// the body is fetched again, keeps the user's answer.
+    pub async fn save_invite(&self, folder_id: i64, uid: i64, invite: &Invite) -> CoreResult<()> {
+        let organizer = invite
+            .organizer
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+        let attendees =
+            serde_json::to_string(&invite.attendees).map_err(|e| CoreError::DatabaseError(e.to_string()))?;
         sqlx::query(
-            "UPDATE folders SET message_count = 0, unread_count = 0 WHERE account_id = ? AND full_path = ?",
+            r#"
+            INSERT INTO invites (message_id, method, uid, sequence, recurrence_id, summary, location,
+                                 starts_at, ends_at, all_day, organizer, attendees, cancelled)
+            SELECT id, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ? FROM messages WHERE folder_id = ? AND uid = ?
+            ON CONFLICT(message_id) DO UPDATE SET
+                method = excluded.method, uid = excluded.uid, sequence = excluded.sequence,
+                recurrence_id = excluded.recurrence_id, summary = excluded.summary,
+                location = excluded.location, starts_at = excluded.starts_at, ends_at = excluded.ends_at,
+                all_day = excluded.all_day, organizer = excluded.organizer,
+                attendees = excluded.attendees, cancelled = excluded.cancelled
+            "#,
         )
-        .bind(account_id)
-        .bind(folder_path)
+        .bind(&invite.method)
+        .bind(&invite.uid)
+        .bind(invite.sequence)
+        .bind(&invite.recurrence_id)
+        .bind(&invite.summary)
+        .bind(&invite.location)
+        .bind(invite.starts_at)
+        .bind(invite.ends_at)
+        .bind(invite.all_day)
+        .bind(organizer)
+        .bind(attendees)
+        .bind(invite.cancelled)
+        .bind(folder_id)
+        .bind(uid)
         .execute(&self.pool)
         .await?;
-
-        debug!("Deleted {} messages in folder {} for account {}", result.rows_affected(), folder_path, account_id);
-        Ok(result.rows_affected())
+        Ok(())
     }
 
-    /// Check if a message is unread (by folder_id + uid)
-    pub async fn is_message_unread(&self, folder_id: i64, uid: i64) -> CoreResult<bool> {
-        let is_unread: bool = sqlx::query_scalar(
-            "SELECT CASE WHEN is_read = 0 THEN 1 ELSE 0 END FROM messages WHERE folder_id = ? AND uid = ?",
+    /// The invitation a cached message carries, with the user's answer
+    pub async fn invite(&self, folder_id: i64, uid: i64) -> CoreResult<Option<StoredInvite>> {
+        let row = sqlx::query(
+            r#"
+            SELECT i.* FROM invites i
+            JOIN messages m ON m.id = i.message_id
+            WHERE m.folder_id = ? AND m.uid = ?
+            "#,
         )
         .bind(folder_id)
         .bind(uid)
         .fetch_optional(&self.pool)
-        .await?
-        .unwrap_or(false);
-        Ok(is_unread)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let organizer: Option<String> = row.get("organizer");
+        let attendees: String = row.get("attendees");
+        let response: Option<String> = row.get("response");
+        Ok(Some(StoredInvite {
+            invite: Invite {
+                method: row.get("method"),
+                uid: row.get("uid"),
+                sequence: row.get("sequence"),
+                recurrence_id: row.get("recurrence_id"),
+                summary: row.get("summary"),
+                location: row.get("location"),
+                starts_at: row.get("starts_at"),
+                ends_at: row.get("ends_at"),
+                all_day: row.get("all_day"),
+                organizer: organizer.and_then(|json| serde_json::from_str::<Attendee>(&json).ok()),
+                attendees: serde_json::from_str(&attendees).unwrap_or_default(),
+                cancelled: row.get("cancelled"),
+            },
+            response: response.as_deref().and_then(Rsvp::parse),
+        }))
     }
 
-    /// Increment unread count for a folder (used when moving an unread message into a folder)
-    pub async fn increment_folder_unread(
-        &self,
-        account_id: &str,
-        folder_path: &str,
-    ) -> CoreResult<()> {
+    /// Record how the user answered the invitation a cached message carries
+    pub async fn set_invite_response(&self, folder_id: i64, uid: i64, response: Rsvp) -> CoreResult<()> {
         sqlx::query(
-            "UPDATE folders SET unread_count = COALESCE(unread_count, 0) + 1 WHERE account_id = ? AND full_path = ?",
+            r#"
+            UPDATE invites SET response = ?
+            WHERE message_id = (SELECT id FROM messages WHERE folder_id = ? AND uid = ?)
+            "#,
         )
-        .bind(account_id)
-        .bind(folder_path)
+        .bind(response.as_str())
+        .bind(folder_id)
+        .bind(uid)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
-    /// Delete folders not in the given set of paths (cleanup stale folders after sync)
-    pub async fn delete_stale_folders(
-        &self,
-        account_id: &str,
-        valid_paths: &[String],
-    ) -> CoreResult<u64> {
-        if valid_paths.is_empty() {
-            return Ok(0);
-        }
-        // Build a query with placeholders for the valid paths
-        let placeholders: Vec<&str> = valid_paths.iter().map(|_| "?").collect();
+    /// Messages cached more than once within `scope`, newest first (see
+    /// [`duplicates`]). Only copies sharing a Message-ID with another, or
+    /// without one, are looked at.
+    pub async fn find_duplicates(&self, scope: &DuplicateScope) -> CoreResult<Vec<DuplicateGroup>> {
+        let scope_sql = match scope {
+            DuplicateScope::Folder(_) => "f.id = ?",
+            DuplicateScope::Account(_) => "f.account_id = ?",
+            DuplicateScope::All => "1 = 1",
+        };
+        let skipped = duplicates::SKIPPED_FOLDER_TYPES
+            .iter()
+            .map(|folder_type| format!("'{}'", folder_type))
+            .collect::<Vec<_>>()
+            .join(", ");
         let query = format!(
-            "DELETE FROM folders WHERE account_id = ? AND full_path NOT IN ({})",
-            placeholders.join(", ")
+            r#"
+            SELECT m.id, m.folder_id, m.uid, f.account_id, f.full_path, f.folder_type,
+                   m.message_id, m.subject, m.from_address, m.to_addresses, m.date_epoch,
+                   m.has_attachments, m.is_starred, m.gmail_thread_id
+            FROM messages m JOIN folders f ON f.id = m.folder_id
+            WHERE {scope} AND f.folder_type NOT IN ({skipped})
+              AND (m.message_id IS NULL OR m.message_id = '' OR m.message_id IN (
+                  SELECT m.message_id FROM messages m JOIN folders f ON f.id = m.folder_id
+                  WHERE {scope} AND f.folder_type NOT IN ({skipped}) AND m.message_id IS NOT NULL
+                  GROUP BY m.message_id HAVING COUNT(*) > 1))
+            "#,
+            scope = scope_sql,
+            skipped = skipped,
         );
-        let mut q = sqlx::query(&query).bind(account_id);
-        for path in valid_paths {
-            q = q.bind(path);
+        // The scope is bound twice: for the copies, and for counting them
+        let q = sqlx::query(&query);
+        let q = match scope {
+            DuplicateScope::Folder(folder_id) => q.bind(*folder_id).bind(*folder_id),
+            DuplicateScope::Account(account_id) => q.bind(account_id).bind(account_id),
+            DuplicateScope::All => q,
+        };
+        let rows = q.fetch_all(&self.pool).await?;
+
+        let copies = rows
+            .iter()
+            .map(|row| {
+                let subject: Option<String> = row.get("subject");
+                let from_address: Option<String> = row.get("from_address");
+                let to_addresses: Option<String> = row.get("to_addresses");
+                let date_epoch: Option<i64> = row.get("date_epoch");
+                let has_attachments = row.get::<Option<i64>, _>("has_attachments").unwrap_or(0) != 0;
+                MessageCopy {
+                    id: row.get("id"),
+                    folder_id: row.get("folder_id"),
+                    uid: row.get::<i64, _>("uid") as u32,
+                    account_id: row.get("account_id"),
+                    folder_path: row.get("full_path"),
+                    folder_type: row.get("folder_type"),
+                    message_id: row.get::<Option<String>, _>("message_id").filter(|id| !id.is_empty()),
+                    content_hash: duplicates::content_hash(
+                        subject.as_deref(),
+                        from_address.as_deref(),
+                        to_addresses.as_deref(),
+                        date_epoch,
+                        has_attachments,
+                    ),
+                    subject: subject.unwrap_or_default(),
+                    from_address: from_address.unwrap_or_default(),
+                    date_epoch,
+                    is_starred: row.get::<Option<i64>, _>("is_starred").unwrap_or(0) != 0,
+                    gmail_thread_id: row.get("gmail_thread_id"),
+                }
+            })
+            .collect();
+        Ok(duplicates::group_duplicates(copies))
+    }
+
+    /// The duplicates in `groups` that are still safe to trash: the copy
+    /// kept is still cached, and the duplicate is still there unstarred.
+    /// Anything moved, deleted or starred since the search is left alone.
+    pub async fn confirm_duplicates(&self, groups: &[DuplicateGroup]) -> CoreResult<Vec<MessageCopy>> {
+        let mut confirmed = Vec::new();
+        for group in groups {
+            if self.cached_copy_starred(&group.keep).await?.is_none() {
+                continue;
+            }
+            for copy in &group.duplicates {
+                if self.cached_copy_starred(copy).await? == Some(false) {
+                    confirmed.push(copy.clone());
+                }
+            }
         }
-        let result = q.execute(&self.pool).await?;
-        Ok(result.rows_affected())
+        Ok(confirmed)
     }
 
-    /// Rename a folder path in the database, updating child folder paths too
-    pub async fn rename_folder_path(
+    /// Whether `copy` is starred, or `None` when it's no longer cached
+    /// where it was
+    async fn cached_copy_starred(&self, copy: &MessageCopy) -> CoreResult<Option<bool>> {
+        let starred: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT is_starred FROM messages WHERE id = ? AND folder_id = ? AND uid = ?")
+                .bind(copy.id)
+                .bind(copy.folder_id)
+                .bind(copy.uid as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(starred.map(|starred| starred.unwrap_or(0) != 0))
+    }
+
+    /// Get the sender of each cached message in a thread. Gmail threads are
+    /// found by the conversation id of the message replied to (`in_reply_to`),
+    /// others by normalized subject. Messages cached in several folders count once.
+    pub async fn get_thread_senders(
         &self,
         account_id: &str,
-        old_path: &str,
-        new_path: &str,
-    ) -> CoreResult<()> {
-        let new_name = new_path.rsplit('/').next()
-            .or_else(|| new_path.rsplit('.').next())
-            .unwrap_or(new_path);
-
-        // Update the folder itself
-        sqlx::query(
-            "UPDATE folders SET full_path = ?, name = ? WHERE account_id = ? AND full_path = ?",
-        )
-        .bind(new_path)
-        .bind(new_name)
-        .bind(account_id)
-        .bind(old_path)
-        .execute(&self.pool)
-        .await?;
-
-        // Update child folders whose paths start with old_path + delimiter
-        // Try both "/" and "." delimiters
-        for delim in &["/", "."] {
-            let old_prefix = format!("{}{}", old_path, delim);
-            let new_prefix = format!("{}{}", new_path, delim);
+        subject: &str,
+        in_reply_to: Option<&str>,
+    ) -> CoreResult<Vec<String>> {
+        let gmail_thread_id = match in_reply_to {
+            Some(message_id) => self.get_gmail_thread_id(account_id, message_id).await?,
+            None => None,
+        };
 
-            // Find child folders
-            let children: Vec<(i64, String)> = sqlx::query_as(
-                "SELECT id, full_path FROM folders WHERE account_id = ? AND full_path LIKE ?",
+        let rows = if let Some(thread_id) = gmail_thread_id {
+            sqlx::query(
+                r#"
+                SELECT m.message_id, m.subject, m.from_address FROM messages m
+                JOIN folders f ON m.folder_id = f.id
+                WHERE f.account_id = ? AND m.gmail_thread_id = ?
+                "#,
             )
             .bind(account_id)
-            .bind(format!("{}%", old_prefix))
+            .bind(thread_id)
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            let key = crate::thread::normalize_subject(subject);
+            if key.is_empty() {
+                return Ok(Vec::new());
+            }
+            let rows = sqlx::query(
+                r#"
+                SELECT m.message_id, m.subject, m.from_address FROM messages m
+                JOIN folders f ON m.folder_id = f.id
+                WHERE f.account_id = ? AND m.subject LIKE ?
+                "#,
+            )
+            .bind(account_id)
+            .bind(format!("%{}%", key))
             .fetch_all(&self.pool)
             .await?;
+            rows.into_iter()
+                .filter(|row| {
+                    let subject: Option<String> = row.get("subject");
+                    crate::thread::normalize_subject(subject.as_deref().unwrap_or("")) == key
+                })
+                .collect()
+        };
 
-            for (child_id, child_path) in children {
-                let updated_path = format!("{}{}", new_prefix, &child_path[old_prefix.len()..]);
-                let child_name = updated_path.rsplit(*delim).next().unwrap_or(&updated_path);
-                sqlx::query("UPDATE folders SET full_path = ?, name = ? WHERE id = ?")
-                    .bind(&updated_path)
-                    .bind(child_name)
-                    .bind(child_id)
-                    .execute(&self.pool)
-                    .await?;
+        let mut seen_ids: Vec<String> = Vec::new();
+        let mut senders = Vec::new();
+        for row in rows {
+            if let Some(message_id) = row.get::<Option<String>, _>("message_id") {
+                if seen_ids.contains(&message_id) {
+                    continue;
+                }
+                seen_ids.push(message_id);
+            }
+            if let Some(from) = row.get::<Option<String>, _>("from_address") {
+                senders.push(from);
             }
         }
-
-        debug!(
-            "Renamed folder {} -> {} for account {}",
-            old_path, new_path, account_id
-        );
-        Ok(())
+        Ok(senders)
     }
 
-    /// Get the graph_folder_id for a folder identified by account_id and full_path
-    pub async fn get_graph_folder_id_by_path(
-        &self,
-        account_id: &str,
-        folder_path: &str,
-    ) -> CoreResult<Option<String>> {
-        let result = sqlx::query_scalar::<_, Option<String>>(
-            "SELECT graph_folder_id FROM folders WHERE account_id = ? AND full_path = ?",
+    /// Gmail conversation id of a cached message, looked up by Message-ID
+    pub async fn get_gmail_thread_id(&self, account_id: &str, message_id: &str) -> CoreResult<Option<i64>> {
+        let row: Option<(i64,)> = sqlx::query_as(
+            r#"
+            SELECT m.gmail_thread_id FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND m.message_id = ? AND m.gmail_thread_id IS NOT NULL
+            LIMIT 1
+            "#,
         )
         .bind(account_id)
-        .bind(folder_path)
+        .bind(message_id)
         .fetch_optional(&self.pool)
         .await?;
-
-        Ok(result.flatten())
+        Ok(row.map(|r| r.0))
     }
 
-    /// Get all message UIDs in a folder (for sync comparison)
-    pub async fn get_message_uids(&self, folder_id: i64) -> CoreResult<Vec<i64>> {
-        let rows = sqlx::query("SELECT uid FROM messages WHERE folder_id = ?")
-            .bind(folder_id)
-            .fetch_all(&self.pool)
+    /// Give messages cached since the last call their [`thread::thread_key`],
+    /// a batch per transaction. Returns how many were keyed; the first call
+    /// after the column was added goes through the whole cache.
+    pub async fn index_thread_keys(&self) -> CoreResult<usize> {
+        const BATCH: i64 = 2000;
+        let mut indexed = 0;
+        loop {
+            let mut tx = self.pool.begin().await?;
+            let rows: Vec<(i64, Option<i64>, Option<String>)> = sqlx::query_as(
+                "SELECT id, gmail_thread_id, subject FROM messages WHERE thread_key IS NULL ORDER BY id LIMIT ?",
+            )
+            .bind(BATCH)
+            .fetch_all(&mut *tx)
             .await?;
-
-        Ok(rows.iter().map(|r| r.get::<i64, _>("uid")).collect())
+            for (id, gmail_thread_id, subject) in &rows {
+                sqlx::query("UPDATE messages SET thread_key = ? WHERE id = ?")
+                    .bind(thread::thread_key(*gmail_thread_id, subject.as_deref(), *id))
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            indexed += rows.len();
+            if (rows.len() as i64) < BATCH {
+                break;
+            }
+        }
+        if indexed > 0 {
+            debug!("Keyed threads of {} messages", indexed);
+        }
+        Ok(indexed)
     }
 
-    /// Get message count for a folder
-    pub async fn get_message_count(&self, folder_id: i64) -> CoreResult<i64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM messages WHERE folder_id = ?")
-            .bind(folder_id)
-            .fetch_one(&self.pool)
-            .await?;
-
-        Ok(row.get::<i64, _>("count"))
+    /// Threads with messages in a folder, by their newest message there,
+    /// each summed up over the whole account (replies in Sent count too)
+    pub async fn get_folder_threads(
+        &self,
+        folder_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> CoreResult<Vec<ThreadSummary>> {
+        self.index_thread_keys().await?;
+        let Some((account_id,)): Option<(String,)> =
+            sqlx::query_as("SELECT account_id FROM folders WHERE id = ?")
+                .bind(folder_id)
+                .fetch_optional(&self.pool)
+                .await?
+        else {
+            return Ok(Vec::new());
+        };
+        let keys: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT thread_key FROM messages
+            WHERE folder_id = ? AND thread_key IS NOT NULL AND snoozed_until IS NULL
+            GROUP BY thread_key
+            ORDER BY MAX(date_epoch) DESC, MAX(id) DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(folder_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+        let keys: Vec<String> = keys.into_iter().map(|(key,)| key).collect();
+        self.thread_summaries(&account_id, &keys).await
     }
 
-    /// Guess folder type from path
-    fn guess_folder_type(folder_path: &str) -> String {
-        let path_lower = folder_path.to_lowercase();
-        if path_lower == "inbox" {
-            "inbox".to_string()
-        } else if path_lower.contains("sent") {
-            "sent".to_string()
-        } else if path_lower.contains("draft") {
-            "drafts".to_string()
-        } else if path_lower.contains("trash") || path_lower.contains("deleted") {
-            "trash".to_string()
-        } else if path_lower.contains("spam") || path_lower.contains("junk") {
-            "spam".to_string()
-        } else if path_lower.contains("archive") {
-            "archive".to_string()
-        } else {
-            "other".to_string()
-        }
+    /// Summary of one thread, to redraw its row after a change
+    pub async fn get_thread_summary(
+        &self,
+        account_id: &str,
+        thread_key: &str,
+    ) -> CoreResult<Option<ThreadSummary>> {
+        self.index_thread_keys().await?;
+        Ok(self
+            .thread_summaries(account_id, &[thread_key.to_string()])
+            .await?
+            .pop())
     }
 
-    /// Get total message count for an account (across all folders)
-    pub async fn get_account_message_count(&self, account_id: &str) -> CoreResult<i64> {
-        let row = sqlx::query(
+    /// Account and thread key of a cached message
+    pub async fn get_message_thread(&self, message_id: i64) -> CoreResult<Option<(String, String)>> {
+        self.index_thread_keys().await?;
+        let row: Option<(String, String)> = sqlx::query_as(
             r#"
-            SELECT COUNT(*) as count FROM messages m
-            INNER JOIN folders f ON m.folder_id = f.id
-            WHERE f.account_id = ?
+            SELECT f.account_id, m.thread_key FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE m.id = ? AND m.thread_key IS NOT NULL
             "#,
         )
-        .bind(account_id)
-        .fetch_one(&self.pool)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
         .await?;
-
-        Ok(row.get::<i64, _>("count"))
+        Ok(row)
     }
 
-    /// Get total cached body count for an account
-    pub async fn get_account_body_count(&self, account_id: &str) -> CoreResult<i64> {
-        let row = sqlx::query(
+    /// Summaries of the threads `keys` of an account, in the order given.
+    /// Two queries whatever the number of threads: one for the counts and
+    /// newest message, one for the participants.
+    async fn thread_summaries(
+        &self,
+        account_id: &str,
+        keys: &[String],
+    ) -> CoreResult<Vec<ThreadSummary>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys_json =
+            serde_json::to_string(keys).map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+
+        let rows = sqlx::query(
             r#"
-            SELECT COUNT(*) as count FROM messages m
-            INNER JOIN folders f ON m.folder_id = f.id
-            WHERE f.account_id = ? AND (m.body_text IS NOT NULL OR m.body_html IS NOT NULL)
+            WITH thread_messages AS (
+                SELECT m.id, m.thread_key, m.message_id, m.subject, m.date_epoch,
+                       m.is_read, m.is_starred, m.has_attachments
+                FROM messages m
+                JOIN folders f ON m.folder_id = f.id
+                WHERE f.account_id = ? AND f.folder_type NOT IN ('trash', 'spam')
+                  AND m.thread_key IN (SELECT value FROM json_each(?))
+            ),
+            latest AS (
+                SELECT thread_key, id, subject,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY thread_key ORDER BY date_epoch DESC, id DESC
+                       ) AS position
+                FROM thread_messages
+            )
+            SELECT t.thread_key,
+                   COUNT(DISTINCT COALESCE(t.message_id, t.id)) AS message_count,
+                   COUNT(DISTINCT CASE WHEN t.is_read = 0
+                       THEN COALESCE(t.message_id, t.id) END) AS unread_count,
+                   MAX(t.date_epoch) AS latest_epoch,
+                   MAX(t.is_starred) AS is_starred,
+                   MAX(t.has_attachments) AS has_attachments,
+                   l.id AS latest_id,
+                   l.subject AS subject
+            FROM thread_messages t
+            JOIN latest l ON l.thread_key = t.thread_key AND l.position = 1
+            GROUP BY t.thread_key
             "#,
         )
         .bind(account_id)
-        .fetch_one(&self.pool)
+        .bind(&keys_json)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(row.get::<i64, _>("count"))
-    }
-
-    /// Get inbox message count for a specific account
-    pub async fn get_inbox_message_count_for_account(&self, account_id: &str) -> CoreResult<i64> {
-        let row = sqlx::query(
+        let participant_rows = sqlx::query(
             r#"
-            SELECT COUNT(*) as count FROM messages m
-            INNER JOIN folders f ON m.folder_id = f.id
-            WHERE f.account_id = ? AND f.folder_type = 'inbox'
+            SELECT m.thread_key, m.from_address, MAX(m.from_name) AS from_name,
+                   MIN(COALESCE(m.date_epoch, 0)) AS first_epoch, MIN(m.id) AS first_id
+            FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE f.account_id = ? AND f.folder_type NOT IN ('trash', 'spam')
+              AND m.thread_key IN (SELECT value FROM json_each(?))
+              AND m.from_address IS NOT NULL AND m.from_address != ''
+            GROUP BY m.thread_key, LOWER(m.from_address)
+            ORDER BY first_epoch, first_id
             "#,
         )
         .bind(account_id)
-        .fetch_one(&self.pool)
+        .bind(&keys_json)
+        .fetch_all(&self.pool)
         .await?;
+        let mut participants: HashMap<String, Vec<Address>> = HashMap::new();
+        for row in participant_rows {
+            let from_name: Option<String> = row.get("from_name");
+            participants
+                .entry(row.get("thread_key"))
+                .or_default()
+                .push(Address::new(
+                    from_name.as_deref().filter(|name| !name.is_empty()),
+                    row.get::<String, _>("from_address").as_str(),
+                ));
+        }
 
-        Ok(row.get::<i64, _>("count"))
+        let mut summaries: HashMap<String, ThreadSummary> = rows
+            .into_iter()
+            .map(|row| {
+                let thread_key: String = row.get("thread_key");
+                let summary = ThreadSummary {
+                    account_id: account_id.to_string(),
+                    participants: participants.remove(&thread_key).unwrap_or_default(),
+                    thread_key: thread_key.clone(),
+                    latest_id: row.get("latest_id"),
+                    subject: row.get("subject"),
+                    latest_epoch: row.get("latest_epoch"),
+                    message_count: row.get("message_count"),
+                    unread_count: row.get("unread_count"),
+                    is_starred: row.get::<Option<i64>, _>("is_starred").unwrap_or(0) != 0,
+                    has_attachments: row.get::<Option<i64>, _>("has_attachments").unwrap_or(0) != 0,
+                };
+                (thread_key, summary)
+            })
+            .collect();
+        Ok(keys.iter().filter_map(|key| summaries.remove(key)).collect())
     }
 
-    /// Get the latest inbox message for a specific account (for notifications)
-    pub async fn get_latest_inbox_message(&self, account_id: &str) -> CoreResult<Option<DbMessage>> {
-        let message = sqlx::query_as::<_, DbMessage>(
+    /// Mark every message of a thread read or unread, outside Trash and
+    /// Junk. Returns the messages that changed, to flag on the server too.
+    pub async fn set_thread_read(
+        &self,
+        account_id: &str,
+        thread_key: &str,
+        is_read: bool,
+    ) -> CoreResult<Vec<ThreadMessageRef>> {
+        let mut tx = self.pool.begin().await?;
+        let changed: Vec<(i64, i64, i64)> = sqlx::query_as(
             r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
+            SELECT m.id, m.folder_id, m.uid FROM messages m
             JOIN folders f ON m.folder_id = f.id
-            WHERE f.account_id = ? AND f.folder_type = 'inbox'
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT 1
+            WHERE f.account_id = ? AND f.folder_type NOT IN ('trash', 'spam')
+              AND m.thread_key = ? AND m.is_read != ?
             "#,
         )
         .bind(account_id)
-        .fetch_optional(&self.pool)
+        .bind(thread_key)
+        .bind(is_read)
+        .fetch_all(&mut *tx)
         .await?;
+        let changed = Self::thread_message_refs(changed);
+        let ids: Vec<i64> = changed.iter().map(|message| message.id).collect();
+        let ids_json =
+            serde_json::to_string(&ids).map_err(|e| CoreError::DatabaseError(e.to_string()))?;
 
-        Ok(message)
-    }
-
-    /// Get total unread count across all accounts (for window badge)
-    pub async fn get_total_unread_count(&self) -> CoreResult<i64> {
-        let row = sqlx::query(
+        sqlx::query(
             r#"
-            SELECT COUNT(*) as count FROM messages m
-            INNER JOIN folders f ON m.folder_id = f.id
-            WHERE f.folder_type = 'inbox' AND m.is_read = 0
+            UPDATE messages SET is_read = ?, updated_at = datetime('now')
+            WHERE id IN (SELECT value FROM json_each(?))
             "#,
         )
-        .fetch_one(&self.pool)
+        .bind(is_read)
+        .bind(&ids_json)
+        .execute(&mut *tx)
         .await?;
-
-        Ok(row.get::<i64, _>("count"))
-    }
-
-    /// Clear all cached data for an account
-    pub async fn clear_account_cache(&self, account_id: &str) -> CoreResult<()> {
-        // Delete messages first (foreign key constraint)
         sqlx::query(
             r#"
-            DELETE FROM messages WHERE folder_id IN (
-                SELECT id FROM folders WHERE account_id = ?
+            UPDATE folders SET
+                unread_count = (SELECT COUNT(*) FROM messages WHERE folder_id = folders.id AND is_read = 0)
+            WHERE id IN (
+                SELECT folder_id FROM messages WHERE id IN (SELECT value FROM json_each(?))
             )
             "#,
         )
-        .bind(account_id)
-        .execute(&self.pool)
+        .bind(&ids_json)
+        .execute(&mut *tx)
         .await?;
-
-        // Delete folders
-        sqlx::query("DELETE FROM folders WHERE account_id = ?")
-            .bind(account_id)
-            .execute(&self.pool)
-            .await?;
-
-        info!("Cleared cache for account {}", account_id);
-        Ok(())
+        tx.commit().await?;
+        self.record_pending_flags(&ids, Flag::Read, is_read).await?;
+
+        if !changed.is_empty() {
+            let mut folder_ids: Vec<i64> = changed.iter().map(|message| message.folder_id).collect();
+            folder_ids.sort_unstable();
+            folder_ids.dedup();
+            self.changes.send(Change::FlagsChanged { message_ids: ids });
+            self.changes.send(Change::FolderCountsChanged { folder_ids });
+        }
+        Ok(changed)
     }
 
-    /// Get messages across all inbox folders (for unified inbox)
-    pub async fn get_inbox_messages(
+    /// Star or unstar a thread. Starring stars its newest message (in every
+    /// folder it's cached in) unless some message already is; unstarring
+    /// clears every star. Trash and Junk are left alone. Returns the
+    /// messages that changed, to flag on the server too.
+    pub async fn set_thread_starred(
         &self,
-        limit: i64,
-        offset: i64,
-    ) -> CoreResult<Vec<DbMessage>> {
-        let messages = sqlx::query_as::<_, DbMessage>(
+        account_id: &str,
+        thread_key: &str,
+        is_starred: bool,
+    ) -> CoreResult<Vec<ThreadMessageRef>> {
+        let mut tx = self.pool.begin().await?;
+        let changed: Vec<(i64, i64, i64)> = if is_starred {
+            sqlx::query_as(
+                r#"
+                WITH thread_messages AS (
+                    SELECT m.id, m.folder_id, m.uid, m.message_id, m.date_epoch, m.is_starred
+                    FROM messages m
+                    JOIN folders f ON m.folder_id = f.id
+                    WHERE f.account_id = ? AND f.folder_type NOT IN ('trash', 'spam')
+                      AND m.thread_key = ?
+                ),
+                newest AS (
+                    SELECT id, message_id FROM thread_messages
+                    WHERE NOT EXISTS (SELECT 1 FROM thread_messages WHERE is_starred = 1)
+                    ORDER BY date_epoch DESC, id DESC
+                    LIMIT 1
+                )
+                SELECT t.id, t.folder_id, t.uid FROM thread_messages t, newest n
+                WHERE t.id = n.id OR t.message_id = n.message_id
+                "#,
+            )
+        } else {
+            sqlx::query_as(
+                r#"
+                SELECT m.id, m.folder_id, m.uid FROM messages m
+                JOIN folders f ON m.folder_id = f.id
+                WHERE f.account_id = ? AND f.folder_type NOT IN ('trash', 'spam')
+                  AND m.thread_key = ? AND m.is_starred = 1
+                "#,
+            )
+        }
+        .bind(account_id)
+        .bind(thread_key)
+        .fetch_all(&mut *tx)
+        .await?;
+        let changed = Self::thread_message_refs(changed);
+        let ids: Vec<i64> = changed.iter().map(|message| message.id).collect();
+        let ids_json =
+            serde_json::to_string(&ids).map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+
+        sqlx::query(
             r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE f.folder_type = 'inbox'
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?
+            UPDATE messages SET is_starred = ?, updated_at = datetime('now')
+            WHERE id IN (SELECT value FROM json_each(?))
             "#,
         )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&self.pool)
+        .bind(is_starred)
+        .bind(&ids_json)
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
+        self.record_pending_flags(&ids, Flag::Starred, is_starred)
+            .await?;
 
-        Ok(messages)
+        if !changed.is_empty() {
+            self.changes.send(Change::FlagsChanged { message_ids: ids });
+        }
+        Ok(changed)
     }
 
-    /// Get total message count across all inbox folders
-    pub async fn get_inbox_message_count(&self) -> CoreResult<i64> {
-        let row = sqlx::query(
-            r#"
-            SELECT COUNT(*) as count FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE f.folder_type = 'inbox'
-            "#,
+    fn thread_message_refs(rows: Vec<(i64, i64, i64)>) -> Vec<ThreadMessageRef> {
+        rows.into_iter()
+            .map(|(id, folder_id, uid)| ThreadMessageRef { id, folder_id, uid })
+            .collect()
+    }
+
+    /// Get the minimum UID in a folder (for resume sync)
+    pub async fn get_min_uid(&self, folder_id: i64) -> CoreResult<Option<u32>> {
+        let row = sqlx::query("SELECT MIN(uid) as min_uid FROM messages WHERE folder_id = ?")
+            .bind(folder_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get::<Option<i64>, _>("min_uid").map(|v| v as u32))
+    }
+
+    /// Get the folder_id for a message by its database ID
+    pub async fn get_message_folder_id(&self, message_id: i64) -> CoreResult<Option<i64>> {
+        let row: Option<(i64,)> = sqlx::query_as(
+            "SELECT folder_id FROM messages WHERE id = ?"
         )
-        .fetch_one(&self.pool)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(row.get::<i64, _>("count"))
+        Ok(row.map(|(folder_id,)| folder_id))
     }
 
-    /// Search messages using FTS scoped to inbox folders (for unified inbox)
-    pub async fn search_inbox_messages(
+    /// Batch update is_read, is_starred and tags from server flags by UID
+    /// within a transaction. Where a read or star change made here hasn't
+    /// reached the server and the server has the other value, `policy`
+    /// decides which is kept and the disagreement is reported. Returns the
+    /// cached messages whose flags actually changed, with their new flags.
+    pub async fn batch_update_flags(
         &self,
-        query: &str,
-        limit: i64,
-    ) -> CoreResult<Vec<DbMessage>> {
-        let fts_query = prepare_fts_query(query);
-        debug!("FTS inbox search: '{}' -> '{}'", query, fts_query);
-
-        if fts_query.is_empty() {
-            return Ok(Vec::new());
+        folder_id: i64,
+        flags: &[(u32, MessageFlags)],
+        policy: &FlagPolicy,
+    ) -> CoreResult<FlagUpdate> {
+        let mut update = FlagUpdate::default();
+        if flags.is_empty() {
+            return Ok(update);
         }
 
-        let messages = sqlx::query_as::<_, DbMessage>(
+        let mut tx = self.pool.begin().await?;
+        let pending: Vec<(i64, String, bool)> = sqlx::query_as(
             r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN messages_fts fts ON m.id = fts.rowid
-            JOIN folders f ON m.folder_id = f.id
-            WHERE messages_fts MATCH ? AND f.folder_type = 'inbox'
-            ORDER BY m.date_epoch DESC
-            LIMIT ?
+            SELECT m.uid, p.flag, p.value FROM pending_flags p
+            JOIN messages m ON m.id = p.message_id
+            WHERE m.folder_id = ?
             "#,
         )
-        .bind(&fts_query)
-        .bind(limit)
-        .fetch_all(&self.pool)
+        .bind(folder_id)
+        .fetch_all(&mut *tx)
         .await?;
+        let pending: HashMap<(u32, Flag), bool> = pending
+            .into_iter()
+            .filter_map(|(uid, flag, value)| Some(((uid as u32, Flag::parse(&flag)?), value)))
+            .collect();
+
+        for (uid, server_flags) in flags {
+            let mut message_flags = server_flags.clone();
+            for flag in [Flag::Read, Flag::Starred] {
+                let Some(&local) = pending.get(&(*uid, flag)) else {
+                    continue;
+                };
+                let server = flag.of(server_flags);
+                let resolution = policy.resolve(flag, local, server);
+                if !resolution.is_pending() {
+                    sqlx::query(
+                        r#"
+                        DELETE FROM pending_flags WHERE flag = ? AND message_id IN (
+                            SELECT id FROM messages WHERE folder_id = ? AND uid = ?
+                        )
+                        "#,
+                    )
+                    .bind(flag.as_str())
+                    .bind(folder_id)
+                    .bind(*uid as i64)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                if local != server {
+                    update.conflicts.push(FlagConflict {
+                        uid: *uid,
+                        flag,
+                        local,
+                        server,
+                        resolution,
+                    });
+                }
+                flag.set(&mut message_flags, resolution.value(local, server));
+            }
 
-        Ok(messages)
+            let tags = crate::tags::encode_tags(&message_flags.keywords());
+            let result = sqlx::query(
+                "UPDATE messages SET is_read = ?, is_starred = ?, tags = ?, updated_at = datetime('now') \
+                 WHERE folder_id = ? AND uid = ? AND (is_read IS NOT ? OR is_starred IS NOT ? OR tags IS NOT ?)",
+            )
+            .bind(message_flags.seen)
+            .bind(message_flags.flagged)
+            .bind(&tags)
+            .bind(folder_id)
+            .bind(*uid as i64)
+            .bind(message_flags.seen)
+            .bind(message_flags.flagged)
+            .bind(&tags)
+            .execute(&mut *tx)
+            .await;
+
+            match result {
+                Ok(r) => {
+                    if r.rows_affected() > 0 {
+                        update.changed.push((*uid, message_flags));
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to update flags for uid={}: {}", uid, e);
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(update)
     }
 
-    /// Get a folder by its ID (for resolving folder→account mapping in unified inbox)
-    pub async fn get_folder_by_id(&self, folder_id: i64) -> CoreResult<Option<DbFolder>> {
-        let folder = sqlx::query_as::<_, DbFolder>(
-            r#"
-            SELECT id, account_id, name, full_path, folder_type, uidvalidity,
-                   uid_next, message_count, unread_count
-            FROM folders
-            WHERE id = ?
-            "#,
+    // ── Starred messages ─────────────────────────────────────────────
+
+    /// Get starred messages across all accounts
+    pub async fn get_starred_messages(
+        &self,
+        limit: i64,
+        after: Option<PageCursor>,
+    ) -> CoreResult<Vec<DbMessage>> {
+        self.get_page(PageScope::Starred, limit, after, &MessageFilter::default())
+            .await
+    }
+
+    /// Get starred message count across all accounts
+    pub async fn get_starred_count(&self) -> CoreResult<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM messages WHERE is_starred = 1")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get::<i64, _>("count"))
+    }
+
+    /// Get starred messages for a specific account
+    pub async fn get_starred_messages_for_account(
+        &self,
+        account_id: &str,
+        limit: i64,
+        after: Option<PageCursor>,
+    ) -> CoreResult<Vec<DbMessage>> {
+        let scope = PageScope::StarredInAccount(account_id);
+        self.get_page(scope, limit, after, &MessageFilter::default()).await
+    }
+
+    /// Get starred message count for a specific account
+    pub async fn get_starred_count_for_account(&self, account_id: &str) -> CoreResult<i64> {
+        let row = sqlx::query(
+            r#"SELECT COUNT(*) as count FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE m.is_starred = 1 AND f.account_id = ?"#,
         )
-        .bind(folder_id)
-        .fetch_optional(&self.pool)
+        .bind(account_id)
+        .fetch_one(&self.pool)
         .await?;
-
-        Ok(folder)
+        Ok(row.get::<i64, _>("count"))
     }
 
-    /// Get messages for a folder with filters applied
-    pub async fn get_messages_filtered(
+    /// Get starred messages with filters applied (all accounts)
+    pub async fn get_starred_messages_filtered(
         &self,
-        folder_id: i64,
         limit: i64,
-        offset: i64,
+        after: Option<PageCursor>,
         filter: &MessageFilter,
     ) -> CoreResult<Vec<DbMessage>> {
-        let mut conditions = vec!["m.folder_id = ?".to_string()];
-        conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
-        let query_str = format!(
-            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            WHERE {}
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?"#,
+        self.get_page(PageScope::Starred, limit, after, filter).await
+    }
+
+    /// Get starred message count with filters (all accounts)
+    pub async fn get_starred_messages_filtered_count(
+        &self,
+        filter: &MessageFilter,
+    ) -> CoreResult<i64> {
+        let mut conditions = vec!["m.is_starred = 1".to_string()];
+        conditions.extend(filter.build_conditions());
+        let where_clause = conditions.join(" AND ");
+        let query_str = format!(
+            "SELECT COUNT(*) as count FROM messages m WHERE {}",
             where_clause
         );
-        let mut query = sqlx::query_as::<_, DbMessage>(&query_str).bind(folder_id);
+        let mut query = sqlx::query(&query_str);
         if !filter.from_contains.is_empty() {
             let pattern = format!("%{}%", filter.from_contains);
             query = query.bind(pattern.clone()).bind(pattern);
@@ -1890,24 +5880,40 @@ impl Database {
         if let Some(before) = filter.date_before {
             query = query.bind(before);
         }
-        let messages = query.bind(limit).bind(offset).fetch_all(&self.pool).await?;
-        Ok(messages)
+        let row = query.fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>("count"))
     }
 
-    /// Get message count for a folder with filters applied
-    pub async fn get_messages_filtered_count(
+    /// Get starred messages with filters applied (single account)
+    pub async fn get_starred_messages_for_account_filtered(
         &self,
-        folder_id: i64,
+        account_id: &str,
+        limit: i64,
+        after: Option<PageCursor>,
+        filter: &MessageFilter,
+    ) -> CoreResult<Vec<DbMessage>> {
+        self.get_page(PageScope::StarredInAccount(account_id), limit, after, filter).await
+    }
+
+    /// Get starred message count with filters (single account)
+    pub async fn get_starred_count_for_account_filtered(
+        &self,
+        account_id: &str,
         filter: &MessageFilter,
     ) -> CoreResult<i64> {
-        let mut conditions = vec!["m.folder_id = ?".to_string()];
+        let mut conditions = vec![
+            "m.is_starred = 1".to_string(),
+            "f.account_id = ?".to_string(),
+        ];
         conditions.extend(filter.build_conditions());
         let where_clause = conditions.join(" AND ");
         let query_str = format!(
-            "SELECT COUNT(*) as count FROM messages m WHERE {}",
+            r#"SELECT COUNT(*) as count FROM messages m
+            JOIN folders f ON m.folder_id = f.id
+            WHERE {}"#,
             where_clause
         );
-        let mut query = sqlx::query(&query_str).bind(folder_id);
+        let mut query = sqlx::query(&query_str).bind(account_id);
         if !filter.from_contains.is_empty() {
             let pattern = format!("%{}%", filter.from_contains);
             query = query.bind(pattern.clone()).bind(pattern);
@@ -1922,29 +5928,47 @@ impl Database {
         Ok(row.get::<i64, _>("count"))
     }
 
-    /// Get messages across all inbox folders with filters applied
-    pub async fn get_inbox_messages_filtered(
+    // ── Snoozed messages ─────────────────────────────────────────────
+
+    /// Snooze a message until `until` (Unix seconds), or bring it back
+    /// now with `None`
+    pub async fn set_message_snoozed(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        until: Option<i64>,
+    ) -> CoreResult<()> {
+        sqlx::query(
+            "UPDATE messages SET snoozed_until = ?, updated_at = datetime('now') \
+             WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(until)
+        .bind(folder_id)
+        .bind(uid)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Get snoozed messages across all accounts with filters applied
+    pub async fn get_snoozed_messages(
         &self,
         limit: i64,
-        offset: i64,
+        after: Option<PageCursor>,
         filter: &MessageFilter,
     ) -> CoreResult<Vec<DbMessage>> {
-        let mut conditions = vec!["f.folder_type = 'inbox'".to_string()];
+        self.get_page(PageScope::Snoozed, limit, after, filter).await
+    }
+
+    /// Get snoozed message count across all accounts with filters applied
+    pub async fn get_snoozed_count(&self, filter: &MessageFilter) -> CoreResult<i64> {
+        let mut conditions = vec!["m.snoozed_until IS NOT NULL".to_string()];
         conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
         let query_str = format!(
-            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE {}
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?"#,
-            where_clause
+            "SELECT COUNT(*) as count FROM messages m WHERE {}",
+            conditions.join(" AND ")
         );
-        let mut query = sqlx::query_as::<_, DbMessage>(&query_str);
+        let mut query = sqlx::query(&query_str);
         if !filter.from_contains.is_empty() {
             let pattern = format!("%{}%", filter.from_contains);
             query = query.bind(pattern.clone()).bind(pattern);
@@ -1955,23 +5979,91 @@ impl Database {
         if let Some(before) = filter.date_before {
             query = query.bind(before);
         }
-        let messages = query.bind(limit).bind(offset).fetch_all(&self.pool).await?;
+        let row = query.fetch_one(&self.pool).await?;
+        Ok(row.get::<i64, _>("count"))
+    }
+
+    /// (folder_id, uid) of every snoozed message
+    pub async fn get_snoozed_keys(&self) -> CoreResult<Vec<(i64, i64)>> {
+        let rows =
+            sqlx::query("SELECT folder_id, uid FROM messages WHERE snoozed_until IS NOT NULL")
+                .fetch_all(&self.pool)
+                .await?;
+        Ok(rows
+            .iter()
+            .map(|r| (r.get::<i64, _>("folder_id"), r.get::<i64, _>("uid")))
+            .collect())
+    }
+
+    /// When the next snoozed message comes back, if any is snoozed
+    pub async fn next_snooze_due(&self) -> CoreResult<Option<i64>> {
+        let due: Option<i64> = sqlx::query_scalar(
+            "SELECT MIN(snoozed_until) FROM messages WHERE snoozed_until IS NOT NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(due)
+    }
+
+    /// Bring back every message snoozed until `now` or earlier, returning
+    /// them
+    pub async fn take_due_snoozes(&self, now: i64) -> CoreResult<Vec<DbMessage>> {
+        let messages = sqlx::query_as::<_, DbMessage>(
+            r#"UPDATE messages SET snoozed_until = NULL, updated_at = datetime('now')
+            WHERE snoozed_until <= ?
+            RETURNING id, folder_id, uid, message_id, subject, from_address,
+                   from_name, to_addresses, cc_addresses, date_sent, date_epoch, snippet,
+                   is_read, is_starred, has_attachments, size, maildir_path,
+                   body_text, body_html, gmail_labels, gmail_thread_id, mention, tags,
+                   snoozed_until, reply_later_at"#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
         Ok(messages)
     }
 
-    /// Get message count across all inbox folders with filters applied
-    pub async fn get_inbox_messages_filtered_count(
+    // ── Reply later ──────────────────────────────────────────────────
+
+    /// Mark a message to reply to at `at` (Unix seconds), or unmark it
+    /// with `None`
+    pub async fn set_message_reply_later(
+        &self,
+        folder_id: i64,
+        uid: i64,
+        at: Option<i64>,
+    ) -> CoreResult<()> {
+        sqlx::query(
+            "UPDATE messages SET reply_later_at = ?, updated_at = datetime('now') \
+             WHERE folder_id = ? AND uid = ?",
+        )
+        .bind(at)
+        .bind(folder_id)
+        .bind(uid)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Get messages marked to reply to later across all accounts with
+    /// filters applied
+    pub async fn get_reply_later_messages(
         &self,
+        limit: i64,
+        after: Option<PageCursor>,
         filter: &MessageFilter,
-    ) -> CoreResult<i64> {
-        let mut conditions = vec!["f.folder_type = 'inbox'".to_string()];
+    ) -> CoreResult<Vec<DbMessage>> {
+        self.get_page(PageScope::ReplyLater, limit, after, filter).await
+    }
+
+    /// Get the count of messages marked to reply to later across all
+    /// accounts with filters applied
+    pub async fn get_reply_later_count(&self, filter: &MessageFilter) -> CoreResult<i64> {
+        let mut conditions = vec!["m.reply_later_at IS NOT NULL".to_string()];
         conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
         let query_str = format!(
-            r#"SELECT COUNT(*) as count FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE {}"#,
-            where_clause
+            "SELECT COUNT(*) as count FROM messages m WHERE {}",
+            conditions.join(" AND ")
         );
         let mut query = sqlx::query(&query_str);
         if !filter.from_contains.is_empty() {
@@ -1988,231 +6080,360 @@ impl Database {
         Ok(row.get::<i64, _>("count"))
     }
 
-    /// Get the Drafts folder path for an account
-    pub async fn get_drafts_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
-        let row = sqlx::query(
-            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'drafts' LIMIT 1",
+    /// When the next reply is due, if any message is marked
+    pub async fn next_reply_due(&self) -> CoreResult<Option<i64>> {
+        let due: Option<i64> = sqlx::query_scalar(
+            "SELECT MIN(reply_later_at) FROM messages WHERE reply_later_at IS NOT NULL",
         )
-        .bind(account_id)
-        .fetch_optional(&self.pool)
+        .fetch_one(&self.pool)
         .await?;
+        Ok(due)
+    }
 
-        Ok(row.map(|r| r.get::<String, _>("full_path")))
+    /// Unmark every message whose reply is due at `now` or earlier,
+    /// returning them
+    pub async fn take_due_replies(&self, now: i64) -> CoreResult<Vec<DbMessage>> {
+        let messages = sqlx::query_as::<_, DbMessage>(
+            r#"UPDATE messages SET reply_later_at = NULL, updated_at = datetime('now')
+            WHERE reply_later_at <= ?
+            RETURNING id, folder_id, uid, message_id, subject, from_address,
+                   from_name, to_addresses, cc_addresses, date_sent, date_epoch, snippet,
+                   is_read, is_starred, has_attachments, size, maildir_path,
+                   body_text, body_html, gmail_labels, gmail_thread_id, mention, tags,
+                   snoozed_until, reply_later_at"#,
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(messages)
     }
 
-    /// Get the trash folder path for an account
-    pub async fn get_trash_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
-        let row = sqlx::query(
-            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'trash' LIMIT 1",
+    // ── Outbox ───────────────────────────────────────────────────────
+
+    /// Queue a message to be sent at `send_at` (Unix seconds).
+    /// `message_json` is the serialized message; `subject` and
+    /// `recipients` are what the outbox shows. Returns its id.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn queue_outgoing(
+        &self,
+        account_id: &str,
+        subject: &str,
+        recipients: &str,
+        message_json: &str,
+        draft_uid: Option<u32>,
+        send_at: i64,
+        now: i64,
+    ) -> CoreResult<i64> {
+        let result = sqlx::query(
+            "INSERT INTO outbox (account_id, subject, recipients, message_json, draft_uid, status, send_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(account_id)
-        .fetch_optional(&self.pool)
+        .bind(subject)
+        .bind(recipients)
+        .bind(message_json)
+        .bind(draft_uid.map(i64::from))
+        .bind(OutboxStatus::waiting(send_at, now).as_str())
+        .bind(send_at)
+        .execute(&self.pool)
         .await?;
+        Ok(result.last_insert_rowid())
+    }
 
-        Ok(row.map(|r| r.get::<String, _>("full_path")))
+    /// Every message in the outbox, the next one due first
+    pub async fn get_outbox(&self) -> CoreResult<Vec<OutboxItem>> {
+        let rows = sqlx::query(
+            "SELECT id, account_id, subject, recipients, status, send_at, error, attempts, draft_uid \
+             FROM outbox ORDER BY send_at, id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(outbox_item).collect())
     }
 
-    /// Get the archive folder path for an account
-    pub async fn get_archive_folder(&self, account_id: &str) -> CoreResult<Option<String>> {
-        let row = sqlx::query(
-            "SELECT full_path FROM folders WHERE account_id = ? AND folder_type = 'archive' LIMIT 1",
+    /// Mark the queued, scheduled and retrying messages due at `now` as
+    /// being sent, returning them with their serialized content
+    pub async fn take_due_outgoing(&self, now: i64) -> CoreResult<Vec<(OutboxItem, String)>> {
+        let rows = sqlx::query(
+            r#"UPDATE outbox SET status = 'sending', attempts = attempts + 1, error = NULL
+            WHERE status IN ('queued', 'scheduled', 'retrying') AND send_at <= ?
+            RETURNING id, account_id, subject, recipients, status, send_at, error, attempts,
+                      draft_uid, message_json"#,
         )
-        .bind(account_id)
-        .fetch_optional(&self.pool)
+        .bind(now)
+        .fetch_all(&self.pool)
         .await?;
+        let mut due: Vec<_> = rows
+            .iter()
+            .map(|row| (outbox_item(row), row.get::<String, _>("message_json")))
+            .collect();
+        due.sort_by_key(|(item, _)| (item.send_at, item.id));
+        Ok(due)
+    }
 
-        Ok(row.map(|r| r.get::<String, _>("full_path")))
+    /// Record why a message couldn't be sent; it waits for the user
+    pub async fn set_outgoing_failed(&self, id: i64, error: &str) -> CoreResult<()> {
+        sqlx::query("UPDATE outbox SET status = 'failed', error = ? WHERE id = ?")
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
-    /// Get the minimum UID in a folder (for resume sync)
-    pub async fn get_min_uid(&self, folder_id: i64) -> CoreResult<Option<u32>> {
-        let row = sqlx::query("SELECT MIN(uid) as min_uid FROM messages WHERE folder_id = ?")
-            .bind(folder_id)
-            .fetch_one(&self.pool)
+    /// Replace a queued message's content, as when its large attachments
+    /// have been uploaded and swapped for links, so a retry doesn't
+    /// upload them again
+    pub async fn update_outgoing_message(&self, id: i64, message_json: &str) -> CoreResult<()> {
+        sqlx::query("UPDATE outbox SET message_json = ? WHERE id = ?")
+            .bind(message_json)
+            .bind(id)
+            .execute(&self.pool)
             .await?;
+        Ok(())
+    }
 
-        Ok(row.get::<Option<i64>, _>("min_uid").map(|v| v as u32))
+    /// Record why a message couldn't be sent for now, and try it again at
+    /// `send_at` (see [`crate::outbox::retry_delay`])
+    pub async fn retry_outgoing(&self, id: i64, send_at: i64, error: &str) -> CoreResult<()> {
+        sqlx::query("UPDATE outbox SET status = 'retrying', send_at = ?, error = ? WHERE id = ?")
+            .bind(send_at)
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
-    /// Get the folder_id for a message by its database ID
-    pub async fn get_message_folder_id(&self, message_id: i64) -> CoreResult<Option<i64>> {
-        let row: Option<(i64,)> = sqlx::query_as(
-            "SELECT folder_id FROM messages WHERE id = ?"
+    /// Queue a message that failed or is scheduled to be sent at `now`.
+    /// A message being sent is left alone.
+    pub async fn send_outgoing_now(&self, id: i64, now: i64) -> CoreResult<()> {
+        sqlx::query(
+            "UPDATE outbox SET status = 'queued', send_at = ?, error = NULL \
+             WHERE id = ? AND status != 'sending'",
         )
-        .bind(message_id)
-        .fetch_optional(&self.pool)
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
         .await?;
+        Ok(())
+    }
 
-        Ok(row.map(|(folder_id,)| folder_id))
+    /// Take a message out of the outbox, once sent or to edit or cancel
+    /// it, returning its serialized content. A message being sent is only
+    /// removed with `sent`.
+    pub async fn remove_outgoing(&self, id: i64, sent: bool) -> CoreResult<Option<String>> {
+        let message_json: Option<String> = sqlx::query_scalar(
+            "DELETE FROM outbox WHERE id = ? AND (? OR status != 'sending') RETURNING message_json",
+        )
+        .bind(id)
+        .bind(sent)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(message_json)
     }
 
-    /// Batch update is_read and is_starred flags by UID within a transaction
-    pub async fn batch_update_flags(
-        &self,
-        folder_id: i64,
-        flags: &[(u32, bool, bool)],
-    ) -> CoreResult<usize> {
-        if flags.is_empty() {
-            return Ok(0);
-        }
+    /// Mark messages left being sent by a previous run as failed. Whether
+    /// they went out is unknown, so they aren't sent again unasked.
+    pub async fn fail_interrupted_outgoing(&self, error: &str) -> CoreResult<u64> {
+        let result = sqlx::query("UPDATE outbox SET status = 'failed', error = ? WHERE status = 'sending'")
+            .bind(error)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
 
-        let mut tx = self.pool.begin().await?;
-        let mut count = 0;
+    // ── Rules ────────────────────────────────────────────────────────
 
-        for &(uid, is_read, is_starred) in flags {
-            let result = sqlx::query(
-                "UPDATE messages SET is_read = ?, is_starred = ?, updated_at = datetime('now') WHERE folder_id = ? AND uid = ?",
-            )
-            .bind(is_read)
-            .bind(is_starred)
-            .bind(folder_id)
-            .bind(uid as i64)
-            .execute(&mut *tx)
-            .await;
+    /// All rules in the order they run
+    pub async fn get_rules(&self) -> CoreResult<Vec<crate::rules::Rule>> {
+        let rows = sqlx::query(
+            "SELECT id, name, account_id, enabled, match_mode, conditions_json, actions_json, \
+             stop_processing FROM rules ORDER BY position, id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-            match result {
-                Ok(r) => {
-                    if r.rows_affected() > 0 {
-                        count += 1;
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to update flags for uid={}: {}", uid, e);
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let id: i64 = row.get("id");
+                // A rule that no longer parses keeps no conditions, so it matches nothing
+                let conditions = serde_json::from_str(row.get("conditions_json")).unwrap_or_else(|e| {
+                    warn!("Ignoring conditions of rule {}: {}", id, e);
+                    Vec::new()
+                });
+                let actions = serde_json::from_str(row.get("actions_json")).unwrap_or_else(|e| {
+                    warn!("Ignoring actions of rule {}: {}", id, e);
+                    Vec::new()
+                });
+                crate::rules::Rule {
+                    id,
+                    name: row.get("name"),
+                    account_id: row.get("account_id"),
+                    enabled: row.get::<i64, _>("enabled") != 0,
+                    match_mode: crate::rules::MatchMode::parse(row.get("match_mode")),
+                    conditions,
+                    actions,
+                    stop_processing: row.get::<i64, _>("stop_processing") != 0,
                 }
-            }
-        }
-
-        tx.commit().await?;
-        Ok(count)
+            })
+            .collect())
     }
 
-    // ── Starred messages ─────────────────────────────────────────────
+    /// Store a rule, adding it after the others when its id is 0. Returns
+    /// its id.
+    pub async fn save_rule(&self, rule: &crate::rules::Rule) -> CoreResult<i64> {
+        let conditions = serde_json::to_string(&rule.conditions)
+            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
+        let actions = serde_json::to_string(&rule.actions)
+            .map_err(|e| CoreError::DatabaseError(e.to_string()))?;
 
-    /// Get starred messages across all accounts
-    pub async fn get_starred_messages(
-        &self,
-        limit: i64,
-        offset: i64,
-    ) -> CoreResult<Vec<DbMessage>> {
-        let messages = sqlx::query_as::<_, DbMessage>(
-            r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            WHERE m.is_starred = 1
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?
-            "#,
+        if rule.id == 0 {
+            let result = sqlx::query(
+                "INSERT INTO rules (name, account_id, enabled, match_mode, conditions_json, \
+                 actions_json, stop_processing, position) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(position), 0) + 1 FROM rules))",
+            )
+            .bind(&rule.name)
+            .bind(&rule.account_id)
+            .bind(rule.enabled)
+            .bind(rule.match_mode.as_str())
+            .bind(&conditions)
+            .bind(&actions)
+            .bind(rule.stop_processing)
+            .execute(&self.pool)
+            .await?;
+            return Ok(result.last_insert_rowid());
+        }
+
+        sqlx::query(
+            "UPDATE rules SET name = ?, account_id = ?, enabled = ?, match_mode = ?, \
+             conditions_json = ?, actions_json = ?, stop_processing = ? WHERE id = ?",
         )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&self.pool)
+        .bind(&rule.name)
+        .bind(&rule.account_id)
+        .bind(rule.enabled)
+        .bind(rule.match_mode.as_str())
+        .bind(&conditions)
+        .bind(&actions)
+        .bind(rule.stop_processing)
+        .bind(rule.id)
+        .execute(&self.pool)
         .await?;
+        Ok(rule.id)
+    }
 
-        Ok(messages)
+    pub async fn set_rule_enabled(&self, rule_id: i64, enabled: bool) -> CoreResult<()> {
+        sqlx::query("UPDATE rules SET enabled = ? WHERE id = ?")
+            .bind(enabled)
+            .bind(rule_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
-    /// Get starred message count across all accounts
-    pub async fn get_starred_count(&self) -> CoreResult<i64> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM messages WHERE is_starred = 1")
-            .fetch_one(&self.pool)
+    pub async fn delete_rule(&self, rule_id: i64) -> CoreResult<()> {
+        sqlx::query("DELETE FROM rules WHERE id = ?")
+            .bind(rule_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Run rules in the order of `rule_ids`; rules left out keep their place
+    /// after them
+    pub async fn reorder_rules(&self, rule_ids: &[i64]) -> CoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE rules SET position = position + ?")
+            .bind(rule_ids.len() as i64)
+            .execute(&mut *tx)
             .await?;
-        Ok(row.get::<i64, _>("count"))
+        for (position, id) in rule_ids.iter().enumerate() {
+            sqlx::query("UPDATE rules SET position = ? WHERE id = ?")
+                .bind(position as i64)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
     }
 
-    /// Get starred messages for a specific account
-    pub async fn get_starred_messages_for_account(
-        &self,
-        account_id: &str,
-        limit: i64,
-        offset: i64,
-    ) -> CoreResult<Vec<DbMessage>> {
-        let messages = sqlx::query_as::<_, DbMessage>(
-            r#"
-            SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE m.is_starred = 1 AND f.account_id = ?
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?
-            "#,
+    // ── Tags ─────────────────────────────────────────────────────────
+
+    /// Every tag with its message count, by name
+    pub async fn get_tags(&self) -> CoreResult<Vec<DbTag>> {
+        let tags = sqlx::query_as::<_, DbTag>(
+            "SELECT t.id, t.name, t.color, \
+             (SELECT COUNT(*) FROM message_tags mt WHERE mt.tag_id = t.id) AS message_count \
+             FROM tags t ORDER BY t.name",
         )
-        .bind(account_id)
-        .bind(limit)
-        .bind(offset)
         .fetch_all(&self.pool)
         .await?;
-
-        Ok(messages)
+        Ok(tags)
     }
 
-    /// Get starred message count for a specific account
-    pub async fn get_starred_count_for_account(&self, account_id: &str) -> CoreResult<i64> {
-        let row = sqlx::query(
-            r#"SELECT COUNT(*) as count FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE m.is_starred = 1 AND f.account_id = ?"#,
+    /// Add a tag, or give an existing one of that name a colour. Returns its id.
+    pub async fn create_tag(&self, name: &str, color: Option<&str>) -> CoreResult<i64> {
+        let id = sqlx::query_scalar(
+            "INSERT INTO tags (name, color) VALUES (?, ?) \
+             ON CONFLICT(name) DO UPDATE SET color = COALESCE(excluded.color, tags.color) \
+             RETURNING id",
         )
-        .bind(account_id)
+        .bind(name)
+        .bind(color)
         .fetch_one(&self.pool)
         .await?;
-        Ok(row.get::<i64, _>("count"))
+        Ok(id)
     }
 
-    /// Get starred messages with filters applied (all accounts)
-    pub async fn get_starred_messages_filtered(
+    /// Set a tag's colour; `None` goes back to its default one
+    pub async fn set_tag_color(&self, tag_id: i64, color: Option<&str>) -> CoreResult<()> {
+        sqlx::query("UPDATE tags SET color = ? WHERE id = ?")
+            .bind(color)
+            .bind(tag_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a tag from the list. Messages keep the keyword or label, and a
+    /// message carrying it that changes adds the tag back.
+    pub async fn delete_tag(&self, tag_id: i64) -> CoreResult<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM message_tags WHERE tag_id = ?")
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM tags WHERE id = ?")
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get messages carrying a tag across all accounts with filters applied
+    pub async fn get_tagged_messages(
         &self,
+        tag_id: i64,
         limit: i64,
-        offset: i64,
+        after: Option<PageCursor>,
         filter: &MessageFilter,
     ) -> CoreResult<Vec<DbMessage>> {
-        let mut conditions = vec!["m.is_starred = 1".to_string()];
-        conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
-        let query_str = format!(
-            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            WHERE {}
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?"#,
-            where_clause
-        );
-        let mut query = sqlx::query_as::<_, DbMessage>(&query_str);
-        if !filter.from_contains.is_empty() {
-            let pattern = format!("%{}%", filter.from_contains);
-            query = query.bind(pattern.clone()).bind(pattern);
-        }
-        if let Some(after) = filter.date_after {
-            query = query.bind(after);
-        }
-        if let Some(before) = filter.date_before {
-            query = query.bind(before);
-        }
-        let messages = query.bind(limit).bind(offset).fetch_all(&self.pool).await?;
-        Ok(messages)
+        self.get_page(PageScope::Tagged(tag_id), limit, after, filter).await
     }
 
-    /// Get starred message count with filters (all accounts)
-    pub async fn get_starred_messages_filtered_count(
-        &self,
-        filter: &MessageFilter,
-    ) -> CoreResult<i64> {
-        let mut conditions = vec!["m.is_starred = 1".to_string()];
+    /// Count messages carrying a tag across all accounts with filters applied
+    pub async fn get_tagged_count(&self, tag_id: i64, filter: &MessageFilter) -> CoreResult<i64> {
+        let mut conditions = vec![PageScope::Tagged(tag_id).condition().to_string()];
         conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
         let query_str = format!(
             "SELECT COUNT(*) as count FROM messages m WHERE {}",
-            where_clause
+            conditions.join(" AND ")
         );
-        let mut query = sqlx::query(&query_str);
+        let mut query = sqlx::query(&query_str).bind(tag_id);
         if !filter.from_contains.is_empty() {
             let pattern = format!("%{}%", filter.from_contains);
             query = query.bind(pattern.clone()).bind(pattern);
@@ -2227,90 +6448,358 @@ impl Database {
         Ok(row.get::<i64, _>("count"))
     }
 
-    /// Get starred messages with filters applied (single account)
-    pub async fn get_starred_messages_for_account_filtered(
-        &self,
-        account_id: &str,
-        limit: i64,
-        offset: i64,
-        filter: &MessageFilter,
-    ) -> CoreResult<Vec<DbMessage>> {
-        let mut conditions = vec![
-            "m.is_starred = 1".to_string(),
-            "f.account_id = ?".to_string(),
-        ];
-        conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
+    /// Set a Gmail message's labels in the cache, after storing them on the server
+    pub async fn set_message_gmail_labels(&self, message_id: i64, labels: &[String]) -> CoreResult<()> {
+        sqlx::query("UPDATE messages SET gmail_labels = ?, updated_at = datetime('now') WHERE id = ?")
+            .bind(crate::gmail::encode_labels(labels))
+            .bind(message_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Size of the cache, its search index and each account's row counts
+    pub async fn stats(&self) -> CoreResult<DatabaseStats> {
+        let (total_bytes, free_bytes) = self.page_bytes().await?;
+        let fts_bytes: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE((SELECT SUM(LENGTH(block)) FROM messages_fts_data), 0)
+                 + COALESCE((SELECT SUM(LENGTH(sz)) FROM messages_fts_docsize), 0)
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT f.account_id,
+                   COUNT(DISTINCT f.id) AS folders,
+                   COUNT(m.id) AS messages,
+                   COUNT(CASE WHEN m.body_text IS NOT NULL OR m.body_html IS NOT NULL THEN 1 END) AS bodies,
+                   (SELECT COUNT(*) FROM attachments a
+                    INNER JOIN messages am ON a.message_id = am.id
+                    INNER JOIN folders af ON am.folder_id = af.id
+                    WHERE af.account_id = f.account_id) AS attachments
+            FROM folders f
+            LEFT JOIN messages m ON m.folder_id = f.id
+            GROUP BY f.account_id
+            ORDER BY f.account_id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let accounts = rows
+            .iter()
+            .map(|row| AccountStats {
+                account_id: row.get("account_id"),
+                folders: row.get("folders"),
+                messages: row.get("messages"),
+                bodies: row.get("bodies"),
+                attachments: row.get("attachments"),
+            })
+            .collect();
+
+        Ok(DatabaseStats {
+            total_bytes,
+            free_bytes,
+            fts_bytes: fts_bytes.max(0) as u64,
+            accounts,
+            last_maintenance: self.last_maintenance().await?,
+        })
+    }
+
+    /// Size of each folder's cached mail, by account and path
+    pub async fn folder_cache_sizes(&self) -> CoreResult<Vec<FolderCacheSize>> {
         let query_str = format!(
-            r#"SELECT m.id, m.folder_id, m.uid, m.message_id, m.subject, m.from_address,
-                   m.from_name, m.to_addresses, m.cc_addresses, m.date_sent, m.date_epoch, m.snippet,
-                   m.is_read, m.is_starred, m.has_attachments, m.size, m.maildir_path,
-                   m.body_text, m.body_html
-            FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE {}
-            ORDER BY m.date_epoch DESC, m.uid DESC
-            LIMIT ? OFFSET ?"#,
-            where_clause
+            r#"
+            SELECT f.id AS folder_id, f.account_id, f.full_path,
+                   COUNT(m.id) AS messages,
+                   COUNT(CASE WHEN m.body_text IS NOT NULL OR m.body_html IS NOT NULL THEN 1 END) AS bodies,
+                   COALESCE(SUM({header}), 0) AS header_bytes,
+                   COALESCE(SUM({body}), 0) AS body_bytes
+            FROM folders f
+            LEFT JOIN messages m ON m.folder_id = f.id
+            GROUP BY f.id
+            ORDER BY f.account_id, f.full_path
+            "#,
+            header = HEADER_BYTES,
+            body = BODY_BYTES,
         );
-        let mut query = sqlx::query_as::<_, DbMessage>(&query_str).bind(account_id);
-        if !filter.from_contains.is_empty() {
-            let pattern = format!("%{}%", filter.from_contains);
-            query = query.bind(pattern.clone()).bind(pattern);
+        let rows = sqlx::query(&query_str).fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| FolderCacheSize {
+                folder_id: row.get("folder_id"),
+                account_id: row.get("account_id"),
+                full_path: row.get("full_path"),
+                messages: row.get("messages"),
+                bodies: row.get("bodies"),
+                header_bytes: row.get::<i64, _>("header_bytes").max(0) as u64,
+                body_bytes: row.get::<i64, _>("body_bytes").max(0) as u64,
+            })
+            .collect())
+    }
+
+    /// Drop an account's cached bodies as its retention policy asks at
+    /// `now`: first those older than the retention period, then the
+    /// oldest ones until the cache fits its size limit. Drafts, local
+    /// folders, which have no other copy, and messages marked to reply to
+    /// later keep their bodies.
+    pub async fn prune_cache(
+        &self,
+        account_id: &str,
+        policy: &RetentionPolicy,
+        now: i64,
+    ) -> CoreResult<PruneReport> {
+        let mut report = PruneReport::default();
+
+        if let Some(cutoff) = policy.body_cutoff(now) {
+            report.expired_bodies = sqlx::query(
+                r#"
+                UPDATE messages SET body_text = NULL, body_html = NULL, updated_at = datetime('now')
+                WHERE (body_text IS NOT NULL OR body_html IS NOT NULL)
+                  AND date_epoch < ? AND reply_later_at IS NULL
+                  AND folder_id IN (SELECT id FROM folders WHERE account_id = ? AND folder_type NOT IN ('drafts', ?))
+                "#,
+            )
+            .bind(cutoff)
+            .bind(account_id)
+            .bind(LOCAL_FOLDER_TYPE)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
         }
-        if let Some(after) = filter.date_after {
-            query = query.bind(after);
+
+        if policy.max_bytes.is_some() {
+            let header_bytes: i64 = sqlx::query_scalar(&format!(
+                "SELECT COALESCE(SUM({}), 0) FROM messages m \
+                 JOIN folders f ON m.folder_id = f.id WHERE f.account_id = ?",
+                HEADER_BYTES
+            ))
+            .bind(account_id)
+            .fetch_one(&self.pool)
+            .await?;
+            let budget = policy.body_budget(header_bytes.max(0) as u64).unwrap_or(u64::MAX);
+
+            // Bodies that are kept come first, so they count against the
+            // budget before any other body does
+            let query_str = format!(
+                r#"
+                UPDATE messages SET body_text = NULL, body_html = NULL, updated_at = datetime('now')
+                WHERE id IN (
+                    SELECT id FROM (
+                        SELECT m.id, {kept} AS kept,
+                               SUM({body}) OVER (ORDER BY {kept} DESC, m.date_epoch DESC, m.id DESC) AS running
+                        FROM messages m
+                        JOIN folders f ON m.folder_id = f.id
+                        WHERE f.account_id = ? AND (m.body_text IS NOT NULL OR m.body_html IS NOT NULL)
+                    )
+                    WHERE NOT kept AND running > ?
+                )
+                "#,
+                kept = "(f.folder_type IN ('drafts', 'local') OR m.reply_later_at IS NOT NULL)",
+                body = BODY_BYTES,
+            );
+            report.evicted_bodies = sqlx::query(&query_str)
+                .bind(account_id)
+                .bind(budget.min(i64::MAX as u64) as i64)
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
         }
-        if let Some(before) = filter.date_before {
-            query = query.bind(before);
+
+        if report.total() > 0 {
+            self.finish_purge().await?;
+            info!(
+                "Pruned cache of {}: {} expired and {} evicted bodies",
+                account_id, report.expired_bodies, report.evicted_bodies
+            );
         }
-        let messages = query.bind(limit).bind(offset).fetch_all(&self.pool).await?;
-        Ok(messages)
+        Ok(report)
     }
 
-    /// Get starred message count with filters (single account)
-    pub async fn get_starred_count_for_account_filtered(
-        &self,
-        account_id: &str,
-        filter: &MessageFilter,
-    ) -> CoreResult<i64> {
-        let mut conditions = vec![
-            "m.is_starred = 1".to_string(),
-            "f.account_id = ?".to_string(),
-        ];
-        conditions.extend(filter.build_conditions());
-        let where_clause = conditions.join(" AND ");
-        let query_str = format!(
-            r#"SELECT COUNT(*) as count FROM messages m
-            JOIN folders f ON m.folder_id = f.id
-            WHERE {}"#,
-            where_clause
-        );
-        let mut query = sqlx::query(&query_str).bind(account_id);
-        if !filter.from_contains.is_empty() {
-            let pattern = format!("%{}%", filter.from_contains);
-            query = query.bind(pattern.clone()).bind(pattern);
+    /// Compact the cache: checkpoint the write-ahead log, merge the search
+    /// index, refresh planner statistics and vacuum. `on_step` is called as
+    /// each step starts. The vacuum needs as much free disk space as the
+    /// database takes and blocks other writers while it runs.
+    pub async fn optimize(&self, mut on_step: impl FnMut(OptimizeStep)) -> CoreResult<()> {
+        on_step(OptimizeStep::Checkpoint);
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+
+        on_step(OptimizeStep::OptimizeIndex);
+        sqlx::query("INSERT INTO messages_fts(messages_fts) VALUES('optimize')")
+            .execute(&self.pool)
+            .await?;
+
+        on_step(OptimizeStep::Analyze);
+        sqlx::query("ANALYZE").execute(&self.pool).await?;
+
+        on_step(OptimizeStep::Vacuum);
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        // In WAL mode the vacuum goes through the log; fold it back in
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&self.pool)
+            .await?;
+
+        info!("Optimized database");
+        Ok(())
+    }
+
+    /// Size of the database file and of its free pages, as SQLite counts
+    /// its pages
+    async fn page_bytes(&self) -> CoreResult<(u64, u64)> {
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+            .fetch_one(&self.pool)
+            .await?;
+        let page_count: i64 = sqlx::query_scalar("PRAGMA page_count")
+            .fetch_one(&self.pool)
+            .await?;
+        let freelist_count: i64 = sqlx::query_scalar("PRAGMA freelist_count")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok((
+            (page_size * page_count).max(0) as u64,
+            (page_size * freelist_count).max(0) as u64,
+        ))
+    }
+
+    /// Run idle-time maintenance (see [`crate::maintenance`]): give free
+    /// pages back with an incremental vacuum, refresh the planner's
+    /// statistics, and check the database and its search index. A damaged
+    /// search index is rebuilt; a damaged database is flagged as
+    /// [`Self::is_corrupt`]. The report is kept for
+    /// [`Self::last_maintenance`].
+    pub async fn maintenance_report(&self) -> CoreResult<MaintenanceReport> {
+        let mut report = MaintenanceReport {
+            checked_at: chrono::Utc::now().timestamp(),
+            ..Default::default()
+        };
+
+        let auto_vacuum: i64 = sqlx::query_scalar("PRAGMA auto_vacuum")
+            .fetch_one(&self.pool)
+            .await?;
+        report.incremental_vacuum = auto_vacuum == 2;
+        if report.incremental_vacuum {
+            let (_, free_before) = self.page_bytes().await?;
+            sqlx::query("PRAGMA incremental_vacuum")
+                .execute(&self.pool)
+                .await?;
+            let (_, free_after) = self.page_bytes().await?;
+            report.reclaimed_bytes = free_before.saturating_sub(free_after);
         }
-        if let Some(after) = filter.date_after {
-            query = query.bind(after);
+
+        // Sampling keeps ANALYZE quick on a large cache; the limit has to be
+        // set on the connection that analyzes
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("PRAGMA analysis_limit = 1000")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("ANALYZE").execute(&mut *conn).await?;
+        drop(conn);
+
+        let results: Vec<String> = sqlx::query_scalar(&format!(
+            "PRAGMA integrity_check({})",
+            maintenance::MAX_INTEGRITY_ERRORS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        report.integrity_errors = results
+            .into_iter()
+            .filter(|result| result != "ok")
+            .collect();
+        if !report.is_sound() && !CORRUPTION_DETECTED.swap(true, Ordering::Relaxed) {
+            tracing::error!(
+                "Database integrity check failed: {}",
+                report.integrity_errors.join("; ")
+            );
         }
-        if let Some(before) = filter.date_before {
-            query = query.bind(before);
+
+        // The index only holds copies of message columns, so it can be
+        // rebuilt from them
+        if let Err(e) =
+            sqlx::query("INSERT INTO messages_fts(messages_fts) VALUES('integrity-check')")
+                .execute(&self.pool)
+                .await
+        {
+            warn!("Search index is damaged, rebuilding it: {}", e);
+            sqlx::query("INSERT INTO messages_fts(messages_fts) VALUES('rebuild')")
+                .execute(&self.pool)
+                .await?;
+            report.search_index_rebuilt = true;
         }
-        let row = query.fetch_one(&self.pool).await?;
-        Ok(row.get::<i64, _>("count"))
+
+        (report.total_bytes, report.free_bytes) = self.page_bytes().await?;
+
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO maintenance_runs
+                (id, checked_at, total_bytes, free_bytes, reclaimed_bytes,
+                 incremental_vacuum, integrity_errors, search_index_rebuilt)
+            VALUES (1, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(report.checked_at)
+        .bind(report.total_bytes as i64)
+        .bind(report.free_bytes as i64)
+        .bind(report.reclaimed_bytes as i64)
+        .bind(report.incremental_vacuum)
+        .bind(report.integrity_errors.join("\n"))
+        .bind(report.search_index_rebuilt)
+        .execute(&self.pool)
+        .await?;
+
+        info!(
+            "Database maintenance: {} bytes reclaimed, {} free, {} integrity problems",
+            report.reclaimed_bytes,
+            report.free_bytes,
+            report.integrity_errors.len()
+        );
+        Ok(report)
+    }
+
+    /// The report of the last [`Self::maintenance_report`], if it has run
+    pub async fn last_maintenance(&self) -> CoreResult<Option<MaintenanceReport>> {
+        let row = sqlx::query(
+            r#"
+            SELECT checked_at, total_bytes, free_bytes, reclaimed_bytes,
+                   incremental_vacuum, integrity_errors, search_index_rebuilt
+            FROM maintenance_runs WHERE id = 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| {
+            let errors: String = row.get("integrity_errors");
+            MaintenanceReport {
+                checked_at: row.get("checked_at"),
+                total_bytes: row.get::<i64, _>("total_bytes").max(0) as u64,
+                free_bytes: row.get::<i64, _>("free_bytes").max(0) as u64,
+                reclaimed_bytes: row.get::<i64, _>("reclaimed_bytes").max(0) as u64,
+                incremental_vacuum: row.get("incremental_vacuum"),
+                integrity_errors: errors.lines().map(str::to_string).collect(),
+                search_index_rebuilt: row.get("search_index_rebuilt"),
+            }
+        }))
     }
 
-    /// Clear all cached data
+    /// Clear all cached data, except local folders
     pub async fn clear_all_cache(&self) -> CoreResult<()> {
-        sqlx::query("DELETE FROM messages")
+        sqlx::query("DELETE FROM messages WHERE folder_id NOT IN (SELECT id FROM folders WHERE folder_type = ?)")
+            .bind(LOCAL_FOLDER_TYPE)
             .execute(&self.pool)
             .await?;
 
-        sqlx::query("DELETE FROM folders")
+        sqlx::query("DELETE FROM folders WHERE folder_type != ?")
+            .bind(LOCAL_FOLDER_TYPE)
             .execute(&self.pool)
             .await?;
 
+        self.finish_purge().await?;
         info!("Cleared all cache");
         Ok(())
     }
@@ -0,0 +1,309 @@
+//! Duplicate messages
+//!
+//! The same message can end up cached more than once: imported twice,
+//! copied into several folders, or delivered to two accounts. Copies are
+//! duplicates when they share a Message-ID and a [`content_hash`] of what
+//! the user sees (subject, sender, recipients, date), so two messages that
+//! happen to reuse a Message-ID aren't taken for one another.
+//!
+//! Gmail shows one message in every folder it has a label for. Copies in
+//! one Gmail account are label views of a single message, not duplicates,
+//! and count once; trashing that message trashes it under all its labels,
+//! which is what's wanted when another copy is kept elsewhere.
+//!
+//! A sent message and the copy of it received back (sent to oneself, or
+//! to a list) aren't duplicates either, nor is anything in Drafts, Junk or
+//! Trash.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Folder types left out of the search
+pub const SKIPPED_FOLDER_TYPES: &[&str] = &["drafts", "spam", "trash"];
+
+/// Which messages to look for duplicates among
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DuplicateScope {
+    /// One folder, by id
+    Folder(i64),
+    /// All folders of an account
+    Account(String),
+    /// Every account
+    All,
+}
+
+/// A cached copy of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageCopy {
+    /// Row id in the messages table
+    pub id: i64,
+    pub folder_id: i64,
+    pub uid: u32,
+    pub account_id: String,
+    pub folder_path: String,
+    pub folder_type: String,
+    pub message_id: Option<String>,
+    pub content_hash: String,
+    pub subject: String,
+    pub from_address: String,
+    pub date_epoch: Option<i64>,
+    pub is_starred: bool,
+    /// Set for copies in a Gmail account, where one message shows in
+    /// every folder it has a label for
+    pub gmail_thread_id: Option<i64>,
+}
+
+/// Copies of one message: the one kept and the ones that can go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub keep: MessageCopy,
+    /// Extra copies, best first; never starred ones
+    pub duplicates: Vec<MessageCopy>,
+}
+
+/// Hash of what the user sees of a message, to tell real duplicates from
+/// different messages sharing a Message-ID. Case and surrounding space
+/// don't count.
+pub fn content_hash(
+    subject: Option<&str>,
+    from_address: Option<&str>,
+    to_addresses: Option<&str>,
+    date_epoch: Option<i64>,
+    has_attachments: bool,
+) -> String {
+    let normalize = |text: Option<&str>| text.unwrap_or("").trim().to_lowercase();
+    let mut hasher = Sha256::new();
+    for part in [
+        normalize(subject),
+        normalize(from_address),
+        normalize(to_addresses),
+        date_epoch.map(|d| d.to_string()).unwrap_or_default(),
+        has_attachments.to_string(),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// `<ID@Host>` and `id@host` are the same Message-ID
+fn normalize_message_id(message_id: &str) -> String {
+    message_id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase()
+}
+
+/// How much a copy is worth keeping, lowest first: starred, then in the
+/// inbox, then filed in a folder, then in an archive; the first cached
+/// breaks ties
+fn keep_rank(copy: &MessageCopy) -> (bool, u8, i64) {
+    let folder = match copy.folder_type.as_str() {
+        "inbox" => 0,
+        "archive" => 2,
+        _ => 1,
+    };
+    (!copy.is_starred, folder, copy.id)
+}
+
+/// Group `copies` into messages cached more than once. Copies in one Gmail
+/// account count as one message (the best placed of them stands for it).
+/// Starred copies are never offered for removal.
+pub fn group_duplicates(copies: Vec<MessageCopy>) -> Vec<DuplicateGroup> {
+    let mut by_key: HashMap<(String, String, bool), Vec<MessageCopy>> = HashMap::new();
+    for copy in copies {
+        let message_id = copy
+            .message_id
+            .as_deref()
+            .map(normalize_message_id)
+            .unwrap_or_default();
+        let key = (
+            message_id,
+            copy.content_hash.clone(),
+            copy.folder_type == "sent",
+        );
+        by_key.entry(key).or_default().push(copy);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_key
+        .into_values()
+        .filter_map(|mut copies| {
+            copies.sort_by_key(keep_rank);
+            // One copy per Gmail account: the rest are label views of it
+            let mut gmail_accounts: Vec<String> = Vec::new();
+            copies.retain(|copy| {
+                if copy.gmail_thread_id.is_none() {
+                    return true;
+                }
+                if gmail_accounts.contains(&copy.account_id) {
+                    return false;
+                }
+                gmail_accounts.push(copy.account_id.clone());
+                true
+            });
+            if copies.len() < 2 {
+                return None;
+            }
+            let keep = copies.remove(0);
+            let duplicates: Vec<MessageCopy> =
+                copies.into_iter().filter(|copy| !copy.is_starred).collect();
+            (!duplicates.is_empty()).then_some(DuplicateGroup { keep, duplicates })
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.keep
+            .date_epoch
+            .cmp(&a.keep.date_epoch)
+            .then_with(|| a.keep.id.cmp(&b.keep.id))
+    });
+    groups
+}
+
+/// Number of copies that can go across `groups`
+pub fn duplicate_count(groups: &[DuplicateGroup]) -> usize {
+    groups.iter().map(|group| group.duplicates.len()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy(id: i64, account: &str, folder_type: &str, message_id: Option<&str>) -> MessageCopy {
+        MessageCopy {
+            id,
+            folder_id: id * 10,
+            uid: id as u32,
+            account_id: account.to_string(),
+            folder_path: folder_type.to_uppercase(),
+            folder_type: folder_type.to_string(),
+            message_id: message_id.map(str::to_string),
+            content_hash: content_hash(
+                Some("Lunch"),
+                Some("ana@example.com"),
+                None,
+                Some(1_700_000_000),
+                false,
+            ),
+            subject: "Lunch".to_string(),
+            from_address: "ana@example.com".to_string(),
+            date_epoch: Some(1_700_000_000),
+            is_starred: false,
+            gmail_thread_id: None,
+        }
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let hash = content_hash(Some("Lunch"), Some("ana@example.com"), None, Some(1), false);
+        assert_eq!(
+            hash,
+            content_hash(
+                Some(" LUNCH "),
+                Some("Ana@Example.com"),
+                Some(""),
+                Some(1),
+                false
+            )
+        );
+        assert_ne!(
+            hash,
+            content_hash(Some("Lunch"), Some("ana@example.com"), None, Some(2), false)
+        );
+        assert_ne!(
+            hash,
+            content_hash(Some("Lunch"), Some("ana@example.com"), None, Some(1), true)
+        );
+        assert_eq!(hash.len(), 32);
+    }
+
+    #[test]
+    fn test_group_duplicates() {
+        let groups = group_duplicates(vec![
+            copy(3, "a", "archive", Some("<x@example.com>")),
+            copy(2, "a", "inbox", Some("x@example.com")),
+            copy(4, "b", "other", Some("<X@example.com>")),
+            copy(5, "a", "inbox", Some("<other@example.com>")),
+        ]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep.id, 2);
+        let ids: Vec<i64> = groups[0].duplicates.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![4, 3]);
+        assert_eq!(duplicate_count(&groups), 2);
+    }
+
+    #[test]
+    fn test_same_message_id_different_content() {
+        let mut other = copy(2, "a", "inbox", Some("<x@example.com>"));
+        other.content_hash = content_hash(Some("Dinner"), None, None, None, false);
+        assert!(
+            group_duplicates(vec![copy(1, "a", "inbox", Some("<x@example.com>")), other])
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_without_message_id() {
+        let groups = group_duplicates(vec![
+            copy(1, "a", "inbox", None),
+            copy(2, "a", "other", None),
+        ]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].duplicates[0].id, 2);
+    }
+
+    #[test]
+    fn test_gmail_labels_count_once() {
+        let gmail = |id, folder_type| MessageCopy {
+            gmail_thread_id: Some(7),
+            ..copy(id, "g", folder_type, Some("<x@example.com>"))
+        };
+        assert!(group_duplicates(vec![
+            gmail(1, "inbox"),
+            gmail(2, "other"),
+            gmail(3, "archive")
+        ])
+        .is_empty());
+
+        // A copy in another account is a duplicate of the Gmail message
+        let groups = group_duplicates(vec![
+            gmail(1, "inbox"),
+            gmail(2, "archive"),
+            copy(3, "a", "other", Some("<x@example.com>")),
+        ]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep.id, 1);
+        assert_eq!(groups[0].duplicates.len(), 1);
+        assert_eq!(groups[0].duplicates[0].id, 3);
+    }
+
+    #[test]
+    fn test_sent_and_received_copies() {
+        assert!(group_duplicates(vec![
+            copy(1, "a", "sent", Some("<x@example.com>")),
+            copy(2, "a", "inbox", Some("<x@example.com>")),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn test_starred_copies() {
+        let starred = MessageCopy {
+            is_starred: true,
+            ..copy(5, "a", "archive", Some("<x@example.com>"))
+        };
+        let groups = group_duplicates(vec![
+            copy(1, "a", "inbox", Some("<x@example.com>")),
+            starred.clone(),
+        ]);
+        // The starred copy is kept, even outside the inbox
+        assert_eq!(groups[0].keep.id, 5);
+        assert_eq!(groups[0].duplicates[0].id, 1);
+
+        let groups = group_duplicates(vec![starred.clone(), MessageCopy { id: 6, ..starred }]);
+        assert!(groups.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Single messages as `.eml` files
+//!
+//! An `.eml` file holds one message as it was sent, its RFC 822 source,
+//! which other mail clients open and file managers preview. Saving a
+//! message fetches the source from the server, or rebuilds it from the
+//! cache when the server can't supply it (see [`Database::message_source`]).
+//! Files opened in NorthMail are parsed for display only and don't join
+//! any folder.
+//!
+//! [`Database::message_source`]: crate::Database::message_source
+
+use crate::import::{self, ImportMessage, LocalMessage};
+use std::io::Write;
+use std::path::Path;
+
+/// File extension of saved messages
+pub const EXTENSION: &str = "eml";
+
+/// MIME type of saved messages
+pub const MIME_TYPE: &str = "message/rfc822";
+
+/// Longest file name stem [`file_name`] makes, in characters
+const MAX_STEM_LENGTH: usize = 80;
+
+/// A message's RFC 822 source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSource {
+    pub data: Vec<u8>,
+    /// Rebuilt from the cache rather than fetched, so headers the cache
+    /// doesn't keep are missing (see [`crate::maildir::rebuild_message`])
+    pub rebuilt: bool,
+}
+
+/// File name to save a message under: its subject, without characters file
+/// systems or file managers object to, or `message-<uid>` when that leaves
+/// nothing
+pub fn file_name(subject: Option<&str>, uid: u32) -> String {
+    let cleaned: String = subject
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    let stem: String = cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_STEM_LENGTH)
+        .collect();
+    // A leading dot hides the file, a trailing one confuses the extension
+    let stem = stem.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if stem.is_empty() {
+        format!("message-{}.{}", uid, EXTENSION)
+    } else {
+        format!("{}.{}", stem, EXTENSION)
+    }
+}
+
+/// Whether `path` names an `.eml` file
+pub fn is_eml_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// Write a message's source to `path`. It goes to a hidden file beside
+/// `path` first and is renamed into place, so whatever reads `path`, such
+/// as a file manager a message was dragged to, never sees half of it.
+pub fn write(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no file name"))?;
+    let partial = path.with_file_name(format!(".{}.part", name.to_string_lossy()));
+    let mut file = std::fs::File::create(&partial)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&partial, path)
+}
+
+/// Parse a message's source for display. `None` if it isn't a message.
+pub fn parse(data: &[u8]) -> Option<LocalMessage> {
+    import::local_message(&ImportMessage {
+        data: data.to_vec(),
+        message_id: None,
+        seen: true,
+        flagged: false,
+        answered: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name(Some("Re: Q3 report"), 7), "Re Q3 report.eml");
+        assert_eq!(file_name(Some("a/b\\c\t d"), 7), "a b c d.eml");
+        assert_eq!(file_name(Some("...hidden..."), 7), "hidden.eml");
+        assert_eq!(file_name(Some("  "), 7), "message-7.eml");
+        assert_eq!(file_name(None, 42), "message-42.eml");
+        let long = "x".repeat(200);
+        assert_eq!(file_name(Some(&long), 1).len(), MAX_STEM_LENGTH + 4);
+    }
+
+    #[test]
+    fn test_is_eml_path() {
+        assert!(is_eml_path(Path::new("/tmp/Message.EML")));
+        assert!(!is_eml_path(Path::new("/tmp/archive.mbox")));
+        assert!(!is_eml_path(Path::new("/tmp/eml")));
+    }
+
+    #[test]
+    fn test_write_and_parse() {
+        let dir = std::env::temp_dir().join(format!("northmail-eml-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name(Some("Hello"), 1));
+        let source = b"From: Ann <ann@example.org>\r\nTo: bob@example.org\r\n\
+            Subject: Hello\r\nDate: Tue, 1 Oct 2024 10:00:00 +0000\r\n\r\nHi Bob\r\n";
+
+        write(&path, source).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), source);
+        let names: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(names.len(), 1, "the partial file is renamed away");
+
+        let message = parse(source).unwrap();
+        assert_eq!(message.header.subject.as_deref(), Some("Hello"));
+        assert_eq!(
+            message.header.from_address.as_deref(),
+            Some("ann@example.org")
+        );
+        assert_eq!(
+            message.header.to_addresses.as_deref(),
+            Some("bob@example.org")
+        );
+        assert!(message.body_text.unwrap().contains("Hi Bob"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -40,6 +40,26 @@ pub enum CoreError {
     #[error("Sync error: {0}")]
     SyncError(String),
 
+    /// JMAP error
+    #[error("JMAP error: {0}")]
+    JmapError(String),
+
+    /// The database is open in another process
+    #[error("Database {0} is in use by another NorthMail process")]
+    DatabaseLocked(String),
+
+    /// SQLite found the database file damaged
+    #[error("Database is damaged: {0}")]
+    DatabaseCorrupt(String),
+
+    /// The database is encrypted, or asked to be, and this build can't
+    #[error("Database {0} needs SQLCipher, which this build of NorthMail doesn't include")]
+    EncryptionUnsupported(String),
+
+    /// A backup is incomplete, damaged or can't be read
+    #[error("Backup can't be used: {0}")]
+    BackupInvalid(String),
+
     /// Storage error
     #[error("Storage error: {0}")]
     StorageError(String),
@@ -51,6 +71,10 @@ pub enum CoreError {
 
 impl From<sqlx::Error> for CoreError {
     fn from(e: sqlx::Error) -> Self {
+        if crate::database::is_corruption_error(&e) {
+            crate::database::note_corruption(&e);
+            return CoreError::DatabaseCorrupt(e.to_string());
+        }
         CoreError::DatabaseError(e.to_string())
     }
 }
@@ -72,3 +96,9 @@ impl From<northmail_smtp::SmtpError> for CoreError {
         CoreError::SmtpError(e.to_string())
     }
 }
+
+impl From<northmail_jmap::JmapError> for CoreError {
+    fn from(e: northmail_jmap::JmapError) -> Self {
+        CoreError::JmapError(e.to_string())
+    }
+}
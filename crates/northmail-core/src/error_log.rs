@@ -0,0 +1,167 @@
+//! Coalescing log of user-facing errors
+//!
+//! A failing account fails again on every sync. The log folds identical
+//! errors into one entry with a count and first/last timestamps, and decides
+//! when an error is worth a toast so repeats don't flood the window.
+
+use std::collections::VecDeque;
+
+/// The same error isn't toasted again within this many seconds
+pub const REPEAT_TOAST_SECS: i64 = 600;
+/// At most `MAX_TOASTS` toasts are shown per `TOAST_WINDOW_SECS`
+pub const TOAST_WINDOW_SECS: i64 = 60;
+pub const MAX_TOASTS: usize = 3;
+/// Oldest entries are dropped beyond this
+pub const MAX_ENTRIES: usize = 50;
+
+/// One distinct error, with how often and when it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorEntry {
+    /// Account the error belongs to, `None` for app-wide errors
+    pub account_id: Option<String>,
+    pub message: String,
+    /// How many times it was reported
+    pub count: u32,
+    /// Unix timestamps (seconds)
+    pub first_seen: i64,
+    pub last_seen: i64,
+    last_toast: Option<i64>,
+}
+
+/// Errors reported this session, most recent first
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    entries: Vec<ErrorEntry>,
+    /// When recent toasts were shown, for the global rate limit
+    toast_times: VecDeque<i64>,
+}
+
+impl ErrorLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an error reported at `now` (unix seconds).
+    ///
+    /// Returns whether it should be shown as a toast: not if the same error
+    /// was toasted recently, or if too many toasts went out in the last minute.
+    pub fn record(&mut self, account_id: Option<&str>, message: &str, now: i64) -> bool {
+        let position = self
+            .entries
+            .iter()
+            .position(|e| e.account_id.as_deref() == account_id && e.message == message);
+
+        let mut entry = match position {
+            Some(index) => {
+                let mut entry = self.entries.remove(index);
+                entry.count += 1;
+                entry.last_seen = now;
+                entry
+            }
+            None => ErrorEntry {
+                account_id: account_id.map(str::to_string),
+                message: message.to_string(),
+                count: 1,
+                first_seen: now,
+                last_seen: now,
+                last_toast: None,
+            },
+        };
+
+        let repeated = entry
+            .last_toast
+            .is_some_and(|t| now - t < REPEAT_TOAST_SECS);
+        while self
+            .toast_times
+            .front()
+            .is_some_and(|&t| now - t >= TOAST_WINDOW_SECS)
+        {
+            self.toast_times.pop_front();
+        }
+        let toast = !repeated && self.toast_times.len() < MAX_TOASTS;
+        if toast {
+            entry.last_toast = Some(now);
+            self.toast_times.push_back(now);
+        }
+
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        toast
+    }
+
+    /// All entries, most recently seen first
+    pub fn entries(&self) -> &[ErrorEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget an account's errors, e.g. after it synced successfully
+    pub fn clear_account(&mut self, account_id: &str) {
+        self.entries
+            .retain(|e| e.account_id.as_deref() != Some(account_id));
+    }
+
+    /// Forget everything
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_errors_coalesce() {
+        let mut log = ErrorLog::new();
+        assert!(log.record(Some("a"), "Connection refused", 100));
+        assert!(!log.record(Some("a"), "Connection refused", 160));
+        assert!(log.record(Some("b"), "Connection refused", 170));
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].account_id.as_deref(), Some("b"));
+        assert_eq!(entries[1].count, 2);
+        assert_eq!(entries[1].first_seen, 100);
+        assert_eq!(entries[1].last_seen, 160);
+    }
+
+    #[test]
+    fn test_repeat_toast_after_quiet_period() {
+        let mut log = ErrorLog::new();
+        assert!(log.record(None, "Timeout", 0));
+        assert!(!log.record(None, "Timeout", REPEAT_TOAST_SECS - 1));
+        assert!(log.record(None, "Timeout", REPEAT_TOAST_SECS));
+    }
+
+    #[test]
+    fn test_global_toast_rate_limit() {
+        let mut log = ErrorLog::new();
+        for i in 0..MAX_TOASTS {
+            assert!(log.record(None, &format!("error {}", i), 10));
+        }
+        assert!(!log.record(None, "one too many", 20));
+        // Still recorded for the panel
+        assert_eq!(log.entries()[0].message, "one too many");
+        assert!(log.record(None, "later", 10 + TOAST_WINDOW_SECS));
+    }
+
+    #[test]
+    fn test_clear_account_and_cap() {
+        let mut log = ErrorLog::new();
+        log.record(Some("a"), "x", 0);
+        log.record(Some("b"), "y", 0);
+        log.clear_account("a");
+        assert_eq!(log.entries().len(), 1);
+        assert_eq!(log.entries()[0].account_id.as_deref(), Some("b"));
+
+        for i in 0..MAX_ENTRIES + 5 {
+            log.record(None, &i.to_string(), i as i64);
+        }
+        assert_eq!(log.entries().len(), MAX_ENTRIES);
+        assert_eq!(log.entries()[0].message, (MAX_ENTRIES + 4).to_string());
+    }
+}
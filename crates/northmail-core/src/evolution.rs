@@ -0,0 +1,633 @@
+//! Importing from Evolution
+//!
+//! Evolution keeps the mail "On This Computer" in a Maildir++ store, its
+//! accounts, identities and signatures in the key files of the
+//! evolution-data-server registry, and its filters in an XML file. Users
+//! switching from it get its local folders as local folders here (see
+//! [`crate::import`]), named with an "Evolution" prefix so they can't clash
+//! with server folders; the names and signatures of its identities whose
+//! address is one of their accounts' (see [`crate::identity`]); and the
+//! filters NorthMail's rules can express (see [`crate::rules`]). Accounts
+//! themselves come from GNOME Online Accounts, so those Evolution has that
+//! NorthMail doesn't are only listed for the user to add.
+
+use crate::identity::Signature;
+use crate::import;
+use crate::rules::{Condition, MatchKind, MatchMode, Rule, RuleAction, RuleField};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// What the names of imported local folders start with
+pub const LOCAL_FOLDER_PREFIX: &str = "Evolution";
+
+/// Local folder Evolution keeps unsent mail in; NorthMail has its own
+const OUTBOX: &str = "Outbox";
+
+/// `BackendName` of the registry's "On This Computer" account, which has
+/// no address of its own
+const LOCAL_BACKEND: &str = "none";
+
+/// Where Evolution keeps what's imported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvolutionPaths {
+    /// The "On This Computer" Maildir++ store
+    pub local_store: PathBuf,
+    /// The evolution-data-server registry, a `.source` key file per
+    /// account, identity, signature and so on
+    pub sources: PathBuf,
+    /// Signature files, named by the UID of their registry source
+    pub signatures: PathBuf,
+    pub filters: PathBuf,
+}
+
+impl EvolutionPaths {
+    /// The paths under the user's XDG data and config directories
+    pub fn new(data_dir: &Path, config_dir: &Path) -> Self {
+        Self {
+            local_store: data_dir.join("evolution/mail/local"),
+            sources: config_dir.join("evolution/sources"),
+            signatures: data_dir.join("evolution/signatures"),
+            filters: config_dir.join("evolution/mail/filters.xml"),
+        }
+    }
+
+    /// Whether Evolution has been used here
+    pub fn exist(&self) -> bool {
+        self.local_store.is_dir() || self.sources.is_dir() || self.filters.is_file()
+    }
+}
+
+/// A folder of Evolution's local store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalStoreFolder {
+    /// As Evolution shows it, e.g. `Work/Projects`
+    pub name: String,
+    /// The folder's Maildir
+    pub path: PathBuf,
+    /// Message files it holds
+    pub messages: usize,
+}
+
+impl LocalStoreFolder {
+    /// Name of the local folder its messages are imported into
+    pub fn local_folder(&self) -> String {
+        import::local_folder_name(&format!("{} {}", LOCAL_FOLDER_PREFIX, self.name))
+    }
+}
+
+/// The folders of the local store at `store` that hold messages: its
+/// top-level Maildir, the Inbox, and the `.`-named Maildirs of the others.
+/// The Outbox is left out.
+pub fn local_folders(store: &Path) -> std::io::Result<Vec<LocalStoreFolder>> {
+    let mut folders = Vec::new();
+    if store.join("cur").is_dir() {
+        folders.push(LocalStoreFolder {
+            name: "Inbox".to_string(),
+            path: store.to_path_buf(),
+            messages: count_messages(store),
+        });
+    }
+    for entry in std::fs::read_dir(store)? {
+        let entry = entry?;
+        let Some(name) = local_store_name(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        let path = entry.path();
+        if name == OUTBOX || !path.join("cur").is_dir() {
+            continue;
+        }
+        folders.push(LocalStoreFolder {
+            name,
+            messages: count_messages(&path),
+            path,
+        });
+    }
+    folders.retain(|folder| folder.messages > 0);
+    folders.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(folders)
+}
+
+/// Message files in a Maildir's `cur` and `new`
+fn count_messages(maildir: &Path) -> usize {
+    ["cur", "new"]
+        .iter()
+        .filter_map(|sub| std::fs::read_dir(maildir.join(sub)).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .count()
+}
+
+/// A folder's name from the name of its directory in the local store:
+/// Maildir++ puts a dot before each level (`.Work.Projects`), and Evolution
+/// writes a dot in a name as `_2E`
+fn local_store_name(dir: &str) -> Option<String> {
+    let levels = dir.strip_prefix('.')?;
+    if levels.is_empty() || levels == "." {
+        return None;
+    }
+    Some(
+        levels
+            .split('.')
+            .map(|level| level.replace("_2E", "."))
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+/// A key file as the registry writes them: `[Group]` headers over
+/// `Key=Value` lines
+type KeyFile = HashMap<String, HashMap<String, String>>;
+
+fn parse_key_file(text: &str) -> KeyFile {
+    let mut file = KeyFile::new();
+    let mut group = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            group = Some(name.to_string());
+            continue;
+        }
+        if let (Some(group), Some((key, value))) = (&group, line.split_once('=')) {
+            file.entry(group.clone())
+                .or_default()
+                .insert(key.trim().to_string(), unescape_value(value.trim()));
+        }
+    }
+    file
+}
+
+/// A key file value with its `\s`, `\n`, `\t` and `\\` escapes undone
+fn unescape_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn key<'a>(file: &'a KeyFile, group: &str, key: &str) -> Option<&'a str> {
+    file.get(group)
+        .and_then(|group| group.get(key))
+        .map(String::as_str)
+        .filter(|value| !value.is_empty())
+}
+
+/// A mail account of Evolution's, by the identity it sends as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvolutionIdentity {
+    /// Registry UID of the mail account, as filters name its folders by
+    pub account_uid: String,
+    /// The account's name in Evolution
+    pub account_name: String,
+    pub address: String,
+    pub name: Option<String>,
+    pub signature: Option<Signature>,
+}
+
+/// The identities of the mail accounts among registry `sources`, given as
+/// (UID, key file text). `read_signature` reads a signature's file by its
+/// UID.
+pub fn parse_sources(
+    sources: &[(String, String)],
+    read_signature: impl Fn(&str) -> Option<String>,
+) -> Vec<EvolutionIdentity> {
+    let files: HashMap<&str, KeyFile> = sources
+        .iter()
+        .map(|(uid, text)| (uid.as_str(), parse_key_file(text)))
+        .collect();
+
+    let mut identities: Vec<EvolutionIdentity> = files
+        .iter()
+        .filter(|(_, file)| key(file, "Mail Account", "BackendName").is_some_and(|backend| backend != LOCAL_BACKEND))
+        .filter_map(|(uid, account)| {
+            let identity = files.get(key(account, "Mail Account", "IdentityUid")?)?;
+            let address = key(identity, "Mail Identity", "Address")?.trim().to_string();
+            let signature = key(identity, "Mail Identity", "SignatureUid")
+                .filter(|uid| *uid != "none")
+                .and_then(|uid| {
+                    let html = files
+                        .get(uid)
+                        .and_then(|file| key(file, "Mail Signature", "MimeType"))
+                        .is_some_and(|mime_type| mime_type == "text/html");
+                    let content = read_signature(uid)?;
+                    signature(&content, html)
+                });
+            Some(EvolutionIdentity {
+                account_uid: uid.to_string(),
+                account_name: key(account, "Data Source", "DisplayName").unwrap_or(&address).to_string(),
+                name: key(identity, "Mail Identity", "Name").map(str::to_string),
+                address,
+                signature,
+            })
+        })
+        .collect();
+    identities.sort_by(|a, b| a.account_name.cmp(&b.account_name));
+    identities
+}
+
+/// A signature from the content of Evolution's signature file
+fn signature(content: &str, html: bool) -> Option<Signature> {
+    let signature = if html {
+        Signature {
+            text: northmail_smtp::html_to_plain_text(content).trim().to_string(),
+            html: Some(content.trim().to_string()),
+            image: None,
+        }
+    } else {
+        Signature {
+            // Evolution adds the "-- " line itself, as does the composer here
+            text: content.trim_start_matches("-- \n").trim_end().to_string(),
+            html: None,
+            image: None,
+        }
+    };
+    (!signature.is_empty()).then_some(signature)
+}
+
+/// Read the identities of the registry at `paths`
+pub fn read_identities(paths: &EvolutionPaths) -> std::io::Result<Vec<EvolutionIdentity>> {
+    let mut sources = Vec::new();
+    for entry in std::fs::read_dir(&paths.sources)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "source") {
+            if let Some(uid) = path.file_stem() {
+                sources.push((uid.to_string_lossy().into_owned(), std::fs::read_to_string(&path)?));
+            }
+        }
+    }
+    Ok(parse_sources(&sources, |uid| {
+        std::fs::read_to_string(paths.signatures.join(uid)).ok()
+    }))
+}
+
+/// Evolution's filters as rules
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedFilters {
+    pub rules: Vec<Rule>,
+    /// Titles of the filters left out, as they test or do something rules
+    /// can't
+    pub skipped: Vec<String>,
+}
+
+/// Read the incoming-mail filters of Evolution's `filters.xml`. A filter is
+/// only taken whole: one with a test or action rules lack is skipped
+/// rather than imported doing something else. `accounts` maps the registry
+/// UIDs of Evolution's mail accounts to the NorthMail accounts with the
+/// same address; a filter moving mail to a folder of one becomes a rule of
+/// that account.
+pub fn parse_filters(xml: &str, accounts: &HashMap<String, String>) -> ImportedFilters {
+    let mut imported = ImportedFilters::default();
+    let Some(root) = parse_xml(xml) else {
+        return imported;
+    };
+    for ruleset in root.children("ruleset") {
+        for filter in ruleset.children("rule") {
+            if filter.attribute("source").is_some_and(|source| source != "incoming") {
+                continue;
+            }
+            let title = filter
+                .child("title")
+                .map(|title| title.text.trim().to_string())
+                .filter(|title| !title.is_empty())
+                .unwrap_or_else(|| "Evolution filter".to_string());
+            match filter_rule(filter, &title, accounts) {
+                Some(rule) => imported.rules.push(rule),
+                None => imported.skipped.push(title),
+            }
+        }
+    }
+    imported
+}
+
+/// The rule a filter becomes, if rules can express it
+fn filter_rule(filter: &Element, title: &str, accounts: &HashMap<String, String>) -> Option<Rule> {
+    let mut rule = Rule::new(title);
+    rule.enabled = filter.attribute("enabled") != Some("false");
+    rule.match_mode = match filter.attribute("grouping") {
+        Some("any") => MatchMode::Any,
+        _ => MatchMode::All,
+    };
+    for part in filter.child("partset")?.children("part") {
+        rule.conditions.push(filter_condition(part)?);
+    }
+    for part in filter.child("actionset")?.children("part") {
+        match part.attribute("name")? {
+            "move-to-folder" => {
+                let uri = part
+                    .children("value")
+                    .filter_map(|value| value.child("folder"))
+                    .find_map(|folder| folder.attribute("uri"))?;
+                let (account_uid, folder) = parse_folder_uri(uri)?;
+                let account_id = accounts.get(&account_uid)?;
+                if rule.account_id.as_ref().is_some_and(|id| id != account_id) {
+                    return None;
+                }
+                rule.account_id = Some(account_id.clone());
+                rule.actions.push(RuleAction::Move { folder });
+            }
+            "delete" => rule.actions.push(RuleAction::Delete),
+            "stop" => rule.stop_processing = true,
+            "set-status" => match option_value(part)? {
+                "seen" => rule.actions.push(RuleAction::MarkRead),
+                "important" => rule.actions.push(RuleAction::Star),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    (!rule.conditions.is_empty() && !rule.actions.is_empty()).then_some(rule)
+}
+
+/// The condition a filter test becomes, if rules can express it
+fn filter_condition(part: &Element) -> Option<Condition> {
+    let strings = || {
+        part.children("value")
+            .filter(|value| value.attribute("type") == Some("string"))
+            .filter_map(|value| Some((value.attribute("name")?, value.child("string")?.text.trim())))
+    };
+    let field = match part.attribute("name")? {
+        "sender" => RuleField::From,
+        "subject" => RuleField::Subject,
+        // Evolution's "Recipients" test looks at To and Cc, as ours does
+        "to" => RuleField::Recipients,
+        "cc" => RuleField::Header("Cc".to_string()),
+        "bcc" => RuleField::Header("Bcc".to_string()),
+        "header" => RuleField::Header(
+            strings()
+                .find(|(name, _)| *name == "header-field")
+                .map(|(_, field)| field.to_string())
+                .filter(|field| !field.is_empty())?,
+        ),
+        _ => return None,
+    };
+    let kind = match option_value(part)? {
+        "contains" => MatchKind::Contains,
+        "not contains" => MatchKind::NotContains,
+        "is" => MatchKind::Is,
+        "starts with" => MatchKind::StartsWith,
+        "ends with" => MatchKind::EndsWith,
+        _ => return None,
+    };
+    let (_, value) = strings().find(|(name, _)| *name != "header-field")?;
+    Some(Condition::new(field, kind, value))
+}
+
+/// The chosen option of a filter part
+fn option_value(part: &Element) -> Option<&str> {
+    part.children("value")
+        .find(|value| value.attribute("type") == Some("option"))?
+        .attribute("value")
+}
+
+/// The account UID and folder path of a `folder://UID/path` URI, the path
+/// percent-decoded
+fn parse_folder_uri(uri: &str) -> Option<(String, String)> {
+    let (account_uid, path) = uri.strip_prefix("folder://")?.split_once('/')?;
+    let path = percent_decode(path);
+    (!account_uid.is_empty() && !path.is_empty()).then(|| (account_uid.to_string(), path))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// An element of a parsed XML document
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn from_start(start: &BytesStart) -> Self {
+        Self {
+            name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+            attributes: start
+                .attributes()
+                .flatten()
+                .map(|attribute| {
+                    (
+                        String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                        attribute
+                            .unescape_value()
+                            .map(|value| value.into_owned())
+                            .unwrap_or_default(),
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// The root element of an XML document, or `None` if it isn't well formed
+fn parse_xml(xml: &str) -> Option<Element> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) => stack.push(Element::from_start(&start)),
+            Event::Empty(start) => {
+                let element = Element::from_start(&start);
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Some(element),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text.unescape().ok()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(_) => {
+                let element = stack.pop()?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Some(element),
+                }
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_store_name() {
+        assert_eq!(local_store_name(".Work").as_deref(), Some("Work"));
+        assert_eq!(local_store_name(".Work.Projects").as_deref(), Some("Work/Projects"));
+        assert_eq!(local_store_name(".v1_2E0").as_deref(), Some("v1.0"));
+        assert_eq!(local_store_name("cur"), None);
+        assert_eq!(local_store_name(".."), None);
+
+        let folder = LocalStoreFolder {
+            name: "Work/Projects".to_string(),
+            path: PathBuf::new(),
+            messages: 1,
+        };
+        assert_eq!(folder.local_folder(), "Evolution Work-Projects");
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        let sources = [
+            (
+                "acct".to_string(),
+                "[Data Source]\nDisplayName=Work\\sMail\n\n[Mail Account]\nBackendName=imapx\nIdentityUid=ident\n"
+                    .to_string(),
+            ),
+            (
+                "ident".to_string(),
+                "[Mail Identity]\nAddress=ann@example.com\nName=Ann\nSignatureUid=sig\n".to_string(),
+            ),
+            ("sig".to_string(), "[Mail Signature]\nMimeType=text/html\n".to_string()),
+            (
+                "local".to_string(),
+                "[Mail Account]\nBackendName=none\nIdentityUid=ident\n".to_string(),
+            ),
+        ];
+        let identities = parse_sources(&sources, |uid| (uid == "sig").then(|| "<b>Ann</b>".to_string()));
+        assert_eq!(identities.len(), 1);
+        let identity = &identities[0];
+        assert_eq!(identity.account_uid, "acct");
+        assert_eq!(identity.account_name, "Work Mail");
+        assert_eq!(identity.address, "ann@example.com");
+        assert_eq!(identity.name.as_deref(), Some("Ann"));
+        let signature = identity.signature.as_ref().unwrap();
+        assert_eq!(signature.html.as_deref(), Some("<b>Ann</b>"));
+        assert_eq!(signature.text, "Ann");
+    }
+
+    const FILTERS: &str = r#"<?xml version="1.0"?>
+<filteroptions>
+  <ruleset>
+    <rule enabled="true" grouping="any" source="incoming">
+      <title>Boss &amp; co</title>
+      <partset>
+        <part name="sender">
+          <value name="sender-type" type="option" value="contains"/>
+          <value name="sender" type="string"><string>boss@example.com</string></value>
+        </part>
+        <part name="header">
+          <value name="header-field" type="string"><string>X-Priority</string></value>
+          <value name="header-type" type="option" value="is"/>
+          <value name="word" type="string"><string>1</string></value>
+        </part>
+      </partset>
+      <actionset>
+        <part name="move-to-folder">
+          <value name="folder" type="folder"><folder uri="folder://acct/Work%2FBoss"/></value>
+        </part>
+        <part name="set-status"><value name="flag" type="option" value="important"/></part>
+        <part name="stop"/>
+      </actionset>
+    </rule>
+    <rule enabled="false" grouping="all" source="incoming">
+      <title>Big</title>
+      <partset>
+        <part name="size"><value name="size-type" type="option" value="greater-than"/></part>
+      </partset>
+      <actionset><part name="delete"/></actionset>
+    </rule>
+    <rule enabled="true" grouping="all" source="outgoing">
+      <title>Sent</title>
+    </rule>
+  </ruleset>
+</filteroptions>"#;
+
+    #[test]
+    fn test_parse_filters() {
+        let accounts = HashMap::from([("acct".to_string(), "goa_1".to_string())]);
+        let imported = parse_filters(FILTERS, &accounts);
+        assert_eq!(imported.skipped, vec!["Big".to_string()]);
+        assert_eq!(imported.rules.len(), 1);
+        let rule = &imported.rules[0];
+        assert_eq!(rule.name, "Boss & co");
+        assert_eq!(rule.account_id.as_deref(), Some("goa_1"));
+        assert_eq!(rule.match_mode, MatchMode::Any);
+        assert!(rule.stop_processing);
+        assert_eq!(
+            rule.conditions,
+            vec![
+                Condition::new(RuleField::From, MatchKind::Contains, "boss@example.com"),
+                Condition::new(RuleField::Header("X-Priority".to_string()), MatchKind::Is, "1"),
+            ]
+        );
+        assert_eq!(
+            rule.actions,
+            vec![
+                RuleAction::Move {
+                    folder: "Work/Boss".to_string()
+                },
+                RuleAction::Star,
+            ]
+        );
+
+        // Moving to a folder of an account NorthMail doesn't have
+        let imported = parse_filters(FILTERS, &HashMap::new());
+        assert!(imported.rules.is_empty());
+        assert_eq!(imported.skipped.len(), 2);
+
+        assert_eq!(parse_filters("<filteroptions>", &accounts), ImportedFilters::default());
+    }
+}
@@ -0,0 +1,223 @@
+//! Merging flag changes made offline with the server's
+//!
+//! Marking a message read or starring it changes the cache at once and is
+//! sent to the server afterwards. Until the server has it the change is
+//! pending, and a sync that finds the server with the other value can't
+//! tell a push that never happened from a change made elsewhere. Each flag
+//! has a [`MergeRule`] saying which side wins then: by default the server's
+//! read state, since reading a message on another device is the likelier
+//! story, and the local star, since a star is a deliberate act the user
+//! would be surprised to see undone. Either way the disagreement is
+//! reported as a [`FlagConflict`].
+
+use northmail_imap::MessageFlags;
+
+/// A flag whose local changes are tracked until the server has them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Read,
+    Starred,
+}
+
+impl Flag {
+    /// Name stored in the `pending_flags` table
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flag::Read => "read",
+            Flag::Starred => "starred",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "read" => Some(Flag::Read),
+            "starred" => Some(Flag::Starred),
+            _ => None,
+        }
+    }
+
+    /// The IMAP system flag it stands for
+    pub fn imap_flag(self) -> &'static str {
+        match self {
+            Flag::Read => "\\Seen",
+            Flag::Starred => "\\Flagged",
+        }
+    }
+
+    /// The flag's value among a message's IMAP flags
+    pub fn of(self, flags: &MessageFlags) -> bool {
+        match self {
+            Flag::Read => flags.seen,
+            Flag::Starred => flags.flagged,
+        }
+    }
+
+    pub fn set(self, flags: &mut MessageFlags, value: bool) {
+        match self {
+            Flag::Read => flags.seen = value,
+            Flag::Starred => flags.flagged = value,
+        }
+    }
+
+    /// The flag an IMAP system flag stands for, ignoring case as IMAP does
+    pub fn from_imap(flag: &str) -> Option<Self> {
+        if flag.eq_ignore_ascii_case("\\Seen") {
+            Some(Flag::Read)
+        } else if flag.eq_ignore_ascii_case("\\Flagged") {
+            Some(Flag::Starred)
+        } else {
+            None
+        }
+    }
+}
+
+/// Which side wins when a pending local change and the server disagree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeRule {
+    /// The server's value replaces the local change
+    ServerWins,
+    /// The local change stays and is sent to the server again
+    LocalWins,
+}
+
+/// The merge rule of each flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagPolicy {
+    pub read: MergeRule,
+    pub starred: MergeRule,
+}
+
+impl Default for FlagPolicy {
+    fn default() -> Self {
+        Self {
+            read: MergeRule::ServerWins,
+            starred: MergeRule::LocalWins,
+        }
+    }
+}
+
+impl FlagPolicy {
+    pub fn rule(&self, flag: Flag) -> MergeRule {
+        match flag {
+            Flag::Read => self.read,
+            Flag::Starred => self.starred,
+        }
+    }
+
+    /// Settle a pending local value against the server's
+    pub fn resolve(&self, flag: Flag, local: bool, server: bool) -> Resolution {
+        if local == server {
+            return Resolution::Agreed;
+        }
+        match self.rule(flag) {
+            MergeRule::ServerWins => Resolution::TakeServer,
+            MergeRule::LocalWins => Resolution::KeepLocal,
+        }
+    }
+}
+
+/// How a pending local change was settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// The server already has the change, so it's no longer pending
+    Agreed,
+    /// The server's value replaces the change
+    TakeServer,
+    /// The change stays pending, to be sent again
+    KeepLocal,
+}
+
+impl Resolution {
+    /// The value to cache
+    pub fn value(self, local: bool, server: bool) -> bool {
+        match self {
+            Resolution::Agreed | Resolution::TakeServer => server,
+            Resolution::KeepLocal => local,
+        }
+    }
+
+    /// Whether the local change still has to reach the server
+    pub fn is_pending(self) -> bool {
+        self == Resolution::KeepLocal
+    }
+}
+
+/// A pending local change the server disagreed with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagConflict {
+    pub uid: u32,
+    pub flag: Flag,
+    pub local: bool,
+    pub server: bool,
+    pub resolution: Resolution,
+}
+
+impl FlagConflict {
+    /// The value the cache kept
+    pub fn value(&self) -> bool {
+        self.resolution.value(self.local, self.server)
+    }
+}
+
+/// What bringing cached flags in line with the server's changed
+#[derive(Debug, Default)]
+pub struct FlagUpdate {
+    /// Messages whose cached flags changed, with the flags now cached
+    pub changed: Vec<(u32, MessageFlags)>,
+    /// Pending local changes the server disagreed with
+    pub conflicts: Vec<FlagConflict>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = FlagPolicy::default();
+        assert_eq!(policy.resolve(Flag::Read, true, false), Resolution::TakeServer);
+        assert_eq!(policy.resolve(Flag::Starred, true, false), Resolution::KeepLocal);
+        assert_eq!(policy.resolve(Flag::Starred, false, true), Resolution::KeepLocal);
+    }
+
+    #[test]
+    fn test_agreement() {
+        let policy = FlagPolicy::default();
+        for flag in [Flag::Read, Flag::Starred] {
+            assert_eq!(policy.resolve(flag, true, true), Resolution::Agreed);
+            assert_eq!(policy.resolve(flag, false, false), Resolution::Agreed);
+        }
+    }
+
+    #[test]
+    fn test_resolution_value() {
+        assert!(Resolution::KeepLocal.value(true, false));
+        assert!(!Resolution::TakeServer.value(true, false));
+        assert!(Resolution::Agreed.value(true, true));
+        assert!(Resolution::KeepLocal.is_pending());
+        assert!(!Resolution::TakeServer.is_pending());
+
+        let policy = FlagPolicy {
+            read: MergeRule::LocalWins,
+            starred: MergeRule::ServerWins,
+        };
+        assert_eq!(policy.resolve(Flag::Read, false, true), Resolution::KeepLocal);
+        assert_eq!(policy.resolve(Flag::Starred, false, true), Resolution::TakeServer);
+    }
+
+    #[test]
+    fn test_flag_names() {
+        for flag in [Flag::Read, Flag::Starred] {
+            assert_eq!(Flag::parse(flag.as_str()), Some(flag));
+            assert_eq!(Flag::from_imap(flag.imap_flag()), Some(flag));
+        }
+        assert_eq!(Flag::from_imap("\\seen"), Some(Flag::Read));
+        assert_eq!(Flag::from_imap("$Work"), None);
+
+        let mut flags = MessageFlags::default();
+        Flag::Starred.set(&mut flags, true);
+        assert!(flags.flagged);
+        assert!(Flag::Starred.of(&flags));
+        assert!(!Flag::Read.of(&flags));
+    }
+}
@@ -0,0 +1,100 @@
+//! Gmail label and search helpers
+//!
+//! Gmail reports each message's labels (X-GM-LABELS) and conversation id
+//! (X-GM-THRID) during header sync. Labels are cached as a JSON array.
+//! Searches written in Gmail's own syntax are sent to the server (X-GM-RAW).
+
+/// Search operators understood by Gmail, as in `from:alice` or `newer_than:7d`
+const SEARCH_OPERATORS: &[&str] = &[
+    "after", "around", "bcc", "before", "category", "cc", "deliveredto", "filename", "from", "has",
+    "in", "is", "label", "larger", "list", "newer", "newer_than", "older", "older_than",
+    "rfc822msgid", "size", "smaller", "subject", "to",
+];
+
+/// Serialize labels for the `gmail_labels` column; `None` when there are none
+pub fn encode_labels(labels: &[String]) -> Option<String> {
+    if labels.is_empty() {
+        None
+    } else {
+        serde_json::to_string(labels).ok()
+    }
+}
+
+/// Labels from the `gmail_labels` column
+pub fn decode_labels(json: Option<&str>) -> Vec<String> {
+    json.and_then(|j| serde_json::from_str(j).ok()).unwrap_or_default()
+}
+
+/// Labels worth showing as chips while viewing `folder_path`: system labels
+/// (`\Inbox`, `\Important`...) and the folder's own label are left out
+pub fn chip_labels(labels: &[String], folder_path: &str) -> Vec<String> {
+    labels
+        .iter()
+        .filter(|label| !label.starts_with('\\') && label.as_str() != folder_path)
+        .cloned()
+        .collect()
+}
+
+/// Labels typed by the user, separated by commas. Labels may contain
+/// spaces; system labels can't be typed.
+pub fn parse_label_list(text: &str) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::new();
+    for label in text.split(',').map(str::trim) {
+        if label.is_empty()
+            || label.starts_with('\\')
+            || labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+        {
+            continue;
+        }
+        labels.push(label.to_string());
+    }
+    labels
+}
+
+/// Whether a search uses Gmail operators and should run on the server
+pub fn is_raw_query(query: &str) -> bool {
+    query.split_whitespace().any(|term| {
+        let term = term.trim_start_matches(['-', '(', '{']);
+        term.split_once(':').is_some_and(|(operator, value)| {
+            !value.is_empty() && SEARCH_OPERATORS.contains(&operator.to_ascii_lowercase().as_str())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_round_trip() {
+        let labels = vec!["Work".to_string(), "Travel/2024 \"Rome\"".to_string()];
+        let json = encode_labels(&labels);
+        assert_eq!(decode_labels(json.as_deref()), labels);
+        assert_eq!(encode_labels(&[]), None);
+        assert!(decode_labels(None).is_empty());
+        assert!(decode_labels(Some("not json")).is_empty());
+    }
+
+    #[test]
+    fn test_chip_labels() {
+        let labels = vec!["\\Inbox".to_string(), "\\Important".to_string(), "Work".to_string(), "Receipts".to_string()];
+        assert_eq!(chip_labels(&labels, "INBOX"), vec!["Work", "Receipts"]);
+        assert_eq!(chip_labels(&labels, "Work"), vec!["Receipts"]);
+    }
+
+    #[test]
+    fn test_parse_label_list() {
+        assert_eq!(parse_label_list(" Work, Travel/Rome ,, work, \\Starred"), vec!["Work", "Travel/Rome"]);
+        assert!(parse_label_list("").is_empty());
+    }
+
+    #[test]
+    fn test_is_raw_query() {
+        assert!(is_raw_query("from:foo has:attachment newer_than:7d"));
+        assert!(is_raw_query("invoice -label:paid"));
+        assert!(is_raw_query("Subject:(quarterly report)"));
+        assert!(!is_raw_query("meeting notes"));
+        assert!(!is_raw_query("re: lunch"));
+        assert!(!is_raw_query("https://example.com"));
+    }
+}
@@ -0,0 +1,100 @@
+//! iCloud Mail provider profile
+//!
+//! iCloud accounts come in through GNOME Online Accounts as plain IMAP/SMTP
+//! password accounts, so nothing marks them as iCloud except the server and
+//! the address. This module holds what NorthMail knows about the service:
+//! its servers, its folder names, and the app-specific password it requires
+//! instead of the Apple Account password.
+
+use crate::recipient_check::email_domain;
+
+/// iCloud IMAP server
+pub const IMAP_HOST: &str = "imap.mail.me.com";
+/// iCloud IMAP port (implicit TLS)
+pub const IMAP_PORT: u16 = 993;
+/// iCloud SMTP server
+pub const SMTP_HOST: &str = "smtp.mail.me.com";
+/// iCloud SMTP port (STARTTLS)
+pub const SMTP_PORT: u16 = 587;
+
+/// Domains iCloud Mail addresses are issued under
+pub const DOMAINS: &[&str] = &["icloud.com", "me.com", "mac.com"];
+
+/// Apple Account page where app-specific passwords are generated, under
+/// Sign-In and Security
+pub const APP_PASSWORD_URL: &str = "https://account.apple.com/account/manage";
+
+/// Whether `address` is an iCloud Mail address
+pub fn is_icloud_address(address: &str) -> bool {
+    email_domain(address).is_some_and(|domain| DOMAINS.contains(&domain.as_str()))
+}
+
+/// Whether `host` is one of iCloud's mail servers. Besides the published
+/// names, accounts may be pointed at a partition such as
+/// `p02-imap.mail.me.com`.
+pub fn is_icloud_host(host: &str) -> bool {
+    let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+    host == "mail.me.com" || host.ends_with(".mail.me.com")
+}
+
+/// Whether an account with this address and configured IMAP server is on
+/// iCloud. An account without a server is taken as iCloud when its address
+/// is, since that's what the server defaults to.
+pub fn is_icloud_account(email: &str, imap_host: Option<&str>) -> bool {
+    match imap_host {
+        Some(host) if !host.trim().is_empty() => is_icloud_host(host),
+        _ => is_icloud_address(email),
+    }
+}
+
+/// iCloud's name for a special folder, by the hint the UI uses ("Trash",
+/// "Archive", "Spam", "Sent", "Drafts"). Used when the server hasn't marked
+/// the folder with a SPECIAL-USE attribute, as iCloud doesn't always do.
+pub fn folder_name(hint: &str) -> Option<&'static str> {
+    match hint {
+        "Trash" => Some("Deleted Messages"),
+        "Sent" => Some("Sent Messages"),
+        "Spam" | "Junk" => Some("Junk"),
+        "Archive" => Some("Archive"),
+        "Drafts" => Some("Drafts"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_icloud_address() {
+        assert!(is_icloud_address("someone@icloud.com"));
+        assert!(is_icloud_address("Someone <someone@Me.com>"));
+        assert!(is_icloud_address("someone@mac.com"));
+        assert!(!is_icloud_address("someone@gmail.com"));
+        assert!(!is_icloud_address("not an address"));
+    }
+
+    #[test]
+    fn test_is_icloud_host() {
+        assert!(is_icloud_host("imap.mail.me.com"));
+        assert!(is_icloud_host("P02-IMAP.mail.me.com."));
+        assert!(!is_icloud_host("imap.gmail.com"));
+        assert!(!is_icloud_host("mail.me.com.example.org"));
+    }
+
+    #[test]
+    fn test_is_icloud_account() {
+        assert!(is_icloud_account("a@example.org", Some("imap.mail.me.com")));
+        assert!(!is_icloud_account("a@icloud.com", Some("imap.example.org")));
+        assert!(is_icloud_account("a@icloud.com", None));
+        assert!(is_icloud_account("a@icloud.com", Some("")));
+        assert!(!is_icloud_account("a@example.org", None));
+    }
+
+    #[test]
+    fn test_folder_name() {
+        assert_eq!(folder_name("Trash"), Some("Deleted Messages"));
+        assert_eq!(folder_name("Spam"), Some("Junk"));
+        assert_eq!(folder_name("Projects"), None);
+    }
+}
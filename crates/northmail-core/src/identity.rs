@@ -0,0 +1,315 @@
+//! Identities: the addresses an account sends from
+//!
+//! An account always sends from its own address. Identities add aliases
+//! (another address the same mailbox receives, such as `sales@` or a
+//! plus-address), each with its own display name and signature, and
+//! optionally its own SMTP server when the provider won't send as the
+//! alias. An identity with the account's own address just gives that
+//! address a name and signature. Replies go out from the identity the
+//! original message was addressed to.
+//!
+//! A signature is plain text, which the composer shows and edits with the
+//! rest of the body, with an optional HTML version and image. The plain
+//! block goes at the end of a new message and above the quoted text of a
+//! reply or forward; when the message goes out as HTML the block is swapped
+//! for the HTML version, with the image sent inline.
+
+/// Line a signature starts after, as RFC 3676 has it ("dash dash space")
+pub const SIGNATURE_SEPARATOR: &str = "-- ";
+
+/// Content-ID the HTML signature shows its image by
+pub const SIGNATURE_IMAGE_CID: &str = "signature-image@northmail";
+
+/// Largest signature image taken, as it goes out with every message
+pub const MAX_SIGNATURE_IMAGE_BYTES: usize = 256 * 1024;
+
+/// An address an account sends from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// Database ID; 0 until stored
+    pub id: i64,
+    pub account_id: String,
+    /// Display name in From; `None` uses the user's name
+    pub name: Option<String>,
+    pub email: String,
+    pub signature: Option<Signature>,
+    /// SMTP server to send through instead of the account's
+    pub smtp: Option<SmtpOverride>,
+}
+
+/// SMTP server an identity sends through. Its password is kept in the
+/// keyring under the identity's address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtpOverride {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+}
+
+/// An identity's signature
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Signature {
+    /// Plain text, put in the body as the user writes
+    pub text: String,
+    /// HTML sent in place of the text when the message has an HTML part
+    pub html: Option<String>,
+    /// Image the HTML shows as `cid:` [`SIGNATURE_IMAGE_CID`]
+    pub image: Option<SignatureImage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureImage {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Signature {
+    /// Whether there is anything to put in a message
+    pub fn is_empty(&self) -> bool {
+        self.text.trim().is_empty() && self.html_fragment().is_none()
+    }
+
+    /// Whether the message should go out as HTML to show this signature
+    pub fn has_html(&self) -> bool {
+        self.html_fragment().is_some()
+    }
+
+    /// The HTML version with its image: the HTML given, or the text
+    /// escaped when only an image was added
+    fn html_fragment(&self) -> Option<String> {
+        let html = self.html.as_deref().map(str::trim).filter(|html| !html.is_empty());
+        let image = self.image.as_ref().map(|_| format!("<br><img src=\"cid:{}\" alt=\"\">", SIGNATURE_IMAGE_CID));
+        match (html, image) {
+            (None, None) => None,
+            (html, image) => Some(format!(
+                "{}{}",
+                html.map(str::to_string).unwrap_or_else(|| text_to_html(self.text.trim_end())),
+                image.unwrap_or_default()
+            )),
+        }
+    }
+
+    /// The block the signature adds to a message's HTML part
+    fn html_block(&self) -> Option<String> {
+        self.html_fragment()
+            .map(|html| format!("<br><br><div class=\"northmail-signature\">{}<br>{}</div>", SIGNATURE_SEPARATOR, html))
+    }
+}
+
+/// Where a signature goes in a message being written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignaturePlacement {
+    /// After what the user writes: new messages
+    End,
+    /// Between what the user writes and the quoted message: replies and
+    /// forwards
+    AboveQuote,
+}
+
+impl Identity {
+    /// `Name <email>`, or the address alone
+    pub fn label(&self) -> String {
+        match self.name.as_deref().filter(|name| !name.is_empty()) {
+            Some(name) => format!("{} <{}>", name, self.email),
+            None => self.email.clone(),
+        }
+    }
+
+    pub fn matches(&self, email: &str) -> bool {
+        self.email.eq_ignore_ascii_case(email.trim())
+    }
+}
+
+/// The identity a message was addressed to, given its recipients in
+/// order (To before Cc)
+pub fn addressed_identity<'a, 'b>(
+    identities: &'a [Identity],
+    recipients: impl IntoIterator<Item = &'b str>,
+) -> Option<&'a Identity> {
+    recipients
+        .into_iter()
+        .find_map(|recipient| identities.iter().find(|identity| identity.matches(recipient)))
+}
+
+/// The block a signature adds to a message's text, which the composer looks
+/// for to swap it when the user picks another identity. `None` when the
+/// signature has no text.
+pub fn signature_block(signature: &Signature) -> Option<String> {
+    let text = signature.text.trim_end();
+    (!text.trim().is_empty()).then(|| format!("\n\n{}\n{}", SIGNATURE_SEPARATOR, text))
+}
+
+/// Byte offset in `body` where a signature goes: after the text the user
+/// wrote, which for `AboveQuote` ends where the quoted message starts
+pub fn signature_offset(body: &str, placement: SignaturePlacement) -> usize {
+    let written = match placement {
+        SignaturePlacement::End => body,
+        SignaturePlacement::AboveQuote => &body[..quote_start(body).unwrap_or(body.len())],
+    };
+    written.trim_end().len()
+}
+
+/// Where the quoted message starts in a reply or forward: its attribution
+/// line ("On …, … wrote:") or the first `>` line, or a forwarded message's
+/// dashed header line
+fn quote_start(body: &str) -> Option<usize> {
+    let mut offset = 0;
+    let mut previous: Option<(usize, &str)> = None;
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed.starts_with("----------") {
+            return Some(offset);
+        }
+        if trimmed.starts_with('>') {
+            return Some(match previous {
+                Some((start, text)) if text.ends_with(':') => start,
+                _ => offset,
+            });
+        }
+        if !trimmed.is_empty() {
+            previous = Some((offset, trimmed));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// `body` with `signature`'s text block put in its place
+pub fn insert_signature(body: &str, signature: Option<&Signature>, placement: SignaturePlacement) -> String {
+    match signature.and_then(signature_block) {
+        Some(block) => {
+            let offset = signature_offset(body, placement);
+            format!("{}{}{}", &body[..offset], block, &body[offset..])
+        }
+        None => body.to_string(),
+    }
+}
+
+/// Text as HTML the way the composer writes an unformatted body
+pub fn text_to_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '\n' => html.push_str("<br>"),
+            _ => html.push(ch),
+        }
+    }
+    html
+}
+
+/// `html`, a message's HTML part written from its text, with the text
+/// block of `signature` replaced by the HTML version. `None` when the
+/// signature has no HTML version or its block was edited away.
+pub fn html_with_signature(html: &str, signature: &Signature) -> Option<String> {
+    let html_block = signature.html_block()?;
+    match signature_block(signature) {
+        Some(text_block) => {
+            let text_block = text_to_html(&text_block);
+            html.contains(&text_block).then(|| html.replacen(&text_block, &html_block, 1))
+        }
+        // An image-only signature has no text in the body to stand in for
+        None => Some(format!("{}{}", html, html_block)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(email: &str) -> Identity {
+        Identity {
+            account_id: "a".to_string(),
+            email: email.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_addressed_identity() {
+        let identities = [identity("me@example.com"), identity("sales@example.com")];
+        let found = addressed_identity(&identities, ["friend@example.org", "Sales@Example.com"]);
+        assert_eq!(found.map(|i| i.email.as_str()), Some("sales@example.com"));
+        // The first recipient that is one of ours wins
+        let found = addressed_identity(&identities, ["me@example.com", "sales@example.com"]);
+        assert_eq!(found.map(|i| i.email.as_str()), Some("me@example.com"));
+        assert!(addressed_identity(&identities, ["other@example.com"]).is_none());
+    }
+
+    fn text(text: &str) -> Signature {
+        Signature {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_insert_signature_in_new_message() {
+        let ann = text("Ann\n");
+        assert_eq!(insert_signature("", Some(&ann), SignaturePlacement::End), "\n\n-- \nAnn");
+        assert_eq!(
+            insert_signature("Hello\n\n", Some(&ann), SignaturePlacement::End),
+            "Hello\n\n-- \nAnn\n\n"
+        );
+        assert_eq!(insert_signature("Hello", Some(&text("  ")), SignaturePlacement::End), "Hello");
+        assert_eq!(insert_signature("Hello", None, SignaturePlacement::End), "Hello");
+    }
+
+    #[test]
+    fn test_insert_signature_above_quote() {
+        let ann = text("Ann");
+        let quoted = "\n\nOn Monday, Bob wrote:\n> Hi\n";
+        assert_eq!(
+            insert_signature(quoted, Some(&ann), SignaturePlacement::AboveQuote),
+            "\n\n-- \nAnn\n\nOn Monday, Bob wrote:\n> Hi\n"
+        );
+        assert_eq!(
+            insert_signature("Thanks!\n\nOn Monday, Bob wrote:\n> Hi\n", Some(&ann), SignaturePlacement::AboveQuote),
+            "Thanks!\n\n-- \nAnn\n\nOn Monday, Bob wrote:\n> Hi\n"
+        );
+        let forward = "\n\n---------- Forwarded message ----------\nFrom: Bob\n";
+        assert_eq!(
+            insert_signature(forward, Some(&ann), SignaturePlacement::AboveQuote),
+            format!("\n\n-- \nAnn{}", forward)
+        );
+        // Nothing quoted: same as a new message
+        assert_eq!(
+            signature_offset("Hi\n", SignaturePlacement::AboveQuote),
+            signature_offset("Hi\n", SignaturePlacement::End)
+        );
+    }
+
+    #[test]
+    fn test_html_with_signature() {
+        let signature = Signature {
+            text: "Ann <Sales>".to_string(),
+            html: Some("<b>Ann</b>".to_string()),
+            image: Some(SignatureImage {
+                mime_type: "image/png".to_string(),
+                data: vec![1, 2, 3],
+            }),
+        };
+        assert!(signature.has_html());
+        let body = insert_signature("Hi", Some(&signature), SignaturePlacement::End);
+        let html = html_with_signature(&text_to_html(&body), &signature).unwrap();
+        assert_eq!(
+            html,
+            "Hi<br><br><div class=\"northmail-signature\">-- <br><b>Ann</b>\
+             <br><img src=\"cid:signature-image@northmail\" alt=\"\"></div>"
+        );
+        // The user deleted it
+        assert_eq!(html_with_signature("Hi", &signature), None);
+        // Text only: nothing to swap in
+        assert_eq!(html_with_signature(&text_to_html(&body), &text("Ann")), None);
+    }
+
+    #[test]
+    fn test_label() {
+        let mut sales = identity("sales@example.com");
+        assert_eq!(sales.label(), "sales@example.com");
+        sales.name = Some("Ann at Sales".to_string());
+        assert_eq!(sales.label(), "Ann at Sales <sales@example.com>");
+    }
+}
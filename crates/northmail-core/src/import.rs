@@ -0,0 +1,671 @@
+//! Importing messages from mbox files and Maildirs
+//!
+//! An mbox file, as Thunderbird and most other clients keep their local
+//! folders, holds messages one after another, each starting on a `From `
+//! line. A Maildir, as mutt, notmuch and Evolution keep theirs, has a file
+//! per message in its `cur` and `new` directories, with the flags in the
+//! file name. Imported messages are APPENDed to a folder in batches sized
+//! by [`ImportLimits`], pausing between batches so providers don't throttle
+//! or lock the account, or kept in a local folder that only the cache
+//! holds (see [`local_message`]). Messages whose Message-ID the folder
+//! already holds, or that came earlier in the same source, are skipped, so
+//! an import that stopped part way can simply be run again.
+
+use crate::models::{AttachmentInfo, DbMessage};
+use mail_parser::{MessageParser, MimeHeaders};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+
+/// `folder_type` of a local folder, one that's only in the cache and
+/// never synced with the server
+pub const LOCAL_FOLDER_TYPE: &str = "local";
+
+/// Longest snippet kept for a message in a local folder
+const SNIPPET_LENGTH: usize = 200;
+
+/// Thunderbird's `X-Mozilla-Status` bit for a read message
+const MOZILLA_READ: u32 = 0x0001;
+/// ...for a message replied to
+const MOZILLA_REPLIED: u32 = 0x0002;
+/// ...for a starred message
+const MOZILLA_MARKED: u32 = 0x0004;
+/// ...for a message deleted but not yet compacted out of the file
+const MOZILLA_EXPUNGED: u32 = 0x0008;
+
+/// How often a throttled batch is tried again before the import gives up
+pub const THROTTLE_RETRIES: u32 = 3;
+
+/// Shortest wait before trying a throttled batch again
+const MIN_RETRY_PAUSE: Duration = Duration::from_secs(5);
+
+/// A message read from an mbox file or a Maildir
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportMessage {
+    /// The message with CRLF line endings, as APPEND wants it
+    pub data: Vec<u8>,
+    /// Message-ID, see [`normalize_message_id`]
+    pub message_id: Option<String>,
+    /// Read, from Thunderbird's `X-Mozilla-Status` or the Maildir `S` flag
+    pub seen: bool,
+    /// Starred, from Thunderbird's `X-Mozilla-Status` or the Maildir `F` flag
+    pub flagged: bool,
+    /// Replied to, from Thunderbird's `X-Mozilla-Status` or the Maildir `R`
+    /// flag
+    pub answered: bool,
+}
+
+impl ImportMessage {
+    /// IMAP flags to APPEND the message with
+    pub fn flags(&self) -> Vec<&'static str> {
+        [
+            (self.seen, "\\Seen"),
+            (self.flagged, "\\Flagged"),
+            (self.answered, "\\Answered"),
+        ]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect()
+    }
+}
+
+/// Split an mbox file into its messages. A message starts at a `From `
+/// line at the start of the file or after a blank line, and `>From `
+/// quoting in bodies is undone (mboxrd). Messages Thunderbird deleted but
+/// hasn't compacted away yet are left out.
+pub fn parse_mbox(data: &[u8]) -> Vec<ImportMessage> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<&[u8]>> = None;
+    let mut previous_blank = true;
+
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        let line = line
+            .strip_suffix(b"\n")
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .unwrap_or(line);
+        if previous_blank && line.starts_with(b"From ") {
+            messages.extend(current.take().and_then(mbox_message));
+            current = Some(Vec::new());
+            previous_blank = false;
+            continue;
+        }
+        previous_blank = line.is_empty();
+        if let Some(lines) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    messages.extend(current.and_then(mbox_message));
+    messages
+}
+
+/// A message from its lines, without the `From ` line
+fn mbox_message(mut lines: Vec<&[u8]>) -> Option<ImportMessage> {
+    // The blank line before the next `From ` line separates messages
+    if lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    let header_end = lines
+        .iter()
+        .position(|line| line.is_empty())
+        .unwrap_or(lines.len());
+    let mut status = 0;
+    let mut message_id = None;
+    for (name, value) in unfold_headers(&lines[..header_end]) {
+        if name.eq_ignore_ascii_case("X-Mozilla-Status") {
+            status = u32::from_str_radix(value.trim(), 16).unwrap_or(0);
+        } else if name.eq_ignore_ascii_case("Message-ID") && message_id.is_none() {
+            message_id = normalize_message_id(&value);
+        }
+    }
+    if status & MOZILLA_EXPUNGED != 0 {
+        return None;
+    }
+
+    let mut data = Vec::with_capacity(lines.iter().map(|line| line.len() + 2).sum());
+    for line in lines {
+        data.extend_from_slice(unquote_from(line));
+        data.extend_from_slice(b"\r\n");
+    }
+    Some(ImportMessage {
+        data,
+        message_id,
+        seen: status & MOZILLA_READ != 0,
+        flagged: status & MOZILLA_MARKED != 0,
+        answered: status & MOZILLA_REPLIED != 0,
+    })
+}
+
+/// Read the messages of the Maildir at `dir`, from `cur` and `new`, in
+/// delivery order as far as their names tell. Messages flagged as trashed
+/// (`T`) are left out, as mail tools do until they expunge them.
+pub fn read_maildir(dir: &Path) -> std::io::Result<Vec<ImportMessage>> {
+    if !dir.join("cur").is_dir() && !dir.join("new").is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a Maildir", dir.display()),
+        ));
+    }
+
+    let mut files = Vec::new();
+    for sub in ["cur", "new"] {
+        let Ok(entries) = std::fs::read_dir(dir.join(sub)) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with('.') && entry.file_type()?.is_file() {
+                files.push((name, entry.path()));
+            }
+        }
+    }
+    files.sort();
+
+    let mut messages = Vec::new();
+    for (name, path) in files {
+        let info = maildir_info(&name);
+        if info.contains('T') {
+            continue;
+        }
+        let data = std::fs::read(&path)?;
+        let message_id = MessageParser::default().parse_headers(&data).and_then(|m| {
+            m.message_id()
+                .and_then(|id| normalize_message_id(&format!("<{}>", id)))
+        });
+        messages.push(ImportMessage {
+            data: crlf(&data),
+            message_id,
+            seen: info.contains('S'),
+            flagged: info.contains('F'),
+            answered: info.contains('R'),
+        });
+    }
+    Ok(messages)
+}
+
+/// Flags of a Maildir file name, after its `:2,` (or `!2,`, as some
+/// systems that can't have colons in names use)
+fn maildir_info(name: &str) -> &str {
+    name.rsplit_once(":2,")
+        .or_else(|| name.rsplit_once("!2,"))
+        .map(|(_, info)| info)
+        .unwrap_or("")
+}
+
+/// Read the messages at `path`: a Maildir when it's a directory, otherwise
+/// an mbox file
+pub fn read_source(path: &Path) -> std::io::Result<Vec<ImportMessage>> {
+    if path.is_dir() {
+        read_maildir(path)
+    } else {
+        Ok(parse_mbox(&std::fs::read(path)?))
+    }
+}
+
+/// A name for a local folder. Local folders have no hierarchy, so the
+/// delimiters servers use for one become dashes.
+pub fn local_folder_name(name: &str) -> String {
+    name.trim().replace(['/', '.'], "-")
+}
+
+/// `data` with bare LF line endings made CRLF
+fn crlf(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 32);
+    let mut previous = 0;
+    for &b in data {
+        if b == b'\n' && previous != b'\r' {
+            out.push(b'\r');
+        }
+        out.push(b);
+        previous = b;
+    }
+    out
+}
+
+/// A message to keep in a local folder: its header row and what would
+/// otherwise be downloaded when it's opened
+#[derive(Debug, Clone)]
+pub struct LocalMessage {
+    /// The cache row, without a folder or UID yet
+    pub header: DbMessage,
+    pub body_text: Option<String>,
+    pub body_html: Option<String>,
+    pub attachments: Vec<AttachmentInfo>,
+}
+
+/// Parse an imported message for a local folder. As there's no server to
+/// fetch it from later, the bodies and attachments are kept whole.
+pub fn local_message(message: &ImportMessage) -> Option<LocalMessage> {
+    let parsed = MessageParser::default().parse(&message.data)?;
+
+    let addresses = |address: Option<&mail_parser::Address>| {
+        let list: Vec<String> = address
+            .into_iter()
+            .flat_map(|a| a.iter())
+            .filter_map(|a| a.address.as_deref().map(str::to_string))
+            .collect();
+        (!list.is_empty()).then(|| list.join(", "))
+    };
+    let from = parsed.from().and_then(|a| a.first());
+    let body_text = parsed
+        .text_part(0)
+        .filter(|part| !part.is_text_html())
+        .and_then(|_| parsed.body_text(0))
+        .map(|text| text.into_owned());
+    let body_html = parsed
+        .html_part(0)
+        .filter(|part| part.is_text_html())
+        .and_then(|_| parsed.body_html(0))
+        .map(|html| html.into_owned());
+    let attachments: Vec<AttachmentInfo> = parsed
+        .attachments()
+        .map(|part| AttachmentInfo {
+            filename: part.attachment_name().unwrap_or("attachment").to_string(),
+            mime_type: part
+                .content_type()
+                .map(|ct| match ct.subtype() {
+                    Some(subtype) => format!("{}/{}", ct.ctype(), subtype),
+                    None => ct.ctype().to_string(),
+                })
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+            size: part.contents().len(),
+            content_id: part.content_id().map(str::to_string),
+            is_inline: part.content_id().is_some(),
+            data: part.contents().to_vec(),
+        })
+        .collect();
+
+    let header = DbMessage {
+        id: 0,
+        folder_id: 0,
+        uid: 0,
+        message_id: message.message_id.clone(),
+        subject: parsed.subject().map(str::to_string),
+        from_address: from.and_then(|a| a.address.as_deref().map(str::to_string)),
+        from_name: from.and_then(|a| a.name.as_deref().map(str::to_string)),
+        to_addresses: addresses(parsed.to()),
+        cc_addresses: addresses(parsed.cc()),
+        date_sent: parsed.date().map(|d| d.to_rfc822()),
+        date_epoch: parsed.date().map(|d| d.to_timestamp()),
+        snippet: parsed
+            .body_preview(SNIPPET_LENGTH)
+            .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" ")),
+        is_read: message.seen,
+        is_starred: message.flagged,
+        has_attachments: !attachments.is_empty(),
+        size: message.data.len() as i64,
+        maildir_path: None,
+        body_text: None,
+        body_html: None,
+        gmail_labels: None,
+        gmail_thread_id: None,
+        mention: None,
+        tags: None,
+        snoozed_until: None,
+        reply_later_at: None,
+    };
+    Some(LocalMessage {
+        header,
+        body_text,
+        body_html,
+        attachments,
+    })
+}
+
+/// Header fields as (name, value), continuation lines joined
+fn unfold_headers(lines: &[&[u8]]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push_str(&line);
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.to_string()));
+        }
+    }
+    headers
+}
+
+/// Undo mboxrd quoting: `>From ` and `>>From ` lose one `>`
+fn unquote_from(line: &[u8]) -> &[u8] {
+    let quotes = line.iter().take_while(|&&b| b == b'>').count();
+    if quotes > 0 && line[quotes..].starts_with(b"From ") {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+/// A Message-ID as compared between the file and the folder: the
+/// `<...>` part of the header's value, or the trimmed value when it has no
+/// angle brackets
+pub fn normalize_message_id(value: &str) -> Option<String> {
+    let value = value.trim();
+    let id = match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start..=end],
+        _ => value,
+    };
+    (!id.is_empty() && id != "<>").then(|| id.to_string())
+}
+
+/// Messages still to import: those whose Message-ID isn't in `existing`
+/// and didn't come earlier in `messages`. Messages without a Message-ID are
+/// always kept. Returns them with how many were skipped.
+pub fn skip_duplicates(
+    messages: Vec<ImportMessage>,
+    existing: &HashSet<String>,
+) -> (Vec<ImportMessage>, usize) {
+    let total = messages.len();
+    let mut seen = HashSet::new();
+    let kept: Vec<ImportMessage> = messages
+        .into_iter()
+        .filter(|message| match &message.message_id {
+            Some(id) => !existing.contains(id) && seen.insert(id.clone()),
+            None => true,
+        })
+        .collect();
+    let skipped = total - kept.len();
+    (kept, skipped)
+}
+
+/// How far an import got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportCounts {
+    /// Messages in the file or Maildir
+    pub total: usize,
+    /// Messages uploaded or stored so far
+    pub imported: usize,
+    /// Messages skipped as already in the folder
+    pub skipped: usize,
+}
+
+/// How fast messages are uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportLimits {
+    /// Most messages per batch
+    pub batch_messages: usize,
+    /// Most bytes per batch; a larger message gets a batch of its own
+    pub batch_bytes: usize,
+    /// Pause after each batch
+    pub pause: Duration,
+}
+
+impl ImportLimits {
+    /// Limits for a provider, as GNOME Online Accounts names it
+    pub fn for_provider(provider_type: &str) -> Self {
+        match provider_type {
+            // Gmail caps IMAP uploads per day and drops connections that
+            // upload too fast
+            "google" => Self {
+                batch_messages: 20,
+                batch_bytes: 10 * 1024 * 1024,
+                pause: Duration::from_secs(1),
+            },
+            // Outlook throttles each mailbox and answers with "Server
+            // Unavailable" when it does
+            "windows_live" | "microsoft" => Self {
+                batch_messages: 10,
+                batch_bytes: 5 * 1024 * 1024,
+                pause: Duration::from_secs(2),
+            },
+            _ => Self {
+                batch_messages: 50,
+                batch_bytes: 20 * 1024 * 1024,
+                pause: Duration::from_millis(250),
+            },
+        }
+    }
+
+    /// Wait before try `attempt` (from 1) of a throttled batch: the batch
+    /// pause doubled each time, at least [`MIN_RETRY_PAUSE`]
+    pub fn retry_pause(&self, attempt: u32) -> Duration {
+        (self.pause * 2u32.pow(attempt)).max(MIN_RETRY_PAUSE * attempt)
+    }
+}
+
+/// Split messages of `sizes` bytes into consecutive batches within
+/// `limits`, as index ranges
+pub fn plan_batches(sizes: &[usize], limits: &ImportLimits) -> Vec<Range<usize>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+    for (i, &size) in sizes.iter().enumerate() {
+        let full = i - start >= limits.batch_messages.max(1) || bytes + size > limits.batch_bytes;
+        if i > start && full {
+            batches.push(start..i);
+            start = i;
+            bytes = 0;
+        }
+        bytes += size;
+    }
+    if start < sizes.len() {
+        batches.push(start..sizes.len());
+    }
+    batches
+}
+
+/// Whether an APPEND failure looks like the server throttling uploads,
+/// worth waiting out and trying again
+pub fn is_throttled(error: &str) -> bool {
+    let error = error.to_ascii_uppercase();
+    [
+        "[LIMIT]",
+        "[UNAVAILABLE]",
+        "[INUSE]",
+        "THROTTL",
+        "TOO MANY",
+        "SERVER UNAVAILABLE",
+        "TRY AGAIN",
+    ]
+    .iter()
+    .any(|sign| error.contains(sign))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mbox() {
+        let mbox = b"From alice@example.com Mon Jan  1 00:00:00 2024\n\
+            Message-ID: <one@example.com>\n\
+            X-Mozilla-Status: 0005\n\
+            Subject: First\n\
+            \n\
+            >From the start\n\
+            >>From quoted twice\n\
+            \n\
+            From bob@example.com Tue Jan  2 00:00:00 2024\n\
+            Message-Id:\n <two@example.com>\n\
+            Subject: Second\n\
+            \n\
+            Body with From in the middle\n\
+            \n\
+            From carol@example.com Wed Jan  3 00:00:00 2024\n\
+            Message-ID: <gone@example.com>\n\
+            X-Mozilla-Status: 0009\n\
+            \n\
+            Deleted\n";
+        let messages = parse_mbox(mbox);
+        assert_eq!(messages.len(), 2);
+
+        let first = &messages[0];
+        assert_eq!(first.message_id.as_deref(), Some("<one@example.com>"));
+        assert!(first.seen && first.flagged);
+        assert_eq!(first.flags(), &["\\Seen", "\\Flagged"]);
+        let text = String::from_utf8(first.data.clone()).unwrap();
+        assert!(text.starts_with("Message-ID: <one@example.com>\r\n"));
+        assert!(text.ends_with("\r\n\r\nFrom the start\r\n>From quoted twice\r\n"));
+
+        let second = &messages[1];
+        assert_eq!(second.message_id.as_deref(), Some("<two@example.com>"));
+        assert!(!second.seen && !second.flagged);
+        assert!(second.flags().is_empty());
+        assert!(String::from_utf8(second.data.clone())
+            .unwrap()
+            .ends_with("\r\nBody with From in the middle\r\n"));
+    }
+
+    #[test]
+    fn test_parse_mbox_crlf_and_junk() {
+        // Text before the first From line isn't a message
+        let mbox = b"junk\r\n\r\nFrom x\r\nSubject: A\r\n\r\nBody\r\n";
+        let messages = parse_mbox(mbox);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].data, b"Subject: A\r\n\r\nBody\r\n");
+        assert_eq!(messages[0].message_id, None);
+        assert!(parse_mbox(b"").is_empty());
+    }
+
+    #[test]
+    fn test_read_maildir() {
+        let dir =
+            std::env::temp_dir().join(format!("northmail-import-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["tmp", "new", "cur"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        let write = |name: &str, data: &str| std::fs::write(dir.join(name), data).unwrap();
+        write("cur/2.host:2,RS", "Message-ID: <two@x>\n\nSecond\n");
+        write("cur/1.host:2,F", "Message-ID: <one@x>\n\nFirst\n");
+        write("cur/3.host:2,ST", "Message-ID: <gone@x>\n\nTrashed\n");
+        write("new/4.host", "Subject: New\r\n\r\nFourth\r\n");
+        write("cur/.hidden", "not a message");
+
+        let messages = read_maildir(&dir).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].message_id.as_deref(), Some("<one@x>"));
+        assert!(messages[0].flagged && !messages[0].seen);
+        assert_eq!(messages[0].data, b"Message-ID: <one@x>\r\n\r\nFirst\r\n");
+        assert_eq!(messages[1].flags(), vec!["\\Seen", "\\Answered"]);
+        assert_eq!(messages[2].message_id, None);
+        assert_eq!(messages[2].data, b"Subject: New\r\n\r\nFourth\r\n");
+
+        assert!(read_source(&dir).is_ok());
+        assert_eq!(local_folder_name(" Old/mail.2019 "), "Old-mail-2019");
+        assert!(read_maildir(&dir.join("cur")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_message() {
+        let data = "From: Ann <ann@example.com>\r\n\
+            To: bob@example.org, carol@example.org\r\n\
+            Subject: Report\r\n\
+            Date: Mon, 1 Jan 2024 10:00:00 +0000\r\n\
+            Message-ID: <r@example.com>\r\n\
+            Content-Type: multipart/mixed; boundary=b\r\n\
+            \r\n\
+            --b\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            See the   attached report.\r\n\
+            --b\r\n\
+            Content-Type: application/pdf; name=report.pdf\r\n\
+            Content-Disposition: attachment; filename=report.pdf\r\n\
+            \r\n\
+            PDF\r\n\
+            --b--\r\n";
+        let message = ImportMessage {
+            data: data.as_bytes().to_vec(),
+            message_id: Some("<r@example.com>".to_string()),
+            seen: true,
+            flagged: false,
+            answered: false,
+        };
+        let local = local_message(&message).unwrap();
+        let header = &local.header;
+        assert_eq!(header.subject.as_deref(), Some("Report"));
+        assert_eq!(header.from_address.as_deref(), Some("ann@example.com"));
+        assert_eq!(header.from_name.as_deref(), Some("Ann"));
+        assert_eq!(
+            header.to_addresses.as_deref(),
+            Some("bob@example.org, carol@example.org")
+        );
+        assert_eq!(header.date_epoch, Some(1704103200));
+        assert_eq!(header.snippet.as_deref(), Some("See the attached report."));
+        assert!(header.is_read && header.has_attachments);
+        assert_eq!(
+            local.body_text.as_deref(),
+            Some("See the   attached report.")
+        );
+        assert_eq!(local.body_html, None);
+        assert_eq!(local.attachments.len(), 1);
+        assert_eq!(local.attachments[0].filename, "report.pdf");
+        assert_eq!(local.attachments[0].mime_type, "application/pdf");
+        assert_eq!(local.attachments[0].data, b"PDF");
+    }
+
+    #[test]
+    fn test_normalize_message_id() {
+        assert_eq!(
+            normalize_message_id(" <a@b> (comment)").as_deref(),
+            Some("<a@b>")
+        );
+        assert_eq!(normalize_message_id("a@b").as_deref(), Some("a@b"));
+        assert_eq!(normalize_message_id("  "), None);
+        assert_eq!(normalize_message_id("<>"), None);
+    }
+
+    #[test]
+    fn test_skip_duplicates() {
+        let message = |id: Option<&str>| ImportMessage {
+            data: Vec::new(),
+            message_id: id.map(str::to_string),
+            seen: false,
+            flagged: false,
+            answered: false,
+        };
+        let messages = vec![
+            message(Some("<a@x>")),
+            message(Some("<b@x>")),
+            message(None),
+            message(Some("<b@x>")),
+            message(None),
+        ];
+        let existing = HashSet::from(["<a@x>".to_string()]);
+        let (kept, skipped) = skip_duplicates(messages, &existing);
+        assert_eq!(skipped, 2);
+        let ids: Vec<_> = kept.iter().map(|m| m.message_id.as_deref()).collect();
+        assert_eq!(ids, vec![Some("<b@x>"), None, None]);
+    }
+
+    #[test]
+    fn test_plan_batches() {
+        let limits = ImportLimits {
+            batch_messages: 3,
+            batch_bytes: 100,
+            pause: Duration::ZERO,
+        };
+        assert_eq!(plan_batches(&[10; 7], &limits), vec![0..3, 3..6, 6..7]);
+        // Bytes close a batch early; an oversized message goes alone
+        assert_eq!(
+            plan_batches(&[60, 30, 20, 500, 10], &limits),
+            vec![0..2, 2..3, 3..4, 4..5]
+        );
+        assert!(plan_batches(&[], &limits).is_empty());
+    }
+
+    #[test]
+    fn test_throttling() {
+        assert!(is_throttled(
+            "APPEND failed: A0003 NO [LIMIT] Too many uploads"
+        ));
+        assert!(is_throttled("A0002 NO Server Unavailable. 15"));
+        assert!(!is_throttled("A0002 NO [OVERQUOTA] Mailbox is full"));
+
+        let limits = ImportLimits::for_provider("google");
+        assert_eq!(limits.retry_pause(1), Duration::from_secs(5));
+        assert_eq!(limits.retry_pause(3), Duration::from_secs(15));
+        let limits = ImportLimits::for_provider("microsoft");
+        assert_eq!(limits.retry_pause(2), Duration::from_secs(10));
+        assert_eq!(limits.retry_pause(3), Duration::from_secs(16));
+    }
+}
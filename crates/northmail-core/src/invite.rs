@@ -0,0 +1,806 @@
+//! Meeting invitations (iCalendar over email, iTIP and iMIP)
+//!
+//! A message inviting the user to an event carries a text/calendar part
+//! whose METHOD is REQUEST ([`invite_in`]). The event's organizer, times and
+//! attendees are read into an [`Invite`], which is kept with the message.
+//! Answering it sends the organizer an iCalendar REPLY naming the user as
+//! attendee with their [`Rsvp`] ([`reply_message`]), as RFC 5546 describes.
+//!
+//! Only the first event of a calendar object is read, and of its time
+//! zones only the yearly rules nearly every client writes; a time in a
+//! zone that can't be worked out is taken as local time.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use mail_parser::{Message, MimeHeaders};
+use northmail_smtp::OutgoingMessage;
+use serde::{Deserialize, Serialize};
+
+/// PRODID of the calendar objects we send
+const PRODUCT_ID: &str = "-//NorthMail//NorthMail//EN";
+
+/// Longest content line, in octets, before it is folded (RFC 5545 §3.1)
+const MAX_LINE: usize = 75;
+
+/// How the user answered an invitation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rsvp {
+    Accepted,
+    Declined,
+    Tentative,
+}
+
+impl Rsvp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rsvp::Accepted => "accepted",
+            Rsvp::Declined => "declined",
+            Rsvp::Tentative => "tentative",
+        }
+    }
+
+    /// Answer stored as `text`
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "accepted" => Some(Rsvp::Accepted),
+            "declined" => Some(Rsvp::Declined),
+            "tentative" => Some(Rsvp::Tentative),
+            _ => None,
+        }
+    }
+
+    /// Value of an attendee's PARTSTAT for this answer
+    pub fn partstat(&self) -> &'static str {
+        match self {
+            Rsvp::Accepted => "ACCEPTED",
+            Rsvp::Declined => "DECLINED",
+            Rsvp::Tentative => "TENTATIVE",
+        }
+    }
+
+    /// The answer a PARTSTAT gives, if it gives one
+    pub fn from_partstat(partstat: &str) -> Option<Self> {
+        match partstat.to_ascii_uppercase().as_str() {
+            "ACCEPTED" => Some(Rsvp::Accepted),
+            "DECLINED" => Some(Rsvp::Declined),
+            "TENTATIVE" => Some(Rsvp::Tentative),
+            _ => None,
+        }
+    }
+}
+
+/// Someone taking part in an event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attendee {
+    pub email: String,
+    pub name: Option<String>,
+    /// Their answer so far, as the organizer last sent it
+    pub status: Option<Rsvp>,
+}
+
+/// A calendar event sent by email
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invite {
+    /// What the message asks: `REQUEST`, `CANCEL`, `PUBLISH`…
+    pub method: String,
+    /// UID of the event, which replies refer to
+    pub uid: String,
+    /// Revision of the event; a reply names the one it answers
+    pub sequence: i64,
+    /// The RECURRENCE-ID line as written, when only one occurrence of a
+    /// repeating event is meant, so replies can repeat it
+    pub recurrence_id: Option<String>,
+    pub summary: Option<String>,
+    pub location: Option<String>,
+    /// Start as a Unix timestamp
+    pub starts_at: Option<i64>,
+    /// End as a Unix timestamp
+    pub ends_at: Option<i64>,
+    /// The event takes whole days rather than a time
+    pub all_day: bool,
+    pub organizer: Option<Attendee>,
+    pub attendees: Vec<Attendee>,
+    /// The event was called off, by a CANCEL or its STATUS
+    pub cancelled: bool,
+}
+
+impl Invite {
+    /// Whether the organizer wants attendees to answer
+    pub fn wants_reply(&self) -> bool {
+        self.method == "REQUEST" && !self.cancelled && self.organizer.is_some()
+    }
+
+    /// The attendee with `email`, if they are invited
+    pub fn attendee(&self, email: &str) -> Option<&Attendee> {
+        self.attendees
+            .iter()
+            .find(|attendee| attendee.email.eq_ignore_ascii_case(email))
+    }
+}
+
+/// An invitation kept with a cached message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredInvite {
+    pub invite: Invite,
+    /// What the user answered from here, if they did
+    pub response: Option<Rsvp>,
+}
+
+/// The invitation in `message`, if it has a calendar part with an event
+pub fn invite_in(message: &Message) -> Option<Invite> {
+    message.parts.iter().find_map(|part| {
+        let content_type = part.content_type()?;
+        let subtype = content_type.subtype()?;
+        let is_calendar = (content_type.ctype().eq_ignore_ascii_case("text")
+            && subtype.eq_ignore_ascii_case("calendar"))
+            || (content_type.ctype().eq_ignore_ascii_case("application") && subtype.eq_ignore_ascii_case("ics"));
+        if !is_calendar {
+            return None;
+        }
+        parse_calendar(&String::from_utf8_lossy(part.contents()))
+    })
+}
+
+/// A content line: `NAME;PARAM=value:value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Property {
+    /// Uppercase name
+    name: String,
+    /// Parameters with uppercase names and unquoted values
+    params: Vec<(String, String)>,
+    value: String,
+    /// The line as written, unfolded
+    raw: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Content lines of a calendar object, joined back where they were folded
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // The value starts at the first colon outside a quoted parameter
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in head.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    let mut parts = parts.into_iter();
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            Some((name.trim().to_ascii_uppercase(), value.to_string()))
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: value.to_string(),
+        raw: line.to_string(),
+    })
+}
+
+/// A TEXT value with its escapes undone
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+/// A TEXT value escaped for a content line
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Who an ORGANIZER or ATTENDEE property names
+fn person(property: &Property) -> Option<Attendee> {
+    let value = property.value.trim();
+    let email = match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+        _ => value,
+    };
+    if !email.contains('@') {
+        return None;
+    }
+    Some(Attendee {
+        email: email.to_string(),
+        name: property
+            .param("CN")
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty()),
+        status: property.param("PARTSTAT").and_then(Rsvp::from_partstat),
+    })
+}
+
+/// A STANDARD or DAYLIGHT part of a VTIMEZONE
+#[derive(Debug, Clone, Default)]
+struct ZoneRule {
+    /// First onset, in the local time before it
+    start: Option<NaiveDateTime>,
+    /// Offset from UTC from the onset on, in seconds
+    offset_to: Option<i32>,
+    /// Month and `BYDAY` of a yearly RRULE, such as 3 and `-1SU`
+    yearly: Option<(u32, String)>,
+}
+
+impl ZoneRule {
+    /// When this rule last took effect at or before `time`
+    fn onset_before(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = self.start?;
+        let Some((month, by_day)) = &self.yearly else {
+            return (start <= time).then_some(start);
+        };
+        [time.year(), time.year() - 1]
+            .into_iter()
+            .filter(|year| *year >= start.year())
+            .filter_map(|year| {
+                let day = nth_weekday(year, *month, by_day)?;
+                Some(day.and_time(start.time()))
+            })
+            .find(|onset| *onset <= time)
+    }
+}
+
+/// The day `by_day` names in `month` of `year`: `2SU` is the second Sunday,
+/// `-1SU` the last
+fn nth_weekday(year: i32, month: u32, by_day: &str) -> Option<NaiveDate> {
+    let split = by_day.len().checked_sub(2)?;
+    let (n, day) = by_day.split_at(split);
+    let n: i32 = if n.is_empty() || n == "+" { 1 } else { n.trim_start_matches('+').parse().ok()? };
+    let weekday = match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let n = u8::try_from(n.unsigned_abs()).ok().filter(|n| *n > 0)?;
+    if by_day.starts_with('-') {
+        // Count back from the first of the next month
+        let next = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        let last = next.pred_opt()?;
+        let back = (last.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+        let day = last - Duration::days(i64::from(back) + 7 * i64::from(n - 1));
+        (day.month() == month).then_some(day)
+    } else {
+        NaiveDate::from_weekday_of_month_opt(year, month, weekday, n)
+    }
+}
+
+/// Offset like `+0100` or `-053000`, in seconds
+fn parse_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let sign = match value.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    if !(digits.len() == 4 || digits.len() == 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[0..2].parse().ok()?;
+    let minutes: i32 = digits[2..4].parse().ok()?;
+    let seconds: i32 = digits.get(4..6).map_or(Some(0), |s| s.parse().ok())?;
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Offset from UTC of the zone with `rules` at local `time`
+fn zone_offset(rules: &[ZoneRule], time: NaiveDateTime) -> Option<i32> {
+    rules
+        .iter()
+        .filter_map(|rule| Some((rule.onset_before(time)?, rule.offset_to?)))
+        .max_by_key(|(onset, _)| *onset)
+        .map(|(_, offset)| offset)
+        // Before any onset, take the first rule
+        .or_else(|| rules.iter().find_map(|rule| rule.offset_to))
+}
+
+/// A DATE-TIME or DATE value as a Unix timestamp, and whether it is a date
+fn parse_time(property: &Property, zones: &[(String, Vec<ZoneRule>)]) -> Option<(i64, bool)> {
+    let value = property.value.trim();
+    if property.param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        let local = Local.from_local_datetime(&date.and_time(NaiveTime::MIN)).earliest()?;
+        return Some((local.timestamp(), true));
+    }
+    if let Some(utc) = value.strip_suffix(['Z', 'z']) {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&time).timestamp(), false));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let offset = property
+        .param("TZID")
+        .and_then(|tzid| zones.iter().find(|(id, _)| id == tzid))
+        .and_then(|(_, rules)| zone_offset(rules, time));
+    let timestamp = match offset {
+        Some(offset) => time.and_utc().timestamp() - i64::from(offset),
+        None => Local.from_local_datetime(&time).earliest()?.timestamp(),
+    };
+    Some((timestamp, false))
+}
+
+/// A DURATION value like `PT1H30M` or `P1D`, in seconds
+fn parse_duration(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut rest = value.strip_prefix('P')?;
+    let mut seconds = 0i64;
+    let mut in_time = false;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('T') {
+            in_time = true;
+            rest = after;
+            continue;
+        }
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let n: i64 = rest[..digits].parse().ok()?;
+        let unit = match (&rest[digits..digits + 1], in_time) {
+            ("W", false) => 7 * 86400,
+            ("D", false) => 86400,
+            ("H", true) => 3600,
+            ("M", true) => 60,
+            ("S", true) => 1,
+            _ => return None,
+        };
+        seconds += n * unit;
+        rest = &rest[digits + 1..];
+    }
+    Some(sign * seconds)
+}
+
+/// The first event of an iCalendar object
+pub fn parse_calendar(text: &str) -> Option<Invite> {
+    let mut method = None;
+    let mut zones: Vec<(String, Vec<ZoneRule>)> = Vec::new();
+    let mut event: Option<Vec<Property>> = None;
+    let mut event_done = false;
+    // Components the current line is in, innermost last
+    let mut stack: Vec<String> = Vec::new();
+
+    for line in unfold(text) {
+        let Some(property) = parse_property(&line) else {
+            continue;
+        };
+        let component = property.value.trim().to_ascii_uppercase();
+        match property.name.as_str() {
+            "BEGIN" => {
+                match component.as_str() {
+                    "VEVENT" if !event_done && event.is_none() => event = Some(Vec::new()),
+                    "VTIMEZONE" => zones.push((String::new(), Vec::new())),
+                    "STANDARD" | "DAYLIGHT" if stack.last().is_some_and(|c| c == "VTIMEZONE") => {
+                        if let Some((_, rules)) = zones.last_mut() {
+                            rules.push(ZoneRule::default());
+                        }
+                    }
+                    _ => {}
+                }
+                stack.push(component);
+                continue;
+            }
+            "END" => {
+                if component == "VEVENT" && event.is_some() {
+                    event_done = true;
+                }
+                stack.pop();
+                continue;
+            }
+            _ => {}
+        }
+
+        match stack.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["VCALENDAR"] if property.name == "METHOD" => {
+                method = Some(property.value.trim().to_ascii_uppercase());
+            }
+            ["VCALENDAR", "VEVENT"] if !event_done => {
+                if let Some(properties) = event.as_mut() {
+                    properties.push(property);
+                }
+            }
+            ["VCALENDAR", "VTIMEZONE"] if property.name == "TZID" => {
+                if let Some((id, _)) = zones.last_mut() {
+                    *id = property.value.trim().to_string();
+                }
+            }
+            ["VCALENDAR", "VTIMEZONE", "STANDARD" | "DAYLIGHT"] => {
+                let Some(rule) = zones.last_mut().and_then(|(_, rules)| rules.last_mut()) else {
+                    continue;
+                };
+                match property.name.as_str() {
+                    "DTSTART" => {
+                        rule.start = NaiveDateTime::parse_from_str(property.value.trim(), "%Y%m%dT%H%M%S").ok();
+                    }
+                    "TZOFFSETTO" => rule.offset_to = parse_offset(&property.value),
+                    "RRULE" => rule.yearly = yearly_rule(&property.value),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let properties = event?;
+    let find = |name: &str| properties.iter().find(|p| p.name == name);
+    let text = |name: &str| {
+        find(name)
+            .map(|p| unescape(p.value.trim()))
+            .filter(|value| !value.is_empty())
+    };
+
+    let uid = text("UID")?;
+    let method = method.unwrap_or_else(|| "PUBLISH".to_string());
+    let start = find("DTSTART").and_then(|p| parse_time(p, &zones));
+    let ends_at = match (find("DTEND").and_then(|p| parse_time(p, &zones)), start) {
+        (Some((end, _)), _) => Some(end),
+        (None, Some((start, all_day))) => match find("DURATION").and_then(|p| parse_duration(&p.value)) {
+            Some(duration) => Some(start + duration),
+            // A date with no end lasts the day
+            None if all_day => Some(start + 86400),
+            None => None,
+        },
+        (None, None) => None,
+    };
+    let cancelled = method == "CANCEL" || text("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED"));
+
+    Some(Invite {
+        uid,
+        sequence: text("SEQUENCE").and_then(|s| s.parse().ok()).unwrap_or(0),
+        recurrence_id: find("RECURRENCE-ID").map(|p| p.raw.clone()),
+        summary: text("SUMMARY"),
+        location: text("LOCATION"),
+        starts_at: start.map(|(start, _)| start),
+        ends_at,
+        all_day: start.is_some_and(|(_, all_day)| all_day),
+        organizer: find("ORGANIZER").and_then(person),
+        attendees: properties
+            .iter()
+            .filter(|p| p.name == "ATTENDEE")
+            .filter_map(person)
+            .collect(),
+        cancelled,
+        method,
+    })
+}
+
+/// Month and BYDAY of an RRULE like `FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU`
+fn yearly_rule(rule: &str) -> Option<(u32, String)> {
+    let part = |name: &str| {
+        rule.split(';').find_map(|part| {
+            let (key, value) = part.split_once('=')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim().to_ascii_uppercase())
+        })
+    };
+    if part("FREQ")? != "YEARLY" {
+        return None;
+    }
+    let month = part("BYMONTH")?.parse().ok().filter(|m| (1..=12).contains(m))?;
+    Some((month, part("BYDAY")?))
+}
+
+/// A content line folded to [`MAX_LINE`] octets, ending in CRLF
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// A CN parameter, quoted; DQUOTE can't appear in one at all
+fn common_name(name: &str) -> String {
+    format!(";CN=\"{}\"", name.replace('"', "").replace(['\r', '\n'], " "))
+}
+
+/// The iCalendar REPLY saying `attendee` answered `invite` with `response`,
+/// stamped `now`
+pub fn reply_calendar(invite: &Invite, attendee: &str, attendee_name: Option<&str>, response: Rsvp, now: i64) -> String {
+    let stamp = DateTime::from_timestamp(now, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ");
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "VERSION:2.0".to_string(),
+        "METHOD:REPLY".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", escape(&invite.uid)),
+    ];
+    if let Some(recurrence_id) = &invite.recurrence_id {
+        lines.push(recurrence_id.clone());
+    }
+    lines.push(format!("SEQUENCE:{}", invite.sequence));
+    lines.push(format!("DTSTAMP:{}", stamp));
+    if let Some(organizer) = &invite.organizer {
+        lines.push(format!(
+            "ORGANIZER{}:mailto:{}",
+            organizer.name.as_deref().map(common_name).unwrap_or_default(),
+            organizer.email
+        ));
+    }
+    lines.push(format!(
+        "ATTENDEE;PARTSTAT={}{}:mailto:{}",
+        response.partstat(),
+        attendee_name.map(common_name).unwrap_or_default(),
+        attendee
+    ));
+    if let Some(summary) = &invite.summary {
+        lines.push(format!("SUMMARY:{}", escape(summary)));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// The message answering `invite` with `response`, from `from` to its
+/// organizer, or `None` if it has no organizer to answer. `message_id` is
+/// the invitation's, to thread the answer with it.
+pub fn reply_message(
+    invite: &Invite,
+    from: &str,
+    from_name: Option<&str>,
+    message_id: Option<&str>,
+    response: Rsvp,
+    now: i64,
+) -> Option<OutgoingMessage> {
+    let organizer = invite.organizer.as_ref()?;
+    let summary = invite
+        .summary
+        .as_deref()
+        .map(str::trim)
+        .filter(|summary| !summary.is_empty())
+        .unwrap_or("(no title)");
+    let (subject, answer) = match response {
+        Rsvp::Accepted => ("Accepted", "accepted"),
+        Rsvp::Declined => ("Declined", "declined"),
+        Rsvp::Tentative => ("Tentative", "tentatively accepted"),
+    };
+    let who = from_name.unwrap_or(from);
+    let mut message = OutgoingMessage::new(from, format!("{}: {}", subject, summary))
+        .to(&organizer.email)
+        .text(format!("{} has {} your invitation to {}.\n", who, answer, summary))
+        .calendar(reply_calendar(invite, from, from_name, response, now));
+    if let Some(name) = from_name {
+        message = message.from_name(name);
+    }
+    if let Some(id) = message_id {
+        message = message.reply_to_message(id).reference(id);
+    }
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST: &str = "BEGIN:VCALENDAR\r\n\
+        PRODID:-//Example//EN\r\n\
+        VERSION:2.0\r\n\
+        METHOD:REQUEST\r\n\
+        BEGIN:VTIMEZONE\r\n\
+        TZID:W. Europe Standard Time\r\n\
+        BEGIN:STANDARD\r\n\
+        DTSTART:16010101T030000\r\n\
+        TZOFFSETFROM:+0200\r\n\
+        TZOFFSETTO:+0100\r\n\
+        RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=10\r\n\
+        END:STANDARD\r\n\
+        BEGIN:DAYLIGHT\r\n\
+        DTSTART:16010101T020000\r\n\
+        TZOFFSETFROM:+0100\r\n\
+        TZOFFSETTO:+0200\r\n\
+        RRULE:FREQ=YEARLY;INTERVAL=1;BYDAY=-1SU;BYMONTH=3\r\n\
+        END:DAYLIGHT\r\n\
+        END:VTIMEZONE\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:040000008200E00074C5B7101A82E008\r\n\
+        SEQUENCE:2\r\n\
+        ORGANIZER;CN=\"Smith, Ann\":mailto:ann@example.com\r\n\
+        ATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE;CN=Bo\r\n \
+        b:mailto:bob@example.org\r\n\
+        ATTENDEE;PARTSTAT=ACCEPTED:mailto:carol@example.net\r\n\
+        SUMMARY:Planning\\, Q4\r\n\
+        LOCATION:Room 1\r\n\
+        DTSTART;TZID=W. Europe Standard Time:20261020T100000\r\n\
+        DTEND;TZID=W. Europe Standard Time:20261020T110000\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parse_request() {
+        let invite = parse_calendar(REQUEST).unwrap();
+        assert_eq!(invite.method, "REQUEST");
+        assert_eq!(invite.uid, "040000008200E00074C5B7101A82E008");
+        assert_eq!(invite.sequence, 2);
+        assert_eq!(invite.summary.as_deref(), Some("Planning, Q4"));
+        assert_eq!(invite.location.as_deref(), Some("Room 1"));
+        // 10:00 in summer time, UTC+2
+        assert_eq!(invite.starts_at, Some(Utc.with_ymd_and_hms(2026, 10, 20, 8, 0, 0).unwrap().timestamp()));
+        assert_eq!(invite.ends_at, Some(Utc.with_ymd_and_hms(2026, 10, 20, 9, 0, 0).unwrap().timestamp()));
+        assert!(!invite.all_day);
+        let organizer = invite.organizer.as_ref().unwrap();
+        assert_eq!(organizer.email, "ann@example.com");
+        assert_eq!(organizer.name.as_deref(), Some("Smith, Ann"));
+        assert_eq!(invite.attendees.len(), 2);
+        let bob = invite.attendee("BOB@example.org").unwrap();
+        assert_eq!(bob.name.as_deref(), Some("Bob"));
+        assert_eq!(bob.status, None);
+        assert_eq!(invite.attendee("carol@example.net").unwrap().status, Some(Rsvp::Accepted));
+        assert!(invite.wants_reply());
+    }
+
+    #[test]
+    fn test_zone_switches_to_standard_time() {
+        let invite = parse_calendar(&REQUEST.replace("20261020T", "20261120T")).unwrap();
+        assert_eq!(invite.starts_at, Some(Utc.with_ymd_and_hms(2026, 11, 20, 9, 0, 0).unwrap().timestamp()));
+    }
+
+    #[test]
+    fn test_parse_utc_and_duration() {
+        let invite = parse_calendar(
+            "BEGIN:VCALENDAR\nMETHOD:CANCEL\nBEGIN:VEVENT\nUID:a\nDTSTART:20261020T100000Z\n\
+             DURATION:PT1H30M\nEND:VEVENT\nEND:VCALENDAR\n",
+        )
+        .unwrap();
+        let start = Utc.with_ymd_and_hms(2026, 10, 20, 10, 0, 0).unwrap().timestamp();
+        assert_eq!(invite.starts_at, Some(start));
+        assert_eq!(invite.ends_at, Some(start + 5400));
+        assert!(invite.cancelled);
+        assert!(!invite.wants_reply());
+    }
+
+    #[test]
+    fn test_all_day_event() {
+        let invite = parse_calendar(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:b\nDTSTART;VALUE=DATE:20261020\nEND:VEVENT\nEND:VCALENDAR\n",
+        )
+        .unwrap();
+        assert!(invite.all_day);
+        assert_eq!(invite.method, "PUBLISH");
+        assert_eq!(invite.ends_at, invite.starts_at.map(|start| start + 86400));
+    }
+
+    #[test]
+    fn test_no_event() {
+        assert_eq!(parse_calendar("BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:c\nEND:VTODO\nEND:VCALENDAR\n"), None);
+        assert_eq!(parse_calendar("BEGIN:VCALENDAR\nBEGIN:VEVENT\nEND:VEVENT\nEND:VCALENDAR\n"), None);
+    }
+
+    #[test]
+    fn test_nth_weekday() {
+        assert_eq!(nth_weekday(2026, 3, "-1SU"), NaiveDate::from_ymd_opt(2026, 3, 29));
+        assert_eq!(nth_weekday(2026, 10, "-1SU"), NaiveDate::from_ymd_opt(2026, 10, 25));
+        assert_eq!(nth_weekday(2026, 3, "2SU"), NaiveDate::from_ymd_opt(2026, 3, 8));
+        assert_eq!(nth_weekday(2026, 11, "1SU"), NaiveDate::from_ymd_opt(2026, 11, 1));
+        assert_eq!(nth_weekday(2026, 3, "XX"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT1H"), Some(3600));
+        assert_eq!(parse_duration("P1DT2H"), Some(93600));
+        assert_eq!(parse_duration("P2W"), Some(14 * 86400));
+        assert_eq!(parse_duration("-PT15M"), Some(-900));
+        assert_eq!(parse_duration("1H"), None);
+    }
+
+    #[test]
+    fn test_reply() {
+        let invite = parse_calendar(REQUEST).unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap().timestamp();
+        let message = reply_message(
+            &invite,
+            "bob@example.org",
+            Some("Bob"),
+            Some("<invite@example.com>"),
+            Rsvp::Tentative,
+            now,
+        )
+        .unwrap();
+        assert_eq!(message.to, vec!["ann@example.com"]);
+        assert_eq!(message.subject, "Tentative: Planning, Q4");
+        assert_eq!(message.in_reply_to.as_deref(), Some("<invite@example.com>"));
+
+        let ics = message.calendar.unwrap();
+        assert!(ics.contains("METHOD:REPLY\r\n"));
+        assert!(ics.contains("UID:040000008200E00074C5B7101A82E008\r\n"));
+        assert!(ics.contains("SEQUENCE:2\r\n"));
+        assert!(ics.contains("DTSTAMP:20261018T120000Z\r\n"));
+        assert!(ics.contains("ATTENDEE;PARTSTAT=TENTATIVE;CN=\"Bob\":mailto:bob@example.org\r\n"));
+        assert!(ics.lines().all(|line| line.len() <= MAX_LINE));
+
+        // What we send reads back as the same answer
+        let reply = parse_calendar(&ics).unwrap();
+        assert_eq!(reply.method, "REPLY");
+        assert_eq!(reply.organizer.as_ref().unwrap().name.as_deref(), Some("Smith, Ann"));
+        assert_eq!(reply.attendee("bob@example.org").unwrap().status, Some(Rsvp::Tentative));
+    }
+
+    #[test]
+    fn test_fold() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= MAX_LINE));
+        assert_eq!(unfold(&folded), vec![line]);
+    }
+
+    #[test]
+    fn test_rsvp_names() {
+        for rsvp in [Rsvp::Accepted, Rsvp::Declined, Rsvp::Tentative] {
+            assert_eq!(Rsvp::parse(rsvp.as_str()), Some(rsvp));
+            assert_eq!(Rsvp::from_partstat(rsvp.partstat()), Some(rsvp));
+        }
+        assert_eq!(Rsvp::from_partstat("NEEDS-ACTION"), None);
+    }
+}
@@ -0,0 +1,834 @@
+//! JMAP accounts (RFC 8620/8621)
+//!
+//! JMAP accounts have no IMAP server. The sync engine keeps them in the
+//! same cache through a [`JmapAccount`], which front ends also use for what
+//! they would otherwise do over IMAP. A mailbox is stored as a folder with
+//! its JMAP ID in `graph_folder_id`, an email as a message with its ID in
+//! `graph_message_id` and a UID derived from it, and each folder's Email
+//! state in `graph_delta_link`: the columns Graph accounts use the same
+//! way.
+//!
+//! Credentials live in the keyring, which front ends read; they hand them
+//! over with [`set_credentials`] so the engine's thread can log in.
+
+use crate::database::DbMessage;
+use crate::flag_merge::{FlagConflict, FlagPolicy};
+use crate::sync::{AppendTarget, FolderRecord};
+use crate::{Account, CoreError, CoreResult, Database};
+use northmail_auth::JmapCredentials;
+use northmail_imap::MessageFlags;
+use northmail_jmap::{Email, JmapAuth, JmapClient, Mailbox};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Provider of JMAP accounts, as stored in the accounts table
+pub const PROVIDER: &str = "jmap";
+
+/// How many of the newest emails the first sync of a mailbox fetches;
+/// older ones stay on the server
+const INITIAL_SYNC_COUNT: u64 = 200;
+
+/// Most changes asked for per Email/changes call
+const MAX_CHANGES: u64 = 500;
+
+/// Most emails fetched per Email/get call
+const GET_CHUNK: usize = 256;
+
+/// How far into a mailbox to look for emails no longer in the cache
+const LOOKUP_LIMIT: u64 = 2000;
+
+/// How often the server pings an open push stream
+const PUSH_PING_SECS: u32 = 60;
+
+/// Wait before reopening a push stream that failed or closed
+const PUSH_RETRY: Duration = Duration::from_secs(30);
+
+/// Credentials of JMAP accounts by account ID
+static CREDENTIALS: OnceLock<Mutex<HashMap<String, JmapCredentials>>> = OnceLock::new();
+
+fn credentials_map() -> &'static Mutex<HashMap<String, JmapCredentials>> {
+    CREDENTIALS.get_or_init(Default::default)
+}
+
+/// Make an account's credentials available to [`JmapAccount::connect`]
+pub fn set_credentials(account_id: &str, credentials: JmapCredentials) {
+    credentials_map()
+        .lock()
+        .unwrap()
+        .insert(account_id.to_string(), credentials);
+}
+
+/// Forget an account's credentials, e.g. when it is removed
+pub fn forget_credentials(account_id: &str) {
+    credentials_map().lock().unwrap().remove(account_id);
+}
+
+fn credentials(account_id: &str) -> Option<JmapCredentials> {
+    credentials_map().lock().unwrap().get(account_id).cloned()
+}
+
+fn auth(credentials: &JmapCredentials) -> JmapAuth {
+    match &credentials.username {
+        Some(username) => JmapAuth::Basic {
+            username: username.clone(),
+            password: credentials.secret.clone(),
+        },
+        None => JmapAuth::Bearer(credentials.secret.clone()),
+    }
+}
+
+/// Log in to a server to check a new account's settings before it is
+/// stored
+pub async fn verify(account: &Account, credentials: &JmapCredentials) -> CoreResult<()> {
+    let url = session_url(account)?;
+    let client = JmapClient::connect(url, auth(credentials)).await?;
+    if !client.can_submit() {
+        warn!(
+            "JMAP: {} can't send mail through this server",
+            account.email
+        );
+    }
+    Ok(())
+}
+
+fn session_url(account: &Account) -> CoreResult<&str> {
+    account
+        .config
+        .jmap_session_url
+        .as_deref()
+        .ok_or_else(|| CoreError::SyncError(format!("{} is not a JMAP account", account.email)))
+}
+
+/// Whether a stored account is synced over JMAP
+pub async fn is_jmap_account(database: &Database, account_id: &str) -> CoreResult<bool> {
+    Ok(database
+        .get_accounts()
+        .await?
+        .iter()
+        .any(|a| a.id == account_id && a.provider == PROVIDER))
+}
+
+/// Stable UID for an email ID. JMAP has no UIDs, and the cache and front
+/// ends key messages by one; this is FNV-1a, kept to 31 bits like the
+/// UIDs made up for Graph messages.
+pub fn email_uid(id: &str) -> u32 {
+    let hash = id.bytes().fold(0x811c_9dc5u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(0x0100_0193)
+    });
+    hash & 0x7FFF_FFFF
+}
+
+/// Folder type for a mailbox role, as IMAP special-use folders get
+fn folder_type(role: Option<&str>) -> &'static str {
+    match role {
+        Some("inbox") => "inbox",
+        Some("sent") => "sent",
+        Some("drafts") => "drafts",
+        Some("trash") => "trash",
+        Some("junk") => "spam",
+        Some("archive") => "archive",
+        _ => "other",
+    }
+}
+
+/// Mailboxes as folders to store. The inbox gets the path `INBOX`, which
+/// front ends look for, and its children move along with it.
+pub fn folder_records(mailboxes: &[Mailbox]) -> Vec<FolderRecord> {
+    let inbox = mailboxes
+        .iter()
+        .find(|m| m.role.as_deref() == Some("inbox") && m.parent_id.is_none())
+        .map(|m| m.full_path.clone());
+
+    mailboxes
+        .iter()
+        .map(|m| {
+            let full_path = match &inbox {
+                Some(inbox) if m.full_path == *inbox => "INBOX".to_string(),
+                Some(inbox) => match m.full_path.strip_prefix(&format!("{}/", inbox)) {
+                    Some(rest) => format!("INBOX/{}", rest),
+                    None => m.full_path.clone(),
+                },
+                None => m.full_path.clone(),
+            };
+            FolderRecord {
+                name: m.name.clone(),
+                full_path,
+                folder_type: folder_type(m.role.as_deref()).to_string(),
+                message_count: u32::try_from(m.total_emails).ok(),
+                unread_count: u32::try_from(m.unread_emails).ok(),
+                is_selectable: true,
+                graph_folder_id: Some(m.id.clone()),
+            }
+        })
+        .collect()
+}
+
+/// JMAP keyword for an IMAP flag; keywords are the same in both, system
+/// flags are spelled `$seen` and so on
+fn keyword(flag: &str) -> String {
+    match flag.strip_prefix('\\') {
+        Some(system) => format!("${}", system.to_lowercase()),
+        None => flag.to_lowercase(),
+    }
+}
+
+/// An email's keywords as IMAP flags
+fn message_flags(email: &Email) -> MessageFlags {
+    let mut flags = MessageFlags::default();
+    for (keyword, set) in &email.keywords {
+        if !set {
+            continue;
+        }
+        match keyword.as_str() {
+            "$seen" => flags.seen = true,
+            "$answered" => flags.answered = true,
+            "$flagged" => flags.flagged = true,
+            "$draft" => flags.draft = true,
+            other => {
+                flags.custom.insert(other.to_string());
+            }
+        }
+    }
+    flags
+}
+
+fn addresses(list: &Option<Vec<northmail_jmap::EmailAddress>>) -> Option<String> {
+    let list: Vec<&str> = list.iter().flatten().map(|a| a.email.as_str()).collect();
+    (!list.is_empty()).then(|| list.join(", "))
+}
+
+/// An email's headers as a message to cache
+pub fn db_message(email: &Email) -> DbMessage {
+    let from = email.from.as_ref().and_then(|from| from.first());
+    let date = email.sent_at.clone().or_else(|| email.received_at.clone());
+    let date_epoch = email
+        .received_at
+        .as_deref()
+        .or(date.as_deref())
+        .and_then(|d| chrono::DateTime::parse_from_rfc3339(d).ok())
+        .map(|d| d.timestamp());
+    let flags = message_flags(email);
+
+    DbMessage {
+        id: 0,
+        folder_id: 0,
+        uid: email_uid(&email.id) as i64,
+        message_id: email
+            .message_id
+            .as_ref()
+            .and_then(|ids| ids.first())
+            .map(|id| format!("<{}>", id)),
+        subject: email.subject.clone(),
+        from_address: from.map(|a| a.email.clone()),
+        from_name: from.and_then(|a| a.name.clone()),
+        to_addresses: addresses(&email.to),
+        cc_addresses: addresses(&email.cc),
+        date_sent: date,
+        date_epoch,
+        snippet: email.preview.clone(),
+        is_read: flags.seen,
+        is_starred: flags.flagged,
+        has_attachments: email.has_attachment,
+        size: email.size as i64,
+        maildir_path: None,
+        body_text: None,
+        body_html: None,
+        gmail_labels: None,
+        gmail_thread_id: None,
+        mention: None,
+        tags: crate::tags::encode_tags(&flags.keywords()),
+        snoozed_until: None,
+        reply_later_at: None,
+    }
+}
+
+/// What syncing a folder changed in the cache
+#[derive(Debug, Default)]
+pub struct FolderSync {
+    pub folder_id: i64,
+    /// Messages added to the cache
+    pub new: usize,
+    /// Cached messages whose flags changed, with their new flags
+    pub changed: Vec<(u32, MessageFlags)>,
+    /// Flags changed here that the server disagreed with
+    pub conflicts: Vec<FlagConflict>,
+    /// Messages removed from the cache
+    pub removed: u64,
+}
+
+/// A JMAP account, logged in
+pub struct JmapAccount {
+    database: Arc<Database>,
+    account_id: String,
+    client: JmapClient,
+}
+
+impl JmapAccount {
+    /// Log in to a stored JMAP account with the credentials given to
+    /// [`set_credentials`]
+    pub async fn connect(database: Arc<Database>, account_id: &str) -> CoreResult<Self> {
+        let account = database
+            .get_accounts()
+            .await?
+            .into_iter()
+            .find(|a| a.id == account_id)
+            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+        let credentials = credentials(account_id).ok_or_else(|| {
+            CoreError::AuthError(format!("No JMAP credentials for {}", account.email))
+        })?;
+        let client = JmapClient::connect(session_url(&account)?, auth(&credentials)).await?;
+
+        Ok(Self {
+            database,
+            account_id: account_id.to_string(),
+            client,
+        })
+    }
+
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    /// The account's mailboxes as folders to store
+    pub async fn folders(&self) -> CoreResult<Vec<FolderRecord>> {
+        let (mailboxes, _) = self.client.list_mailboxes().await?;
+        Ok(folder_records(&mailboxes))
+    }
+
+    /// A cached folder's ID and its mailbox's JMAP ID
+    async fn folder(&self, folder_path: &str) -> CoreResult<(i64, String)> {
+        let folder = self
+            .database
+            .get_folder_by_path(&self.account_id, folder_path)
+            .await?
+            .ok_or_else(|| CoreError::FolderNotFound(folder_path.to_string()))?;
+        let mailbox_id = self
+            .database
+            .get_graph_folder_id(folder.id)
+            .await?
+            .ok_or_else(|| CoreError::FolderNotFound(folder_path.to_string()))?;
+        Ok((folder.id, mailbox_id))
+    }
+
+    /// JMAP IDs of messages in a folder. Front ends may drop a message
+    /// from the cache before it is moved; such UIDs are looked up among the
+    /// mailbox's newest emails, and left out if not found.
+    async fn email_ids(
+        &self,
+        folder_id: i64,
+        mailbox_id: &str,
+        uids: &[u32],
+    ) -> CoreResult<Vec<String>> {
+        let mut ids = Vec::with_capacity(uids.len());
+        let mut missing = HashSet::new();
+        for &uid in uids {
+            match self
+                .database
+                .get_graph_message_id(folder_id, uid as i64)
+                .await?
+            {
+                Some(id) => ids.push(id),
+                None => {
+                    missing.insert(uid);
+                }
+            }
+        }
+
+        let mut position = 0;
+        while !missing.is_empty() && position < LOOKUP_LIMIT {
+            let (emails, _) = self
+                .client
+                .list_emails(mailbox_id, position, INITIAL_SYNC_COUNT, false)
+                .await?;
+            for email in &emails {
+                if missing.remove(&email_uid(&email.id)) {
+                    ids.push(email.id.clone());
+                }
+            }
+            if (emails.len() as u64) < INITIAL_SYNC_COUNT {
+                break;
+            }
+            position += INITIAL_SYNC_COUNT;
+        }
+        for uid in missing {
+            warn!("JMAP: no email found for UID {}", uid);
+        }
+        Ok(ids)
+    }
+
+    /// Bring a cached folder up to date: the newest emails on its first
+    /// sync, afterwards the changes since the state stored with it
+    pub async fn sync_folder(
+        &self,
+        folder_path: &str,
+        policy: &FlagPolicy,
+    ) -> CoreResult<FolderSync> {
+        let (folder_id, mailbox_id) = self.folder(folder_path).await?;
+        let mut sync = FolderSync {
+            folder_id,
+            ..Default::default()
+        };
+        let cached: HashSet<i64> = self
+            .database
+            .get_message_uids(folder_id)
+            .await?
+            .into_iter()
+            .collect();
+
+        let changes = match self.database.get_graph_delta_link(folder_id).await? {
+            Some(state) => self.changes_since(&state).await?,
+            None => None,
+        };
+        let state = match changes {
+            Some((changed, destroyed, state)) => {
+                let emails = self.get_emails(&changed).await?;
+                // Emails gone again before they could be fetched
+                let found: HashSet<&str> = emails.iter().map(|e| e.id.as_str()).collect();
+                let mut removed: Vec<String> = changed
+                    .iter()
+                    .filter(|id| !found.contains(id.as_str()))
+                    .cloned()
+                    .collect();
+                removed.extend(destroyed);
+                removed.extend(
+                    emails
+                        .iter()
+                        .filter(|e| !e.is_in(&mailbox_id))
+                        .map(|e| e.id.clone()),
+                );
+                sync.removed = self
+                    .database
+                    .delete_messages_by_graph_ids(folder_id, &removed)
+                    .await?
+                    .len() as u64;
+
+                let emails: Vec<Email> = emails
+                    .into_iter()
+                    .filter(|e| e.is_in(&mailbox_id))
+                    .collect();
+                self.store_emails(folder_id, &emails, &cached, policy, &mut sync)
+                    .await?;
+                state
+            }
+            None => {
+                debug!("JMAP: listing {} from the start", folder_path);
+                let (emails, state) = self
+                    .client
+                    .list_emails(&mailbox_id, 0, INITIAL_SYNC_COUNT, false)
+                    .await?;
+                self.store_emails(folder_id, &emails, &cached, policy, &mut sync)
+                    .await?;
+                // Without a state to go on, a short mailbox shows what was
+                // removed; in a longer one, older cached messages are kept
+                if (emails.len() as u64) < INITIAL_SYNC_COUNT {
+                    let keep: Vec<i64> = emails.iter().map(|e| email_uid(&e.id) as i64).collect();
+                    sync.removed = self
+                        .database
+                        .delete_messages_not_in_uids(folder_id, &keep)
+                        .await?;
+                }
+                state
+            }
+        };
+        self.database
+            .set_graph_delta_link(folder_id, Some(&state))
+            .await?;
+
+        debug!(
+            "JMAP: {} synced, {} new, {} changed, {} removed",
+            folder_path,
+            sync.new,
+            sync.changed.len(),
+            sync.removed
+        );
+        Ok(sync)
+    }
+
+    /// IDs of emails created or updated, and of those destroyed, since
+    /// `state`, with the new state. `None` when the state is too old for
+    /// the server to tell.
+    async fn changes_since(
+        &self,
+        state: &str,
+    ) -> CoreResult<Option<(Vec<String>, Vec<String>, String)>> {
+        let mut changed = Vec::new();
+        let mut destroyed = Vec::new();
+        let mut state = state.to_string();
+        loop {
+            let changes = match self.client.email_changes(&state, MAX_CHANGES).await {
+                Ok(changes) => changes,
+                Err(e) if e.is_method_error("cannotCalculateChanges") => {
+                    info!("JMAP: state {} expired, starting over", state);
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            changed.extend(changes.created);
+            changed.extend(changes.updated);
+            destroyed.extend(changes.destroyed);
+            state = changes.new_state;
+            if !changes.has_more_changes {
+                break;
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        changed.retain(|id| !destroyed.contains(id));
+        Ok(Some((changed, destroyed, state)))
+    }
+
+    async fn get_emails(&self, ids: &[String]) -> CoreResult<Vec<Email>> {
+        let mut emails = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(GET_CHUNK) {
+            emails.extend(self.client.get_emails(chunk, false).await?);
+        }
+        Ok(emails)
+    }
+
+    /// Add emails new to the folder and update the flags of cached ones
+    async fn store_emails(
+        &self,
+        folder_id: i64,
+        emails: &[Email],
+        cached: &HashSet<i64>,
+        policy: &FlagPolicy,
+        sync: &mut FolderSync,
+    ) -> CoreResult<()> {
+        let (known, new): (Vec<&Email>, Vec<&Email>) = emails
+            .iter()
+            .partition(|e| cached.contains(&(email_uid(&e.id) as i64)));
+
+        let new: Vec<(DbMessage, String)> = new
+            .into_iter()
+            .map(|e| (db_message(e), e.id.clone()))
+            .collect();
+        sync.new = self
+            .database
+            .upsert_messages_batch_graph(folder_id, &new)
+            .await?;
+
+        let flags: Vec<(u32, MessageFlags)> = known
+            .into_iter()
+            .map(|e| (email_uid(&e.id), message_flags(e)))
+            .collect();
+        let update = self
+            .database
+            .batch_update_flags(folder_id, &flags, policy)
+            .await?;
+        sync.changed = update.changed;
+        sync.conflicts = update.conflicts;
+        Ok(())
+    }
+
+    /// A cached message as raw RFC 5322 bytes
+    pub async fn fetch_raw(&self, folder_path: &str, uid: u32) -> CoreResult<Vec<u8>> {
+        let (folder_id, mailbox_id) = self.folder(folder_path).await?;
+        let ids = self.email_ids(folder_id, &mailbox_id, &[uid]).await?;
+        let email = self
+            .client
+            .get_emails(&ids, false)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(CoreError::MessageNotFound(uid as i64))?;
+        Ok(self.client.download_message(&email).await?)
+    }
+
+    /// Set or clear an IMAP flag (`\Seen`, `\Flagged`, a keyword...) on
+    /// cached messages
+    pub async fn set_flag(
+        &self,
+        folder_path: &str,
+        uids: &[u32],
+        flag: &str,
+        set: bool,
+    ) -> CoreResult<()> {
+        let (folder_id, mailbox_id) = self.folder(folder_path).await?;
+        let ids = self.email_ids(folder_id, &mailbox_id, uids).await?;
+        if !ids.is_empty() {
+            self.client.set_keyword(&ids, &keyword(flag), set).await?;
+        }
+        Ok(())
+    }
+
+    /// The mailbox with a role
+    async fn mailbox_with_role(&self, role: &str) -> CoreResult<Option<Mailbox>> {
+        let (mailboxes, _) = self.client.list_mailboxes().await?;
+        Ok(mailboxes
+            .into_iter()
+            .find(|m| m.role.as_deref() == Some(role)))
+    }
+
+    /// Move cached messages to the folder at `destination`, or to the
+    /// mailbox for one of the hints `Trash`, `Archive`, `Spam` or `INBOX`.
+    /// Moving from the trash to the trash deletes them for good.
+    pub async fn move_messages(
+        &self,
+        folder_path: &str,
+        uids: &[u32],
+        destination: &str,
+    ) -> CoreResult<()> {
+        let (folder_id, mailbox_id) = self.folder(folder_path).await?;
+        let ids = self.email_ids(folder_id, &mailbox_id, uids).await?;
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let target = match self.folder(destination).await {
+            Ok((_, id)) => id,
+            Err(_) => {
+                let role = match destination {
+                    "Trash" => "trash",
+                    "Archive" => "archive",
+                    "Spam" | "Junk" => "junk",
+                    "INBOX" | "Inbox" => "inbox",
+                    other => return Err(CoreError::FolderNotFound(other.to_string())),
+                };
+                self.mailbox_with_role(role)
+                    .await?
+                    .ok_or_else(|| CoreError::FolderNotFound(destination.to_string()))?
+                    .id
+            }
+        };
+
+        if target == mailbox_id {
+            if self
+                .mailbox_with_role("trash")
+                .await?
+                .is_some_and(|m| m.id == target)
+            {
+                info!("JMAP: deleting {} messages from the trash", ids.len());
+                self.client.destroy_emails(&ids).await?;
+            }
+        } else {
+            self.client.move_emails(&ids, &mailbox_id, &target).await?;
+        }
+        self.database
+            .delete_messages_by_graph_ids(folder_id, &ids)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete cached messages for good, without moving them to the trash.
+    /// Returns how many were cached.
+    pub async fn destroy_messages(&self, folder_path: &str, uids: &[u32]) -> CoreResult<u64> {
+        let (folder_id, mailbox_id) = self.folder(folder_path).await?;
+        let ids = self.email_ids(folder_id, &mailbox_id, uids).await?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        info!("JMAP: deleting {} messages from {}", ids.len(), folder_path);
+        self.client.destroy_emails(&ids).await?;
+        Ok(self
+            .database
+            .delete_messages_by_graph_ids(folder_id, &ids)
+            .await?
+            .len() as u64)
+    }
+
+    /// Store a raw message in the Drafts or Sent mailbox, returning the
+    /// path of its folder
+    pub async fn append(&self, target: AppendTarget, message: Vec<u8>) -> CoreResult<String> {
+        let (role, keywords): (&str, &[&str]) = match target {
+            AppendTarget::Drafts => ("drafts", &["$draft", "$seen"]),
+            AppendTarget::Sent => ("sent", &["$seen"]),
+        };
+        let mailbox = self
+            .mailbox_with_role(role)
+            .await?
+            .ok_or_else(|| CoreError::FolderNotFound(role.to_string()))?;
+        self.client
+            .import_message(message, &[&mailbox.id], keywords)
+            .await?;
+        let path = folder_records(std::slice::from_ref(&mailbox))
+            .pop()
+            .map(|f| f.full_path)
+            .unwrap_or_default();
+        Ok(path)
+    }
+
+    /// Send a raw message from `from`, a copy to the recipients of each of
+    /// `envelopes` (see [`northmail_smtp::BccDelivery`]). The server files
+    /// it in the Sent mailbox.
+    pub async fn send(
+        &self,
+        from: &str,
+        envelopes: &[Vec<String>],
+        message: Vec<u8>,
+    ) -> CoreResult<()> {
+        let identities = self.client.identities().await?;
+        let identity = identities
+            .iter()
+            .find(|i| i.email.eq_ignore_ascii_case(from))
+            .or_else(|| identities.first())
+            .ok_or_else(|| CoreError::SmtpError(format!("No identity to send as {}", from)))?;
+
+        let (mailboxes, _) = self.client.list_mailboxes().await?;
+        let with_role = |role: &str| {
+            mailboxes
+                .iter()
+                .find(|m| m.role.as_deref() == Some(role))
+                .map(|m| m.id.as_str())
+                .ok_or_else(|| CoreError::FolderNotFound(role.to_string()))
+        };
+
+        self.client
+            .send_message(
+                message,
+                &identity.id,
+                from,
+                envelopes,
+                with_role("drafts")?,
+                with_role("sent")?,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Tell `changed` the account's ID whenever its mail or mailboxes change
+/// on the server, until `changed` is closed. The push stream is reopened
+/// when it fails or the server closes it.
+pub async fn watch(database: Arc<Database>, account_id: String, changed: mpsc::Sender<String>) {
+    info!("JMAP: watching {} for changes", account_id);
+    let mut reopened = false;
+    loop {
+        match watch_once(&database, &account_id, &changed, reopened).await {
+            Ok(false) => return,
+            Ok(true) => debug!("JMAP: push stream of {} closed", account_id),
+            Err(CoreError::AccountNotFound(_)) => {
+                info!("JMAP: {} was removed, no longer watching it", account_id);
+                return;
+            }
+            Err(e) => warn!("JMAP: push stream of {} failed: {}", account_id, e),
+        }
+        reopened = true;
+        tokio::time::sleep(PUSH_RETRY).await;
+        if changed.is_closed() {
+            return;
+        }
+    }
+}
+
+/// Follow one push stream until it ends; false once `changed` is closed.
+/// A `reopened` stream first reports the changes it may have missed.
+async fn watch_once(
+    database: &Arc<Database>,
+    account_id: &str,
+    changed: &mpsc::Sender<String>,
+    reopened: bool,
+) -> CoreResult<bool> {
+    let account = JmapAccount::connect(database.clone(), account_id).await?;
+    let mut stream = account
+        .client
+        .event_stream(&["Email", "Mailbox"], PUSH_PING_SECS)
+        .await?;
+    if reopened && changed.send(account_id.to_string()).await.is_err() {
+        return Ok(false);
+    }
+    while let Some(change) = stream.next_change().await? {
+        if !change.touches(account.client.account_id(), &["Email", "Mailbox"]) {
+            continue;
+        }
+        if changed.send(account_id.to_string()).await.is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailbox(
+        id: &str,
+        name: &str,
+        parent: Option<&str>,
+        role: Option<&str>,
+        path: &str,
+    ) -> Mailbox {
+        serde_json::from_value::<Mailbox>(serde_json::json!({
+            "id": id,
+            "name": name,
+            "parentId": parent,
+            "role": role,
+        }))
+        .map(|mut m| {
+            m.full_path = path.to_string();
+            m
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_email_uid_is_stable() {
+        assert_eq!(email_uid("M1a2b3c"), email_uid("M1a2b3c"));
+        assert_ne!(email_uid("M1a2b3c"), email_uid("M1a2b3d"));
+        assert!(email_uid("anything") <= 0x7FFF_FFFF);
+        // FNV-1a of the empty string, top bit cleared
+        assert_eq!(email_uid(""), 0x811c_9dc5 & 0x7FFF_FFFF);
+    }
+
+    #[test]
+    fn test_folder_records() {
+        let records = folder_records(&[
+            mailbox("a", "Inbox", None, Some("inbox"), "Inbox"),
+            mailbox("b", "Lists", Some("a"), None, "Inbox/Lists"),
+            mailbox("c", "Junk Mail", None, Some("junk"), "Junk Mail"),
+            mailbox("d", "Inboxes", None, None, "Inboxes"),
+        ]);
+        let paths: Vec<(&str, &str)> = records
+            .iter()
+            .map(|r| (r.full_path.as_str(), r.folder_type.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("INBOX", "inbox"),
+                ("INBOX/Lists", "other"),
+                ("Junk Mail", "spam"),
+                ("Inboxes", "other"),
+            ]
+        );
+        assert_eq!(records[1].graph_folder_id.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_keyword() {
+        assert_eq!(keyword("\\Seen"), "$seen");
+        assert_eq!(keyword("\\Flagged"), "$flagged");
+        assert_eq!(keyword("$Work"), "$work");
+    }
+
+    #[test]
+    fn test_db_message() {
+        let email: Email = serde_json::from_value(serde_json::json!({
+            "id": "e1",
+            "blobId": "b1",
+            "keywords": { "$seen": true, "$flagged": true, "$work": true },
+            "messageId": ["abc@example.com"],
+            "from": [{ "name": "Ann", "email": "ann@example.com" }],
+            "to": [{ "name": null, "email": "bob@example.com" }, { "email": "cy@example.com" }],
+            "receivedAt": "2024-05-01T10:00:00Z",
+            "subject": "Hi",
+            "size": 1234,
+        }))
+        .unwrap();
+        let msg = db_message(&email);
+        assert_eq!(msg.uid, email_uid("e1") as i64);
+        assert_eq!(msg.message_id.as_deref(), Some("<abc@example.com>"));
+        assert_eq!(msg.from_name.as_deref(), Some("Ann"));
+        assert_eq!(
+            msg.to_addresses.as_deref(),
+            Some("bob@example.com, cy@example.com")
+        );
+        assert_eq!(msg.cc_addresses, None);
+        assert_eq!(msg.date_epoch, Some(1714557600));
+        assert!(msg.is_read && msg.is_starred);
+        assert_eq!(msg.tags.as_deref(), Some("[\"$work\"]"));
+    }
+}
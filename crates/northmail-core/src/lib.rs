@@ -2,17 +2,69 @@
 //!
 //! Provides the sync engine, storage, and data models.
 
+pub mod address;
+pub mod avatar;
 mod account;
+pub mod auto_empty;
+pub mod backup;
+pub mod bandwidth;
+pub mod changes;
 mod database;
+pub mod duplicates;
+pub mod eml;
+pub mod evolution;
 mod error;
+pub mod error_log;
+pub mod flag_merge;
+pub mod gmail;
+pub mod icloud;
+pub mod identity;
+pub mod import;
+pub mod invite;
+pub mod jmap;
+pub mod link_preview;
+pub mod maildir;
+pub mod maintenance;
+pub mod mailto;
+pub mod mdn;
+pub mod mention;
+pub mod offline;
+mod migrations;
+pub mod outbox;
+pub mod parallel_sync;
+pub mod purge;
+pub mod quota;
+pub mod read_aloud;
+pub mod recipient_check;
+pub mod recipients;
+pub mod reply_later;
+pub mod retention;
+pub mod rules;
+pub mod runtime;
+pub mod search;
+pub mod snooze;
+pub mod structured_data;
 mod sync;
+pub mod sync_policy;
+pub mod tags;
+pub mod thread;
+pub mod timeline;
+pub mod translate;
+pub mod unified;
+pub mod wipe;
 
 pub use account::{Account, AccountConfig};
 pub use database::Database;
 pub use error::{CoreError, CoreResult};
-pub use sync::{SyncCommand, SyncEngine, SyncEvent};
+pub use sync::{
+    create_sync_channels, AppendTarget, FolderRecord, ImapConnector, SyncCommand, SyncEngine,
+    SyncEvent,
+};
 
 /// Re-export models for convenience
 pub mod models {
-    pub use crate::database::{AttachmentInfo, AttachmentMetadata, DbFolder, DbMessage, MessageFilter};
+    pub use crate::database::{
+        AccountStats, AttachmentInfo, AttachmentMetadata, DatabaseStats, DbFolder, DbMessage, DbTag,
+        OptimizeStep, PageCursor, MessageFilter, SearchScope,
+    };
 }
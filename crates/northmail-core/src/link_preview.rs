@@ -0,0 +1,305 @@
+//! Link previews
+//!
+//! Enrichers recognize links to things worth calling out (meeting rooms,
+//! issues and merge requests, map locations) and describe them as small
+//! chips shown under the message. Everything is worked out from the URL
+//! alone, so nothing is fetched and previews work offline. Each enricher is
+//! enabled separately by its id.
+
+/// Most chips shown for one message
+const MAX_CHIPS: usize = 5;
+
+/// What a recognized link points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Meeting,
+    Issue,
+    MergeRequest,
+    Map,
+}
+
+/// A recognized link, ready to show as a chip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkChip {
+    pub kind: LinkKind,
+    /// Short label, e.g. `owner/repo#42` or `Google Meet`
+    pub title: String,
+    /// Secondary text such as the meeting code or the address
+    pub detail: Option<String>,
+    pub url: String,
+}
+
+/// Recognizes one family of links
+pub trait LinkEnricher {
+    /// Stable id stored in settings
+    fn id(&self) -> &'static str;
+
+    /// Describe `url` if this enricher knows it
+    fn enrich(&self, url: &str) -> Option<LinkChip>;
+}
+
+/// Video meeting links: Google Meet, Zoom, Microsoft Teams, Jitsi
+pub struct MeetingEnricher;
+
+impl LinkEnricher for MeetingEnricher {
+    fn id(&self) -> &'static str {
+        "meeting"
+    }
+
+    fn enrich(&self, url: &str) -> Option<LinkChip> {
+        let (host, path, _) = split_url(url)?;
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+        let (title, detail) = if host == "meet.google.com" {
+            ("Google Meet", segments.next().map(str::to_string))
+        } else if host == "zoom.us" || host.ends_with(".zoom.us") {
+            match (segments.next(), segments.next()) {
+                (Some("j" | "my"), Some(id)) => ("Zoom", Some(id.to_string())),
+                _ => return None,
+            }
+        } else if host == "teams.microsoft.com" || host == "teams.live.com" {
+            if !path.contains("meetup-join") && !path.starts_with("/meet/") {
+                return None;
+            }
+            ("Microsoft Teams", None)
+        } else if host == "meet.jit.si" {
+            ("Jitsi Meet", segments.next().map(str::to_string))
+        } else {
+            return None;
+        };
+        Some(LinkChip {
+            kind: LinkKind::Meeting,
+            title: title.to_string(),
+            detail,
+            url: url.to_string(),
+        })
+    }
+}
+
+/// GitHub and GitLab issues, pull requests and merge requests
+pub struct IssueEnricher;
+
+impl LinkEnricher for IssueEnricher {
+    fn id(&self) -> &'static str {
+        "issue"
+    }
+
+    fn enrich(&self, url: &str) -> Option<LinkChip> {
+        let (host, path, _) = split_url(url)?;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let (project, kind, number) = if host == "github.com" {
+            match segments.as_slice() {
+                [owner, repo, "issues", number, ..] => (format!("{}/{}", owner, repo), LinkKind::Issue, *number),
+                [owner, repo, "pull", number, ..] => (format!("{}/{}", owner, repo), LinkKind::MergeRequest, *number),
+                _ => return None,
+            }
+        } else if host == "gitlab.com" || host.starts_with("gitlab.") {
+            // GitLab projects can be nested in groups: group/sub/project/-/issues/7
+            let dash = segments.iter().position(|s| *s == "-")?;
+            let kind = match segments.get(dash + 1) {
+                Some(&"issues") => LinkKind::Issue,
+                Some(&"merge_requests") => LinkKind::MergeRequest,
+                _ => return None,
+            };
+            (segments[..dash].join("/"), kind, *segments.get(dash + 2)?)
+        } else {
+            return None;
+        };
+        if project.is_empty() || number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let marker = if kind == LinkKind::MergeRequest && host != "github.com" { '!' } else { '#' };
+        Some(LinkChip {
+            kind,
+            title: format!("{}{}{}", project, marker, number),
+            detail: Some(host.to_string()),
+            url: url.to_string(),
+        })
+    }
+}
+
+/// Map links with a place or address: Google Maps, Apple Maps, OpenStreetMap
+pub struct MapEnricher;
+
+impl LinkEnricher for MapEnricher {
+    fn id(&self) -> &'static str {
+        "map"
+    }
+
+    fn enrich(&self, url: &str) -> Option<LinkChip> {
+        let (host, path, query) = split_url(url)?;
+        let google = host == "maps.google.com"
+            || ((host == "google.com" || host == "www.google.com") && path.starts_with("/maps"));
+        let place = if google {
+            path.split_once("/place/")
+                .map(|(_, rest)| rest.split('/').next().unwrap_or_default().to_string())
+                .or_else(|| query_param(query, "q"))
+                .or_else(|| query_param(query, "query"))
+        } else if host == "maps.apple.com" {
+            query_param(query, "q").or_else(|| query_param(query, "address"))
+        } else if host == "www.openstreetmap.org" || host == "openstreetmap.org" {
+            query_param(query, "query")
+        } else {
+            return None;
+        };
+        let place = percent_decode(&place?.replace('+', " "));
+        let place = place.trim();
+        if place.is_empty() {
+            return None;
+        }
+        Some(LinkChip {
+            kind: LinkKind::Map,
+            title: place.to_string(),
+            detail: None,
+            url: url.to_string(),
+        })
+    }
+}
+
+/// Every enricher NorthMail ships, in the order their chips are shown
+pub fn enrichers() -> Vec<Box<dyn LinkEnricher>> {
+    vec![Box::new(MeetingEnricher), Box::new(IssueEnricher), Box::new(MapEnricher)]
+}
+
+/// Chips for the links in a message, using the enrichers whose ids are in
+/// `enabled`. `body` may be plain text or HTML.
+pub fn preview_links(body: &str, enabled: &[String]) -> Vec<LinkChip> {
+    let enrichers: Vec<_> = enrichers()
+        .into_iter()
+        .filter(|e| enabled.iter().any(|id| id == e.id()))
+        .collect();
+    if enrichers.is_empty() {
+        return Vec::new();
+    }
+
+    let mut chips: Vec<LinkChip> = Vec::new();
+    for url in find_urls(body) {
+        let Some(chip) = enrichers.iter().find_map(|e| e.enrich(&url)) else {
+            continue;
+        };
+        if chips.iter().any(|c| c.kind == chip.kind && c.title == chip.title) {
+            continue;
+        }
+        chips.push(chip);
+        if chips.len() == MAX_CHIPS {
+            break;
+        }
+    }
+    chips
+}
+
+/// http(s) URLs in text or HTML, in order of appearance
+fn find_urls(body: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("http") {
+        rest = &rest[start..];
+        if !(rest.starts_with("https://") || rest.starts_with("http://")) {
+            rest = &rest[4..];
+            continue;
+        }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | '`'))
+            .unwrap_or(rest.len());
+        let url = rest[..end]
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']'])
+            .replace("&amp;", "&");
+        rest = &rest[end..];
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Lowercase host, path and query of an http(s) URL
+fn split_url(url: &str) -> Option<(String, &str, &str)> {
+    let rest = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path_and_query) = match rest.find(['/', '?']) {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    let host = authority.rsplit('@').next()?.split(':').next()?.to_ascii_lowercase();
+    let (path, query) = path_and_query.split_once('?').unwrap_or((path_and_query, ""));
+    Some((host, path, query))
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name && !value.is_empty()).then(|| value.to_string())
+    })
+}
+
+pub(crate) fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> Vec<String> {
+        enrichers().iter().map(|e| e.id().to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_urls() {
+        let body = r#"See <a href="https://github.com/a/b/issues/1?x=1&amp;y=2">this</a> (https://meet.google.com/abc-defg-hij)."#;
+        assert_eq!(
+            find_urls(body),
+            vec!["https://github.com/a/b/issues/1?x=1&y=2", "https://meet.google.com/abc-defg-hij"]
+        );
+    }
+
+    #[test]
+    fn test_issue_links() {
+        let chip = IssueEnricher.enrich("https://github.com/rust-lang/rust/pull/1234#discussion").unwrap();
+        assert_eq!(chip.kind, LinkKind::MergeRequest);
+        assert_eq!(chip.title, "rust-lang/rust#1234");
+        let chip = IssueEnricher.enrich("https://gitlab.gnome.org/GNOME/gtk/-/merge_requests/7").unwrap();
+        assert_eq!(chip.title, "GNOME/gtk!7");
+        let chip = IssueEnricher.enrich("https://gitlab.com/group/sub/project/-/issues/42").unwrap();
+        assert_eq!((chip.kind, chip.title.as_str()), (LinkKind::Issue, "group/sub/project#42"));
+        assert!(IssueEnricher.enrich("https://github.com/rust-lang/rust/issues").is_none());
+        assert!(IssueEnricher.enrich("https://github.com/rust-lang/rust/issues/new").is_none());
+    }
+
+    #[test]
+    fn test_meeting_and_map_links() {
+        let chip = MeetingEnricher.enrich("https://us02web.zoom.us/j/8123456789?pwd=x").unwrap();
+        assert_eq!((chip.title.as_str(), chip.detail.as_deref()), ("Zoom", Some("8123456789")));
+        assert!(MeetingEnricher.enrich("https://zoom.us/pricing").is_none());
+        let chip = MapEnricher.enrich("https://www.google.com/maps/place/Caf%C3%A9+Central,+Vienna/@48.2,16.3").unwrap();
+        assert_eq!(chip.title, "Café Central, Vienna");
+        let chip = MapEnricher.enrich("https://maps.apple.com/?q=1+Infinite+Loop").unwrap();
+        assert_eq!(chip.title, "1 Infinite Loop");
+        assert!(MapEnricher.enrich("https://www.google.com/search?q=maps").is_none());
+    }
+
+    #[test]
+    fn test_preview_links_respects_enabled() {
+        let body = "Call: https://meet.google.com/abc-defg-hij\nBug: https://github.com/a/b/issues/9\nAgain https://github.com/a/b/issues/9";
+        assert_eq!(preview_links(body, &all()).len(), 2);
+        let chips = preview_links(body, &["issue".to_string()]);
+        assert_eq!(chips.len(), 1);
+        assert_eq!(chips[0].title, "a/b#9");
+        assert!(preview_links(body, &[]).is_empty());
+    }
+}
@@ -0,0 +1,427 @@
+//! Exporting folders as Maildir
+//!
+//! A Maildir is a directory with `tmp`, `new` and `cur` subdirectories and
+//! one file per message, which mutt, notmuch, Dovecot and most other mail
+//! tools read directly. Each message is written to `tmp` and renamed into
+//! `cur`, so nothing ever sees half of it, and its flags go in the `:2,`
+//! suffix of its name. Names come from the message's date and UID, so
+//! exporting a folder again into the same directory only adds what's new.
+
+use base64::Engine;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Suffix of every exported message's unique name, which also tells them
+/// apart from files other programs put in the Maildir
+const NAME_SUFFIX: &str = ".northmail";
+
+/// What an export did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportCounts {
+    /// Messages written to the Maildir
+    pub exported: usize,
+    /// ...of which were rebuilt from the cache, see [`rebuild_message`]
+    pub rebuilt: usize,
+    /// Messages already in the Maildir from an earlier export
+    pub skipped: usize,
+    /// Messages left out because neither the server nor the cache had them
+    pub missing: usize,
+}
+
+/// Create the Maildir at `path`, and its parents, if they don't exist
+pub fn create_dirs(path: &Path) -> std::io::Result<()> {
+    for sub in ["tmp", "new", "cur"] {
+        std::fs::create_dir_all(path.join(sub))?;
+    }
+    Ok(())
+}
+
+/// Unique part of an exported message's file name
+pub fn unique_name(date_epoch: Option<i64>, uid: i64) -> String {
+    format!("{}.U{}{}", date_epoch.unwrap_or(0).max(0), uid, NAME_SUFFIX)
+}
+
+/// File name for a message in `cur`: the unique part and its flags, which
+/// the Maildir spec wants in ASCII order
+pub fn file_name(unique: &str, seen: bool, flagged: bool) -> String {
+    let mut name = format!("{}:2,", unique);
+    if flagged {
+        name.push('F');
+    }
+    if seen {
+        name.push('S');
+    }
+    name
+}
+
+/// Unique parts of the messages earlier exports left in the Maildir. Mail
+/// tools move messages from `new` to `cur` and change their flags, so only
+/// the part before the flags counts.
+pub fn exported_names(path: &Path) -> std::io::Result<HashSet<String>> {
+    let mut names = HashSet::new();
+    for sub in ["new", "cur"] {
+        let entries = match std::fs::read_dir(path.join(sub)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            let unique = name.split(':').next().unwrap_or(&name);
+            if unique.ends_with(NAME_SUFFIX) {
+                names.insert(unique.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Write a message to `tmp` and move it into `cur` as `name`, returning
+/// where it ended up
+pub fn deliver(path: &Path, name: &str, data: &[u8]) -> std::io::Result<PathBuf> {
+    let tmp = path.join("tmp").join(name);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    let target = path.join("cur").join(name);
+    if let Err(e) = std::fs::rename(&tmp, &target) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    Ok(target)
+}
+
+/// Turn CRLF line endings, as IMAP sends messages, into the LF ones
+/// Maildir files use
+pub fn to_unix_line_endings(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().peekable();
+    while let Some(&b) = bytes.next() {
+        if b == b'\r' && bytes.peek() == Some(&&b'\n') {
+            continue;
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// An attachment of a [`CachedMessage`]
+#[derive(Debug, Clone, Copy)]
+pub struct CachedAttachment<'a> {
+    pub filename: &'a str,
+    pub mime_type: &'a str,
+    pub data: &'a [u8],
+}
+
+/// What the cache keeps of a message: the envelope and the decoded bodies
+/// and attachments, not the message as it was sent
+#[derive(Debug, Clone, Default)]
+pub struct CachedMessage<'a> {
+    pub message_id: Option<&'a str>,
+    /// Date header as the message had it
+    pub date: Option<&'a str>,
+    /// Unix time, for when the date header wasn't kept
+    pub date_epoch: Option<i64>,
+    pub from_name: Option<&'a str>,
+    pub from_address: Option<&'a str>,
+    /// Comma-separated addresses
+    pub to: Option<&'a str>,
+    /// Comma-separated addresses
+    pub cc: Option<&'a str>,
+    pub subject: Option<&'a str>,
+    pub body_text: Option<&'a str>,
+    pub body_html: Option<&'a str>,
+    pub attachments: Vec<CachedAttachment<'a>>,
+}
+
+/// Build a MIME message from a cached copy, for messages the server can no
+/// longer supply. Headers the cache doesn't keep, such as `References` and
+/// `Received`, are lost, so the message is marked with an
+/// `X-NorthMail-Rebuilt` header. `boundary` separates the MIME parts and
+/// must not occur in the content; the base64 encoding of every part makes
+/// anything that isn't base64 safe.
+pub fn rebuild_message(message: &CachedMessage, boundary: &str) -> Vec<u8> {
+    let mut out = String::new();
+
+    if let Some(id) = message.message_id.filter(|id| !id.trim().is_empty()) {
+        let id = id.trim();
+        if id.starts_with('<') {
+            out.push_str(&format!("Message-ID: {}\n", id));
+        } else {
+            out.push_str(&format!("Message-ID: <{}>\n", id));
+        }
+    }
+    let date = match message.date.filter(|date| !date.trim().is_empty()) {
+        Some(date) => Some(date.trim().to_string()),
+        None => message
+            .date_epoch
+            .and_then(|epoch| chrono::DateTime::from_timestamp(epoch, 0))
+            .map(|date| date.to_rfc2822()),
+    };
+    if let Some(date) = date {
+        out.push_str(&format!("Date: {}\n", date));
+    }
+    if let Some(address) = message.from_address {
+        match message.from_name.filter(|name| !name.is_empty()) {
+            Some(name) => out.push_str(&format!("From: {} <{}>\n", display_name(name), address)),
+            None => out.push_str(&format!("From: {}\n", address)),
+        }
+    }
+    for (name, addresses) in [("To", message.to), ("Cc", message.cc)] {
+        if let Some(addresses) = addresses.filter(|a| !a.trim().is_empty()) {
+            out.push_str(&format!("{}: {}\n", name, fold_addresses(addresses)));
+        }
+    }
+    if let Some(subject) = message.subject {
+        out.push_str(&format!("Subject: {}\n", encode_header(subject)));
+    }
+    out.push_str("MIME-Version: 1.0\n");
+    out.push_str("X-NorthMail-Rebuilt: from cache; original headers not kept\n");
+    out.push_str(&format!(
+        "Content-Type: multipart/mixed; boundary=\"{}\"\n\n",
+        boundary
+    ));
+
+    let bodies = [
+        ("text/plain", message.body_text),
+        ("text/html", message.body_html),
+    ];
+    let bodies: Vec<_> = bodies
+        .into_iter()
+        .filter_map(|(mime_type, body)| body.map(|body| (mime_type, body)))
+        .collect();
+    if bodies.len() > 1 {
+        let inner = format!("alt-{}", boundary);
+        out.push_str(&format!(
+            "--{}\nContent-Type: multipart/alternative; boundary=\"{}\"\n\n",
+            boundary, inner
+        ));
+        for (mime_type, body) in &bodies {
+            out.push_str(&format!("--{}\n", inner));
+            push_text_part(&mut out, mime_type, body);
+        }
+        out.push_str(&format!("--{}--\n", inner));
+    } else {
+        for (mime_type, body) in &bodies {
+            out.push_str(&format!("--{}\n", boundary));
+            push_text_part(&mut out, mime_type, body);
+        }
+    }
+
+    for attachment in &message.attachments {
+        let filename = attachment.filename.replace(['"', '\\', '\r', '\n'], "_");
+        out.push_str(&format!("--{}\n", boundary));
+        out.push_str(&format!(
+            "Content-Type: {}; name=\"{}\"\n",
+            attachment.mime_type,
+            encode_header(&filename)
+        ));
+        out.push_str(&format!(
+            "Content-Disposition: attachment; filename=\"{}\"\n",
+            encode_header(&filename)
+        ));
+        out.push_str("Content-Transfer-Encoding: base64\n\n");
+        out.push_str(&base64_lines(attachment.data));
+    }
+    out.push_str(&format!("--{}--\n", boundary));
+
+    out.into_bytes()
+}
+
+fn push_text_part(out: &mut String, mime_type: &str, body: &str) {
+    out.push_str(&format!("Content-Type: {}; charset=utf-8\n", mime_type));
+    out.push_str("Content-Transfer-Encoding: base64\n\n");
+    out.push_str(&base64_lines(body.as_bytes()));
+}
+
+/// Base64 in lines of 76 characters, as MIME wants
+fn base64_lines(data: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for line in encoded.as_bytes().chunks(76) {
+        // Base64 is ASCII, so every chunk is valid UTF-8
+        out.push_str(std::str::from_utf8(line).unwrap_or_default());
+        out.push('\n');
+    }
+    out
+}
+
+/// Display name for a From header, quoted when it has characters that
+/// would otherwise end it early
+fn display_name(name: &str) -> String {
+    if !name.is_ascii() {
+        encode_header(name)
+    } else if name.chars().any(|c| "()<>[]:;@\\,.\"".contains(c)) {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_string()
+    }
+}
+
+/// Put each address of a comma-separated list on its own line, so long
+/// lists stay under the line length limit
+fn fold_addresses(addresses: &str) -> String {
+    addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .collect::<Vec<_>>()
+        .join(",\n ")
+}
+
+/// RFC 2047 encoded words for a header value that isn't plain ASCII, in
+/// pieces short enough for a header line, split between characters
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() && !value.contains(['\r', '\n']) {
+        return value.to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars().filter(|c| *c != '\r' && *c != '\n') {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(std::mem::take(&mut chunk));
+        }
+        chunk.push(c);
+    }
+    if !chunk.is_empty() {
+        words.push(chunk);
+    }
+    words
+        .iter()
+        .map(|word| {
+            format!(
+                "=?UTF-8?B?{}?=",
+                base64::engine::general_purpose::STANDARD.encode(word)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail_parser::MimeHeaders;
+
+    #[test]
+    fn test_file_name_flags() {
+        let unique = unique_name(Some(1700000000), 42);
+        assert_eq!(unique, "1700000000.U42.northmail");
+        assert_eq!(
+            file_name(&unique, false, false),
+            "1700000000.U42.northmail:2,"
+        );
+        assert_eq!(
+            file_name(&unique, true, false),
+            "1700000000.U42.northmail:2,S"
+        );
+        assert_eq!(
+            file_name(&unique, true, true),
+            "1700000000.U42.northmail:2,FS"
+        );
+        assert_eq!(unique_name(None, 7), "0.U7.northmail");
+    }
+
+    #[test]
+    fn test_deliver_and_exported_names() {
+        let dir =
+            std::env::temp_dir().join(format!("northmail-maildir-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        create_dirs(&dir).unwrap();
+
+        let name = file_name(&unique_name(Some(1), 1), true, false);
+        let path = deliver(&dir, &name, b"Subject: hi\n\nbody\n").unwrap();
+        assert_eq!(path, dir.join("cur").join(&name));
+        assert_eq!(std::fs::read(&path).unwrap(), b"Subject: hi\n\nbody\n");
+        assert!(!dir.join("tmp").join(&name).exists());
+
+        // A mail tool moved one to new, and left its own message there
+        std::fs::write(dir.join("new").join(unique_name(Some(2), 2)), b"").unwrap();
+        std::fs::write(dir.join("new").join("1.M1P1.host"), b"").unwrap();
+
+        let names = exported_names(&dir).unwrap();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains("1.U1.northmail"));
+        assert!(names.contains("2.U2.northmail"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_exported_names_missing_dir() {
+        let dir = std::env::temp_dir().join("northmail-maildir-test-missing");
+        assert!(exported_names(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_to_unix_line_endings() {
+        assert_eq!(to_unix_line_endings(b"a\r\nb\r\n\r\nc"), b"a\nb\n\nc");
+        assert_eq!(to_unix_line_endings(b"lone\rcr\n"), b"lone\rcr\n");
+    }
+
+    #[test]
+    fn test_encode_header() {
+        assert_eq!(encode_header("Hello"), "Hello");
+        assert_eq!(encode_header("Grüße"), "=?UTF-8?B?R3LDvMOfZQ==?=");
+        let long = "ä".repeat(40);
+        let encoded = encode_header(&long);
+        assert_eq!(encoded.lines().count(), 2);
+        assert!(encoded.lines().all(|line| line.len() <= 76));
+    }
+
+    #[test]
+    fn test_rebuild_message_parses() {
+        let message = CachedMessage {
+            message_id: Some("abc@example.com"),
+            date: Some("Mon, 13 Nov 2023 10:00:00 +0000"),
+            from_name: Some("Dana, Ops"),
+            from_address: Some("dana@example.com"),
+            to: Some("a@example.com, b@example.com"),
+            subject: Some("Grüße aus Berlin"),
+            body_text: Some("Hallo\nWelt"),
+            body_html: Some("<p>Hallo</p>"),
+            attachments: vec![CachedAttachment {
+                filename: "notes.txt",
+                mime_type: "text/plain",
+                data: b"attached",
+            }],
+            ..Default::default()
+        };
+        let data = rebuild_message(&message, "northmail-boundary");
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert!(text.contains("Message-ID: <abc@example.com>\n"));
+        assert!(text.contains("From: \"Dana, Ops\" <dana@example.com>\n"));
+        assert!(text.contains("To: a@example.com,\n b@example.com\n"));
+        assert!(!text.contains('\r'));
+
+        let parsed = mail_parser::MessageParser::default().parse(&data).unwrap();
+        assert_eq!(parsed.message_id(), Some("abc@example.com"));
+        assert_eq!(parsed.subject(), Some("Grüße aus Berlin"));
+        assert_eq!(parsed.body_text(0).as_deref(), Some("Hallo\nWelt"));
+        assert_eq!(parsed.body_html(0).as_deref(), Some("<p>Hallo</p>"));
+        let attachment = parsed.attachment(0).unwrap();
+        assert_eq!(attachment.attachment_name(), Some("notes.txt"));
+        assert_eq!(attachment.contents(), b"attached");
+    }
+
+    #[test]
+    fn test_rebuild_message_from_epoch() {
+        let message = CachedMessage {
+            date_epoch: Some(0),
+            from_address: Some("x@example.com"),
+            body_text: Some("only text"),
+            ..Default::default()
+        };
+        let data = rebuild_message(&message, "b");
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert!(text.contains("Date: Thu, 1 Jan 1970 00:00:00 +0000\n"));
+        assert!(!text.contains("multipart/alternative"));
+        let parsed = mail_parser::MessageParser::default().parse(&data).unwrap();
+        assert_eq!(parsed.body_text(0).as_deref(), Some("only text"));
+    }
+}
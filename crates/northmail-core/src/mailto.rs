@@ -0,0 +1,86 @@
+//! `mailto:` links (RFC 6068)
+//!
+//! NorthMail is registered as the `mailto` handler, so clicking a link
+//! elsewhere on the desktop launches it (or hands the link to the running
+//! instance) with the URI as an argument. Only the recipient fields,
+//! subject and body are taken; other header fields are ignored, and so are
+//! non-standard ones like `attach` that would let a web page pick local
+//! files to send.
+
+use crate::address::emails_in;
+use crate::link_preview::percent_decode;
+
+/// A message to start composing, from a `mailto:` URI
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MailtoLink {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+impl MailtoLink {
+    /// Parse a `mailto:` URI; `None` for other schemes
+    pub fn parse(uri: &str) -> Option<Self> {
+        let scheme = uri.get(..7)?;
+        if !scheme.eq_ignore_ascii_case("mailto:") {
+            return None;
+        }
+        let rest = &uri[7..];
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        let mut link = Self {
+            to: addresses(&percent_decode(path)),
+            ..Self::default()
+        };
+        for pair in query.split('&') {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value);
+            match name.to_ascii_lowercase().as_str() {
+                "to" => link.to.extend(addresses(&value)),
+                "cc" => link.cc.extend(addresses(&value)),
+                "bcc" => link.bcc.extend(addresses(&value)),
+                "subject" => link.subject = value,
+                // Line breaks are encoded as %0D%0A
+                "body" => link.body = value.replace("\r\n", "\n"),
+                _ => {}
+            }
+        }
+        Some(link)
+    }
+}
+
+/// Email addresses in a comma-separated field
+fn addresses(field: &str) -> Vec<String> {
+    emails_in(field).into_iter().filter(|email| email.contains('@')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mailto() {
+        let link = MailtoLink::parse(
+            "mailto:ann@example.com,bob@example.org?cc=carol@example.net&subject=Lunch%20plans&body=Noon%3F%0D%0AAnn&attach=/etc/passwd",
+        )
+        .unwrap();
+        assert_eq!(link.to, vec!["ann@example.com", "bob@example.org"]);
+        assert_eq!(link.cc, vec!["carol@example.net"]);
+        assert!(link.bcc.is_empty());
+        assert_eq!(link.subject, "Lunch plans");
+        assert_eq!(link.body, "Noon?\nAnn");
+    }
+
+    #[test]
+    fn test_parse_mailto_edge_cases() {
+        let link = MailtoLink::parse("MAILTO:?to=ann%40example.com&BCC=bob@example.org").unwrap();
+        assert_eq!(link.to, vec!["ann@example.com"]);
+        assert_eq!(link.bcc, vec!["bob@example.org"]);
+
+        assert_eq!(MailtoLink::parse("mailto:"), Some(MailtoLink::default()));
+        assert_eq!(MailtoLink::parse("https://example.com"), None);
+        assert_eq!(MailtoLink::parse("mail"), None);
+    }
+}
@@ -0,0 +1,113 @@
+//! Idle-time database maintenance
+//!
+//! About once a day, when nothing else has kept it busy for a while, the
+//! sync engine runs [`Database::maintenance_report`]: an incremental vacuum
+//! hands free pages back to the file system, ANALYZE refreshes the query
+//! planner's statistics, and integrity checks look for damage in the
+//! database and its search index. The [`MaintenanceReport`] is kept for the
+//! storage dialog, which offers a full compaction ([`Database::optimize`])
+//! when free pages pile up that the incremental vacuum can't give back.
+//!
+//! Incremental vacuum needs `auto_vacuum = INCREMENTAL`, which databases
+//! are created with. Older ones only switch over with a full compaction.
+//!
+//! [`Database::maintenance_report`]: crate::Database::maintenance_report
+//! [`Database::optimize`]: crate::Database::optimize
+
+use std::time::Duration;
+
+/// How often maintenance runs, in seconds
+pub const INTERVAL_SECS: i64 = 24 * 60 * 60;
+
+/// How long the sync engine has to have been idle before it runs
+/// maintenance
+pub const IDLE_TIME: Duration = Duration::from_secs(5 * 60);
+
+/// Free space below which a full compaction isn't worth offering
+const COMPACT_MIN_FREE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Integrity problems reported at most, so a badly damaged database
+/// doesn't take forever to list
+pub const MAX_INTEGRITY_ERRORS: u32 = 20;
+
+/// What a maintenance run found and did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Unix time it ran
+    pub checked_at: i64,
+    /// Size of the database file afterwards, as SQLite counts its pages
+    pub total_bytes: u64,
+    /// Bytes in free pages afterwards, which a full compaction gives back
+    pub free_bytes: u64,
+    /// Bytes the incremental vacuum gave back
+    pub reclaimed_bytes: u64,
+    /// Whether the database gives back free pages incrementally
+    pub incremental_vacuum: bool,
+    /// Problems the integrity check found, empty if there were none
+    pub integrity_errors: Vec<String>,
+    /// Whether the search index was damaged and rebuilt
+    pub search_index_rebuilt: bool,
+}
+
+impl MaintenanceReport {
+    /// Whether the database passed its integrity check. A damaged search
+    /// index doesn't count, as it's rebuilt on the spot.
+    pub fn is_sound(&self) -> bool {
+        self.integrity_errors.is_empty()
+    }
+
+    /// Whether a full compaction would give back enough space to offer it:
+    /// at least [`COMPACT_MIN_FREE_BYTES`] and a fifth of the file
+    pub fn needs_compaction(&self) -> bool {
+        self.free_bytes >= COMPACT_MIN_FREE_BYTES && self.free_bytes * 5 >= self.total_bytes
+    }
+}
+
+/// Whether maintenance last run at `last` is due again at `now`. A last
+/// run in the future means the clock was turned back, so that's due too.
+pub fn is_due(last: Option<i64>, now: i64) -> bool {
+    last.is_none_or(|last| last > now || now - last >= INTERVAL_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn test_is_due() {
+        let now = 1_700_000_000;
+        assert!(is_due(None, now));
+        assert!(!is_due(Some(now - 60), now));
+        assert!(is_due(Some(now - INTERVAL_SECS), now));
+        assert!(is_due(Some(now + 3600), now));
+    }
+
+    #[test]
+    fn test_needs_compaction() {
+        let report = |total, free| MaintenanceReport {
+            total_bytes: total * MIB,
+            free_bytes: free * MIB,
+            ..Default::default()
+        };
+        assert!(report(200, 100).needs_compaction());
+        // Too little to bother with, however large a share
+        assert!(!report(40, 20).needs_compaction());
+        // A small share of a large database
+        assert!(!report(2000, 100).needs_compaction());
+    }
+
+    #[test]
+    fn test_is_sound() {
+        let mut report = MaintenanceReport {
+            search_index_rebuilt: true,
+            ..Default::default()
+        };
+        assert!(report.is_sound());
+        report
+            .integrity_errors
+            .push("row 12 missing from index".to_string());
+        assert!(!report.is_sound());
+    }
+}
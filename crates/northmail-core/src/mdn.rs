@@ -0,0 +1,407 @@
+//! Read receipts (message disposition notifications, RFC 8098)
+//!
+//! A message whose sender wants to know it was read carries a
+//! `Disposition-Notification-To` header ([`receipt_request`]). When the
+//! message is opened, [`decide`] says whether to send the receipt, ask
+//! first or do nothing, going by the [`ReceiptPolicy`] for the sender: one
+//! the user chose for them, else the default. The receipt itself is an
+//! ordinary outgoing message carrying a disposition report
+//! ([`receipt_message`]).
+//!
+//! Going the other way, a message sent with a receipt requested gets a
+//! Message-ID of our own ([`new_message_id`]) so the receipt that comes back
+//! ([`returned_receipt`]) can be matched to it.
+
+use crate::address::{emails_in, parse_address_list};
+use mail_parser::{Message, MimeHeaders};
+use northmail_smtp::OutgoingMessage;
+
+/// Value of `Reporting-UA` in the receipts we send
+const REPORTING_UA: &str = "NorthMail";
+
+/// What to do about receipts a sender asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptPolicy {
+    /// Ask each time
+    Ask,
+    /// Send without asking, where that's safe (see [`decide`])
+    Always,
+    /// Never send
+    Never,
+}
+
+impl ReceiptPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReceiptPolicy::Ask => "ask",
+            ReceiptPolicy::Always => "always",
+            ReceiptPolicy::Never => "never",
+        }
+    }
+
+    /// Policy stored as `text`; anything unknown asks
+    pub fn parse(text: &str) -> Self {
+        match text {
+            "always" => ReceiptPolicy::Always,
+            "never" => ReceiptPolicy::Never,
+            _ => ReceiptPolicy::Ask,
+        }
+    }
+}
+
+/// A receipt asked for by a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiptRequest {
+    /// Where the receipt goes
+    pub notify_to: String,
+    /// The receipt goes back to where the message came from (its
+    /// Return-Path), so it can be sent without asking
+    pub return_path_matches: bool,
+}
+
+/// The receipt `message` asks for, if any
+pub fn receipt_request(message: &Message) -> Option<ReceiptRequest> {
+    let notify_to = message
+        .header_raw("Disposition-Notification-To")
+        .and_then(|value| parse_address_list(value).into_iter().next())
+        .map(|address| address.email)
+        .filter(|email| email.contains('@'))?;
+    let return_path_matches = message
+        .header_raw("Return-Path")
+        .and_then(|value| emails_in(value).into_iter().next())
+        .is_some_and(|return_path| return_path.eq_ignore_ascii_case(&notify_to));
+    Some(ReceiptRequest {
+        notify_to,
+        return_path_matches,
+    })
+}
+
+/// What to do about a receipt request when the message is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiptAction {
+    Send,
+    Ask,
+    Ignore,
+}
+
+/// What to do about `request` on a message in a folder of type
+/// `folder_type` under `policy`. Junk, drafts and the user's own messages
+/// never get one. Even with [`ReceiptPolicy::Always`] the user is asked when
+/// the receipt would go somewhere other than where the message came from,
+/// or the message reached them without being addressed to them (a list, or
+/// Bcc), as RFC 8098 wants.
+pub fn decide(
+    policy: ReceiptPolicy,
+    request: &ReceiptRequest,
+    folder_type: &str,
+    addressed_to_user: bool,
+) -> ReceiptAction {
+    if matches!(folder_type, "spam" | "trash" | "drafts" | "sent") {
+        return ReceiptAction::Ignore;
+    }
+    match policy {
+        ReceiptPolicy::Never => ReceiptAction::Ignore,
+        ReceiptPolicy::Always if request.return_path_matches && addressed_to_user => {
+            ReceiptAction::Send
+        }
+        ReceiptPolicy::Always | ReceiptPolicy::Ask => ReceiptAction::Ask,
+    }
+}
+
+/// Whether `own_address` is among the To and Cc addresses
+pub fn is_addressed_to(own_address: &str, to: Option<&str>, cc: Option<&str>) -> bool {
+    [to, cc]
+        .into_iter()
+        .flatten()
+        .flat_map(emails_in)
+        .any(|email| email.eq_ignore_ascii_case(own_address))
+}
+
+/// How the user came to send a receipt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendingMode {
+    /// They said so for this message
+    Manual,
+    /// Their policy for the sender said so
+    Automatic,
+}
+
+impl SendingMode {
+    fn disposition_mode(self) -> &'static str {
+        match self {
+            SendingMode::Manual => "manual-action/MDN-sent-manually",
+            SendingMode::Automatic => "automatic-action/MDN-sent-automatically",
+        }
+    }
+}
+
+/// The receipt for a message `from` received: to `request.notify_to`,
+/// saying the message with `message_id` and `subject` was displayed
+pub fn receipt_message(
+    request: &ReceiptRequest,
+    from: &str,
+    from_name: Option<&str>,
+    message_id: Option<&str>,
+    subject: &str,
+    mode: SendingMode,
+) -> OutgoingMessage {
+    let subject = if subject.trim().is_empty() {
+        "(no subject)"
+    } else {
+        subject.trim()
+    };
+    let mut message = OutgoingMessage::new(from, format!("Read: {}", subject))
+        .to(&request.notify_to)
+        .text(format!(
+            "This is a receipt for the message you sent to {}.\n\nSubject: {}\n\n\
+             It was displayed on the recipient's screen. That doesn't mean it was read \
+             or understood.\n",
+            from, subject
+        ))
+        .disposition_notification(disposition_fields(from, message_id, mode));
+    if let Some(name) = from_name {
+        message = message.from_name(name);
+    }
+    if let Some(id) = message_id {
+        message = message.reply_to_message(id).reference(id);
+    }
+    message
+}
+
+/// The machine-readable part of a receipt
+pub fn disposition_fields(
+    final_recipient: &str,
+    message_id: Option<&str>,
+    mode: SendingMode,
+) -> String {
+    let mut fields = format!(
+        "Reporting-UA: {}\r\nFinal-Recipient: rfc822;{}\r\n",
+        REPORTING_UA, final_recipient
+    );
+    if let Some(id) = message_id {
+        fields.push_str(&format!("Original-Message-ID: {}\r\n", angle_bracketed(id)));
+    }
+    fields.push_str(&format!(
+        "Disposition: {}; displayed\r\n",
+        mode.disposition_mode()
+    ));
+    fields
+}
+
+/// A Message-ID for a message sent from `from`, to match the receipt
+/// that comes back for it
+pub fn new_message_id(from: &str) -> String {
+    let domain = from
+        .rsplit_once('@')
+        .map_or("northmail.invalid", |(_, domain)| domain);
+    format!("<{}@{}>", uuid::Uuid::new_v4().simple(), domain)
+}
+
+/// A message the user sent asking for a receipt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestedReceipt {
+    pub message_id: String,
+    /// Recipients as shown, comma-separated
+    pub recipients: String,
+    /// When it was sent, as a Unix timestamp
+    pub sent_at: i64,
+    /// When the receipt came back, if it has
+    pub returned_at: Option<i64>,
+    /// What the receipt said happened to the message: `displayed`…
+    pub disposition: Option<String>,
+}
+
+/// A receipt that came back for a message the user sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReturnedReceipt {
+    /// Message-ID of the message it is for, in angle brackets
+    pub original_message_id: String,
+    /// What happened to the message: `displayed`, `deleted`…
+    pub disposition: String,
+}
+
+/// The receipt `message` is, if it is one
+pub fn returned_receipt(message: &Message) -> Option<ReturnedReceipt> {
+    message.parts.iter().find_map(|part| {
+        let content_type = part.content_type()?;
+        let is_report = content_type.ctype().eq_ignore_ascii_case("message")
+            && content_type
+                .subtype()
+                .is_some_and(|subtype| subtype.eq_ignore_ascii_case("disposition-notification"));
+        if !is_report {
+            return None;
+        }
+        parse_disposition_fields(&String::from_utf8_lossy(part.contents()))
+    })
+}
+
+/// The receipt described by the fields of a `message/disposition-notification`
+/// part
+pub fn parse_disposition_fields(fields: &str) -> Option<ReturnedReceipt> {
+    let field = |name: &str| {
+        fields.lines().find_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            field
+                .trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    let original_message_id = field("Original-Message-ID").filter(|id| !id.is_empty())?;
+    // "manual-action/MDN-sent-manually; displayed/error" → "displayed"
+    let disposition = field("Disposition")?
+        .rsplit(';')
+        .next()?
+        .split('/')
+        .next()?
+        .trim()
+        .to_lowercase();
+    Some(ReturnedReceipt {
+        original_message_id: angle_bracketed(&original_message_id),
+        disposition,
+    })
+}
+
+fn angle_bracketed(message_id: &str) -> String {
+    format!(
+        "<{}>",
+        message_id
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mail_parser::MessageParser;
+
+    fn request(return_path_matches: bool) -> ReceiptRequest {
+        ReceiptRequest {
+            notify_to: "ana@example.com".to_string(),
+            return_path_matches,
+        }
+    }
+
+    #[test]
+    fn test_receipt_request() {
+        let raw = b"Return-Path: <Ana@example.com>\r\n\
+            From: Ana <ana@example.com>\r\n\
+            Disposition-Notification-To: \"Ana\" <ana@example.com>\r\n\
+            Subject: Hi\r\n\r\nBody\r\n";
+        let message = MessageParser::default().parse(raw).unwrap();
+        assert_eq!(receipt_request(&message), Some(request(true)));
+
+        let raw = b"Return-Path: <bounces@lists.example.com>\r\n\
+            Disposition-Notification-To: ana@example.com\r\n\r\nBody\r\n";
+        let message = MessageParser::default().parse(raw).unwrap();
+        assert_eq!(receipt_request(&message), Some(request(false)));
+
+        let raw = b"From: ana@example.com\r\nSubject: Hi\r\n\r\nBody\r\n";
+        let message = MessageParser::default().parse(raw).unwrap();
+        assert_eq!(receipt_request(&message), None);
+    }
+
+    #[test]
+    fn test_decide() {
+        use ReceiptAction::*;
+        assert_eq!(
+            decide(ReceiptPolicy::Always, &request(true), "inbox", true),
+            Send
+        );
+        // Somewhere else, or not addressed to the user: ask anyway
+        assert_eq!(
+            decide(ReceiptPolicy::Always, &request(false), "inbox", true),
+            Ask
+        );
+        assert_eq!(
+            decide(ReceiptPolicy::Always, &request(true), "inbox", false),
+            Ask
+        );
+        assert_eq!(
+            decide(ReceiptPolicy::Ask, &request(true), "archive", true),
+            Ask
+        );
+        assert_eq!(
+            decide(ReceiptPolicy::Never, &request(true), "inbox", true),
+            Ignore
+        );
+        assert_eq!(
+            decide(ReceiptPolicy::Always, &request(true), "spam", true),
+            Ignore
+        );
+    }
+
+    #[test]
+    fn test_is_addressed_to() {
+        assert!(is_addressed_to(
+            "me@example.com",
+            Some("Ana <ana@example.com>"),
+            Some("Me@Example.com")
+        ));
+        assert!(!is_addressed_to(
+            "me@example.com",
+            Some("list@example.com"),
+            None
+        ));
+    }
+
+    #[test]
+    fn test_receipt_message() {
+        let message = receipt_message(
+            &request(true),
+            "me@example.com",
+            Some("Me"),
+            Some("<abc@example.com>"),
+            "Lunch",
+            SendingMode::Manual,
+        );
+        assert_eq!(message.to, vec!["ana@example.com".to_string()]);
+        assert_eq!(message.subject, "Read: Lunch");
+        assert_eq!(message.in_reply_to.as_deref(), Some("<abc@example.com>"));
+        assert_eq!(
+            message.disposition_notification.as_deref(),
+            Some(
+                "Reporting-UA: NorthMail\r\n\
+                 Final-Recipient: rfc822;me@example.com\r\n\
+                 Original-Message-ID: <abc@example.com>\r\n\
+                 Disposition: manual-action/MDN-sent-manually; displayed\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_returned_receipt() {
+        let raw = b"From: ana@example.com\r\n\
+            Subject: Read: Lunch\r\n\
+            Content-Type: multipart/report; report-type=disposition-notification; boundary=\"b\"\r\n\r\n\
+            --b\r\nContent-Type: text/plain\r\n\r\nDisplayed.\r\n\
+            --b\r\nContent-Type: message/disposition-notification\r\n\r\n\
+            Reporting-UA: Example\r\n\
+            Original-Message-ID: abc@example.com\r\n\
+            Disposition: automatic-action/MDN-sent-automatically; Displayed\r\n\
+            --b--\r\n";
+        let message = MessageParser::default().parse(raw).unwrap();
+        assert_eq!(
+            returned_receipt(&message),
+            Some(ReturnedReceipt {
+                original_message_id: "<abc@example.com>".to_string(),
+                disposition: "displayed".to_string(),
+            })
+        );
+
+        let plain = MessageParser::default()
+            .parse(b"Subject: Hi\r\n\r\nBody\r\n")
+            .unwrap();
+        assert_eq!(returned_receipt(&plain), None);
+        assert_eq!(parse_disposition_fields("Disposition: x; displayed"), None);
+    }
+
+    #[test]
+    fn test_new_message_id() {
+        let id = new_message_id("me@example.com");
+        assert!(id.starts_with('<') && id.ends_with("@example.com>"));
+        assert_ne!(id, new_message_id("me@example.com"));
+    }
+}
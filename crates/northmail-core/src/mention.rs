@@ -0,0 +1,73 @@
+//! Mention keywords
+//!
+//! The user lists words that matter to them: their name, nicknames, project
+//! codenames. New mail whose subject or text contains one is tagged with
+//! the keyword and gets a more insistent notification. Matching is
+//! case-insensitive and on whole words, so "Ann" doesn't match "annual".
+
+/// Keywords from the settings list: trimmed, without blanks or duplicates
+pub fn keywords(list: &[String]) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for keyword in list {
+        let keyword = keyword.trim();
+        if keyword.is_empty() || keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword)) {
+            continue;
+        }
+        keywords.push(keyword.to_string());
+    }
+    keywords
+}
+
+/// Split a comma-separated line as typed in preferences
+pub fn parse_list(text: &str) -> Vec<String> {
+    keywords(&text.split(',').map(str::to_string).collect::<Vec<_>>())
+}
+
+/// First keyword found in any of `texts`, in the order keywords are listed
+pub fn find_mention<'a>(keywords: &'a [String], texts: &[&str]) -> Option<&'a str> {
+    let texts: Vec<String> = texts.iter().map(|t| t.to_lowercase()).collect();
+    keywords
+        .iter()
+        .find(|keyword| {
+            let keyword = keyword.to_lowercase();
+            texts.iter().any(|text| contains_word(text, &keyword))
+        })
+        .map(String::as_str)
+}
+
+/// Whether `needle` occurs in `haystack` with no letter or digit on either side
+fn contains_word(haystack: &str, needle: &str) -> bool {
+    if needle.is_empty() {
+        return false;
+    }
+    let is_word_char = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    haystack.match_indices(needle).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + needle.len()..].chars().next();
+        !is_word_char(before) && !is_word_char(after)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(parse_list(" Ann Lee, ann lee,,Bluebird "), vec!["Ann Lee", "Bluebird"]);
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_find_mention() {
+        let keywords = parse_list("Ann, Project Bluebird");
+        assert_eq!(find_mention(&keywords, &["Hi ann, see below"]), Some("Ann"));
+        assert_eq!(find_mention(&keywords, &["Annual report", "Planning"]), None);
+        assert_eq!(
+            find_mention(&keywords, &["Status", "Update on project bluebird."]),
+            Some("Project Bluebird")
+        );
+        assert_eq!(find_mention(&keywords, &["Ann's notes"]), Some("Ann"));
+        assert_eq!(find_mention(&[], &["Ann"]), None);
+    }
+}
@@ -0,0 +1,267 @@
+//! Versioned schema migrations
+//!
+//! The schema [`Database`] creates on first open, together with the
+//! column-probing upgrades earlier versions ran at every start, is the
+//! baseline, version [`BASELINE_VERSION`]. Every schema change since is a
+//! numbered [`Migration`] in [`MIGRATIONS`]. Opening a database applies the
+//! ones it hasn't had, in order, each in a transaction with the row that
+//! records it in the `schema_version` table, so a migration that fails
+//! leaves the database as it was. Before the first of them the database
+//! file is copied to [`backup_path`], in case one succeeds but does the
+//! wrong thing.
+//!
+//! To change the schema, append a migration with the next version. Never
+//! edit, reorder or remove one that has shipped: databases that have it
+//! won't run it again.
+//!
+//! [`Database`]: crate::Database
+
+use std::path::{Path, PathBuf};
+
+/// Schema version of a database with no numbered migrations applied
+pub const BASELINE_VERSION: i64 = 1;
+
+/// A numbered schema change
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// One more than the version of the migration before it
+    pub version: i64,
+    /// What it does, recorded with it in `schema_version`
+    pub name: &'static str,
+    /// SQL statements, run in one transaction
+    pub sql: &'static str,
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        name: "last maintenance report",
+        sql: r#"
+            CREATE TABLE maintenance_runs (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                checked_at INTEGER NOT NULL,
+                total_bytes INTEGER NOT NULL,
+                free_bytes INTEGER NOT NULL,
+                reclaimed_bytes INTEGER NOT NULL,
+                incremental_vacuum INTEGER NOT NULL,
+                integrity_errors TEXT NOT NULL,
+                search_index_rebuilt INTEGER NOT NULL
+            );
+        "#,
+    },
+    Migration {
+        version: 3,
+        name: "folder sync policies",
+        sql: r#"
+            ALTER TABLE folders ADD COLUMN sync_interval_minutes INTEGER;
+            ALTER TABLE folders ADD COLUMN sync_days INTEGER;
+            ALTER TABLE folders ADD COLUMN fetch_bodies INTEGER NOT NULL DEFAULT 1;
+            ALTER TABLE folders ADD COLUMN unified_inbox INTEGER NOT NULL DEFAULT 1;
+            ALTER TABLE folders ADD COLUMN synced_at INTEGER;
+        "#,
+    },
+    Migration {
+        version: 4,
+        name: "recipient frecency",
+        sql: r#"
+            CREATE TABLE recipients (
+                email TEXT PRIMARY KEY COLLATE NOCASE,
+                name TEXT COLLATE NOCASE,
+                sent_count INTEGER NOT NULL DEFAULT 0,
+                received_count INTEGER NOT NULL DEFAULT 0,
+                last_used INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX idx_recipients_name ON recipients(name);
+            CREATE TABLE recipient_messages (key INTEGER PRIMARY KEY);
+            CREATE TABLE recipients_indexed (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                last_message_id INTEGER NOT NULL
+            );
+            INSERT INTO recipients_indexed (id, last_message_id) VALUES (1, 0);
+        "#,
+    },
+    Migration {
+        version: 5,
+        name: "read receipts",
+        sql: r#"
+            CREATE TABLE receipt_policies (
+                email TEXT PRIMARY KEY COLLATE NOCASE,
+                policy TEXT NOT NULL
+            );
+            CREATE TABLE receipt_requests (
+                message_id INTEGER PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+                notify_to TEXT NOT NULL,
+                return_path_matches INTEGER NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending'
+            );
+            CREATE TABLE requested_receipts (
+                message_id TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+                subject TEXT,
+                recipients TEXT NOT NULL,
+                sent_at INTEGER NOT NULL,
+                returned_at INTEGER,
+                disposition TEXT
+            );
+        "#,
+    },
+    Migration {
+        version: 6,
+        name: "thread keys",
+        sql: r#"
+            ALTER TABLE messages ADD COLUMN thread_key TEXT;
+            CREATE INDEX idx_messages_thread_key ON messages(thread_key);
+            CREATE INDEX idx_messages_unthreaded ON messages(id) WHERE thread_key IS NULL;
+        "#,
+    },
+    Migration {
+        version: 7,
+        name: "calendar invites",
+        sql: r#"
+            CREATE TABLE invites (
+                message_id INTEGER PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+                method TEXT NOT NULL,
+                uid TEXT NOT NULL,
+                sequence INTEGER NOT NULL,
+                recurrence_id TEXT,
+                summary TEXT,
+                location TEXT,
+                starts_at INTEGER,
+                ends_at INTEGER,
+                all_day INTEGER NOT NULL,
+                organizer TEXT,
+                attendees TEXT NOT NULL,
+                cancelled INTEGER NOT NULL,
+                response TEXT
+            );
+        "#,
+    },
+    Migration {
+        version: 8,
+        name: "pending flag changes",
+        sql: r#"
+            CREATE TABLE pending_flags (
+                message_id INTEGER NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+                flag TEXT NOT NULL,
+                value INTEGER NOT NULL,
+                changed_at INTEGER NOT NULL,
+                PRIMARY KEY (message_id, flag)
+            );
+        "#,
+    },
+    Migration {
+        version: 9,
+        name: "trash and junk auto-empty",
+        sql: r#"
+            ALTER TABLE accounts ADD COLUMN auto_empty_trash_days INTEGER;
+            ALTER TABLE accounts ADD COLUMN auto_empty_junk_days INTEGER;
+        "#,
+    },
+    Migration {
+        version: 10,
+        name: "sending identities",
+        sql: r#"
+            CREATE TABLE identities (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+                name TEXT,
+                email TEXT NOT NULL,
+                signature TEXT,
+                smtp_host TEXT,
+                smtp_port INTEGER,
+                smtp_username TEXT,
+                UNIQUE (account_id, email)
+            );
+        "#,
+    },
+    Migration {
+        version: 11,
+        name: "html signatures",
+        sql: r#"
+            ALTER TABLE identities ADD COLUMN signature_html TEXT;
+            ALTER TABLE identities ADD COLUMN signature_image BLOB;
+            ALTER TABLE identities ADD COLUMN signature_image_type TEXT;
+        "#,
+    },
+];
+
+/// Version of the newest schema this build knows
+pub fn latest_version() -> i64 {
+    MIGRATIONS
+        .last()
+        .map_or(BASELINE_VERSION, |migration| migration.version)
+}
+
+/// Migrations a database at schema version `current` hasn't had, in the
+/// order to apply them
+pub fn pending(current: i64) -> &'static [Migration] {
+    pending_in(MIGRATIONS, current)
+}
+
+fn pending_in(migrations: &[Migration], current: i64) -> &[Migration] {
+    &migrations[migrations.partition_point(|migration| migration.version <= current)..]
+}
+
+/// Where the database at `path` is copied before migrating it. Each
+/// migration run replaces the copy from the one before.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".pre-migration");
+    PathBuf::from(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 2,
+            name: "threads",
+            sql: "CREATE TABLE threads (id INTEGER PRIMARY KEY)",
+        },
+        Migration {
+            version: 3,
+            name: "thread index",
+            sql: "CREATE INDEX idx_threads ON threads(id)",
+        },
+    ];
+
+    #[test]
+    fn test_migrations_are_numbered_in_order() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(
+                migration.version,
+                BASELINE_VERSION + 1 + i as i64,
+                "migration {:?} is out of order",
+                migration.name
+            );
+            assert!(!migration.sql.trim().is_empty());
+        }
+        assert_eq!(latest_version(), BASELINE_VERSION + MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn test_pending() {
+        let versions = |current| {
+            pending_in(TEST_MIGRATIONS, current)
+                .iter()
+                .map(|m| m.version)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(versions(BASELINE_VERSION), vec![2, 3]);
+        assert_eq!(versions(2), vec![3]);
+        assert_eq!(versions(3), Vec::<i64>::new());
+        // A database from a newer build has nothing left to apply
+        assert_eq!(versions(7), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(
+            backup_path(Path::new("/data/northmail/mail.db")),
+            PathBuf::from("/data/northmail/mail.db.pre-migration")
+        );
+    }
+}
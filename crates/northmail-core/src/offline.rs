@@ -0,0 +1,228 @@
+//! Download for offline: a folder's mail, whole, before going without a
+//! connection
+//!
+//! The background prefetch only fetches the bodies of recent messages, a
+//! few dozen at a time, and leaves attachments on the server. Before a
+//! flight the user can instead have every message of a folder in a date
+//! range downloaded with its attachments. The download keeps to the
+//! account's retention policy (see [`crate::retention`]): messages whose
+//! bodies would be dropped again for their age are left out, and it stops
+//! before the cache outgrows its size limit, newest messages first.
+
+use crate::retention::RetentionPolicy;
+use chrono::{Local, NaiveDate};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Ranges offered, in days back from now; `None` takes the whole folder
+pub const DOWNLOAD_RANGES: [Option<u32>; 5] = [Some(7), Some(30), Some(90), Some(365), None];
+
+/// Messages to download by date, as Unix seconds: from `since` up to but
+/// not including `until`. Open ends are unbounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl DateRange {
+    /// The last `days` days before `now`, or everything
+    pub fn last_days(days: Option<u32>, now: i64) -> Self {
+        Self {
+            since: days.map(|days| now.saturating_sub(i64::from(days) * SECONDS_PER_DAY)),
+            until: None,
+        }
+    }
+
+    /// From the start of `from` to the end of `to`, local time
+    pub fn between(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        let start_of = |day: NaiveDate| {
+            day.and_hms_opt(0, 0, 0)
+                .and_then(|time| time.and_local_timezone(Local).earliest())
+                .map(|time| time.timestamp())
+        };
+        Self {
+            since: from.and_then(start_of),
+            until: to.and_then(|day| day.succ_opt()).and_then(start_of),
+        }
+    }
+
+    /// The range without what the retention policy would drop again for
+    /// its age at `now`
+    pub fn within_retention(self, policy: &RetentionPolicy, now: i64) -> Self {
+        let since = match (self.since, policy.body_cutoff(now)) {
+            (Some(since), Some(cutoff)) => Some(since.max(cutoff)),
+            (since, cutoff) => since.or(cutoff),
+        };
+        Self { since, ..self }
+    }
+
+    /// Whether a message dated `date_epoch` is in the range. Undated
+    /// messages are only in a range with no ends.
+    pub fn contains(&self, date_epoch: Option<i64>) -> bool {
+        match date_epoch {
+            Some(date) => {
+                self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date < until)
+            }
+            None => self.since.is_none() && self.until.is_none(),
+        }
+    }
+
+    /// Whether nothing can be in the range
+    pub fn is_empty(&self) -> bool {
+        matches!((self.since, self.until), (Some(since), Some(until)) if since >= until)
+    }
+}
+
+/// A day as typed in the download dialog, `YYYY-MM-DD`
+pub fn parse_day(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()
+}
+
+/// A cached message whose body or attachments are still on the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OfflineCandidate {
+    pub uid: u32,
+    pub date_epoch: Option<i64>,
+    /// Size of the whole message on the server
+    pub size: u64,
+}
+
+/// What a download fetches
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadPlan {
+    /// Messages to download, newest first
+    pub uids: Vec<u32>,
+    /// Their size on the server
+    pub bytes: u64,
+    /// Messages in the range left out to stay within the cache limit
+    pub left_out: usize,
+}
+
+/// Plan downloading the `candidates` in `range`, newest first, within
+/// `budget` bytes of room left in the cache (`None` when unlimited). Once a
+/// message doesn't fit the older ones are left out too, as the cache would
+/// drop them first anyway.
+pub fn plan_download(mut candidates: Vec<OfflineCandidate>, range: &DateRange, budget: Option<u64>) -> DownloadPlan {
+    candidates.retain(|candidate| range.contains(candidate.date_epoch));
+    candidates.sort_by(|a, b| b.date_epoch.cmp(&a.date_epoch).then(b.uid.cmp(&a.uid)));
+
+    let mut plan = DownloadPlan::default();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let bytes = plan.bytes + candidate.size;
+        if budget.is_some_and(|budget| bytes > budget) {
+            plan.left_out = candidates.len() - index;
+            break;
+        }
+        plan.uids.push(candidate.uid);
+        plan.bytes = bytes;
+    }
+    plan
+}
+
+/// How far a download has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Messages planned
+    pub total: usize,
+    pub downloaded: usize,
+    pub failed: usize,
+    /// Messages left out for the cache limit
+    pub left_out: usize,
+}
+
+impl DownloadProgress {
+    pub fn done(&self) -> usize {
+        self.downloaded + self.failed
+    }
+
+    /// Share of the planned messages done, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done() as f64 / self.total as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(uid: u32, date_epoch: Option<i64>, size: u64) -> OfflineCandidate {
+        OfflineCandidate { uid, date_epoch, size }
+    }
+
+    #[test]
+    fn test_range() {
+        let range = DateRange::last_days(Some(7), 10 * SECONDS_PER_DAY);
+        assert!(range.contains(Some(3 * SECONDS_PER_DAY)));
+        assert!(!range.contains(Some(2 * SECONDS_PER_DAY)));
+        assert!(!range.contains(None));
+        assert!(DateRange::last_days(None, 0).contains(None));
+
+        let day = parse_day(" 2024-05-01 ").unwrap();
+        let range = DateRange::between(Some(day), Some(day));
+        assert_eq!(range.until.unwrap() - range.since.unwrap(), SECONDS_PER_DAY);
+        assert!(range.contains(range.since));
+        assert!(!range.contains(range.until));
+        assert!(parse_day("May 1st").is_none());
+        assert!(DateRange::between(parse_day("2024-05-02"), Some(day)).is_empty());
+    }
+
+    #[test]
+    fn test_within_retention() {
+        let now = 100 * SECONDS_PER_DAY;
+        let policy = RetentionPolicy {
+            body_days: Some(30),
+            max_bytes: None,
+        };
+        let cutoff = 70 * SECONDS_PER_DAY;
+        assert_eq!(DateRange::last_days(None, now).within_retention(&policy, now).since, Some(cutoff));
+        assert_eq!(
+            DateRange::last_days(Some(7), now).within_retention(&policy, now).since,
+            Some(93 * SECONDS_PER_DAY)
+        );
+        let range = DateRange::last_days(Some(7), now);
+        assert_eq!(range.within_retention(&RetentionPolicy::default(), now), range);
+    }
+
+    #[test]
+    fn test_plan_download() {
+        let candidates = vec![
+            candidate(1, Some(100), 40),
+            candidate(2, Some(300), 50),
+            candidate(3, Some(200), 30),
+            candidate(4, Some(10), 5),
+        ];
+        let range = DateRange {
+            since: Some(50),
+            until: None,
+        };
+
+        let plan = plan_download(candidates.clone(), &range, None);
+        assert_eq!(plan.uids, vec![2, 3, 1]);
+        assert_eq!(plan.bytes, 120);
+        assert_eq!(plan.left_out, 0);
+
+        // The newest that fit; older ones are left out even if smaller
+        let plan = plan_download(candidates, &range, Some(85));
+        assert_eq!(plan.uids, vec![2, 3]);
+        assert_eq!(plan.left_out, 1);
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut progress = DownloadProgress {
+            total: 4,
+            ..Default::default()
+        };
+        assert_eq!(progress.fraction(), 0.0);
+        progress.downloaded = 2;
+        progress.failed = 1;
+        assert_eq!(progress.done(), 3);
+        assert_eq!(progress.fraction(), 0.75);
+        assert_eq!(DownloadProgress::default().fraction(), 1.0);
+    }
+}
@@ -0,0 +1,187 @@
+//! Outbox: outgoing mail waiting to be sent
+//!
+//! Every message the user sends waits in the `outbox` table until the
+//! app's outbox worker delivers it. A message sent later is scheduled for
+//! the time it is due. One that was delivered leaves the outbox. One that
+//! failed for the time being, because the network or the server was
+//! unavailable, is tried again later, waiting twice as long each time (see
+//! [`retry_delay`]); the worker holds off altogether while there is no
+//! network. A message that failed otherwise, or too often, stays there with
+//! its error until the user sends it again, edits it or cancels it.
+
+/// Where an outgoing message stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxStatus {
+    /// Waiting to be sent as soon as the worker gets to it
+    Queued,
+    /// Waiting for the time it is due
+    Scheduled,
+    /// Being delivered right now
+    Sending,
+    /// Delivery failed for now; waits to be tried again at its due time
+    Retrying,
+    /// Delivery failed; waits for the user
+    Failed,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Queued => "queued",
+            OutboxStatus::Scheduled => "scheduled",
+            OutboxStatus::Sending => "sending",
+            OutboxStatus::Retrying => "retrying",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+
+    /// Status stored as `text`; anything unknown counts as failed, so it
+    /// is never sent without the user looking at it
+    pub fn parse(text: &str) -> Self {
+        match text {
+            "queued" => OutboxStatus::Queued,
+            "scheduled" => OutboxStatus::Scheduled,
+            "sending" => OutboxStatus::Sending,
+            "retrying" => OutboxStatus::Retrying,
+            _ => OutboxStatus::Failed,
+        }
+    }
+
+    /// Status of a message queued at `now` to be sent at `send_at`
+    pub fn waiting(send_at: i64, now: i64) -> Self {
+        if send_at > now {
+            OutboxStatus::Scheduled
+        } else {
+            OutboxStatus::Queued
+        }
+    }
+}
+
+/// Delivery attempts before a message that keeps failing for the time
+/// being is left for the user
+pub const MAX_ATTEMPTS: i64 = 6;
+
+/// Wait before the first retry; each one after waits twice as long
+pub const FIRST_RETRY_SECS: i64 = 60;
+
+/// Longest wait between retries
+pub const MAX_RETRY_SECS: i64 = 60 * 60;
+
+/// How long to wait before trying a message again after `attempts`
+/// attempts that failed for the time being; `None` once it has had
+/// [`MAX_ATTEMPTS`]
+pub fn retry_delay(attempts: i64) -> Option<i64> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    let doublings = attempts.saturating_sub(1).clamp(0, 30) as u32;
+    Some(FIRST_RETRY_SECS.saturating_mul(1 << doublings).min(MAX_RETRY_SECS))
+}
+
+/// Why a delivery failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryError {
+    pub message: String,
+    /// Whether trying again later may succeed without the user changing
+    /// anything: the network or server was unavailable, or the server
+    /// deferred the message
+    pub transient: bool,
+}
+
+impl DeliveryError {
+    /// A failure worth another try later
+    pub fn transient(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            transient: true,
+        }
+    }
+
+    /// A failed send, transient if the SMTP or Graph error is
+    pub fn from_smtp(context: &str, error: &northmail_smtp::SmtpError) -> Self {
+        Self {
+            message: format!("{}: {}", context, error),
+            transient: error.is_transient(),
+        }
+    }
+}
+
+/// Failures are final unless said otherwise
+impl From<String> for DeliveryError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            transient: false,
+        }
+    }
+}
+
+impl std::fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A message in the outbox, without its content
+#[derive(Debug, Clone)]
+pub struct OutboxItem {
+    pub id: i64,
+    pub account_id: String,
+    pub subject: String,
+    /// Recipients as shown in the outbox, comma-separated
+    pub recipients: String,
+    pub status: OutboxStatus,
+    /// When the message is due, in Unix seconds
+    pub send_at: i64,
+    /// Why the last delivery failed
+    pub error: Option<String>,
+    /// Delivery attempts so far
+    pub attempts: i64,
+    /// Saved draft of the message, sent in place where the provider
+    /// supports it
+    pub draft_uid: Option<u32>,
+}
+
+impl OutboxItem {
+    /// Whether the user can still change the message; not while it is
+    /// being delivered
+    pub fn is_editable(&self) -> bool {
+        self.status != OutboxStatus::Sending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [
+            OutboxStatus::Queued,
+            OutboxStatus::Scheduled,
+            OutboxStatus::Sending,
+            OutboxStatus::Retrying,
+            OutboxStatus::Failed,
+        ] {
+            assert_eq!(OutboxStatus::parse(status.as_str()), status);
+        }
+        assert_eq!(OutboxStatus::parse("bogus"), OutboxStatus::Failed);
+    }
+
+    #[test]
+    fn test_waiting_status() {
+        assert_eq!(OutboxStatus::waiting(100, 100), OutboxStatus::Queued);
+        assert_eq!(OutboxStatus::waiting(50, 100), OutboxStatus::Queued);
+        assert_eq!(OutboxStatus::waiting(101, 100), OutboxStatus::Scheduled);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Some(60));
+        assert_eq!(retry_delay(2), Some(120));
+        assert_eq!(retry_delay(3), Some(240));
+        assert_eq!(retry_delay(MAX_ATTEMPTS - 1), Some(960));
+        assert_eq!(retry_delay(MAX_ATTEMPTS), None);
+        assert_eq!(retry_delay(0), Some(60));
+    }
+}
@@ -0,0 +1,83 @@
+//! Parallel folder backfill
+//!
+//! Backfilling a huge folder is bound by round trips rather than bandwidth:
+//! a single connection asking for a few hundred headers at a time spends
+//! most of the sync waiting on the server. Opening one or two more
+//! connections and letting each take the next pending range keeps the link
+//! busy, taking the first sync of a 60k-message folder from tens of minutes
+//! to a few. Providers limit how many connections an account may hold (and
+//! push already holds some), so the count is capped per provider.
+
+/// Most connections a single folder sync uses
+pub const MAX_SYNC_CONNECTIONS: u32 = 3;
+
+/// Messages left to fetch per connection; below this another login costs
+/// more than it saves
+pub const MESSAGES_PER_CONNECTION: u32 = 5000;
+
+/// Most connections a folder sync may use on a server, leaving room for
+/// push and on-demand fetches within the provider's per-account limit
+pub fn provider_connection_cap(host: &str) -> u32 {
+    match host.to_ascii_lowercase().as_str() {
+        // Gmail allows 15 connections per account, Microsoft 365 about 20
+        "imap.gmail.com" | "outlook.office365.com" | "imap-mail.outlook.com" | "imap.fastmail.com" => 3,
+        // iCloud and Yahoo drop connections well before their documented
+        // limits, and unknown servers may be stricter still
+        _ => 2,
+    }
+}
+
+/// Connections to sync `remaining` messages with: one per
+/// [`MESSAGES_PER_CONNECTION`], within `cap` and [`MAX_SYNC_CONNECTIONS`]
+pub fn sync_connections(remaining: u32, cap: u32) -> u32 {
+    remaining
+        .div_ceil(MESSAGES_PER_CONNECTION)
+        .clamp(1, cap.clamp(1, MAX_SYNC_CONNECTIONS))
+}
+
+/// Split `lowest..=highest` into ranges of at most `batch`, highest first,
+/// so the newest messages arrive first however many connections share them
+pub fn descending_ranges(lowest: u32, highest: u32, batch: u32) -> Vec<(u32, u32)> {
+    let batch = batch.max(1);
+    let mut ranges = Vec::new();
+    if lowest == 0 || lowest > highest {
+        return ranges;
+    }
+    let mut upper = highest;
+    loop {
+        let lower = upper.saturating_sub(batch - 1).max(lowest);
+        ranges.push((lower, upper));
+        if lower == lowest {
+            break;
+        }
+        upper = lower - 1;
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_connections() {
+        assert_eq!(sync_connections(0, 3), 1);
+        assert_eq!(sync_connections(4000, 3), 1);
+        assert_eq!(sync_connections(8000, 3), 2);
+        assert_eq!(sync_connections(60_000, 3), 3);
+        assert_eq!(sync_connections(60_000, 2), 2);
+        assert_eq!(sync_connections(60_000, 10), MAX_SYNC_CONNECTIONS);
+        assert_eq!(sync_connections(60_000, 0), 1);
+        assert_eq!(provider_connection_cap("IMAP.gmail.com"), 3);
+        assert_eq!(provider_connection_cap("mail.example.org"), 2);
+    }
+
+    #[test]
+    fn test_descending_ranges() {
+        assert_eq!(descending_ranges(1, 1200, 500), vec![(701, 1200), (201, 700), (1, 200)]);
+        assert_eq!(descending_ranges(1, 500, 500), vec![(1, 500)]);
+        assert_eq!(descending_ranges(3, 3, 500), vec![(3, 3)]);
+        assert!(descending_ranges(5, 4, 500).is_empty());
+        assert!(descending_ranges(0, 4, 500).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+//! Removing an account without a trace
+//!
+//! Deleting the account row takes its folders, messages, attachments and
+//! identities with it, but not everything an account leaves behind: its
+//! outbox and rules, the recipients its mail added to the suggestions, and
+//! the passwords and tokens in the keyring under its addresses.
+//! [`purge_account`] removes all of these in one call, reporting how far it
+//! got as it goes. With secure wipe on, the database overwrites what it
+//! deletes.
+//!
+//! Some files hold the account's mail together with every other account's:
+//! opened attachments and messages dragged out as `.eml` files, which don't
+//! say which account they came from, and the copies of the database kept
+//! next to it (see [`Database::aside_copies`]), the only way back from a
+//! migration or restore. A purge leaves them alone and lists them in its
+//! report, for the user to remove.
+
+use crate::runtime::spawn_db;
+use crate::{CoreError, CoreResult, Database};
+use futures::StreamExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// What a purge is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurgeStage {
+    /// Deleting cached mail, a folder at a time
+    Mail,
+    /// Deleting the account and what else the cache keeps for it
+    Records,
+    /// Deleting passwords and tokens from the keyring, an address at a time
+    Secrets,
+}
+
+/// How far a purge has got: `done` of `total` items in `stage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PurgeProgress {
+    pub stage: PurgeStage,
+    pub done: usize,
+    pub total: usize,
+}
+
+impl PurgeProgress {
+    pub fn new(stage: PurgeStage, done: usize, total: usize) -> Self {
+        Self { stage, done, total }
+    }
+
+    /// Share of the stage done, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f64 / self.total as f64
+        }
+    }
+}
+
+/// What [`Database::purge_account`] removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PurgedAccount {
+    pub email: String,
+    /// Messages deleted from the cache
+    pub messages: u64,
+    /// Addresses whose keyring entries belonged to the account alone
+    pub secret_addresses: Vec<String>,
+}
+
+/// How a purge ended. The cache is always cleared before the rest; what
+/// couldn't be removed after that is listed rather than stopping it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub email: String,
+    pub messages: u64,
+    /// What was left behind, and why
+    pub failures: Vec<String>,
+    /// Files kept because they hold other accounts' mail too
+    pub kept: Vec<PathBuf>,
+}
+
+/// Addresses to clear from the keyring for an account: its own and those
+/// of identities with an SMTP server of their own, leaving out any that is
+/// also the address of an account or identity that stays (`others`), as
+/// the keyring files secrets by address
+pub fn secret_addresses(email: &str, identities: &[(String, bool)], others: &[String]) -> Vec<String> {
+    let mut addresses: Vec<String> = Vec::new();
+    let candidates = std::iter::once(email).chain(
+        identities
+            .iter()
+            .filter(|(_, own_smtp)| *own_smtp)
+            .map(|(address, _)| address.as_str()),
+    );
+    for address in candidates {
+        let address = address.trim();
+        let taken = |list: &[String]| list.iter().any(|other| other.trim().eq_ignore_ascii_case(address));
+        if !address.is_empty() && !taken(others) && !taken(&addresses) {
+            addresses.push(address.to_string());
+        }
+    }
+    addresses
+}
+
+/// Remove every trace of an account: its cache (see
+/// [`Database::purge_account`]) and its keyring entries. Opened attachments
+/// in `attachments_dir` and the copies kept next to the database file at
+/// `database_path` are listed as kept. Await it on the GLib main loop,
+/// which the keyring needs; the cache work runs on the database runtime.
+/// Fails only if the cache can't be cleared.
+pub async fn purge_account(
+    db: Arc<Database>,
+    account_id: &str,
+    attachments_dir: Option<PathBuf>,
+    database_path: Option<PathBuf>,
+    mut progress: impl FnMut(PurgeProgress),
+) -> CoreResult<PurgeReport> {
+    let (sender, mut receiver) = futures::channel::mpsc::unbounded();
+    let id = account_id.to_string();
+    let task = spawn_db(async move {
+        db.purge_account(&id, move |update| {
+            let _ = sender.unbounded_send(update);
+        })
+        .await
+    });
+    while let Some(update) = receiver.next().await {
+        progress(update);
+    }
+    let purged = task.await.map_err(|e| CoreError::DatabaseError(e.to_string()))??;
+
+    let mut report = PurgeReport {
+        email: purged.email,
+        messages: purged.messages,
+        failures: Vec::new(),
+        kept: Vec::new(),
+    };
+
+    crate::jmap::forget_credentials(account_id);
+    let store = northmail_auth::SecretStore::new();
+    let total = purged.secret_addresses.len();
+    for (done, address) in purged.secret_addresses.iter().enumerate() {
+        progress(PurgeProgress::new(PurgeStage::Secrets, done, total));
+        let results = [
+            store.delete_tokens(address).await,
+            store.delete_jmap_credentials(address).await,
+            store.delete_smtp_password(address).await,
+        ];
+        if let Some(Err(e)) = results.into_iter().find(Result::is_err) {
+            warn!("Failed to delete keyring entries for {}: {}", address, e);
+            report.failures.push(format!("{}: {}", address, e));
+        }
+    }
+
+    let copies = match database_path.as_deref().map(Database::aside_copies).transpose() {
+        Ok(copies) => copies.unwrap_or_default(),
+        Err(e) => {
+            warn!("Failed to list copies of the database: {}", e);
+            report.failures.push(e.to_string());
+            Vec::new()
+        }
+    };
+    let opened = attachments_dir.filter(|dir| dir.read_dir().is_ok_and(|mut entries| entries.next().is_some()));
+    report.kept = opened.into_iter().chain(copies).collect();
+
+    info!(
+        "Purged account {}: {} messages, {} addresses from the keyring",
+        report.email, report.messages, total
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_addresses() {
+        let identities = vec![
+            ("sales@example.com".to_string(), true),
+            ("alias@example.com".to_string(), false),
+            ("Me@Example.com".to_string(), true),
+            ("shared@example.com".to_string(), true),
+        ];
+        let others = vec!["shared@example.com".to_string()];
+        assert_eq!(
+            secret_addresses("me@example.com", &identities, &others),
+            vec!["me@example.com", "sales@example.com"]
+        );
+        // The address is another account's too
+        assert!(secret_addresses("shared@example.com", &[], &others).is_empty());
+    }
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(PurgeProgress::new(PurgeStage::Mail, 1, 4).fraction(), 0.25);
+        assert_eq!(PurgeProgress::new(PurgeStage::Secrets, 0, 0).fraction(), 1.0);
+    }
+}
@@ -0,0 +1,92 @@
+//! Account storage usage
+//!
+//! Turns the quota roots a server reports into one usage figure per
+//! account, for the accounts page ("14.2 GB of 15 GB used") and for warning
+//! when the mailbox is nearly full.
+
+use northmail_imap::Quota;
+
+/// Share of the quota in use from which the account is flagged as nearly full
+pub const NEAR_QUOTA_FRACTION: f64 = 0.9;
+
+/// Storage used by an account and its limit, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageUsage {
+    pub used: u64,
+    pub limit: u64,
+}
+
+impl StorageUsage {
+    /// Storage usage from the quota roots covering the inbox. With several
+    /// roots the fullest one counts, since it's the first to run out.
+    pub fn from_quotas(quotas: &[Quota]) -> Option<Self> {
+        quotas
+            .iter()
+            .filter_map(Quota::storage_bytes)
+            .filter(|(_, limit)| *limit > 0)
+            .map(|(used, limit)| Self { used, limit })
+            .max_by(|a, b| a.fraction().total_cmp(&b.fraction()))
+    }
+
+    /// Share of the limit in use, 0.0 to 1.0 (more when over quota)
+    pub fn fraction(&self) -> f64 {
+        if self.limit == 0 {
+            return 0.0;
+        }
+        self.used as f64 / self.limit as f64
+    }
+
+    pub fn is_near_limit(&self) -> bool {
+        self.fraction() >= NEAR_QUOTA_FRACTION
+    }
+}
+
+/// Human-readable size in decimal units, e.g. "14.2 GB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["kB", "MB", "GB", "TB", "PB"];
+    if bytes < 1000 {
+        return format!("{} bytes", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    let text = format!("{:.1}", value);
+    // Whole numbers read better without the ".0": "15 GB"
+    let text = text.strip_suffix(".0").unwrap_or(&text);
+    format!("{} {}", text, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use northmail_imap::QuotaResource;
+
+    fn quota(usage_kib: u64, limit_kib: u64) -> Quota {
+        Quota {
+            root: String::new(),
+            resources: vec![QuotaResource { name: "STORAGE".to_string(), usage: usage_kib, limit: limit_kib }],
+        }
+    }
+
+    #[test]
+    fn test_storage_usage() {
+        let usage = StorageUsage::from_quotas(&[quota(100, 1000), quota(950, 1000)]).unwrap();
+        assert_eq!(usage, StorageUsage { used: 950 * 1024, limit: 1000 * 1024 });
+        assert!(usage.is_near_limit());
+        assert!(!StorageUsage::from_quotas(&[quota(100, 1000)]).unwrap().is_near_limit());
+        // A zero limit means no storage quota
+        assert_eq!(StorageUsage::from_quotas(&[quota(100, 0)]), None);
+        assert_eq!(StorageUsage::from_quotas(&[]), None);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(14_200_000_000), "14.2 GB");
+        assert_eq!(format_size(15_000_000_000), "15 GB");
+        assert_eq!(format_size(2_500_000), "2.5 MB");
+    }
+}
@@ -0,0 +1,97 @@
+//! Read aloud
+//!
+//! Builds what a message sounds like when read out, and frames it for
+//! speech-dispatcher's SSIP protocol. The spoken text is the sender, the
+//! subject and the body, without quoted replies or the signature, and with
+//! links shortened to their site so a URL isn't spelled out character by
+//! character.
+
+use crate::thread::split_quote;
+
+/// Most characters of body read out
+const MAX_SPOKEN_CHARS: usize = 20_000;
+
+/// Text to speak for a message. `from`, `subject` and `body` are the
+/// display name, subject line and plain text body; the labels come from
+/// the caller so they can be translated.
+pub fn spoken_text(from_label: &str, from: &str, subject_label: &str, subject: &str, body: &str) -> String {
+    let mut text = String::new();
+    if !from.trim().is_empty() {
+        text.push_str(&format!("{} {}.\n", from_label, from.trim()));
+    }
+    if !subject.trim().is_empty() {
+        text.push_str(&format!("{} {}.\n", subject_label, subject.trim()));
+    }
+    text.push('\n');
+    text.push_str(&spoken_body(body));
+    text.trim().to_string()
+}
+
+/// The body as it should be read: no quotes, no signature, short links
+fn spoken_body(body: &str) -> String {
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        // Signature separator: everything after it is the signature
+        if line == "-- " || line == "--" {
+            break;
+        }
+        if split_quote(line).0 > 0 {
+            continue;
+        }
+        lines.push(shorten_links(line));
+    }
+    let text = lines.join("\n");
+    match text.char_indices().nth(MAX_SPOKEN_CHARS) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text,
+    }
+}
+
+/// Replace each http(s) URL with its host, e.g. `example.com`
+fn shorten_links(line: &str) -> String {
+    line.split(' ')
+        .map(|word| {
+            let url = word.trim_start_matches(['<', '(']);
+            let Some(rest) = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")) else {
+                return word.to_string();
+            };
+            let host = rest.split(['/', '?', '#', '>', ')']).next().unwrap_or_default();
+            host.strip_prefix("www.").unwrap_or(host).to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Frame text as the data of an SSIP `SPEAK` command: CRLF line endings,
+/// lines starting with a dot doubled, ended by a lone dot
+pub fn ssip_data(text: &str) -> String {
+    let mut data = String::with_capacity(text.len() + 8);
+    for line in text.lines() {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push_str(".\r\n");
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spoken_text() {
+        let body = "Hi,\nthe slides are at https://www.example.com/deck?id=4 now.\n> old quoted text\n-- \nAnn\n+1 555 0100";
+        let text = spoken_text("From", "Ann Lee", "Subject", "Slides", body);
+        assert_eq!(text, "From Ann Lee.\nSubject Slides.\n\nHi,\nthe slides are at example.com now.");
+        assert_eq!(spoken_text("From", "", "Subject", "", "Hello"), "Hello");
+    }
+
+    #[test]
+    fn test_ssip_data() {
+        assert_eq!(ssip_data("Hello\n.hidden\nBye"), "Hello\r\n..hidden\r\nBye\r\n.\r\n");
+        assert_eq!(ssip_data(""), ".\r\n");
+    }
+}
@@ -0,0 +1,245 @@
+//! Recipient domain typo detection
+//!
+//! Catches addresses like `someone@gmial.com` before they are sent by
+//! comparing the domain against well-known providers and domains the user
+//! has sent to before. Only the usual slips count: a mistyped ending
+//! (`gmail.co`), swapped letters (`gmial.com`), or a letter too many or too
+//! few in a long name (`outlok.com`). Short names are a letter away from
+//! too many real domains (`email.com`, `mail.com`) to go further.
+
+/// Widely used mail domains checked for near-misses
+pub const POPULAR_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "msn.com",
+    "yahoo.com",
+    "ymail.com",
+    "icloud.com",
+    "me.com",
+    "mac.com",
+    "aol.com",
+    "proton.me",
+    "protonmail.com",
+    "gmx.com",
+    "gmx.de",
+    "gmx.net",
+    "mail.com",
+    "web.de",
+    "yandex.com",
+    "zoho.com",
+    "fastmail.com",
+    "hey.com",
+];
+
+/// Regional domains of the popular providers; close to the entries above
+/// but real addresses rather than typos
+const REGIONAL_DOMAINS: &[&str] = &[
+    "yahoo.ca",
+    "yahoo.co.uk",
+    "yahoo.co.in",
+    "yahoo.co.jp",
+    "yahoo.com.au",
+    "yahoo.com.br",
+    "yahoo.de",
+    "yahoo.es",
+    "yahoo.fr",
+    "yahoo.it",
+    "hotmail.ca",
+    "hotmail.co.uk",
+    "hotmail.de",
+    "hotmail.es",
+    "hotmail.fr",
+    "hotmail.it",
+    "outlook.de",
+    "outlook.es",
+    "outlook.fr",
+    "outlook.it",
+    "live.ca",
+    "live.co.uk",
+    "live.de",
+    "live.fr",
+    "live.it",
+    "gmx.at",
+    "gmx.ch",
+    "gmx.fr",
+    "yandex.ru",
+];
+
+/// Shortest name (the part before the first dot) a missing, extra or
+/// wrong letter of which is taken for a typo; shorter names only count
+/// swapped letters
+const MIN_EDITED_NAME: usize = 6;
+
+/// Domain part of an email address, lowercased
+pub fn email_domain(address: &str) -> Option<String> {
+    let (_, domain) = address.trim().rsplit_once('@')?;
+    let domain = domain.trim_end_matches('>').trim();
+    if domain.is_empty() {
+        None
+    } else {
+        Some(domain.to_lowercase())
+    }
+}
+
+/// Extract all domains from a stored address list such as
+/// `"Ann <ann@example.com>, bob@example.org"`
+pub fn domains_in(addresses: &str) -> Vec<String> {
+    crate::address::emails_in(addresses)
+        .into_iter()
+        .filter_map(|a| email_domain(&a))
+        .collect()
+}
+
+/// Suggest a correction for a recipient domain that looks like a typo of a
+/// popular domain or of one in `known` (e.g. from send history).
+/// Returns `None` if the domain is itself known or isn't a likely typo of
+/// any of them.
+pub fn suggest_domain(domain: &str, known: &[String]) -> Option<String> {
+    let domain = domain.to_lowercase();
+    if POPULAR_DOMAINS.contains(&domain.as_str())
+        || REGIONAL_DOMAINS.contains(&domain.as_str())
+        || known.contains(&domain)
+    {
+        return None;
+    }
+
+    POPULAR_DOMAINS
+        .iter()
+        .map(|d| d.to_string())
+        .chain(known.iter().cloned())
+        .filter(|candidate| is_likely_typo(&domain, candidate))
+        .min_by_key(|candidate| edit_distance(&domain, candidate))
+}
+
+/// Whether `domain` is `candidate` with a slip in its ending, its name, or
+/// one of each (see the module docs)
+fn is_likely_typo(domain: &str, candidate: &str) -> bool {
+    let (Some((name, ending)), Some((candidate_name, candidate_ending))) =
+        (domain.split_once('.'), candidate.split_once('.'))
+    else {
+        return false;
+    };
+    let name_slip = match edit_distance(name, candidate_name) {
+        0 => true,
+        1 => {
+            candidate_name.chars().count() >= MIN_EDITED_NAME
+                || is_transposition(name, candidate_name)
+        }
+        _ => false,
+    };
+    name_slip && edit_distance(ending, candidate_ending) <= 1 && domain != candidate
+}
+
+/// Whether `a` is `b` with two neighbouring letters swapped
+fn is_transposition(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len() != b.len() {
+        return false;
+    }
+    let differ: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    matches!(differ[..], [i, j] if j == i + 1 && a[i] == b[j] && a[j] == b[i])
+}
+
+/// Optimal string alignment distance (Levenshtein plus adjacent transpositions)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggests_popular_domains() {
+        assert_eq!(suggest_domain("gmial.com", &[]), Some("gmail.com".to_string()));
+        assert_eq!(suggest_domain("outlok.com", &[]), Some("outlook.com".to_string()));
+        assert_eq!(suggest_domain("hotmial.co", &[]), Some("hotmail.com".to_string()));
+    }
+
+    #[test]
+    fn test_suggests_real_tld_typos() {
+        assert_eq!(suggest_domain("gmail.co", &[]), Some("gmail.com".to_string()));
+        assert_eq!(suggest_domain("gmail.cm", &[]), Some("gmail.com".to_string()));
+        assert_eq!(suggest_domain("gmail.om", &[]), Some("gmail.com".to_string()));
+        assert_eq!(suggest_domain("hotmail.co", &[]), Some("hotmail.com".to_string()));
+        assert_eq!(suggest_domain("yahoo.cm", &[]), Some("yahoo.com".to_string()));
+    }
+
+    #[test]
+    fn test_known_domains_are_not_flagged() {
+        assert_eq!(suggest_domain("gmail.com", &[]), None);
+        assert_eq!(suggest_domain("mail.com", &[]), None);
+        assert_eq!(suggest_domain("email.com", &[]), None);
+        assert_eq!(suggest_domain("acme.io", &["acme.io".to_string()]), None);
+        assert_eq!(suggest_domain("example.org", &[]), None);
+    }
+
+    #[test]
+    fn test_regional_domains_are_not_flagged() {
+        assert_eq!(suggest_domain("yahoo.ca", &[]), None);
+        assert_eq!(suggest_domain("hotmail.ca", &[]), None);
+        assert_eq!(suggest_domain("live.ca", &[]), None);
+        assert_eq!(suggest_domain("outlook.de", &[]), None);
+        assert_eq!(suggest_domain("gmail.co", &["gmail.co".to_string()]), None);
+    }
+
+    #[test]
+    fn test_short_names_only_count_swapped_letters() {
+        // One letter away from mail.com, gmail.com, me.com or hey.com
+        assert_eq!(suggest_domain("gmal.com", &[]), None);
+        assert_eq!(suggest_domain("mai.com", &[]), None);
+        assert_eq!(suggest_domain("mx.com", &[]), None);
+        assert_eq!(suggest_domain("key.com", &[]), None);
+        assert_eq!(suggest_domain("hye.com", &[]), Some("hey.com".to_string()));
+        assert_eq!(suggest_domain("acme.io", &["acne.io".to_string()]), None);
+    }
+
+    #[test]
+    fn test_transposition() {
+        assert!(is_transposition("gmial", "gmail"));
+        assert!(!is_transposition("gmail", "gmail"));
+        assert!(!is_transposition("email", "gmail"));
+        assert!(!is_transposition("gimal", "gmail"));
+    }
+
+    #[test]
+    fn test_suggests_history_domains() {
+        let known = vec!["northwind.com".to_string()];
+        assert_eq!(suggest_domain("nortwind.com", &known), Some("northwind.com".to_string()));
+        assert_eq!(suggest_domain("nrtwnd.com", &known), None);
+    }
+
+    #[test]
+    fn test_domains_in() {
+        assert_eq!(
+            domains_in("Ann <ann@Example.com>, bob@example.org, nobody"),
+            vec!["example.com".to_string(), "example.org".to_string()]
+        );
+    }
+}
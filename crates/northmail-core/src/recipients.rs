@@ -0,0 +1,335 @@
+//! Recipient suggestions ranked by frecency
+//!
+//! The composer suggests the people the user is actually in touch with,
+//! whether or not they're in GNOME Contacts. The sync engine feeds every
+//! cached message into the `recipients` table ([`Database::index_recipients`]):
+//! whom a sent message went to, or whom another one came from. Suggestions
+//! ([`Database::suggest_recipients`]) rank by frecency, how often and how
+//! lately, with writing to someone counting for more than hearing from them,
+//! and are merged with the matching contacts ([`merge_suggestions`]).
+//!
+//! A message cached in several folders, or moved and cached again, counts
+//! once: each one is remembered by a hash of its Message-ID.
+//!
+//! [`Database::index_recipients`]: crate::Database::index_recipients
+//! [`Database::suggest_recipients`]: crate::Database::suggest_recipients
+
+use crate::address::{parse_address_list, Address};
+
+/// How much more a sent message counts than a received one
+pub const SENT_WEIGHT: f64 = 4.0;
+
+/// Days after which a recipient's score has halved
+pub const HALF_LIFE_DAYS: f64 = 30.0;
+
+/// Recipients suggested at most, before the contacts that remain
+pub const SUGGESTION_LIMIT: usize = 10;
+
+/// Recipients matching what's typed that are ranked, the ones most
+/// recently in touch
+pub const CANDIDATE_LIMIT: i64 = 200;
+
+/// Local parts of addresses that send mail nobody replies to
+const AUTOMATED_LOCAL_PARTS: &[&str] = &[
+    "noreply",
+    "no-reply",
+    "no_reply",
+    "donotreply",
+    "do-not-reply",
+    "mailer-daemon",
+    "postmaster",
+    "bounce",
+    "bounces",
+    "notifications",
+    "notification",
+];
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Someone the user has written to or heard from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipient {
+    pub email: String,
+    /// Name from the most recent message that gave one
+    pub name: Option<String>,
+    /// Sent messages addressed to them
+    pub sent_count: i64,
+    /// Messages received from them
+    pub received_count: i64,
+    /// Date of the most recent of those messages, as a Unix timestamp
+    pub last_used: i64,
+}
+
+impl Recipient {
+    /// Score at `now`: the weighted message count, halving every
+    /// [`HALF_LIFE_DAYS`] since the last message
+    pub fn frecency(&self, now: i64) -> f64 {
+        let count = self.sent_count as f64 * SENT_WEIGHT + self.received_count as f64;
+        let age_days = (now - self.last_used).max(0) as f64 / SECONDS_PER_DAY;
+        count * 0.5f64.powf(age_days / HALF_LIFE_DAYS)
+    }
+}
+
+/// Whom a message shows the user in touch with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correspondence {
+    /// The user wrote to them rather than heard from them
+    pub sent: bool,
+    pub addresses: Vec<Address>,
+}
+
+/// Whom a message in a folder of type `folder_type` shows the user in touch
+/// with: To and Cc of sent mail, the sender of anything else. Drafts, junk
+/// and trash say nothing either way, and the account's own address
+/// (`own_address`) and automated senders are left out; so a sent message
+/// Gmail also lists in All Mail counts once, from Sent.
+pub fn correspondence(
+    folder_type: &str,
+    own_address: Option<&str>,
+    from_address: Option<&str>,
+    from_name: Option<&str>,
+    to: Option<&str>,
+    cc: Option<&str>,
+) -> Option<Correspondence> {
+    let (sent, addresses) = match folder_type {
+        "drafts" | "spam" | "trash" => return None,
+        "sent" => (
+            true,
+            [to, cc]
+                .into_iter()
+                .flatten()
+                .flat_map(parse_address_list)
+                .collect::<Vec<_>>(),
+        ),
+        _ => (
+            false,
+            from_address
+                .map(|email| vec![Address::new(from_name, email)])
+                .unwrap_or_default(),
+        ),
+    };
+    let addresses: Vec<Address> = addresses
+        .into_iter()
+        .filter(|address| {
+            address.email.contains('@')
+                && !is_automated(&address.email)
+                && !own_address.is_some_and(|own| own.eq_ignore_ascii_case(&address.email))
+        })
+        .collect();
+    (!addresses.is_empty()).then_some(Correspondence { sent, addresses })
+}
+
+/// Whether an address sends mail nobody replies to, like
+/// `noreply@example.com`
+pub fn is_automated(email: &str) -> bool {
+    let local = email.rsplit_once('@').map_or(email, |(local, _)| local);
+    let local = local.to_lowercase();
+    AUTOMATED_LOCAL_PARTS
+        .iter()
+        .any(|automated| local == *automated || local.starts_with(&format!("{}+", automated)))
+}
+
+/// Key a message is remembered by once counted: a 64-bit FNV-1a hash of
+/// its Message-ID, which stays the same across folders and releases
+pub fn message_key(message_id: &str) -> i64 {
+    let hash = message_id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+    hash as i64
+}
+
+/// Suggestions for the composer as `(name, email)`: up to `limit` of
+/// `recipients` by frecency at `now`, then the `contacts` not among them.
+/// A contact's name wins over one taken from mail.
+pub fn merge_suggestions(
+    mut recipients: Vec<Recipient>,
+    contacts: &[(String, String)],
+    now: i64,
+    limit: usize,
+) -> Vec<(String, String)> {
+    recipients.sort_by(|a, b| {
+        b.frecency(now)
+            .total_cmp(&a.frecency(now))
+            .then_with(|| a.email.cmp(&b.email))
+    });
+    recipients.truncate(limit);
+
+    let contact_name = |email: &str| {
+        contacts
+            .iter()
+            .find(|(name, contact)| !name.is_empty() && contact.eq_ignore_ascii_case(email))
+            .map(|(name, _)| name.clone())
+    };
+    let mut suggestions: Vec<(String, String)> = recipients
+        .into_iter()
+        .map(|recipient| {
+            let name = contact_name(&recipient.email)
+                .or(recipient.name)
+                .unwrap_or_default();
+            (name, recipient.email)
+        })
+        .collect();
+    for (name, email) in contacts {
+        if !suggestions
+            .iter()
+            .any(|(_, suggested)| suggested.eq_ignore_ascii_case(email))
+        {
+            suggestions.push((name.clone(), email.clone()));
+        }
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn recipient(email: &str, sent: i64, received: i64, days_ago: i64) -> Recipient {
+        Recipient {
+            email: email.to_string(),
+            name: None,
+            sent_count: sent,
+            received_count: received,
+            last_used: NOW - days_ago * DAY,
+        }
+    }
+
+    #[test]
+    fn test_frecency() {
+        let colleague = recipient("ana@example.com", 3, 0, 2);
+        let newsletter = recipient("news@example.com", 0, 10, 2);
+        let old_friend = recipient("bo@example.com", 40, 0, 365);
+        assert!(colleague.frecency(NOW) > newsletter.frecency(NOW));
+        assert!(colleague.frecency(NOW) > old_friend.frecency(NOW));
+        // Halves every half-life
+        let score = recipient("x@example.com", 1, 0, 30).frecency(NOW);
+        assert!((score - SENT_WEIGHT / 2.0).abs() < 1e-9);
+        // A date in the future doesn't inflate the score
+        assert_eq!(
+            recipient("x@example.com", 1, 0, -5).frecency(NOW),
+            SENT_WEIGHT
+        );
+    }
+
+    #[test]
+    fn test_correspondence() {
+        let me = Some("me@example.com");
+        let sent = correspondence(
+            "sent",
+            me,
+            me,
+            None,
+            Some("\"Doe, Jane\" <jane@example.com>, noreply@example.com"),
+            Some("bo@example.org, Me@Example.com"),
+        )
+        .unwrap();
+        assert!(sent.sent);
+        assert_eq!(
+            sent.addresses,
+            vec![
+                Address::new(Some("Doe, Jane"), "jane@example.com"),
+                Address::new(None, "bo@example.org"),
+            ]
+        );
+
+        let received = correspondence(
+            "inbox",
+            me,
+            Some("ana@example.com"),
+            Some("Ana"),
+            Some("me@example.com"),
+            None,
+        )
+        .unwrap();
+        assert!(!received.sent);
+        assert_eq!(
+            received.addresses,
+            vec![Address::new(Some("Ana"), "ana@example.com")]
+        );
+
+        assert_eq!(
+            correspondence("spam", me, Some("ana@example.com"), None, None, None),
+            None
+        );
+        assert_eq!(
+            correspondence(
+                "archive",
+                me,
+                Some("no-reply@shop.example"),
+                None,
+                None,
+                None
+            ),
+            None
+        );
+        assert_eq!(correspondence("inbox", me, None, None, None, None), None);
+        // A sent message as Gmail's All Mail lists it
+        assert_eq!(
+            correspondence("archive", me, me, None, Some("ana@example.com"), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_automated() {
+        assert!(is_automated("noreply@example.com"));
+        assert!(is_automated("No-Reply@example.com"));
+        assert!(is_automated("bounces+1234@lists.example.com"));
+        assert!(!is_automated("noreen@example.com"));
+        assert!(!is_automated("ana@noreply.example.com"));
+    }
+
+    #[test]
+    fn test_message_key() {
+        assert_eq!(
+            message_key("<abc@example.com>"),
+            message_key("abc@example.com")
+        );
+        assert_ne!(
+            message_key("<abc@example.com>"),
+            message_key("<abd@example.com>")
+        );
+    }
+
+    #[test]
+    fn test_merge_suggestions() {
+        let recipients = vec![
+            recipient("ana@example.com", 1, 0, 300),
+            Recipient {
+                name: Some("Bo".to_string()),
+                ..recipient("bo@example.com", 5, 2, 1)
+            },
+            recipient("cy@example.com", 0, 1, 1),
+        ];
+        let contacts = vec![
+            ("Ana Lima".to_string(), "Ana@Example.com".to_string()),
+            ("Dee".to_string(), "dee@example.com".to_string()),
+        ];
+        assert_eq!(
+            merge_suggestions(recipients.clone(), &contacts, NOW, 10),
+            vec![
+                ("Bo".to_string(), "bo@example.com".to_string()),
+                (String::new(), "cy@example.com".to_string()),
+                ("Ana Lima".to_string(), "ana@example.com".to_string()),
+                ("Dee".to_string(), "dee@example.com".to_string()),
+            ]
+        );
+        // Contacts still follow when recipients are cut short
+        assert_eq!(
+            merge_suggestions(recipients, &contacts, NOW, 1),
+            vec![
+                ("Bo".to_string(), "bo@example.com".to_string()),
+                ("Ana Lima".to_string(), "Ana@Example.com".to_string()),
+                ("Dee".to_string(), "dee@example.com".to_string()),
+            ]
+        );
+    }
+}
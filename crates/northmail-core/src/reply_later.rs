@@ -0,0 +1,101 @@
+//! Reply later: coming back to a message with a reply already started
+//!
+//! Unlike snoozing, marking a message to reply to later leaves it in its
+//! folder. The cache records when the reply is due (`reply_later_at`, Unix
+//! seconds) and the Reply Later list shows every marked message. Once the
+//! time passes the sync engine clears it and reports the message, and a
+//! draft addressed to its sender is saved for the user to finish.
+
+use chrono::{DateTime, TimeZone, Timelike};
+
+use crate::snooze::{hour_after, SnoozePreset};
+
+/// Hour of the day "end of day" means
+const END_OF_DAY_HOUR: u32 = 17;
+
+/// Times offered when marking a message to reply to later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyLaterPreset {
+    /// 17:00 today, or tomorrow if that has passed
+    EndOfDay,
+    /// Tomorrow morning
+    Tomorrow,
+    /// Monday morning
+    NextWeek,
+}
+
+impl ReplyLaterPreset {
+    pub const ALL: [ReplyLaterPreset; 3] = [
+        ReplyLaterPreset::EndOfDay,
+        ReplyLaterPreset::Tomorrow,
+        ReplyLaterPreset::NextWeek,
+    ];
+
+    /// When the reply to a message marked at `now` is due, in Unix seconds,
+    /// in `now`'s time zone
+    pub fn at<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> i64 {
+        match self {
+            ReplyLaterPreset::EndOfDay => {
+                let days = if now.hour() < END_OF_DAY_HOUR { 0 } else { 1 };
+                hour_after(now, days, END_OF_DAY_HOUR)
+            }
+            ReplyLaterPreset::Tomorrow => SnoozePreset::Tomorrow.until(now),
+            ReplyLaterPreset::NextWeek => SnoozePreset::NextWeek.until(now),
+        }
+    }
+}
+
+/// Subject of a reply to a message with `subject`, adding "Re: " unless it
+/// is already there
+pub fn reply_subject(subject: &str) -> String {
+    let subject = subject.trim();
+    let is_reply = subject
+        .get(..3)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:"));
+    if is_reply {
+        subject.to_string()
+    } else {
+        format!("Re: {}", subject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn test_end_of_day() {
+        let now = at("2024-03-06T10:30:00+01:00");
+        assert_eq!(
+            ReplyLaterPreset::EndOfDay.at(&now),
+            at("2024-03-06T17:00:00+01:00").timestamp()
+        );
+        // Past 17:00 the reply is due the next evening
+        let now = at("2024-03-06T17:05:00+01:00");
+        assert_eq!(
+            ReplyLaterPreset::EndOfDay.at(&now),
+            at("2024-03-07T17:00:00+01:00").timestamp()
+        );
+    }
+
+    #[test]
+    fn test_tomorrow() {
+        let now = at("2024-03-06T23:45:00-05:00");
+        assert_eq!(
+            ReplyLaterPreset::Tomorrow.at(&now),
+            at("2024-03-07T08:00:00-05:00").timestamp()
+        );
+    }
+
+    #[test]
+    fn test_reply_subject() {
+        assert_eq!(reply_subject("Lunch?"), "Re: Lunch?");
+        assert_eq!(reply_subject("RE: Lunch?"), "RE: Lunch?");
+        assert_eq!(reply_subject(""), "Re: ");
+    }
+}
@@ -0,0 +1,116 @@
+//! Cache retention: how much of an account's mail stays on this computer
+//!
+//! Headers are always kept, so folder lists stay complete. What an account
+//! can limit is the message bodies: those older than a number of days are
+//! dropped, and when the account's cache is over its size limit the oldest
+//! bodies go first until it fits. A dropped body is fetched from the server
+//! again when the message is opened; until then search only finds the
+//! message by its headers.
+
+/// Day counts offered for keeping message bodies; `None` keeps them forever
+pub const BODY_RETENTION_DAYS: [Option<u32>; 5] = [None, Some(30), Some(90), Some(180), Some(365)];
+
+/// Cache size limits offered, in bytes; `None` leaves the cache unlimited
+pub const CACHE_LIMITS: [Option<u64>; 5] = [
+    None,
+    Some(250_000_000),
+    Some(1_000_000_000),
+    Some(5_000_000_000),
+    Some(10_000_000_000),
+];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// An account's retention settings. The default keeps everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Days a message body is kept after the message's date
+    pub body_days: Option<u32>,
+    /// Most bytes the account's cached mail may take
+    pub max_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy ever drops anything
+    pub fn is_limited(&self) -> bool {
+        self.body_days.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Date (Unix seconds) before which message bodies are dropped at `now`
+    pub fn body_cutoff(&self, now: i64) -> Option<i64> {
+        self.body_days
+            .map(|days| now.saturating_sub(i64::from(days) * SECONDS_PER_DAY))
+    }
+
+    /// Bytes left for bodies once `header_bytes` of headers are counted
+    /// against the size limit
+    pub fn body_budget(&self, header_bytes: u64) -> Option<u64> {
+        self.max_bytes.map(|max| max.saturating_sub(header_bytes))
+    }
+}
+
+/// Cached mail of one folder
+#[derive(Debug, Clone, Default)]
+pub struct FolderCacheSize {
+    pub folder_id: i64,
+    pub account_id: String,
+    pub full_path: String,
+    pub messages: i64,
+    /// Messages with a cached body
+    pub bodies: i64,
+    /// Bytes of cached header fields
+    pub header_bytes: u64,
+    /// Bytes of cached bodies
+    pub body_bytes: u64,
+}
+
+impl FolderCacheSize {
+    /// Bytes the folder's mail takes, not counting indexes
+    pub fn total_bytes(&self) -> u64 {
+        self.header_bytes + self.body_bytes
+    }
+}
+
+/// What a pruning pass dropped from one account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Bodies dropped for being older than the retention period
+    pub expired_bodies: u64,
+    /// Bodies dropped to bring the cache under its size limit
+    pub evicted_bodies: u64,
+}
+
+impl PruneReport {
+    pub fn total(&self) -> u64 {
+        self.expired_bodies + self.evicted_bodies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_cutoff() {
+        let now = 1_700_000_000;
+        let policy = RetentionPolicy {
+            body_days: Some(90),
+            max_bytes: None,
+        };
+        assert_eq!(policy.body_cutoff(now), Some(now - 90 * 86_400));
+        assert_eq!(RetentionPolicy::default().body_cutoff(now), None);
+    }
+
+    #[test]
+    fn test_body_budget() {
+        let policy = RetentionPolicy {
+            body_days: None,
+            max_bytes: Some(1_000),
+        };
+        assert_eq!(policy.body_budget(300), Some(700));
+        // Headers alone over the limit leave nothing for bodies
+        assert_eq!(policy.body_budget(1_500), Some(0));
+        assert!(policy.is_limited());
+        assert!(!RetentionPolicy::default().is_limited());
+    }
+}
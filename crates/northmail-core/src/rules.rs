@@ -0,0 +1,406 @@
+//! Client-side message rules
+//!
+//! A rule matches messages on their sender, subject, recipients or another
+//! header, and lists what to do with them: move, tag, mark read, star,
+//! delete or notify. While NorthMail runs, the sync engine checks messages
+//! newly arriving in an Inbox against the enabled rules, in order, and
+//! carries out the combined actions on the server. Rules are stored in the
+//! cache database with their conditions and actions as JSON.
+
+use serde::{Deserialize, Serialize};
+
+/// What part of a message a condition looks at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "field", content = "name")]
+pub enum RuleField {
+    /// The From header, name and address
+    From,
+    Subject,
+    /// The To and Cc headers
+    Recipients,
+    /// Any header by name, case-insensitive
+    Header(String),
+}
+
+/// How a condition compares the field with its value. Comparisons ignore
+/// case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Contains,
+    /// Matches when no value of the field contains the text, including when
+    /// the message lacks the field
+    NotContains,
+    Is,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Condition {
+    pub field: RuleField,
+    pub kind: MatchKind,
+    pub value: String,
+}
+
+impl Condition {
+    pub fn new(field: RuleField, kind: MatchKind, value: impl Into<String>) -> Self {
+        Self {
+            field,
+            kind,
+            value: value.into(),
+        }
+    }
+
+    fn matches(&self, message: &RuleMessage) -> bool {
+        let needle = self.value.to_lowercase();
+        let mut values = message.field_values(&self.field).map(str::to_lowercase);
+        match self.kind {
+            MatchKind::Contains => values.any(|v| v.contains(&needle)),
+            MatchKind::NotContains => !values.any(|v| v.contains(&needle)),
+            MatchKind::Is => values.any(|v| v.trim() == needle.trim()),
+            MatchKind::StartsWith => values.any(|v| v.starts_with(&needle)),
+            MatchKind::EndsWith => values.any(|v| v.ends_with(&needle)),
+        }
+    }
+}
+
+/// Something a rule does with a matching message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum RuleAction {
+    /// Move to another folder of the same account, by full path
+    Move {
+        folder: String,
+    },
+    /// Add a tag (IMAP keyword, see [`crate::tags`])
+    Label {
+        tag: String,
+    },
+    MarkRead,
+    Star,
+    /// Move to Trash, or delete outright when the account has none
+    Delete,
+    /// Show a desktop notification naming the rule
+    Notify,
+}
+
+/// Whether all or any of a rule's conditions must hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    #[default]
+    All,
+    Any,
+}
+
+impl MatchMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchMode::All => "all",
+            MatchMode::Any => "any",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "any" => MatchMode::Any,
+            _ => MatchMode::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// Database id, 0 for a rule not saved yet
+    pub id: i64,
+    pub name: String,
+    /// Account the rule applies to, or every account
+    pub account_id: Option<String>,
+    pub enabled: bool,
+    pub match_mode: MatchMode,
+    pub conditions: Vec<Condition>,
+    pub actions: Vec<RuleAction>,
+    /// Skip the rules after this one for messages it matches
+    pub stop_processing: bool,
+}
+
+impl Rule {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: 0,
+            name: name.into(),
+            account_id: None,
+            enabled: true,
+            match_mode: MatchMode::All,
+            conditions: Vec::new(),
+            actions: Vec::new(),
+            stop_processing: false,
+        }
+    }
+
+    /// Whether the rule applies to a message of `account_id`. A rule without
+    /// conditions matches nothing, so a half-made rule can't delete mail.
+    pub fn matches(&self, account_id: &str, message: &RuleMessage) -> bool {
+        if !self.enabled || self.conditions.is_empty() {
+            return false;
+        }
+        if self
+            .account_id
+            .as_deref()
+            .is_some_and(|id| id != account_id)
+        {
+            return false;
+        }
+        match self.match_mode {
+            MatchMode::All => self.conditions.iter().all(|c| c.matches(message)),
+            MatchMode::Any => self.conditions.iter().any(|c| c.matches(message)),
+        }
+    }
+}
+
+/// The headers of a message that rules look at
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleMessage {
+    headers: Vec<(String, String)>,
+}
+
+impl RuleMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a header; a header can be added more than once, e.g. one `To`
+    /// per recipient
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn field_values<'a>(&'a self, field: &'a RuleField) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+        match field {
+            RuleField::From => Box::new(self.header_values("From")),
+            RuleField::Subject => Box::new(self.header_values("Subject")),
+            RuleField::Recipients => {
+                Box::new(self.header_values("To").chain(self.header_values("Cc")))
+            }
+            RuleField::Header(name) => Box::new(self.header_values(name)),
+        }
+    }
+}
+
+/// What the matching rules do with one message, combined
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleOutcome {
+    /// Folder from the first matching Move action
+    pub move_to: Option<String>,
+    /// Delete wins over moving
+    pub delete: bool,
+    pub labels: Vec<String>,
+    pub mark_read: bool,
+    pub star: bool,
+    /// Names of the matching rules that notify
+    pub notify: Vec<String>,
+}
+
+impl RuleOutcome {
+    pub fn is_empty(&self) -> bool {
+        *self == RuleOutcome::default()
+    }
+}
+
+/// Run `rules` in order on a message of `account_id`
+pub fn evaluate(rules: &[Rule], account_id: &str, message: &RuleMessage) -> RuleOutcome {
+    let mut outcome = RuleOutcome::default();
+    for rule in rules.iter().filter(|r| r.matches(account_id, message)) {
+        for action in &rule.actions {
+            match action {
+                RuleAction::Move { folder } => {
+                    if outcome.move_to.is_none() {
+                        outcome.move_to = Some(folder.clone());
+                    }
+                }
+                RuleAction::Label { tag } => {
+                    if !outcome.labels.iter().any(|l| l.eq_ignore_ascii_case(tag)) {
+                        outcome.labels.push(tag.clone());
+                    }
+                }
+                RuleAction::MarkRead => outcome.mark_read = true,
+                RuleAction::Star => outcome.star = true,
+                RuleAction::Delete => outcome.delete = true,
+                RuleAction::Notify => {
+                    if !outcome.notify.contains(&rule.name) {
+                        outcome.notify.push(rule.name.clone());
+                    }
+                }
+            }
+        }
+        if rule.stop_processing {
+            break;
+        }
+    }
+    if outcome.delete {
+        outcome.move_to = None;
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> RuleMessage {
+        RuleMessage::new()
+            .header("From", "GitHub <notifications@github.com>")
+            .header("To", "me@example.com")
+            .header("Cc", "Team <team@example.com>")
+            .header("Subject", "[repo] Build failed")
+            .header("List-Id", "repo.github.com")
+    }
+
+    fn rule(conditions: Vec<Condition>, actions: Vec<RuleAction>) -> Rule {
+        Rule {
+            conditions,
+            actions,
+            ..Rule::new("test")
+        }
+    }
+
+    #[test]
+    fn test_match_kinds() {
+        let msg = message();
+        let check = |field, kind, value| Condition::new(field, kind, value).matches(&msg);
+        assert!(check(RuleField::From, MatchKind::Contains, "GITHUB.com"));
+        assert!(check(RuleField::Subject, MatchKind::StartsWith, "[repo]"));
+        assert!(check(RuleField::Subject, MatchKind::EndsWith, "failed"));
+        assert!(check(RuleField::Recipients, MatchKind::Contains, "team@"));
+        assert!(check(
+            RuleField::Header("list-id".into()),
+            MatchKind::Is,
+            "repo.github.com"
+        ));
+        assert!(!check(RuleField::Subject, MatchKind::Is, "build failed"));
+        assert!(check(RuleField::Subject, MatchKind::NotContains, "passed"));
+        assert!(check(
+            RuleField::Header("X-Spam".into()),
+            MatchKind::NotContains,
+            "yes"
+        ));
+        assert!(!check(
+            RuleField::Header("X-Spam".into()),
+            MatchKind::Contains,
+            ""
+        ));
+    }
+
+    #[test]
+    fn test_all_and_any() {
+        let conditions = vec![
+            Condition::new(RuleField::From, MatchKind::Contains, "github"),
+            Condition::new(RuleField::Subject, MatchKind::Contains, "passed"),
+        ];
+        let mut r = rule(conditions, vec![RuleAction::MarkRead]);
+        assert!(!r.matches("acc", &message()));
+        r.match_mode = MatchMode::Any;
+        assert!(r.matches("acc", &message()));
+    }
+
+    #[test]
+    fn test_rule_scope() {
+        let mut r = rule(
+            vec![Condition::new(
+                RuleField::From,
+                MatchKind::Contains,
+                "github",
+            )],
+            vec![RuleAction::Star],
+        );
+        r.account_id = Some("work".into());
+        assert!(r.matches("work", &message()));
+        assert!(!r.matches("home", &message()));
+        r.enabled = false;
+        assert!(!r.matches("work", &message()));
+        assert!(!rule(vec![], vec![RuleAction::Delete]).matches("work", &message()));
+    }
+
+    #[test]
+    fn test_evaluate_combines_actions() {
+        let github = Condition::new(RuleField::From, MatchKind::Contains, "github");
+        let rules = vec![
+            rule(
+                vec![github.clone()],
+                vec![
+                    RuleAction::Move {
+                        folder: "GitHub".into(),
+                    },
+                    RuleAction::Label { tag: "ci".into() },
+                ],
+            ),
+            rule(
+                vec![github.clone()],
+                vec![
+                    RuleAction::Move {
+                        folder: "Other".into(),
+                    },
+                    RuleAction::Label { tag: "CI".into() },
+                    RuleAction::MarkRead,
+                    RuleAction::Notify,
+                ],
+            ),
+        ];
+        let outcome = evaluate(&rules, "acc", &message());
+        assert_eq!(outcome.move_to.as_deref(), Some("GitHub"));
+        assert_eq!(outcome.labels, ["ci"]);
+        assert!(outcome.mark_read);
+        assert!(!outcome.star);
+        assert_eq!(outcome.notify, ["test"]);
+    }
+
+    #[test]
+    fn test_evaluate_stop_and_delete() {
+        let github = Condition::new(RuleField::From, MatchKind::Contains, "github");
+        let mut first = rule(
+            vec![github.clone()],
+            vec![RuleAction::Move {
+                folder: "GitHub".into(),
+            }],
+        );
+        let second = rule(vec![github], vec![RuleAction::Delete]);
+        let outcome = evaluate(&[first.clone(), second.clone()], "acc", &message());
+        assert!(outcome.delete);
+        assert_eq!(outcome.move_to, None);
+
+        first.stop_processing = true;
+        let outcome = evaluate(&[first, second], "acc", &message());
+        assert!(!outcome.delete);
+        assert_eq!(outcome.move_to.as_deref(), Some("GitHub"));
+        assert!(evaluate(&[], "acc", &message()).is_empty());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let conditions = vec![Condition::new(
+            RuleField::Header("List-Id".into()),
+            MatchKind::Contains,
+            "rust",
+        )];
+        let actions = vec![RuleAction::Label { tag: "rust".into() }, RuleAction::Notify];
+        let json = serde_json::to_string(&conditions).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<Condition>>(&json).unwrap(),
+            conditions
+        );
+        let json = serde_json::to_string(&actions).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<RuleAction>>(&json).unwrap(),
+            actions
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Shared async runtimes
+//!
+//! Work that needs tokio (sqlx queries, HTTP, IMAP) runs on one of two
+//! long-lived runtimes instead of on a runtime built for each call: cache
+//! work through [`spawn_db`] and network work through [`spawn_io`], so a
+//! slow server never holds up a cache read. Both return a [`Task`], a
+//! future any executor can await (the GTK main loop included) without
+//! polling a channel.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use thiserror::Error;
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::JoinHandle;
+
+/// Worker threads for cache work; SQLite serialises writes anyway
+const DB_WORKERS: usize = 2;
+
+static DB_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+static IO_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Why a [`Task`] finished without an output
+#[derive(Debug, Error)]
+#[error("Background task failed: {0}")]
+pub struct TaskError(String);
+
+/// Work running on a shared runtime; await it for the output
+pub struct Task<T> {
+    handle: JoinHandle<T>,
+}
+
+impl<T> Future for Task<T> {
+    type Output = Result<T, TaskError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle)
+            .poll(cx)
+            .map_err(|e| TaskError(e.to_string()))
+    }
+}
+
+fn db_runtime() -> &'static Runtime {
+    DB_RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .worker_threads(DB_WORKERS)
+            .thread_name("northmail-db")
+            .enable_all()
+            .build()
+            .expect("Failed to start database runtime")
+    })
+}
+
+fn io_runtime() -> &'static Runtime {
+    IO_RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .thread_name("northmail-io")
+            .enable_all()
+            .build()
+            .expect("Failed to start network runtime")
+    })
+}
+
+/// Run cache work (database queries, local files) on the shared database
+/// runtime
+pub fn spawn_db<F>(future: F) -> Task<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Task {
+        handle: db_runtime().spawn(future),
+    }
+}
+
+/// Run network work (IMAP, SMTP, HTTP APIs) on the shared network runtime
+pub fn spawn_io<F>(future: F) -> Task<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Task {
+        handle: io_runtime().spawn(future),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasks_resolve() {
+        let db = spawn_db(async { 2 + 2 });
+        let io = spawn_io(async {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            "done"
+        });
+        assert_eq!(futures::executor::block_on(db).unwrap(), 4);
+        assert_eq!(futures::executor::block_on(io).unwrap(), "done");
+    }
+
+    #[test]
+    fn test_panic_is_an_error() {
+        let task = spawn_db(async {
+            if true {
+                panic!("boom");
+            }
+        });
+        assert!(futures::executor::block_on(task).is_err());
+    }
+}
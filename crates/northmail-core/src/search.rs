@@ -0,0 +1,702 @@
+//! Search query language
+//!
+//! What's typed in the search bar is parsed into a [`SearchQuery`]: words
+//! and "quoted phrases" found anywhere in a message, the operators
+//! `from:`, `to:`, `subject:`, `has:attachment`, `is:unread` (and
+//! `is:read`, `is:starred`), `before:` and `after:` with a date
+//! (`2024-03-01` or `2024/03/01`), and `AND`, `OR`, `NOT` or `-` with
+//! parentheses. Terms side by side must all match, as if joined by `AND`.
+//!
+//! Parsing never fails, since the query is searched for as it's typed: an
+//! unclosed parenthesis or quote closes at the end, a date that doesn't
+//! parse is a word, and so is an operator the parser doesn't know
+//! (`label:work`), which Gmail still gets to understand.
+//!
+//! A query compiles to a condition on the cache ([`SearchQuery::to_sql`],
+//! matching words against the full-text index), to IMAP SEARCH criteria
+//! ([`SearchQuery::to_imap`]) and to Gmail's own syntax for X-GM-RAW
+//! ([`SearchQuery::to_gmail_raw`]), so a search finds the same messages
+//! wherever it runs. IMAP has no test for attachments; there
+//! `has:attachment` looks for `multipart/mixed` messages.
+
+use chrono::{FixedOffset, NaiveDate, TimeZone};
+
+/// A search term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    /// A word, matching words starting with it
+    Word(String),
+    /// Words next to each other, in order
+    Phrase(String),
+    From(String),
+    To(String),
+    Subject(String),
+    HasAttachment,
+    Unread,
+    Read,
+    Starred,
+    /// Sent before the day
+    Before(NaiveDate),
+    /// Sent on the day or later
+    After(NaiveDate),
+}
+
+/// A parsed search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchQuery {
+    Term(Term),
+    Not(Box<SearchQuery>),
+    And(Vec<SearchQuery>),
+    Or(Vec<SearchQuery>),
+}
+
+/// A value bound to a compiled SQL condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlParam {
+    Text(String),
+    Int(i64),
+}
+
+/// A query compiled to a condition on `messages m`, with the values bound
+/// to its `?` placeholders in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlFilter {
+    pub condition: String,
+    pub params: Vec<SqlParam>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    And,
+    Or,
+    Text {
+        field: Option<String>,
+        value: String,
+        quoted: bool,
+    },
+}
+
+impl SearchQuery {
+    /// Parse `text`; `None` when there's nothing to search for
+    pub fn parse(text: &str) -> Option<SearchQuery> {
+        let tokens = tokenize(text);
+        let mut parser = Parser { tokens, pos: 0 };
+        let mut parts = Vec::new();
+        while parser.pos < parser.tokens.len() {
+            if let Some(query) = parser.or() {
+                parts.push(query);
+            }
+            // A stray closing parenthesis
+            if parser.peek() == Some(&Token::Close) {
+                parser.pos += 1;
+            }
+        }
+        combine(parts, SearchQuery::And)
+    }
+
+    /// Whether the query is only words, which the plain full-text search
+    /// handles on its own
+    pub fn is_plain_words(&self) -> bool {
+        match self {
+            SearchQuery::Term(Term::Word(_)) => true,
+            SearchQuery::And(parts) => parts.iter().all(SearchQuery::is_plain_words),
+            _ => false,
+        }
+    }
+
+    /// Condition on the cached message `m` selecting what the query
+    /// matches. Dates are days in the time zone `offset`.
+    pub fn to_sql(&self, offset: FixedOffset) -> SqlFilter {
+        let mut params = Vec::new();
+        let condition = self.sql(offset, &mut params);
+        SqlFilter { condition, params }
+    }
+
+    fn sql(&self, offset: FixedOffset, params: &mut Vec<SqlParam>) -> String {
+        match self {
+            SearchQuery::Term(term) => term.sql(offset, params),
+            SearchQuery::Not(query) => format!("NOT {}", query.sql(offset, params)),
+            SearchQuery::And(parts) => join(parts.iter().map(|p| p.sql(offset, params)), " AND "),
+            SearchQuery::Or(parts) => join(parts.iter().map(|p| p.sql(offset, params)), " OR "),
+        }
+    }
+
+    /// IMAP SEARCH criteria for the query, declaring UTF-8 when it needs it
+    pub fn to_imap(&self) -> String {
+        let criteria = match self {
+            // Keys side by side are ANDed; no parentheses needed at the top
+            SearchQuery::And(parts) => parts
+                .iter()
+                .map(SearchQuery::imap)
+                .collect::<Vec<_>>()
+                .join(" "),
+            query => query.imap(),
+        };
+        if criteria.is_ascii() {
+            criteria
+        } else {
+            format!("CHARSET UTF-8 {}", criteria)
+        }
+    }
+
+    fn imap(&self) -> String {
+        match self {
+            SearchQuery::Term(term) => term.imap(),
+            SearchQuery::Not(query) => format!("NOT {}", query.imap()),
+            SearchQuery::And(parts) => format!(
+                "({})",
+                parts
+                    .iter()
+                    .map(SearchQuery::imap)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            // OR takes two keys: OR a OR b c
+            SearchQuery::Or(parts) => {
+                let mut keys = parts.iter().rev().map(SearchQuery::imap);
+                let last = keys.next().unwrap_or_default();
+                keys.fold(last, |rest, key| format!("OR {} {}", key, rest))
+            }
+        }
+    }
+
+    /// The query in Gmail's search syntax, for X-GM-RAW
+    pub fn to_gmail_raw(&self) -> String {
+        match self {
+            SearchQuery::And(parts) => parts
+                .iter()
+                .map(SearchQuery::gmail_raw)
+                .collect::<Vec<_>>()
+                .join(" "),
+            query => query.gmail_raw(),
+        }
+    }
+
+    fn gmail_raw(&self) -> String {
+        match self {
+            SearchQuery::Term(term) => term.gmail_raw(),
+            SearchQuery::Not(query) => format!("-{}", query.gmail_raw()),
+            SearchQuery::And(parts) => format!(
+                "({})",
+                parts
+                    .iter()
+                    .map(SearchQuery::gmail_raw)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            SearchQuery::Or(parts) => format!(
+                "({})",
+                parts
+                    .iter()
+                    .map(SearchQuery::gmail_raw)
+                    .collect::<Vec<_>>()
+                    .join(" OR ")
+            ),
+        }
+    }
+}
+
+impl Term {
+    fn sql(&self, offset: FixedOffset, params: &mut Vec<SqlParam>) -> String {
+        let mut fts = |expression: String| {
+            if expression.is_empty() {
+                return "1".to_string();
+            }
+            params.push(SqlParam::Text(expression));
+            "m.id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?)".to_string()
+        };
+        match self {
+            Term::Word(word) => fts(fts_tokens(word)
+                .iter()
+                .map(|token| format!("\"{}\"*", token))
+                .collect::<Vec<_>>()
+                .join(" ")),
+            Term::Phrase(phrase) => fts(fts_phrase(phrase, false)),
+            Term::From(value) => fts(fts_column("{from_address from_name}", value)),
+            Term::To(value) => fts(fts_column("to_addresses", value)),
+            Term::Subject(value) => fts(fts_column("subject", value)),
+            Term::HasAttachment => "m.has_attachments = 1".to_string(),
+            Term::Unread => "m.is_read = 0".to_string(),
+            Term::Read => "m.is_read = 1".to_string(),
+            Term::Starred => "m.is_starred = 1".to_string(),
+            Term::Before(date) => {
+                params.push(SqlParam::Int(start_of_day(*date, offset)));
+                "m.date_epoch < ?".to_string()
+            }
+            Term::After(date) => {
+                params.push(SqlParam::Int(start_of_day(*date, offset)));
+                "m.date_epoch >= ?".to_string()
+            }
+        }
+    }
+
+    fn imap(&self) -> String {
+        match self {
+            Term::Word(text) | Term::Phrase(text) => format!("TEXT {}", imap_quoted(text)),
+            Term::From(value) => format!("FROM {}", imap_quoted(value)),
+            Term::To(value) => format!("TO {}", imap_quoted(value)),
+            Term::Subject(value) => format!("SUBJECT {}", imap_quoted(value)),
+            Term::HasAttachment => "HEADER Content-Type \"multipart/mixed\"".to_string(),
+            Term::Unread => "UNSEEN".to_string(),
+            Term::Read => "SEEN".to_string(),
+            Term::Starred => "FLAGGED".to_string(),
+            Term::Before(date) => format!("SENTBEFORE {}", date.format("%-d-%b-%Y")),
+            Term::After(date) => format!("SENTSINCE {}", date.format("%-d-%b-%Y")),
+        }
+    }
+
+    fn gmail_raw(&self) -> String {
+        match self {
+            // Gmail's own operators pass through as they were typed
+            Term::Word(word) => word.clone(),
+            Term::Phrase(phrase) => format!("\"{}\"", phrase.replace('"', "")),
+            Term::From(value) => format!("from:{}", gmail_value(value)),
+            Term::To(value) => format!("to:{}", gmail_value(value)),
+            Term::Subject(value) => format!("subject:{}", gmail_value(value)),
+            Term::HasAttachment => "has:attachment".to_string(),
+            Term::Unread => "is:unread".to_string(),
+            Term::Read => "is:read".to_string(),
+            Term::Starred => "is:starred".to_string(),
+            Term::Before(date) => format!("before:{}", date.format("%Y/%m/%d")),
+            Term::After(date) => format!("after:{}", date.format("%Y/%m/%d")),
+        }
+    }
+
+    /// The term for `field:value`; unknown operators and values that don't
+    /// make sense are searched for as typed
+    fn operator(field: &str, value: String, quoted: bool) -> Term {
+        let date = || {
+            NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .or_else(|_| NaiveDate::parse_from_str(&value, "%Y/%m/%d"))
+                .ok()
+        };
+        let term = match field.to_lowercase().as_str() {
+            _ if value.is_empty() => None,
+            "from" => Some(Term::From(value.clone())),
+            "to" => Some(Term::To(value.clone())),
+            "subject" => Some(Term::Subject(value.clone())),
+            "has" if matches!(value.to_lowercase().as_str(), "attachment" | "attachments") => {
+                Some(Term::HasAttachment)
+            }
+            "is" => match value.to_lowercase().as_str() {
+                "unread" => Some(Term::Unread),
+                "read" => Some(Term::Read),
+                "starred" | "flagged" => Some(Term::Starred),
+                _ => None,
+            },
+            "before" => date().map(Term::Before),
+            "after" => date().map(Term::After),
+            _ => None,
+        };
+        term.unwrap_or_else(|| {
+            if quoted {
+                Term::Word(format!("{}:\"{}\"", field, value))
+            } else {
+                Term::Word(format!("{}:{}", field, value))
+            }
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// `and (OR and)*`
+    fn or(&mut self) -> Option<SearchQuery> {
+        let mut parts: Vec<SearchQuery> = self.and().into_iter().collect();
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            parts.extend(self.and());
+        }
+        combine(parts, SearchQuery::Or)
+    }
+
+    /// `unary ([AND] unary)*`, up to an `OR` or closing parenthesis
+    fn and(&mut self) -> Option<SearchQuery> {
+        let mut parts = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) | Some(Token::Or) => break,
+                Some(Token::And) => self.pos += 1,
+                _ => parts.extend(self.unary()),
+            }
+        }
+        combine(parts, SearchQuery::And)
+    }
+
+    /// `NOT unary`, `( or )` or a term
+    fn unary(&mut self) -> Option<SearchQuery> {
+        let token = self.tokens.get(self.pos).cloned()?;
+        self.pos += 1;
+        match token {
+            Token::Not => match self.peek() {
+                None | Some(Token::Close) | Some(Token::Or) | Some(Token::And) => None,
+                _ => self.unary().map(|query| SearchQuery::Not(Box::new(query))),
+            },
+            Token::Open => {
+                let query = self.or();
+                if self.peek() == Some(&Token::Close) {
+                    self.pos += 1;
+                }
+                query
+            }
+            Token::Text {
+                field,
+                value,
+                quoted,
+            } => {
+                let term = match field {
+                    Some(field) => Term::operator(&field, value, quoted),
+                    None if quoted => Term::Phrase(value),
+                    None => Term::Word(value),
+                };
+                match &term {
+                    Term::Phrase(text) | Term::Word(text) if text.trim().is_empty() => None,
+                    _ => Some(SearchQuery::Term(term)),
+                }
+            }
+            Token::Close | Token::And | Token::Or => None,
+        }
+    }
+}
+
+/// `parts` as one query: nothing, the only part, or `join` of them all
+fn combine(
+    mut parts: Vec<SearchQuery>,
+    join: fn(Vec<SearchQuery>) -> SearchQuery,
+) -> Option<SearchQuery> {
+    match parts.len() {
+        0 => None,
+        1 => parts.pop(),
+        _ => Some(join(parts)),
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let quoted = |i: &mut usize| {
+        // Up to the closing quote, or the end
+        let start = *i + 1;
+        let end = chars[start..]
+            .iter()
+            .position(|&c| c == '"')
+            .map_or(chars.len(), |p| start + p);
+        *i = end + 1;
+        chars[start..end].iter().collect::<String>()
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::Open);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::Close);
+            i += 1;
+        } else if c == '-' && chars.get(i + 1).is_some_and(|next| !next.is_whitespace()) {
+            tokens.push(Token::Not);
+            i += 1;
+        } else if c == '"' {
+            let value = quoted(&mut i);
+            tokens.push(Token::Text {
+                field: None,
+                value,
+                quoted: true,
+            });
+        } else {
+            let start = i;
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && !matches!(chars[i], '(' | ')' | '"')
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            match word.as_str() {
+                "AND" => tokens.push(Token::And),
+                "OR" => tokens.push(Token::Or),
+                "NOT" => tokens.push(Token::Not),
+                _ => match word.split_once(':') {
+                    // from:"Ana Lima"
+                    Some((field, "")) if chars.get(i) == Some(&'"') && !field.is_empty() => {
+                        let value = quoted(&mut i);
+                        tokens.push(Token::Text {
+                            field: Some(field.to_string()),
+                            value,
+                            quoted: true,
+                        });
+                    }
+                    Some((field, value)) if !field.is_empty() && !value.is_empty() => {
+                        tokens.push(Token::Text {
+                            field: Some(field.to_string()),
+                            value: value.to_string(),
+                            quoted: false,
+                        });
+                    }
+                    _ => tokens.push(Token::Text {
+                        field: None,
+                        value: word,
+                        quoted: false,
+                    }),
+                },
+            }
+        }
+    }
+    tokens
+}
+
+fn join(parts: impl Iterator<Item = String>, separator: &str) -> String {
+    format!("({})", parts.collect::<Vec<_>>().join(separator))
+}
+
+/// The words of `text` as the full-text index splits them
+fn fts_tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `text` as an FTS5 phrase, its last word matched as a prefix if `prefix`
+fn fts_phrase(text: &str, prefix: bool) -> String {
+    let tokens = fts_tokens(text);
+    if tokens.is_empty() {
+        return String::new();
+    }
+    format!("\"{}\"{}", tokens.join(" "), if prefix { "*" } else { "" })
+}
+
+/// `value` matched as a phrase in the `columns` of the full-text index
+fn fts_column(columns: &str, value: &str) -> String {
+    let phrase = fts_phrase(value, true);
+    if phrase.is_empty() {
+        return phrase;
+    }
+    format!("{} : {}", columns, phrase)
+}
+
+/// Unix time at the start of `date` in the time zone `offset`
+fn start_of_day(date: NaiveDate, offset: FixedOffset) -> i64 {
+    offset
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .single()
+        .map_or(0, |start| start.timestamp())
+}
+
+fn imap_quoted(text: &str) -> String {
+    let text = text.replace(['\r', '\n'], " ");
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A Gmail operator value, quoted when it has spaces
+fn gmail_value(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value.replace('"', ""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> SearchQuery {
+        SearchQuery::Term(Term::Word(text.to_string()))
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse_terms() {
+        assert_eq!(
+            SearchQuery::parse(r#"from:ana subject:"q3 plan" has:attachment is:unread"#),
+            Some(SearchQuery::And(vec![
+                SearchQuery::Term(Term::From("ana".to_string())),
+                SearchQuery::Term(Term::Subject("q3 plan".to_string())),
+                SearchQuery::Term(Term::HasAttachment),
+                SearchQuery::Term(Term::Unread),
+            ]))
+        );
+        assert_eq!(
+            SearchQuery::parse("before:2024-03-01 after:2024/01/15"),
+            Some(SearchQuery::And(vec![
+                SearchQuery::Term(Term::Before(date(2024, 3, 1))),
+                SearchQuery::Term(Term::After(date(2024, 1, 15))),
+            ]))
+        );
+        assert_eq!(
+            SearchQuery::parse("\"budget review\""),
+            Some(SearchQuery::Term(Term::Phrase("budget review".to_string())))
+        );
+        assert_eq!(SearchQuery::parse("   "), None);
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        assert_eq!(
+            SearchQuery::parse("invoice OR receipt -spam"),
+            Some(SearchQuery::Or(vec![
+                word("invoice"),
+                SearchQuery::And(vec![
+                    word("receipt"),
+                    SearchQuery::Not(Box::new(word("spam")))
+                ]),
+            ]))
+        );
+        assert_eq!(
+            SearchQuery::parse("(a OR b) AND NOT c"),
+            Some(SearchQuery::And(vec![
+                SearchQuery::Or(vec![word("a"), word("b")]),
+                SearchQuery::Not(Box::new(word("c"))),
+            ]))
+        );
+        // Lowercase "or" is a word
+        assert_eq!(
+            SearchQuery::parse("this or that"),
+            Some(SearchQuery::And(vec![
+                word("this"),
+                word("or"),
+                word("that")
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_is_lenient() {
+        // Unclosed parenthesis and quote, stray closing parenthesis
+        assert_eq!(
+            SearchQuery::parse("(a OR \"b c"),
+            Some(SearchQuery::Or(vec![
+                word("a"),
+                SearchQuery::Term(Term::Phrase("b c".to_string()))
+            ]))
+        );
+        assert_eq!(
+            SearchQuery::parse("a) b"),
+            Some(SearchQuery::And(vec![word("a"), word("b")]))
+        );
+        // Unknown operators, bad dates and dangling operators
+        assert_eq!(
+            SearchQuery::parse("label:work before:yesterday re: - OR"),
+            Some(SearchQuery::And(vec![
+                word("label:work"),
+                word("before:yesterday"),
+                word("re:"),
+                word("-"),
+            ]))
+        );
+        assert_eq!(SearchQuery::parse("OR AND"), None);
+    }
+
+    #[test]
+    fn test_is_plain_words() {
+        assert!(SearchQuery::parse("meeting notes")
+            .unwrap()
+            .is_plain_words());
+        assert!(!SearchQuery::parse("from:ana").unwrap().is_plain_words());
+        assert!(!SearchQuery::parse("\"meeting notes\"")
+            .unwrap()
+            .is_plain_words());
+        assert!(!SearchQuery::parse("a OR b").unwrap().is_plain_words());
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let filter = SearchQuery::parse("from:ana@example.com -is:read after:2024-01-01")
+            .unwrap()
+            .to_sql(utc);
+        assert_eq!(
+            filter.condition,
+            "(m.id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?) \
+             AND NOT m.is_read = 1 AND m.date_epoch >= ?)"
+        );
+        assert_eq!(
+            filter.params,
+            vec![
+                SqlParam::Text("{from_address from_name} : \"ana example com\"*".to_string()),
+                SqlParam::Int(1_704_067_200),
+            ]
+        );
+
+        let filter = SearchQuery::parse("\"q3 plan\" OR subject:budget")
+            .unwrap()
+            .to_sql(utc);
+        assert_eq!(
+            filter.params,
+            vec![
+                SqlParam::Text("\"q3 plan\"".to_string()),
+                SqlParam::Text("subject : \"budget\"*".to_string()),
+            ]
+        );
+
+        // Dates are days where the user is
+        let berlin = FixedOffset::east_opt(3600).unwrap();
+        let filter = SearchQuery::parse("before:2024-01-01")
+            .unwrap()
+            .to_sql(berlin);
+        assert_eq!(filter.params, vec![SqlParam::Int(1_704_067_200 - 3600)]);
+
+        // Nothing left to look for in a word of punctuation
+        assert_eq!(
+            SearchQuery::parse("\"--\"").unwrap().to_sql(utc).condition,
+            "1"
+        );
+    }
+
+    #[test]
+    fn test_to_imap() {
+        assert_eq!(
+            SearchQuery::parse("from:ana is:unread before:2024-03-01")
+                .unwrap()
+                .to_imap(),
+            "FROM \"ana\" UNSEEN SENTBEFORE 1-Mar-2024"
+        );
+        assert_eq!(
+            SearchQuery::parse("a OR b OR -c").unwrap().to_imap(),
+            "OR TEXT \"a\" OR TEXT \"b\" NOT TEXT \"c\""
+        );
+        assert_eq!(
+            SearchQuery::parse(r#"(a b) OR subject:"C:\temp""#)
+                .unwrap()
+                .to_imap(),
+            r#"OR (TEXT "a" TEXT "b") SUBJECT "C:\\temp""#
+        );
+        assert_eq!(
+            SearchQuery::parse("from:zoë").unwrap().to_imap(),
+            "CHARSET UTF-8 FROM \"zoë\""
+        );
+    }
+
+    #[test]
+    fn test_to_gmail_raw() {
+        assert_eq!(
+            SearchQuery::parse("from:\"Ana Lima\" has:attachment after:2024-01-15 label:work")
+                .unwrap()
+                .to_gmail_raw(),
+            "from:\"Ana Lima\" has:attachment after:2024/01/15 label:work"
+        );
+        assert_eq!(
+            SearchQuery::parse("(invoice OR receipt) -is:read")
+                .unwrap()
+                .to_gmail_raw(),
+            "(invoice OR receipt) -is:read"
+        );
+    }
+}
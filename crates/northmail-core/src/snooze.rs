@@ -0,0 +1,105 @@
+//! Snoozing: when a snoozed message comes back
+//!
+//! A snoozed message keeps its folder and flags; the cache only records
+//! when it is due (`snoozed_until`, Unix seconds). Until then folder and
+//! inbox lists leave it out and the Snoozed list shows it. The sync engine
+//! clears the time once it passes, which puts the message back in its
+//! folder, and reports it so the user is notified again.
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone};
+
+/// Hour of the day a message snoozed until tomorrow or next week returns
+const MORNING_HOUR: u32 = 8;
+
+/// How long "later today" lasts
+const LATER_TODAY_HOURS: i64 = 3;
+
+/// Times offered when snoozing a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnoozePreset {
+    /// A few hours from now
+    LaterToday,
+    /// Tomorrow morning
+    Tomorrow,
+    /// Monday morning
+    NextWeek,
+}
+
+impl SnoozePreset {
+    pub const ALL: [SnoozePreset; 3] = [
+        SnoozePreset::LaterToday,
+        SnoozePreset::Tomorrow,
+        SnoozePreset::NextWeek,
+    ];
+
+    /// When a message snoozed at `now` comes back, in Unix seconds. Morning
+    /// times are in `now`'s time zone.
+    pub fn until<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> i64 {
+        match self {
+            SnoozePreset::LaterToday => {
+                (now.clone() + Duration::hours(LATER_TODAY_HOURS)).timestamp()
+            }
+            SnoozePreset::Tomorrow => hour_after(now, 1, MORNING_HOUR),
+            SnoozePreset::NextWeek => {
+                let days = 7 - now.weekday().num_days_from_monday();
+                hour_after(now, days as i64, MORNING_HOUR)
+            }
+        }
+    }
+}
+
+/// `hour` o'clock on the day `days` after `now`'s, or the same time as `now`
+/// on that day if a clock change skips that hour
+pub(crate) fn hour_after<Tz: TimeZone>(now: &DateTime<Tz>, days: i64, hour: u32) -> i64 {
+    let time = NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default();
+    let date = now.date_naive() + Duration::days(days);
+    now.timezone()
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|time| time.timestamp())
+        .unwrap_or_else(|| (now.clone() + Duration::days(days)).timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn test_later_today() {
+        let now = at("2024-03-06T10:30:00+01:00");
+        assert_eq!(
+            SnoozePreset::LaterToday.until(&now),
+            at("2024-03-06T13:30:00+01:00").timestamp()
+        );
+    }
+
+    #[test]
+    fn test_tomorrow_morning() {
+        let now = at("2024-03-06T23:45:00-05:00");
+        assert_eq!(
+            SnoozePreset::Tomorrow.until(&now),
+            at("2024-03-07T08:00:00-05:00").timestamp()
+        );
+    }
+
+    #[test]
+    fn test_next_week() {
+        // Wednesday
+        let now = at("2024-03-06T10:00:00+00:00");
+        assert_eq!(
+            SnoozePreset::NextWeek.until(&now),
+            at("2024-03-11T08:00:00+00:00").timestamp()
+        );
+        // A Monday snoozes to the following Monday
+        let now = at("2024-03-11T07:00:00+00:00");
+        assert_eq!(
+            SnoozePreset::NextWeek.until(&now),
+            at("2024-03-18T08:00:00+00:00").timestamp()
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Order and shipment summaries from embedded markup
+//!
+//! Shops and carriers embed schema.org data in their mail, either as JSON-LD
+//! (`<script type="application/ld+json">`) or as microdata attributes
+//! (`itemscope`, `itemtype`, `itemprop`). The `Order` and `ParcelDelivery`
+//! types are read into a [`MailSummary`] shown as a card above the message.
+//! Microdata is read by scanning tags and tracking element depth rather than
+//! with a full HTML parser, which covers the shapes senders use in practice.
+
+use chrono::NaiveDate;
+use serde_json::{Map, Value};
+
+/// Which card to show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryKind {
+    Order,
+    Shipment,
+}
+
+/// schema.org `OrderStatus` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    Processing,
+    PaymentDue,
+    InTransit,
+    PickupAvailable,
+    Delivered,
+    Cancelled,
+    Returned,
+    Problem,
+}
+
+impl OrderStatus {
+    fn parse(value: &str) -> Option<Self> {
+        let name = value.rsplit('/').next().unwrap_or(value);
+        Some(match name.strip_prefix("Order").unwrap_or(name) {
+            "Processing" => Self::Processing,
+            "PaymentDue" => Self::PaymentDue,
+            "InTransit" => Self::InTransit,
+            "PickupAvailable" => Self::PickupAvailable,
+            "Delivered" => Self::Delivered,
+            "Cancelled" => Self::Cancelled,
+            "Returned" => Self::Returned,
+            "Problem" => Self::Problem,
+            _ => return None,
+        })
+    }
+}
+
+/// What the card shows; every field is optional since senders fill in
+/// different subsets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailSummary {
+    pub kind: SummaryKind,
+    pub merchant: Option<String>,
+    pub order_number: Option<String>,
+    pub status: Option<OrderStatus>,
+    /// Total with its currency, e.g. `29.99 EUR`
+    pub total: Option<String>,
+    pub expected_delivery: Option<NaiveDate>,
+    pub carrier: Option<String>,
+    pub tracking_number: Option<String>,
+    /// Tracking page for shipments, order page for orders
+    pub url: Option<String>,
+}
+
+/// Summary of the order or shipment described in a message's HTML, if any.
+/// A shipment wins over an order when both are present.
+pub fn extract_summary(html: &str) -> Option<MailSummary> {
+    let mut items: Vec<Value> = json_ld_blocks(html).into_iter().flat_map(flatten_items).collect();
+    if items.is_empty() {
+        items.extend(microdata_item(html));
+    }
+
+    let parcel = items.iter().find(|item| has_type(item, "ParcelDelivery"));
+    let order = items
+        .iter()
+        .find(|item| has_type(item, "Order"))
+        .or_else(|| parcel.and_then(|p| p.get("partOfOrder")).filter(|o| o.is_object()));
+
+    if parcel.is_none() && order.is_none() {
+        return None;
+    }
+
+    let order_field = |key: &str| order.and_then(|o| text(o.get(key)?));
+    let merchant = order
+        .and_then(|o| o.get("merchant").or_else(|| o.get("seller")))
+        .or_else(|| parcel.and_then(|p| p.get("provider")))
+        .and_then(name_of);
+    let total = order_field("price")
+        .or_else(|| order.and_then(|o| text(o.get("acceptedOffer")?.get("price")?)))
+        .map(|price| match order_field("priceCurrency") {
+            Some(currency) => format!("{} {}", price, currency),
+            None => price,
+        });
+    let status = order_field("orderStatus")
+        .or_else(|| parcel.and_then(|p| text(p.get("deliveryStatus")?)))
+        .and_then(|s| OrderStatus::parse(&s));
+
+    let summary = match parcel {
+        Some(parcel) => {
+            let field = |key: &str| text(parcel.get(key)?);
+            MailSummary {
+                kind: SummaryKind::Shipment,
+                merchant,
+                order_number: order_field("orderNumber"),
+                status,
+                total,
+                expected_delivery: field("expectedArrivalUntil")
+                    .or_else(|| field("expectedArrivalFrom"))
+                    .and_then(|d| parse_date(&d)),
+                carrier: parcel.get("carrier").and_then(name_of),
+                tracking_number: field("trackingNumber"),
+                url: field("trackingUrl").or_else(|| field("url")),
+            }
+        }
+        None => MailSummary {
+            kind: SummaryKind::Order,
+            merchant,
+            order_number: order_field("orderNumber"),
+            status,
+            total,
+            expected_delivery: None,
+            carrier: None,
+            tracking_number: None,
+            url: order_field("url"),
+        },
+    };
+    Some(summary)
+}
+
+/// Parsed contents of every JSON-LD script block
+fn json_ld_blocks(html: &str) -> Vec<Value> {
+    let lower = html.to_ascii_lowercase();
+    let mut blocks = Vec::new();
+    let mut from = 0;
+    while let Some(start) = lower[from..].find("<script").map(|i| i + from) {
+        let Some(tag_end) = lower[start..].find('>').map(|i| i + start) else {
+            break;
+        };
+        let Some(end) = lower[tag_end..].find("</script").map(|i| i + tag_end) else {
+            break;
+        };
+        if lower[start..tag_end].contains("application/ld+json") {
+            if let Ok(value) = serde_json::from_str(html[tag_end + 1..end].trim()) {
+                blocks.push(value);
+            }
+        }
+        from = end;
+    }
+    blocks
+}
+
+/// Top-level items of a JSON-LD value: arrays and `@graph` are unwrapped
+fn flatten_items(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.into_iter().flat_map(flatten_items).collect(),
+        Value::Object(mut map) => match map.remove("@graph") {
+            Some(graph) => flatten_items(graph),
+            None => vec![Value::Object(map)],
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn has_type(item: &Value, wanted: &str) -> bool {
+    let matches = |t: &Value| t.as_str().is_some_and(|t| t.rsplit('/').next() == Some(wanted));
+    match item.get("@type") {
+        Some(Value::Array(types)) => types.iter().any(matches),
+        Some(t) => matches(t),
+        None => false,
+    }
+}
+
+/// A string or number as text; objects give their `@id` (used for enum values)
+fn text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Object(map) => return map.get("@id").and_then(text),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// `name` of an Organization, or the value itself when it is plain text
+fn name_of(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(map) => map.get("name").and_then(text),
+        other => text(other),
+    }
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "hr", "img", "input", "link", "meta", "source", "wbr"];
+
+/// The first Order or ParcelDelivery described with microdata, in the shape
+/// JSON-LD would give it. Nesting is followed through element depth, so a
+/// stray unclosed tag can attach properties to the wrong item.
+fn microdata_item(html: &str) -> Option<Value> {
+    let mut depth = 0usize;
+    // Open items: depth of the element that opened them, the property they
+    // fill in their parent, and their properties so far
+    let mut open: Vec<(usize, Option<String>, Map<String, Value>)> = Vec::new();
+
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('>') else { break };
+        let tag = &after[..end];
+        rest = &after[end + 1..];
+
+        if tag.starts_with('/') {
+            depth = depth.saturating_sub(1);
+            while open.last().is_some_and(|(opened, _, _)| *opened >= depth) {
+                let (_, prop, item) = open.pop()?;
+                match (open.last_mut(), prop) {
+                    (Some((_, _, parent)), Some(prop)) => {
+                        parent.entry(prop).or_insert(Value::Object(item));
+                    }
+                    (Some(_), None) => {}
+                    (None, _) => return Some(Value::Object(item)),
+                }
+            }
+            continue;
+        }
+        if tag.starts_with('!') {
+            continue;
+        }
+
+        let element = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        let void = tag.ends_with('/') || VOID_ELEMENTS.contains(&element.to_ascii_lowercase().as_str());
+        let itemprop = attribute(tag, "itemprop");
+
+        if has_attribute(tag, "itemscope") && !void {
+            let itemtype = attribute(tag, "itemtype").unwrap_or_default();
+            let itemtype = itemtype.rsplit('/').next().unwrap_or_default();
+            if !open.is_empty() || itemtype == "Order" || itemtype == "ParcelDelivery" {
+                let mut item = Map::new();
+                item.insert("@type".to_string(), Value::String(itemtype.to_string()));
+                open.push((depth, itemprop, item));
+            }
+        } else if let (Some(prop), Some((_, _, item))) = (itemprop, open.last_mut()) {
+            let value = attribute(tag, "content")
+                .or_else(|| attribute(tag, "href"))
+                .or_else(|| attribute(tag, "datetime"))
+                .or_else(|| {
+                    let text = rest[..rest.find('<').unwrap_or(rest.len())].trim();
+                    (!text.is_empty()).then(|| decode_entities(text))
+                });
+            if let Some(value) = value {
+                item.entry(prop).or_insert(Value::String(value));
+            }
+        }
+
+        if !void {
+            depth += 1;
+        }
+    }
+
+    // Items left open when the document ends
+    let (_, _, mut item) = open.pop()?;
+    while let Some((_, prop, mut parent)) = open.pop() {
+        if let Some(prop) = prop {
+            parent.entry(prop).or_insert(Value::Object(item));
+        }
+        item = parent;
+    }
+    Some(Value::Object(item))
+}
+
+/// Value of an attribute in a start tag's text
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find(name).map(|i| i + from) {
+        from = pos + name.len();
+        let preceded = pos == 0 || lower.as_bytes()[pos - 1].is_ascii_whitespace();
+        let rest = tag[from..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+        let rest = rest[1..].trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next().unwrap_or_default(),
+            _ => rest.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default(),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+fn has_attribute(tag: &str, name: &str) -> bool {
+    tag.to_ascii_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '=' || c == '/')
+        .any(|word| word == name)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_ld_order() {
+        let html = r#"<html><head><script type="application/ld+json">
+            {"@context": "http://schema.org", "@type": "Order",
+             "merchant": {"@type": "Organization", "name": "Bookshop"},
+             "orderNumber": "A-1001", "orderStatus": "http://schema.org/OrderProcessing",
+             "price": "29.99", "priceCurrency": "EUR", "url": "https://shop.example/orders/A-1001"}
+            </script></head><body>Thanks!</body></html>"#;
+        let summary = extract_summary(html).unwrap();
+        assert_eq!(summary.kind, SummaryKind::Order);
+        assert_eq!(summary.merchant.as_deref(), Some("Bookshop"));
+        assert_eq!(summary.order_number.as_deref(), Some("A-1001"));
+        assert_eq!(summary.status, Some(OrderStatus::Processing));
+        assert_eq!(summary.total.as_deref(), Some("29.99 EUR"));
+        assert_eq!(summary.url.as_deref(), Some("https://shop.example/orders/A-1001"));
+    }
+
+    #[test]
+    fn test_json_ld_parcel_in_graph() {
+        let html = r#"<script type='application/ld+json'>{"@graph": [
+            {"@type": "ParcelDelivery", "carrier": {"name": "DHL"}, "trackingNumber": "JD0123",
+             "trackingUrl": "https://track.example/JD0123", "expectedArrivalUntil": "2025-03-14T18:00:00+01:00",
+             "partOfOrder": {"@type": "Order", "orderNumber": "77", "merchant": {"name": "Gadgets"},
+                             "orderStatus": {"@id": "http://schema.org/OrderInTransit"}}}
+            ]}</script>"#;
+        let summary = extract_summary(html).unwrap();
+        assert_eq!(summary.kind, SummaryKind::Shipment);
+        assert_eq!(summary.carrier.as_deref(), Some("DHL"));
+        assert_eq!(summary.tracking_number.as_deref(), Some("JD0123"));
+        assert_eq!(summary.expected_delivery, NaiveDate::from_ymd_opt(2025, 3, 14));
+        assert_eq!(summary.order_number.as_deref(), Some("77"));
+        assert_eq!(summary.merchant.as_deref(), Some("Gadgets"));
+        assert_eq!(summary.status, Some(OrderStatus::InTransit));
+        assert_eq!(summary.url.as_deref(), Some("https://track.example/JD0123"));
+    }
+
+    #[test]
+    fn test_microdata_parcel() {
+        let html = r#"<div itemscope itemtype="http://schema.org/ParcelDelivery">
+            <div itemprop="carrier" itemscope itemtype="http://schema.org/Organization">
+              <meta itemprop="name" content="UPS"/>
+            </div>
+            <span itemprop="trackingNumber">1Z999</span>
+            <link itemprop="trackingUrl" href="https://ups.example/?t=1Z999&amp;l=en"/>
+            <time itemprop="expectedArrivalUntil" datetime="2025-06-02">Mon</time>
+          </div>"#;
+        let summary = extract_summary(html).unwrap();
+        assert_eq!(summary.kind, SummaryKind::Shipment);
+        assert_eq!(summary.carrier.as_deref(), Some("UPS"));
+        assert_eq!(summary.tracking_number.as_deref(), Some("1Z999"));
+        assert_eq!(summary.url.as_deref(), Some("https://ups.example/?t=1Z999&l=en"));
+        assert_eq!(summary.expected_delivery, NaiveDate::from_ymd_opt(2025, 6, 2));
+    }
+
+    #[test]
+    fn test_no_summary() {
+        assert!(extract_summary("<p>Hello</p>").is_none());
+        assert!(extract_summary(r#"<script type="application/ld+json">{"@type": "EmailMessage"}</script>"#).is_none());
+        assert!(extract_summary(r#"<script type="application/ld+json">not json</script>"#).is_none());
+    }
+}
@@ -1,22 +1,88 @@
 //! Sync engine for email synchronization
+//!
+//! The engine owns folder listing, incremental header sync, flag sync and
+//! cleanup of messages gone from the server. Front ends send it
+//! [`SyncCommand`]s and react to the [`SyncEvent`]s it sends back, so the
+//! GTK app, a daemon or a test drive the same code. How accounts log in is
+//! left to an [`ImapConnector`].
 
-use crate::{Account, CoreError, CoreResult, Database};
-use northmail_auth::AuthManager;
-use northmail_imap::ImapClient;
+use crate::auto_empty::ExpiredMessages;
+use crate::bandwidth::{BandwidthPolicy, LowBandwidthMode};
+use crate::flag_merge::{FlagConflict, FlagPolicy, FlagUpdate};
+use crate::database::DbMessage;
+use crate::eml::{self, MessageSource};
+use crate::import::{self, ImportCounts, ImportLimits};
+use crate::jmap::{self, JmapAccount};
+use crate::maildir::ExportCounts;
+use crate::maintenance::{self, MaintenanceReport};
+use crate::rules::{self, RuleMessage};
+use crate::{CoreError, CoreResult, Database};
+use futures::future::BoxFuture;
+use northmail_imap::{ImapClient, MessageFlags, MessageHeader};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, instrument, warn};
+
+/// How many of the newest messages the first sync of a folder fetches;
+/// older ones are left to on-demand paging
+const INITIAL_SYNC_COUNT: u32 = 200;
+
+/// Longest the engine waits before checking for snoozed messages and
+/// replies that are due. Both are set straight in the cache, and the
+/// monotonic clock stops during suspend, so the wait is never planned far
+/// ahead.
+const DUE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often the engine applies the accounts' cache retention policies
+/// (see [`crate::retention`])
+const CACHE_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+
+/// How often the engine empties Trash and Junk as the accounts' auto-empty
+/// policies ask (see [`crate::auto_empty`])
+const AUTO_EMPTY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Provider of accounts whose mail is reached through the Graph API, which
+/// the app talks to rather than the engine
+const GRAPH_PROVIDER: &str = "ms_graph";
 
 /// Commands sent from UI to sync engine
 #[derive(Debug, Clone)]
 pub enum SyncCommand {
     /// Sync all folders for an account
     SyncAccount { account_id: String },
-    /// Sync a specific folder
+    /// Sync a specific folder: new headers, flag changes and removals
     SyncFolder {
         account_id: String,
         folder_path: String,
     },
+    /// List an account's folders and store them with their counts and
+    /// subscriptions, dropping folders no longer on the server
+    SyncFolderList { account_id: String },
+    /// Store a folder list fetched elsewhere (e.g. from the Graph API) the
+    /// same way [`SyncCommand::SyncFolderList`] stores its own
+    StoreFolders {
+        account_id: String,
+        folders: Vec<FolderRecord>,
+        /// Subscribed paths; `None` or empty leaves subscriptions alone
+        subscribed: Option<Vec<String>>,
+    },
+    /// Apply flags read from the server to cached messages. With `prune`,
+    /// `flags` covers the whole folder and cached messages missing from it
+    /// are removed.
+    ApplyFlags {
+        account_id: String,
+        folder_path: String,
+        flags: Vec<(u32, MessageFlags)>,
+        prune: bool,
+    },
+    /// Remove cached messages whose UIDs are not in `server_uids`
+    PruneMessages {
+        account_id: String,
+        folder_path: String,
+        server_uids: Vec<u32>,
+    },
     /// Fetch full message body
     FetchMessage {
         account_id: String,
@@ -37,10 +103,133 @@ pub enum SyncCommand {
         to_folder: String,
         uid: u32,
     },
+    /// Save a composed RFC 822 message into the Drafts or Sent folder
+    AppendMessage {
+        account_id: String,
+        target: AppendTarget,
+        message: Vec<u8>,
+    },
+    /// Create a folder on the server and add it to the folder list
+    CreateFolder {
+        account_id: String,
+        folder_path: String,
+    },
+    /// Rename a folder (and its children)
+    RenameFolder {
+        account_id: String,
+        from_path: String,
+        to_path: String,
+    },
+    /// Delete a folder and its cached messages
+    DeleteFolder {
+        account_id: String,
+        folder_path: String,
+    },
+    /// Subscribe to or unsubscribe from a folder
+    SetSubscribed {
+        account_id: String,
+        folder_path: String,
+        subscribed: bool,
+    },
+    /// Import the messages of an mbox file or a Maildir into a folder, in
+    /// the background (see [`crate::import`]). With `local`, `folder_path`
+    /// names a local folder, created if needed, that they're kept in
+    /// instead of being uploaded.
+    ImportMessages {
+        account_id: String,
+        folder_path: String,
+        source: PathBuf,
+        limits: ImportLimits,
+        local: bool,
+    },
+    /// Export a folder as a Maildir, in the background (see
+    /// [`Database::export_maildir`])
+    ExportMaildir {
+        account_id: String,
+        folder_path: String,
+        destination: PathBuf,
+    },
+    /// Save a message as an `.eml` file, in the background (see
+    /// [`crate::eml`])
+    SaveMessage {
+        account_id: String,
+        folder_path: String,
+        uid: u32,
+        destination: PathBuf,
+    },
+    /// Apply every account's cache retention policy now
+    PruneCache,
+    /// Empty Trash and Junk as every account's auto-empty policy asks, now
+    AutoEmpty,
+    /// What scheduled folder syncs (see [`crate::sync_policy`]) have to
+    /// respect: accounts the user paused, and the bandwidth policy that
+    /// stretches their intervals
+    SetBackgroundSync {
+        paused_accounts: Vec<String>,
+        bandwidth: BandwidthPolicy,
+    },
     /// Stop the sync engine
     Shutdown,
 }
 
+/// Destination for an appended message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendTarget {
+    /// Drafts folder, stored with `\Draft \Seen`
+    Drafts,
+    /// Sent folder, stored with `\Seen`
+    Sent,
+}
+
+impl AppendTarget {
+    /// IMAP flags to store the message with
+    pub fn flags(&self) -> &'static [&'static str] {
+        match self {
+            AppendTarget::Drafts => &["\\Draft", "\\Seen"],
+            AppendTarget::Sent => &["\\Seen"],
+        }
+    }
+
+    /// Folder name to use when no folder has the matching special-use type
+    fn fallback_folder(&self) -> &'static str {
+        match self {
+            AppendTarget::Drafts => "Drafts",
+            AppendTarget::Sent => "Sent",
+        }
+    }
+}
+
+/// A folder to store, as listed over IMAP or the Graph API
+#[derive(Debug, Clone)]
+pub struct FolderRecord {
+    pub name: String,
+    /// Full path as used in commands (raw modified UTF-7 for IMAP)
+    pub full_path: String,
+    /// Lowercase folder type, e.g. `inbox` or `other`
+    pub folder_type: String,
+    pub message_count: Option<u32>,
+    pub unread_count: Option<u32>,
+    /// False for `\Noselect` containers that only hold children
+    pub is_selectable: bool,
+    /// Graph API folder ID or JMAP mailbox ID, for accounts synced
+    /// through Graph or JMAP
+    pub graph_folder_id: Option<String>,
+}
+
+impl From<&northmail_imap::Folder> for FolderRecord {
+    fn from(folder: &northmail_imap::Folder) -> Self {
+        Self {
+            name: folder.name.clone(),
+            full_path: folder.full_path.clone(),
+            folder_type: format!("{:?}", folder.folder_type).to_lowercase(),
+            message_count: folder.message_count,
+            unread_count: folder.unread_count,
+            is_selectable: folder.is_selectable(),
+            graph_folder_id: None,
+        }
+    }
+}
+
 /// Events sent from sync engine to UI
 #[derive(Debug, Clone)]
 pub enum SyncEvent {
@@ -70,39 +259,203 @@ pub enum SyncEvent {
         folder_path: String,
         count: u32,
     },
+    /// Message saved to a folder via APPEND
+    MessageAppended {
+        account_id: String,
+        folder_path: String,
+    },
+    /// Headers of new messages were stored for a folder
+    NewMessages {
+        account_id: String,
+        folder_path: String,
+        count: usize,
+    },
+    /// Cached flags were brought in line with the server and messages gone
+    /// from it removed
+    CacheReconciled {
+        account_id: String,
+        folder_path: String,
+        updated: usize,
+        removed: u64,
+    },
+    /// Flags of cached messages changed on the server
+    FlagsChanged {
+        account_id: String,
+        folder_path: String,
+        folder_id: i64,
+        changes: Vec<(u32, MessageFlags)>,
+    },
+    /// Read or star changes made here hadn't reached the server, which had
+    /// the other value; each was settled by the [`FlagPolicy`]. Those the
+    /// cache kept still have to be sent to the server.
+    FlagConflicts {
+        account_id: String,
+        folder_path: String,
+        folder_id: i64,
+        conflicts: Vec<FlagConflict>,
+    },
+    /// Messages of a Graph account's Trash or Junk are due to be deleted
+    /// by its auto-empty policy; the app deletes them through Graph
+    AutoEmptyDue {
+        account_id: String,
+        expired: Vec<ExpiredMessages>,
+    },
+    /// Snoozed messages came back to their folders
+    SnoozesExpired { messages: Vec<DbMessage> },
+    /// Messages marked to reply to later are due (see
+    /// [`crate::reply_later`])
+    RepliesDue { messages: Vec<DbMessage> },
+    /// An import uploaded another batch
+    ImportProgress {
+        account_id: String,
+        folder_path: String,
+        counts: ImportCounts,
+    },
+    /// An import ended, with the error that stopped it if it didn't finish
+    ImportFinished {
+        account_id: String,
+        folder_path: String,
+        counts: ImportCounts,
+        error: Option<String>,
+    },
+    /// A Maildir export ended, with the error that stopped it if it didn't
+    /// finish
+    MaildirExported {
+        account_id: String,
+        folder_path: String,
+        destination: PathBuf,
+        counts: ExportCounts,
+        error: Option<String>,
+    },
+    /// A message was saved as an `.eml` file, rebuilt from the cache if
+    /// `rebuilt`, or failed to be with `error`
+    MessageSaved {
+        account_id: String,
+        folder_path: String,
+        uid: u32,
+        destination: PathBuf,
+        rebuilt: bool,
+        error: Option<String>,
+    },
+    /// Idle-time database maintenance ran
+    MaintenanceFinished { report: MaintenanceReport },
+    /// New messages matched a rule with a Notify action
+    RuleMatched {
+        account_id: String,
+        rule_name: String,
+        messages: Vec<DbMessage>,
+    },
     /// Error occurred
     Error { message: String },
 }
 
+/// Opens logged-in IMAP connections for the engine
+pub trait ImapConnector: Send + Sync {
+    /// An IMAP client logged in to the account
+    fn connect<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, CoreResult<ImapClient>>;
+}
+
 /// Sync engine that runs in a background tokio task
 pub struct SyncEngine {
     database: Arc<Database>,
-    auth_manager: Arc<AuthManager>,
+    connector: Arc<dyn ImapConnector>,
     command_rx: mpsc::Receiver<SyncCommand>,
     event_tx: mpsc::Sender<SyncEvent>,
+    /// When the cache was last pruned
+    last_prune: Option<std::time::Instant>,
+    /// When Trash and Junk were last emptied
+    last_auto_empty: Option<std::time::Instant>,
+    /// When the engine last handled a command or push, so maintenance
+    /// waits for a quiet moment
+    last_activity: std::time::Instant,
+    /// Accounts whose folders aren't synced on schedule
+    paused: HashSet<String>,
+    /// Bandwidth policy scheduled folder syncs follow
+    bandwidth: BandwidthPolicy,
+    /// Which side wins when a flag changed here and on the server
+    flag_policy: FlagPolicy,
+    /// IDs of JMAP accounts whose mail changed on the server, from their
+    /// push streams
+    push_tx: mpsc::Sender<String>,
+    push_rx: mpsc::Receiver<String>,
+    /// Tasks following the push streams of JMAP accounts
+    watched: HashMap<String, tokio::task::JoinHandle<()>>,
 }
 
 impl SyncEngine {
-    /// Create a new sync engine
-    pub fn new(
+    /// Create a sync engine that logs in through `connector`
+    pub fn with_connector(
         database: Arc<Database>,
-        auth_manager: Arc<AuthManager>,
+        connector: Arc<dyn ImapConnector>,
         command_rx: mpsc::Receiver<SyncCommand>,
         event_tx: mpsc::Sender<SyncEvent>,
     ) -> Self {
+        let (push_tx, push_rx) = mpsc::channel(16);
         Self {
             database,
-            auth_manager,
+            connector,
             command_rx,
             event_tx,
+            last_prune: None,
+            last_auto_empty: None,
+            last_activity: std::time::Instant::now(),
+            paused: HashSet::new(),
+            bandwidth: BandwidthPolicy::new(LowBandwidthMode::Never, false, true),
+            flag_policy: FlagPolicy::default(),
+            push_tx,
+            push_rx,
+            watched: HashMap::new(),
         }
     }
 
-    /// Run the sync engine
+    /// Run the sync engine. Between commands it brings back snoozed
+    /// messages and reports replies as they fall due, syncs folders with a
+    /// sync interval, now and then prunes the cache, maintains the database
+    /// once a day when it has been idle, and syncs JMAP accounts their
+    /// servers report changes in.
     pub async fn run(mut self) {
         info!("Sync engine started");
 
-        while let Some(command) = self.command_rx.recv().await {
+        loop {
+            let wait = self.due_wait().await;
+            let command = tokio::select! {
+                command = self.command_rx.recv() => command,
+                Some(account_id) = self.push_rx.recv() => {
+                    // Changes come in bursts; sync each account once
+                    let mut accounts = HashSet::from([account_id]);
+                    while let Ok(account_id) = self.push_rx.try_recv() {
+                        accounts.insert(account_id);
+                    }
+                    for account_id in accounts {
+                        if let Err(e) = self.sync_jmap_changes(&account_id).await {
+                            warn!("Failed to sync changes of {}: {}", account_id, e);
+                        }
+                    }
+                    self.last_activity = std::time::Instant::now();
+                    continue;
+                }
+                _ = tokio::time::sleep(wait) => {
+                    self.wake_snoozed().await;
+                    self.wake_replies().await;
+                    self.sync_scheduled_folders().await;
+                    if let Err(e) = self.database.index_recipients().await {
+                        warn!("Failed to index recipients: {}", e);
+                    }
+                    if self.last_prune.is_none_or(|at| at.elapsed() >= CACHE_PRUNE_INTERVAL) {
+                        self.prune_cache().await;
+                    }
+                    if self.last_auto_empty.is_none_or(|at| at.elapsed() >= AUTO_EMPTY_INTERVAL) {
+                        self.auto_empty().await;
+                    }
+                    if self.last_activity.elapsed() >= maintenance::IDLE_TIME {
+                        self.maintain_if_due().await;
+                    }
+                    continue;
+                }
+            };
+            let Some(command) = command else {
+                break;
+            };
             match command {
                 SyncCommand::Shutdown => {
                     info!("Sync engine shutting down");
@@ -118,6 +471,7 @@ impl SyncEngine {
                             })
                             .await;
                     }
+                    self.last_activity = std::time::Instant::now();
                 }
             }
         }
@@ -125,8 +479,249 @@ impl SyncEngine {
         info!("Sync engine stopped");
     }
 
+    /// How long until the next snoozed message or reply is due, at most
+    /// [`DUE_CHECK_INTERVAL`]
+    async fn due_wait(&self) -> std::time::Duration {
+        let snooze = self.database.next_snooze_due().await.unwrap_or_else(|e| {
+            warn!("Failed to look up the next snooze: {}", e);
+            None
+        });
+        let reply = self.database.next_reply_due().await.unwrap_or_else(|e| {
+            warn!("Failed to look up the next reply due: {}", e);
+            None
+        });
+        match snooze.into_iter().chain(reply).min() {
+            Some(due) => {
+                let now = chrono::Utc::now().timestamp();
+                let secs = due.saturating_sub(now).max(1) as u64;
+                std::time::Duration::from_secs(secs).min(DUE_CHECK_INTERVAL)
+            }
+            None => DUE_CHECK_INTERVAL,
+        }
+    }
+
+    /// Bring back snoozed messages that are due and report them
+    async fn wake_snoozed(&self) {
+        let messages = match self
+            .database
+            .take_due_snoozes(chrono::Utc::now().timestamp())
+            .await
+        {
+            Ok(messages) => messages,
+            Err(e) => {
+                warn!("Failed to bring back snoozed messages: {}", e);
+                return;
+            }
+        };
+        if messages.is_empty() {
+            return;
+        }
+        info!("{} snoozed messages are back", messages.len());
+        let _ = self
+            .event_tx
+            .send(SyncEvent::SnoozesExpired { messages })
+            .await;
+    }
+
+    /// Unmark messages whose reply is due and report them
+    async fn wake_replies(&self) {
+        let messages = match self
+            .database
+            .take_due_replies(chrono::Utc::now().timestamp())
+            .await
+        {
+            Ok(messages) => messages,
+            Err(e) => {
+                warn!("Failed to take replies that are due: {}", e);
+                return;
+            }
+        };
+        if messages.is_empty() {
+            return;
+        }
+        info!("{} replies are due", messages.len());
+        let _ = self
+            .event_tx
+            .send(SyncEvent::RepliesDue { messages })
+            .await;
+    }
+
+    /// Drop cached bodies as each account's retention policy asks
+    async fn prune_cache(&mut self) {
+        self.last_prune = Some(std::time::Instant::now());
+        let retentions = match self.database.get_limited_retentions().await {
+            Ok(retentions) => retentions,
+            Err(e) => {
+                warn!("Failed to read retention policies: {}", e);
+                return;
+            }
+        };
+        let now = chrono::Utc::now().timestamp();
+        for (account_id, policy) in retentions {
+            if let Err(e) = self.database.prune_cache(&account_id, &policy, now).await {
+                warn!("Failed to prune cache of {}: {}", account_id, e);
+            }
+        }
+    }
+
+    /// Delete what has been in Trash and Junk longer than each account's
+    /// auto-empty policy allows, other than in paused accounts
+    async fn auto_empty(&mut self) {
+        self.last_auto_empty = Some(std::time::Instant::now());
+        let policies = match self.database.get_auto_empty_policies().await {
+            Ok(policies) => policies,
+            Err(e) => {
+                warn!("Failed to read auto-empty policies: {}", e);
+                return;
+            }
+        };
+        let now = chrono::Utc::now().timestamp();
+        for (account_id, policy) in policies {
+            if self.paused.contains(&account_id) {
+                continue;
+            }
+            let expired = match self
+                .database
+                .get_auto_empty_expired(&account_id, &policy, now)
+                .await
+            {
+                Ok(expired) => expired,
+                Err(e) => {
+                    warn!("Failed to find expired messages of {}: {}", account_id, e);
+                    continue;
+                }
+            };
+            if expired.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.purge_expired(&account_id, expired).await {
+                warn!("Failed to empty Trash and Junk of {}: {}", account_id, e);
+            }
+        }
+    }
+
+    /// Delete expired messages on the server and from the cache: over IMAP
+    /// with UID EXPUNGE, over JMAP by destroying them, and for Graph
+    /// accounts by handing them to the app
+    async fn purge_expired(
+        &mut self,
+        account_id: &str,
+        expired: Vec<ExpiredMessages>,
+    ) -> CoreResult<()> {
+        let provider = self
+            .database
+            .get_accounts()
+            .await?
+            .into_iter()
+            .find(|a| a.id == account_id)
+            .map(|a| a.provider)
+            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+
+        if provider == GRAPH_PROVIDER {
+            let _ = self
+                .event_tx
+                .send(SyncEvent::AutoEmptyDue {
+                    account_id: account_id.to_string(),
+                    expired,
+                })
+                .await;
+            return Ok(());
+        }
+
+        if provider == jmap::PROVIDER {
+            let account = JmapAccount::connect(self.database.clone(), account_id).await?;
+            for folder in &expired {
+                let removed = account
+                    .destroy_messages(&folder.full_path, &folder.uids)
+                    .await?;
+                info!("Auto-emptied {} messages from {}", removed, folder.full_path);
+            }
+            return Ok(());
+        }
+
+        let mut client = self.connect_account(account_id).await?;
+        for folder in &expired {
+            client.select(&folder.full_path).await?;
+            client.purge_messages(&folder.uids).await?;
+            let removed = self
+                .database
+                .delete_messages_by_uids(folder.folder_id, &folder.uids)
+                .await?;
+            info!("Auto-emptied {} messages from {}", removed, folder.full_path);
+        }
+        client.logout().await?;
+        Ok(())
+    }
+
+    /// Sync the folders whose sync interval has passed, other than those of
+    /// paused accounts
+    async fn sync_scheduled_folders(&mut self) {
+        let folders = match self.database.get_scheduled_folders().await {
+            Ok(folders) => folders,
+            Err(e) => {
+                warn!("Failed to read scheduled folders: {}", e);
+                return;
+            }
+        };
+        let now = chrono::Utc::now().timestamp();
+        for folder in folders {
+            if self.paused.contains(&folder.account_id)
+                || !folder.policy.is_due(folder.synced_at, now, &self.bandwidth)
+            {
+                continue;
+            }
+            // Recorded first, so a folder that fails to sync waits for its
+            // next turn rather than being retried every minute
+            if let Err(e) = self
+                .database
+                .set_folder_synced_at(folder.folder_id, now)
+                .await
+            {
+                warn!("Failed to record sync of {}: {}", folder.full_path, e);
+                continue;
+            }
+            debug!(
+                "Scheduled sync of {} in {}",
+                folder.full_path, folder.account_id
+            );
+            let command = SyncCommand::SyncFolder {
+                account_id: folder.account_id,
+                folder_path: folder.full_path.clone(),
+            };
+            if let Err(e) = self.handle_command(command).await {
+                warn!("Scheduled sync of {} failed: {}", folder.full_path, e);
+            }
+        }
+    }
+
+    /// Run database maintenance if a day has passed since it last ran
+    async fn maintain_if_due(&self) {
+        let last = match self.database.last_maintenance().await {
+            Ok(last) => last.map(|report| report.checked_at),
+            Err(e) => {
+                warn!("Failed to read the last maintenance run: {}", e);
+                return;
+            }
+        };
+        if !maintenance::is_due(last, chrono::Utc::now().timestamp()) {
+            return;
+        }
+        match self.database.maintenance_report().await {
+            Ok(report) => {
+                let _ = self
+                    .event_tx
+                    .send(SyncEvent::MaintenanceFinished { report })
+                    .await;
+            }
+            Err(e) => warn!("Database maintenance failed: {}", e),
+        }
+    }
+
     /// Handle a sync command
     async fn handle_command(&mut self, command: SyncCommand) -> CoreResult<()> {
+        let Some(command) = self.handle_jmap_command(command).await? else {
+            return Ok(());
+        };
         match command {
             SyncCommand::SyncAccount { account_id } => {
                 self.sync_account(&account_id).await?;
@@ -137,6 +732,36 @@ impl SyncEngine {
             } => {
                 self.sync_folder(&account_id, &folder_path).await?;
             }
+            SyncCommand::SyncFolderList { account_id } => {
+                let mut client = self.connect_account(&account_id).await?;
+                self.sync_folder_list(&mut client, &account_id).await?;
+                client.logout().await?;
+            }
+            SyncCommand::StoreFolders {
+                account_id,
+                folders,
+                subscribed,
+            } => {
+                self.store_folders(&account_id, &folders, subscribed.as_deref())
+                    .await?;
+            }
+            SyncCommand::ApplyFlags {
+                account_id,
+                folder_path,
+                flags,
+                prune,
+            } => {
+                self.apply_flags(&account_id, &folder_path, &flags, prune)
+                    .await?;
+            }
+            SyncCommand::PruneMessages {
+                account_id,
+                folder_path,
+                server_uids,
+            } => {
+                self.prune_messages(&account_id, &folder_path, &server_uids)
+                    .await?;
+            }
             SyncCommand::FetchMessage {
                 account_id,
                 folder_path,
@@ -162,47 +787,286 @@ impl SyncEngine {
                 self.move_message(&account_id, &from_folder, &to_folder, uid)
                     .await?;
             }
+            SyncCommand::AppendMessage {
+                account_id,
+                target,
+                message,
+            } => {
+                self.append_message(&account_id, target, &message).await?;
+            }
+            SyncCommand::CreateFolder {
+                account_id,
+                folder_path,
+            } => {
+                self.create_folder(&account_id, &folder_path).await?;
+            }
+            SyncCommand::RenameFolder {
+                account_id,
+                from_path,
+                to_path,
+            } => {
+                self.rename_folder(&account_id, &from_path, &to_path)
+                    .await?;
+            }
+            SyncCommand::DeleteFolder {
+                account_id,
+                folder_path,
+            } => {
+                self.delete_folder(&account_id, &folder_path).await?;
+            }
+            SyncCommand::SetSubscribed {
+                account_id,
+                folder_path,
+                subscribed,
+            } => {
+                self.set_subscribed(&account_id, &folder_path, subscribed)
+                    .await?;
+            }
+            SyncCommand::ImportMessages {
+                account_id,
+                folder_path,
+                source,
+                limits,
+                local,
+            } => {
+                self.start_import(account_id, folder_path, source, limits, local);
+            }
+            SyncCommand::ExportMaildir {
+                account_id,
+                folder_path,
+                destination,
+            } => {
+                self.start_export(account_id, folder_path, destination);
+            }
+            SyncCommand::SaveMessage {
+                account_id,
+                folder_path,
+                uid,
+                destination,
+            } => {
+                self.start_save_message(account_id, folder_path, uid, destination);
+            }
+            SyncCommand::PruneCache => {
+                self.prune_cache().await;
+            }
+            SyncCommand::AutoEmpty => {
+                self.auto_empty().await;
+            }
+            SyncCommand::SetBackgroundSync {
+                paused_accounts,
+                bandwidth,
+            } => {
+                self.paused = paused_accounts.into_iter().collect();
+                self.bandwidth = bandwidth;
+            }
             SyncCommand::Shutdown => unreachable!(),
         }
 
         Ok(())
     }
 
-    /// Get an authenticated IMAP client for an account
-    async fn get_imap_client(&self, account: &Account) -> CoreResult<ImapClient> {
-        let token = self
-            .auth_manager
-            .get_xoauth2_token(&account.auth_method)
-            .await?;
-
-        let mut client = ImapClient::new(&account.config.imap_host, account.config.imap_port);
-        client
-            .authenticate_xoauth2(token.email(), token.access_token())
-            .await?;
-
-        Ok(client)
-    }
-
-    /// Sync all folders for an account
-    async fn sync_account(&mut self, account_id: &str) -> CoreResult<()> {
-        info!("Syncing account {}", account_id);
+    /// Carry out a command for a JMAP account. Other commands, and those
+    /// JMAP accounts don't support, are handed back.
+    async fn handle_jmap_command(
+        &mut self,
+        command: SyncCommand,
+    ) -> CoreResult<Option<SyncCommand>> {
+        let account_id = match &command {
+            SyncCommand::SyncAccount { account_id }
+            | SyncCommand::SyncFolder { account_id, .. }
+            | SyncCommand::SyncFolderList { account_id }
+            | SyncCommand::FetchMessage { account_id, .. }
+            | SyncCommand::SetRead { account_id, .. }
+            | SyncCommand::MoveMessage { account_id, .. }
+            | SyncCommand::AppendMessage { account_id, .. } => account_id.clone(),
+            _ => return Ok(Some(command)),
+        };
+        if !jmap::is_jmap_account(&self.database, &account_id).await? {
+            return Ok(Some(command));
+        }
 
-        let _ = self
+        if let SyncCommand::SyncAccount { account_id } = &command {
+            self.sync_jmap_account(account_id).await?;
+            return Ok(None);
+        }
+        let account = JmapAccount::connect(self.database.clone(), &account_id).await?;
+        match command {
+            SyncCommand::SyncFolder { folder_path, .. } => {
+                self.sync_jmap_folder(&account, &folder_path).await?;
+            }
+            SyncCommand::SyncFolderList { .. } => {
+                let folders = account.folders().await?;
+                self.store_folders(&account_id, &folders, None).await?;
+            }
+            SyncCommand::FetchMessage {
+                folder_path, uid, ..
+            } => {
+                let body = account.fetch_raw(&folder_path, uid).await?;
+                let _ = self
+                    .event_tx
+                    .send(SyncEvent::MessageFetched {
+                        account_id,
+                        folder_path,
+                        uid,
+                        body,
+                    })
+                    .await;
+            }
+            SyncCommand::SetRead {
+                folder_path,
+                uid,
+                is_read,
+                ..
+            } => {
+                account
+                    .set_flag(&folder_path, &[uid], "\\Seen", is_read)
+                    .await?;
+            }
+            SyncCommand::MoveMessage {
+                from_folder,
+                to_folder,
+                uid,
+                ..
+            } => {
+                account
+                    .move_messages(&from_folder, &[uid], &to_folder)
+                    .await?;
+            }
+            SyncCommand::AppendMessage {
+                target, message, ..
+            } => {
+                let folder_path = account.append(target, message).await?;
+                let _ = self
+                    .event_tx
+                    .send(SyncEvent::MessageAppended {
+                        account_id,
+                        folder_path,
+                    })
+                    .await;
+            }
+            _ => unreachable!(),
+        }
+        Ok(None)
+    }
+
+    /// Sync a JMAP account's mailboxes and inbox, and open its push stream
+    /// if it has none yet
+    #[instrument(skip_all, fields(account = %account_id))]
+    async fn sync_jmap_account(&mut self, account_id: &str) -> CoreResult<()> {
+        info!("Syncing JMAP account {}", account_id);
+        let _ = self
             .event_tx
             .send(SyncEvent::SyncStarted {
                 account_id: account_id.to_string(),
             })
             .await;
 
-        // Get account from database
-        let accounts = self.database.get_accounts().await?;
-        let account = accounts
-            .iter()
-            .find(|a| a.id == account_id)
-            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+        let result = async {
+            let account = JmapAccount::connect(self.database.clone(), account_id).await?;
+            let folders = account.folders().await?;
+            self.store_folders(account_id, &folders, None).await?;
+            if let Some(inbox) = folders.iter().find(|f| f.folder_type == "inbox") {
+                self.sync_jmap_folder(&account, &inbox.full_path).await?;
+            }
+            Ok::<_, CoreError>(())
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = self
+                .event_tx
+                .send(SyncEvent::SyncFailed {
+                    account_id: account_id.to_string(),
+                    error: e.to_string(),
+                })
+                .await;
+            return Err(e);
+        }
+
+        if self
+            .watched
+            .get(account_id)
+            .is_none_or(|task| task.is_finished())
+        {
+            let task = tokio::spawn(jmap::watch(
+                self.database.clone(),
+                account_id.to_string(),
+                self.push_tx.clone(),
+            ));
+            self.watched.insert(account_id.to_string(), task);
+        }
+
+        let _ = self
+            .event_tx
+            .send(SyncEvent::SyncCompleted {
+                account_id: account_id.to_string(),
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Sync a JMAP account's mailboxes and every folder synced before,
+    /// after its server reported changes
+    async fn sync_jmap_changes(&mut self, account_id: &str) -> CoreResult<()> {
+        let account = JmapAccount::connect(self.database.clone(), account_id).await?;
+        let folders = account.folders().await?;
+        self.store_folders(account_id, &folders, None).await?;
+        for folder in self.database.get_folders(account_id).await? {
+            if self
+                .database
+                .get_graph_delta_link(folder.id)
+                .await?
+                .is_some()
+            {
+                self.sync_jmap_folder(&account, &folder.full_path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sync a JMAP account's folder and report what changed
+    async fn sync_jmap_folder(&self, account: &JmapAccount, folder_path: &str) -> CoreResult<()> {
+        let account_id = account.account_id();
+        let sync = account.sync_folder(folder_path, &self.flag_policy).await?;
+        if sync.new > 0 {
+            let _ = self
+                .event_tx
+                .send(SyncEvent::NewMessages {
+                    account_id: account_id.to_string(),
+                    folder_path: folder_path.to_string(),
+                    count: sync.new,
+                })
+                .await;
+        }
+        let updated = sync.changed.len();
+        self.notify_flags_changed(
+            account_id,
+            folder_path,
+            sync.folder_id,
+            FlagUpdate {
+                changed: sync.changed,
+                conflicts: sync.conflicts,
+            },
+        )
+        .await;
+        self.notify_cache_reconciled(account_id, folder_path, updated, sync.removed)
+            .await;
+        Ok(())
+    }
+
+    /// Sync all folders for an account
+    #[instrument(skip_all, fields(account = %account_id))]
+    async fn sync_account(&mut self, account_id: &str) -> CoreResult<()> {
+        info!("Syncing account {}", account_id);
+
+        let _ = self
+            .event_tx
+            .send(SyncEvent::SyncStarted {
+                account_id: account_id.to_string(),
+            })
+            .await;
 
         // Connect to IMAP
-        let mut client = match self.get_imap_client(account).await {
+        let mut client = match self.connect_account(account_id).await {
             Ok(c) => c,
             Err(e) => {
                 let _ = self
@@ -216,25 +1080,7 @@ impl SyncEngine {
             }
         };
 
-        // List and sync folders
-        let folders = client.list_folders().await?;
-        for folder in &folders {
-            if !folder.is_selectable() {
-                continue;
-            }
-
-            let folder_type = format!("{:?}", folder.folder_type).to_lowercase();
-            self.database
-                .upsert_folder(account_id, &folder.name, &folder.full_path, &folder_type)
-                .await?;
-        }
-
-        let _ = self
-            .event_tx
-            .send(SyncEvent::FoldersUpdated {
-                account_id: account_id.to_string(),
-            })
-            .await;
+        let folders = self.sync_folder_list(&mut client, account_id).await?;
 
         // Sync inbox first (most important)
         for folder in &folders {
@@ -258,15 +1104,125 @@ impl SyncEngine {
         Ok(())
     }
 
-    /// Sync a specific folder
-    async fn sync_folder(&mut self, account_id: &str, folder_path: &str) -> CoreResult<()> {
-        let accounts = self.database.get_accounts().await?;
-        let account = accounts
+    /// List the account's folders with their counts and subscriptions and
+    /// store them
+    #[instrument(skip_all, fields(account = %account_id))]
+    async fn sync_folder_list(
+        &mut self,
+        client: &mut ImapClient,
+        account_id: &str,
+    ) -> CoreResult<Vec<northmail_imap::Folder>> {
+        let mut folders = client.list_folders().await?;
+        let subscribed = match client.list_subscribed_folders().await {
+            Ok(paths) => Some(paths),
+            Err(e) => {
+                debug!("Could not list subscribed folders: {}", e);
+                None
+            }
+        };
+
+        let paths: Vec<&str> = folders
             .iter()
-            .find(|a| a.id == account_id)
-            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+            .filter(|f| f.is_selectable())
+            .map(|f| f.full_path.as_str())
+            .collect();
+        let status = client.batch_folder_status(&paths).await?;
+        for folder in folders.iter_mut() {
+            if let Some((_, messages, unseen)) =
+                status.iter().find(|(path, _, _)| *path == folder.full_path)
+            {
+                folder.message_count = Some(*messages);
+                folder.unread_count = Some(*unseen);
+            }
+        }
 
-        let mut client = self.get_imap_client(account).await?;
+        let records: Vec<FolderRecord> = folders.iter().map(FolderRecord::from).collect();
+        self.store_folders(account_id, &records, subscribed.as_deref())
+            .await?;
+        Ok(folders)
+    }
+
+    /// Store a folder list, then drop cached folders missing from it
+    async fn store_folders(
+        &mut self,
+        account_id: &str,
+        folders: &[FolderRecord],
+        subscribed: Option<&[String]>,
+    ) -> CoreResult<()> {
+        for f in folders {
+            let message_count = f.message_count.map(i64::from);
+            let unread_count = f.unread_count.map(i64::from);
+            let res = match &f.graph_folder_id {
+                Some(gid) => {
+                    self.database
+                        .upsert_folder_graph(
+                            account_id,
+                            &f.name,
+                            &f.full_path,
+                            &f.folder_type,
+                            message_count,
+                            unread_count,
+                            gid,
+                        )
+                        .await
+                }
+                None => {
+                    self.database
+                        .upsert_folder_with_counts(
+                            account_id,
+                            &f.name,
+                            &f.full_path,
+                            &f.folder_type,
+                            message_count,
+                            unread_count,
+                        )
+                        .await
+                }
+            };
+            if let Err(e) = res {
+                warn!("Failed to upsert folder {}: {}", f.full_path, e);
+                continue;
+            }
+            self.database
+                .set_folder_selectable(account_id, &f.full_path, f.is_selectable)
+                .await?;
+            if f.graph_folder_id.is_none() {
+                // IMAP paths stay raw; store the decoded form for display
+                self.database
+                    .set_folder_display_names(
+                        account_id,
+                        &f.full_path,
+                        &northmail_imap::decode_mailbox_name(&f.name),
+                        &northmail_imap::decode_mailbox_name(&f.full_path),
+                    )
+                    .await?;
+            }
+        }
+
+        // An empty subscription list means the server doesn't track
+        // subscriptions; leave every folder visible then
+        if let Some(paths) = subscribed.filter(|p| !p.is_empty()) {
+            self.database
+                .set_subscribed_folders(account_id, paths)
+                .await?;
+        }
+
+        let valid_paths: Vec<String> = folders.iter().map(|f| f.full_path.clone()).collect();
+        let removed = self
+            .database
+            .delete_stale_folders(account_id, &valid_paths)
+            .await?;
+        if removed > 0 {
+            info!("Cleaned up {} stale folders for {}", removed, account_id);
+        }
+
+        self.notify_folders_updated(account_id).await;
+        Ok(())
+    }
+
+    /// Sync a specific folder
+    async fn sync_folder(&mut self, account_id: &str, folder_path: &str) -> CoreResult<()> {
+        let mut client = self.connect_account(account_id).await?;
         self.sync_folder_internal(&mut client, account_id, folder_path)
             .await?;
         client.logout().await?;
@@ -274,7 +1230,12 @@ impl SyncEngine {
         Ok(())
     }
 
-    /// Internal folder sync with an existing client
+    /// Internal folder sync with an existing client. Fetches headers newer
+    /// than the newest cached message (on a first sync, those within the
+    /// folder's sync window, or else the newest [`INITIAL_SYNC_COUNT`]),
+    /// then updates flags of cached messages and removes the ones no longer
+    /// on the server.
+    #[instrument(skip_all, fields(account = %account_id, folder = %folder_path))]
     async fn sync_folder_internal(
         &mut self,
         client: &mut ImapClient,
@@ -284,123 +1245,149 @@ impl SyncEngine {
         debug!("Syncing folder: {}", folder_path);
 
         // Select the folder
-        let folder_info = client.select_folder(folder_path).await?;
+        let folder_info = client.select(folder_path).await?;
 
-        // Get folder ID from database
-        let folders = self.database.get_folders(account_id).await?;
-        let db_folder = folders
-            .iter()
-            .find(|f| f.full_path == folder_path)
+        let folder_id = self
+            .database
+            .get_or_create_folder_id(account_id, folder_path)
+            .await?;
+        let db_folder = self
+            .database
+            .get_folder_by_id(folder_id)
+            .await?
             .ok_or_else(|| CoreError::FolderNotFound(folder_path.to_string()))?;
 
-        // Check UIDVALIDITY - if changed, we need to re-sync everything
+        // Check UIDVALIDITY - if changed, cached UIDs name other messages
         let uidvalidity = folder_info.uidvalidity.unwrap_or(0) as i64;
-        let needs_full_sync = db_folder.uidvalidity != Some(uidvalidity);
-
-        if needs_full_sync {
+        if db_folder.uidvalidity.is_some_and(|v| v != uidvalidity) {
             info!(
                 "UIDVALIDITY changed for {}, performing full sync",
                 folder_path
             );
+            self.database
+                .delete_messages_not_in_uids(folder_id, &[])
+                .await?;
         }
 
-        // Fetch message headers
-        let message_count = folder_info.message_count.unwrap_or(0);
-        if message_count > 0 {
-            // Fetch last 100 messages for now (TODO: pagination)
-            let start = if message_count > 100 {
-                message_count - 100
-            } else {
-                1
-            };
-            let uid_range = format!("{}:*", start);
-
-            let headers = client.fetch_headers(&uid_range).await?;
-            let mut unread_count = 0;
+        let cached = self.database.get_message_uids(folder_id).await?;
+        let lowest = cached.iter().min().copied();
+        let highest = cached.iter().max().copied();
 
-            for header in &headers {
-                if !header.is_read() {
-                    unread_count += 1;
+        // New messages
+        let message_count = folder_info.message_count.unwrap_or(0);
+        let mut headers = if message_count == 0 {
+            Vec::new()
+        } else if let Some(highest) = highest {
+            client
+                .uid_fetch_headers(&format!("{}:*", highest + 1))
+                .await?
+        } else {
+            let policy = self
+                .database
+                .get_folder_sync_policy(account_id, folder_path)
+                .await?;
+            match policy.since_date(chrono::Utc::now().timestamp()) {
+                Some(since) => {
+                    let uids = client.uid_search(&format!("SINCE {}", since)).await?;
+                    if uids.is_empty() {
+                        Vec::new()
+                    } else {
+                        client
+                            .uid_fetch_headers(&northmail_imap::format_uid_set(&uids))
+                            .await?
+                    }
+                }
+                None => {
+                    let start = message_count.saturating_sub(INITIAL_SYNC_COUNT) + 1;
+                    client.fetch_headers(&format!("{}:*", start)).await?
                 }
+            }
+        };
+        // `n:*` always matches the newest message, even one below n
+        if let Some(highest) = highest {
+            headers.retain(|h| h.uid as i64 > highest);
+        }
 
-                let db_msg = crate::database::DbMessage {
-                    id: 0, // Will be assigned by database
-                    folder_id: db_folder.id,
-                    uid: header.uid as i64,
-                    message_id: header.envelope.message_id.clone(),
-                    subject: header.envelope.subject.clone(),
-                    from_address: header.envelope.from.first().map(|a| a.address.clone()),
-                    from_name: header.envelope.from.first().and_then(|a| a.name.clone()),
-                    to_addresses: Some(
-                        header
-                            .envelope
-                            .to
-                            .iter()
-                            .map(|a| a.address.clone())
-                            .collect::<Vec<_>>()
-                            .join(", "),
-                    ),
-                    cc_addresses: if header.envelope.cc.is_empty() {
-                        None
-                    } else {
-                        Some(
-                            header
-                                .envelope
-                                .cc
-                                .iter()
-                                .map(|a| a.address.clone())
-                                .collect::<Vec<_>>()
-                                .join(", "),
-                        )
-                    },
-                    date_sent: header.envelope.date.clone(),
-                    date_epoch: header.envelope.date.as_deref().and_then(|d| {
-                        let mut s = d.to_string();
-                        if let Some(paren) = s.rfind('(') {
-                            s = s[..paren].trim().to_string();
-                        }
-                        while s.contains("  ") {
-                            s = s.replace("  ", " ");
-                        }
-                        s = s.replace(" ,", ",");
-                        chrono::DateTime::parse_from_rfc2822(&s)
-                            .map(|dt| dt.timestamp())
-                            .ok()
-                    }),
-                    snippet: None, // Would need BODY[TEXT] for snippet
-                    is_read: header.is_read(),
-                    is_starred: header.is_starred(),
-                    has_attachments: header.has_attachments,
-                    size: header.size as i64,
-                    maildir_path: None,
-                    body_text: None,
-                    body_html: None,
-                };
+        let messages: Vec<DbMessage> = headers
+            .iter()
+            .map(|h| header_to_db_message(folder_id, h))
+            .collect();
+        self.database
+            .upsert_messages_batch(folder_id, &messages)
+            .await?;
+        if !messages.is_empty() {
+            let _ = self
+                .event_tx
+                .send(SyncEvent::NewMessages {
+                    account_id: account_id.to_string(),
+                    folder_path: folder_path.to_string(),
+                    count: messages.len(),
+                })
+                .await;
+        }
 
-                self.database.upsert_message(db_folder.id, &db_msg).await?;
+        // Rules only see mail that arrives after the first sync
+        if highest.is_some() && db_folder.folder_type == "inbox" && !headers.is_empty() {
+            if let Err(e) = self
+                .apply_rules(client, account_id, folder_path, folder_id, &headers)
+                .await
+            {
+                warn!("Failed to apply rules in {}: {}", folder_path, e);
             }
+        }
 
-            // Update folder sync state
-            self.database
-                .update_folder_sync(
-                    db_folder.id,
-                    uidvalidity,
-                    folder_info.uid_next.unwrap_or(0) as i64,
-                    message_count as i64,
-                    unread_count,
-                )
+        // Flag changes and removals among cached messages
+        if let (Some(lowest), Some(highest)) = (lowest, highest) {
+            let flags = client
+                .uid_fetch_flags(&format!("{}:{}", lowest, highest))
+                .await?;
+            let update = self
+                .database
+                .batch_update_flags(folder_id, &flags, &self.flag_policy)
+                .await?;
+            let updated = update.changed.len();
+            self.notify_flags_changed(account_id, folder_path, folder_id, update)
+                .await;
+
+            let mut keep: Vec<i64> = flags.iter().map(|(uid, _)| *uid as i64).collect();
+            keep.extend(headers.iter().map(|h| h.uid as i64));
+            let removed = self
+                .database
+                .delete_messages_not_in_uids(folder_id, &keep)
                 .await?;
 
             let _ = self
                 .event_tx
-                .send(SyncEvent::UnreadCountChanged {
+                .send(SyncEvent::CacheReconciled {
                     account_id: account_id.to_string(),
                     folder_path: folder_path.to_string(),
-                    count: unread_count as u32,
+                    updated,
+                    removed,
                 })
                 .await;
         }
 
+        // Update folder sync state
+        let unread_count = client.uid_search("UNSEEN").await?.len() as i64;
+        self.database
+            .update_folder_sync(
+                folder_id,
+                uidvalidity,
+                folder_info.uid_next.unwrap_or(0) as i64,
+                message_count as i64,
+                unread_count,
+            )
+            .await?;
+
+        let _ = self
+            .event_tx
+            .send(SyncEvent::UnreadCountChanged {
+                account_id: account_id.to_string(),
+                folder_path: folder_path.to_string(),
+                count: unread_count as u32,
+            })
+            .await;
+
         let _ = self
             .event_tx
             .send(SyncEvent::MessagesUpdated {
@@ -413,23 +1400,255 @@ impl SyncEngine {
         Ok(())
     }
 
+    /// Carry out the actions of the rules matching newly arrived messages,
+    /// grouped into one command per flag, tag and destination
+    #[instrument(skip_all, fields(account = %account_id, folder = %folder_path, count = headers.len()))]
+    async fn apply_rules(
+        &mut self,
+        client: &mut ImapClient,
+        account_id: &str,
+        folder_path: &str,
+        folder_id: i64,
+        headers: &[MessageHeader],
+    ) -> CoreResult<()> {
+        let rules = self.database.get_rules().await?;
+        if !rules.iter().any(|r| r.enabled) {
+            return Ok(());
+        }
+
+        let mut read = Vec::new();
+        let mut starred = Vec::new();
+        let mut deleted = Vec::new();
+        let mut labels: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        let mut moves: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+        let mut notify: BTreeMap<String, Vec<DbMessage>> = BTreeMap::new();
+        for header in headers {
+            let outcome = rules::evaluate(&rules, account_id, &rule_message(header));
+            if outcome.is_empty() {
+                continue;
+            }
+            if outcome.mark_read && !header.is_read() {
+                read.push(header.uid);
+            }
+            if outcome.star && !header.is_starred() {
+                starred.push(header.uid);
+            }
+            for tag in outcome.labels {
+                labels.entry(tag).or_default().push(header.uid);
+            }
+            if outcome.delete {
+                deleted.push(header.uid);
+            } else if let Some(folder) = outcome.move_to.filter(|f| f != folder_path) {
+                moves.entry(folder).or_default().push(header.uid);
+            }
+            for rule_name in outcome.notify {
+                notify
+                    .entry(rule_name)
+                    .or_default()
+                    .push(header_to_db_message(folder_id, header));
+            }
+        }
+
+        // Flags first, so moved messages keep them
+        client.store_flags(&read, "\\Seen", true).await?;
+        client.store_flags(&starred, "\\Flagged", true).await?;
+        let mut flagged: Vec<u32> = read.iter().chain(&starred).copied().collect();
+        for (tag, uids) in &labels {
+            client.store_flags(uids, tag, true).await?;
+            flagged.extend(uids);
+        }
+
+        let mut gone: Vec<u32> = deleted.clone();
+        for (folder, uids) in &moves {
+            client.move_messages(uids, folder).await?;
+            gone.extend(uids);
+        }
+        if !deleted.is_empty() {
+            let trash = self.database.get_trash_folder(account_id).await?;
+            client.delete_messages(&deleted, trash.as_deref()).await?;
+        }
+        for uid in &gone {
+            self.database
+                .delete_message_by_uid(folder_id, *uid as i64)
+                .await?;
+        }
+
+        flagged.retain(|uid| !gone.contains(uid));
+        flagged.sort_unstable();
+        flagged.dedup();
+        if !flagged.is_empty() {
+            let flags = client
+                .uid_fetch_flags(&northmail_imap::format_uid_set(&flagged))
+                .await?;
+            self.apply_flags(account_id, folder_path, &flags, false)
+                .await?;
+        }
+
+        info!(
+            "Rules in {}: {} marked read, {} starred, {} tagged, {} moved, {} deleted",
+            folder_path,
+            read.len(),
+            starred.len(),
+            labels.values().map(Vec::len).sum::<usize>(),
+            moves.values().map(Vec::len).sum::<usize>(),
+            deleted.len()
+        );
+        for (rule_name, messages) in notify {
+            let _ = self
+                .event_tx
+                .send(SyncEvent::RuleMatched {
+                    account_id: account_id.to_string(),
+                    rule_name,
+                    messages,
+                })
+                .await;
+        }
+        Ok(())
+    }
+
+    /// Apply flags read from the server; with `prune`, also remove cached
+    /// messages the server no longer has
+    #[instrument(skip_all, fields(account = %account_id, folder = %folder_path, count = flags.len()))]
+    async fn apply_flags(
+        &mut self,
+        account_id: &str,
+        folder_path: &str,
+        flags: &[(u32, MessageFlags)],
+        prune: bool,
+    ) -> CoreResult<()> {
+        let folder_id = self
+            .database
+            .get_or_create_folder_id(account_id, folder_path)
+            .await?;
+        let update = self
+            .database
+            .batch_update_flags(folder_id, flags, &self.flag_policy)
+            .await?;
+        let updated = update.changed.len();
+        self.notify_flags_changed(account_id, folder_path, folder_id, update)
+            .await;
+
+        // An empty list more likely means a failed fetch than an empty folder
+        let removed = if prune && !flags.is_empty() {
+            let server_uids: Vec<i64> = flags.iter().map(|(uid, _)| *uid as i64).collect();
+            self.database
+                .delete_messages_not_in_uids(folder_id, &server_uids)
+                .await?
+        } else {
+            0
+        };
+
+        self.notify_cache_reconciled(account_id, folder_path, updated, removed)
+            .await;
+        Ok(())
+    }
+
+    /// Remove cached messages whose UIDs the server no longer lists
+    async fn prune_messages(
+        &mut self,
+        account_id: &str,
+        folder_path: &str,
+        server_uids: &[u32],
+    ) -> CoreResult<()> {
+        if server_uids.is_empty() {
+            return Ok(());
+        }
+        let folder_id = self
+            .database
+            .get_or_create_folder_id(account_id, folder_path)
+            .await?;
+        let server_uids: Vec<i64> = server_uids.iter().map(|&uid| uid as i64).collect();
+        let removed = self
+            .database
+            .delete_messages_not_in_uids(folder_id, &server_uids)
+            .await?;
+
+        self.notify_cache_reconciled(account_id, folder_path, 0, removed)
+            .await;
+        Ok(())
+    }
+
+    async fn notify_flags_changed(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+        folder_id: i64,
+        update: FlagUpdate,
+    ) {
+        if !update.conflicts.is_empty() {
+            info!(
+                "{} flag changes in {}/{} disagreed with the server",
+                update.conflicts.len(),
+                account_id,
+                folder_path
+            );
+            let _ = self
+                .event_tx
+                .send(SyncEvent::FlagConflicts {
+                    account_id: account_id.to_string(),
+                    folder_path: folder_path.to_string(),
+                    folder_id,
+                    conflicts: update.conflicts,
+                })
+                .await;
+        }
+        if update.changed.is_empty() {
+            return;
+        }
+        let _ = self
+            .event_tx
+            .send(SyncEvent::FlagsChanged {
+                account_id: account_id.to_string(),
+                folder_path: folder_path.to_string(),
+                folder_id,
+                changes: update.changed,
+            })
+            .await;
+    }
+
+    async fn notify_cache_reconciled(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+        updated: usize,
+        removed: u64,
+    ) {
+        if removed > 0 {
+            info!(
+                "Removed {} stale messages from {}/{}",
+                removed, account_id, folder_path
+            );
+        }
+        let _ = self
+            .event_tx
+            .send(SyncEvent::CacheReconciled {
+                account_id: account_id.to_string(),
+                folder_path: folder_path.to_string(),
+                updated,
+                removed,
+            })
+            .await;
+        let _ = self
+            .event_tx
+            .send(SyncEvent::MessagesUpdated {
+                account_id: account_id.to_string(),
+                folder_path: folder_path.to_string(),
+            })
+            .await;
+    }
+
     /// Fetch a full message body
+    #[instrument(skip_all, fields(account = %account_id, folder = %folder_path, uid = uid))]
     async fn fetch_message(
         &mut self,
         account_id: &str,
         folder_path: &str,
         uid: u32,
     ) -> CoreResult<()> {
-        let accounts = self.database.get_accounts().await?;
-        let account = accounts
-            .iter()
-            .find(|a| a.id == account_id)
-            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
-
-        let mut client = self.get_imap_client(account).await?;
-        client.select_folder(folder_path).await?;
+        let mut client = self.connect_account(account_id).await?;
+        client.select(folder_path).await?;
 
-        let body = client.fetch_body(uid).await?;
+        let body = client.fetch_raw(uid).await?;
         client.logout().await?;
 
         let _ = self
@@ -446,6 +1665,7 @@ impl SyncEngine {
     }
 
     /// Set message read status
+    #[instrument(skip_all, fields(account = %account_id, folder = %folder_path, uid = uid))]
     async fn set_read(
         &mut self,
         account_id: &str,
@@ -453,20 +1673,10 @@ impl SyncEngine {
         uid: u32,
         is_read: bool,
     ) -> CoreResult<()> {
-        let accounts = self.database.get_accounts().await?;
-        let account = accounts
-            .iter()
-            .find(|a| a.id == account_id)
-            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+        let mut client = self.connect_account(account_id).await?;
+        client.select(folder_path).await?;
 
-        let mut client = self.get_imap_client(account).await?;
-        client.select_folder(folder_path).await?;
-
-        if is_read {
-            client.mark_read(uid).await?;
-        } else {
-            client.mark_unread(uid).await?;
-        }
+        client.store_flags(&[uid], "\\Seen", is_read).await?;
 
         client.logout().await?;
 
@@ -474,6 +1684,7 @@ impl SyncEngine {
     }
 
     /// Move a message to another folder
+    #[instrument(skip_all, fields(account = %account_id, folder = %from_folder, uid = uid))]
     async fn move_message(
         &mut self,
         account_id: &str,
@@ -481,24 +1692,577 @@ impl SyncEngine {
         to_folder: &str,
         uid: u32,
     ) -> CoreResult<()> {
-        let accounts = self.database.get_accounts().await?;
-        let account = accounts
-            .iter()
-            .find(|a| a.id == account_id)
-            .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+        let mut client = self.connect_account(account_id).await?;
+        client.select(from_folder).await?;
+        client.move_messages(&[uid], to_folder).await?;
+        client.logout().await?;
+
+        Ok(())
+    }
+
+    /// Save a raw message into the account's Drafts or Sent folder
+    #[instrument(skip_all, fields(account = %account_id, size = message.len()))]
+    async fn append_message(
+        &mut self,
+        account_id: &str,
+        target: AppendTarget,
+        message: &[u8],
+    ) -> CoreResult<()> {
+        let folder = match target {
+            AppendTarget::Drafts => self.database.get_drafts_folder(account_id).await?,
+            AppendTarget::Sent => self.database.get_sent_folder(account_id).await?,
+        }
+        .unwrap_or_else(|| target.fallback_folder().to_string());
 
-        let mut client = self.get_imap_client(account).await?;
-        client.select_folder(from_folder).await?;
-        client.move_message(uid, to_folder).await?;
+        let mut client = self.connect_account(account_id).await?;
+        client.append(&folder, target.flags(), message).await?;
         client.logout().await?;
 
+        let _ = self
+            .event_tx
+            .send(SyncEvent::MessageAppended {
+                account_id: account_id.to_string(),
+                folder_path: folder,
+            })
+            .await;
+
         Ok(())
     }
+
+    /// Import an mbox file or a Maildir in a task of its own, on a
+    /// connection of its own, so syncing goes on while it uploads. A local
+    /// import only goes to the cache.
+    fn start_import(
+        &self,
+        account_id: String,
+        folder_path: String,
+        source: PathBuf,
+        limits: ImportLimits,
+        local: bool,
+    ) {
+        let connector = self.connector.clone();
+        let database = self.database.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let mut counts = ImportCounts::default();
+            let result = if local {
+                import_local(&database, &account_id, &folder_path, &source, &mut counts).await
+            } else {
+                upload_messages(
+                    connector.as_ref(),
+                    &event_tx,
+                    &account_id,
+                    &folder_path,
+                    &source,
+                    &limits,
+                    &mut counts,
+                )
+                .await
+            };
+            if local && counts.imported > 0 {
+                let _ = event_tx
+                    .send(SyncEvent::FoldersUpdated {
+                        account_id: account_id.clone(),
+                    })
+                    .await;
+            }
+            match &result {
+                Ok(()) => info!(
+                    "Imported {} messages into {} ({} already there)",
+                    counts.imported, folder_path, counts.skipped
+                ),
+                Err(e) => warn!(
+                    "Import into {} stopped after {} messages: {}",
+                    folder_path, counts.imported, e
+                ),
+            }
+            let _ = event_tx
+                .send(SyncEvent::ImportFinished {
+                    account_id,
+                    folder_path,
+                    counts,
+                    error: result.err().map(|e| e.to_string()),
+                })
+                .await;
+        });
+    }
+
+    /// Export a folder as a Maildir in the background, reporting the result
+    /// with [`SyncEvent::MaildirExported`]
+    fn start_export(&self, account_id: String, folder_path: String, destination: PathBuf) {
+        let connector = self.connector.clone();
+        let database = self.database.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            // Offline, cached copies are better than nothing
+            let mut client = match connector.connect(&account_id).await {
+                Ok(client) => Some(client),
+                Err(e) => {
+                    warn!("Exporting {} from the cache only: {}", folder_path, e);
+                    None
+                }
+            };
+            let result = database
+                .export_maildir(&account_id, &folder_path, &destination, client.as_mut())
+                .await;
+            if let Some(mut client) = client {
+                let _ = client.logout().await;
+            }
+
+            let (counts, error) = match result {
+                Ok(counts) => {
+                    info!(
+                        "Exported {} messages from {} to {} ({} already there, {} missing)",
+                        counts.exported,
+                        folder_path,
+                        destination.display(),
+                        counts.skipped,
+                        counts.missing
+                    );
+                    (counts, None)
+                }
+                Err(e) => {
+                    warn!("Export of {} failed: {}", folder_path, e);
+                    (ExportCounts::default(), Some(e.to_string()))
+                }
+            };
+            let _ = event_tx
+                .send(SyncEvent::MaildirExported {
+                    account_id,
+                    folder_path,
+                    destination,
+                    counts,
+                    error,
+                })
+                .await;
+        });
+    }
+
+    /// Save a message as an `.eml` file in the background, reporting the
+    /// result with [`SyncEvent::MessageSaved`]
+    fn start_save_message(
+        &self,
+        account_id: String,
+        folder_path: String,
+        uid: u32,
+        destination: PathBuf,
+    ) {
+        let connector = self.connector.clone();
+        let database = self.database.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let result = save_message(
+                connector.as_ref(),
+                &database,
+                &account_id,
+                &folder_path,
+                uid,
+                &destination,
+            )
+            .await;
+            let (rebuilt, error) = match result {
+                Ok(rebuilt) => {
+                    info!("Saved message {} of {} to {}", uid, folder_path, destination.display());
+                    (rebuilt, None)
+                }
+                Err(e) => {
+                    warn!("Saving message {} of {} failed: {}", uid, folder_path, e);
+                    (false, Some(e.to_string()))
+                }
+            };
+            let _ = event_tx
+                .send(SyncEvent::MessageSaved {
+                    account_id,
+                    folder_path,
+                    uid,
+                    destination,
+                    rebuilt,
+                    error,
+                })
+                .await;
+        });
+    }
+
+    /// Get an authenticated IMAP client for an account by ID
+    async fn connect_account(&self, account_id: &str) -> CoreResult<ImapClient> {
+        self.connector.connect(account_id).await
+    }
+
+    async fn notify_folders_updated(&self, account_id: &str) {
+        let _ = self
+            .event_tx
+            .send(SyncEvent::FoldersUpdated {
+                account_id: account_id.to_string(),
+            })
+            .await;
+    }
+
+    /// Create a folder, e.g. an Archive folder on providers that lack one.
+    /// New folders are subscribed so they show up in other clients too.
+    async fn create_folder(&mut self, account_id: &str, folder_path: &str) -> CoreResult<()> {
+        let mut client = self.connect_account(account_id).await?;
+        client.create_folder(folder_path).await?;
+        if let Err(e) = client.subscribe_folder(folder_path).await {
+            debug!("Could not subscribe to new folder {}: {}", folder_path, e);
+        }
+        client.logout().await?;
+
+        let leaf = folder_path.rsplit('/').next().unwrap_or(folder_path);
+        let folder = northmail_imap::Folder::new(
+            leaf.to_string(),
+            folder_path.to_string(),
+            None,
+            Vec::new(),
+        );
+        let folder_type = format!("{:?}", folder.folder_type).to_lowercase();
+        self.database
+            .upsert_folder(account_id, &folder.name, &folder.full_path, &folder_type)
+            .await?;
+        self.database
+            .set_folder_display_names(account_id, folder_path, &folder.name, &folder.display_path())
+            .await?;
+
+        self.notify_folders_updated(account_id).await;
+        Ok(())
+    }
+
+    /// Rename a folder on the server and in the cache
+    async fn rename_folder(
+        &mut self,
+        account_id: &str,
+        from_path: &str,
+        to_path: &str,
+    ) -> CoreResult<()> {
+        let mut client = self.connect_account(account_id).await?;
+        client.rename_folder(from_path, to_path).await?;
+        client.logout().await?;
+
+        self.database
+            .rename_folder_path(account_id, from_path, to_path)
+            .await?;
+
+        self.notify_folders_updated(account_id).await;
+        Ok(())
+    }
+
+    /// Delete a folder on the server and drop it from the cache
+    async fn delete_folder(&mut self, account_id: &str, folder_path: &str) -> CoreResult<()> {
+        let mut client = self.connect_account(account_id).await?;
+        client.delete_folder(folder_path).await?;
+        client.logout().await?;
+
+        self.database
+            .delete_folder_by_path(account_id, folder_path)
+            .await?;
+
+        self.notify_folders_updated(account_id).await;
+        Ok(())
+    }
+
+    /// Change a folder's subscription on the server and in the cache
+    async fn set_subscribed(
+        &mut self,
+        account_id: &str,
+        folder_path: &str,
+        subscribed: bool,
+    ) -> CoreResult<()> {
+        let mut client = self.connect_account(account_id).await?;
+        if subscribed {
+            client.subscribe_folder(folder_path).await?;
+        } else {
+            client.unsubscribe_folder(folder_path).await?;
+        }
+        client.logout().await?;
+
+        self.database
+            .set_folder_subscribed(account_id, folder_path, subscribed)
+            .await?;
+
+        self.notify_folders_updated(account_id).await;
+        Ok(())
+    }
+}
+
+/// Write a message's source to `destination` as an `.eml` file, fetched
+/// from the server when it can be reached and otherwise rebuilt from the
+/// cache. Returns whether it was rebuilt.
+async fn save_message(
+    connector: &dyn ImapConnector,
+    database: &Arc<Database>,
+    account_id: &str,
+    folder_path: &str,
+    uid: u32,
+    destination: &Path,
+) -> CoreResult<bool> {
+    let source = if jmap::is_jmap_account(database, account_id).await? {
+        let fetched = match JmapAccount::connect(database.clone(), account_id).await {
+            Ok(account) => account.fetch_raw(folder_path, uid).await,
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Ok(data) if !data.is_empty() => Some(MessageSource { data, rebuilt: false }),
+            Ok(_) => database.message_source(account_id, folder_path, uid, None).await?,
+            Err(e) => {
+                warn!("Saving message {} from the cache only: {}", uid, e);
+                database.message_source(account_id, folder_path, uid, None).await?
+            }
+        }
+    } else {
+        // Offline, a cached copy is better than nothing
+        let mut client = match connector.connect(account_id).await {
+            Ok(client) => Some(client),
+            Err(e) => {
+                warn!("Saving message {} from the cache only: {}", uid, e);
+                None
+            }
+        };
+        let source = database
+            .message_source(account_id, folder_path, uid, client.as_mut())
+            .await;
+        if let Some(mut client) = client {
+            let _ = client.logout().await;
+        }
+        source?
+    };
+
+    let source = source.ok_or(CoreError::MessageNotFound(uid as i64))?;
+    let destination = destination.to_path_buf();
+    let data = source.data;
+    tokio::task::spawn_blocking(move || eml::write(&destination, &data))
+        .await
+        .map_err(|e| CoreError::SyncError(e.to_string()))??;
+    Ok(source.rebuilt)
+}
+
+/// Read the messages of the mbox file or Maildir at `source`, off the
+/// async threads
+async fn read_import_source(source: &Path) -> CoreResult<Vec<import::ImportMessage>> {
+    let source = source.to_path_buf();
+    let messages = tokio::task::spawn_blocking(move || import::read_source(&source))
+        .await
+        .map_err(|e| CoreError::SyncError(e.to_string()))??;
+    Ok(messages)
+}
+
+/// Keep the messages of the mbox file or Maildir at `source` in the local
+/// folder `folder_path`, skipping those it already holds. `counts` says how
+/// far it got, also when it fails.
+#[instrument(skip_all, fields(account = %account_id, folder = %folder_path))]
+async fn import_local(
+    database: &Database,
+    account_id: &str,
+    folder_path: &str,
+    source: &Path,
+    counts: &mut ImportCounts,
+) -> CoreResult<()> {
+    let messages = read_import_source(source).await?;
+    counts.total = messages.len();
+
+    let folder_id = database.create_local_folder(account_id, folder_path).await?;
+    let existing: HashSet<String> = database
+        .get_folder_message_ids(folder_id)
+        .await?
+        .iter()
+        .filter_map(|id| import::normalize_message_id(id))
+        .collect();
+    let (messages, skipped) = import::skip_duplicates(messages, &existing);
+    counts.skipped = skipped;
+    info!(
+        "Keeping {} of {} messages from {} in a local folder",
+        messages.len(),
+        counts.total,
+        source.display()
+    );
+
+    let local: Vec<_> = messages.iter().filter_map(import::local_message).collect();
+    if local.len() < messages.len() {
+        warn!("{} messages couldn't be parsed", messages.len() - local.len());
+    }
+    database.store_local_messages(folder_id, &local).await?;
+    counts.imported = local.len();
+    Ok(())
+}
+
+/// Upload the messages of the mbox file or Maildir at `source` to a
+/// folder, skipping those it already holds, in batches within `limits`.
+/// Servers with MULTIAPPEND get each batch in one command, others one
+/// message at a time. A throttled upload is tried again after a pause, on
+/// a new connection. `counts` says how far it got, also when it fails.
+#[instrument(skip_all, fields(account = %account_id, folder = %folder_path))]
+async fn upload_messages(
+    connector: &dyn ImapConnector,
+    event_tx: &mpsc::Sender<SyncEvent>,
+    account_id: &str,
+    folder_path: &str,
+    source: &Path,
+    limits: &ImportLimits,
+    counts: &mut ImportCounts,
+) -> CoreResult<()> {
+    let messages = read_import_source(source).await?;
+    counts.total = messages.len();
+
+    let mut client = connector.connect(account_id).await?;
+    client.examine(folder_path).await?;
+    let existing: HashSet<String> = client
+        .fetch_message_ids()
+        .await?
+        .iter()
+        .filter_map(|id| import::normalize_message_id(id))
+        .collect();
+    let (messages, skipped) = import::skip_duplicates(messages, &existing);
+    counts.skipped = skipped;
+    let multiappend = client.capabilities().await?.multiappend;
+    info!(
+        "Importing {} of {} messages from {}",
+        messages.len(),
+        counts.total,
+        source.display()
+    );
+
+    let sizes: Vec<usize> = messages.iter().map(|m| m.data.len()).collect();
+    for batch in import::plan_batches(&sizes, limits) {
+        let batch = &messages[batch];
+        let per_command = if multiappend { batch.len() } else { 1 };
+        for unit in batch.chunks(per_command) {
+            let mut attempt = 0;
+            loop {
+                let flags: Vec<Vec<&str>> = unit.iter().map(|m| m.flags()).collect();
+                let result = if multiappend {
+                    let unit: Vec<(&[&str], &[u8])> = unit
+                        .iter()
+                        .zip(&flags)
+                        .map(|(m, flags)| (flags.as_slice(), m.data.as_slice()))
+                        .collect();
+                    client.append_many(folder_path, &unit).await
+                } else {
+                    client
+                        .append(folder_path, &flags[0], &unit[0].data)
+                        .await
+                        .map(|_| ())
+                };
+                match result {
+                    Ok(()) => break,
+                    Err(e)
+                        if attempt < import::THROTTLE_RETRIES
+                            && import::is_throttled(&e.to_string()) =>
+                    {
+                        attempt += 1;
+                        let pause = limits.retry_pause(attempt);
+                        warn!("Upload throttled ({}), trying again in {:?}", e, pause);
+                        tokio::time::sleep(pause).await;
+                        client = connector.connect(account_id).await?;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            counts.imported += unit.len();
+        }
+
+        let _ = event_tx
+            .send(SyncEvent::ImportProgress {
+                account_id: account_id.to_string(),
+                folder_path: folder_path.to_string(),
+                counts: *counts,
+            })
+            .await;
+        tokio::time::sleep(limits.pause).await;
+    }
+
+    client.logout().await?;
+    Ok(())
+}
+
+/// A cache row for a fetched header
+/// The headers rules can match, from a message's envelope
+fn rule_message(header: &MessageHeader) -> RuleMessage {
+    let format = |a: &northmail_imap::EmailAddress| match &a.name {
+        Some(name) => format!("{} <{}>", name, a.address),
+        None => a.address.clone(),
+    };
+    let envelope = &header.envelope;
+    let mut message = RuleMessage::new();
+    for (name, addresses) in [
+        ("From", &envelope.from),
+        ("To", &envelope.to),
+        ("Cc", &envelope.cc),
+        ("Reply-To", &envelope.reply_to),
+    ] {
+        for address in addresses {
+            message = message.header(name, format(address));
+        }
+    }
+    for (name, value) in [
+        ("Subject", &envelope.subject),
+        ("Message-ID", &envelope.message_id),
+        ("In-Reply-To", &envelope.in_reply_to),
+    ] {
+        if let Some(value) = value {
+            message = message.header(name, value.as_str());
+        }
+    }
+    message
+}
+
+fn header_to_db_message(folder_id: i64, header: &MessageHeader) -> DbMessage {
+    let join = |addresses: &[northmail_imap::EmailAddress]| {
+        addresses
+            .iter()
+            .map(|a| a.address.clone())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    DbMessage {
+        id: 0, // Will be assigned by database
+        folder_id,
+        uid: header.uid as i64,
+        message_id: header.envelope.message_id.clone(),
+        subject: header.envelope.subject.clone(),
+        from_address: header.envelope.from.first().map(|a| a.address.clone()),
+        from_name: header.envelope.from.first().and_then(|a| a.name.clone()),
+        to_addresses: Some(join(&header.envelope.to)),
+        cc_addresses: if header.envelope.cc.is_empty() {
+            None
+        } else {
+            Some(join(&header.envelope.cc))
+        },
+        date_sent: header.envelope.date.clone(),
+        date_epoch: header.envelope.date.as_deref().and_then(parse_date_epoch),
+        snippet: None, // Would need BODY[TEXT] for snippet
+        is_read: header.is_read(),
+        is_starred: header.is_starred(),
+        has_attachments: header.has_attachments,
+        size: header.size as i64,
+        maildir_path: None,
+        body_text: None,
+        body_html: None,
+        gmail_labels: crate::gmail::encode_labels(&header.gmail_labels),
+        gmail_thread_id: header.gmail_thread_id.map(|id| id as i64),
+        mention: None,
+        tags: crate::tags::encode_tags(&header.flags.keywords()),
+        snoozed_until: None,
+        reply_later_at: None,
+    }
+}
+
+/// Unix time of an envelope date, tolerating a trailing `(UTC)` comment and
+/// stray spaces
+fn parse_date_epoch(date: &str) -> Option<i64> {
+    let mut s = date.to_string();
+    if let Some(paren) = s.rfind('(') {
+        s = s[..paren].trim().to_string();
+    }
+    while s.contains("  ") {
+        s = s.replace("  ", " ");
+    }
+    s = s.replace(" ,", ",");
+    chrono::DateTime::parse_from_rfc2822(&s)
+        .map(|dt| dt.timestamp())
+        .ok()
 }
 
 /// Create sync engine channels
 /// Returns (command_sender, command_receiver, event_sender, event_receiver)
-#[allow(dead_code)]
 pub fn create_sync_channels() -> (
     mpsc::Sender<SyncCommand>,
     mpsc::Receiver<SyncCommand>,
@@ -0,0 +1,120 @@
+//! Per-folder sync policies
+//!
+//! Folders aren't all alike: INBOX wants new mail quickly, while an 80,000
+//! message Archive is better left alone until it's opened. Each folder can
+//! have its own [`FolderSyncPolicy`]: how often the sync engine syncs it in
+//! the background, how far back its messages are fetched, whether bodies
+//! are prefetched, and, for inboxes, whether it shows in the unified inbox.
+//! The default policy keeps the app-wide behaviour.
+
+use crate::bandwidth::BandwidthPolicy;
+
+/// Background sync intervals offered, in minutes; `None` leaves the folder
+/// to the account's periodic check (inboxes) or to when it's opened
+pub const SYNC_INTERVALS: [Option<u32>; 5] = [None, Some(5), Some(15), Some(60), Some(24 * 60)];
+
+/// Sync windows offered, in days; `None` follows the app-wide setting
+pub const SYNC_DAYS: [Option<u32>; 5] = [None, Some(7), Some(30), Some(90), Some(365)];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A folder's sync settings. The default follows the app-wide ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FolderSyncPolicy {
+    /// Minutes between background syncs
+    pub interval_minutes: Option<u32>,
+    /// Days back messages are fetched
+    pub sync_days: Option<u32>,
+    /// Bodies are prefetched in the background; without it only headers
+    /// are, and a body is downloaded when its message is opened
+    pub fetch_bodies: bool,
+    /// An inbox shows in the unified inbox
+    pub unified_inbox: bool,
+}
+
+impl Default for FolderSyncPolicy {
+    fn default() -> Self {
+        Self {
+            interval_minutes: None,
+            sync_days: None,
+            fetch_bodies: true,
+            unified_inbox: true,
+        }
+    }
+}
+
+impl FolderSyncPolicy {
+    /// Whether the folder is due a background sync at `now`, having last
+    /// been synced at `synced_at`. Intervals stretch in low-bandwidth mode
+    /// like the periodic check does, and a last sync in the future means
+    /// the clock was turned back, so that's due too.
+    pub fn is_due(&self, synced_at: Option<i64>, now: i64, bandwidth: &BandwidthPolicy) -> bool {
+        let Some(minutes) = self.interval_minutes else {
+            return false;
+        };
+        let interval = i64::from(bandwidth.poll_interval(minutes)) * 60;
+        synced_at.is_none_or(|at| at > now || now - at >= interval)
+    }
+
+    /// Date (Unix seconds) of the oldest messages to fetch at `now`
+    pub fn since(&self, now: i64) -> Option<i64> {
+        self.sync_days
+            .map(|days| now.saturating_sub(i64::from(days) * SECONDS_PER_DAY))
+    }
+
+    /// The sync window as an IMAP SEARCH date (e.g. `1-Jan-2024`)
+    pub fn since_date(&self, now: i64) -> Option<String> {
+        let since = chrono::DateTime::from_timestamp(self.since(now)?, 0)?;
+        Some(since.format("%-d-%b-%Y").to_string())
+    }
+}
+
+/// A folder with a background sync interval
+#[derive(Debug, Clone)]
+pub struct ScheduledFolder {
+    pub folder_id: i64,
+    pub account_id: String,
+    pub full_path: String,
+    pub policy: FolderSyncPolicy,
+    /// When the sync engine last synced it on schedule, as a Unix timestamp
+    pub synced_at: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bandwidth::LowBandwidthMode;
+
+    #[test]
+    fn test_is_due() {
+        let normal = BandwidthPolicy::new(LowBandwidthMode::Never, false, false);
+        let low = BandwidthPolicy::new(LowBandwidthMode::Always, false, false);
+        let now = 1_700_000_000;
+        let policy = FolderSyncPolicy {
+            interval_minutes: Some(15),
+            ..Default::default()
+        };
+
+        assert!(!FolderSyncPolicy::default().is_due(None, now, &normal));
+        assert!(policy.is_due(None, now, &normal));
+        assert!(!policy.is_due(Some(now - 10 * 60), now, &normal));
+        assert!(policy.is_due(Some(now - 15 * 60), now, &normal));
+        // Four times as long in low-bandwidth mode
+        assert!(!policy.is_due(Some(now - 15 * 60), now, &low));
+        assert!(policy.is_due(Some(now - 60 * 60), now, &low));
+        assert!(policy.is_due(Some(now + 60), now, &normal));
+    }
+
+    #[test]
+    fn test_since_date() {
+        let policy = FolderSyncPolicy {
+            sync_days: Some(30),
+            ..Default::default()
+        };
+        // 2024-03-01 12:00 UTC
+        let now = 1_709_294_400;
+        assert_eq!(policy.since(now), Some(now - 30 * SECONDS_PER_DAY));
+        assert_eq!(policy.since_date(now).as_deref(), Some("31-Jan-2024"));
+        assert_eq!(FolderSyncPolicy::default().since_date(now), None);
+    }
+}
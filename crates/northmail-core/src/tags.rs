@@ -0,0 +1,195 @@
+//! Message tags from IMAP keywords
+//!
+//! Keywords are the flags without a backslash (`$Work`, `$label1`,
+//! `project-x`) that clients set on messages to tag them. They are fetched
+//! with the other flags, cached as a JSON array in the `tags` column and
+//! stored back with UID STORE, so tags set here show up in other clients
+//! and the other way round. Keywords servers and clients set for their own
+//! bookkeeping are kept but not shown.
+//!
+//! Each tag also has an entry in the `tags` table, which gives it a colour,
+//! and links in `message_tags` to the messages carrying it, so a tag's
+//! messages across every account are found without reading each message's
+//! keywords. Gmail labels count as tags of the same name.
+
+/// Keywords used for bookkeeping rather than tagging (RFC 5788 registry
+/// and common junk markers), lowercased
+const SYSTEM_KEYWORDS: &[&str] = &[
+    "$forwarded",
+    "$mdnsent",
+    "$submitpending",
+    "$submitted",
+    "$junk",
+    "$notjunk",
+    "$phishing",
+    "$hasattachment",
+    "$hasnoattachment",
+    "$muted",
+    "junk",
+    "nonjunk",
+    "notjunk",
+];
+
+/// Names Thunderbird gives its five built-in tag keywords
+const THUNDERBIRD_LABELS: &[(&str, &str)] = &[
+    ("$label1", "Important"),
+    ("$label2", "Work"),
+    ("$label3", "Personal"),
+    ("$label4", "To Do"),
+    ("$label5", "Later"),
+];
+
+/// Colours offered for tags (the GNOME palette)
+pub const TAG_COLORS: &[&str] = &[
+    "#3584e4", "#2190a4", "#3a944a", "#c88800", "#ed5b00", "#e62d42", "#d56199", "#9141ac",
+    "#6f8396",
+];
+
+/// Colour of a tag that wasn't given one; a name always gets the same colour
+pub fn default_color(name: &str) -> &'static str {
+    let hash = name
+        .to_lowercase()
+        .bytes()
+        .fold(0u32, |hash, b| hash.wrapping_mul(31).wrapping_add(b as u32));
+    TAG_COLORS[hash as usize % TAG_COLORS.len()]
+}
+
+/// SQL condition on `value` matching [`is_user_tag`]
+pub fn user_tag_sql(value: &str) -> String {
+    let system: Vec<String> = SYSTEM_KEYWORDS.iter().map(|k| format!("'{}'", k)).collect();
+    format!(
+        "substr({value}, 1, 1) <> '\\' AND lower({value}) NOT IN ({})",
+        system.join(", ")
+    )
+}
+
+/// Keywords among a message's flags, in the order the server sent them
+pub fn keywords_from_flags<'a>(flags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for flag in flags {
+        if flag.starts_with('\\') || keywords.iter().any(|k| k.eq_ignore_ascii_case(flag)) {
+            continue;
+        }
+        keywords.push(flag.to_string());
+    }
+    keywords
+}
+
+/// Serialize keywords for the `tags` column. An empty list is kept as `[]`
+/// so a sync that finds none clears tags removed elsewhere.
+pub fn encode_tags(keywords: &[String]) -> Option<String> {
+    serde_json::to_string(keywords).ok()
+}
+
+/// Keywords from the `tags` column
+pub fn decode_tags(json: Option<&str>) -> Vec<String> {
+    json.and_then(|j| serde_json::from_str(j).ok()).unwrap_or_default()
+}
+
+/// Whether a keyword is a tag the user would recognise, rather than
+/// bookkeeping like `$Forwarded` or `NonJunk`
+pub fn is_user_tag(keyword: &str) -> bool {
+    let lower = keyword.to_lowercase();
+    !keyword.starts_with('\\') && !SYSTEM_KEYWORDS.contains(&lower.as_str())
+}
+
+/// Keywords worth showing as tags
+pub fn user_tags(keywords: &[String]) -> Vec<String> {
+    keywords.iter().filter(|k| is_user_tag(k)).cloned().collect()
+}
+
+/// How to show a keyword: Thunderbird's numbered labels by name, others
+/// without the leading `$`
+pub fn display_name(keyword: &str) -> String {
+    let lower = keyword.to_lowercase();
+    if let Some((_, name)) = THUNDERBIRD_LABELS.iter().find(|(k, _)| *k == lower) {
+        return name.to_string();
+    }
+    keyword.strip_prefix('$').unwrap_or(keyword).to_string()
+}
+
+/// The keyword for a tag typed by the user. IMAP keywords are atoms, so
+/// spaces become underscores and characters atoms can't hold are dropped.
+/// `None` when nothing usable is left.
+pub fn keyword_for_tag(tag: &str) -> Option<String> {
+    let keyword: String = tag
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('_'),
+            '(' | ')' | '{' | '%' | '*' | '"' | '\\' | ']' => None,
+            c if c.is_ascii_graphic() => Some(c),
+            _ => None,
+        })
+        .collect();
+    if keyword.is_empty() {
+        None
+    } else {
+        Some(keyword)
+    }
+}
+
+/// Parse a comma-separated tag list as typed by the user into keywords,
+/// without duplicates
+pub fn parse_tag_list(text: &str) -> Vec<String> {
+    let keywords: Vec<String> = text.split(',').filter_map(keyword_for_tag).collect();
+    keywords_from_flags(keywords.iter().map(String::as_str))
+}
+
+/// Keywords to add and to remove to go from `old` to `new`; keywords
+/// compare case-insensitively
+pub fn tag_changes(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let contains = |list: &[String], k: &str| list.iter().any(|x| x.eq_ignore_ascii_case(k));
+    let added = new.iter().filter(|k| !contains(old, k)).cloned().collect();
+    let removed = old.iter().filter(|k| !contains(new, k)).cloned().collect();
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keywords_from_flags() {
+        let flags = ["\\Seen", "$Work", "$Forwarded", "project-x", "$work"];
+        let keywords = keywords_from_flags(flags);
+        assert_eq!(keywords, vec!["$Work", "$Forwarded", "project-x"]);
+        assert_eq!(user_tags(&keywords), vec!["$Work", "project-x"]);
+        assert_eq!(decode_tags(encode_tags(&keywords).as_deref()), keywords);
+        assert_eq!(encode_tags(&[]).as_deref(), Some("[]"));
+        assert!(decode_tags(None).is_empty());
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name("$Work"), "Work");
+        assert_eq!(display_name("$Label4"), "To Do");
+        assert_eq!(display_name("project-x"), "project-x");
+    }
+
+    #[test]
+    fn test_tag_editing() {
+        assert_eq!(keyword_for_tag(" Big (deal) "), Some("Big_deal".to_string()));
+        assert_eq!(keyword_for_tag("\\Seen"), Some("Seen".to_string()));
+        assert_eq!(keyword_for_tag("()"), None);
+        assert_eq!(parse_tag_list("$Work, work,, To do"), vec!["$Work", "work", "To_do"]);
+
+        let old = vec!["$Work".to_string(), "later".to_string()];
+        let new = vec!["$work".to_string(), "urgent".to_string()];
+        assert_eq!(tag_changes(&old, &new), (vec!["urgent".to_string()], vec!["later".to_string()]));
+    }
+
+    #[test]
+    fn test_default_color() {
+        assert_eq!(default_color("$Work"), default_color("$work"));
+        assert!(TAG_COLORS.contains(&default_color("project-x")));
+        assert!(TAG_COLORS.contains(&default_color("")));
+    }
+
+    #[test]
+    fn test_user_tag_sql() {
+        let sql = user_tag_sql("j.value");
+        assert!(sql.starts_with("substr(j.value, 1, 1) <> '\\' AND lower(j.value) NOT IN ('$forwarded', "));
+        assert!(sql.ends_with("'notjunk')"));
+    }
+}
@@ -0,0 +1,224 @@
+//! Thread participation helpers
+//!
+//! Threads are matched by normalized subject, which works for the cache even
+//! when full References chains were never stored. Quoted history inside a
+//! reply is attributed to its authors from the "... wrote:" lines.
+//!
+//! Each cached message carries a [`thread_key`], so the database can sum up
+//! whole threads ([`ThreadSummary`]) in one query per page of a list.
+
+use crate::address::Address;
+
+/// Minimum cached messages in a thread before suggesting recipient trimming
+pub const TRIM_MIN_THREAD_MESSAGES: usize = 4;
+
+/// Minimum recipients on a reply before suggesting recipient trimming
+pub const TRIM_MIN_RECIPIENTS: usize = 4;
+
+/// Strip reply/forward prefixes (`Re:`, `Fwd:`, `AW:`, `[list]` tags...)
+/// and lowercase, so all messages of a thread share one key
+pub fn normalize_subject(subject: &str) -> String {
+    let mut s = subject.trim();
+    loop {
+        let before = s;
+        if s.starts_with('[') {
+            if let Some(end) = s.find(']') {
+                s = s[end + 1..].trim_start();
+            }
+        }
+        if let Some((prefix, rest)) = s.split_once(':') {
+            let prefix = prefix.trim().to_lowercase();
+            // Allow counters like "Re[2]"
+            let prefix = prefix.split('[').next().unwrap_or("");
+            if matches!(prefix, "re" | "fw" | "fwd" | "aw" | "wg" | "sv" | "vs" | "tr" | "rif") {
+                s = rest.trim_start();
+            }
+        }
+        if s == before {
+            break;
+        }
+    }
+    s.to_lowercase()
+}
+
+/// Key grouping an account's cached messages into threads: the Gmail
+/// conversation id when there is one, else the normalized subject. Messages
+/// without a subject are threads of their own, keyed by their row id.
+pub fn thread_key(gmail_thread_id: Option<i64>, subject: Option<&str>, row_id: i64) -> String {
+    if let Some(thread_id) = gmail_thread_id {
+        return format!("g:{}", thread_id);
+    }
+    let subject = normalize_subject(subject.unwrap_or(""));
+    if subject.is_empty() {
+        format!("m:{}", row_id)
+    } else {
+        format!("s:{}", subject)
+    }
+}
+
+/// A thread as a conversation list shows it. Copies of a message in several
+/// folders count once; Trash and Junk are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadSummary {
+    pub account_id: String,
+    pub thread_key: String,
+    /// Row id of the newest message
+    pub latest_id: i64,
+    /// Subject of the newest message
+    pub subject: Option<String>,
+    pub latest_epoch: Option<i64>,
+    pub message_count: i64,
+    pub unread_count: i64,
+    /// Whether any message is starred
+    pub is_starred: bool,
+    pub has_attachments: bool,
+    /// Senders, in the order they first wrote
+    pub participants: Vec<Address>,
+}
+
+/// A cached message whose flags a thread-level change touched, for the
+/// caller to set on the server too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadMessageRef {
+    pub id: i64,
+    pub folder_id: i64,
+    pub uid: i64,
+}
+
+/// Recipients who never sent a message in the thread, in their original order.
+/// Comparison is case-insensitive on the bare address.
+pub fn inactive_recipients(recipients: &[String], thread_senders: &[String]) -> Vec<String> {
+    let senders: Vec<String> = thread_senders.iter().map(|s| s.trim().to_lowercase()).collect();
+    recipients
+        .iter()
+        .filter(|r| !senders.contains(&r.trim().to_lowercase()))
+        .cloned()
+        .collect()
+}
+
+/// Number of `>` markers quoting a plain-text line, and the text after them
+pub fn split_quote(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+    while let Some(inner) = rest.trim_start_matches(' ').strip_prefix('>') {
+        depth += 1;
+        rest = inner;
+    }
+    (depth, if depth > 0 { rest.strip_prefix(' ').unwrap_or(rest) } else { rest })
+}
+
+/// Who an attribution line such as `On Tue, 4 Mar 2025, Ann <ann@example.com> wrote:`
+/// names: the lowercased address when one is given, otherwise the name
+pub fn attribution_author(line: &str) -> Option<String> {
+    let who = line.trim().strip_suffix("wrote:")?.trim_end();
+    if let (Some(open), Some(close)) = (who.rfind('<'), who.rfind('>')) {
+        let email = who.get(open + 1..close).unwrap_or_default().trim();
+        if email.contains('@') {
+            return Some(email.to_lowercase());
+        }
+    }
+    // "On <date>, <name>": the name follows the last comma
+    let who = match who.strip_prefix("On ") {
+        Some(rest) => rest.rsplit(',').next().unwrap_or(rest),
+        None => who,
+    };
+    let who = who.trim().trim_matches('"');
+    (!who.is_empty()).then(|| who.to_string())
+}
+
+/// Author of each quote level in a reply, outermost first (`[0]` wrote the
+/// text at depth 1). Attributions at the same depth fill the next free level,
+/// so text flattened from HTML, where quotes lost their `>` markers but
+/// attributions still appear in nesting order, is attributed too.
+pub fn quote_authors(text: &str) -> Vec<Option<String>> {
+    let mut authors: Vec<Option<String>> = Vec::new();
+    let mut previous = "";
+    for line in text.lines() {
+        let (depth, content) = split_quote(line);
+        // Long attributions get wrapped: "On <date>, Ann <" / "ann@example.com> wrote:"
+        let author = if previous.starts_with("On ") && !previous.ends_with("wrote:") {
+            attribution_author(&format!("{} {}", previous, content.trim()))
+        } else {
+            None
+        }
+        .or_else(|| attribution_author(content));
+        previous = content.trim();
+
+        let Some(author) = author else { continue };
+        let mut level = depth;
+        while authors.get(level).is_some_and(|a| a.is_some()) {
+            level += 1;
+        }
+        if authors.len() <= level {
+            authors.resize(level + 1, None);
+        }
+        authors[level] = Some(author);
+    }
+    authors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_subject() {
+        assert_eq!(normalize_subject("Re: RE: Fwd: Budget"), "budget");
+        assert_eq!(normalize_subject("[team] Re[2]: Budget "), "budget");
+        assert_eq!(normalize_subject("AW: Budget"), "budget");
+        assert_eq!(normalize_subject("Agenda: Monday"), "agenda: monday");
+    }
+
+    #[test]
+    fn test_thread_key() {
+        assert_eq!(thread_key(Some(42), Some("Re: Budget"), 1), "g:42");
+        assert_eq!(thread_key(None, Some("Re: Budget"), 1), thread_key(None, Some("Budget"), 2));
+        assert_eq!(thread_key(None, Some("Fwd: "), 7), "m:7");
+        assert_eq!(thread_key(None, None, 8), "m:8");
+    }
+
+    #[test]
+    fn test_inactive_recipients() {
+        let recipients = vec![
+            "ann@example.com".to_string(),
+            "Bob@Example.com".to_string(),
+            "carol@example.com".to_string(),
+        ];
+        let senders = vec!["bob@example.com".to_string(), "ann@example.com".to_string()];
+        assert_eq!(inactive_recipients(&recipients, &senders), vec!["carol@example.com".to_string()]);
+    }
+
+    #[test]
+    fn test_split_quote() {
+        assert_eq!(split_quote("> > nested"), (2, "nested"));
+        assert_eq!(split_quote(">>tight"), (2, "tight"));
+        assert_eq!(split_quote("plain > text"), (0, "plain > text"));
+    }
+
+    #[test]
+    fn test_attribution_author() {
+        assert_eq!(
+            attribution_author("On Tue, 4 Mar 2025 at 10:02, Ann Lee <Ann@Example.com> wrote:").as_deref(),
+            Some("ann@example.com")
+        );
+        assert_eq!(attribution_author("On Tue, 4 Mar 2025, Bob Stone wrote:").as_deref(), Some("Bob Stone"));
+        assert_eq!(attribution_author("\"Carol\" wrote:").as_deref(), Some("Carol"));
+        assert_eq!(attribution_author("Thanks, see below"), None);
+    }
+
+    #[test]
+    fn test_quote_authors() {
+        let text = "Sounds good.\n\nOn Mon, 3 Mar 2025, Bob <bob@example.com> wrote:\n> Fine by me.\n> On Sun, 2 Mar 2025, Ann <\n> ann@example.com> wrote:\n>> Friday?\n";
+        assert_eq!(
+            quote_authors(text),
+            vec![Some("bob@example.com".to_string()), Some("ann@example.com".to_string())]
+        );
+
+        // Flattened HTML: no markers, attributions in nesting order
+        let flat = "Sounds good.\nBob wrote:\nFine by me.\nAnn wrote:\nFriday?";
+        assert_eq!(quote_authors(flat), vec![Some("Bob".to_string()), Some("Ann".to_string())]);
+
+        // A quote with no attribution leaves its level unknown
+        assert_eq!(quote_authors("> hi\n> Ann wrote:\n>> there"), vec![None, Some("Ann".to_string())]);
+    }
+}
@@ -0,0 +1,168 @@
+//! Timeline of traced operations
+//!
+//! Sync, fetch and send work runs inside tracing spans carrying the account,
+//! folder and UID involved. The app keeps the most recent finished spans in
+//! a [`Timeline`] so a slow sync can be inspected after the fact, and writes
+//! them out in the Chrome trace event format, which chrome://tracing and
+//! Perfetto open, to attach to a bug report.
+
+use std::collections::VecDeque;
+
+/// Spans kept before the oldest are dropped
+pub const DEFAULT_CAPACITY: usize = 2000;
+
+/// One finished span
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanRecord {
+    /// Span name, usually the instrumented function
+    pub name: String,
+    /// Module that opened the span
+    pub target: String,
+    /// Recorded fields such as `account`, `folder` and `uid`
+    pub fields: Vec<(String, String)>,
+    /// Start, in microseconds since the app started
+    pub start_us: u64,
+    /// Time from opening to closing the span, in microseconds
+    pub duration_us: u64,
+    /// Thread that opened the span, numbered in order of first use
+    pub thread: u64,
+}
+
+impl SpanRecord {
+    /// Fields as `name=value` pairs separated by spaces
+    pub fn fields_text(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Bounded list of the most recent finished spans, oldest first
+#[derive(Debug)]
+pub struct Timeline {
+    records: VecDeque<SpanRecord>,
+    capacity: usize,
+}
+
+impl Timeline {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Add a finished span, dropping the oldest when full
+    pub fn push(&mut self, record: SpanRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &SpanRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// The spans as a Chrome trace: one complete ("X") event per span, with
+    /// the module as category and the fields as arguments
+    pub fn to_chrome_trace(&self) -> String {
+        let events: Vec<serde_json::Value> = self
+            .records
+            .iter()
+            .map(|record| {
+                let args: serde_json::Map<String, serde_json::Value> = record
+                    .fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), serde_json::Value::from(value.as_str())))
+                    .collect();
+                serde_json::json!({
+                    "name": record.name,
+                    "cat": record.target,
+                    "ph": "X",
+                    "ts": record.start_us,
+                    "dur": record.duration_us,
+                    "pid": 1,
+                    "tid": record.thread,
+                    "args": args,
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+        })
+        .to_string()
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, start_us: u64) -> SpanRecord {
+        SpanRecord {
+            name: name.to_string(),
+            target: "northmail_core::sync".to_string(),
+            fields: vec![
+                ("account".to_string(), "acc1".to_string()),
+                ("folder".to_string(), "INBOX".to_string()),
+            ],
+            start_us,
+            duration_us: 1500,
+            thread: 2,
+        }
+    }
+
+    #[test]
+    fn test_drops_oldest_when_full() {
+        let mut timeline = Timeline::new(2);
+        timeline.push(record("a", 0));
+        timeline.push(record("b", 10));
+        timeline.push(record("c", 20));
+        let names: Vec<_> = timeline.records().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["b", "c"]);
+    }
+
+    #[test]
+    fn test_fields_text() {
+        assert_eq!(record("a", 0).fields_text(), "account=acc1 folder=INBOX");
+    }
+
+    #[test]
+    fn test_chrome_trace() {
+        let mut timeline = Timeline::default();
+        timeline.push(record("sync_folder", 42));
+        let trace: serde_json::Value = serde_json::from_str(&timeline.to_chrome_trace()).unwrap();
+        let event = &trace["traceEvents"][0];
+        assert_eq!(event["name"], "sync_folder");
+        assert_eq!(event["ph"], "X");
+        assert_eq!(event["ts"], 42);
+        assert_eq!(event["dur"], 1500);
+        assert_eq!(event["tid"], 2);
+        assert_eq!(event["args"]["folder"], "INBOX");
+    }
+}
@@ -0,0 +1,316 @@
+//! Message translation
+//!
+//! Translation is optional and goes through a backend the user sets up: a
+//! LibreTranslate server, or a local command (an Argos or Bergamot model,
+//! say) that reads the text on stdin and writes the translation to stdout.
+//! The message language is guessed here first, from its script or its most
+//! common words, so mail already in the reader's language isn't offered a
+//! translation. Quoted replies are left out of what gets sent.
+
+use crate::thread::split_quote;
+
+/// Most characters sent for translation at once
+const MAX_TRANSLATION_CHARS: usize = 10_000;
+
+/// Where translations come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslationBackend {
+    /// A LibreTranslate server, e.g. `https://libretranslate.example.org`
+    LibreTranslate { url: String, api_key: Option<String> },
+    /// A local program; `{source}` and `{target}` in its arguments are
+    /// replaced by language codes
+    Command { command_line: String },
+}
+
+impl TranslationBackend {
+    /// Backend from the `translation-*` settings; `None` when translation
+    /// is off or the chosen backend isn't filled in
+    pub fn from_settings(kind: &str, url: &str, api_key: &str, command_line: &str) -> Option<Self> {
+        match kind {
+            "libretranslate" if !url.trim().is_empty() => Some(Self::LibreTranslate {
+                url: url.trim().trim_end_matches('/').to_string(),
+                api_key: Some(api_key.trim().to_string()).filter(|k| !k.is_empty()),
+            }),
+            "command" if !command_line.trim().is_empty() => Some(Self::Command {
+                command_line: command_line.trim().to_string(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A translated message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    pub text: String,
+    /// Language the backend translated from, if it said
+    pub source_language: Option<String>,
+}
+
+/// The part of a message worth translating: unquoted lines, capped in length
+pub fn translatable_text(body: &str) -> String {
+    let text: Vec<&str> = body
+        .lines()
+        .filter(|line| split_quote(line).0 == 0)
+        .collect();
+    let text = text.join("\n");
+    let text = text.trim();
+    match text.char_indices().nth(MAX_TRANSLATION_CHARS) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text.to_string(),
+    }
+}
+
+/// Language code (ISO 639-1) from a locale name like `de_DE.UTF-8`
+pub fn language_from_locale(locale: &str) -> Option<String> {
+    let code = locale.split(['_', '.', '@', '-']).next()?.to_ascii_lowercase();
+    (code.len() == 2 || code.len() == 3)
+        .then_some(code)
+        .filter(|c| c.bytes().all(|b| b.is_ascii_lowercase()))
+}
+
+/// Best guess at the language of `text`, or `None` if it's too short or
+/// unclear
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    detect_script(text).or_else(|| detect_latin_language(text))
+}
+
+/// Whether to offer translating `text` for a reader of `target`
+pub fn should_offer(text: &str, target: &str) -> bool {
+    let text = translatable_text(text);
+    if text.chars().filter(|c| c.is_alphabetic()).count() < 20 {
+        return false;
+    }
+    detect_language(&text).is_none_or(|lang| lang != target)
+}
+
+/// English name of a language code, for "Translated from ..."
+pub fn language_name(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "ar" => "Arabic",
+        "de" => "German",
+        "el" => "Greek",
+        "en" => "English",
+        "es" => "Spanish",
+        "fr" => "French",
+        "he" => "Hebrew",
+        "hi" => "Hindi",
+        "it" => "Italian",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        "nl" => "Dutch",
+        "pl" => "Polish",
+        "pt" => "Portuguese",
+        "ro" => "Romanian",
+        "ru" => "Russian",
+        "sv" => "Swedish",
+        "th" => "Thai",
+        "tr" => "Turkish",
+        "uk" => "Ukrainian",
+        "zh" => "Chinese",
+        _ => return None,
+    })
+}
+
+/// JSON body for LibreTranslate's `/translate`
+pub fn libretranslate_request(
+    text: &str,
+    source: Option<&str>,
+    target: &str,
+    api_key: Option<&str>,
+) -> serde_json::Value {
+    let mut body = serde_json::json!({
+        "q": text,
+        "source": source.unwrap_or("auto"),
+        "target": target,
+        "format": "text",
+    });
+    if let Some(key) = api_key {
+        body["api_key"] = serde_json::Value::from(key);
+    }
+    body
+}
+
+/// Read a LibreTranslate `/translate` reply
+pub fn parse_libretranslate_response(body: &str) -> Result<Translation, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("Invalid response: {}", e))?;
+    if let Some(error) = json.get("error").and_then(|e| e.as_str()) {
+        return Err(error.to_string());
+    }
+    let text = json
+        .get("translatedText")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| "Response has no translation".to_string())?;
+    Ok(Translation {
+        text: text.to_string(),
+        source_language: json
+            .pointer("/detectedLanguage/language")
+            .and_then(|l| l.as_str())
+            .map(str::to_string),
+    })
+}
+
+/// Program and arguments for a command backend. Arguments are split on
+/// whitespace; `{source}` becomes the detected language (or `auto`) and
+/// `{target}` the reader's language.
+pub fn command_args(command_line: &str, source: Option<&str>, target: &str) -> Option<(String, Vec<String>)> {
+    let mut words = command_line.split_whitespace().map(|word| {
+        word.replace("{source}", source.unwrap_or("auto"))
+            .replace("{target}", target)
+    });
+    let program = words.next()?;
+    Some((program, words.collect()))
+}
+
+/// Languages told apart by their script alone
+fn detect_script(text: &str) -> Option<&'static str> {
+    let mut letters = 0usize;
+    let mut counts: [usize; 10] = [0; 10];
+    let mut ukrainian = false;
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let slot = match c {
+            '\u{0400}'..='\u{04FF}' => {
+                ukrainian |= matches!(c, 'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ');
+                0
+            }
+            '\u{0370}'..='\u{03FF}' => 1,
+            '\u{0600}'..='\u{06FF}' => 2,
+            '\u{0590}'..='\u{05FF}' => 3,
+            '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' => 4,
+            '\u{3040}'..='\u{30FF}' => 5,
+            '\u{4E00}'..='\u{9FFF}' => 6,
+            '\u{0E00}'..='\u{0E7F}' => 7,
+            '\u{0900}'..='\u{097F}' => 8,
+            _ => 9,
+        };
+        counts[slot] += 1;
+    }
+    if letters == 0 {
+        return None;
+    }
+    // Japanese mixes kana with Han characters
+    if counts[5] > 0 && (counts[5] + counts[6]) * 2 > letters {
+        return Some("ja");
+    }
+    let (slot, count) = counts[..9].iter().enumerate().max_by_key(|(_, n)| **n)?;
+    if *count * 2 <= letters {
+        return None;
+    }
+    Some(match slot {
+        0 if ukrainian => "uk",
+        0 => "ru",
+        1 => "el",
+        2 => "ar",
+        3 => "he",
+        4 => "ko",
+        6 => "zh",
+        7 => "th",
+        _ => "hi",
+    })
+}
+
+/// Common words of the Latin-script languages told apart here
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "is", "are", "you", "that", "with", "for", "this", "have", "of", "to", "will", "your"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ich", "sie", "mit", "für", "auf", "ein", "eine", "wir"]),
+    ("fr", &["le", "la", "les", "et", "est", "vous", "nous", "pour", "avec", "une", "des", "pas", "dans", "que"]),
+    ("es", &["el", "los", "las", "y", "es", "para", "con", "una", "por", "que", "del", "está", "usted", "pero"]),
+    ("it", &["il", "di", "che", "è", "per", "con", "una", "non", "sono", "gli", "della", "anche", "grazie", "questo"]),
+    ("pt", &["o", "os", "e", "é", "para", "com", "uma", "não", "você", "que", "do", "da", "obrigado", "está"]),
+    ("nl", &["de", "het", "een", "en", "is", "niet", "ik", "je", "met", "voor", "van", "dat", "wij", "zijn"]),
+    ("sv", &["och", "att", "det", "är", "en", "jag", "inte", "med", "för", "på", "som", "vi", "till", "har"]),
+    ("pl", &["i", "w", "nie", "się", "na", "jest", "że", "to", "do", "dla", "jak", "ale", "czy", "z"]),
+    ("ro", &["și", "este", "în", "pentru", "cu", "nu", "că", "pe", "la", "o", "un", "sunt", "mulțumesc", "vă"]),
+    ("tr", &["ve", "bir", "bu", "için", "ile", "değil", "çok", "ben", "siz", "olarak", "daha", "teşekkürler", "mi", "ama"]),
+];
+
+/// Latin-script languages, by which language's common words turn up most
+fn detect_latin_language(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .take(500)
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < 4 {
+        return None;
+    }
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            (*lang, words.iter().filter(|w| stopwords.contains(&w.as_str())).count())
+        })
+        .collect();
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    let (best, score) = scores[0];
+    // Needs a few hits and a clear lead over the runner-up
+    (score >= 3 && score > scores[1].1 * 3 / 2).then_some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language("Hello, thank you for your order. The package will ship with the next truck and you can track it."), Some("en"));
+        assert_eq!(detect_language("Sehr geehrte Damen und Herren, die Rechnung ist nicht angekommen und wir warten auf eine Antwort."), Some("de"));
+        assert_eq!(detect_language("Bonjour, nous avons bien reçu votre message et nous vous répondrons dans les plus brefs délais."), Some("fr"));
+        assert_eq!(detect_language("Здравствуйте, спасибо за письмо"), Some("ru"));
+        assert_eq!(detect_language("Дякую, ї є"), Some("uk"));
+        assert_eq!(detect_language("お問い合わせありがとうございます。確認いたします。"), Some("ja"));
+        assert_eq!(detect_language("感谢您的来信，我们会尽快回复。"), Some("zh"));
+        assert_eq!(detect_language("ok"), None);
+    }
+
+    #[test]
+    fn test_should_offer() {
+        let german = "Hallo, ich bin nicht sicher, ob die Lieferung mit der Post für uns ist.";
+        assert!(should_offer(german, "en"));
+        assert!(!should_offer(german, "de"));
+        assert!(!should_offer("Thanks!", "de"));
+        // Only the new text counts, not the quoted reply
+        let reply = "Danke, das ist gut für mich und die Anderen.\n> Thanks, the plan is that we meet with the team for the review and the demo.";
+        assert!(translatable_text(reply).starts_with("Danke"));
+        assert!(!translatable_text(reply).contains("Thanks"));
+        assert!(!should_offer(reply, "de"));
+    }
+
+    #[test]
+    fn test_backend_and_locale() {
+        assert_eq!(
+            TranslationBackend::from_settings("libretranslate", " https://lt.example.org/ ", "", ""),
+            Some(TranslationBackend::LibreTranslate {
+                url: "https://lt.example.org".to_string(),
+                api_key: None
+            })
+        );
+        assert_eq!(TranslationBackend::from_settings("libretranslate", "", "key", ""), None);
+        assert_eq!(TranslationBackend::from_settings("none", "https://x", "", "cmd"), None);
+        assert_eq!(language_from_locale("de_DE.UTF-8").as_deref(), Some("de"));
+        assert_eq!(language_from_locale("pt-BR").as_deref(), Some("pt"));
+        assert_eq!(language_from_locale("C"), None);
+        assert_eq!(
+            command_args("argos-translate --from {source} --to {target}", Some("de"), "en"),
+            Some(("argos-translate".to_string(), vec!["--from".into(), "de".into(), "--to".into(), "en".into()]))
+        );
+    }
+
+    #[test]
+    fn test_libretranslate_response() {
+        let reply = r#"{"translatedText":"Hello","detectedLanguage":{"confidence":90,"language":"de"}}"#;
+        assert_eq!(
+            parse_libretranslate_response(reply),
+            Ok(Translation { text: "Hello".to_string(), source_language: Some("de".to_string()) })
+        );
+        assert_eq!(
+            parse_libretranslate_response(r#"{"error":"Invalid API key"}"#),
+            Err("Invalid API key".to_string())
+        );
+        let request = libretranslate_request("Hallo", None, "en", Some("k"));
+        assert_eq!(request["source"], "auto");
+        assert_eq!(request["api_key"], "k");
+    }
+}
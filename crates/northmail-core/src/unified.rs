@@ -0,0 +1,179 @@
+//! The unified inbox
+//!
+//! The inboxes of every account, less those a folder sync policy leaves
+//! out, listed as one. [`Database::unified_inbox`] answers a
+//! [`UnifiedQuery`] with a [`UnifiedPage`]: messages newest first, keyed on
+//! the last message of the page before so pages stay put while new mail
+//! arrives at the top, with the colour, message count and unread count of
+//! each inbox in it.
+//!
+//! [`Database::unified_inbox`]: crate::Database::unified_inbox
+
+use crate::models::{DbMessage, MessageFilter, PageCursor};
+use crate::tags::TAG_COLORS;
+use std::collections::HashMap;
+
+/// Messages in a page of the unified inbox
+pub const PAGE_SIZE: i64 = 100;
+
+/// A page of the unified inbox to fetch
+#[derive(Debug, Clone)]
+pub struct UnifiedQuery {
+    /// Start after this message, or at the top
+    pub after: Option<PageCursor>,
+    pub limit: i64,
+    pub filter: MessageFilter,
+}
+
+impl Default for UnifiedQuery {
+    fn default() -> Self {
+        Self {
+            after: None,
+            limit: PAGE_SIZE,
+            filter: MessageFilter::default(),
+        }
+    }
+}
+
+/// An inbox in the unified inbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnifiedInbox {
+    pub folder_id: i64,
+    pub account_id: String,
+    /// `#rrggbb` marking the account's messages
+    pub color: &'static str,
+    /// Messages in the inbox, less snoozed ones
+    pub total: i64,
+    pub unread: i64,
+}
+
+/// A page of the unified inbox
+#[derive(Debug, Clone, Default)]
+pub struct UnifiedPage {
+    pub messages: Vec<DbMessage>,
+    /// Every inbox in the unified inbox, whether or not it has messages on
+    /// the page
+    pub inboxes: Vec<UnifiedInbox>,
+    /// Messages matching the query's filter across every page
+    pub total: i64,
+    /// Unread messages in the unified inbox, whatever the filter
+    pub unread: i64,
+}
+
+impl UnifiedPage {
+    /// The inbox a message on the page came from
+    pub fn inbox(&self, folder_id: i64) -> Option<&UnifiedInbox> {
+        self.inboxes
+            .iter()
+            .find(|inbox| inbox.folder_id == folder_id)
+    }
+
+    /// Where the page after this one starts; `None` if this one is empty
+    pub fn next_cursor(&self) -> Option<PageCursor> {
+        self.messages.last().map(|message| PageCursor {
+            date_epoch: message.date_epoch,
+            id: message.id,
+            uid: message.uid,
+        })
+    }
+}
+
+/// A colour for each account, the same one every time for the same
+/// accounts. Each account starts from a colour hashed from its id and moves
+/// on to the next free one, so accounts only share a colour once there are
+/// more of them than colours.
+pub fn account_colors<'a>(
+    account_ids: impl IntoIterator<Item = &'a str>,
+) -> HashMap<String, &'static str> {
+    let mut ids: Vec<&str> = account_ids.into_iter().collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut taken = vec![0usize; TAG_COLORS.len()];
+    let mut colors = HashMap::new();
+    for id in ids {
+        let start = color_index(id);
+        let least = *taken.iter().min().unwrap_or(&0);
+        let index = (0..TAG_COLORS.len())
+            .map(|offset| (start + offset) % TAG_COLORS.len())
+            .find(|&i| taken[i] == least)
+            .unwrap_or(start);
+        taken[index] += 1;
+        colors.insert(id.to_string(), TAG_COLORS[index]);
+    }
+    colors
+}
+
+fn color_index(account_id: &str) -> usize {
+    let hash = account_id
+        .bytes()
+        .fold(0u32, |hash, b| hash.wrapping_mul(31).wrapping_add(b as u32));
+    hash as usize % TAG_COLORS.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_colors() {
+        let colors = account_colors(["work", "home", "lists"]);
+        assert_eq!(colors.len(), 3);
+        let distinct: std::collections::HashSet<_> = colors.values().collect();
+        assert_eq!(distinct.len(), 3);
+        // Order and repeats don't change the result
+        assert_eq!(account_colors(["lists", "home", "work", "home"]), colors);
+
+        // Colours are shared evenly once they run out
+        let ids: Vec<String> = (0..TAG_COLORS.len() * 2)
+            .map(|i| format!("account{}", i))
+            .collect();
+        let colors = account_colors(ids.iter().map(String::as_str));
+        for color in TAG_COLORS {
+            assert_eq!(colors.values().filter(|c| *c == color).count(), 2);
+        }
+    }
+
+    #[test]
+    fn test_next_cursor() {
+        let message = |id, uid, date_epoch| DbMessage {
+            id,
+            folder_id: 1,
+            uid,
+            message_id: None,
+            subject: None,
+            from_address: None,
+            from_name: None,
+            to_addresses: None,
+            cc_addresses: None,
+            date_sent: None,
+            date_epoch,
+            snippet: None,
+            is_read: false,
+            is_starred: false,
+            has_attachments: false,
+            size: 0,
+            maildir_path: None,
+            body_text: None,
+            body_html: None,
+            gmail_labels: None,
+            gmail_thread_id: None,
+            mention: None,
+            tags: None,
+            snoozed_until: None,
+            reply_later_at: None,
+        };
+        let mut page = UnifiedPage::default();
+        assert_eq!(page.next_cursor(), None);
+
+        page.messages = vec![message(7, 70, Some(2000)), message(3, 30, None)];
+        assert_eq!(
+            page.next_cursor(),
+            Some(PageCursor {
+                date_epoch: None,
+                id: 3,
+                uid: 30
+            })
+        );
+    }
+}
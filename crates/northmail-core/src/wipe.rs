@@ -0,0 +1,92 @@
+//! Secure wipe of local files
+//!
+//! With secure wipe on, files holding mail (attachments opened from a
+//! message) are overwritten with zeros and synced to disk before they are
+//! unlinked, so their contents don't linger in free blocks. The database
+//! side is SQLite's `secure_delete` pragma (see
+//! [`crate::Database::set_secure_delete`]).
+//!
+//! This is best effort: copy-on-write filesystems, SSD wear levelling and
+//! snapshots can keep old blocks no matter what is written over them.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Zeros written per call while overwriting
+const CHUNK: usize = 64 * 1024;
+
+/// Overwrite a file with zeros, sync it and remove it
+pub fn wipe_file(path: &Path) -> io::Result<()> {
+    let len = fs::metadata(path)?.len();
+    {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.seek(SeekFrom::Start(0))?;
+        let zeros = vec![0u8; CHUNK];
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(CHUNK as u64) as usize;
+            file.write_all(&zeros[..n])?;
+            remaining -= n as u64;
+        }
+        file.sync_all()?;
+    }
+    fs::remove_file(path)
+}
+
+/// Wipe every file under `dir`, then remove the directory. Symlinks are
+/// removed without touching what they point to.
+pub fn wipe_dir(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            wipe_dir(&path)?;
+        } else if file_type.is_file() {
+            wipe_file(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    fs::remove_dir(dir)
+}
+
+/// Remove a file, wiping it first when `secure`. A missing file is fine.
+pub fn remove_file(path: &Path, secure: bool) -> io::Result<()> {
+    let removed = if secure { wipe_file(path) } else { fs::remove_file(path) };
+    match removed {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Remove a directory of cached files, wiping them first when `secure`
+pub fn remove_dir(dir: &Path, secure: bool) -> io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    if secure {
+        wipe_dir(dir)
+    } else {
+        fs::remove_dir_all(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe_dir() {
+        let dir = std::env::temp_dir().join(format!("northmail-wipe-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("invoice.pdf"), vec![7u8; CHUNK + 10]).unwrap();
+        fs::write(dir.join("nested").join("empty.txt"), b"").unwrap();
+
+        remove_dir(&dir, true).unwrap();
+        assert!(!dir.exists());
+        // Nothing to remove is fine
+        remove_dir(&dir, true).unwrap();
+    }
+}
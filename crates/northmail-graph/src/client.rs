@@ -3,6 +3,7 @@ use crate::types::*;
 use tracing::{debug, info};
 
 const GRAPH_BASE: &str = "https://graph.microsoft.com/v1.0";
+const GRAPH_HOST: &str = "graph.microsoft.com";
 
 /// Message fields to select in list queries (keeps payload small)
 const MESSAGE_SELECT: &str = "id,internetMessageId,subject,from,toRecipients,ccRecipients,receivedDateTime,isRead,isDraft,hasAttachments,bodyPreview,flag,inferenceClassification";
@@ -15,7 +16,7 @@ pub struct GraphMailClient {
 impl GraphMailClient {
     pub fn new(access_token: String) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: http_client(),
             access_token,
         }
     }
@@ -103,18 +104,20 @@ impl GraphMailClient {
         Ok(())
     }
 
-    /// List messages in a folder with pagination
+    /// List the newest `top` messages in a folder. Follow the returned
+    /// next link with [`Self::list_messages_next`] for older pages; unlike
+    /// `$skip` offsets it has no cap and stays correct when messages arrive
+    /// or are deleted while paging.
     pub async fn list_messages(
         &self,
         folder_id: &str,
         top: u32,
-        skip: u32,
     ) -> GraphResult<(Vec<GraphMessageEnvelope>, Option<String>)> {
         let url = format!(
-            "{}/me/mailFolders/{}/messages?$select={}&$top={}&$skip={}&$orderby=receivedDateTime desc",
-            GRAPH_BASE, folder_id, MESSAGE_SELECT, top, skip
+            "{}/me/mailFolders/{}/messages?$select={}&$top={}&$orderby=receivedDateTime desc",
+            GRAPH_BASE, folder_id, MESSAGE_SELECT, top
         );
-        debug!("Graph: listing messages folder={} top={} skip={}", folder_id, top, skip);
+        debug!("Graph: listing messages folder={} top={}", folder_id, top);
 
         let response = self
             .client
@@ -168,6 +171,88 @@ impl GraphMailClient {
         Ok((list.value, next_link))
     }
 
+    /// Start a delta query over a folder: pages listing the id of every
+    /// message in it, ending in a delta link that
+    /// [`Self::list_messages_delta_next`] turns into the ids changed since.
+    /// Only ids are selected, as a delta link keeps its query's selection and
+    /// the first round only serves to get one; fetch changed messages with
+    /// [`Self::get_message`].
+    pub async fn list_messages_delta(&self, folder_id: &str, page_size: u32) -> GraphResult<GraphDeltaPage> {
+        let url = format!("{}/me/mailFolders/{}/messages/delta?$select=id", GRAPH_BASE, folder_id);
+        debug!("Graph: starting delta query folder={}", folder_id);
+        self.delta_page(&url, page_size).await
+    }
+
+    /// Fetch a page of a delta query from its next link, or the changes
+    /// since a delta link
+    pub async fn list_messages_delta_next(&self, link: &str, page_size: u32) -> GraphResult<GraphDeltaPage> {
+        debug!("Graph: fetching delta page");
+        self.delta_page(link, page_size).await
+    }
+
+    async fn delta_page(&self, url: &str, page_size: u32) -> GraphResult<GraphDeltaPage> {
+        // Delta queries take their page size from this header, not $top
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(&self.access_token)
+            .header("Prefer", format!("odata.maxpagesize={}", page_size))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(GraphError::ApiError { status, body });
+        }
+
+        let list: GraphListResponse<GraphDeltaItem> = response
+            .json()
+            .await
+            .map_err(|e| GraphError::ParseError(e.to_string()))?;
+
+        let mut page = GraphDeltaPage {
+            next_link: list.next_link,
+            delta_link: list.delta_link,
+            ..Default::default()
+        };
+        for item in list.value {
+            match item {
+                GraphDeltaItem::Removed { id, .. } => page.removed.push(id),
+                GraphDeltaItem::Changed { id } => page.changed.push(id),
+            }
+        }
+        debug!(
+            "Graph: delta page with {} changed, {} removed",
+            page.changed.len(),
+            page.removed.len()
+        );
+        Ok(page)
+    }
+
+    /// Fetch one message's envelope
+    pub async fn get_message(&self, message_id: &str) -> GraphResult<GraphMessageEnvelope> {
+        let url = format!("{}/me/messages/{}?$select={}", GRAPH_BASE, message_id, MESSAGE_SELECT);
+
+        let response = self
+            .client
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(GraphError::ApiError { status, body });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| GraphError::ParseError(e.to_string()))
+    }
+
     /// Fetch raw MIME (RFC 2822) body of a message
     pub async fn fetch_mime_body(&self, message_id: &str) -> GraphResult<String> {
         let url = format!("{}/me/messages/{}/$value", GRAPH_BASE, message_id);
@@ -276,49 +361,13 @@ impl GraphMailClient {
     /// Returns the Graph message ID of the created draft.
     pub async fn create_draft_from_message(
         &self,
-        subject: &str,
-        body_text: &str,
-        body_html: Option<&str>,
-        to: &[String],
-        cc: &[String],
+        content: &DraftContent<'_>,
         attachments: &[(String, String, Vec<u8>)], // (filename, mime_type, data)
     ) -> GraphResult<String> {
         use base64::Engine;
         let engine = base64::engine::general_purpose::STANDARD;
 
-        let to_recipients: Vec<serde_json::Value> = to.iter()
-            .filter(|addr| !addr.is_empty())
-            .map(|addr| serde_json::json!({
-                "emailAddress": { "address": addr }
-            }))
-            .collect();
-
-        let cc_recipients: Vec<serde_json::Value> = cc.iter()
-            .filter(|addr| !addr.is_empty())
-            .map(|addr| serde_json::json!({
-                "emailAddress": { "address": addr }
-            }))
-            .collect();
-
-        let (content_type, content) = match body_html {
-            Some(html) => ("HTML", html),
-            None => ("Text", body_text),
-        };
-
-        let mut draft = serde_json::json!({
-            "subject": subject,
-            "body": {
-                "contentType": content_type,
-                "content": content,
-            },
-        });
-
-        if !to_recipients.is_empty() {
-            draft["toRecipients"] = serde_json::Value::Array(to_recipients);
-        }
-        if !cc_recipients.is_empty() {
-            draft["ccRecipients"] = serde_json::Value::Array(cc_recipients);
-        }
+        let mut draft = content.to_json();
         if !attachments.is_empty() {
             let graph_attachments: Vec<serde_json::Value> = attachments.iter()
                 .map(|(filename, mime_type, data)| serde_json::json!({
@@ -332,7 +381,7 @@ impl GraphMailClient {
         }
 
         let url = format!("{}/me/messages", GRAPH_BASE);
-        debug!("Graph: creating draft, subject={}, attachments={}", subject, attachments.len());
+        debug!("Graph: creating draft, subject={}, attachments={}", content.subject, attachments.len());
 
         let response = self
             .client
@@ -425,60 +474,138 @@ impl GraphMailClient {
 
     /// Update an existing draft message (PATCH - preserves attachments).
     /// Only updates subject, body, and recipients. Does NOT touch attachments.
-    pub async fn update_draft(
+    pub async fn update_draft(&self, message_id: &str, content: &DraftContent<'_>) -> GraphResult<()> {
+        let patch = content.to_json();
+
+        let url = format!("{}/me/messages/{}", GRAPH_BASE, message_id);
+        debug!("Graph: updating draft {}", message_id);
+
+        let response = self
+            .client
+            .patch(&url)
+            .bearer_auth(&self.access_token)
+            .json(&patch)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(GraphError::ApiError { status, body });
+        }
+
+        info!("Graph: updated draft {}", message_id);
+        Ok(())
+    }
+
+    /// Bring a draft's file attachments in line with `attachments`, matching
+    /// by name: server attachments no longer wanted are deleted and wanted
+    /// ones the server lacks are uploaded. Entries with no data are kept if
+    /// the server has them but never uploaded.
+    pub async fn sync_draft_attachments(
         &self,
         message_id: &str,
-        subject: &str,
-        body_text: &str,
-        body_html: Option<&str>,
-        to: &[String],
-        cc: &[String],
+        attachments: &[(String, String, Vec<u8>)], // (filename, mime_type, data)
     ) -> GraphResult<()> {
-        let to_recipients: Vec<serde_json::Value> = to.iter()
-            .filter(|addr| !addr.is_empty())
-            .map(|addr| serde_json::json!({
-                "emailAddress": { "address": addr }
-            }))
-            .collect();
+        use base64::Engine;
+        let engine = base64::engine::general_purpose::STANDARD;
 
-        let cc_recipients: Vec<serde_json::Value> = cc.iter()
-            .filter(|addr| !addr.is_empty())
-            .map(|addr| serde_json::json!({
-                "emailAddress": { "address": addr }
-            }))
-            .collect();
+        let url = format!(
+            "{}/me/messages/{}/attachments?$filter=isInline eq false&$select=id,name",
+            GRAPH_BASE, message_id
+        );
 
-        let (content_type, content) = match body_html {
-            Some(html) => ("HTML", html),
-            None => ("Text", body_text),
-        };
+        let response = self
+            .client
+            .get(&url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
 
-        let mut patch = serde_json::json!({
-            "subject": subject,
-            "body": {
-                "contentType": content_type,
-                "content": content,
-            },
-            "toRecipients": to_recipients,
-            "ccRecipients": cc_recipients,
-        });
-
-        // If no recipients, set empty arrays (don't omit, otherwise server keeps old values)
-        if to_recipients.is_empty() {
-            patch["toRecipients"] = serde_json::Value::Array(vec![]);
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(GraphError::ApiError { status, body });
         }
-        if cc_recipients.is_empty() {
-            patch["ccRecipients"] = serde_json::Value::Array(vec![]);
+
+        let list: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| GraphError::ParseError(e.to_string()))?;
+
+        let existing: Vec<(String, String)> = list["value"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| {
+                        Some((item["id"].as_str()?.to_string(), item["name"].as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (attachment_id, name) in &existing {
+            if attachments.iter().any(|(filename, _, _)| filename == name) {
+                continue;
+            }
+            let url = format!("{}/me/messages/{}/attachments/{}", GRAPH_BASE, message_id, attachment_id);
+            debug!("Graph: removing attachment '{}' from draft {}", name, message_id);
+
+            let response = self
+                .client
+                .delete(&url)
+                .bearer_auth(&self.access_token)
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status().as_u16();
+                let body = response.text().await.unwrap_or_default();
+                return Err(GraphError::ApiError { status, body });
+            }
         }
 
-        let url = format!("{}/me/messages/{}", GRAPH_BASE, message_id);
-        debug!("Graph: updating draft {}", message_id);
+        let url = format!("{}/me/messages/{}/attachments", GRAPH_BASE, message_id);
+        for (filename, mime_type, data) in attachments {
+            if data.is_empty() || existing.iter().any(|(_, name)| name == filename) {
+                continue;
+            }
+            debug!("Graph: adding attachment '{}' to draft {}", filename, message_id);
+
+            let response = self
+                .client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&serde_json::json!({
+                    "@odata.type": "#microsoft.graph.fileAttachment",
+                    "name": filename,
+                    "contentType": mime_type,
+                    "contentBytes": engine.encode(data),
+                }))
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                let status = response.status().as_u16();
+                let body = response.text().await.unwrap_or_default();
+                return Err(GraphError::ApiError { status, body });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send an existing draft. The server moves it to Sent Items.
+    pub async fn send_draft(&self, message_id: &str) -> GraphResult<()> {
+        let url = format!("{}/me/messages/{}/send", GRAPH_BASE, message_id);
+        debug!("Graph: sending draft {}", message_id);
 
         let response = self
             .client
-            .patch(&url)
+            .post(&url)
             .bearer_auth(&self.access_token)
-            .json(&patch)
+            .header("Content-Length", "0")
             .send()
             .await?;
 
@@ -488,7 +615,7 @@ impl GraphMailClient {
             return Err(GraphError::ApiError { status, body });
         }
 
-        info!("Graph: updated draft {}", message_id);
+        info!("Graph: sent draft {}", message_id);
         Ok(())
     }
 
@@ -659,3 +786,15 @@ impl GraphMailClient {
         Ok(())
     }
 }
+
+/// HTTP client that goes through the proxy configured for Graph, if any
+fn http_client() -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = northmail_proxy::proxy_for(GRAPH_HOST) {
+        debug!("Graph: using proxy {}", proxy);
+        let proxy = reqwest::Proxy::all(proxy.to_url()).expect("proxy URL is built from a parsed config");
+        builder = builder.proxy(proxy);
+    }
+    // Same failure mode as reqwest::Client::new()
+    builder.build().expect("failed to initialize HTTP client")
+}
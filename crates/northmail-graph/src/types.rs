@@ -6,6 +6,38 @@ pub struct GraphListResponse<T> {
     pub value: Vec<T>,
     #[serde(rename = "@odata.nextLink")]
     pub next_link: Option<String>,
+    /// Only on the last page of a delta query: the URL that returns changes
+    /// made after it
+    #[serde(rename = "@odata.deltaLink", default)]
+    pub delta_link: Option<String>,
+}
+
+/// An entry of a delta query: a message that is new or changed, or one that
+/// was deleted or moved out of the folder
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum GraphDeltaItem {
+    Removed {
+        id: String,
+        #[serde(rename = "@removed")]
+        removed: serde_json::Value,
+    },
+    Changed {
+        id: String,
+    },
+}
+
+/// One page of a delta query
+#[derive(Debug, Default)]
+pub struct GraphDeltaPage {
+    /// Ids of messages new to the folder or changed
+    pub changed: Vec<String>,
+    /// Ids of messages no longer in the folder
+    pub removed: Vec<String>,
+    /// Link to the next page of this round of changes
+    pub next_link: Option<String>,
+    /// Link for the next round, on the last page
+    pub delta_link: Option<String>,
 }
 
 /// A mail folder from Graph API
@@ -82,3 +114,45 @@ pub struct MoveRequest {
 pub struct MoveResponse {
     pub id: String,
 }
+
+/// Content of a draft being created or updated
+#[derive(Debug, Clone, Copy)]
+pub struct DraftContent<'a> {
+    pub subject: &'a str,
+    pub body_text: &'a str,
+    /// Sent as the HTML body when present, otherwise `body_text` is used
+    pub body_html: Option<&'a str>,
+    pub to: &'a [String],
+    pub cc: &'a [String],
+    pub bcc: &'a [String],
+}
+
+impl DraftContent<'_> {
+    /// The draft as a Graph message resource. Recipient lists are always
+    /// included, even when empty, so a PATCH clears removed recipients.
+    pub fn to_json(&self) -> serde_json::Value {
+        fn recipients(addrs: &[String]) -> Vec<serde_json::Value> {
+            addrs
+                .iter()
+                .filter(|addr| !addr.is_empty())
+                .map(|addr| serde_json::json!({ "emailAddress": { "address": addr } }))
+                .collect()
+        }
+
+        let (content_type, content) = match self.body_html {
+            Some(html) => ("HTML", html),
+            None => ("Text", self.body_text),
+        };
+
+        serde_json::json!({
+            "subject": self.subject,
+            "body": {
+                "contentType": content_type,
+                "content": content,
+            },
+            "toRecipients": recipients(self.to),
+            "ccRecipients": recipients(self.cc),
+            "bccRecipients": recipients(self.bcc),
+        })
+    }
+}
@@ -2,6 +2,17 @@
 //!
 //! Manages persistent IDLE connections for each email account,
 //! detecting new mail in real-time and sending events to the main application.
+//!
+//! INBOX always gets its own connection. Extra folders the user opted into
+//! get one connection each up to the account's connection budget; any
+//! folders beyond that share the last connection, which rotates between
+//! them. When the server refuses a connection because of its own limit, the
+//! refused worker hands its folders to another worker and stops.
+//!
+//! Lost connections are retried with exponential backoff and jitter, and
+//! each wait is reported so the UI can show that push is reconnecting. A
+//! connection that was IDLE across a suspend is replaced right away rather
+//! than waiting for the dead socket to time out.
 
 use std::collections::HashMap;
 use std::sync::mpsc;
@@ -9,16 +20,43 @@ use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
-use northmail_imap::{IdleEvent, SimpleImapClient};
+use northmail_imap::health::Backoff;
+use northmail_imap::{IdleEvent, ImapClient};
 use tracing::{debug, error, info, warn};
 
+/// How long to IDLE on each folder when a connection rotates between several
+const ROTATE_SLICE: Duration = Duration::from_secs(60);
+
+/// IDLE timeout for a connection watching a single folder (RFC recommends <29 min)
+const SINGLE_FOLDER_TIMEOUT: Duration = Duration::from_secs(28 * 60);
+
+/// First reconnect delay; doubles with each failure up to the maximum
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
 /// Events sent from the IDLE manager to the application
 #[derive(Debug, Clone)]
 pub enum IdleManagerEvent {
-    /// New mail detected for an account
-    NewMail { account_id: String },
+    /// New mail detected in a watched folder
+    NewMail {
+        account_id: String,
+        folder_path: String,
+    },
+    /// Flags changed on a message in a watched folder
+    FlagsChanged {
+        account_id: String,
+        folder_path: String,
+    },
     /// Connection was lost for an account (will auto-reconnect)
     ConnectionLost { account_id: String },
+    /// Waiting to reconnect; `attempt` counts failures since the last success
+    Reconnecting {
+        account_id: String,
+        attempt: u32,
+        retry_in: Duration,
+    },
+    /// Connected and watching again after a loss
+    Reconnected { account_id: String },
     /// IDLE is not supported by this server
     NotSupported { account_id: String },
 }
@@ -29,6 +67,10 @@ pub struct IdleCredentials {
     pub account_id: String,
     pub email: String,
     pub auth_type: IdleAuthType,
+    /// Folders to watch, INBOX first
+    pub folders: Vec<String>,
+    /// Maximum number of IDLE connections to open for this account
+    pub max_connections: usize,
 }
 
 /// Authentication type for IDLE connection
@@ -48,6 +90,11 @@ pub enum IdleAuthType {
     },
 }
 
+/// Folders watched by each worker of an account, indexed by worker slot.
+/// Workers re-read their slot between folders so hand-offs take effect
+/// without restarting.
+type FolderAssignments = Arc<Mutex<Vec<Vec<String>>>>;
+
 /// Handle to a running IDLE worker
 struct IdleWorkerHandle {
     /// Channel to send shutdown signal
@@ -59,7 +106,7 @@ struct IdleWorkerHandle {
 /// Manages IDLE connections for multiple accounts
 pub struct IdleManager {
     /// Active workers keyed by account ID
-    workers: Mutex<HashMap<String, IdleWorkerHandle>>,
+    workers: Mutex<HashMap<String, Vec<IdleWorkerHandle>>>,
     /// Channel to send events to the application
     event_tx: mpsc::Sender<IdleManagerEvent>,
 }
@@ -90,60 +137,125 @@ impl IdleManager {
             }
         }
 
-        info!("Starting IDLE for account {}", account_id);
+        let assignments = assign_folders(&credentials.folders, credentials.max_connections);
+        info!(
+            "Starting IDLE for account {} ({} folders on {} connections)",
+            account_id,
+            credentials.folders.len(),
+            assignments.len()
+        );
+        let assignments: FolderAssignments = Arc::new(Mutex::new(assignments));
+        let slots = assignments.lock().unwrap().len();
 
-        // Create shutdown channel
-        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let mut handles = Vec::with_capacity(slots);
+        for slot in 0..slots {
+            // Create shutdown channel
+            let (shutdown_tx, shutdown_rx) = mpsc::channel();
 
-        // Clone event sender for the worker
-        let event_tx = self.event_tx.clone();
+            // Clone shared state for the worker
+            let event_tx = self.event_tx.clone();
+            let credentials = credentials.clone();
+            let assignments = assignments.clone();
 
-        // Spawn worker thread
-        let thread = thread::spawn(move || {
-            idle_worker_loop(credentials, event_tx, shutdown_rx);
-        });
+            // Spawn worker thread
+            let thread = thread::spawn(move || {
+                idle_worker_loop(credentials, slot, assignments, event_tx, shutdown_rx);
+            });
 
-        // Store handle
-        let mut workers = self.workers.lock().unwrap();
-        workers.insert(
-            account_id,
-            IdleWorkerHandle {
+            handles.push(IdleWorkerHandle {
                 shutdown_tx,
                 thread: Some(thread),
-            },
-        );
+            });
+        }
+
+        // Store handles
+        let mut workers = self.workers.lock().unwrap();
+        workers.insert(account_id, handles);
     }
 
     /// Stop IDLE monitoring for an account
     pub fn stop_idle(&self, account_id: &str) {
         let mut workers = self.workers.lock().unwrap();
-        if let Some(mut handle) = workers.remove(account_id) {
+        if let Some(handles) = workers.remove(account_id) {
             info!("Stopping IDLE for account {}", account_id);
-            // Send shutdown signal
-            let _ = handle.shutdown_tx.send(());
-            // Wait for thread to finish
-            if let Some(thread) = handle.thread.take() {
-                let _ = thread.join();
+            // Workers can sit in IDLE for minutes before they see the signal,
+            // so let them exit on their own rather than blocking the caller
+            for handle in &handles {
+                let _ = handle.shutdown_tx.send(());
             }
         }
     }
 
+    /// Restart IDLE for an account, e.g. after its watched folders changed
+    pub fn restart_idle(&self, credentials: IdleCredentials) {
+        self.stop_idle(&credentials.account_id);
+        self.start_idle(credentials);
+    }
+
     /// Stop all IDLE workers
     pub fn shutdown(&self) {
         let mut workers = self.workers.lock().unwrap();
-        for (account_id, mut handle) in workers.drain() {
+        for (account_id, mut handles) in workers.drain() {
             info!("Shutting down IDLE for account {}", account_id);
-            let _ = handle.shutdown_tx.send(());
-            if let Some(thread) = handle.thread.take() {
-                let _ = thread.join();
+            for handle in &handles {
+                let _ = handle.shutdown_tx.send(());
+            }
+            for handle in &mut handles {
+                if let Some(thread) = handle.thread.take() {
+                    let _ = thread.join();
+                }
             }
         }
     }
 }
 
-/// Worker loop that maintains IDLE connection for one account
+/// Split folders across at most `max_connections` workers. Every folder but
+/// the last few gets a dedicated connection; the remainder share the last one.
+fn assign_folders(folders: &[String], max_connections: usize) -> Vec<Vec<String>> {
+    let max_connections = max_connections.max(1);
+    if folders.len() <= max_connections {
+        return folders.iter().map(|f| vec![f.clone()]).collect();
+    }
+
+    let mut slots: Vec<Vec<String>> = folders[..max_connections - 1]
+        .iter()
+        .map(|f| vec![f.clone()])
+        .collect();
+    slots.push(folders[max_connections - 1..].to_vec());
+    slots
+}
+
+/// Whether a connect error means the server refused because the account has
+/// too many open connections
+fn is_connection_limit_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("too many")
+        || message.contains("maximum number of connections")
+        || message.contains("connection limit")
+        || message.contains("[limit]")
+}
+
+/// Give a refused worker's folders to another live worker. Returns false if
+/// this is the only worker left, in which case it should keep retrying.
+fn hand_off_folders(assignments: &FolderAssignments, slot: usize) -> bool {
+    let mut slots = assignments.lock().unwrap();
+    // Prefer the last live worker so INBOX keeps a dedicated connection
+    let Some(target) = (0..slots.len())
+        .rev()
+        .find(|&i| i != slot && !slots[i].is_empty())
+    else {
+        return false;
+    };
+    let folders = std::mem::take(&mut slots[slot]);
+    slots[target].extend(folders);
+    true
+}
+
+/// Worker loop that maintains one IDLE connection for an account
 fn idle_worker_loop(
     credentials: IdleCredentials,
+    slot: usize,
+    assignments: FolderAssignments,
     event_tx: mpsc::Sender<IdleManagerEvent>,
     shutdown_rx: mpsc::Receiver<()>,
 ) {
@@ -151,8 +263,14 @@ fn idle_worker_loop(
 
     // Use async-std runtime for this thread
     async_std::task::block_on(async {
-        let mut reconnect_delay = Duration::from_secs(5);
-        const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300); // 5 minutes
+        let mut backoff = Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY);
+        // Set once a loss was reported, until the connection is back
+        let mut reconnecting = false;
+
+        // Last known message count per folder, to spot mail that arrived
+        // while a rotating connection was watching another folder
+        let mut last_counts: HashMap<String, u32> = HashMap::new();
+        let mut next_folder = 0usize;
 
         loop {
             // Check for shutdown signal (non-blocking)
@@ -162,7 +280,7 @@ fn idle_worker_loop(
             }
 
             // Connect and authenticate
-            let mut client = SimpleImapClient::new();
+            let mut client = ImapClient::new();
             let connect_result = match &credentials.auth_type {
                 IdleAuthType::OAuth2 { host, access_token } => {
                     match host.as_str() {
@@ -191,30 +309,24 @@ fn idle_worker_loop(
             };
 
             if let Err(e) = connect_result {
-                error!("IDLE connect failed for {}: {}", account_id, e);
-                let _ = event_tx.send(IdleManagerEvent::ConnectionLost {
-                    account_id: account_id.clone(),
-                });
+                if is_connection_limit_error(&e.to_string()) && hand_off_folders(&assignments, slot) {
+                    warn!(
+                        "IDLE connection {} refused for {} (server connection limit), sharing its folders",
+                        slot, account_id
+                    );
+                    return;
+                }
 
-                // Wait before reconnecting with exponential backoff
-                async_std::task::sleep(reconnect_delay).await;
-                reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                error!("IDLE connect failed for {}: {}", account_id, e);
+                reconnecting = true;
+                wait_to_reconnect(&account_id, &mut backoff, &event_tx).await;
                 continue;
             }
 
-            info!("IDLE connected for {}", account_id);
+            info!("IDLE connected for {} (connection {})", account_id, slot);
 
-            // Select INBOX
-            if let Err(e) = client.select("INBOX").await {
-                error!("IDLE select INBOX failed for {}: {}", account_id, e);
-                let _ = client.logout().await;
-                async_std::task::sleep(reconnect_delay).await;
-                reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
-                continue;
-            }
-
-            // Reset reconnect delay on successful connection
-            reconnect_delay = Duration::from_secs(5);
+            // Whether the machine slept, so reconnecting can't wait
+            let mut suspended = false;
 
             // IDLE loop
             loop {
@@ -225,73 +337,107 @@ fn idle_worker_loop(
                     return;
                 }
 
-                // Enter IDLE mode with 28-minute timeout (RFC recommends <29 min)
-                let idle_timeout = Duration::from_secs(28 * 60);
+                let folders = assignments.lock().unwrap()[slot].clone();
+                if folders.is_empty() {
+                    let _ = client.logout().await;
+                    return;
+                }
+                let rotating = folders.len() > 1;
+                let folder = folders[next_folder % folders.len()].clone();
+
+                // Select the folder to watch
+                let count = match client.select(&folder).await {
+                    Ok(info) => info.message_count.unwrap_or(0),
+                    Err(e) => {
+                        error!("IDLE select {} failed for {}: {}", folder, account_id, e);
+                        break; // Reconnect
+                    }
+                };
+
+                // Healthy again: reset the backoff and clear "Reconnecting"
+                backoff.reset();
+                if reconnecting {
+                    reconnecting = false;
+                    info!("IDLE reconnected for {} (connection {})", account_id, slot);
+                    let _ = event_tx.send(IdleManagerEvent::Reconnected {
+                        account_id: account_id.clone(),
+                    });
+                }
+
+                let previous = last_counts.insert(folder.clone(), count);
+                if rotating && previous.is_some_and(|prev| count > prev) {
+                    info!("IDLE: new messages in {} for {} since last visit", folder, account_id);
+                    let _ = event_tx.send(IdleManagerEvent::NewMail {
+                        account_id: account_id.clone(),
+                        folder_path: folder.clone(),
+                    });
+                }
+
+                let idle_timeout = if rotating { ROTATE_SLICE } else { SINGLE_FOLDER_TIMEOUT };
                 match client.idle(idle_timeout).await {
                     Ok(IdleEvent::NewMessages(count)) => {
-                        info!("IDLE: {} new messages for {}", count, account_id);
+                        info!("IDLE: {} new messages in {} for {}", count, folder, account_id);
                         // Exit IDLE to allow sync
                         if let Err(e) = client.idle_done().await {
                             warn!("IDLE DONE failed for {}: {}", account_id, e);
                             break; // Reconnect
                         }
+                        last_counts.insert(folder.clone(), count);
                         let _ = event_tx.send(IdleManagerEvent::NewMail {
                             account_id: account_id.clone(),
+                            folder_path: folder.clone(),
                         });
-                        // Re-select to refresh state
-                        if let Err(e) = client.select("INBOX").await {
-                            warn!("IDLE re-select failed for {}: {}", account_id, e);
-                            break; // Reconnect
-                        }
+                        // Loop re-selects the same folder to refresh state
                     }
                     Ok(IdleEvent::Expunge(_)) => {
-                        // Message deleted - might want to sync
+                        // Message deleted - re-select to refresh state
                         if let Err(e) = client.idle_done().await {
                             warn!("IDLE DONE failed for {}: {}", account_id, e);
                             break;
                         }
-                        // Re-select to refresh state
-                        if let Err(e) = client.select("INBOX").await {
-                            warn!("IDLE re-select failed for {}: {}", account_id, e);
-                            break;
-                        }
                     }
                     Ok(IdleEvent::FlagsChanged) => {
-                        // Flags changed - might want to sync
+                        debug!("IDLE: flags changed in {} for {}", folder, account_id);
                         if let Err(e) = client.idle_done().await {
                             warn!("IDLE DONE failed for {}: {}", account_id, e);
                             break;
                         }
-                        if let Err(e) = client.select("INBOX").await {
-                            warn!("IDLE re-select failed for {}: {}", account_id, e);
-                            break;
-                        }
+                        let _ = event_tx.send(IdleManagerEvent::FlagsChanged {
+                            account_id: account_id.clone(),
+                            folder_path: folder.clone(),
+                        });
                     }
                     Ok(IdleEvent::Timeout) => {
-                        // Normal timeout - send DONE and re-enter IDLE (keepalive)
-                        debug!("IDLE timeout (keepalive) for {}", account_id);
+                        // Normal timeout - send DONE and move on (keepalive or rotation)
+                        debug!("IDLE timeout on {} for {}", folder, account_id);
                         if let Err(e) = client.idle_done().await {
                             warn!("IDLE DONE failed for {}: {}", account_id, e);
                             break;
                         }
-                        // Quick NOOP to keep connection alive
-                        if let Err(e) = client.noop().await {
+                        if rotating {
+                            next_folder = next_folder.wrapping_add(1);
+                        } else if let Err(e) = client.keepalive().await {
+                            // Quick NOOP to keep connection alive
                             warn!("NOOP failed for {}: {}", account_id, e);
                             break;
                         }
                     }
                     Ok(IdleEvent::ServerBye) => {
                         info!("Server closed IDLE connection for {}", account_id);
-                        let _ = event_tx.send(IdleManagerEvent::ConnectionLost {
-                            account_id: account_id.clone(),
-                        });
                         break; // Reconnect
                     }
+                    Ok(IdleEvent::Suspended) => {
+                        info!("IDLE for {} was interrupted by a suspend, reconnecting", account_id);
+                        suspended = true;
+                        break;
+                    }
                     Err(e) => {
                         let err_msg = e.to_string();
-                        // Detect IDLE not supported: server responds with BAD/NO
-                        // instead of '+' continuation
-                        if err_msg.contains("Expected '+' continuation") {
+                        // IDLE not supported: the server doesn't advertise it, or
+                        // responds with BAD/NO instead of '+' continuation
+                        if matches!(e, northmail_imap::ImapError::Unsupported(_))
+                            || err_msg.contains("Expected '+' continuation")
+                        {
                             warn!("IDLE not supported by server for {}: {}", account_id, err_msg);
                             let _ = event_tx.send(IdleManagerEvent::NotSupported {
                                 account_id: account_id.clone(),
@@ -300,17 +446,45 @@ fn idle_worker_loop(
                             return; // Stop entirely - don't reconnect
                         }
                         error!("IDLE error for {}: {}", account_id, e);
-                        let _ = event_tx.send(IdleManagerEvent::ConnectionLost {
-                            account_id: account_id.clone(),
-                        });
                         break; // Reconnect
                     }
                 }
             }
 
-            // Connection lost - wait before reconnecting
-            async_std::task::sleep(reconnect_delay).await;
-            reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+            // Connection lost - report it and wait before reconnecting. After
+            // a suspend the old socket is just dropped and a new one opened
+            // straight away; the network may still be coming up, in which
+            // case the connect fails and backs off as usual.
+            let _ = event_tx.send(IdleManagerEvent::ConnectionLost {
+                account_id: account_id.clone(),
+            });
+            reconnecting = true;
+            if suspended {
+                backoff.reset();
+            } else {
+                wait_to_reconnect(&account_id, &mut backoff, &event_tx).await;
+            }
         }
     });
 }
+
+/// Back off before the next connection attempt, telling the UI how long
+async fn wait_to_reconnect(
+    account_id: &str,
+    backoff: &mut Backoff,
+    event_tx: &mpsc::Sender<IdleManagerEvent>,
+) {
+    let delay = backoff.next_delay();
+    debug!(
+        "IDLE for {}: reconnect attempt {} in {:.1}s",
+        account_id,
+        backoff.attempt(),
+        delay.as_secs_f64()
+    );
+    let _ = event_tx.send(IdleManagerEvent::Reconnecting {
+        account_id: account_id.to_string(),
+        attempt: backoff.attempt(),
+        retry_in: delay,
+    });
+    async_std::task::sleep(delay).await;
+}
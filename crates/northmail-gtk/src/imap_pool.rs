@@ -2,14 +2,38 @@
 //!
 //! Maintains persistent IMAP connections per account to avoid repeated
 //! connection/authentication overhead.
+//!
+//! Idle workers send keepalive NOOPs with a deadline and exit when the
+//! connection turns out to be dead; the next request starts a new worker.
+//! After a suspend a worker checks its connection before using it and logs
+//! in again if needed. Failed logins back off exponentially with jitter, and
+//! requests during the wait fail fast instead of reconnecting each time.
+//!
+//! Each command carries a [`ResponseSender`] for its answers, which the
+//! caller awaits on the main loop.
 
-use northmail_imap::SimpleImapClient;
+use futures::channel::mpsc::UnboundedSender;
+use northmail_imap::health::{Backoff, SuspendDetector};
+use northmail_imap::{BodyPart, ImapClient};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+/// A search as the server takes it
+#[derive(Debug, Clone)]
+pub enum ServerQuery {
+    /// Gmail query syntax, sent as X-GM-RAW
+    Gmail(String),
+    /// IMAP SEARCH criteria
+    Imap(String),
+}
+
+/// Where a worker answers a command
+pub type ResponseSender = UnboundedSender<ImapResponse>;
+
 /// Commands that can be sent to an IMAP worker
 #[derive(Debug)]
 pub enum ImapCommand {
@@ -18,55 +42,114 @@ pub enum ImapCommand {
         folder: String,
         /// Sequence range like "1:50" or "*:*" for last N
         range: String,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
+    },
+    /// Select a folder and fetch headers for specific UIDs
+    FetchHeadersByUid {
+        folder: String,
+        /// UID set like "1234" or "1200:1210"
+        uids: String,
+        response_tx: ResponseSender,
     },
     /// Fetch a message body
     FetchBody {
         folder: String,
         uid: u32,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
     },
-    /// Set or remove flags on a message
-    StoreFlags {
+    /// Fetch only the displayable parts of a message (text, HTML and inline
+    /// images) using BODYSTRUCTURE, leaving attachments on the server
+    FetchBodyParts {
+        folder: String,
+        uid: u32,
+        /// Download inline images too; off in low-bandwidth mode
+        inline_resources: bool,
+        response_tx: ResponseSender,
+    },
+    /// Fetch one attachment's data, located by section or by filename
+    FetchAttachment {
         folder: String,
         uid: u32,
+        section: Option<String>,
+        filename: String,
+        response_tx: ResponseSender,
+    },
+    /// Set or remove flags on messages, one UID STORE for the whole set
+    StoreFlags {
+        folder: String,
+        uids: Vec<u32>,
         /// Flags to add (e.g., "\\Seen", "\\Flagged")
         add_flags: Vec<String>,
         /// Flags to remove
         remove_flags: Vec<String>,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
+    },
+    /// Add and remove Gmail labels on messages (X-GM-LABELS)
+    StoreGmailLabels {
+        folder: String,
+        uids: Vec<u32>,
+        add_labels: Vec<String>,
+        remove_labels: Vec<String>,
+        response_tx: ResponseSender,
     },
-    /// Move a message to another folder (COPY + DELETE)
+    /// Move messages to another folder (UID MOVE, or COPY + EXPUNGE fallback)
     MoveMessage {
         source_folder: String,
         dest_folder: String,
-        uid: u32,
-        response_tx: mpsc::Sender<ImapResponse>,
+        uids: Vec<u32>,
+        response_tx: ResponseSender,
+    },
+    /// Delete messages: move them to `trash`, or flag and expunge them when
+    /// they are already there or there is no Trash
+    DeleteMessages {
+        folder: String,
+        trash: Option<String>,
+        uids: Vec<u32>,
+        response_tx: ResponseSender,
     },
     /// Create a new folder
     CreateFolder {
         folder_path: String,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
     },
     /// Rename a folder
     RenameFolder {
         from_path: String,
         to_path: String,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
     },
     /// Delete a folder
     DeleteFolder {
         folder_path: String,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
     },
     /// Empty a folder (mark all messages as deleted, then expunge)
     EmptyFolder {
         folder_path: String,
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
+    },
+    /// Counts and the newest headers of a folder, read-only and without
+    /// touching the cache
+    PeekFolder {
+        folder: String,
+        count: u32,
+        response_tx: ResponseSender,
+    },
+    /// Search a folder on the server and fetch the headers of the newest
+    /// `limit` matches
+    Search {
+        folder: String,
+        query: ServerQuery,
+        limit: usize,
+        response_tx: ResponseSender,
+    },
+    /// Storage quota of the account (GETQUOTAROOT INBOX)
+    GetQuota {
+        response_tx: ResponseSender,
     },
     /// Check connection health
     Noop {
-        response_tx: mpsc::Sender<ImapResponse>,
+        response_tx: ResponseSender,
     },
     /// Shutdown the worker
     Shutdown,
@@ -83,8 +166,24 @@ pub enum ImapResponse {
     },
     /// Message headers
     Headers(Vec<northmail_imap::MessageHeader>),
+    /// Result of a folder peek
+    Peek(northmail_imap::FolderPeek),
+    /// Quota roots covering the inbox; empty when the server has no quota
+    Quota(Vec<northmail_imap::Quota>),
     /// Message body (raw)
     Body(String),
+    /// Message header and decoded display parts, plus attachment parts that
+    /// were not downloaded
+    BodyParts {
+        header: Vec<u8>,
+        fetched: Vec<(BodyPart, Vec<u8>)>,
+        deferred: Vec<BodyPart>,
+    },
+    /// Decoded attachment data
+    Attachment(Vec<u8>),
+    /// Messages moved; carries the UIDs in the destination folder when the
+    /// server reports them via COPYUID (UIDPLUS)
+    Moved { dest_uids: Vec<u32> },
     /// Operation completed successfully
     Ok,
     /// Error occurred
@@ -114,6 +213,15 @@ pub enum ImapCredentials {
 }
 
 impl ImapCredentials {
+    /// IMAP server these credentials log in to
+    pub fn host(&self) -> &str {
+        match self {
+            ImapCredentials::Gmail { .. } => "imap.gmail.com",
+            ImapCredentials::Microsoft { .. } => "outlook.office365.com",
+            ImapCredentials::Password { host, .. } => host,
+        }
+    }
+
     /// Get a key for this credential (for pooling)
     pub fn pool_key(&self) -> String {
         match self {
@@ -126,10 +234,28 @@ impl ImapCredentials {
     }
 }
 
+/// First delay after a failed login; doubles with each failure up to the maximum
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(120);
+
+/// Login failures per pool key, with the earliest time to try again
+type ReconnectBackoff = Arc<Mutex<HashMap<String, (Backoff, Instant)>>>;
+
+/// Clears a worker's `alive` flag however the worker exits
+struct AliveGuard(Arc<AtomicBool>);
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
 /// Handle to communicate with an IMAP worker
 pub struct ImapWorkerHandle {
     command_tx: mpsc::Sender<ImapCommand>,
     last_used: Instant,
+    /// Cleared when the worker thread exits, e.g. on a dead connection
+    alive: Arc<AtomicBool>,
 }
 
 impl ImapWorkerHandle {
@@ -156,6 +282,10 @@ pub struct ImapPool {
     workers: Mutex<HashMap<String, ImapWorkerHandle>>,
     /// How long to keep idle connections
     idle_timeout: Duration,
+    /// Most workers kept open at once; the least recently used is closed beyond this
+    max_connections: AtomicUsize,
+    /// Backoff after failed logins
+    backoff: ReconnectBackoff,
 }
 
 impl ImapPool {
@@ -164,9 +294,16 @@ impl ImapPool {
         Self {
             workers: Mutex::new(HashMap::new()),
             idle_timeout: Duration::from_secs(300), // 5 minutes
+            max_connections: AtomicUsize::new(usize::MAX),
+            backoff: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Limit how many connections stay open across accounts
+    pub fn set_max_connections(&self, max: usize) {
+        self.max_connections.store(max.max(1), Ordering::Relaxed);
+    }
+
     /// Remove a dead worker so the next call to get_or_create reconnects
     pub fn remove_worker(&self, credentials: &ImapCredentials) {
         let key = credentials.pool_key();
@@ -176,6 +313,19 @@ impl ImapPool {
         }
     }
 
+    /// Close a live worker, e.g. after the account's credentials changed;
+    /// the next get_or_create logs in again
+    pub fn close_worker(&self, credentials: &ImapCredentials) {
+        let key = credentials.pool_key();
+        // New credentials or proxy: try again right away
+        self.backoff.lock().unwrap().remove(&key);
+        let mut workers = self.workers.lock().unwrap();
+        if let Some(handle) = workers.remove(&key) {
+            info!("Closing IMAP worker for {}", key);
+            let _ = handle.send(ImapCommand::Shutdown);
+        }
+    }
+
     /// Get or create a worker for the given credentials
     pub fn get_or_create(&self, credentials: ImapCredentials) -> Result<mpsc::Sender<ImapCommand>, String> {
         let key = credentials.pool_key();
@@ -186,7 +336,10 @@ impl ImapPool {
             // Just check if the channel is still connected by trying to clone the sender
             // If the worker has died, the receiver is dropped and this will work,
             // but the next actual command will fail and we'll detect it then
-            if !handle.is_stale(self.idle_timeout) {
+            if !handle.alive.load(Ordering::Relaxed) {
+                debug!("Worker for {} has exited, creating new one", key);
+                workers.remove(&key);
+            } else if !handle.is_stale(self.idle_timeout) {
                 debug!("♻️ Reusing existing IMAP connection for {}", key);
                 handle.touch();
                 return Ok(handle.command_tx.clone());
@@ -198,6 +351,29 @@ impl ImapPool {
             }
         }
 
+        // Don't log in again while backing off after a failure
+        if let Some((_, retry_at)) = self.backoff.lock().unwrap().get(&key) {
+            if let Some(wait) = retry_at.checked_duration_since(Instant::now()) {
+                return Err(format!("Connection failed, retrying in {}s", wait.as_secs().max(1)));
+            }
+        }
+
+        // Make room by closing the least recently used connection
+        let max_connections = self.max_connections.load(Ordering::Relaxed);
+        while workers.len() >= max_connections {
+            let Some(oldest) = workers
+                .iter()
+                .min_by_key(|(_, h)| h.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(handle) = workers.remove(&oldest) {
+                info!("Closing IMAP connection for {} (pool limit {})", oldest, max_connections);
+                let _ = handle.send(ImapCommand::Shutdown);
+            }
+        }
+
         // Create new worker
         info!("🔌 Creating new IMAP connection for {}", key);
         let (command_tx, command_rx) = mpsc::channel();
@@ -205,65 +381,103 @@ impl ImapPool {
         // Spawn worker thread - it will connect and then start processing commands
         // Commands sent before connection completes will queue up in the channel
         let creds = credentials.clone();
+        let alive = Arc::new(AtomicBool::new(true));
+        let guard = AliveGuard(alive.clone());
+        let backoff = self.backoff.clone();
         std::thread::spawn(move || {
-            Self::run_worker(creds, command_rx);
+            let _guard = guard;
+            Self::run_worker(creds, command_rx, backoff);
         });
 
         // Store handle immediately - the worker will start processing once connected
         let handle = ImapWorkerHandle {
             command_tx: command_tx.clone(),
             last_used: Instant::now(),
+            alive,
         };
         workers.insert(key, handle);
 
         Ok(command_tx)
     }
 
+    /// Log in with the given credentials
+    pub(crate) async fn connect(client: &mut ImapClient, credentials: &ImapCredentials) -> northmail_imap::ImapResult<()> {
+        match credentials {
+            ImapCredentials::Gmail { email, access_token } => client.connect_gmail(email, access_token).await,
+            ImapCredentials::Microsoft { email, access_token } => client.connect_outlook(email, access_token).await,
+            ImapCredentials::Password {
+                host,
+                port,
+                username,
+                password,
+            } => client.connect_login(host, *port, username, password).await,
+        }
+    }
+
+    /// Record a failed login and return how long until the next try
+    fn record_failure(backoff: &ReconnectBackoff, key: &str) -> Duration {
+        let mut backoff = backoff.lock().unwrap();
+        let (state, retry_at) = backoff
+            .entry(key.to_string())
+            .or_insert_with(|| (Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY), Instant::now()));
+        let delay = state.next_delay();
+        *retry_at = Instant::now() + delay;
+        delay
+    }
+
+    /// Answer every queued command with an error
+    fn fail_pending(command_rx: &mpsc::Receiver<ImapCommand>, error: &str) {
+        while let Ok(cmd) = command_rx.try_recv() {
+            Self::send_error_response(&cmd, error);
+        }
+    }
+
     /// Run the IMAP worker in a dedicated thread
-    fn run_worker(credentials: ImapCredentials, command_rx: mpsc::Receiver<ImapCommand>) {
-        info!("IMAP worker thread started for {}", credentials.pool_key());
+    fn run_worker(credentials: ImapCredentials, command_rx: mpsc::Receiver<ImapCommand>, backoff: ReconnectBackoff) {
+        let key = credentials.pool_key();
+        info!("IMAP worker thread started for {}", key);
 
         async_std::task::block_on(async {
-            let mut client = SimpleImapClient::new();
+            let mut client = ImapClient::new();
 
             info!("IMAP worker connecting...");
 
-            // Connect based on credentials
-            let connect_result = match &credentials {
-                ImapCredentials::Gmail { email, access_token } => {
-                    client.connect_gmail(email, access_token).await
-                }
-                ImapCredentials::Microsoft { email, access_token } => {
-                    client.connect_outlook(email, access_token).await
-                }
-                ImapCredentials::Password {
-                    host,
-                    port,
-                    username,
-                    password,
-                } => {
-                    client.connect_login(host, *port, username, password).await
-                }
-            };
-
-            if let Err(e) = connect_result {
-                error!("IMAP worker failed to connect: {}", e);
+            if let Err(e) = Self::connect(&mut client, &credentials).await {
+                let delay = Self::record_failure(&backoff, &key);
+                error!("IMAP worker failed to connect: {} (next try in {:.1}s)", e, delay.as_secs_f64());
                 // Drain any pending commands with error responses
-                while let Ok(cmd) = command_rx.try_recv() {
-                    Self::send_error_response(&cmd, &format!("Connection failed: {}", e));
-                }
+                Self::fail_pending(&command_rx, &format!("Connection failed: {}", e));
                 return;
             }
+            backoff.lock().unwrap().remove(&key);
 
-            info!("IMAP worker connected for {}", credentials.pool_key());
+            info!("IMAP worker connected for {}", key);
 
             // Track currently selected folder to avoid redundant SELECTs
             let mut current_folder: Option<String> = None;
+            let mut clock = SuspendDetector::new();
 
             // Process commands
             loop {
                 match command_rx.recv_timeout(Duration::from_secs(60)) {
                     Ok(command) => {
+                        // The connection probably died if the machine slept;
+                        // check it, and log in again before running the command
+                        if clock.slept() && !matches!(command, ImapCommand::Shutdown) {
+                            if let Err(e) = client.keepalive().await {
+                                info!("IMAP connection for {} lost across suspend ({}), reconnecting", key, e);
+                                current_folder = None;
+                                client = ImapClient::new();
+                                if let Err(e) = Self::connect(&mut client, &credentials).await {
+                                    Self::record_failure(&backoff, &key);
+                                    error!("IMAP worker failed to reconnect: {}", e);
+                                    let error = format!("Connection lost: {}", e);
+                                    Self::send_error_response(&command, &error);
+                                    Self::fail_pending(&command_rx, &error);
+                                    return;
+                                }
+                            }
+                        }
                         match command {
                             ImapCommand::Shutdown => {
                                 debug!("IMAP worker shutting down");
@@ -271,12 +485,12 @@ impl ImapPool {
                                 return;
                             }
                             ImapCommand::Noop { response_tx } => {
-                                match client.noop().await {
+                                match client.keepalive().await {
                                     Ok(_) => {
-                                        let _ = response_tx.send(ImapResponse::Ok);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Ok);
                                     }
                                     Err(e) => {
-                                        let _ = response_tx.send(ImapResponse::Error(e.to_string()));
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
                                         // Connection is dead, exit
                                         return;
                                     }
@@ -296,6 +510,20 @@ impl ImapPool {
                                 .await;
                                 current_folder = Some(folder);
                             }
+                            ImapCommand::FetchHeadersByUid {
+                                folder,
+                                uids,
+                                response_tx,
+                            } => {
+                                Self::handle_fetch_headers_by_uid(
+                                    &mut client,
+                                    &folder,
+                                    &uids,
+                                    &response_tx,
+                                    &mut current_folder,
+                                )
+                                .await;
+                            }
                             ImapCommand::FetchBody {
                                 folder,
                                 uid,
@@ -304,23 +532,77 @@ impl ImapPool {
                                 Self::handle_fetch_body(&mut client, &folder, uid, &response_tx, &mut current_folder)
                                     .await;
                             }
-                            ImapCommand::StoreFlags {
+                            ImapCommand::FetchBodyParts {
+                                folder,
+                                uid,
+                                inline_resources,
+                                response_tx,
+                            } => {
+                                Self::handle_fetch_body_parts(&mut client, &folder, uid, inline_resources, &response_tx, &mut current_folder)
+                                    .await;
+                            }
+                            ImapCommand::FetchAttachment {
                                 folder,
                                 uid,
+                                section,
+                                filename,
+                                response_tx,
+                            } => {
+                                Self::handle_fetch_attachment(
+                                    &mut client,
+                                    &folder,
+                                    uid,
+                                    section.as_deref(),
+                                    &filename,
+                                    &response_tx,
+                                    &mut current_folder,
+                                )
+                                .await;
+                            }
+                            ImapCommand::StoreFlags {
+                                folder,
+                                uids,
                                 add_flags,
                                 remove_flags,
                                 response_tx,
                             } => {
-                                Self::handle_store_flags(&mut client, &folder, uid, &add_flags, &remove_flags, &response_tx, &mut current_folder)
+                                Self::handle_store_flags(&mut client, &folder, &uids, &add_flags, &remove_flags, &response_tx, &mut current_folder)
                                     .await;
                             }
+                            ImapCommand::StoreGmailLabels {
+                                folder,
+                                uids,
+                                add_labels,
+                                remove_labels,
+                                response_tx,
+                            } => {
+                                Self::handle_store_gmail_labels(
+                                    &mut client,
+                                    &folder,
+                                    &uids,
+                                    &add_labels,
+                                    &remove_labels,
+                                    &response_tx,
+                                    &mut current_folder,
+                                )
+                                .await;
+                            }
                             ImapCommand::MoveMessage {
                                 source_folder,
                                 dest_folder,
-                                uid,
+                                uids,
                                 response_tx,
                             } => {
-                                Self::handle_move_message(&mut client, &source_folder, &dest_folder, uid, &response_tx, &mut current_folder)
+                                Self::handle_move_message(&mut client, &source_folder, &dest_folder, &uids, &response_tx, &mut current_folder)
+                                    .await;
+                            }
+                            ImapCommand::DeleteMessages {
+                                folder,
+                                trash,
+                                uids,
+                                response_tx,
+                            } => {
+                                Self::handle_delete_messages(&mut client, &folder, trash.as_deref(), &uids, &response_tx, &mut current_folder)
                                     .await;
                             }
                             ImapCommand::CreateFolder {
@@ -330,11 +612,11 @@ impl ImapPool {
                                 match client.create_folder(&folder_path).await {
                                     Ok(_) => {
                                         info!("IMAP: created folder {}", folder_path);
-                                        let _ = response_tx.send(ImapResponse::Ok);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Ok);
                                     }
                                     Err(e) => {
                                         error!("IMAP: create folder failed: {}", e);
-                                        let _ = response_tx.send(ImapResponse::Error(e.to_string()));
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
                                     }
                                 }
                             }
@@ -350,11 +632,11 @@ impl ImapPool {
                                         if current_folder.as_deref() == Some(&from_path) {
                                             current_folder = Some(to_path);
                                         }
-                                        let _ = response_tx.send(ImapResponse::Ok);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Ok);
                                     }
                                     Err(e) => {
                                         error!("IMAP: rename folder failed: {}", e);
-                                        let _ = response_tx.send(ImapResponse::Error(e.to_string()));
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
                                     }
                                 }
                             }
@@ -369,11 +651,11 @@ impl ImapPool {
                                 match client.delete_folder(&folder_path).await {
                                     Ok(_) => {
                                         info!("IMAP: deleted folder {}", folder_path);
-                                        let _ = response_tx.send(ImapResponse::Ok);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Ok);
                                     }
                                     Err(e) => {
                                         error!("IMAP: delete folder failed: {}", e);
-                                        let _ = response_tx.send(ImapResponse::Error(e.to_string()));
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
                                     }
                                 }
                             }
@@ -385,20 +667,64 @@ impl ImapPool {
                                     Ok(_) => {
                                         info!("IMAP: emptied folder {}", folder_path);
                                         current_folder = Some(folder_path);
-                                        let _ = response_tx.send(ImapResponse::Ok);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Ok);
                                     }
                                     Err(e) => {
                                         error!("IMAP: empty folder failed: {}", e);
-                                        let _ = response_tx.send(ImapResponse::Error(e.to_string()));
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
+                                    }
+                                }
+                            }
+                            ImapCommand::PeekFolder {
+                                folder,
+                                count,
+                                response_tx,
+                            } => {
+                                // The peek leaves the folder EXAMINEd (read-only),
+                                // so the next command must SELECT again
+                                current_folder = None;
+                                match client.peek_folder(&folder, count).await {
+                                    Ok(peek) => {
+                                        let _ = response_tx.unbounded_send(ImapResponse::Peek(peek));
+                                    }
+                                    Err(e) => {
+                                        debug!("IMAP: peek at {} failed: {}", folder, e);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
+                                    }
+                                }
+                            }
+                            ImapCommand::Search {
+                                folder,
+                                query,
+                                limit,
+                                response_tx,
+                            } => {
+                                Self::handle_search(&mut client, &folder, &query, limit, &response_tx, &mut current_folder)
+                                    .await;
+                            }
+                            ImapCommand::GetQuota { response_tx } => {
+                                match client.get_quota_root().await {
+                                    Ok(quotas) => {
+                                        let _ = response_tx.unbounded_send(ImapResponse::Quota(quotas));
+                                    }
+                                    Err(northmail_imap::ImapError::Unsupported(_)) => {
+                                        let _ = response_tx.unbounded_send(ImapResponse::Quota(Vec::new()));
+                                    }
+                                    Err(e) => {
+                                        debug!("IMAP: quota query failed: {}", e);
+                                        let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
                                     }
                                 }
                             }
                         }
                     }
                     Err(mpsc::RecvTimeoutError::Timeout) => {
-                        // Send NOOP to keep connection alive
-                        if let Err(e) = client.noop().await {
-                            warn!("NOOP failed, connection may be dead: {}", e);
+                        // Send NOOP to keep connection alive; a dead connection
+                        // ends the worker and the next request starts a new one
+                        clock = SuspendDetector::new();
+                        if let Err(e) = client.keepalive().await {
+                            warn!("NOOP failed, connection is dead: {}", e);
+                            Self::fail_pending(&command_rx, &format!("Connection lost: {}", e));
                             return;
                         }
                         debug!("IMAP keepalive NOOP sent");
@@ -415,15 +741,15 @@ impl ImapPool {
 
     /// Handle FetchHeaders command
     async fn handle_fetch_headers(
-        client: &mut SimpleImapClient,
+        client: &mut ImapClient,
         folder: &str,
         range: &str,
-        response_tx: &mpsc::Sender<ImapResponse>,
+        response_tx: &ResponseSender,
     ) {
         // Select folder
         match client.select(folder).await {
             Ok(info) => {
-                let _ = response_tx.send(ImapResponse::FolderInfo {
+                let _ = response_tx.unbounded_send(ImapResponse::FolderInfo {
                     message_count: info.message_count.unwrap_or(0),
                     uid_next: info.uid_next,
                     uidvalidity: info.uidvalidity,
@@ -432,10 +758,10 @@ impl ImapPool {
                 // Fetch headers
                 match client.fetch_headers(range).await {
                     Ok(headers) => {
-                        let _ = response_tx.send(ImapResponse::Headers(headers));
+                        let _ = response_tx.unbounded_send(ImapResponse::Headers(headers));
                     }
                     Err(e) => {
-                        let _ = response_tx.send(ImapResponse::Error(format!(
+                        let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                             "Failed to fetch headers: {}",
                             e
                         )));
@@ -443,20 +769,107 @@ impl ImapPool {
                 }
             }
             Err(e) => {
-                let _ = response_tx.send(ImapResponse::Error(format!(
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to select folder: {}",
+                    e
+                )));
+            }
+        }
+    }
+
+    /// Handle Search command
+    async fn handle_search(
+        client: &mut ImapClient,
+        folder: &str,
+        query: &ServerQuery,
+        limit: usize,
+        response_tx: &ResponseSender,
+        current_folder: &mut Option<String>,
+    ) {
+        if current_folder.as_deref() != Some(folder) {
+            if let Err(e) = client.select(folder).await {
+                *current_folder = None;
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to select folder: {}",
+                    e
+                )));
+                return;
+            }
+            *current_folder = Some(folder.to_string());
+        }
+
+        let result = match query {
+            ServerQuery::Gmail(raw) => client.gmail_raw_search(raw).await,
+            ServerQuery::Imap(criteria) => client.uid_search(criteria).await,
+        };
+        let mut uids = match result {
+            Ok(uids) => uids,
+            Err(e) => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(e.to_string()));
+                return;
+            }
+        };
+        info!("Server search {:?} in {} matched {} messages", query, folder, uids.len());
+        if uids.is_empty() {
+            let _ = response_tx.unbounded_send(ImapResponse::Headers(Vec::new()));
+            return;
+        }
+
+        uids.sort_unstable();
+        let newest = &uids[uids.len().saturating_sub(limit)..];
+        match client.uid_fetch_headers(&northmail_imap::format_uid_set(newest)).await {
+            Ok(mut headers) => {
+                headers.sort_by_key(|h| h.uid);
+                let _ = response_tx.unbounded_send(ImapResponse::Headers(headers));
+            }
+            Err(e) => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to fetch headers: {}",
+                    e
+                )));
+            }
+        }
+    }
+
+    /// Handle FetchHeadersByUid command
+    async fn handle_fetch_headers_by_uid(
+        client: &mut ImapClient,
+        folder: &str,
+        uids: &str,
+        response_tx: &ResponseSender,
+        current_folder: &mut Option<String>,
+    ) {
+        if current_folder.as_deref() != Some(folder) {
+            if let Err(e) = client.select(folder).await {
+                *current_folder = None;
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                     "Failed to select folder: {}",
                     e
                 )));
+                return;
+            }
+            *current_folder = Some(folder.to_string());
+        }
+
+        match client.uid_fetch_headers(uids).await {
+            Ok(headers) => {
+                let _ = response_tx.unbounded_send(ImapResponse::Headers(headers));
+            }
+            Err(e) => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to fetch headers: {}",
+                    e
+                )));
             }
         }
     }
 
     /// Handle FetchBody command (with folder tracking to avoid redundant SELECTs)
     async fn handle_fetch_body(
-        client: &mut SimpleImapClient,
+        client: &mut ImapClient,
         folder: &str,
         uid: u32,
-        response_tx: &mpsc::Sender<ImapResponse>,
+        response_tx: &ResponseSender,
         current_folder: &mut Option<String>,
     ) {
         // Only SELECT if folder changed (like Geary's approach)
@@ -471,7 +884,7 @@ impl ImapPool {
                 Err(e) => {
                     error!("handle_fetch_body: failed to select folder: {}", e);
                     *current_folder = None;
-                    let _ = response_tx.send(ImapResponse::Error(format!(
+                    let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                         "Failed to select folder: {}",
                         e
                     )));
@@ -488,11 +901,11 @@ impl ImapPool {
         match client.fetch_body(uid).await {
             Ok(body) => {
                 debug!("handle_fetch_body: got body, {} bytes", body.len());
-                let _ = response_tx.send(ImapResponse::Body(body));
+                let _ = response_tx.unbounded_send(ImapResponse::Body(body));
             }
             Err(e) => {
                 error!("handle_fetch_body: failed to fetch body: {}", e);
-                let _ = response_tx.send(ImapResponse::Error(format!(
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                     "Failed to fetch body: {}",
                     e
                 )));
@@ -500,14 +913,160 @@ impl ImapPool {
         }
     }
 
+    /// Handle FetchBodyParts command: read BODYSTRUCTURE, then download only
+    /// the parts needed to display the message. Falls back to the full
+    /// message if the structure can't be read.
+    async fn handle_fetch_body_parts(
+        client: &mut ImapClient,
+        folder: &str,
+        uid: u32,
+        inline_resources: bool,
+        response_tx: &ResponseSender,
+        current_folder: &mut Option<String>,
+    ) {
+        if let Err(e) = Self::ensure_selected(client, folder, current_folder).await {
+            let _ = response_tx.unbounded_send(ImapResponse::Error(e));
+            return;
+        }
+
+        let structure = match client.uid_fetch_bodystructure(uid).await {
+            Ok(parts) if !parts.is_empty() => parts,
+            Ok(_) | Err(_) => {
+                warn!("handle_fetch_body_parts: no usable BODYSTRUCTURE for uid {}, fetching full message", uid);
+                Self::handle_fetch_body(client, folder, uid, response_tx, current_folder).await;
+                return;
+            }
+        };
+
+        // The header says whether a read receipt is asked for
+        let header = match client.uid_fetch_section(uid, "HEADER").await {
+            Ok(header) => header,
+            Err(e) => {
+                warn!("handle_fetch_body_parts: failed to fetch header for uid {}: {}", uid, e);
+                Vec::new()
+            }
+        };
+
+        let mut fetched = Vec::new();
+        let mut deferred = Vec::new();
+        for part in structure {
+            if !(part.is_body_text()
+                || part.is_disposition_notification()
+                || part.is_calendar()
+                || (inline_resources && part.is_inline_resource()))
+            {
+                deferred.push(part);
+                continue;
+            }
+            match client.uid_fetch_section(uid, &part.section).await {
+                Ok(raw) => {
+                    let data = part.decode(&raw);
+                    fetched.push((part, data));
+                }
+                Err(e) => {
+                    error!("handle_fetch_body_parts: failed to fetch part {}: {}", part.section, e);
+                    let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                        "Failed to fetch body: {}",
+                        e
+                    )));
+                    return;
+                }
+            }
+        }
+
+        debug!(
+            "handle_fetch_body_parts: uid {} fetched {} parts, deferred {}",
+            uid,
+            fetched.len(),
+            deferred.len()
+        );
+        let _ = response_tx.unbounded_send(ImapResponse::BodyParts { header, fetched, deferred });
+    }
+
+    /// Handle FetchAttachment command (download a single deferred part)
+    async fn handle_fetch_attachment(
+        client: &mut ImapClient,
+        folder: &str,
+        uid: u32,
+        section: Option<&str>,
+        filename: &str,
+        response_tx: &ResponseSender,
+        current_folder: &mut Option<String>,
+    ) {
+        if let Err(e) = Self::ensure_selected(client, folder, current_folder).await {
+            let _ = response_tx.unbounded_send(ImapResponse::Error(e));
+            return;
+        }
+
+        // The structure tells us the transfer encoding, and locates the part
+        // by filename for attachments cached before sections were known
+        let part = match client.uid_fetch_bodystructure(uid).await {
+            Ok(parts) => parts.into_iter().find(|p| match section {
+                Some(section) => p.section == section,
+                None => p.filename().as_deref() == Some(filename),
+            }),
+            Err(e) => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to read message structure: {}",
+                    e
+                )));
+                return;
+            }
+        };
+        let Some(part) = part else {
+            let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                "Attachment {} not found",
+                filename
+            )));
+            return;
+        };
+
+        match client.uid_fetch_section(uid, &part.section).await {
+            Ok(raw) => {
+                let data = part.decode(&raw);
+                debug!("handle_fetch_attachment: uid {} part {} -> {} bytes", uid, part.section, data.len());
+                let _ = response_tx.unbounded_send(ImapResponse::Attachment(data));
+            }
+            Err(e) => {
+                error!("handle_fetch_attachment: failed to fetch part {}: {}", part.section, e);
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to fetch attachment: {}",
+                    e
+                )));
+            }
+        }
+    }
+
+    /// SELECT a folder unless it is already the selected one
+    async fn ensure_selected(
+        client: &mut ImapClient,
+        folder: &str,
+        current_folder: &mut Option<String>,
+    ) -> Result<(), String> {
+        if current_folder.as_deref() == Some(folder) {
+            return Ok(());
+        }
+        match client.select(folder).await {
+            Ok(_) => {
+                *current_folder = Some(folder.to_string());
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to select folder {}: {}", folder, e);
+                *current_folder = None;
+                Err(format!("Failed to select folder: {}", e))
+            }
+        }
+    }
+
     /// Handle StoreFlags command (set/remove flags on a message)
     async fn handle_store_flags(
-        client: &mut SimpleImapClient,
+        client: &mut ImapClient,
         folder: &str,
-        uid: u32,
+        uids: &[u32],
         add_flags: &[String],
         remove_flags: &[String],
-        response_tx: &mpsc::Sender<ImapResponse>,
+        response_tx: &ResponseSender,
         current_folder: &mut Option<String>,
     ) {
         // Select folder if needed
@@ -520,7 +1079,7 @@ impl ImapPool {
                 Err(e) => {
                     error!("handle_store_flags: failed to select folder: {}", e);
                     *current_folder = None;
-                    let _ = response_tx.send(ImapResponse::Error(format!(
+                    let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                         "Failed to select folder: {}",
                         e
                     )));
@@ -532,10 +1091,10 @@ impl ImapPool {
         // Add flags
         if !add_flags.is_empty() {
             let flags_str = add_flags.join(" ");
-            debug!("handle_store_flags: adding flags {} to uid {}", flags_str, uid);
-            if let Err(e) = client.uid_store_flags(uid, &flags_str, true).await {
+            debug!("handle_store_flags: adding flags {} to uids {:?}", flags_str, uids);
+            if let Err(e) = client.store_flags(uids, &flags_str, true).await {
                 error!("handle_store_flags: failed to add flags: {}", e);
-                let _ = response_tx.send(ImapResponse::Error(format!(
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                     "Failed to add flags: {}",
                     e
                 )));
@@ -546,10 +1105,10 @@ impl ImapPool {
         // Remove flags
         if !remove_flags.is_empty() {
             let flags_str = remove_flags.join(" ");
-            debug!("handle_store_flags: removing flags {} from uid {}", flags_str, uid);
-            if let Err(e) = client.uid_store_flags(uid, &flags_str, false).await {
+            debug!("handle_store_flags: removing flags {} from uids {:?}", flags_str, uids);
+            if let Err(e) = client.store_flags(uids, &flags_str, false).await {
                 error!("handle_store_flags: failed to remove flags: {}", e);
-                let _ = response_tx.send(ImapResponse::Error(format!(
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                     "Failed to remove flags: {}",
                     e
                 )));
@@ -557,16 +1116,58 @@ impl ImapPool {
             }
         }
 
-        let _ = response_tx.send(ImapResponse::Ok);
+        let _ = response_tx.unbounded_send(ImapResponse::Ok);
     }
 
-    /// Handle MoveMessage command (COPY to dest folder, then mark \Deleted in source)
+    /// Handle StoreGmailLabels command
+    async fn handle_store_gmail_labels(
+        client: &mut ImapClient,
+        folder: &str,
+        uids: &[u32],
+        add_labels: &[String],
+        remove_labels: &[String],
+        response_tx: &ResponseSender,
+        current_folder: &mut Option<String>,
+    ) {
+        if current_folder.as_deref() != Some(folder) {
+            match client.select(folder).await {
+                Ok(_) => {
+                    *current_folder = Some(folder.to_string());
+                }
+                Err(e) => {
+                    error!("handle_store_gmail_labels: failed to select folder: {}", e);
+                    *current_folder = None;
+                    let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                        "Failed to select folder: {}",
+                        e
+                    )));
+                    return;
+                }
+            }
+        }
+
+        for (labels, add) in [(add_labels, true), (remove_labels, false)] {
+            debug!("handle_store_gmail_labels: {} {:?} on uids {:?}", if add { "adding" } else { "removing" }, labels, uids);
+            if let Err(e) = client.store_gmail_labels(uids, labels, add).await {
+                error!("handle_store_gmail_labels: failed to store labels: {}", e);
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to store labels: {}",
+                    e
+                )));
+                return;
+            }
+        }
+
+        let _ = response_tx.unbounded_send(ImapResponse::Ok);
+    }
+
+    /// Handle MoveMessage command (UID MOVE, falling back to COPY + EXPUNGE)
     async fn handle_move_message(
-        client: &mut SimpleImapClient,
+        client: &mut ImapClient,
         source_folder: &str,
         dest_folder: &str,
-        uid: u32,
-        response_tx: &mpsc::Sender<ImapResponse>,
+        uids: &[u32],
+        response_tx: &ResponseSender,
         current_folder: &mut Option<String>,
     ) {
         // Select source folder if needed
@@ -579,7 +1180,7 @@ impl ImapPool {
                 Err(e) => {
                     error!("handle_move_message: failed to select source folder: {}", e);
                     *current_folder = None;
-                    let _ = response_tx.send(ImapResponse::Error(format!(
+                    let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
                         "Failed to select source folder: {}",
                         e
                     )));
@@ -588,76 +1189,122 @@ impl ImapPool {
             }
         }
 
-        // Copy to destination folder
-        debug!("handle_move_message: copying uid {} to {}", uid, dest_folder);
-        if let Err(e) = client.uid_copy(uid, dest_folder).await {
-            error!("handle_move_message: failed to copy message: {}", e);
-            let _ = response_tx.send(ImapResponse::Error(format!(
-                "Failed to copy message: {}",
-                e
-            )));
-            return;
-        }
+        debug!("handle_move_message: moving uids {:?} to {}", uids, dest_folder);
+        let dest_uids = match client.move_messages(uids, dest_folder).await {
+            Ok(copy_uid) => copy_uid.map(|c| c.dest_uids).unwrap_or_default(),
+            Err(e) => {
+                error!("handle_move_message: failed to move messages: {}", e);
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to move messages: {}",
+                    e
+                )));
+                return;
+            }
+        };
 
-        // Mark as deleted in source folder
-        debug!("handle_move_message: marking uid {} as deleted", uid);
-        if let Err(e) = client.uid_store_flags(uid, "\\Deleted", true).await {
-            error!("handle_move_message: failed to mark as deleted: {}", e);
-            let _ = response_tx.send(ImapResponse::Error(format!(
-                "Failed to mark as deleted: {}",
-                e
-            )));
-            return;
+        info!(
+            "handle_move_message: moved uids {:?} from {} to {} (dest uids {:?})",
+            uids, source_folder, dest_folder, dest_uids
+        );
+        let _ = response_tx.unbounded_send(ImapResponse::Moved { dest_uids });
+    }
+
+    /// Handle DeleteMessages command (see `ImapClient::delete_messages`)
+    async fn handle_delete_messages(
+        client: &mut ImapClient,
+        folder: &str,
+        trash: Option<&str>,
+        uids: &[u32],
+        response_tx: &ResponseSender,
+        current_folder: &mut Option<String>,
+    ) {
+        if current_folder.as_deref() != Some(folder) {
+            debug!("handle_delete_messages: selecting folder {}", folder);
+            match client.select(folder).await {
+                Ok(_) => {
+                    *current_folder = Some(folder.to_string());
+                }
+                Err(e) => {
+                    error!("handle_delete_messages: failed to select folder: {}", e);
+                    *current_folder = None;
+                    let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                        "Failed to select folder: {}",
+                        e
+                    )));
+                    return;
+                }
+            }
         }
 
-        // Expunge to permanently remove from source (use UID EXPUNGE for reliability)
-        debug!("handle_move_message: uid_expunge uid {}", uid);
-        if let Err(e) = client.uid_expunge(uid).await {
-            // Fall back to regular EXPUNGE if UID EXPUNGE not supported
-            debug!("handle_move_message: uid_expunge failed, trying regular expunge: {}", e);
-            if let Err(e2) = client.expunge().await {
-                error!("handle_move_message: failed to expunge: {}", e2);
-                let _ = response_tx.send(ImapResponse::Error(format!(
-                    "Failed to expunge: {}",
-                    e2
+        let dest_uids = match client.delete_messages(uids, trash).await {
+            Ok(copy_uid) => copy_uid.map(|c| c.dest_uids).unwrap_or_default(),
+            Err(e) => {
+                error!("handle_delete_messages: failed to delete messages: {}", e);
+                let _ = response_tx.unbounded_send(ImapResponse::Error(format!(
+                    "Failed to delete messages: {}",
+                    e
                 )));
                 return;
             }
-        }
+        };
 
-        info!("handle_move_message: moved uid {} from {} to {}", uid, source_folder, dest_folder);
-        let _ = response_tx.send(ImapResponse::Ok);
+        info!("handle_delete_messages: deleted uids {:?} from {}", uids, folder);
+        let _ = response_tx.unbounded_send(ImapResponse::Moved { dest_uids });
     }
 
     /// Send an error response for a command
     fn send_error_response(cmd: &ImapCommand, error: &str) {
         match cmd {
             ImapCommand::FetchHeaders { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::FetchHeadersByUid { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::FetchBody { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::FetchBodyParts { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::FetchAttachment { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::StoreFlags { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::StoreGmailLabels { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::MoveMessage { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::DeleteMessages { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::CreateFolder { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::RenameFolder { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::DeleteFolder { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::EmptyFolder { response_tx, .. } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::PeekFolder { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::Search { response_tx, .. } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
+            }
+            ImapCommand::GetQuota { response_tx } => {
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::Noop { response_tx } => {
-                let _ = response_tx.send(ImapResponse::Error(error.to_string()));
+                let _ = response_tx.unbounded_send(ImapResponse::Error(error.to_string()));
             }
             ImapCommand::Shutdown => {}
         }
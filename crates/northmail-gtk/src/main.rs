@@ -6,6 +6,10 @@ mod application;
 pub mod i18n;
 mod idle_manager;
 mod imap_pool;
+mod profile;
+mod speech;
+mod sync_engine;
+mod timeline;
 mod window;
 mod widgets;
 
@@ -17,10 +21,23 @@ fn main() {
     // Initialize logging
     tracing_subscriber::registry()
         .with(fmt::layer())
+        .with(timeline::TimelineLayer::new())
         .with(EnvFilter::from_default_env().add_directive("northmail=debug".parse().unwrap()))
         .init();
 
-    tracing::info!("Starting NorthMail");
+    // Select the profile before anything reads settings or opens files
+    let args = match profile::init_from_args(std::env::args().collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    match profile::name() {
+        Some(name) => tracing::info!("Starting NorthMail (profile {})", name),
+        None => tracing::info!("Starting NorthMail"),
+    }
 
     // Initialize i18n/gettext
     i18n::init();
@@ -44,5 +61,5 @@ fn main() {
 
     // Create and run the application
     let app = NorthMailApplication::new();
-    std::process::exit(app.run().into());
+    std::process::exit(app.run_with_args(&args).into());
 }
@@ -0,0 +1,182 @@
+//! Profiles
+//!
+//! `northmail --profile work` runs with its own settings, database, cache,
+//! UI state and keyring entries, so personal and work mail stay apart and a
+//! second profile can be used to test against without touching the first.
+//! The default profile keeps the original locations. Named profiles live in
+//! a `profiles/<name>` subdirectory of each XDG directory, keep their
+//! settings in a keyfile instead of dconf, and use their own application
+//! ID so they can run alongside the default profile.
+//!
+//! Online accounts come from GNOME Online Accounts and are shared by all
+//! profiles.
+
+use gtk4::{gio, glib};
+use std::cell::OnceCell;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Application ID of the default profile, also the settings schema ID
+pub const APP_ID: &str = "com.petrariu.NorthMail";
+
+/// Directory name under each XDG base directory
+const DIR_NAME: &str = "northmail";
+
+/// Profile name for this process; `None` is the default profile
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+thread_local! {
+    /// Settings backend shared by every `gio::Settings` of a named profile,
+    /// so they all see each other's changes
+    static BACKEND: OnceCell<gio::SettingsBackend> = const { OnceCell::new() };
+}
+
+/// Take `--profile <name>` or `--profile=<name>` out of the command line
+/// and select that profile. Returns the remaining arguments for GTK, or an
+/// error message when the name is missing or not usable.
+pub fn init_from_args(args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut remaining = Vec::with_capacity(args.len());
+    let mut name = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            name = Some(args.next().ok_or("--profile needs a name")?);
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            name = Some(value.to_string());
+        } else {
+            remaining.push(arg);
+        }
+    }
+
+    let name = match name {
+        Some(name) if name == "default" => None,
+        Some(name) if is_valid_name(&name) => Some(name),
+        Some(name) => {
+            return Err(format!(
+                "Invalid profile name “{}”: use letters, digits, ‘-’ and ‘_’",
+                name
+            ))
+        }
+        None => None,
+    };
+    if let Some(name) = &name {
+        northmail_auth::set_secrets_profile(name);
+    }
+    let _ = PROFILE.set(name);
+    Ok(remaining)
+}
+
+/// Profile names become directory names and part of the application ID
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Name of the running profile, `None` for the default one
+pub fn name() -> Option<&'static str> {
+    PROFILE.get().and_then(|p| p.as_deref())
+}
+
+/// Application ID; named profiles get their own so they don't hand their
+/// window to an instance already running another profile
+pub fn application_id() -> String {
+    match name() {
+        // An ID element may not start with a digit, so prefix it
+        Some(name) => format!("{}.profile_{}", APP_ID, id_element(name)),
+        None => APP_ID.to_string(),
+    }
+}
+
+/// A profile name as an ID element, which can't hold `-`. Both `-` and `_`
+/// are escaped as `_` and their hex code, so `a-b` and `a_b` stay apart.
+fn id_element(name: &str) -> String {
+    let mut element = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '-' | '_' => element.push_str(&format!("_{:02x}", c as u32)),
+            c => element.push(c),
+        }
+    }
+    element
+}
+
+/// Application settings for the running profile
+pub fn settings() -> gio::Settings {
+    if name().is_none() {
+        return gio::Settings::new(APP_ID);
+    }
+    BACKEND.with(|backend| {
+        let backend = backend.get_or_init(|| {
+            let dir = config_dir();
+            std::fs::create_dir_all(&dir).ok();
+            let path = dir.join("settings.ini");
+            gio::keyfile_settings_backend_new(&path.to_string_lossy(), "/com/petrariu/NorthMail/", None)
+        });
+        gio::Settings::with_backend(APP_ID, backend)
+    })
+}
+
+fn profile_dir(base: PathBuf) -> PathBuf {
+    let dir = base.join(DIR_NAME);
+    match name() {
+        Some(name) => dir.join("profiles").join(name),
+        None => dir,
+    }
+}
+
+/// Configuration directory, e.g. `~/.config/northmail`
+pub fn config_dir() -> PathBuf {
+    profile_dir(glib::user_config_dir())
+}
+
+/// Data directory holding the mail database, e.g. `~/.local/share/northmail`
+pub fn data_dir() -> PathBuf {
+    profile_dir(glib::user_data_dir())
+}
+
+/// Cache directory, e.g. `~/.cache/northmail`
+pub fn cache_dir() -> PathBuf {
+    profile_dir(glib::user_cache_dir())
+}
+
+/// State directory for things like the last open folder, e.g.
+/// `~/.local/state/northmail`
+pub fn state_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| glib::home_dir().join(".local").join("state"));
+    profile_dir(base)
+}
+
+/// Temporary directory for attachments opened in other applications,
+/// removed at shutdown
+pub fn attachments_temp_dir() -> PathBuf {
+    match name() {
+        Some(name) => std::env::temp_dir().join(format!("northmail-attachments-{}", name)),
+        None => std::env::temp_dir().join("northmail-attachments"),
+    }
+}
+
+/// Temporary directory for messages dragged out as `.eml` files, inside
+/// [`attachments_temp_dir`] so it goes at shutdown too
+pub fn dragged_messages_dir() -> PathBuf {
+    attachments_temp_dir().join("messages")
+}
+
+/// Path of a state file, creating its directory. Older versions kept state
+/// files in `legacy_dir`; one found there is moved over on first use.
+pub fn state_file(file: &str, legacy_dir: &Path) -> PathBuf {
+    let dir = state_dir();
+    std::fs::create_dir_all(&dir).ok();
+    let path = dir.join(file);
+    let legacy = legacy_dir.join(file);
+    if !path.exists() && legacy.exists() {
+        if let Err(e) = std::fs::rename(&legacy, &path) {
+            tracing::warn!("Failed to move {} to {}: {}", legacy.display(), path.display(), e);
+            return legacy;
+        }
+    }
+    path
+}
@@ -0,0 +1,155 @@
+//! Read-aloud through speech-dispatcher
+//!
+//! Talks SSIP to the speech-dispatcher socket directly, so there's no
+//! library to link. A reader thread splits the server's lines into command
+//! replies and speech events (begin, end, pause...), which the UI polls to
+//! keep its play/pause controls in step.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+/// How long to wait for speech-dispatcher to answer a command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Progress of the message being spoken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeechEvent {
+    Started,
+    Paused,
+    Resumed,
+    /// Finished or stopped
+    Ended,
+}
+
+/// Connection to speech-dispatcher
+pub struct Speech {
+    stream: UnixStream,
+    replies: mpsc::Receiver<String>,
+    events: mpsc::Receiver<SpeechEvent>,
+}
+
+impl Speech {
+    /// Connect to speech-dispatcher, starting it if it isn't running
+    pub fn connect() -> io::Result<Self> {
+        let path = socket_path();
+        let stream = match UnixStream::connect(&path) {
+            Ok(stream) => stream,
+            Err(_) => {
+                debug!("Starting speech-dispatcher");
+                std::process::Command::new("speech-dispatcher")
+                    .arg("--spawn")
+                    .status()?;
+                UnixStream::connect(&path)?
+            }
+        };
+
+        let (reply_tx, replies) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let reader = BufReader::new(stream.try_clone()?);
+        std::thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                // Events are 7xx; the last line of each carries the kind
+                if line.starts_with('7') {
+                    let event = match line.get(..4) {
+                        Some("701 ") => SpeechEvent::Started,
+                        Some("702 ") | Some("703 ") => SpeechEvent::Ended,
+                        Some("704 ") => SpeechEvent::Paused,
+                        Some("705 ") => SpeechEvent::Resumed,
+                        _ => continue,
+                    };
+                    if event_tx.send(event).is_err() {
+                        break;
+                    }
+                } else if line.as_bytes().get(3) == Some(&b' ') && reply_tx.send(line).is_err() {
+                    // Only the final line of a reply ("230 OK ...") is kept
+                    break;
+                }
+            }
+        });
+
+        let mut speech = Self { stream, replies, events };
+        speech.command("SET self CLIENT_NAME user:northmail:read-aloud")?;
+        speech.command("SET self NOTIFICATION BEGIN on")?;
+        speech.command("SET self NOTIFICATION END on")?;
+        speech.command("SET self NOTIFICATION CANCEL on")?;
+        speech.command("SET self NOTIFICATION PAUSE on")?;
+        speech.command("SET self NOTIFICATION RESUME on")?;
+        Ok(speech)
+    }
+
+    /// Send one command line and wait for its reply
+    fn command(&mut self, command: &str) -> io::Result<String> {
+        self.stream.write_all(format!("{}\r\n", command).as_bytes())?;
+        let reply = self
+            .replies
+            .recv_timeout(REPLY_TIMEOUT)
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "speech-dispatcher did not answer"))?;
+        if !reply.starts_with('2') {
+            warn!("speech-dispatcher: {} -> {}", command, reply);
+            return Err(io::Error::other(reply));
+        }
+        Ok(reply)
+    }
+
+    /// Speak `text`, replacing anything still being read. `language` is an
+    /// ISO 639-1 code, when known.
+    pub fn speak(&mut self, text: &str, language: Option<&str>) -> io::Result<()> {
+        self.command("CANCEL self")?;
+        while self.events.try_recv().is_ok() {}
+        if let Some(language) = language {
+            // Unknown languages keep the default voice
+            let _ = self.command(&format!("SET self LANGUAGE {}", language));
+        }
+        self.command("SPEAK")?;
+        self.stream
+            .write_all(northmail_core::read_aloud::ssip_data(text).as_bytes())?;
+        self.replies
+            .recv_timeout(REPLY_TIMEOUT)
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "speech-dispatcher did not answer"))?;
+        Ok(())
+    }
+
+    pub fn pause(&mut self) -> io::Result<()> {
+        self.command("PAUSE self").map(|_| ())
+    }
+
+    pub fn resume(&mut self) -> io::Result<()> {
+        self.command("RESUME self").map(|_| ())
+    }
+
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.command("CANCEL self").map(|_| ())
+    }
+
+    /// Next speech event, if one has arrived
+    pub fn try_event(&self) -> Option<SpeechEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Drop for Speech {
+    fn drop(&mut self) {
+        let _ = self.stream.write_all(b"CANCEL self\r\nQUIT\r\n");
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// speech-dispatcher's socket: `SPEECHD_ADDRESS` when it names a Unix
+/// socket, else the per-user default
+fn socket_path() -> PathBuf {
+    if let Ok(address) = std::env::var("SPEECHD_ADDRESS") {
+        if let Some(path) = address.strip_prefix("unix_socket:") {
+            return PathBuf::from(path);
+        }
+    }
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    runtime_dir.join("speech-dispatcher").join("speechd.sock")
+}
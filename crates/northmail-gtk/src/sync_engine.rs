@@ -0,0 +1,77 @@
+//! The core sync engine, run for the GTK app
+//!
+//! The engine runs on the shared network runtime, like other background
+//! work here, and is driven through its command channel. Its events are
+//! polled on the main loop by the application. Accounts log in with GNOME
+//! Online Accounts credentials, the same way IMAP pool workers do, read
+//! straight from GOA over D-Bus: the keyring side of [`AuthManager`] can't
+//! leave the main thread.
+//!
+//! [`AuthManager`]: northmail_auth::AuthManager
+
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use northmail_auth::GoaManager;
+use northmail_core::runtime::spawn_io;
+use northmail_core::{CoreError, CoreResult, Database, ImapConnector, SyncCommand, SyncEngine, SyncEvent};
+use northmail_imap::ImapClient;
+use tokio::sync::mpsc;
+
+use crate::imap_pool::{ImapCredentials, ImapPool};
+
+/// Logs in to GNOME Online Accounts mail accounts over IMAP
+struct GoaConnector;
+
+impl ImapConnector for GoaConnector {
+    fn connect<'a>(&'a self, account_id: &'a str) -> BoxFuture<'a, CoreResult<ImapClient>> {
+        Box::pin(async move {
+            let goa = GoaManager::new().await?;
+            let account = goa
+                .list_mail_accounts()
+                .await?
+                .into_iter()
+                .find(|a| a.id == account_id)
+                .ok_or_else(|| CoreError::AccountNotFound(account_id.to_string()))?;
+
+            let credentials = match account.provider_type.as_str() {
+                "google" => ImapCredentials::Gmail {
+                    email: account.email.clone(),
+                    access_token: goa.get_access_token(&account.id).await?,
+                },
+                "windows_live" | "microsoft" => ImapCredentials::Microsoft {
+                    email: account.email.clone(),
+                    access_token: goa.get_access_token(&account.id).await?,
+                },
+                _ if account.auth_type == northmail_auth::GoaAuthType::Password => ImapCredentials::Password {
+                    host: account.imap_host.clone().unwrap_or_else(|| northmail_core::icloud::IMAP_HOST.to_string()),
+                    port: 993,
+                    username: account.imap_username.clone().unwrap_or_else(|| account.email.clone()),
+                    password: goa.get_password(&account.id).await?,
+                },
+                // ms_graph accounts have no IMAP access
+                _ => {
+                    return Err(CoreError::SyncError(format!(
+                        "{} has no IMAP access",
+                        account.email
+                    )))
+                }
+            };
+
+            let mut client = ImapClient::new();
+            ImapPool::connect(&mut client, &credentials).await?;
+            Ok(client)
+        })
+    }
+}
+
+/// Start the engine on the network runtime. Returns the sender for commands
+/// and the receiver for the events it reports.
+pub fn spawn(database: Arc<Database>) -> (mpsc::Sender<SyncCommand>, mpsc::Receiver<SyncEvent>) {
+    let (command_tx, command_rx, event_tx, event_rx) = northmail_core::create_sync_channels();
+
+    let engine = SyncEngine::with_connector(database, Arc::new(GoaConnector), command_rx, event_tx);
+    spawn_io(engine.run());
+
+    (command_tx, event_rx)
+}
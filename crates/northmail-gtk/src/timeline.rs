@@ -0,0 +1,118 @@
+//! Records finished tracing spans into the sync timeline
+//!
+//! [`TimelineLayer`] sits next to the log output in the subscriber set up in
+//! `main`, so it only sees spans the log filter lets through.
+
+use northmail_core::timeline::{SpanRecord, Timeline, DEFAULT_CAPACITY};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+static TIMELINE: Mutex<Timeline> = Mutex::new(Timeline::new(DEFAULT_CAPACITY));
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Run `f` with the recorded spans, oldest first
+pub fn with_timeline<T>(f: impl FnOnce(&mut Timeline) -> T) -> T {
+    let mut timeline = TIMELINE.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut timeline)
+}
+
+fn thread_number() -> u64 {
+    THREAD.with(|thread| {
+        if thread.get() == 0 {
+            thread.set(NEXT_THREAD.fetch_add(1, Ordering::Relaxed));
+        }
+        thread.get()
+    })
+}
+
+/// Fields and start of a span that is still open
+struct OpenSpan {
+    fields: Vec<(String, String)>,
+    start: Instant,
+    thread: u64,
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<(String, String)>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field, format!("{:?}", value));
+    }
+}
+
+impl FieldVisitor<'_> {
+    fn set(&mut self, field: &Field, value: String) {
+        match self.0.iter_mut().find(|(name, _)| name == field.name()) {
+            Some(existing) => existing.1 = value,
+            None => self.0.push((field.name().to_string(), value)),
+        }
+    }
+}
+
+/// Layer that adds each span to the timeline when it closes
+pub struct TimelineLayer;
+
+impl TimelineLayer {
+    pub fn new() -> Self {
+        EPOCH.get_or_init(Instant::now);
+        Self
+    }
+}
+
+impl<S> Layer<S> for TimelineLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Vec::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        span.extensions_mut().insert(OpenSpan {
+            fields,
+            start: Instant::now(),
+            thread: thread_number(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(open) = extensions.get_mut::<OpenSpan>() {
+            values.record(&mut FieldVisitor(&mut open.fields));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else {
+            return;
+        };
+        let epoch = *EPOCH.get_or_init(Instant::now);
+        let record = SpanRecord {
+            name: span.name().to_string(),
+            target: span.metadata().target().to_string(),
+            fields: open.fields,
+            start_us: open.start.saturating_duration_since(epoch).as_micros() as u64,
+            duration_us: open.start.elapsed().as_micros() as u64,
+            thread: open.thread,
+        };
+        with_timeline(|timeline| timeline.push(record));
+    }
+}
@@ -5,9 +5,13 @@ use gtk4::{glib, prelude::*, subclass::prelude::*};
 use libadwaita as adw;
 use libadwaita::prelude::*;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-use crate::i18n::tr;
+use crate::i18n::{ntr, tr};
+
+/// How long the pointer rests on a folder before it is peeked at
+const PEEK_HOVER_DELAY: std::time::Duration = std::time::Duration::from_millis(700);
 
 /// Row widget name encoding: "section:kind:account_id:folder_path"
 /// Parsed with splitn(4, ':') so folder_path can contain ':'.
@@ -15,10 +19,11 @@ use crate::i18n::tr;
 /// Sections:
 ///   0 — unified inbox
 ///   1 — per-account inboxes
-///   1000 — starred section (virtual)
+///   1000 — starred, snoozed and tags section (virtual)
 ///   2+ — per-account folder groups (2 = first account, 3 = second, …)
 ///
-/// Kinds: unified, inbox, header, folder, starred-header, starred-all, starred-account
+/// Kinds: unified, inbox, header, folder, starred-header, starred-all, starred-account, snoozed,
+/// tag (folder_path holds the tag id)
 
 const STARRED_SECTION: usize = 1000;
 
@@ -79,6 +84,16 @@ mod imp {
         pub folder_expanded_states: RefCell<HashMap<String, bool>>,
         /// Starred section expansion state
         pub starred_expanded: RefCell<bool>,
+        /// Tags listed below Snoozed
+        pub tags: RefCell<Vec<super::TagInfo>>,
+        /// Folders opted into IDLE push (key: "account_id\0folder_path")
+        pub watched_folders: RefCell<HashSet<String>>,
+        /// Accounts whose push connection is reconnecting
+        pub reconnecting_accounts: RefCell<HashSet<String>>,
+        /// Folder row hovered for a peek: (account_id, folder_path, row)
+        pub peek_target: RefCell<Option<(String, String, glib::WeakRef<gtk4::ListBoxRow>)>>,
+        /// Popover showing the current peek
+        pub peek_popover: RefCell<Option<gtk4::Popover>>,
         // -- sync-status widgets (unchanged) --
         pub sync_status_box: RefCell<Option<gtk4::Box>>,
         pub sync_spinner: RefCell<Option<gtk4::Spinner>>,
@@ -136,6 +151,70 @@ mod imp {
                             String::static_type(), // folder_path
                         ])
                         .build(),
+                    Signal::builder("folder-import-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                        ])
+                        .build(),
+                    Signal::builder("folder-export-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                        ])
+                        .build(),
+                    Signal::builder("folder-offline-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                        ])
+                        .build(),
+                    Signal::builder("folder-duplicates-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                        ])
+                        .build(),
+                    Signal::builder("folder-sync-settings-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                        ])
+                        .build(),
+                    Signal::builder("folder-watch-toggled")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                            bool::static_type(),   // watched
+                        ])
+                        .build(),
+                    Signal::builder("account-pause-toggled")
+                        .param_types([
+                            String::static_type(), // account_id
+                            bool::static_type(),   // paused
+                        ])
+                        .build(),
+                    Signal::builder("account-ca-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                        ])
+                        .build(),
+                    Signal::builder("account-proxy-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                        ])
+                        .build(),
+                    Signal::builder("account-identities-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                        ])
+                        .build(),
+                    Signal::builder("folder-peek-requested")
+                        .param_types([
+                            String::static_type(), // account_id
+                            String::static_type(), // folder_path
+                        ])
+                        .build(),
                     Signal::builder("empty-trash-requested")
                         .param_types([
                             String::static_type(), // account_id
@@ -260,6 +339,91 @@ impl FolderSidebar {
         )
     }
 
+    /// Connect to the folder-import-requested signal (import an mbox file
+    /// into the folder)
+    pub fn connect_folder_import_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "folder-import-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar,
+                                       account_id: &str,
+                                       folder_path: &str| {
+                f(sidebar, account_id, folder_path);
+            }),
+        )
+    }
+
+    /// Connect to the folder-duplicates-requested signal (look for messages
+    /// cached more than once)
+    pub fn connect_folder_duplicates_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "folder-duplicates-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar,
+                                       account_id: &str,
+                                       folder_path: &str| {
+                f(sidebar, account_id, folder_path);
+            }),
+        )
+    }
+
+    /// Connect to the folder-export-requested signal (export the folder as
+    /// a Maildir)
+    pub fn connect_folder_export_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "folder-export-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar,
+                                       account_id: &str,
+                                       folder_path: &str| {
+                f(sidebar, account_id, folder_path);
+            }),
+        )
+    }
+
+    /// Connect to the folder-offline-requested signal (download the
+    /// folder's mail for reading offline)
+    pub fn connect_folder_offline_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "folder-offline-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar,
+                                       account_id: &str,
+                                       folder_path: &str| {
+                f(sidebar, account_id, folder_path);
+            }),
+        )
+    }
+
+    /// Connect to the folder-sync-settings-requested signal (edit the
+    /// folder's sync policy)
+    pub fn connect_folder_sync_settings_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "folder-sync-settings-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar,
+                                       account_id: &str,
+                                       folder_path: &str| {
+                f(sidebar, account_id, folder_path);
+            }),
+        )
+    }
+
     pub fn connect_empty_trash_requested<F>(&self, f: F) -> glib::SignalHandlerId
     where
         F: Fn(&Self, &str, &str) + 'static,
@@ -275,6 +439,239 @@ impl FolderSidebar {
         )
     }
 
+    /// Connect to the folder-watch-toggled signal (opt a folder in or out of IDLE)
+    pub fn connect_folder_watch_toggled<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str, bool) + 'static,
+    {
+        self.connect_closure(
+            "folder-watch-toggled",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar,
+                                       account_id: &str,
+                                       folder_path: &str,
+                                       watched: bool| {
+                f(sidebar, account_id, folder_path, watched);
+            }),
+        )
+    }
+
+    /// Connect to the account-pause-toggled signal (pause or resume background sync)
+    pub fn connect_account_pause_toggled<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, bool) + 'static,
+    {
+        self.connect_closure(
+            "account-pause-toggled",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar, account_id: &str, paused: bool| {
+                f(sidebar, account_id, paused);
+            }),
+        )
+    }
+
+    /// Connect to the account-ca-requested signal (choose a CA bundle to trust)
+    pub fn connect_account_ca_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str) + 'static,
+    {
+        self.connect_closure(
+            "account-ca-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar, account_id: &str| {
+                f(sidebar, account_id);
+            }),
+        )
+    }
+
+    /// Connect to the account-proxy-requested signal (set a proxy for the account)
+    pub fn connect_account_proxy_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str) + 'static,
+    {
+        self.connect_closure(
+            "account-proxy-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar, account_id: &str| {
+                f(sidebar, account_id);
+            }),
+        )
+    }
+
+    /// Connect to the account-identities-requested signal (manage the addresses the account sends from)
+    pub fn connect_account_identities_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str) + 'static,
+    {
+        self.connect_closure(
+            "account-identities-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar, account_id: &str| {
+                f(sidebar, account_id);
+            }),
+        )
+    }
+
+    /// Connect to the folder-peek-requested signal (folder hovered; answer
+    /// with [`Self::show_folder_peek`])
+    pub fn connect_folder_peek_requested<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &str, &str) + 'static,
+    {
+        self.connect_closure(
+            "folder-peek-requested",
+            false,
+            glib::closure_local!(move |sidebar: &FolderSidebar, account_id: &str, folder_path: &str| {
+                f(sidebar, account_id, folder_path);
+            }),
+        )
+    }
+
+    /// Show a peek at a folder's newest messages as (sender, subject) pairs,
+    /// if the pointer is still on that folder
+    pub fn show_folder_peek(
+        &self,
+        account_id: &str,
+        folder_path: &str,
+        message_count: u32,
+        unread_count: u32,
+        recent: &[(String, String)],
+    ) {
+        let row = match self.imp().peek_target.borrow().as_ref() {
+            Some((aid, fp, row)) if aid == account_id && fp == folder_path => row.upgrade(),
+            _ => None,
+        };
+        let Some(row) = row else {
+            return;
+        };
+        if let Some(old) = self.imp().peek_popover.take() {
+            old.popdown();
+        }
+
+        let vbox = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(8)
+            .margin_top(6)
+            .margin_bottom(6)
+            .margin_start(6)
+            .margin_end(6)
+            .width_request(260)
+            .build();
+
+        let mut summary = ntr("{count} message", "{count} messages", message_count)
+            .replace("{count}", &format_number(message_count));
+        if unread_count > 0 {
+            summary = format!(
+                "{} · {}",
+                summary,
+                tr("{count} unread").replace("{count}", &format_number(unread_count))
+            );
+        }
+        vbox.append(
+            &gtk4::Label::builder()
+                .label(&summary)
+                .xalign(0.0)
+                .css_classes(["dim-label", "caption"])
+                .build(),
+        );
+
+        if recent.is_empty() {
+            vbox.append(&gtk4::Label::builder().label(&tr("No messages")).xalign(0.0).build());
+        }
+        for (sender, subject) in recent {
+            let entry = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+            entry.append(
+                &gtk4::Label::builder()
+                    .label(sender)
+                    .xalign(0.0)
+                    .max_width_chars(36)
+                    .ellipsize(gtk4::pango::EllipsizeMode::End)
+                    .css_classes(["caption-heading"])
+                    .build(),
+            );
+            let subject = if subject.is_empty() { tr("(No Subject)") } else { subject.clone() };
+            entry.append(
+                &gtk4::Label::builder()
+                    .label(&subject)
+                    .xalign(0.0)
+                    .max_width_chars(36)
+                    .ellipsize(gtk4::pango::EllipsizeMode::End)
+                    .build(),
+            );
+            vbox.append(&entry);
+        }
+
+        // Not autohide: the peek must not take focus or block clicks, and
+        // it closes when the pointer leaves the row
+        let popover = gtk4::Popover::builder()
+            .child(&vbox)
+            .autohide(false)
+            .position(gtk4::PositionType::Right)
+            .build();
+        popover.set_parent(&row);
+        popover.connect_closed(|popover| popover.unparent());
+        popover.popup();
+        self.imp().peek_popover.replace(Some(popover));
+    }
+
+    /// Ask for a peek at a hovered folder
+    fn request_folder_peek(&self, row: &gtk4::ListBoxRow, account_id: &str, folder_path: &str) {
+        self.imp()
+            .peek_target
+            .replace(Some((account_id.to_string(), folder_path.to_string(), row.downgrade())));
+        self.emit_by_name::<()>("folder-peek-requested", &[&account_id, &folder_path]);
+    }
+
+    /// Close the peek, and drop any peek still being fetched
+    fn dismiss_folder_peek(&self) {
+        self.imp().peek_target.replace(None);
+        if let Some(popover) = self.imp().peek_popover.take() {
+            popover.popdown();
+        }
+    }
+
+    /// Set which folders of an account are watched for new mail
+    pub fn set_watched_folders(&self, account_id: &str, folder_paths: &[String]) {
+        let prefix = format!("{}\0", account_id);
+        let mut watched = self.imp().watched_folders.borrow_mut();
+        watched.retain(|key| !key.starts_with(&prefix));
+        watched.extend(folder_paths.iter().map(|path| format!("{}{}", prefix, path)));
+    }
+
+    /// Show or hide the "Reconnecting…" indicator on an account's header
+    pub fn set_account_reconnecting(&self, account_id: &str, reconnecting: bool) {
+        let imp = self.imp();
+        let changed = if reconnecting {
+            imp.reconnecting_accounts.borrow_mut().insert(account_id.to_string())
+        } else {
+            imp.reconnecting_accounts.borrow_mut().remove(account_id)
+        };
+        if !changed {
+            return;
+        }
+
+        let Some(folders_list) = imp.folders_list_box.borrow().clone() else {
+            return;
+        };
+        let mut idx = 0;
+        while let Some(row) = folders_list.row_at_index(idx) {
+            idx += 1;
+            let name = row.widget_name();
+            let (_section, kind, aid, _path) = decode_row_name(&name);
+            if kind != "header" || aid != account_id {
+                continue;
+            }
+            let mut child = row.child().and_then(|c| c.first_child());
+            while let Some(widget) = child {
+                if widget.widget_name() == "reconnecting-indicator" {
+                    widget.set_visible(reconnecting);
+                }
+                child = widget.next_sibling();
+            }
+            break;
+        }
+    }
+
     /// Parse drop data (single or multi) and emit message-dropped for each message.
     /// Returns true if at least one message was processed.
     fn handle_drop_data(&self, data: &str, target_account_id: &str, target_folder_path: &str) -> bool {
@@ -579,7 +976,7 @@ impl FolderSidebar {
         let folders_list_cell_for_starred = folders_list_cell.clone();
         starred_list_box.connect_row_activated(move |list_box, row| {
             let name = row.widget_name();
-            let (_section, kind, account_id, _folder_path) = decode_row_name(&name);
+            let (_section, kind, account_id, folder_path) = decode_row_name(&name);
 
             match kind {
                 "starred-header" => {
@@ -608,6 +1005,46 @@ impl FolderSidebar {
                         &[&account_id, &"__STARRED__", &false],
                     );
                 }
+                "snoozed" => {
+                    // Deselect other lists
+                    inboxes_list_for_starred.unselect_all();
+                    inboxes_container_for_starred.borrow().add_css_class("inactive");
+                    if let Some(ref folders_list) = *folders_list_cell_for_starred.borrow() {
+                        folders_list.unselect_all();
+                    }
+
+                    sidebar_starred.emit_by_name::<()>(
+                        "folder-selected",
+                        &[&"", &"__SNOOZED__", &false],
+                    );
+                }
+                "reply-later" => {
+                    // Deselect other lists
+                    inboxes_list_for_starred.unselect_all();
+                    inboxes_container_for_starred.borrow().add_css_class("inactive");
+                    if let Some(ref folders_list) = *folders_list_cell_for_starred.borrow() {
+                        folders_list.unselect_all();
+                    }
+
+                    sidebar_starred.emit_by_name::<()>(
+                        "folder-selected",
+                        &[&"", &"__REPLY_LATER__", &false],
+                    );
+                }
+                "tag" => {
+                    // Deselect other lists
+                    inboxes_list_for_starred.unselect_all();
+                    inboxes_container_for_starred.borrow().add_css_class("inactive");
+                    if let Some(ref folders_list) = *folders_list_cell_for_starred.borrow() {
+                        folders_list.unselect_all();
+                    }
+
+                    let path = format!("__TAG__:{}", folder_path);
+                    sidebar_starred.emit_by_name::<()>(
+                        "folder-selected",
+                        &[&"", &path, &false],
+                    );
+                }
                 _ => {
                     list_box.unselect_row(row);
                 }
@@ -658,6 +1095,8 @@ impl FolderSidebar {
                     sidebar2.toggle_account_expansion(account_id);
                 }
                 "folder" => {
+                    sidebar2.dismiss_folder_peek();
+
                     // Deselect inboxes and starred lists
                     inboxes_list_for_folders.unselect_all();
                     if let Some(ref starred_list) = *starred_list_for_folders.borrow() {
@@ -812,7 +1251,11 @@ impl FolderSidebar {
 
         // ── Section 0: Unified Inbox (in inboxes list) ──
         // No drop target for unified inbox (can't drop to all accounts at once)
-        let total_unread: u32 = accounts.iter().filter_map(|a| a.inbox_unread).sum();
+        let total_unread: u32 = accounts
+            .iter()
+            .filter(|a| a.unified_inbox)
+            .filter_map(|a| a.inbox_unread)
+            .sum();
         let row = self.create_inbox_row("mail-inbox-symbolic", &tr("All Inboxes"), Some(total_unread), None);
         row.set_widget_name(&encode_row_name(0, "unified", "", ""));
         inboxes_list.append(&row);
@@ -844,6 +1287,16 @@ impl FolderSidebar {
                 row.set_visible(starred_expanded);
                 starred_list.append(&row);
             }
+
+            let row = self.create_view_row("alarm-symbolic", &tr("Snoozed"));
+            row.set_widget_name(&encode_row_name(STARRED_SECTION, "snoozed", "", ""));
+            starred_list.append(&row);
+
+            let row = self.create_view_row("mail-reply-sender-symbolic", &tr("Reply Later"));
+            row.set_widget_name(&encode_row_name(STARRED_SECTION, "reply-later", "", ""));
+            starred_list.append(&row);
+
+            self.append_tag_rows(&starred_list);
         }
 
         // Load persisted folder expansion states
@@ -860,7 +1313,7 @@ impl FolderSidebar {
             let folder_paths: Vec<&str> = account.folders.iter().map(|f| f.full_path.as_str()).collect();
 
             // Section header row (not selectable, just toggles expansion)
-            let header = self.create_section_header_row(&account.email, expanded, &account.id);
+            let header = self.create_section_header_row(&account.email, expanded, &account.id, account.paused);
             header.set_widget_name(&encode_row_name(section, "header", &account.id, ""));
             folders_list.append(&header);
 
@@ -889,6 +1342,7 @@ impl FolderSidebar {
                     &folder.folder_type,
                     has_children,
                     folder_expanded,
+                    folder.is_selectable,
                 );
                 row.set_widget_name(&encode_row_name(
                     section,
@@ -963,6 +1417,54 @@ impl FolderSidebar {
         }
     }
 
+    /// Replace the tags listed below Snoozed, keeping the selected tag selected
+    pub fn set_tags(&self, tags: Vec<TagInfo>) {
+        let imp = self.imp();
+        imp.tags.replace(tags);
+
+        let starred_list = match imp.starred_list_box.borrow().as_ref() {
+            Some(lb) => lb.clone(),
+            None => return,
+        };
+        // Tag rows only follow the rest of the section once accounts are set
+        if imp.accounts.borrow().is_empty() {
+            return;
+        }
+
+        let selected_name = starred_list.selected_row().map(|row| row.widget_name().to_string());
+        let mut idx = 0;
+        while let Some(row) = starred_list.row_at_index(idx) {
+            let name = row.widget_name();
+            let (_section, kind, _aid, _path) = decode_row_name(&name);
+            if kind == "tag" {
+                starred_list.remove(&row);
+            } else {
+                idx += 1;
+            }
+        }
+
+        self.append_tag_rows(&starred_list);
+
+        if let Some(name) = selected_name {
+            let mut idx = 0;
+            while let Some(row) = starred_list.row_at_index(idx) {
+                if row.widget_name() == name.as_str() {
+                    starred_list.select_row(Some(&row));
+                    break;
+                }
+                idx += 1;
+            }
+        }
+    }
+
+    fn append_tag_rows(&self, starred_list: &gtk4::ListBox) {
+        for tag in self.imp().tags.borrow().iter() {
+            let row = self.create_tag_row(tag);
+            row.set_widget_name(&encode_row_name(STARRED_SECTION, "tag", "", &tag.id.to_string()));
+            starred_list.append(&row);
+        }
+    }
+
     // ── Row factories ────────────────────────────────────────────────
 
     /// Create a row for the inboxes section (white text on accent background)
@@ -1063,10 +1565,12 @@ impl FolderSidebar {
         folder_type: &str,
         has_children: bool,
         folder_expanded: bool,
+        is_selectable: bool,
     ) -> gtk4::ListBoxRow {
+        // \Noselect containers only group their children and can't be opened
         let row = gtk4::ListBoxRow::builder()
-            .selectable(true)
-            .activatable(true)
+            .selectable(is_selectable)
+            .activatable(is_selectable)
             .css_classes(["folder-entry-row"])
             .build();
 
@@ -1124,6 +1628,10 @@ impl FolderSidebar {
                 .build(),
         );
 
+        if !is_selectable {
+            content.add_css_class("dim-label");
+        }
+
         if let Some(count) = unread_count {
             if count > 0 {
                 content.append(
@@ -1137,40 +1645,78 @@ impl FolderSidebar {
 
         row.set_child(Some(&content));
 
-        // Add drop target for drag-and-drop message moving
-        let drop_target = gtk4::DropTarget::builder()
-            .actions(gtk4::gdk::DragAction::MOVE)
-            .build();
-        drop_target.set_types(&[glib::Type::STRING]);
+        // Add drop target for drag-and-drop message moving (containers
+        // can't hold messages)
+        if is_selectable {
+            let drop_target = gtk4::DropTarget::builder()
+                .actions(gtk4::gdk::DragAction::MOVE)
+                .build();
+            drop_target.set_types(&[glib::Type::STRING]);
 
-        let sidebar = self.clone();
-        let target_account_id = account_id.to_string();
-        let target_folder_path = folder_path.to_string();
-        let row_weak = row.downgrade();
+            let sidebar = self.clone();
+            let target_account_id = account_id.to_string();
+            let target_folder_path = folder_path.to_string();
+            let row_weak = row.downgrade();
 
-        drop_target.connect_drop(move |_target, value, _x, _y| {
-            if let Ok(data) = value.get::<String>() {
-                return sidebar.handle_drop_data(&data, &target_account_id, &target_folder_path);
-            }
-            false
-        });
+            drop_target.connect_drop(move |_target, value, _x, _y| {
+                if let Ok(data) = value.get::<String>() {
+                    return sidebar.handle_drop_data(&data, &target_account_id, &target_folder_path);
+                }
+                false
+            });
 
-        // Visual feedback when dragging over
-        drop_target.connect_enter(move |_target, _x, _y| {
-            if let Some(row) = row_weak.upgrade() {
-                row.add_css_class("drop-highlight");
-            }
-            gtk4::gdk::DragAction::MOVE
-        });
+            // Visual feedback when dragging over
+            drop_target.connect_enter(move |_target, _x, _y| {
+                if let Some(row) = row_weak.upgrade() {
+                    row.add_css_class("drop-highlight");
+                }
+                gtk4::gdk::DragAction::MOVE
+            });
 
-        let row_weak2 = row.downgrade();
-        drop_target.connect_leave(move |_target| {
-            if let Some(row) = row_weak2.upgrade() {
-                row.remove_css_class("drop-highlight");
-            }
-        });
+            let row_weak2 = row.downgrade();
+            drop_target.connect_leave(move |_target| {
+                if let Some(row) = row_weak2.upgrade() {
+                    row.remove_css_class("drop-highlight");
+                }
+            });
+
+            row.add_controller(drop_target);
 
-        row.add_controller(drop_target);
+            // Resting the pointer on a folder peeks at its newest messages
+            // without opening it
+            let motion = gtk4::EventControllerMotion::new();
+            let hover_timer: Rc<RefCell<Option<glib::SourceId>>> = Rc::default();
+            let sidebar = self.clone();
+            let peek_account_id = account_id.to_string();
+            let peek_folder_path = folder_path.to_string();
+            let row_weak = row.downgrade();
+            let timer = hover_timer.clone();
+            motion.connect_enter(move |_, _, _| {
+                let sidebar = sidebar.clone();
+                let account_id = peek_account_id.clone();
+                let folder_path = peek_folder_path.clone();
+                let row_weak = row_weak.clone();
+                let fired = timer.clone();
+                let source = glib::timeout_add_local_once(PEEK_HOVER_DELAY, move || {
+                    fired.borrow_mut().take();
+                    // The open folder is already on screen
+                    if let Some(row) = row_weak.upgrade().filter(|row| !row.is_selected()) {
+                        sidebar.request_folder_peek(&row, &account_id, &folder_path);
+                    }
+                });
+                if let Some(old) = timer.borrow_mut().replace(source) {
+                    old.remove();
+                }
+            });
+            let sidebar = self.clone();
+            motion.connect_leave(move |_| {
+                if let Some(source) = hover_timer.borrow_mut().take() {
+                    source.remove();
+                }
+                sidebar.dismiss_folder_peek();
+            });
+            row.add_controller(motion);
+        }
 
         // Right-click context menu
         let gesture = gtk4::GestureClick::new();
@@ -1188,6 +1734,10 @@ impl FolderSidebar {
                     ctx_folder_type.as_str(),
                     "inbox" | "sent" | "drafts" | "trash" | "spam" | "archive"
                 );
+                // INBOX is always watched; containers have nothing to watch
+                let can_watch = is_selectable && ctx_folder_type != "inbox";
+                // Local folders are only in the cache
+                let can_sync = is_selectable && ctx_folder_type != northmail_core::import::LOCAL_FOLDER_TYPE;
                 sidebar.show_folder_context_menu(
                     &row,
                     x as i32,
@@ -1197,6 +1747,8 @@ impl FolderSidebar {
                     &ctx_folder_name,
                     is_system,
                     &ctx_folder_type,
+                    can_watch,
+                    can_sync,
                 );
             }
         });
@@ -1205,7 +1757,7 @@ impl FolderSidebar {
         row
     }
 
-    fn create_section_header_row(&self, email: &str, expanded: bool, account_id: &str) -> gtk4::ListBoxRow {
+    fn create_section_header_row(&self, email: &str, expanded: bool, account_id: &str, paused: bool) -> gtk4::ListBoxRow {
         let row = gtk4::ListBoxRow::builder()
             .selectable(false)
             .activatable(true)
@@ -1243,9 +1795,30 @@ impl FolderSidebar {
                 .build(),
         );
 
+        // Shown while the account's push connection is reconnecting
+        let reconnecting = gtk4::Image::builder()
+            .icon_name("network-offline-symbolic")
+            .pixel_size(12)
+            .tooltip_text(tr("Reconnecting…"))
+            .css_classes(["dim-label"])
+            .visible(self.imp().reconnecting_accounts.borrow().contains(account_id))
+            .build();
+        reconnecting.set_widget_name("reconnecting-indicator");
+        content.append(&reconnecting);
+
+        if paused {
+            let pause_icon = gtk4::Image::builder()
+                .icon_name("media-playback-pause-symbolic")
+                .pixel_size(12)
+                .tooltip_text(tr("Sync paused"))
+                .css_classes(["dim-label"])
+                .build();
+            content.append(&pause_icon);
+        }
+
         row.set_child(Some(&content));
 
-        // Right-click on section header: "New Folder" at root level
+        // Right-click on section header: "New Folder" at root level, pause/resume
         let gesture = gtk4::GestureClick::new();
         gesture.set_button(3);
         let sidebar = self.clone();
@@ -1254,7 +1827,7 @@ impl FolderSidebar {
         gesture.connect_pressed(move |gesture, _n, x, y| {
             gesture.set_state(gtk4::EventSequenceState::Claimed);
             if let Some(row) = row_weak.upgrade() {
-                sidebar.show_header_context_menu(&row, x as i32, y as i32, &hdr_account_id);
+                sidebar.show_header_context_menu(&row, x as i32, y as i32, &hdr_account_id, paused);
             }
         });
         row.add_controller(gesture);
@@ -1351,6 +1924,87 @@ impl FolderSidebar {
         row
     }
 
+    /// Create a row for a list such as "Snoozed", lined up with the
+    /// "Starred" row's icon
+    fn create_view_row(&self, icon_name: &str, label: &str) -> gtk4::ListBoxRow {
+        let row = gtk4::ListBoxRow::builder()
+            .selectable(true)
+            .activatable(true)
+            .css_classes(["folder-entry-row"])
+            .build();
+
+        let content = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(4)
+            .margin_start(28)
+            .margin_end(12)
+            .margin_top(4)
+            .margin_bottom(4)
+            .css_classes(["folder-entry"])
+            .build();
+
+        content.append(&gtk4::Image::from_icon_name(icon_name));
+        content.append(
+            &gtk4::Label::builder()
+                .label(label)
+                .xalign(0.0)
+                .hexpand(true)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .build(),
+        );
+
+        row.set_child(Some(&content));
+        row
+    }
+
+    /// Create a tag row: a dot in the tag's colour, lined up with the
+    /// "Snoozed" row's icon
+    fn create_tag_row(&self, tag: &TagInfo) -> gtk4::ListBoxRow {
+        let row = gtk4::ListBoxRow::builder()
+            .selectable(true)
+            .activatable(true)
+            .css_classes(["folder-entry-row"])
+            .tooltip_text(tag.name.as_str())
+            .build();
+
+        let content = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(4)
+            .margin_start(28)
+            .margin_end(12)
+            .margin_top(4)
+            .margin_bottom(4)
+            .css_classes(["folder-entry"])
+            .build();
+
+        let dot = gtk4::Label::new(None);
+        dot.set_markup(&format!(
+            "<span foreground=\"{}\">●</span>",
+            glib::markup_escape_text(&tag.color)
+        ));
+        dot.set_width_chars(2);
+        content.append(&dot);
+        content.append(
+            &gtk4::Label::builder()
+                .label(northmail_core::tags::display_name(&tag.name))
+                .xalign(0.0)
+                .hexpand(true)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .build(),
+        );
+        if tag.message_count > 0 {
+            content.append(
+                &gtk4::Label::builder()
+                    .label(format_number(tag.message_count as u32))
+                    .css_classes(["dim-label"])
+                    .build(),
+            );
+        }
+
+        row.set_child(Some(&content));
+        row
+    }
+
     // ── Context menus ────────────────────────────────────────────────
 
     /// Create a context menu button, left-aligned, normal weight.
@@ -1387,6 +2041,8 @@ impl FolderSidebar {
         folder_name: &str,
         is_system: bool,
         folder_type: &str,
+        can_watch: bool,
+        can_sync: bool,
     ) {
         let popover = gtk4::Popover::new();
         popover.set_parent(row);
@@ -1442,6 +2098,95 @@ impl FolderSidebar {
             });
         }
 
+        // "Import Messages" — upload an mbox file into this folder
+        {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Import Messages…"), Some("document-open-symbolic"));
+            let sidebar = self.clone();
+            let aid = account_id.to_string();
+            let fp = folder_path.to_string();
+            let pop = popover.clone();
+            btn.connect_clicked(move |_| {
+                pop.popdown();
+                sidebar.emit_by_name::<()>("folder-import-requested", &[&aid, &fp]);
+            });
+        }
+
+        // "Export as Maildir" — back the folder up for mutt, notmuch and co.
+        {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Export as Maildir…"), Some("document-save-symbolic"));
+            let sidebar = self.clone();
+            let aid = account_id.to_string();
+            let fp = folder_path.to_string();
+            let pop = popover.clone();
+            btn.connect_clicked(move |_| {
+                pop.popdown();
+                sidebar.emit_by_name::<()>("folder-export-requested", &[&aid, &fp]);
+            });
+        }
+
+        // "Download for Offline" — everything in a date range, before a flight
+        {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Download for Offline…"), Some("folder-download-symbolic"));
+            let sidebar = self.clone();
+            let aid = account_id.to_string();
+            let fp = folder_path.to_string();
+            let pop = popover.clone();
+            btn.connect_clicked(move |_| {
+                pop.popdown();
+                sidebar.emit_by_name::<()>("folder-offline-requested", &[&aid, &fp]);
+            });
+        }
+
+        // "Find Duplicates" — messages cached more than once, e.g. after an import
+        {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Find Duplicates…"), Some("edit-copy-symbolic"));
+            let sidebar = self.clone();
+            let aid = account_id.to_string();
+            let fp = folder_path.to_string();
+            let pop = popover.clone();
+            btn.connect_clicked(move |_| {
+                pop.popdown();
+                sidebar.emit_by_name::<()>("folder-duplicates-requested", &[&aid, &fp]);
+            });
+        }
+
+        // "Watch for New Mail" — keep an IDLE connection on this folder
+        if can_watch {
+            let key = format!("{}\0{}", account_id, folder_path);
+            let watched = self.imp().watched_folders.borrow().contains(&key);
+            let label = if watched { tr("Stop Watching for New Mail") } else { tr("Watch for New Mail") };
+            let btn = Self::make_context_menu_item(&vbox, &label, Some("alarm-symbolic"));
+            let sidebar = self.clone();
+            let aid = account_id.to_string();
+            let fp = folder_path.to_string();
+            let pop = popover.clone();
+            btn.connect_clicked(move |_| {
+                pop.popdown();
+                {
+                    let mut set = sidebar.imp().watched_folders.borrow_mut();
+                    if watched {
+                        set.remove(&key);
+                    } else {
+                        set.insert(key.clone());
+                    }
+                }
+                sidebar.emit_by_name::<()>("folder-watch-toggled", &[&aid, &fp, &!watched]);
+            });
+        }
+
+        // "Sync Settings" — how often, how far back and how much to sync
+        if can_sync {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Sync Settings…"), Some("emblem-synchronizing-symbolic"));
+            let sidebar = self.clone();
+            let aid = account_id.to_string();
+            let fp = folder_path.to_string();
+            let pop = popover.clone();
+            btn.connect_clicked(move |_| {
+                pop.popdown();
+                sidebar.emit_by_name::<()>("folder-sync-settings-requested", &[&aid, &fp]);
+            });
+        }
+
         // "Empty Trash" — only for trash folder
         if folder_type == "trash" {
             let btn = Self::make_context_menu_item(&vbox, &tr("Empty Trash"), Some("user-trash-symbolic"));
@@ -1459,13 +2204,14 @@ impl FolderSidebar {
         popover.popup();
     }
 
-    /// Show context menu for a section header (only "New Folder")
+    /// Show context menu for a section header ("New Folder", pause/resume sync, CA certificate)
     fn show_header_context_menu(
         &self,
         row: &gtk4::ListBoxRow,
         x: i32,
         y: i32,
         account_id: &str,
+        paused: bool,
     ) {
         let popover = gtk4::Popover::new();
         popover.set_parent(row);
@@ -1487,6 +2233,51 @@ impl FolderSidebar {
             sidebar.show_new_folder_dialog(&aid, "");
         });
 
+        // "Pause Sync" / "Resume Sync" — stops timers, IDLE and prefetch for the account
+        let (label, icon) = if paused {
+            (tr("Resume Sync"), "media-playback-start-symbolic")
+        } else {
+            (tr("Pause Sync"), "media-playback-pause-symbolic")
+        };
+        let btn = Self::make_context_menu_item(&vbox, &label, Some(icon));
+        let sidebar = self.clone();
+        let aid = account_id.to_string();
+        let pop = popover.clone();
+        btn.connect_clicked(move |_| {
+            pop.popdown();
+            sidebar.emit_by_name::<()>("account-pause-toggled", &[&aid, &!paused]);
+        });
+
+        // "Trusted CA Certificate…" — for self-hosted servers with a private CA
+        let btn = Self::make_context_menu_item(&vbox, &tr("Trusted CA Certificate…"), Some("channel-secure-symbolic"));
+        let sidebar = self.clone();
+        let aid = account_id.to_string();
+        let pop = popover.clone();
+        btn.connect_clicked(move |_| {
+            pop.popdown();
+            sidebar.emit_by_name::<()>("account-ca-requested", &[&aid]);
+        });
+
+        // "Proxy…" — route this account differently from the global proxy setting
+        let btn = Self::make_context_menu_item(&vbox, &tr("Proxy…"), Some("network-server-symbolic"));
+        let sidebar = self.clone();
+        let aid = account_id.to_string();
+        let pop = popover.clone();
+        btn.connect_clicked(move |_| {
+            pop.popdown();
+            sidebar.emit_by_name::<()>("account-proxy-requested", &[&aid]);
+        });
+
+        // "Identities…" — other addresses to send from, with names and signatures
+        let btn = Self::make_context_menu_item(&vbox, &tr("Identities…"), Some("contact-new-symbolic"));
+        let sidebar = self.clone();
+        let aid = account_id.to_string();
+        let pop = popover.clone();
+        btn.connect_clicked(move |_| {
+            pop.popdown();
+            sidebar.emit_by_name::<()>("account-identities-requested", &[&aid]);
+        });
+
         popover.set_child(Some(&vbox));
         popover.popup();
     }
@@ -1934,9 +2725,7 @@ impl FolderSidebar {
     // ── Expansion state persistence ──────────────────────────────────
 
     fn get_state_file_path() -> std::path::PathBuf {
-        let data_dir = glib::user_data_dir().join("northmail");
-        std::fs::create_dir_all(&data_dir).ok();
-        data_dir.join("sidebar_state.json")
+        crate::profile::state_file("sidebar_state.json", &crate::profile::data_dir())
     }
 
     fn load_expander_states(&self) -> HashMap<String, bool> {
@@ -1959,9 +2748,7 @@ impl FolderSidebar {
     }
 
     fn get_folder_state_file_path() -> std::path::PathBuf {
-        let data_dir = glib::user_data_dir().join("northmail");
-        std::fs::create_dir_all(&data_dir).ok();
-        data_dir.join("folder_expand_state.json")
+        crate::profile::state_file("folder_expand_state.json", &crate::profile::data_dir())
     }
 
     fn load_folder_expander_states(&self) -> HashMap<String, bool> {
@@ -2073,7 +2860,22 @@ pub struct AccountFolders {
     pub id: String,
     pub email: String,
     pub inbox_unread: Option<u32>,
+    /// The account's inbox shows in the unified inbox
+    pub unified_inbox: bool,
     pub folders: Vec<FolderInfo>,
+    /// Background sync is paused for this account
+    pub paused: bool,
+}
+
+/// A tag listed in the sidebar
+#[derive(Clone)]
+pub struct TagInfo {
+    pub id: i64,
+    /// Keyword or Gmail label
+    pub name: String,
+    /// `#rrggbb`
+    pub color: String,
+    pub message_count: i64,
 }
 
 /// Information about a folder for display
@@ -2088,4 +2890,6 @@ pub struct FolderInfo {
     pub folder_type: String,
     /// Nesting depth (0 = top-level, 1 = child of top-level, etc.)
     pub depth: u32,
+    /// False for `\Noselect` containers, which are shown but can't be opened
+    pub is_selectable: bool,
 }
@@ -3,11 +3,14 @@
 use gtk4::{glib, prelude::*, subclass::prelude::*};
 use libadwaita as adw;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::application::NorthMailApplication;
 use crate::i18n::{tr, ntr};
-use crate::window::base_domain;
+
+/// Most Gmail labels shown on one row, to keep the subject readable
+const MAX_LABEL_CHIPS: usize = 3;
 
 /// Escape XML/Pango markup special characters
 fn escape_markup(text: &str) -> String {
@@ -123,6 +126,9 @@ mod imp {
         pub is_loading_more: Cell<bool>,
         pub on_load_more: RefCell<Option<Box<dyn Fn()>>>,
         pub on_filter_changed: RefCell<Option<Box<dyn Fn()>>>,
+        /// Gives the `.eml` file a message dragged out of the list is
+        /// written to, from (uid, folder_id, subject)
+        pub on_drag_export: RefCell<Option<Box<dyn Fn(u32, i64, &str) -> Option<std::path::PathBuf>>>>,
         pub message_count: Cell<usize>,
         pub total_count: Cell<u32>,
         /// Store message info for each row
@@ -151,6 +157,12 @@ mod imp {
         pub is_search_results: Cell<bool>,
         /// Guard flag to suppress message-selected emission during list rebuilds
         pub is_rebuilding: Cell<bool>,
+        /// Lines of preview text under each row's subject, 0 for none
+        pub preview_lines: Cell<u32>,
+        /// Tag colours by lowercased tag name
+        pub tag_colors: RefCell<HashMap<String, String>>,
+        /// Account colours by inbox folder id, for the unified inbox
+        pub account_colors: RefCell<HashMap<i64, &'static str>>,
     }
 
     #[glib::object_subclass]
@@ -178,6 +190,21 @@ mod imp {
                     Signal::builder("mark-read")
                         .param_types([u32::static_type(), i64::static_type(), i64::static_type(), bool::static_type()])
                         .build(),
+                    Signal::builder("edit-tags")
+                        .param_types([u32::static_type(), i64::static_type(), i64::static_type()])
+                        .build(),
+                    // (uid, msg_id, folder_id): save as an .eml file
+                    Signal::builder("save-as")
+                        .param_types([u32::static_type(), i64::static_type(), i64::static_type()])
+                        .build(),
+                    // (uid, msg_id, folder_id, snooze): snooze or bring back now
+                    Signal::builder("snooze")
+                        .param_types([u32::static_type(), i64::static_type(), i64::static_type(), bool::static_type()])
+                        .build(),
+                    // (uid, msg_id, folder_id, mark): mark to reply to later or unmark
+                    Signal::builder("reply-later")
+                        .param_types([u32::static_type(), i64::static_type(), i64::static_type(), bool::static_type()])
+                        .build(),
                     Signal::builder("archive")
                         .param_types([u32::static_type(), i64::static_type(), i64::static_type()])
                         .build(),
@@ -333,6 +360,21 @@ impl MessageList {
                 background-color: @accent_color;
                 border-radius: 4px;
             }
+            .label-chip {
+                background-color: alpha(@window_fg_color, 0.1);
+                border-radius: 6px;
+                padding: 0 6px;
+            }
+            .message-list > row:selected .label-chip {
+                background-color: alpha(@accent_fg_color, 0.2);
+            }
+            .mention-chip {
+                background-color: alpha(@warning_color, 0.2);
+                font-weight: bold;
+            }
+            .tag-chip {
+                background-color: alpha(@accent_bg_color, 0.15);
+            }
             .message-list > row:selected .unread-dot {
                 background-color: @accent_fg_color;
             }
@@ -391,6 +433,26 @@ impl MessageList {
             gtk4::STYLE_PROVIDER_PRIORITY_USER + 1,
         );
 
+        // One class per tag colour for the chips
+        let tag_css: String = northmail_core::tags::TAG_COLORS
+            .iter()
+            .enumerate()
+            .map(|(i, color)| format!(".tag-chip.tag-color-{} {{ background-color: alpha({}, 0.25); }}\n", i, color))
+            .collect();
+        // and the account stripes in the unified inbox
+        let account_css: String = northmail_core::tags::TAG_COLORS
+            .iter()
+            .enumerate()
+            .map(|(i, color)| format!(".account-stripe.account-color-{} {{ background-color: {}; border-radius: 2px; }}\n", i, color))
+            .collect();
+        let tag_provider = gtk4::CssProvider::new();
+        tag_provider.load_from_string(&(tag_css + &account_css));
+        gtk4::style_context_add_provider_for_display(
+            &gtk4::gdk::Display::default().unwrap(),
+            &tag_provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_USER + 1,
+        );
+
         // Placeholder content - initially empty, will be populated when folder is selected
         let placeholder = adw::StatusPage::builder()
             .icon_name("mail-inbox-symbolic")
@@ -728,6 +790,13 @@ impl MessageList {
         self.imp().on_filter_changed.replace(Some(Box::new(callback)));
     }
 
+    /// Connect callback giving the `.eml` file for a message dragged out of
+    /// the list, e.g. to a file manager. It should start writing the file
+    /// and return its path; `None` drags the message within NorthMail only.
+    pub fn connect_drag_export<F: Fn(u32, i64, &str) -> Option<std::path::PathBuf> + 'static>(&self, callback: F) {
+        self.imp().on_drag_export.replace(Some(Box::new(callback)));
+    }
+
     /// Get the current filter state as a MessageFilter for DB queries
     pub fn get_message_filter(&self) -> northmail_core::models::MessageFilter {
         let state = self.imp().filter_state.borrow();
@@ -748,6 +817,62 @@ impl MessageList {
         state.is_active() || !query.is_empty()
     }
 
+    /// Show up to `lines` lines of preview text per row (0 to 3). Applies to
+    /// rows added from now on.
+    pub fn set_preview_lines(&self, lines: u32) {
+        self.imp().preview_lines.set(lines.min(3));
+    }
+
+    /// Colour tag chips by tag, from each tag's name and `#rrggbb` colour.
+    /// Tags not listed get their default colour.
+    pub fn set_tag_colors(&self, colors: impl IntoIterator<Item = (String, String)>) {
+        let colors: HashMap<String, String> =
+            colors.into_iter().map(|(name, color)| (name.to_lowercase(), color)).collect();
+        if *self.imp().tag_colors.borrow() == colors {
+            return;
+        }
+        self.imp().tag_colors.replace(colors);
+        self.rebuild_visible_rows_direct();
+    }
+
+    /// CSS class giving a tag's chip its colour
+    fn tag_color_class(&self, name: &str) -> Option<String> {
+        let colors = self.imp().tag_colors.borrow();
+        let color = colors
+            .get(&name.to_lowercase())
+            .map(String::as_str)
+            .unwrap_or_else(|| northmail_core::tags::default_color(name));
+        northmail_core::tags::TAG_COLORS
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(color))
+            .map(|i| format!("tag-color-{}", i))
+    }
+
+    /// Mark unified inbox rows with their account's colour, from each
+    /// inbox's folder id and `#rrggbb` colour
+    pub fn set_account_colors(&self, colors: impl IntoIterator<Item = (i64, &'static str)>) {
+        let colors: HashMap<i64, &'static str> = colors.into_iter().collect();
+        if *self.imp().account_colors.borrow() == colors {
+            return;
+        }
+        self.imp().account_colors.replace(colors);
+        self.rebuild_visible_rows_direct();
+    }
+
+    /// CSS class giving a row's account stripe its colour; only rows of
+    /// the unified inbox have one
+    fn account_color_class(&self, folder_id: i64) -> Option<String> {
+        if *self.imp().current_folder_path.borrow() != "UNIFIED_INBOX" {
+            return None;
+        }
+        let colors = self.imp().account_colors.borrow();
+        let color = colors.get(&folder_id)?;
+        northmail_core::tags::TAG_COLORS
+            .iter()
+            .position(|c| c == color)
+            .map(|i| format!("account-color-{}", i))
+    }
+
     /// Set the current folder context for drag-and-drop operations
     pub fn set_folder_context(&self, account_id: &str, folder_path: &str) {
         let imp = self.imp();
@@ -764,6 +889,11 @@ impl MessageList {
         )
     }
 
+    /// The search query currently applied
+    pub fn search_query(&self) -> String {
+        self.imp().search_query.borrow().clone()
+    }
+
     /// Clear the search query and search entry text
     pub fn clear_search(&self) {
         let imp = self.imp();
@@ -1085,13 +1215,15 @@ impl MessageList {
         subject.add_css_class("skeleton-box");
         content.append(&subject);
 
-        // Snippet preview
-        let snippet = gtk4::Box::builder()
-            .height_request(12)
-            .hexpand(true)
-            .build();
-        snippet.add_css_class("skeleton-box");
-        content.append(&snippet);
+        // Snippet preview, one bar per line
+        for _ in 0..self.imp().preview_lines.get() {
+            let snippet = gtk4::Box::builder()
+                .height_request(12)
+                .hexpand(true)
+                .build();
+            snippet.add_css_class("skeleton-box");
+            content.append(&snippet);
+        }
 
         row_box.append(&content);
         row.set_child(Some(&row_box));
@@ -1121,11 +1253,12 @@ impl MessageList {
         let scrolled = imp.scrolled.borrow();
         let list_box = imp.list_box.borrow();
 
-        // Deduplicate by UID (keep first occurrence)
-        let mut seen_uids = std::collections::HashSet::new();
+        // Deduplicate by UID within a folder (keep first occurrence); UIDs
+        // of different folders collide in lists spanning them
+        let mut seen = std::collections::HashSet::new();
         let deduped: Vec<MessageInfo> = messages
             .into_iter()
-            .filter(|m| seen_uids.insert(m.uid))
+            .filter(|m| seen.insert((m.uid, m.folder_id)))
             .collect();
 
         // Sort messages by date (newest first) to ensure correct order
@@ -1414,18 +1547,21 @@ impl MessageList {
 
     /// Append messages, skipping any whose UID is already in the list (dedup).
     /// Used during background sync to add new messages without duplicating
-    /// those already loaded from cache or a previous batch.
-    pub fn append_new_messages(&self, messages: Vec<MessageInfo>) {
-        let existing_uids: std::collections::HashSet<u32> = self.imp().messages.borrow()
+    /// those already loaded from cache or a previous batch. Returns how
+    /// many were added.
+    pub fn append_new_messages(&self, messages: Vec<MessageInfo>) -> usize {
+        let existing: std::collections::HashSet<(u32, i64)> = self.imp().messages.borrow()
             .iter()
-            .map(|m| m.uid)
+            .map(|m| (m.uid, m.folder_id))
             .collect();
         let new_msgs: Vec<MessageInfo> = messages.into_iter()
-            .filter(|m| !existing_uids.contains(&m.uid))
+            .filter(|m| !existing.contains(&(m.uid, m.folder_id)))
             .collect();
-        if !new_msgs.is_empty() {
+        let added = new_msgs.len();
+        if added > 0 {
             self.append_messages(new_msgs);
         }
+        added
     }
 
     fn add_message_row(&self, list_box: &gtk4::ListBox, msg: &MessageInfo) {
@@ -1456,6 +1592,16 @@ impl MessageList {
             .css_classes(["message-row-content"])
             .build();
 
+        // Account stripe in the unified inbox
+        if let Some(class) = self.account_color_class(msg.folder_id) {
+            hbox.append(
+                &gtk4::Box::builder()
+                    .width_request(3)
+                    .css_classes(["account-stripe", class.as_str()])
+                    .build(),
+            );
+        }
+
         // Indicator column (unread dot only)
         let indicator_box = gtk4::Box::builder()
             .orientation(gtk4::Orientation::Vertical)
@@ -1478,7 +1624,7 @@ impl MessageList {
 
         hbox.append(&indicator_box);
 
-        // Avatar (32px circle: contact photo → favicon → initials)
+        // Avatar (32px circle: contact photo → sender image → initials)
         let app_ref = self.root()
             .and_then(|r| r.downcast_ref::<gtk4::Window>().cloned())
             .and_then(|w| w.application())
@@ -1487,7 +1633,7 @@ impl MessageList {
         let contact_photo = app_ref.as_ref()
             .and_then(|app| app.get_contact_photo(&msg.from_address));
 
-        let (avatar_widget, favicon_slot) = crate::window::create_avatar(
+        let (avatar_widget, image_slot) = crate::window::create_avatar(
             &msg.from, &msg.from_address, contact_photo.as_deref(),
         );
 
@@ -1497,36 +1643,9 @@ impl MessageList {
             da.set_height_request(32);
         }
 
-        // Kick off favicon fetch if no contact photo
-        if let (Some((drawing_area, slot)), Some(app)) = (favicon_slot, app_ref) {
-            if let Some(domain) = msg.from_address.rsplit('@').next().map(base_domain) {
-                let da = drawing_area.clone();
-                let slot_cb = slot.clone();
-                app.fetch_favicon_async(domain, move |png_bytes| {
-                    if let Some(bytes) = png_bytes {
-                        let gbytes = glib::Bytes::from(&bytes);
-                        if let Ok(tex) = gtk4::gdk::Texture::from_bytes(&gbytes) {
-                            let tw = tex.width();
-                            let th = tex.height();
-                            let stride = gtk4::cairo::Format::ARgb32
-                                .stride_for_width(tw as u32)
-                                .unwrap_or(tw * 4);
-                            let mut pixel_data = vec![0u8; (stride * th) as usize];
-                            tex.download(&mut pixel_data, stride as usize);
-                            if let Ok(surface) = gtk4::cairo::ImageSurface::create_for_data(
-                                pixel_data,
-                                gtk4::cairo::Format::ARgb32,
-                                tw,
-                                th,
-                                stride,
-                            ) {
-                                *slot_cb.borrow_mut() = Some(surface);
-                                da.queue_draw();
-                            }
-                        }
-                    }
-                });
-            }
+        // Look up a sender image if no contact photo
+        if let (Some(image_slot), Some(app)) = (image_slot, app_ref) {
+            crate::window::load_sender_image(&app, &msg.from_address, image_slot);
         }
 
         avatar_widget.set_margin_end(6);
@@ -1594,6 +1713,57 @@ impl MessageList {
         }
         middle_row.append(&subject_label);
 
+        // Mention keyword chip
+        if let Some(keyword) = &msg.mention {
+            let chip = gtk4::Label::builder()
+                .label(keyword.as_str())
+                .tooltip_text(tr("Mentions “{}”").replace("{}", keyword))
+                .valign(gtk4::Align::Center)
+                .max_width_chars(12)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .css_classes(["label-chip", "mention-chip", "caption"])
+                .build();
+            middle_row.append(&chip);
+        }
+
+        // Tag chips for IMAP keywords
+        for keyword in northmail_core::tags::user_tags(&msg.tags).iter().take(MAX_LABEL_CHIPS) {
+            let chip = gtk4::Label::builder()
+                .label(northmail_core::tags::display_name(keyword))
+                .tooltip_text(keyword.as_str())
+                .valign(gtk4::Align::Center)
+                .max_width_chars(12)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .css_classes(["label-chip", "tag-chip", "caption"])
+                .build();
+            if let Some(class) = self.tag_color_class(keyword) {
+                chip.add_css_class(&class);
+            }
+            middle_row.append(&chip);
+        }
+
+        // Gmail label chips, leaving out the folder being viewed
+        let (_, current_folder) = self.folder_context();
+        for label in northmail_core::gmail::chip_labels(&msg.gmail_labels, &current_folder)
+            .iter()
+            .take(MAX_LABEL_CHIPS)
+        {
+            let name = label.rsplit('/').next().unwrap_or(label);
+            let chip = gtk4::Label::builder()
+                .label(name)
+                .tooltip_text(label.as_str())
+                .valign(gtk4::Align::Center)
+                .max_width_chars(12)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .css_classes(["label-chip", "caption"])
+                .build();
+            if let Some(class) = self.tag_color_class(label) {
+                chip.add_css_class("tag-chip");
+                chip.add_css_class(&class);
+            }
+            middle_row.append(&chip);
+        }
+
         // Attachment indicator
         if msg.has_attachments {
             let attachment = gtk4::Image::from_icon_name("mail-attachment-symbolic");
@@ -1626,13 +1796,19 @@ impl MessageList {
 
         content_box.append(&middle_row);
 
-        // Bottom row: Preview snippet (if available)
-        if let Some(snippet) = &msg.snippet {
+        // Bottom rows: Preview snippet (if available and wanted)
+        let preview_lines = self.imp().preview_lines.get();
+        if let Some(snippet) = msg.snippet.as_deref().filter(|_| preview_lines > 0) {
+            // Body text fills in for missing snippets; keep its line breaks out
+            let snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
             if !snippet.is_empty() {
                 let preview_label = gtk4::Label::builder()
-                    .label(&escape_markup(snippet))
+                    .label(&escape_markup(&snippet))
                     .use_markup(true)
                     .xalign(0.0)
+                    .wrap(preview_lines > 1)
+                    .wrap_mode(gtk4::pango::WrapMode::WordChar)
+                    .lines(preview_lines as i32)
                     .ellipsize(gtk4::pango::EllipsizeMode::End)
                     .css_classes(["dim-label", "caption"])
                     .build();
@@ -1643,9 +1819,10 @@ impl MessageList {
         hbox.append(&content_box);
         row.set_child(Some(&hbox));
 
-        // Add drag source for drag-and-drop to folders
+        // Add drag source for drag-and-drop to folders, and of single
+        // messages out to other applications as .eml files
         let drag_source = gtk4::DragSource::builder()
-            .actions(gtk4::gdk::DragAction::MOVE)
+            .actions(gtk4::gdk::DragAction::MOVE | gtk4::gdk::DragAction::COPY)
             .build();
 
         // Store message data for drag (use folder context from MessageList, not msg.folder_id which may be 0)
@@ -1654,6 +1831,8 @@ impl MessageList {
         let drag_msg_id = msg.id;
         let drag_account_id = account_id.clone();
         let drag_folder_path = folder_path.clone();
+        let drag_folder_id = msg.folder_id;
+        let drag_subject = msg.subject.clone();
 
         let widget_for_prepare = self.clone();
         drag_source.connect_prepare(move |_source, _x, _y| {
@@ -1673,7 +1852,20 @@ impl MessageList {
             } else {
                 // Single message: "uid:msg_id:account_id:folder_path"
                 let data = format!("{}:{}:{}:{}", drag_msg_uid, drag_msg_id, drag_account_id, drag_folder_path);
-                Some(gtk4::gdk::ContentProvider::for_value(&data.to_value()))
+                let provider = gtk4::gdk::ContentProvider::for_value(&data.to_value());
+                // Also as a file, for dropping outside NorthMail
+                let file = widget_for_prepare.imp().on_drag_export.borrow().as_ref()
+                    .and_then(|export| export(drag_msg_uid, drag_folder_id, &drag_subject));
+                match file {
+                    Some(path) => {
+                        let files = gtk4::gdk::FileList::from_array(&[gtk4::gio::File::for_path(path)]);
+                        Some(gtk4::gdk::ContentProvider::new_union(&[
+                            provider,
+                            gtk4::gdk::ContentProvider::for_value(&files.to_value()),
+                        ]))
+                    }
+                    None => Some(provider),
+                }
             }
         });
 
@@ -1843,6 +2035,8 @@ impl MessageList {
         let msg_folder_id = msg.folder_id;
         let is_read = msg.is_read;
         let is_starred = msg.is_starred;
+        let is_snoozed = msg.snoozed_until.is_some();
+        let is_reply_later = msg.reply_later_at.is_some();
 
         let vbox = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
         vbox.set_margin_top(4);
@@ -1896,6 +2090,56 @@ impl MessageList {
             });
         }
 
+        // Tags (IMAP keywords)
+        {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Edit Tags…"), Some("document-edit-symbolic"));
+            let w = widget.clone();
+            let p = popover.clone();
+            btn.connect_clicked(move |_| {
+                p.popdown();
+                w.imp().context_menu_open.set(false);
+                w.emit_by_name::<()>("edit-tags", &[&msg_uid, &msg_id, &msg_folder_id]);
+            });
+        }
+
+        // Snooze / bring back
+        {
+            let label = if is_snoozed { tr("Unsnooze") } else { tr("Snooze…") };
+            let btn = Self::make_context_menu_item(&vbox, &label, Some("alarm-symbolic"));
+            let w = widget.clone();
+            let p = popover.clone();
+            btn.connect_clicked(move |_| {
+                p.popdown();
+                w.imp().context_menu_open.set(false);
+                w.emit_by_name::<()>("snooze", &[&msg_uid, &msg_id, &msg_folder_id, &!is_snoozed]);
+            });
+        }
+
+        // Save as .eml
+        {
+            let btn = Self::make_context_menu_item(&vbox, &tr("Save As…"), Some("document-save-as-symbolic"));
+            let w = widget.clone();
+            let p = popover.clone();
+            btn.connect_clicked(move |_| {
+                p.popdown();
+                w.imp().context_menu_open.set(false);
+                w.emit_by_name::<()>("save-as", &[&msg_uid, &msg_id, &msg_folder_id]);
+            });
+        }
+
+        // Reply later / cancel
+        {
+            let label = if is_reply_later { tr("Cancel Reply Later") } else { tr("Reply Later…") };
+            let btn = Self::make_context_menu_item(&vbox, &label, Some("mail-reply-sender-symbolic"));
+            let w = widget.clone();
+            let p = popover.clone();
+            btn.connect_clicked(move |_| {
+                p.popdown();
+                w.imp().context_menu_open.set(false);
+                w.emit_by_name::<()>("reply-later", &[&msg_uid, &msg_id, &msg_folder_id, &!is_reply_later]);
+            });
+        }
+
         Self::add_context_menu_separator(&vbox);
 
         // Reply / Reply All / Forward
@@ -2106,36 +2350,125 @@ impl MessageList {
         row.add_controller(gesture);
     }
 
-    /// Update a message's starred status in the list
-    pub fn update_message_starred(&self, uid: u32, is_starred: bool) {
+    /// Replace a message's IMAP keywords after its tags were edited
+    pub fn update_message_tags(&self, uid: u32, folder_id: i64, tags: Vec<String>) {
         let imp = self.imp();
         let mut messages = imp.messages.borrow_mut();
-        if let Some(msg) = messages.iter_mut().find(|m| m.uid == uid) {
-            msg.is_starred = is_starred;
+        if let Some(msg) = messages.iter_mut().find(|m| m.uid == uid && m.folder_id == folder_id) {
+            msg.tags = tags;
         }
         drop(messages);
-        // Rebuild directly to preserve FTS search results
         self.rebuild_visible_rows_direct();
     }
 
+    /// Replace a Gmail message's labels after its tags were edited
+    pub fn update_message_labels(&self, uid: u32, folder_id: i64, labels: Vec<String>) {
+        let imp = self.imp();
+        let mut messages = imp.messages.borrow_mut();
+        if let Some(msg) = messages.iter_mut().find(|m| m.uid == uid && m.folder_id == folder_id) {
+            msg.gmail_labels = labels;
+        }
+        drop(messages);
+        self.rebuild_visible_rows_direct();
+    }
+
+    /// Record when a listed message comes back from snoozing, for lists
+    /// that keep showing it
+    pub fn update_message_snoozed(&self, uid: u32, folder_id: i64, until: Option<i64>) {
+        let imp = self.imp();
+        let mut messages = imp.messages.borrow_mut();
+        let Some(msg) = messages.iter_mut().find(|m| m.uid == uid && m.folder_id == folder_id) else {
+            return;
+        };
+        msg.snoozed_until = until;
+        drop(messages);
+        self.rebuild_visible_rows_direct();
+    }
+
+    /// Record when the reply to a listed message is due, or `None` once it
+    /// is unmarked
+    pub fn update_message_reply_later(&self, uid: u32, folder_id: i64, at: Option<i64>) {
+        let imp = self.imp();
+        let mut messages = imp.messages.borrow_mut();
+        let Some(msg) = messages.iter_mut().find(|m| m.uid == uid && m.folder_id == folder_id) else {
+            return;
+        };
+        msg.reply_later_at = at;
+    }
+
+    /// Tag messages with the mention keywords found in them, by message id
+    pub fn update_message_mentions(&self, mentions: &[(i64, String)]) {
+        let imp = self.imp();
+        let mut changed = false;
+        for msg in imp.messages.borrow_mut().iter_mut() {
+            if let Some((_, keyword)) = mentions.iter().find(|(id, _)| *id == msg.id) {
+                msg.mention = Some(keyword.clone());
+                changed = true;
+            }
+        }
+        if changed {
+            self.rebuild_visible_rows_direct();
+        }
+    }
+
+    /// Apply flag changes broadcast by the application. Messages fetched
+    /// straight from the server have no folder id; they belong to
+    /// `current_folder_id`. Read changes update rows in place, so a message
+    /// doesn't vanish from an unread-only view; star changes rebuild once.
+    pub fn apply_flag_changes(&self, changes: &[FlagChange], current_folder_id: i64) {
+        let imp = self.imp();
+        let by_message: HashMap<(i64, u32), &FlagChange> = changes
+            .iter()
+            .map(|c| ((c.folder_id, c.uid), c))
+            .collect();
+
+        let mut read_changes = Vec::new();
+        let mut starred_changed = false;
+        for msg in imp.messages.borrow_mut().iter_mut() {
+            let folder_id = if msg.folder_id > 0 { msg.folder_id } else { current_folder_id };
+            let Some(change) = by_message.get(&(folder_id, msg.uid)) else {
+                continue;
+            };
+            if let Some(is_read) = change.is_read {
+                if msg.is_read != is_read {
+                    read_changes.push((msg.folder_id, msg.uid, is_read));
+                }
+            }
+            if let Some(is_starred) = change.is_starred {
+                if msg.is_starred != is_starred {
+                    msg.is_starred = is_starred;
+                    starred_changed = true;
+                }
+            }
+        }
+
+        for (folder_id, uid, is_read) in read_changes {
+            self.set_row_read(|m| m.folder_id == folder_id && m.uid == uid, is_read);
+        }
+        if starred_changed {
+            // Rebuild directly to preserve FTS search results
+            self.rebuild_visible_rows_direct();
+        }
+    }
+
     /// Update a message's read status in the list (in-place, no rebuild)
-    pub fn update_message_read(&self, uid: u32, is_read: bool) {
+    fn set_row_read(&self, matches: impl Fn(&MessageInfo) -> bool, is_read: bool) {
         let imp = self.imp();
 
-        // Find the row index for this UID
+        // Find the row index for this message
         let row_index = {
             let messages = imp.messages.borrow();
             let skip_search = imp.is_search_results.get();
             let filtered: Vec<&MessageInfo> = messages.iter()
                 .filter(|m| self.message_matches_with_options(m, skip_search))
                 .collect();
-            filtered.iter().position(|m| m.uid == uid)
+            filtered.iter().position(|m| matches(m))
         };
 
         // Update the data model
         {
             let mut messages = imp.messages.borrow_mut();
-            if let Some(msg) = messages.iter_mut().find(|m| m.uid == uid) {
+            if let Some(msg) = messages.iter_mut().find(|m| matches(m)) {
                 msg.is_read = is_read;
             }
         }
@@ -2221,6 +2554,51 @@ impl MessageList {
         self.rebuild_visible_rows_direct();
     }
 
+    /// UID of the visible message below (`forward`) or above the given one
+    pub fn adjacent_uid(&self, uid: u32, forward: bool) -> Option<u32> {
+        let imp = self.imp();
+        let messages = imp.messages.borrow();
+        let skip_search = imp.is_search_results.get();
+        let visible: Vec<u32> = messages.iter()
+            .filter(|m| self.message_matches_with_options(m, skip_search))
+            .map(|m| m.uid)
+            .collect();
+        let index = visible.iter().position(|&u| u == uid)?;
+        if forward {
+            visible.get(index + 1).copied()
+        } else {
+            index.checked_sub(1).and_then(|i| visible.get(i).copied())
+        }
+    }
+
+    /// Select a message by UID and emit message-selected for it.
+    /// Deferred to idle so it runs after any pending row rebuild.
+    pub fn select_message(&self, uid: u32) {
+        let imp = self.imp();
+        imp.selected_uids.replace(vec![uid]);
+
+        let widget = self.clone();
+        glib::idle_add_local_once(move || {
+            let imp = widget.imp();
+            if let Some(lb) = imp.list_box.borrow().as_ref() {
+                let mut index = 0;
+                while let Some(row) = lb.row_at_index(index) {
+                    if MessageList::uid_from_row(&row) == Some(uid) {
+                        imp.is_rebuilding.set(true);
+                        lb.unselect_all();
+                        lb.select_row(Some(&row));
+                        imp.is_rebuilding.set(false);
+                        imp.anchor_index.set(Some(index));
+                        row.grab_focus();
+                        break;
+                    }
+                    index += 1;
+                }
+            }
+            widget.emit_by_name::<()>("message-selected", &[&uid]);
+        });
+    }
+
     /// Rebuild visible rows from stored messages (used after status updates)
     /// Rebuild visible rows, delegating to the filter-changed callback if a
     /// DB-level filter is active. Used when filter state changes.
@@ -2383,6 +2761,16 @@ fn parse_date_to_epoch(s: &str) -> Option<i64> {
 }
 
 
+/// A change to a message's flags, broadcast to every open view. `None`
+/// leaves that flag as it is.
+#[derive(Debug, Clone, Copy)]
+pub struct FlagChange {
+    pub folder_id: i64,
+    pub uid: u32,
+    pub is_read: Option<bool>,
+    pub is_starred: Option<bool>,
+}
+
 /// Information about a message for display
 #[derive(Clone)]
 pub struct MessageInfo {
@@ -2401,6 +2789,18 @@ pub struct MessageInfo {
     pub is_read: bool,
     pub is_starred: bool,
     pub has_attachments: bool,
+    /// Gmail labels, including system labels like `\Inbox`
+    pub gmail_labels: Vec<String>,
+    /// Gmail conversation id
+    pub gmail_thread_id: Option<i64>,
+    /// Mention keyword found in the message
+    pub mention: Option<String>,
+    /// IMAP keywords, including ones not shown as tags
+    pub tags: Vec<String>,
+    /// When a snoozed message comes back, as a Unix timestamp
+    pub snoozed_until: Option<i64>,
+    /// When the reply to the message is due, as a Unix timestamp
+    pub reply_later_at: Option<i64>,
 }
 
 impl From<&northmail_core::models::DbMessage> for MessageInfo {
@@ -2425,6 +2825,12 @@ impl From<&northmail_core::models::DbMessage> for MessageInfo {
             is_read: db_msg.is_read,
             is_starred: db_msg.is_starred,
             has_attachments: db_msg.has_attachments,
+            gmail_labels: northmail_core::gmail::decode_labels(db_msg.gmail_labels.as_deref()),
+            gmail_thread_id: db_msg.gmail_thread_id,
+            mention: db_msg.mention.clone(),
+            tags: northmail_core::tags::decode_tags(db_msg.tags.as_deref()),
+            snoozed_until: db_msg.snoozed_until,
+            reply_later_at: db_msg.reply_later_at,
         }
     }
 }
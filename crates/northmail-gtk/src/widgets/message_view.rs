@@ -334,89 +334,20 @@ impl MessageView {
         imp.content_box.replace(Some(content_box));
     }
 
-    /// Generate a color from a string (for avatar background)
-    fn string_to_color(s: &str) -> String {
-        let colors = [
-            "#4A90D9", "#E74C3C", "#2ECC71", "#9B59B6",
-            "#F39C12", "#1ABC9C", "#E91E63", "#3F51B5",
-            "#00BCD4", "#8BC34A", "#FF5722", "#607D8B",
-        ];
-
-        let hash: usize = s.bytes().fold(0, |acc, b| acc.wrapping_add(b as usize));
-        colors[hash % colors.len()].to_string()
-    }
-
-    /// Get initials from a name or email
-    fn get_initials(name: &str, email: &str) -> String {
-        // Try to get initials from name first
-        let display = if name.is_empty() || name == email {
-            email.split('@').next().unwrap_or("?")
-        } else {
-            name
-        };
-
-        let words: Vec<&str> = display.split_whitespace().collect();
-        match words.len() {
-            0 => "?".to_string(),
-            1 => words[0].chars().next().unwrap_or('?').to_uppercase().to_string(),
-            _ => {
-                let first = words[0].chars().next().unwrap_or('?');
-                let last = words[words.len() - 1].chars().next().unwrap_or('?');
-                format!("{}{}", first, last).to_uppercase()
-            }
+    /// Create an avatar widget: contact photo, else sender image, else initials
+    fn create_avatar(&self, name: &str, email: &str) -> gtk4::Widget {
+        let app_ref = self.root()
+            .and_then(|r| r.downcast_ref::<gtk4::Window>().cloned())
+            .and_then(|w| w.application())
+            .and_then(|a| a.downcast_ref::<crate::application::NorthMailApplication>().cloned());
+        let contact_photo = app_ref.as_ref()
+            .and_then(|app| app.get_contact_photo(email));
+
+        let (avatar, image_slot) = crate::window::create_avatar(name, email, contact_photo.as_deref());
+        if let (Some(image_slot), Some(app)) = (image_slot, app_ref) {
+            crate::window::load_sender_image(&app, email, image_slot);
         }
-    }
-
-    /// Create an avatar widget
-    fn create_avatar(&self, name: &str, email: &str, _contact_photo: Option<&str>) -> gtk4::Widget {
-        // TODO: If contact_photo is available, use it instead of initials
-        let initials = Self::get_initials(name, email);
-        let color = Self::string_to_color(email);
-
-        let avatar_box = gtk4::Box::builder()
-            .width_request(40)
-            .height_request(40)
-            .halign(gtk4::Align::Center)
-            .valign(gtk4::Align::Center)
-            .build();
-
-        let drawing_area = gtk4::DrawingArea::builder()
-            .width_request(40)
-            .height_request(40)
-            .build();
-
-        let initials_clone = initials.clone();
-        let color_clone = color.clone();
-        drawing_area.set_draw_func(move |_, cr, width, height| {
-            // Parse color
-            let r = u8::from_str_radix(&color_clone[1..3], 16).unwrap_or(74) as f64 / 255.0;
-            let g = u8::from_str_radix(&color_clone[3..5], 16).unwrap_or(144) as f64 / 255.0;
-            let b = u8::from_str_radix(&color_clone[5..7], 16).unwrap_or(217) as f64 / 255.0;
-
-            // Draw circle
-            let radius = (width.min(height) as f64) / 2.0;
-            let cx = width as f64 / 2.0;
-            let cy = height as f64 / 2.0;
-
-            cr.arc(cx, cy, radius, 0.0, 2.0 * std::f64::consts::PI);
-            cr.set_source_rgb(r, g, b);
-            let _ = cr.fill();
-
-            // Draw text
-            cr.set_source_rgb(1.0, 1.0, 1.0);
-            cr.select_font_face("Sans", gtk4::cairo::FontSlant::Normal, gtk4::cairo::FontWeight::Bold);
-            cr.set_font_size(16.0);
-
-            let extents = cr.text_extents(&initials_clone).unwrap();
-            let x = cx - extents.width() / 2.0 - extents.x_bearing();
-            let y = cy - extents.height() / 2.0 - extents.y_bearing();
-
-            cr.move_to(x, y);
-            let _ = cr.show_text(&initials_clone);
-        });
-
-        avatar_box.append(&drawing_area);
-        avatar_box.upcast()
+        avatar
     }
 
     /// Display a message
@@ -442,7 +373,7 @@ impl MessageView {
                 .build();
 
             // Avatar
-            let avatar = self.create_avatar(&message.from_name, &message.from_email, None);
+            let avatar = self.create_avatar(&message.from_name, &message.from_email);
             sender_row.append(&avatar);
 
             // Sender name and email (wrapped in clickable box with context menu)
@@ -1,10 +1,14 @@
 //! Main application window
 
-use crate::application::{NorthMailApplication, ParsedAttachment, ParsedEmailBody};
-use crate::widgets::{FolderSidebar, MessageList, MessageView};
+use crate::application::{NorthMailApplication, ParsedAttachment, ParsedEmailBody, SendRequest};
+use crate::widgets::{FlagChange, FolderSidebar, MessageList, MessageView};
 use gtk4::{gio, glib, prelude::*, subclass::prelude::*};
 use libadwaita as adw;
 use libadwaita::prelude::*;
+use northmail_core::address::{emails_in, format_address_list, parse_address_list, Address};
+use northmail_core::identity::{insert_signature, signature_block, signature_offset, Identity, Signature, SignaturePlacement};
+use northmail_core::runtime::spawn_db;
+use northmail_smtp::cloud::{self, CloudStorage};
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use tracing::debug;
@@ -24,6 +28,7 @@ pub enum ComposeMode {
         quoted_body: String,
         in_reply_to: Option<String>,
         references: Vec<String>,
+        addressed_to: Vec<String>,   // recipients of the original, to reply as
     },
     ReplyAll {
         to: Vec<(String, String)>,   // (email, display_name) pairs
@@ -32,6 +37,7 @@ pub enum ComposeMode {
         quoted_body: String,
         in_reply_to: Option<String>,
         references: Vec<String>,
+        addressed_to: Vec<String>,   // recipients of the original, to reply as
     },
     Forward {
         subject: String,
@@ -46,6 +52,23 @@ pub enum ComposeMode {
         attachments: Vec<(String, String, Vec<u8>)>, // (filename, mime_type, data)
         draft_uid: u32,        // UID of draft to delete after sending
         account_index: u32,    // Account the draft belongs to
+        from: String,          // Address the draft is from
+    },
+    /// From a `mailto:` link opened elsewhere on the desktop
+    Mailto(northmail_core::mailto::MailtoLink),
+    /// A message taken back out of the outbox to edit it
+    Outgoing {
+        to: Vec<String>,
+        cc: Vec<String>,
+        bcc: Vec<String>,
+        subject: String,
+        body: String,
+        attachments: Vec<(String, String, Vec<u8>)>, // (filename, mime_type, data)
+        account_index: u32,
+        from: String,
+        in_reply_to: Option<String>,
+        references: Vec<String>,
+        draft_uid: Option<u32>, // ms_graph draft the message is sent in place of
     },
 }
 
@@ -59,8 +82,51 @@ fn extract_email_address(from: &str) -> String {
     from.trim().to_string()
 }
 
+/// Recipients of an address list as (email, display) pairs for reply-all,
+/// leaving out `exclude` and entries that aren't addresses
+fn reply_recipients(list: &str, exclude: &str) -> Vec<(String, String)> {
+    parse_address_list(list)
+        .into_iter()
+        .filter(|a| a.email.contains('@') && !a.email.eq_ignore_ascii_case(exclude))
+        .map(|a| (a.email.clone(), a.display_name().to_string()))
+        .collect()
+}
+
+/// Addresses a message was sent to, To before Cc, to pick the identity a
+/// reply is sent as
+fn addressed_to(to: &str, cc: &str) -> Vec<String> {
+    emails_in(to).into_iter().chain(emails_in(cc)).collect()
+}
+
+/// Swap the signature of the identity `old` in a compose body for `new`'s,
+/// in place so the rest keeps its formatting. Where `old`'s was edited
+/// away, `new`'s goes where `placement` puts it.
+fn swap_signature(
+    buffer: &gtk4::TextBuffer,
+    old: Option<&Signature>,
+    new: Option<&Signature>,
+    placement: SignaturePlacement,
+) {
+    let new = new.and_then(signature_block);
+    let found = old
+        .and_then(signature_block)
+        .and_then(|old| buffer.start_iter().forward_search(&old, gtk4::TextSearchFlags::empty(), None));
+    if let Some((mut start, mut end)) = found {
+        buffer.delete(&mut start, &mut end);
+        if let Some(new) = new {
+            buffer.insert(&mut start, &new);
+        }
+        return;
+    }
+    let Some(new) = new else { return };
+    let (start, end) = buffer.bounds();
+    let text = buffer.text(&start, &end, false);
+    let offset = text[..signature_offset(&text, placement)].chars().count();
+    buffer.insert(&mut buffer.iter_at_offset(offset as i32), &new);
+}
+
 /// Format the quoted body for reply
-fn format_quoted_body(from: &str, date: &str, body: &str) -> String {
+pub(crate) fn format_quoted_body(from: &str, date: &str, body: &str) -> String {
     let mut quoted = format!("\n\n{} {}, {} {}:\n", tr("On"), date, from, tr("wrote"));
     for line in body.lines() {
         quoted.push_str(&format!("> {}\n", line));
@@ -122,6 +188,358 @@ pub(crate) fn string_to_avatar_color(s: &str) -> (f64, f64, f64) {
     colors[hash % colors.len()]
 }
 
+/// Most quote levels given their own colour; deeper ones share the last
+const MAX_COLORED_QUOTE_LEVELS: usize = 6;
+
+/// Colour for each quote level of a message body, outermost first. A level
+/// takes its author's avatar colour so a participant looks the same at every
+/// depth; levels with no attribution get a colour of their own.
+fn quote_level_colors(text: &str) -> Vec<String> {
+    let mut authors = northmail_core::thread::quote_authors(text);
+    let deepest = text
+        .lines()
+        .map(|line| northmail_core::thread::split_quote(line).0)
+        .max()
+        .unwrap_or(0);
+    authors.resize(authors.len().max(deepest).min(MAX_COLORED_QUOTE_LEVELS), None);
+    authors
+        .iter()
+        .enumerate()
+        .map(|(i, author)| {
+            let key = author.clone().unwrap_or_else(|| format!("quote level {}", i + 1));
+            let (r, g, b) = string_to_avatar_color(&key);
+            let channel = |c: f64| (c * 255.0).round() as u8;
+            format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+        })
+        .collect()
+}
+
+/// Style sheet giving nested blockquotes a border in their level's colour.
+/// With high contrast on, borders take the text colour instead.
+#[cfg(feature = "webkit")]
+fn quote_colors_css(colors: &[String]) -> String {
+    if colors.is_empty() {
+        return String::new();
+    }
+    let high_contrast = adw::StyleManager::default().is_high_contrast();
+    let rules: String = colors
+        .iter()
+        .enumerate()
+        .map(|(i, color)| {
+            format!(
+                "{} {{ border-left: 3px solid {} !important; padding-left: 1ex !important; }}",
+                vec!["blockquote"; i + 1].join(" "),
+                if high_contrast { "currentColor" } else { color.as_str() }
+            )
+        })
+        .collect();
+    format!("<style>{}</style>", rules)
+}
+
+/// GNOME's document font, which message text is shown in. `None` outside
+/// GNOME, where GTK's own font applies.
+fn document_font() -> Option<gtk4::pango::FontDescription> {
+    let schema = gio::SettingsSchemaSource::default()?.lookup("org.gnome.desktop.interface", true)?;
+    if !schema.has_key("document-font-name") {
+        return None;
+    }
+    let name = gio::Settings::new("org.gnome.desktop.interface").string("document-font-name");
+    (!name.is_empty()).then(|| gtk4::pango::FontDescription::from_string(&name))
+}
+
+/// GNOME's "Large Text" factor, 1.0 outside GNOME
+#[cfg(feature = "webkit")]
+fn text_scaling_factor() -> f64 {
+    gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup("org.gnome.desktop.interface", true))
+        .filter(|schema| schema.has_key("text-scaling-factor"))
+        .map(|_| gio::Settings::new("org.gnome.desktop.interface").double("text-scaling-factor"))
+        .filter(|factor| *factor > 0.0)
+        .unwrap_or(1.0)
+}
+
+/// Style sheet put in front of HTML bodies: the document font as the base
+/// font, which the message's own styles override, and with high contrast on,
+/// underlined links. Text scaling is applied as the view's zoom level.
+#[cfg(feature = "webkit")]
+fn reading_css() -> String {
+    let mut rules = String::new();
+    if let Some(font) = document_font() {
+        if let Some(family) = font.family() {
+            let family: String = family.chars().filter(|c| !matches!(c, '"' | '\\' | '<' | '>')).collect();
+            rules.push_str(&format!("html {{ font-family: \"{}\", sans-serif; }}", family));
+        }
+        if font.size() > 0 {
+            let size = font.size() as f64 / gtk4::pango::SCALE as f64;
+            // Points to CSS pixels, unless the size is already in pixels
+            let px = if font.is_size_absolute() { size } else { size * 96.0 / 72.0 };
+            rules.push_str(&format!("html {{ font-size: {:.1}px; }}", px));
+        }
+    }
+    if adw::StyleManager::default().is_high_contrast() {
+        rules.push_str("a { text-decoration: underline !important; }");
+    }
+    if rules.is_empty() {
+        return String::new();
+    }
+    format!("<style>{}</style>", rules)
+}
+
+/// Read-only view of a plain-text body in the document font, with quoted
+/// lines indented and coloured by level and attribution lines in the colour
+/// of the quote they open. With high contrast on, quotes keep the text
+/// colour. Text scaling reaches the view through GTK's font resolution.
+fn quoted_text_view(text: &str) -> gtk4::TextView {
+    let text_view = gtk4::TextView::builder()
+        .editable(false)
+        .cursor_visible(false)
+        .wrap_mode(gtk4::WrapMode::Word)
+        .vexpand(true)
+        .build();
+    let buffer = text_view.buffer();
+    buffer.set_text(text);
+
+    // Added first, so quote tags take priority over it
+    if let Some(font) = document_font() {
+        buffer.tag_table().add(
+            &gtk4::TextTag::builder()
+                .name("document-font")
+                .font_desc(&font)
+                .build(),
+        );
+        let (start, end) = buffer.bounds();
+        buffer.apply_tag_by_name("document-font", &start, &end);
+    }
+
+    let colors = quote_level_colors(text);
+    if colors.is_empty() {
+        return text_view;
+    }
+    let high_contrast = adw::StyleManager::default().is_high_contrast();
+    for (i, color) in colors.iter().enumerate() {
+        let quote = gtk4::TextTag::builder()
+            .name(format!("quote-{}", i + 1))
+            .left_margin(12 * (i as i32 + 1))
+            .build();
+        let attribution = gtk4::TextTag::builder()
+            .name(format!("attribution-{}", i + 1))
+            .weight(700)
+            .build();
+        if !high_contrast {
+            quote.set_foreground(Some(color.as_str()));
+            attribution.set_foreground(Some(color.as_str()));
+        }
+        buffer.tag_table().add(&quote);
+        buffer.tag_table().add(&attribution);
+    }
+
+    for (line_number, line) in text.lines().enumerate() {
+        let (depth, content) = northmail_core::thread::split_quote(line);
+        let tag = if northmail_core::thread::attribution_author(content).is_some() && depth < colors.len() {
+            format!("attribution-{}", depth + 1)
+        } else if depth > 0 {
+            format!("quote-{}", depth.min(colors.len()))
+        } else {
+            continue;
+        };
+        let Some(start) = buffer.iter_at_line(line_number as i32) else {
+            continue;
+        };
+        let mut end = start.clone();
+        end.forward_to_line_end();
+        buffer.apply_tag_by_name(&tag, &start, &end);
+    }
+    text_view
+}
+
+/// Card summing up an order or shipment: merchant, order number, status,
+/// delivery date and a button to the tracking or order page
+fn summary_card(summary: &northmail_core::structured_data::MailSummary) -> gtk4::Box {
+    use chrono::Datelike;
+    use northmail_core::structured_data::{OrderStatus, SummaryKind};
+
+    let card = gtk4::Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .spacing(12)
+        .margin_bottom(12)
+        .css_classes(["card", "summary-card"])
+        .build();
+
+    let (icon, title) = match (summary.kind, &summary.merchant) {
+        (SummaryKind::Shipment, Some(merchant)) => ("package-x-generic-symbolic", tr("Package from {}").replace("{}", merchant)),
+        (SummaryKind::Shipment, None) => ("package-x-generic-symbolic", tr("Package")),
+        (SummaryKind::Order, Some(merchant)) => ("emblem-documents-symbolic", tr("Order from {}").replace("{}", merchant)),
+        (SummaryKind::Order, None) => ("emblem-documents-symbolic", tr("Order")),
+    };
+    card.append(
+        &gtk4::Image::builder()
+            .icon_name(icon)
+            .pixel_size(32)
+            .valign(gtk4::Align::Center)
+            .build(),
+    );
+
+    let text = gtk4::Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(2)
+        .hexpand(true)
+        .valign(gtk4::Align::Center)
+        .build();
+    text.append(
+        &gtk4::Label::builder()
+            .label(&title)
+            .xalign(0.0)
+            .css_classes(["heading"])
+            .ellipsize(gtk4::pango::EllipsizeMode::End)
+            .build(),
+    );
+
+    let mut details = Vec::new();
+    if let Some(status) = summary.status {
+        details.push(match status {
+            OrderStatus::Processing => tr("Processing"),
+            OrderStatus::PaymentDue => tr("Payment due"),
+            OrderStatus::InTransit => tr("In transit"),
+            OrderStatus::PickupAvailable => tr("Ready for pickup"),
+            OrderStatus::Delivered => tr("Delivered"),
+            OrderStatus::Cancelled => tr("Cancelled"),
+            OrderStatus::Returned => tr("Returned"),
+            OrderStatus::Problem => tr("Problem with order"),
+        });
+    }
+    if let Some(date) = summary.expected_delivery {
+        let date = glib::DateTime::from_local(date.year(), date.month() as i32, date.day() as i32, 0, 0, 0.0)
+            .and_then(|dt| dt.format("%x"))
+            .map(|s| s.to_string())
+            .unwrap_or_else(|_| date.to_string());
+        details.push(tr("Arrives {}").replace("{}", &date));
+    }
+    if let Some(number) = &summary.order_number {
+        details.push(tr("Order {}").replace("{}", number));
+    }
+    if let Some(total) = &summary.total {
+        details.push(total.clone());
+    }
+    match (&summary.carrier, &summary.tracking_number) {
+        (Some(carrier), Some(number)) => details.push(format!("{} {}", carrier, number)),
+        (Some(carrier), None) => details.push(carrier.clone()),
+        (None, Some(number)) => details.push(number.clone()),
+        (None, None) => {}
+    }
+    if !details.is_empty() {
+        text.append(
+            &gtk4::Label::builder()
+                .label(&details.join(" · "))
+                .xalign(0.0)
+                .wrap(true)
+                .selectable(true)
+                .css_classes(["dim-label", "caption"])
+                .build(),
+        );
+    }
+    card.append(&text);
+
+    if let Some(url) = summary.url.clone().filter(|u| u.starts_with("https://") || u.starts_with("http://")) {
+        let label = match summary.kind {
+            SummaryKind::Shipment => tr("Track Package"),
+            SummaryKind::Order => tr("View Order"),
+        };
+        let button = gtk4::Button::builder()
+            .label(&label)
+            .valign(gtk4::Align::Center)
+            .css_classes(["pill"])
+            .tooltip_text(&url)
+            .build();
+        button.connect_clicked(move |_| {
+            if let Err(e) = gtk4::gio::AppInfo::launch_default_for_uri(&url, gtk4::gio::AppLaunchContext::NONE) {
+                tracing::warn!("Failed to open {}: {}", url, e);
+            }
+        });
+        card.append(&button);
+    }
+    card
+}
+
+/// Where read-aloud is for the open message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadAloudState {
+    Idle,
+    Speaking,
+    Paused,
+}
+
+/// Show the read-aloud button as play, pause or resume, and the stop button
+/// while reading
+fn set_read_aloud_controls(button: &gtk4::Button, stop_button: &gtk4::Button, state: ReadAloudState) {
+    let (icon, tooltip) = match state {
+        ReadAloudState::Idle => ("audio-speakers-symbolic", tr("Read Aloud")),
+        ReadAloudState::Speaking => ("media-playback-pause-symbolic", tr("Pause Reading")),
+        ReadAloudState::Paused => ("media-playback-start-symbolic", tr("Resume Reading")),
+    };
+    button.set_icon_name(icon);
+    button.set_tooltip_text(Some(&tooltip));
+    stop_button.set_visible(state != ReadAloudState::Idle);
+}
+
+/// Row of link preview chips; each opens its link in the browser
+fn link_preview_row(chips: Vec<northmail_core::link_preview::LinkChip>) -> Option<gtk4::FlowBox> {
+    use northmail_core::link_preview::LinkKind;
+
+    if chips.is_empty() {
+        return None;
+    }
+    let flow = gtk4::FlowBox::builder()
+        .selection_mode(gtk4::SelectionMode::None)
+        .column_spacing(6)
+        .row_spacing(6)
+        .max_children_per_line(4)
+        .margin_top(12)
+        .build();
+    for chip in chips {
+        let (icon, kind) = match chip.kind {
+            LinkKind::Meeting => ("camera-web-symbolic", tr("Meeting")),
+            LinkKind::Issue => ("dialog-information-symbolic", tr("Issue")),
+            LinkKind::MergeRequest => ("emblem-shared-symbolic", tr("Merge request")),
+            LinkKind::Map => ("mark-location-symbolic", tr("Location")),
+        };
+        let content = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(6)
+            .build();
+        content.append(&gtk4::Image::from_icon_name(icon));
+        content.append(
+            &gtk4::Label::builder()
+                .label(&chip.title)
+                .ellipsize(gtk4::pango::EllipsizeMode::End)
+                .max_width_chars(32)
+                .build(),
+        );
+        if let Some(detail) = &chip.detail {
+            content.append(
+                &gtk4::Label::builder()
+                    .label(detail)
+                    .css_classes(["dim-label", "caption"])
+                    .ellipsize(gtk4::pango::EllipsizeMode::End)
+                    .max_width_chars(24)
+                    .build(),
+            );
+        }
+        let button = gtk4::Button::builder()
+            .child(&content)
+            .css_classes(["flat", "link-chip"])
+            .tooltip_text(&format!("{} — {}", kind, chip.url))
+            .build();
+        let url = chip.url;
+        button.connect_clicked(move |_| {
+            if let Err(e) = gtk4::gio::AppInfo::launch_default_for_uri(&url, gtk4::gio::AppLaunchContext::NONE) {
+                tracing::warn!("Failed to open {}: {}", url, e);
+            }
+        });
+        flow.append(&button);
+    }
+    Some(flow)
+}
+
 /// Get initials from a name or email
 pub(crate) fn get_initials(name: &str, email: &str) -> String {
     let display = if name.is_empty() || name == email || name.contains('@') {
@@ -194,72 +612,65 @@ pub(crate) fn base_domain(domain: &str) -> &str {
     &domain[start..]
 }
 
+/// Decode image bytes (PNG, JPEG, SVG…) into a Cairo ImageSurface (ARGB32 premultiplied)
+pub(crate) fn image_surface(bytes: &[u8]) -> Option<gtk4::cairo::ImageSurface> {
+    let gbytes = glib::Bytes::from(bytes);
+    let tex = gtk4::gdk::Texture::from_bytes(&gbytes).ok()?;
+    let tw = tex.width();
+    let th = tex.height();
+    let stride = gtk4::cairo::Format::ARgb32.stride_for_width(tw as u32).unwrap_or(tw * 4);
+    let mut pixel_data = vec![0u8; (stride * th) as usize];
+    tex.download(&mut pixel_data, stride as usize);
+    gtk4::cairo::ImageSurface::create_for_data(pixel_data, gtk4::cairo::Format::ARgb32, tw, th, stride).ok()
+}
+
 /// Create an avatar widget with contact photo or colored initials.
-/// Returns `(widget, Option<favicon_slot>)`. If the contact has a photo, favicon_slot is None.
-/// Otherwise, favicon_slot is a shared slot that can be filled later to swap initials for a favicon.
+/// Returns `(widget, Option<image_slot>)`. If the contact has a photo, image_slot is None.
+/// Otherwise, image_slot is a shared slot that can be filled later to swap initials for a
+/// sender image (see `load_sender_image`).
 pub(crate) fn create_avatar(
     name: &str,
     email: &str,
     photo: Option<&[u8]>,
 ) -> (gtk4::Widget, Option<(gtk4::DrawingArea, Rc<RefCell<Option<gtk4::cairo::ImageSurface>>>)>) {
-    // Try to create a texture from photo bytes
-    let texture = photo.and_then(|bytes| {
-        let gbytes = glib::Bytes::from(bytes);
-        gtk4::gdk::Texture::from_bytes(&gbytes).ok()
-    });
+    if let Some(surface) = photo.and_then(image_surface) {
+        let drawing_area = gtk4::DrawingArea::builder()
+            .width_request(40)
+            .height_request(40)
+            .valign(gtk4::Align::Center)
+            .build();
 
-    if let Some(tex) = texture {
-        // Download texture pixels to a Cairo ImageSurface (ARGB32 premultiplied)
-        let tw = tex.width();
-        let th = tex.height();
-        let stride = gtk4::cairo::Format::ARgb32.stride_for_width(tw as u32).unwrap_or(tw * 4);
-        let mut pixel_data = vec![0u8; (stride * th) as usize];
-        tex.download(&mut pixel_data, stride as usize);
-        if let Ok(surface) = gtk4::cairo::ImageSurface::create_for_data(
-            pixel_data,
-            gtk4::cairo::Format::ARgb32,
-            tw,
-            th,
-            stride,
-        ) {
-            let drawing_area = gtk4::DrawingArea::builder()
-                .width_request(40)
-                .height_request(40)
-                .valign(gtk4::Align::Center)
-                .build();
+        drawing_area.set_draw_func(move |_, cr, width, height| {
+            let size = width.min(height) as f64;
+            let radius = size / 2.0;
+            let cx = width as f64 / 2.0;
+            let cy = height as f64 / 2.0;
 
-            drawing_area.set_draw_func(move |_, cr, width, height| {
-                let size = width.min(height) as f64;
-                let radius = size / 2.0;
-                let cx = width as f64 / 2.0;
-                let cy = height as f64 / 2.0;
-
-                // Clip to circle
-                cr.arc(cx, cy, radius, 0.0, 2.0 * std::f64::consts::PI);
-                let _ = cr.clip();
-
-                // Scale and paint surface
-                let surf_w = surface.width() as f64;
-                let surf_h = surface.height() as f64;
-                let scale = size / surf_w.min(surf_h);
-                let offset_x = cx - (surf_w * scale) / 2.0;
-                let offset_y = cy - (surf_h * scale) / 2.0;
-
-                cr.translate(offset_x, offset_y);
-                cr.scale(scale, scale);
-                cr.set_source_surface(&surface, 0.0, 0.0).expect("set_source_surface");
-                let _ = cr.paint();
-            });
+            // Clip to circle
+            cr.arc(cx, cy, radius, 0.0, 2.0 * std::f64::consts::PI);
+            let _ = cr.clip();
 
-            return (drawing_area.upcast(), None);
-        }
+            // Scale and paint surface
+            let surf_w = surface.width() as f64;
+            let surf_h = surface.height() as f64;
+            let scale = size / surf_w.min(surf_h);
+            let offset_x = cx - (surf_w * scale) / 2.0;
+            let offset_y = cy - (surf_h * scale) / 2.0;
+
+            cr.translate(offset_x, offset_y);
+            cr.scale(scale, scale);
+            cr.set_source_surface(&surface, 0.0, 0.0).expect("set_source_surface");
+            let _ = cr.paint();
+        });
+
+        return (drawing_area.upcast(), None);
     }
 
-    // Fallback: colored initials (with favicon slot for async upgrade)
+    // Fallback: colored initials (with image slot for async upgrade)
     let initials = get_initials(name, email);
     let (r, g, b) = string_to_avatar_color(email);
-    let favicon_slot: Rc<RefCell<Option<gtk4::cairo::ImageSurface>>> = Rc::new(RefCell::new(None));
-    let slot_for_draw = favicon_slot.clone();
+    let image_slot: Rc<RefCell<Option<gtk4::cairo::ImageSurface>>> = Rc::new(RefCell::new(None));
+    let slot_for_draw = image_slot.clone();
 
     let drawing_area = gtk4::DrawingArea::builder()
         .width_request(40)
@@ -273,7 +684,7 @@ pub(crate) fn create_avatar(
         let cx = width as f64 / 2.0;
         let cy = height as f64 / 2.0;
 
-        // Check if a favicon has been loaded
+        // Check if a sender image has been loaded
         if let Some(ref surface) = *slot_for_draw.borrow() {
             cr.arc(cx, cy, radius, 0.0, 2.0 * std::f64::consts::PI);
             let _ = cr.clip();
@@ -308,7 +719,21 @@ pub(crate) fn create_avatar(
         let _ = cr.show_text(&initials);
     });
 
-    (drawing_area.clone().upcast(), Some((drawing_area, favicon_slot)))
+    (drawing_area.clone().upcast(), Some((drawing_area, image_slot)))
+}
+
+/// Look up the sender image for `email` and draw it in an avatar's image slot once it arrives
+pub(crate) fn load_sender_image(
+    app: &NorthMailApplication,
+    email: &str,
+    (drawing_area, slot): (gtk4::DrawingArea, Rc<RefCell<Option<gtk4::cairo::ImageSurface>>>),
+) {
+    app.fetch_avatar_async(email, move |image| {
+        if let Some(surface) = image.as_deref().and_then(image_surface) {
+            *slot.borrow_mut() = Some(surface);
+            drawing_area.queue_draw();
+        }
+    });
 }
 
 mod imp {
@@ -359,6 +784,25 @@ mod imp {
                                                 <property name="action-name">app.show-settings</property>
                                             </object>
                                         </child>
+                                        <child type="end">
+                                            <object class="GtkButton" id="health_button">
+                                                <property name="icon-name">dialog-warning-symbolic</property>
+                                                <property name="tooltip-text">Account Health</property>
+                                                <property name="action-name">app.account-health</property>
+                                                <property name="visible">false</property>
+                                                <style>
+                                                    <class name="warning"/>
+                                                </style>
+                                            </object>
+                                        </child>
+                                        <child type="end">
+                                            <object class="GtkButton" id="outbox_button">
+                                                <property name="icon-name">mail-send-symbolic</property>
+                                                <property name="tooltip-text">Outbox</property>
+                                                <property name="action-name">app.outbox</property>
+                                                <property name="visible">false</property>
+                                            </object>
+                                        </child>
                                         <child type="end">
                                             <object class="GtkButton" id="refresh_button">
                                                 <property name="icon-name">view-refresh-symbolic</property>
@@ -420,6 +864,10 @@ mod imp {
         #[template_child]
         pub app_icon_image: TemplateChild<gtk4::Image>,
         #[template_child]
+        pub health_button: TemplateChild<gtk4::Button>,
+        #[template_child]
+        pub outbox_button: TemplateChild<gtk4::Button>,
+        #[template_child]
         pub outer_paned: TemplateChild<gtk4::Paned>,
         /// Sidebar toggle button (created in setup_widgets)
         pub sidebar_toggle: std::cell::RefCell<Option<gtk4::ToggleButton>>,
@@ -441,6 +889,8 @@ mod imp {
         pub loading_progress_label: std::cell::RefCell<Option<gtk4::Label>>,
         /// Currently displayed message UID (to avoid reloading the same message)
         pub current_message_uid: std::cell::RefCell<Option<u32>>,
+        /// Message-ID of the displayed message, to thread answers to it
+        pub current_message_id: std::cell::RefCell<Option<String>>,
         /// Timer to auto-mark message as read after 2 seconds
         pub auto_read_timer: std::cell::RefCell<Option<glib::SourceId>>,
         /// Star button in the currently displayed message view header
@@ -449,10 +899,15 @@ mod imp {
         pub current_read_button: std::cell::RefCell<Option<gtk4::Button>>,
         /// Read state for the currently displayed message's read button
         pub current_read_state: std::cell::RefCell<Option<std::rc::Rc<std::cell::Cell<bool>>>>,
+        /// Set while broadcast flag changes update the message view, so its
+        /// buttons don't send the change back to the server
+        pub applying_flag_changes: std::cell::Cell<bool>,
         /// Body text of the currently displayed message (for reply/forward from context menu)
         pub current_body_text: std::cell::RefCell<Option<String>>,
         /// Attachments of the currently displayed message (for forward from context menu)
         pub current_attachments: std::cell::RefCell<Vec<(String, String, Vec<u8>)>>,
+        /// speech-dispatcher connection for read-aloud, opened on first use
+        pub speech: std::cell::RefCell<Option<crate::speech::Speech>>,
     }
 
     #[glib::object_subclass]
@@ -478,6 +933,7 @@ mod imp {
             obj.setup_widgets();
             obj.setup_actions();
             obj.setup_bindings();
+            obj.setup_message_file_drop();
         }
     }
 
@@ -622,6 +1078,26 @@ impl NorthMailWindow {
         self.imp().toast_overlay.add_toast(toast);
     }
 
+    /// Show the header warning button while there are errors to look at
+    pub fn set_has_errors(&self, has_errors: bool) {
+        self.imp().health_button.set_visible(has_errors);
+    }
+
+    /// Show the header Outbox button while messages wait to be sent,
+    /// marked as a warning when some of them failed
+    pub fn set_outbox_count(&self, count: u32, failed: bool) {
+        let button = &self.imp().outbox_button;
+        button.set_visible(count > 0);
+        button.set_tooltip_text(Some(
+            &ntr("Outbox: {n} message", "Outbox: {n} messages", count).replace("{n}", &count.to_string()),
+        ));
+        if failed {
+            button.add_css_class("warning");
+        } else {
+            button.remove_css_class("warning");
+        }
+    }
+
     fn setup_widgets(&self) {
         let imp = self.imp();
 
@@ -724,6 +1200,27 @@ impl NorthMailWindow {
                  background: alpha(@view_fg_color, 0.08);
                  cursor: pointer;
              }
+             /* Order and shipment card above the message body */
+             .summary-card {
+                 padding: 12px;
+             }
+             /* Meeting invitation card above the message body */
+             .invite-card {
+                 padding: 12px;
+             }
+             /* Translation offer and result above the message body */
+             .translation-card {
+                 padding: 12px;
+             }
+             /* Link preview chips under the message body */
+             .link-chip {
+                 padding: 4px 10px;
+                 border-radius: 14px;
+                 background: alpha(@view_fg_color, 0.06);
+             }
+             .link-chip:hover {
+                 background: alpha(@view_fg_color, 0.12);
+             }
              /* Context menu item styling */
              .context-menu-item {
                  font-size: 13px;
@@ -969,7 +1466,9 @@ impl NorthMailWindow {
                         }
 
                         // Extract just the folder name for a friendlier message
-                        let folder_name = target_folder_path.rsplit('/').next().unwrap_or(target_folder_path);
+                        let folder_name = northmail_imap::decode_mailbox_name(
+                            target_folder_path.rsplit('/').next().unwrap_or(target_folder_path),
+                        );
                         window.add_toast(adw::Toast::new(&format!("{} {}", tr("Moved to"), folder_name)));
                     } else {
                         window.add_toast(adw::Toast::new(&tr("Cannot move between different accounts")));
@@ -1011,6 +1510,126 @@ impl NorthMailWindow {
             }
         });
 
+        // Connect folder-import-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_import_requested(move |_sidebar, account_id, folder_path| {
+            debug!("Folder import requested: account={}, path={}", account_id, folder_path);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.import_messages(account_id, folder_path);
+                }
+            }
+        });
+
+        // Connect folder-export-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_export_requested(move |_sidebar, account_id, folder_path| {
+            debug!("Folder export requested: account={}, path={}", account_id, folder_path);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.export_maildir(account_id, folder_path);
+                }
+            }
+        });
+
+        // Connect folder-offline-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_offline_requested(move |_sidebar, account_id, folder_path| {
+            debug!("Offline download requested: account={}, path={}", account_id, folder_path);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.show_offline_download_dialog(account_id, folder_path);
+                }
+            }
+        });
+
+        // Connect folder-duplicates-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_duplicates_requested(move |_sidebar, account_id, folder_path| {
+            debug!("Duplicates requested: account={}, path={}", account_id, folder_path);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.show_duplicates(Some((account_id.to_string(), folder_path.to_string())));
+                }
+            }
+        });
+
+        // Connect folder-sync-settings-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_sync_settings_requested(move |_sidebar, account_id, folder_path| {
+            debug!("Folder sync settings requested: account={}, path={}", account_id, folder_path);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.show_folder_sync_settings(account_id, folder_path);
+                }
+            }
+        });
+
+        // Connect folder-watch-toggled signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_watch_toggled(move |_sidebar, account_id, folder_path, watched| {
+            debug!("Folder watch toggled: account={}, path={}, watched={}", account_id, folder_path, watched);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.set_folder_watched(account_id, folder_path, watched);
+                }
+            }
+        });
+
+        // Connect account-pause-toggled signal
+        let window = self.clone();
+        folder_sidebar.connect_account_pause_toggled(move |_sidebar, account_id, paused| {
+            debug!("Account pause toggled: account={}, paused={}", account_id, paused);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.set_account_paused(account_id, paused);
+                }
+            }
+        });
+
+        // Connect account-ca-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_account_ca_requested(move |_sidebar, account_id| {
+            debug!("Account CA certificate requested: account={}", account_id);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.choose_account_ca_file(account_id);
+                }
+            }
+        });
+
+        // Connect account-proxy-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_account_proxy_requested(move |_sidebar, account_id| {
+            debug!("Account proxy requested: account={}", account_id);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.configure_account_proxy(account_id);
+                }
+            }
+        });
+
+        // Connect account-identities-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_account_identities_requested(move |_sidebar, account_id| {
+            debug!("Account identities requested: account={}", account_id);
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.show_identities_dialog(account_id);
+                }
+            }
+        });
+
+        // Connect folder-peek-requested signal
+        let window = self.clone();
+        folder_sidebar.connect_folder_peek_requested(move |_sidebar, account_id, folder_path| {
+            if let Some(app) = window.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    app.peek_folder(account_id, folder_path);
+                }
+            }
+        });
+
         // Connect empty-trash-requested signal
         let window = self.clone();
         folder_sidebar.connect_empty_trash_requested(move |_sidebar, account_id, folder_path| {
@@ -1026,6 +1645,11 @@ impl NorthMailWindow {
 
         // Create and add message list
         let message_list = MessageList::new();
+        if let Some(app) = self.application() {
+            if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                message_list.set_preview_lines(app.settings().int("preview-lines").max(0) as u32);
+            }
+        }
         imp.message_list_box.append(&message_list);
 
         // Connect message selection to show in message view
@@ -1054,10 +1678,8 @@ impl NorthMailWindow {
 
         // Connect star-toggled callback (star button clicked in message list or context menu)
         let window = self.clone();
-        message_list.connect_star_toggled(move |list, uid, msg_id, folder_id, is_starred| {
+        message_list.connect_star_toggled(move |_list, uid, msg_id, folder_id, is_starred| {
             debug!("Star toggled in list: uid={}, is_starred={}", uid, is_starred);
-            list.update_message_starred(uid, is_starred);
-            window.update_message_view_starred(uid, is_starred);
             if let Some(app) = window.application() {
                 if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                     app.set_message_starred(msg_id, uid, folder_id, is_starred);
@@ -1070,10 +1692,8 @@ impl NorthMailWindow {
         message_list.connect_closure(
             "mark-read",
             false,
-            glib::closure_local!(move |list: &MessageList, uid: u32, msg_id: i64, folder_id: i64, is_read: bool| {
+            glib::closure_local!(move |_list: &MessageList, uid: u32, msg_id: i64, folder_id: i64, is_read: bool| {
                 debug!("Mark read from context menu: uid={}, is_read={}", uid, is_read);
-                list.update_message_read(uid, is_read);
-                window.update_message_view_read(uid, is_read);
                 if let Some(app) = window.application() {
                     if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                         app.set_message_read(msg_id, uid, folder_id, is_read);
@@ -1082,49 +1702,149 @@ impl NorthMailWindow {
             }),
         );
 
-        // Connect archive callback from context menu
+        // Connect edit-tags callback from context menu
         let window = self.clone();
         message_list.connect_closure(
-            "archive",
+            "edit-tags",
             false,
             glib::closure_local!(move |list: &MessageList, uid: u32, msg_id: i64, folder_id: i64| {
-                debug!("Archive from context menu: uid={}", uid);
-                list.remove_message(uid);
+                let (tags, labels) = list
+                    .imp()
+                    .messages
+                    .borrow()
+                    .iter()
+                    .find(|m| m.uid == uid && m.folder_id == folder_id)
+                    .map(|m| (m.tags.clone(), m.gmail_labels.clone()))
+                    .unwrap_or_default();
                 if let Some(app) = window.application() {
                     if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                        app.archive_message(msg_id, uid, folder_id);
+                        app.show_edit_tags_dialog(msg_id, uid, folder_id, tags, labels);
                     }
                 }
             }),
         );
 
-        // Connect trash callback from context menu
+        // Connect save-as callback from context menu
         let window = self.clone();
         message_list.connect_closure(
-            "trash",
+            "save-as",
             false,
-            glib::closure_local!(move |list: &MessageList, uid: u32, msg_id: i64, folder_id: i64| {
-                debug!("Trash from context menu: uid={}", uid);
-                list.remove_message(uid);
+            glib::closure_local!(move |list: &MessageList, uid: u32, _msg_id: i64, folder_id: i64| {
+                let subject = list
+                    .imp()
+                    .messages
+                    .borrow()
+                    .iter()
+                    .find(|m| m.uid == uid && m.folder_id == folder_id)
+                    .map(|m| m.subject.clone())
+                    .unwrap_or_default();
                 if let Some(app) = window.application() {
                     if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                        app.delete_message(msg_id, uid, folder_id);
+                        app.save_message_as(uid, folder_id, &subject);
                     }
                 }
             }),
         );
 
-        // Connect spam callback from context menu
+        // Messages dragged out of the list become .eml files
+        let window = self.clone();
+        message_list.connect_drag_export(move |uid, folder_id, subject| {
+            let app = window.application()?;
+            let app = app.downcast_ref::<NorthMailApplication>()?;
+            app.export_dragged_message(uid, folder_id, subject)
+        });
+
+        // Connect snooze callback from context menu
         let window = self.clone();
         message_list.connect_closure(
-            "spam",
+            "snooze",
             false,
-            glib::closure_local!(move |list: &MessageList, uid: u32, msg_id: i64, folder_id: i64| {
-                debug!("Mark as spam from context menu: uid={}", uid);
-                list.remove_message(uid);
-                if let Some(app) = window.application() {
-                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                        app.move_to_spam(msg_id, uid, folder_id);
+            glib::closure_local!(move |_list: &MessageList, uid: u32, _msg_id: i64, folder_id: i64, snooze: bool| {
+                debug!("Snooze from context menu: uid={}, snooze={}", uid, snooze);
+                let Some(app) = window.application() else { return };
+                let Some(app) = app.downcast_ref::<NorthMailApplication>() else { return };
+                // Starred lists show messages whether snoozed or not
+                let keep_row = app.is_starred_mode();
+                if snooze {
+                    let w = window.clone();
+                    app.show_snooze_dialog(uid, folder_id, move || {
+                        if !keep_row {
+                            w.remove_message_and_advance(uid);
+                        }
+                    });
+                } else {
+                    if !keep_row {
+                        window.remove_message_and_advance(uid);
+                    }
+                    app.set_message_snoozed(uid, folder_id, None);
+                }
+            }),
+        );
+
+        // Connect reply-later callback from context menu
+        let window = self.clone();
+        message_list.connect_closure(
+            "reply-later",
+            false,
+            glib::closure_local!(move |_list: &MessageList, uid: u32, _msg_id: i64, folder_id: i64, mark: bool| {
+                debug!("Reply later from context menu: uid={}, mark={}", uid, mark);
+                let Some(app) = window.application() else { return };
+                let Some(app) = app.downcast_ref::<NorthMailApplication>() else { return };
+                if mark {
+                    app.show_reply_later_dialog(uid, folder_id);
+                } else {
+                    // Only the Reply Later list loses the message
+                    if app.cache_folder_id() == -6 {
+                        window.remove_message_and_advance(uid);
+                    }
+                    app.set_message_reply_later(uid, folder_id, None);
+                }
+            }),
+        );
+
+        // Connect archive callback from context menu
+        let window = self.clone();
+        message_list.connect_closure(
+            "archive",
+            false,
+            glib::closure_local!(move |_list: &MessageList, uid: u32, msg_id: i64, folder_id: i64| {
+                debug!("Archive from context menu: uid={}", uid);
+                window.remove_message_and_advance(uid);
+                if let Some(app) = window.application() {
+                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                        app.archive_message(msg_id, uid, folder_id);
+                    }
+                }
+            }),
+        );
+
+        // Connect trash callback from context menu
+        let window = self.clone();
+        message_list.connect_closure(
+            "trash",
+            false,
+            glib::closure_local!(move |_list: &MessageList, uid: u32, msg_id: i64, folder_id: i64| {
+                debug!("Trash from context menu: uid={}", uid);
+                window.remove_message_and_advance(uid);
+                if let Some(app) = window.application() {
+                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                        app.delete_message(msg_id, uid, folder_id);
+                    }
+                }
+            }),
+        );
+
+        // Connect spam callback from context menu
+        let window = self.clone();
+        message_list.connect_closure(
+            "spam",
+            false,
+            glib::closure_local!(move |_list: &MessageList, uid: u32, msg_id: i64, folder_id: i64| {
+                debug!("Mark as spam from context menu: uid={}", uid);
+                window.remove_message_and_advance(uid);
+                if let Some(app) = window.application() {
+                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                        app.move_to_spam(msg_id, uid, folder_id);
                     }
                 }
             }),
@@ -1144,6 +1864,7 @@ impl NorthMailWindow {
                     let from_for_quote = msg.from.clone();
                     let date_for_quote = msg.date.clone();
                     let orig_message_id = msg.message_id.clone();
+                    let addressed_to = addressed_to(&msg.to, &msg.cc);
                     let subject = if msg.subject.to_lowercase().starts_with("re:") {
                         msg.subject.clone()
                     } else {
@@ -1168,6 +1889,7 @@ impl NorthMailWindow {
                         quoted_body,
                         in_reply_to: orig_message_id,
                         references,
+                        addressed_to,
                     };
                     window.show_compose_dialog_with_mode(mode);
                 }
@@ -1188,24 +1910,9 @@ impl NorthMailWindow {
                     let from_for_quote = msg.from.clone();
                     let date_for_quote = msg.date.clone();
                     let orig_message_id = msg.message_id.clone();
-                    let to_addrs: Vec<(String, String)> = msg.to.split(',')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty())
-                        .map(|s| {
-                            let email = extract_email_address(&s);
-                            (email.clone(), s)
-                        })
-                        .filter(|(e, _)| !e.is_empty() && e.contains('@') && e != &reply_to)
-                        .collect();
-                    let cc_addrs: Vec<(String, String)> = msg.cc.split(',')
-                        .map(|s| s.trim().to_string())
-                        .filter(|s| !s.is_empty())
-                        .map(|s| {
-                            let email = extract_email_address(&s);
-                            (email.clone(), s)
-                        })
-                        .filter(|(e, _)| !e.is_empty() && e.contains('@') && e != &reply_to)
-                        .collect();
+                    let to_addrs = reply_recipients(&msg.to, &reply_to);
+                    let cc_addrs = reply_recipients(&msg.cc, &reply_to);
+                    let addressed_to = addressed_to(&msg.to, &msg.cc);
                     let subject = if msg.subject.to_lowercase().starts_with("re:") {
                         msg.subject.clone()
                     } else {
@@ -1230,6 +1937,7 @@ impl NorthMailWindow {
                         quoted_body,
                         in_reply_to: orig_message_id,
                         references,
+                        addressed_to,
                     };
                     window.show_compose_dialog_with_mode(mode);
                 }
@@ -1246,11 +1954,13 @@ impl NorthMailWindow {
                 let messages = list.imp().messages.borrow();
                 if let Some(msg) = messages.iter().find(|m| m.uid == uid) {
                     let from_for_quote = msg.from.clone();
-                    let to_for_quote: Vec<String> = msg.to.split(',')
-                        .map(|s| s.trim().to_string())
+                    let to_for_quote: Vec<String> = parse_address_list(&msg.to)
+                        .iter()
+                        .map(Address::to_header)
                         .collect();
                     let date_for_quote = msg.date.clone();
                     let subject_for_quote = msg.subject.clone();
+                    let msg_folder_id = if msg.folder_id != 0 { Some(msg.folder_id) } else { None };
                     let subject = if msg.subject.to_lowercase().starts_with("fwd:") {
                         msg.subject.clone()
                     } else {
@@ -1269,12 +1979,15 @@ impl NorthMailWindow {
                     } else {
                         (String::new(), Vec::new())
                     };
-                    let mode = ComposeMode::Forward {
-                        subject,
-                        quoted_body,
-                        attachments,
-                    };
-                    window.show_compose_dialog_with_mode(mode);
+                    let window_ref = window.clone();
+                    window.complete_attachments(attachments, uid, msg_folder_id, move |attachments| {
+                        let mode = ComposeMode::Forward {
+                            subject,
+                            quoted_body,
+                            attachments,
+                        };
+                        window_ref.show_compose_dialog_with_mode(mode);
+                    });
                 }
             }),
         );
@@ -1336,9 +2049,7 @@ impl NorthMailWindow {
                 list.remove_messages(&uids);
                 if let Some(app) = window.application() {
                     if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                        for (uid, msg_id, folder_id) in &items {
-                            app.move_to_spam(*msg_id, *uid, *folder_id);
-                        }
+                        app.move_messages_to_spam(&items);
                     }
                 }
                 window.add_toast(adw::Toast::new(&ntr("Marked 1 message as Spam", &format!("Marked {} messages as Spam", count), count as u32)));
@@ -1350,16 +2061,13 @@ impl NorthMailWindow {
         message_list.connect_closure(
             "bulk-mark-read",
             false,
-            glib::closure_local!(move |list: &MessageList, data: String, is_read: bool| {
+            glib::closure_local!(move |_list: &MessageList, data: String, is_read: bool| {
                 let items = parse_bulk_data(&data);
                 let count = items.len();
                 debug!("Bulk mark read: {} messages, is_read={}", count, is_read);
-                for (uid, msg_id, folder_id) in &items {
-                    list.update_message_read(*uid, is_read);
-                    if let Some(app) = window.application() {
-                        if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                            app.set_message_read(*msg_id, *uid, *folder_id, is_read);
-                        }
+                if let Some(app) = window.application() {
+                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                        app.set_messages_read(&items, is_read);
                     }
                 }
                 let label = if is_read { tr("read") } else { tr("unread") };
@@ -1376,16 +2084,13 @@ impl NorthMailWindow {
         message_list.connect_closure(
             "bulk-star",
             false,
-            glib::closure_local!(move |list: &MessageList, data: String, is_starred: bool| {
+            glib::closure_local!(move |_list: &MessageList, data: String, is_starred: bool| {
                 let items = parse_bulk_data(&data);
                 let count = items.len();
                 debug!("Bulk star: {} messages, is_starred={}", count, is_starred);
-                for (uid, msg_id, folder_id) in &items {
-                    list.update_message_starred(*uid, is_starred);
-                    if let Some(app) = window.application() {
-                        if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                            app.set_message_starred(*msg_id, *uid, *folder_id, is_starred);
-                        }
+                if let Some(app) = window.application() {
+                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                        app.set_messages_starred(&items, is_starred);
                     }
                 }
                 if is_starred {
@@ -1430,7 +2135,9 @@ impl NorthMailWindow {
 
             // Track the currently displayed message
             *imp.current_message_uid.borrow_mut() = Some(uid);
+            *imp.current_message_id.borrow_mut() = msg.message_id.clone();
             *imp.current_body_text.borrow_mut() = None;
+            self.stop_reading();
             *imp.current_attachments.borrow_mut() = Vec::new();
 
             // Auto-mark as read after 2 seconds if currently unread
@@ -1438,14 +2145,11 @@ impl NorthMailWindow {
                 let window = self.clone();
                 let msg_id = msg.id;
                 let folder_id = msg.folder_id;
-                let list = message_list.clone();
                 let source_id = glib::timeout_add_local_once(
                     std::time::Duration::from_secs(2),
                     move || {
                         // Verify we're still showing the same message
                         if *window.imp().current_message_uid.borrow() == Some(uid) {
-                            list.update_message_read(uid, true);
-                            window.update_message_view_read(uid, true);
                             if let Some(app) = window.application() {
                                 if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                                     app.set_message_read(msg_id, uid, folder_id, true);
@@ -1537,6 +2241,7 @@ impl NorthMailWindow {
                         quoted_body: quoted,
                         in_reply_to: msg_clone.message_id.clone(),
                         references,
+                        addressed_to: addressed_to(&msg_clone.to, &msg_clone.cc),
                     };
                     window.show_compose_dialog_with_mode(mode);
                 });
@@ -1560,15 +2265,9 @@ impl NorthMailWindow {
                     let reply_to_display = msg_clone.from.clone();
                     // For reply-all, include the sender as primary To
                     let to_addrs = vec![(reply_to_email.clone(), reply_to_display)];
-                    // Parse additional recipients from the To field (comma-separated)
-                    let cc_addrs: Vec<(String, String)> = msg_clone.to
-                        .split(',')
-                        .map(|s| {
-                            let email = extract_email_address(s.trim());
-                            (email.clone(), email) // email as both display and address
-                        })
-                        .filter(|(e, _)| !e.is_empty() && e.contains('@') && e != &reply_to_email)
-                        .collect();
+                    // Other To and Cc recipients go to Cc
+                    let mut cc_addrs = reply_recipients(&msg_clone.to, &reply_to_email);
+                    cc_addrs.extend(reply_recipients(&msg_clone.cc, &reply_to_email));
 
                     let subject = if msg_clone.subject.to_lowercase().starts_with("re:") {
                         msg_clone.subject.clone()
@@ -1584,6 +2283,7 @@ impl NorthMailWindow {
                         quoted_body: quoted,
                         in_reply_to: msg_clone.message_id.clone(),
                         references,
+                        addressed_to: addressed_to(&msg_clone.to, &msg_clone.cc),
                     };
                     window.show_compose_dialog_with_mode(mode);
                 });
@@ -1604,9 +2304,9 @@ impl NorthMailWindow {
                     } else {
                         format!("Fwd: {}", msg_clone.subject)
                     };
-                    let to_list: Vec<String> = msg_clone.to
-                        .split(',')
-                        .map(|s| s.trim().to_string())
+                    let to_list: Vec<String> = parse_address_list(&msg_clone.to)
+                        .iter()
+                        .map(Address::to_header)
                         .collect();
                     let quoted = format_forward_body(&msg_clone.from, &to_list, &msg_clone.date, &msg_clone.subject, &body);
 
@@ -1631,18 +2331,23 @@ impl NorthMailWindow {
                         let subject_ref = subject.clone();
                         let quoted_ref = quoted.clone();
                         let attachments_ref = stored_attachments.clone();
+                        let msg_uid = msg_clone.uid;
+                        let msg_folder_id = if msg_clone.folder_id != 0 { Some(msg_clone.folder_id) } else { None };
                         dialog.choose(window.upcast_ref::<gtk4::Window>(), None::<&gio::Cancellable>, move |response| {
                             let attachments = if response == "yes" {
                                 attachments_ref.clone()
                             } else {
                                 Vec::new()
                             };
-                            let mode = ComposeMode::Forward {
-                                subject: subject_ref.clone(),
-                                quoted_body: quoted_ref.clone(),
-                                attachments,
-                            };
-                            window_ref.show_compose_dialog_with_mode(mode);
+                            let window_compose = window_ref.clone();
+                            window_ref.complete_attachments(attachments, msg_uid, msg_folder_id, move |attachments| {
+                                let mode = ComposeMode::Forward {
+                                    subject: subject_ref.clone(),
+                                    quoted_body: quoted_ref.clone(),
+                                    attachments,
+                                };
+                                window_compose.show_compose_dialog_with_mode(mode);
+                            });
                         });
                     } else {
                         // No attachments, forward directly
@@ -1703,37 +2408,17 @@ impl NorthMailWindow {
                         }
                     };
 
-                    // Parse To addresses, removing placeholder (sender's own email)
-                    let sender_email = if let Some(app) = window.application() {
-                        if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
-                            let accs = app.imp().accounts.borrow();
-                            accs.first().map(|a| a.email.clone()).unwrap_or_default()
-                        } else {
-                            String::new()
-                        }
-                    } else {
-                        String::new()
-                    };
-
-                    let to: Vec<String> = msg_clone.to
-                        .split(',')
-                        .map(|s| extract_email_address(s.trim()))
-                        .filter(|e| !e.is_empty() && e != &sender_email)
-                        .collect();
-
-                    // For now, CC is not stored in message view - would need to parse from raw headers
-                    let cc: Vec<String> = Vec::new();
-
                     // Determine account index by matching the draft's From address to accounts
+                    // and their identities
                     // Fall back to the currently selected account if from is empty (Graph API drafts)
+                    let from_email = extract_email_address(&msg_clone.from);
                     let account_index = if let Some(app) = window.application() {
                         if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                             let accs = app.imp().accounts.borrow();
-                            let from_email = extract_email_address(&msg_clone.from);
                             if !from_email.is_empty() {
-                                accs.iter()
-                                    .position(|a| a.email.eq_ignore_ascii_case(&from_email))
-                                    .unwrap_or(0) as u32
+                                app.addressed_sender(std::slice::from_ref(&from_email))
+                                    .map(|(index, _)| index)
+                                    .unwrap_or(0)
                             } else if let Some(acct_email) = app.current_account_email() {
                                 accs.iter()
                                     .position(|a| a.email.eq_ignore_ascii_case(&acct_email))
@@ -1742,17 +2427,47 @@ impl NorthMailWindow {
                         } else { 0 }
                     } else { 0 };
 
-                    let draft_attachments = attachments_for_edit.borrow().clone();
-                    let mode = ComposeMode::EditDraft {
-                        to,
-                        cc,
-                        subject: msg_clone.subject.clone(),
-                        body,
-                        attachments: draft_attachments,
-                        draft_uid: msg_clone.uid,
-                        account_index,
+                    // Parse recipients, removing placeholder (sender's own email)
+                    let sender_email = if !from_email.is_empty() {
+                        from_email.clone()
+                    } else if let Some(app) = window.application() {
+                        if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                            let accs = app.imp().accounts.borrow();
+                            accs.get(account_index as usize).map(|a| a.email.clone()).unwrap_or_default()
+                        } else {
+                            String::new()
+                        }
+                    } else {
+                        String::new()
                     };
-                    window.show_compose_dialog_with_mode(mode);
+
+                    let to: Vec<String> = emails_in(&msg_clone.to)
+                        .into_iter()
+                        .filter(|e| !e.eq_ignore_ascii_case(&sender_email))
+                        .collect();
+                    let cc: Vec<String> = emails_in(&msg_clone.cc)
+                        .into_iter()
+                        .filter(|e| !e.eq_ignore_ascii_case(&sender_email))
+                        .collect();
+
+                    let draft_attachments = attachments_for_edit.borrow().clone();
+                    let msg_folder_id = if msg_clone.folder_id != 0 { Some(msg_clone.folder_id) } else { None };
+                    let subject = msg_clone.subject.clone();
+                    let draft_uid = msg_clone.uid;
+                    let window_ref = window.clone();
+                    window.complete_attachments(draft_attachments, draft_uid, msg_folder_id, move |attachments| {
+                        let mode = ComposeMode::EditDraft {
+                            to,
+                            cc,
+                            subject,
+                            body,
+                            attachments,
+                            draft_uid,
+                            account_index,
+                            from: sender_email,
+                        };
+                        window_ref.show_compose_dialog_with_mode(mode);
+                    });
                 });
             }
 
@@ -1767,16 +2482,7 @@ impl NorthMailWindow {
                     if let Some(app) = window.application() {
                         if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                             app.archive_message(message_id, msg_uid, msg_folder_id);
-                            // Update message list by removing this message
-                            let imp = window.imp();
-                            if let Some(message_list) = imp.message_list.get() {
-                                message_list.remove_message(msg_uid);
-                            }
-                            // Clear message view
-                            while let Some(child) = imp.message_view_box.first_child() {
-                                imp.message_view_box.remove(&child);
-                            }
-                            *imp.current_message_uid.borrow_mut() = None;
+                            window.remove_message_and_advance(msg_uid);
                             window.add_toast(adw::Toast::new(&tr("Message archived")));
                         }
                     }
@@ -1813,16 +2519,7 @@ impl NorthMailWindow {
                                 if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                                     debug!("Calling delete_message");
                                     app.delete_message(message_id, msg_uid, msg_folder_id);
-                                    // Update message list by removing this message
-                                    let imp = window.imp();
-                                    if let Some(message_list) = imp.message_list.get() {
-                                        message_list.remove_message(msg_uid);
-                                    }
-                                    // Clear message view
-                                    while let Some(child) = imp.message_view_box.first_child() {
-                                        imp.message_view_box.remove(&child);
-                                    }
-                                    *imp.current_message_uid.borrow_mut() = None;
+                                    window.remove_message_and_advance(msg_uid);
                                     window.add_toast(adw::Toast::new(&tr("Message deleted")));
                                 }
                             }
@@ -1857,17 +2554,15 @@ impl NorthMailWindow {
                     button.set_icon_name(if is_starred { "starred-symbolic" } else { "non-starred-symbolic" });
                     let tip = if is_starred { tr("Unstar") } else { tr("Star") };
                     button.set_tooltip_text(Some(&tip));
-                    // Update database and IMAP via application
+                    if window.imp().applying_flag_changes.get() {
+                        return;
+                    }
+                    // Update database and IMAP via application; it updates every view
                     if let Some(app) = window.application() {
                         if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                             app.set_message_starred(message_id, msg_uid, msg_folder_id, is_starred);
                         }
                     }
-                    // Update the message list indicator
-                    let imp = window.imp();
-                    if let Some(message_list) = imp.message_list.get() {
-                        message_list.update_message_starred(msg_uid, is_starred);
-                    }
                 });
             }
 
@@ -1895,23 +2590,85 @@ impl NorthMailWindow {
                     button.set_icon_name(if new_read { "mail-unread-symbolic" } else { "mail-read-symbolic" });
                     let tip = if new_read { tr("Mark as Unread") } else { tr("Mark as Read") };
                     button.set_tooltip_text(Some(&tip));
-                    // Update database and IMAP via application
+                    // Update database and IMAP via application; it updates every view
                     if let Some(app) = window.application() {
                         if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                             app.set_message_read(message_id, msg_uid, msg_folder_id, new_read);
                         }
                     }
-                    // Update the message list indicator
-                    let imp = window.imp();
-                    if let Some(message_list) = imp.message_list.get() {
-                        message_list.update_message_read(msg_uid, new_read);
-                    }
+                });
+            }
+
+            // Read aloud: play/pause, with a stop button while reading
+            let read_aloud_button = gtk4::Button::builder()
+                .icon_name("audio-speakers-symbolic")
+                .tooltip_text(&tr("Read Aloud"))
+                .css_classes(["flat"])
+                .build();
+            let stop_reading_button = gtk4::Button::builder()
+                .icon_name("media-playback-stop-symbolic")
+                .tooltip_text(&tr("Stop Reading"))
+                .css_classes(["flat"])
+                .visible(false)
+                .build();
+            let reading = Rc::new(Cell::new(ReadAloudState::Idle));
+            {
+                let window = self.clone();
+                let msg_clone = msg.clone();
+                let body_text = body_text.clone();
+                let reading = reading.clone();
+                let stop_button = stop_reading_button.clone();
+                read_aloud_button.connect_clicked(move |button| {
+                    let next = match reading.get() {
+                        ReadAloudState::Idle => {
+                            let Some(body) = body_text.borrow().clone() else {
+                                window.add_toast(adw::Toast::new(&tr("Please wait for the message to load")));
+                                return;
+                            };
+                            let text = northmail_core::read_aloud::spoken_text(
+                                &tr("From"),
+                                &msg_clone.from,
+                                &tr("Subject"),
+                                &msg_clone.subject,
+                                &body,
+                            );
+                            if let Err(e) = window.start_reading(&text) {
+                                tracing::warn!("Read aloud failed: {}", e);
+                                window.add_toast(adw::Toast::new(&tr("Speech is not available. Is speech-dispatcher installed?")));
+                                return;
+                            }
+                            window.follow_reading(button, &stop_button, &reading);
+                            ReadAloudState::Speaking
+                        }
+                        ReadAloudState::Speaking => {
+                            window.with_speech(|speech| speech.pause());
+                            ReadAloudState::Paused
+                        }
+                        ReadAloudState::Paused => {
+                            window.with_speech(|speech| speech.resume());
+                            ReadAloudState::Speaking
+                        }
+                    };
+                    reading.set(next);
+                    set_read_aloud_controls(button, &stop_button, next);
+                });
+            }
+            {
+                let window = self.clone();
+                let reading = reading.clone();
+                let play_button = read_aloud_button.clone();
+                stop_reading_button.connect_clicked(move |button| {
+                    window.stop_reading();
+                    reading.set(ReadAloudState::Idle);
+                    set_read_aloud_controls(&play_button, button, ReadAloudState::Idle);
                 });
             }
 
             // Star and read on left, actions on right
             toolbar.append(&star_button);
             toolbar.append(&read_button);
+            toolbar.append(&read_aloud_button);
+            toolbar.append(&stop_reading_button);
             if is_drafts {
                 toolbar.append(&edit_button);
             }
@@ -1969,39 +2726,12 @@ impl NorthMailWindow {
                 .and_then(|app| app.downcast_ref::<NorthMailApplication>().cloned());
             let contact_photo = app_ref.as_ref()
                 .and_then(|app| app.get_contact_photo(&from_email));
-            let (avatar, favicon_slot) = create_avatar(&from_name, &from_email, contact_photo.as_deref());
+            let (avatar, image_slot) = create_avatar(&from_name, &from_email, contact_photo.as_deref());
             sender_row.append(&avatar);
 
-            // If no contact photo, try fetching a domain favicon
-            if let (Some((drawing_area, slot)), Some(app)) = (favicon_slot, app_ref) {
-                if let Some(domain) = from_email.rsplit('@').next().map(base_domain) {
-                    let da = drawing_area.clone();
-                    let slot_cb = slot.clone();
-                    app.fetch_favicon_async(domain, move |png_bytes| {
-                        if let Some(bytes) = png_bytes {
-                            let gbytes = glib::Bytes::from(&bytes);
-                            if let Ok(tex) = gtk4::gdk::Texture::from_bytes(&gbytes) {
-                                let tw = tex.width();
-                                let th = tex.height();
-                                let stride = gtk4::cairo::Format::ARgb32
-                                    .stride_for_width(tw as u32)
-                                    .unwrap_or(tw * 4);
-                                let mut pixel_data = vec![0u8; (stride * th) as usize];
-                                tex.download(&mut pixel_data, stride as usize);
-                                if let Ok(surface) = gtk4::cairo::ImageSurface::create_for_data(
-                                    pixel_data,
-                                    gtk4::cairo::Format::ARgb32,
-                                    tw,
-                                    th,
-                                    stride,
-                                ) {
-                                    *slot_cb.borrow_mut() = Some(surface);
-                                    da.queue_draw();
-                                }
-                            }
-                        }
-                    });
-                }
+            // If no contact photo, look up a sender image
+            if let (Some(image_slot), Some(app)) = (image_slot, app_ref) {
+                load_sender_image(&app, &from_email, image_slot);
             }
 
             // Sender name and email (clickable to compose)
@@ -2189,19 +2919,19 @@ impl NorthMailWindow {
                 } else {
                     None
                 };
-                let filtered: Vec<&str> = msg.to.split(',')
-                    .map(|s| s.trim())
-                    .filter(|s| {
+                let filtered: Vec<Address> = parse_address_list(&msg.to)
+                    .into_iter()
+                    .filter(|a| {
                         if let Some(ref acct) = account_email {
-                            !extract_email_address(s).eq_ignore_ascii_case(acct)
+                            !a.email.eq_ignore_ascii_case(acct)
                         } else {
                             true
                         }
                     })
                     .collect();
-                if filtered.is_empty() { String::new() } else { filtered.join(", ") }
+                format_address_list(&filtered)
             } else {
-                msg.to.clone()
+                format_address_list(&parse_address_list(&msg.to))
             };
             let to_row = gtk4::Box::builder()
                 .orientation(gtk4::Orientation::Horizontal)
@@ -2258,7 +2988,7 @@ impl NorthMailWindow {
                     .build();
 
                 let cc_value = gtk4::Label::builder()
-                    .label(&msg.cc)
+                    .label(&format_address_list(&parse_address_list(&msg.cc)))
                     .css_classes(["message-recipients-value"])
                     .xalign(0.0)
                     .hexpand(true)
@@ -2272,6 +3002,39 @@ impl NorthMailWindow {
                 header_content.append(&cc_row);
             }
 
+            // Read receipt, for a sent message that asked for one
+            if let Some(message_id) = msg.message_id.as_deref() {
+                let receipt_label = gtk4::Label::builder()
+                    .css_classes(["message-recipients-value"])
+                    .xalign(0.0)
+                    .margin_top(4)
+                    .visible(false)
+                    .build();
+                header_content.append(&receipt_label);
+                if let Some(app) = self.application() {
+                    if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                        app.requested_receipt(message_id, move |receipt| {
+                            let Some(receipt) = receipt else {
+                                return;
+                            };
+                            let text = match (receipt.returned_at, receipt.disposition.as_deref()) {
+                                (Some(returned_at), Some("displayed")) => tr("Read receipt: opened {}").replace(
+                                    "{}",
+                                    &glib::DateTime::from_unix_local(returned_at)
+                                        .and_then(|dt| dt.format("%x %X"))
+                                        .map(|s| s.to_string())
+                                        .unwrap_or_default(),
+                                ),
+                                (Some(_), _) => tr("Read receipt: not opened"),
+                                (None, _) => tr("Read receipt requested, none returned yet"),
+                            };
+                            receipt_label.set_label(&text);
+                            receipt_label.set_visible(true);
+                        });
+                    }
+                }
+            }
+
             // Subject row with attachment indicator on right
             let subject_row = gtk4::Box::builder()
                 .orientation(gtk4::Orientation::Horizontal)
@@ -2352,9 +3115,11 @@ impl NorthMailWindow {
             let body_text_for_fetch = body_text.clone();
             let attachments_data_for_fetch = attachments_data.clone();
             let window_for_fetch = self.clone();
+            let msg_for_fetch = msg.clone();
             if let Some(app) = self.application() {
                 if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
                     let msg_folder_id = if msg.folder_id != 0 { Some(msg.folder_id) } else { None };
+                    let app_for_fetch = app.clone();
                     app.fetch_message_body(uid, msg_folder_id, move |result| {
                         // Guard: skip if user has already navigated to a different message
                         if *window_for_fetch.imp().current_message_uid.borrow() != Some(uid) {
@@ -2369,7 +3134,9 @@ impl NorthMailWindow {
 
                         match result {
                             Ok(parsed) => {
+                                let receipt_request = parsed.receipt_request.clone();
                                 Self::display_parsed_body(&body_box_ref, &attachment_box_ref, &body_text_for_fetch, &attachments_data_for_fetch, &window_for_fetch, parsed, uid, msg_folder_id);
+                                app_for_fetch.handle_receipt_request(&msg_for_fetch, receipt_request);
                             }
                             Err(e) => {
                                 debug!("Failed to fetch body: {}, auto-retrying...", e);
@@ -2433,6 +3200,50 @@ impl NorthMailWindow {
         }
     }
 
+    /// Download any attachments that were left on the server, then call `f`
+    /// with the complete list. Attachments that fail to download are dropped.
+    fn complete_attachments(
+        &self,
+        attachments: Vec<(String, String, Vec<u8>)>,
+        uid: u32,
+        msg_folder_id: Option<i64>,
+        f: impl FnOnce(Vec<(String, String, Vec<u8>)>) + 'static,
+    ) {
+        if attachments.iter().all(|(_, _, data)| !data.is_empty()) {
+            f(attachments);
+            return;
+        }
+        let Some(app) = self.application().and_then(|app| app.downcast::<NorthMailApplication>().ok()) else {
+            return;
+        };
+
+        self.add_toast(adw::Toast::new(&tr("Downloading attachments…")));
+        let window = self.clone();
+        glib::spawn_future_local(async move {
+            let mut attachments = attachments;
+            let mut failed = Vec::new();
+            for (filename, _, data) in attachments.iter_mut().filter(|(_, _, data)| data.is_empty()) {
+                let (tx, rx) = futures::channel::oneshot::channel();
+                app.fetch_attachment_data(uid, msg_folder_id, filename, None, move |result| {
+                    let _ = tx.send(result);
+                });
+                match rx.await {
+                    Ok(Ok(bytes)) => *data = bytes,
+                    Ok(Err(e)) => {
+                        tracing::warn!("Failed to download attachment '{}': {}", filename, e);
+                        failed.push(filename.clone());
+                    }
+                    Err(_) => failed.push(filename.clone()),
+                }
+            }
+            if !failed.is_empty() {
+                attachments.retain(|(filename, _, _)| !failed.contains(filename));
+                window.add_toast(adw::Toast::new(&tr("Some attachments could not be downloaded")));
+            }
+            f(attachments);
+        });
+    }
+
     /// Display parsed email body content in the body box
     fn display_parsed_body(
         body_box: &gtk4::Box,
@@ -2441,8 +3252,8 @@ impl NorthMailWindow {
         attachments_store: &Rc<std::cell::RefCell<Vec<(String, String, Vec<u8>)>>>,
         window: &Self,
         parsed: ParsedEmailBody,
-        _uid: u32,
-        _msg_folder_id: Option<i64>,
+        uid: u32,
+        msg_folder_id: Option<i64>,
     ) {
         // Store plain text for reply/forward
         let plain_text = if let Some(ref text) = parsed.text {
@@ -2458,9 +3269,41 @@ impl NorthMailWindow {
             .map(|a| (a.filename.clone(), a.mime_type.clone(), a.data.clone()))
             .collect();
         *attachments_store.borrow_mut() = stored.clone();
-        *window.imp().current_body_text.borrow_mut() = Some(plain_text);
+        *window.imp().current_body_text.borrow_mut() = Some(plain_text.clone());
         *window.imp().current_attachments.borrow_mut() = stored;
 
+        Self::render_parsed_body(body_box, attachment_box, attachments_store, window, parsed, &plain_text, uid, msg_folder_id);
+    }
+
+    /// Fill the body box and attachment menu with a parsed body. Unlike
+    /// [`Self::display_parsed_body`], this leaves the message the window
+    /// shows alone, so other views, such as opened `.eml` files, use it too.
+    fn render_parsed_body(
+        body_box: &gtk4::Box,
+        attachment_box: &gtk4::Box,
+        attachments_store: &Rc<std::cell::RefCell<Vec<(String, String, Vec<u8>)>>>,
+        window: &Self,
+        parsed: ParsedEmailBody,
+        plain_text: &str,
+        uid: u32,
+        msg_folder_id: Option<i64>,
+    ) {
+        let translation_bar = window.translation_bar(plain_text);
+
+        let link_chips = window.link_previews(parsed.html.as_deref().or(parsed.text.as_deref()).unwrap_or_default());
+
+        // Order and shipment card from schema.org markup, above the body
+        if let Some(summary) = parsed.html.as_deref().and_then(northmail_core::structured_data::extract_summary) {
+            body_box.append(&summary_card(&summary));
+        }
+        // Meeting invitation, answerable when the message is in the cache
+        if let Some(invite) = &parsed.invite {
+            body_box.append(&window.invite_card(invite, uid, msg_folder_id));
+        }
+        if let Some(bar) = translation_bar {
+            body_box.append(&bar);
+        }
+
         if let Some(html) = parsed.html {
             #[cfg(feature = "webkit")]
             {
@@ -2571,31 +3414,20 @@ impl NorthMailWindow {
                 // Sanitize: strip <script> tags and inline JS event handlers from email HTML
                 // Our UserScript (click interceptor) still runs via UserContentManager
                 let sanitized_html = sanitize_email_html(&html);
+                // Colour quote levels by author, after sanitizing so our style survives
+                let quote_css = quote_colors_css(&quote_level_colors(plain_text));
                 eprintln!("[LINK] Loading HTML with JS click interceptor ({} bytes)", sanitized_html.len());
-                web_view.load_html(&sanitized_html, None);
+                web_view.set_zoom_level(text_scaling_factor());
+                web_view.load_html(&format!("{}{}{}", reading_css(), quote_css, sanitized_html), None);
                 body_box.append(&web_view);
             }
             #[cfg(not(feature = "webkit"))]
             {
                 let text = NorthMailApplication::strip_html_tags_public(&html);
-                let text_view = gtk4::TextView::builder()
-                    .editable(false)
-                    .cursor_visible(false)
-                    .wrap_mode(gtk4::WrapMode::Word)
-                    .vexpand(true)
-                    .build();
-                text_view.buffer().set_text(&text);
-                body_box.append(&text_view);
+                body_box.append(&quoted_text_view(&text));
             }
         } else if let Some(text) = parsed.text {
-            let text_view = gtk4::TextView::builder()
-                .editable(false)
-                .cursor_visible(false)
-                .wrap_mode(gtk4::WrapMode::Word)
-                .vexpand(true)
-                .build();
-            text_view.buffer().set_text(&text);
-            body_box.append(&text_view);
+            body_box.append(&quoted_text_view(&text));
         } else {
             let label = gtk4::Label::builder()
                 .label(&tr("No content available"))
@@ -2604,6 +3436,10 @@ impl NorthMailWindow {
             body_box.append(&label);
         }
 
+        if let Some(row) = link_preview_row(link_chips) {
+            body_box.append(&row);
+        }
+
         // Show attachment dropdown if any
         if !parsed.attachments.is_empty() {
             let count = parsed.attachments.len();
@@ -2638,7 +3474,14 @@ impl NorthMailWindow {
                 .width_request(360)
                 .build();
             for attachment in parsed.attachments {
-                let row = build_attachment_row(attachment);
+                let source = AttachmentSource {
+                    window: window.clone(),
+                    uid,
+                    msg_folder_id,
+                    section: attachment.section.clone(),
+                    store: attachments_store.clone(),
+                };
+                let row = build_attachment_row(attachment, source);
                 list_box.append(&row);
             }
             if count > 5 {
@@ -2659,6 +3502,372 @@ impl NorthMailWindow {
         }
     }
 
+    /// Link preview chips for a message body, from the enrichers turned on in settings
+    fn link_previews(&self, body: &str) -> Vec<northmail_core::link_preview::LinkChip> {
+        let mut enabled: Vec<String> = Vec::new();
+        if let Some(app) = self.application() {
+            if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                enabled = app.settings().strv("link-previews").iter().map(|id| id.to_string()).collect();
+            }
+        }
+        northmail_core::link_preview::preview_links(body, &enabled)
+    }
+
+    /// "Translate" bar for a message that looks like it's in another
+    /// language; the translation is shown in the bar, above the original
+    /// Card with what a meeting invitation is about, and buttons to answer
+    /// it when it asks for an answer and its message is in the cache
+    fn invite_card(&self, stored: &northmail_core::invite::StoredInvite, uid: u32, msg_folder_id: Option<i64>) -> gtk4::Box {
+        use northmail_core::invite::Rsvp;
+
+        let invite = &stored.invite;
+        let card = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(8)
+            .margin_bottom(12)
+            .css_classes(["card", "invite-card"])
+            .build();
+        let header = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(12)
+            .build();
+        header.append(
+            &gtk4::Image::builder()
+                .icon_name("x-office-calendar-symbolic")
+                .pixel_size(32)
+                .valign(gtk4::Align::Center)
+                .build(),
+        );
+
+        let text = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(2)
+            .hexpand(true)
+            .valign(gtk4::Align::Center)
+            .build();
+        let summary = invite.summary.clone().unwrap_or_else(|| tr("Meeting"));
+        let title = if invite.cancelled {
+            tr("Cancelled: {}").replace("{}", &summary)
+        } else {
+            summary
+        };
+        text.append(
+            &gtk4::Label::builder()
+                .label(&title)
+                .xalign(0.0)
+                .wrap(true)
+                .css_classes(["heading"])
+                .build(),
+        );
+
+        let format_time = |timestamp: i64, format: &str| {
+            glib::DateTime::from_unix_local(timestamp)
+                .and_then(|dt| dt.format(format))
+                .map(|s| s.to_string())
+                .unwrap_or_default()
+        };
+        let mut details = Vec::new();
+        match (invite.starts_at, invite.ends_at, invite.all_day) {
+            (Some(start), _, true) => details.push(format_time(start, "%x")),
+            (Some(start), Some(end), false) if end > start => {
+                let same_day = format_time(start, "%x") == format_time(end, "%x");
+                let end = if same_day { format_time(end, "%X") } else { format_time(end, "%x %X") };
+                details.push(format!("{} – {}", format_time(start, "%x %X"), end));
+            }
+            (Some(start), _, false) => details.push(format_time(start, "%x %X")),
+            (None, _, _) => {}
+        }
+        if let Some(location) = &invite.location {
+            details.push(location.clone());
+        }
+        if let Some(organizer) = &invite.organizer {
+            let name = organizer.name.as_deref().unwrap_or(&organizer.email);
+            details.push(tr("Organized by {}").replace("{}", name));
+        }
+        if !details.is_empty() {
+            text.append(
+                &gtk4::Label::builder()
+                    .label(&details.join(" · "))
+                    .xalign(0.0)
+                    .wrap(true)
+                    .selectable(true)
+                    .css_classes(["dim-label", "caption"])
+                    .build(),
+            );
+        }
+        header.append(&text);
+        card.append(&header);
+
+        let Some(folder_id) = msg_folder_id.filter(|_| invite.wants_reply()) else {
+            return card;
+        };
+        let app = match self.application().and_then(|app| app.downcast::<NorthMailApplication>().ok()) {
+            Some(app) => app,
+            None => return card,
+        };
+
+        let answer_label = |response: Option<Rsvp>| match response {
+            Some(Rsvp::Accepted) => tr("You accepted"),
+            Some(Rsvp::Declined) => tr("You declined"),
+            Some(Rsvp::Tentative) => tr("You said maybe"),
+            None => tr("Going?"),
+        };
+        let row = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(8)
+            .build();
+        let status = gtk4::Label::builder()
+            .label(&answer_label(stored.response))
+            .xalign(0.0)
+            .hexpand(true)
+            .css_classes(["dim-label"])
+            .build();
+        row.append(&status);
+        let buttons = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["linked"])
+            .valign(gtk4::Align::Center)
+            .build();
+        let choices = [
+            (Rsvp::Accepted, tr("Accept")),
+            (Rsvp::Tentative, tr("Maybe")),
+            (Rsvp::Declined, tr("Decline")),
+        ];
+        let toggles: Rc<Vec<(Rsvp, gtk4::ToggleButton)>> = Rc::new(
+            choices
+                .into_iter()
+                .map(|(response, label)| {
+                    let toggle = gtk4::ToggleButton::builder()
+                        .label(&label)
+                        .active(stored.response == Some(response))
+                        .build();
+                    buttons.append(&toggle);
+                    (response, toggle)
+                })
+                .collect(),
+        );
+        row.append(&buttons);
+        card.append(&row);
+
+        let answered = Rc::new(std::cell::Cell::new(stored.response));
+        for (response, toggle) in toggles.iter() {
+            let response = *response;
+            let toggles = toggles.clone();
+            let status = status.clone();
+            let app = app.clone();
+            let window = self.clone();
+            let invite = invite.clone();
+            let answered = answered.clone();
+            toggle.connect_clicked(move |toggle| {
+                // Clicking only sends; the toggles show what was sent
+                toggle.set_active(answered.get() == Some(response));
+                if answered.get() == Some(response) {
+                    return;
+                }
+                for (_, toggle) in toggles.iter() {
+                    toggle.set_sensitive(false);
+                }
+                let toggles = toggles.clone();
+                let status = status.clone();
+                let window = window.clone();
+                let answered = answered.clone();
+                let message_id = window.imp().current_message_id.borrow().clone();
+                app.respond_to_invite(folder_id, uid, message_id, invite.clone(), response, move |result| {
+                    match result {
+                        Ok(()) => {
+                            answered.set(Some(response));
+                            status.set_label(&answer_label(Some(response)));
+                        }
+                        Err(e) => window.add_toast(adw::Toast::new(
+                            &tr("Failed to answer the invitation: {}").replace("{}", &e),
+                        )),
+                    }
+                    for (choice, toggle) in toggles.iter() {
+                        toggle.set_active(answered.get() == Some(*choice));
+                        toggle.set_sensitive(true);
+                    }
+                });
+            });
+        }
+        card
+    }
+
+    fn translation_bar(&self, body: &str) -> Option<gtk4::Box> {
+        use northmail_core::translate;
+
+        let app = self.application()?.downcast::<NorthMailApplication>().ok()?;
+        app.translation_backend()?;
+        let target = app.translation_language();
+        if !translate::should_offer(body, &target) {
+            return None;
+        }
+        let text = translate::translatable_text(body);
+        let language_label = |code: Option<&str>| code.and_then(translate::language_name).map(tr);
+
+        let bar = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(8)
+            .margin_bottom(12)
+            .css_classes(["card", "translation-card"])
+            .build();
+        let header = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(8)
+            .build();
+        header.append(&gtk4::Image::from_icon_name("preferences-desktop-locale-symbolic"));
+        let title = match language_label(translate::detect_language(&text)) {
+            Some(language) => tr("This message seems to be in {}").replace("{}", &language),
+            None => tr("This message may be in another language"),
+        };
+        header.append(
+            &gtk4::Label::builder()
+                .label(&title)
+                .xalign(0.0)
+                .hexpand(true)
+                .wrap(true)
+                .css_classes(["dim-label"])
+                .build(),
+        );
+        let button = gtk4::Button::builder()
+            .label(&tr("Translate"))
+            .valign(gtk4::Align::Center)
+            .css_classes(["pill"])
+            .build();
+        header.append(&button);
+        bar.append(&header);
+
+        let result = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(4)
+            .visible(false)
+            .build();
+        bar.append(&result);
+
+        let window = self.clone();
+        button.connect_clicked(move |button| {
+            // Already translated: toggle it
+            if result.first_child().is_some() {
+                let show = !result.is_visible();
+                result.set_visible(show);
+                button.set_label(&if show { tr("Hide Translation") } else { tr("Show Translation") });
+                return;
+            }
+            button.set_sensitive(false);
+            button.set_label(&tr("Translating…"));
+            let button = button.clone();
+            let result = result.clone();
+            let window = window.clone();
+            app.translate_async(text.clone(), target.clone(), move |translation| {
+                button.set_sensitive(true);
+                match translation {
+                    Ok(translation) => {
+                        let caption = match language_label(translation.source_language.as_deref()) {
+                            Some(language) => tr("Translated from {}").replace("{}", &language),
+                            None => tr("Translated"),
+                        };
+                        result.append(
+                            &gtk4::Label::builder()
+                                .label(&caption)
+                                .xalign(0.0)
+                                .css_classes(["caption-heading"])
+                                .build(),
+                        );
+                        result.append(
+                            &gtk4::Label::builder()
+                                .label(&translation.text)
+                                .xalign(0.0)
+                                .wrap(true)
+                                .wrap_mode(gtk4::pango::WrapMode::WordChar)
+                                .selectable(true)
+                                .build(),
+                        );
+                        result.set_visible(true);
+                        button.set_label(&tr("Hide Translation"));
+                    }
+                    Err(e) => {
+                        tracing::warn!("Translation failed: {}", e);
+                        button.set_label(&tr("Translate"));
+                        window.add_toast(adw::Toast::new(&format!("{}: {}", tr("Translation failed"), e)));
+                    }
+                }
+            });
+        });
+        Some(bar)
+    }
+
+    /// Read `text` aloud, connecting to speech-dispatcher on first use
+    fn start_reading(&self, text: &str) -> std::io::Result<()> {
+        let mut speech = self.imp().speech.borrow_mut();
+        if speech.is_none() {
+            *speech = Some(crate::speech::Speech::connect()?);
+        }
+        let language = northmail_core::translate::detect_language(text);
+        let result = speech.as_mut().map_or(Ok(()), |s| s.speak(text, language));
+        if result.is_err() {
+            // Reconnect next time, e.g. after speech-dispatcher restarted
+            *speech = None;
+        }
+        result
+    }
+
+    /// Run a command on the speech connection, dropping it if it failed
+    fn with_speech(&self, f: impl FnOnce(&mut crate::speech::Speech) -> std::io::Result<()>) {
+        let mut speech = self.imp().speech.borrow_mut();
+        if let Some(s) = speech.as_mut() {
+            if let Err(e) = f(s) {
+                tracing::warn!("Read aloud: {}", e);
+                *speech = None;
+            }
+        }
+    }
+
+    /// Stop reading the current message aloud
+    fn stop_reading(&self) {
+        self.with_speech(|speech| speech.stop());
+    }
+
+    /// Keep the read-aloud controls in step with speech-dispatcher until
+    /// reading ends
+    fn follow_reading(&self, button: &gtk4::Button, stop_button: &gtk4::Button, state: &Rc<Cell<ReadAloudState>>) {
+        let window = self.downgrade();
+        let button = button.downgrade();
+        let stop_button = stop_button.downgrade();
+        let state = state.clone();
+        // An end event left over from a cancelled message comes before our start
+        let mut started = false;
+        glib::timeout_add_local(std::time::Duration::from_millis(250), move || {
+            let (Some(window), Some(button), Some(stop_button)) = (window.upgrade(), button.upgrade(), stop_button.upgrade()) else {
+                return glib::ControlFlow::Break;
+            };
+            if state.get() == ReadAloudState::Idle {
+                return glib::ControlFlow::Break;
+            }
+            let speech = window.imp().speech.borrow();
+            let Some(speech) = speech.as_ref() else {
+                state.set(ReadAloudState::Idle);
+                set_read_aloud_controls(&button, &stop_button, ReadAloudState::Idle);
+                return glib::ControlFlow::Break;
+            };
+            while let Some(event) = speech.try_event() {
+                started |= event == crate::speech::SpeechEvent::Started;
+                if !started {
+                    continue;
+                }
+                let next = match event {
+                    crate::speech::SpeechEvent::Started | crate::speech::SpeechEvent::Resumed => ReadAloudState::Speaking,
+                    crate::speech::SpeechEvent::Paused => ReadAloudState::Paused,
+                    crate::speech::SpeechEvent::Ended => ReadAloudState::Idle,
+                };
+                state.set(next);
+                set_read_aloud_controls(&button, &stop_button, next);
+                if next == ReadAloudState::Idle {
+                    return glib::ControlFlow::Break;
+                }
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
     /// Show error state with a Retry button for body fetch failures
     fn show_body_error(
         body_box: &gtk4::Box,
@@ -2751,10 +3960,209 @@ impl NorthMailWindow {
             }
         });
 
-        error_box.append(&icon);
-        error_box.append(&label);
-        error_box.append(&retry_btn);
-        body_box.append(&error_box);
+        error_box.append(&icon);
+        error_box.append(&label);
+        error_box.append(&retry_btn);
+        body_box.append(&error_box);
+    }
+
+    /// Open `.eml` files dropped on the window, each in a viewer of its own
+    fn setup_message_file_drop(&self) {
+        let drop_target = gtk4::DropTarget::new(gtk4::gdk::FileList::static_type(), gtk4::gdk::DragAction::COPY);
+        let window = self.clone();
+        drop_target.connect_drop(move |target, value, _, _| {
+            // Messages dragged within the window aren't files to open
+            if target.current_drop().and_then(|drop| drop.drag()).is_some() {
+                return false;
+            }
+            let Ok(file_list) = value.get::<gtk4::gdk::FileList>() else {
+                return false;
+            };
+            let paths: Vec<_> = file_list.files().iter()
+                .filter_map(|file| file.path())
+                .filter(|path| northmail_core::eml::is_eml_path(path))
+                .collect();
+            for path in &paths {
+                window.show_message_file(path);
+            }
+            !paths.is_empty()
+        });
+        self.add_controller(drop_target);
+    }
+
+    /// Show a message saved as an `.eml` file, read-only, in a window of its
+    /// own. The message isn't added to any folder.
+    pub fn show_message_file(&self, path: &std::path::Path) {
+        let file_name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let opened = std::fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| match northmail_core::eml::parse(&data) {
+                Some(message) => Ok((data, message)),
+                None => Err(tr("Not an email message")),
+            });
+        let (data, message) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                tracing::warn!("Can't open {}: {}", path.display(), e);
+                self.add_toast(adw::Toast::new(
+                    &tr("Can't open {file}: {error}").replace("{file}", &file_name).replace("{error}", &e),
+                ));
+                return;
+            }
+        };
+        let header = &message.header;
+        let subject = header.subject.clone()
+            .filter(|subject| !subject.trim().is_empty())
+            .unwrap_or_else(|| tr("(No Subject)"));
+
+        let viewer = adw::Window::builder()
+            .title(&subject)
+            .default_width(720)
+            .default_height(640)
+            .transient_for(self)
+            .build();
+        let toolbar_view = adw::ToolbarView::new();
+        let header_bar = adw::HeaderBar::new();
+        header_bar.set_title_widget(Some(&adw::WindowTitle::new(&subject, &file_name)));
+        toolbar_view.add_top_bar(&header_bar);
+
+        let content = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(0)
+            .vexpand(true)
+            .css_classes(["message-view-content"])
+            .build();
+        let header_card = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .css_classes(["message-header-card"])
+            .build();
+        let header_content = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(2)
+            .css_classes(["message-header-content"])
+            .build();
+
+        // Sender and date
+        let from_email = header.from_address.clone().unwrap_or_default();
+        let from_name = header.from_name.clone()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| from_email.clone());
+        let sender_row = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(12)
+            .build();
+        let sender_info = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .spacing(2)
+            .hexpand(true)
+            .build();
+        sender_info.append(&gtk4::Label::builder()
+            .label(&from_name)
+            .xalign(0.0)
+            .css_classes(["message-sender-name"])
+            .build());
+        if from_name != from_email {
+            sender_info.append(&gtk4::Label::builder()
+                .label(&from_email)
+                .xalign(0.0)
+                .css_classes(["message-sender-email"])
+                .build());
+        }
+        sender_row.append(&sender_info);
+        let date = header.date_epoch
+            .and_then(|epoch| glib::DateTime::from_unix_local(epoch).ok())
+            .and_then(|dt| dt.format("%x %X").ok())
+            .map(|s| s.to_string())
+            .or_else(|| header.date_sent.clone())
+            .unwrap_or_default();
+        sender_row.append(&gtk4::Label::builder()
+            .label(&date)
+            .css_classes(["message-date-small"])
+            .valign(gtk4::Align::Start)
+            .build());
+        header_content.append(&sender_row);
+
+        // Recipients
+        for (label, addresses) in [(tr("To:"), &header.to_addresses), (tr("Cc:"), &header.cc_addresses)] {
+            let Some(addresses) = addresses.as_deref().filter(|a| !a.is_empty()) else {
+                continue;
+            };
+            let row = gtk4::Box::builder()
+                .orientation(gtk4::Orientation::Horizontal)
+                .spacing(0)
+                .margin_top(4)
+                .build();
+            row.append(&gtk4::Label::builder()
+                .label(&format!("{} ", label))
+                .css_classes(["message-recipients-label"])
+                .xalign(0.0)
+                .build());
+            row.append(&gtk4::Label::builder()
+                .label(&format_address_list(&parse_address_list(addresses)))
+                .css_classes(["message-recipients-value"])
+                .xalign(0.0)
+                .hexpand(true)
+                .wrap(true)
+                .build());
+            header_content.append(&row);
+        }
+
+        // Subject, with the attachment menu on the right
+        let subject_row = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .spacing(8)
+            .margin_top(8)
+            .build();
+        subject_row.append(&gtk4::Label::builder()
+            .label(&subject)
+            .xalign(0.0)
+            .wrap(true)
+            .hexpand(true)
+            .css_classes(["message-subject-large"])
+            .build());
+        let attachment_box = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .halign(gtk4::Align::End)
+            .valign(gtk4::Align::End)
+            .spacing(4)
+            .build();
+        subject_row.append(&attachment_box);
+        header_content.append(&subject_row);
+        header_card.append(&header_content);
+        content.append(&header_card);
+
+        let body_scrolled = gtk4::ScrolledWindow::builder()
+            .vexpand(true)
+            .hexpand(true)
+            .css_classes(["message-body-scrolled"])
+            .build();
+        let body_box = gtk4::Box::builder()
+            .orientation(gtk4::Orientation::Vertical)
+            .margin_start(16)
+            .margin_end(16)
+            .margin_top(12)
+            .margin_bottom(12)
+            .css_classes(["message-body-box"])
+            .build();
+        body_scrolled.set_child(Some(&body_box));
+        content.append(&body_scrolled);
+
+        let parsed = NorthMailApplication::parse_email_body(&String::from_utf8_lossy(&data));
+        let plain_text = match (&parsed.text, &parsed.html) {
+            (Some(text), _) => text.clone(),
+            (None, Some(html)) => NorthMailApplication::strip_html_tags_public(html),
+            (None, None) => String::new(),
+        };
+        let attachments_store = Rc::new(RefCell::new(parsed.attachments.iter()
+            .map(|a| (a.filename.clone(), a.mime_type.clone(), a.data.clone()))
+            .collect::<Vec<_>>()));
+        Self::render_parsed_body(&body_box, &attachment_box, &attachments_store, self, parsed, &plain_text, 0, None);
+
+        toolbar_view.set_content(Some(&content));
+        viewer.set_content(Some(&toolbar_view));
+        viewer.present();
     }
 
     fn setup_actions(&self) {
@@ -2835,9 +4243,21 @@ impl NorthMailWindow {
         });
     }
 
-    fn show_compose_dialog_with_mode(&self, mode: ComposeMode) {
+    /// Start a message from a `mailto:` link
+    pub fn show_compose_dialog_mailto(&self, link: northmail_core::mailto::MailtoLink) {
+        self.show_compose_dialog_with_mode(ComposeMode::Mailto(link));
+    }
+
+    pub(crate) fn show_compose_dialog_with_mode(&self, mode: ComposeMode) {
         debug!("Opening compose window with mode");
 
+        // Refresh send history for recipient typo detection
+        if let Some(app) = self.application() {
+            if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                app.load_sent_domains();
+            }
+        }
+
         // Compose-specific CSS
         let css_provider = gtk4::CssProvider::new();
         css_provider.load_from_string(
@@ -2896,7 +4316,23 @@ impl NorthMailWindow {
             .css_classes(["suggested-action", "pill", "compose-send"])
             .build();
 
+        let send_later_button = gtk4::Button::builder()
+            .icon_name("alarm-symbolic")
+            .tooltip_text(&tr("Send Later…"))
+            .build();
+        send_button
+            .bind_property("sensitive", &send_later_button, "sensitive")
+            .sync_create()
+            .build();
+
+        let receipt_button = gtk4::ToggleButton::builder()
+            .icon_name("mail-read-symbolic")
+            .tooltip_text(&tr("Request Read Receipt"))
+            .build();
+
         header.pack_end(&send_button);
+        header.pack_end(&send_later_button);
+        header.pack_end(&receipt_button);
         toolbar_view.add_top_bar(&header);
 
         // Main content
@@ -2939,8 +4375,18 @@ impl NorthMailWindow {
             .visible(false)
             .build();
 
+        // Identity of the chosen account to send as, shown when it has more than one
+        let identity_model = gtk4::StringList::new(&[]);
+        let identity_dropdown = gtk4::DropDown::builder()
+            .model(&identity_model)
+            .css_classes(["flat"])
+            .tooltip_text(&tr("Send As"))
+            .visible(false)
+            .build();
+
         // Add from dropdown and warning to header
         header.pack_start(&from_dropdown);
+        header.pack_start(&identity_dropdown);
         header.pack_start(&warning_button);
 
         // --- Header fields (To, Cc, Subject) ---
@@ -2962,9 +4408,9 @@ impl NorthMailWindow {
             std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
 
         let all_chips = vec![to_chips.clone(), cc_chips.clone(), bcc_chips.clone()];
-        let (to_row, to_add_chip) = Self::build_chip_row(&tr("To"), to_chips.clone(), all_chips.clone(), self, label_width);
-        let (cc_row, cc_add_chip) = Self::build_chip_row(&tr("Cc"), cc_chips.clone(), all_chips.clone(), self, label_width);
-        let (bcc_row, _bcc_add_chip) = Self::build_chip_row(&tr("Bcc"), bcc_chips.clone(), all_chips.clone(), self, label_width);
+        let (to_row, to_add_chip, to_remove_chip) = Self::build_chip_row(&tr("To"), to_chips.clone(), all_chips.clone(), self, label_width);
+        let (cc_row, cc_add_chip, cc_remove_chip) = Self::build_chip_row(&tr("Cc"), cc_chips.clone(), all_chips.clone(), self, label_width);
+        let (bcc_row, bcc_add_chip, _bcc_remove_chip) = Self::build_chip_row(&tr("Bcc"), bcc_chips.clone(), all_chips.clone(), self, label_width);
 
         // Bcc row starts hidden
         bcc_row.set_visible(false);
@@ -3615,7 +5061,8 @@ impl NorthMailWindow {
         // Extract threading headers from mode for use in send
         let (reply_in_reply_to, reply_references) = match &mode {
             ComposeMode::Reply { in_reply_to, references, .. }
-            | ComposeMode::ReplyAll { in_reply_to, references, .. } => {
+            | ComposeMode::ReplyAll { in_reply_to, references, .. }
+            | ComposeMode::Outgoing { in_reply_to, references, .. } => {
                 (in_reply_to.clone(), references.clone())
             }
             _ => (None, Vec::new()),
@@ -3676,6 +5123,233 @@ impl NorthMailWindow {
                     ));
                 }
             }
+            ComposeMode::Mailto(link) => {
+                for email in &link.to {
+                    to_add_chip(email, email);
+                }
+                for email in &link.cc {
+                    cc_add_chip(email, email);
+                }
+                if !link.bcc.is_empty() {
+                    bcc_button.emit_clicked();
+                    for email in &link.bcc {
+                        bcc_add_chip(email, email);
+                    }
+                }
+                subject_entry.set_text(&link.subject);
+                text_view.buffer().set_text(&link.body);
+            }
+            ComposeMode::Outgoing { to, cc, bcc, subject, body, attachments: outgoing_atts, account_index, .. } => {
+                for email in to {
+                    to_add_chip(email, email);
+                }
+                for email in cc {
+                    cc_add_chip(email, email);
+                }
+                if !bcc.is_empty() {
+                    bcc_button.emit_clicked();
+                    for email in bcc {
+                        bcc_add_chip(email, email);
+                    }
+                }
+                subject_entry.set_text(subject);
+                text_view.buffer().set_text(body);
+                from_dropdown.set_selected(*account_index);
+                for (filename, mime_type, data) in outgoing_atts {
+                    attachments.borrow_mut().push((
+                        filename.clone(),
+                        mime_type.clone(),
+                        data.clone(),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        // Identities: reply as the address the message was sent to, list the
+        // chosen account's identities and keep the signature in step
+        let sender_app = self.application().and_downcast::<NorthMailApplication>();
+        let identities: Rc<RefCell<Vec<Identity>>> = Rc::new(RefCell::new(Vec::new()));
+        let signature: Rc<RefCell<Option<Signature>>> = Rc::new(RefCell::new(None));
+        // At the end of a new message, above what a reply or forward quotes
+        let placement = match &mode {
+            ComposeMode::New { .. } | ComposeMode::Mailto(_) => SignaturePlacement::End,
+            _ => SignaturePlacement::AboveQuote,
+        };
+        let fill_identities = {
+            let identities = identities.clone();
+            let identity_model = identity_model.clone();
+            let identity_dropdown = identity_dropdown.clone();
+            let sender_app = sender_app.clone();
+            Rc::new(move |account_index: u32| {
+                let list = sender_app
+                    .as_ref()
+                    .map(|app| app.sender_identities(account_index))
+                    .unwrap_or_default();
+                let labels: Vec<String> = list.iter().map(Identity::label).collect();
+                identity_dropdown.set_visible(list.len() > 1);
+                *identities.borrow_mut() = list;
+                let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+                identity_model.splice(0, identity_model.n_items(), &labels);
+            })
+        };
+        {
+            let sender = match &mode {
+                ComposeMode::Reply { addressed_to, .. } | ComposeMode::ReplyAll { addressed_to, .. } => {
+                    sender_app.as_ref().and_then(|app| app.addressed_sender(addressed_to))
+                }
+                ComposeMode::EditDraft { from, account_index, .. }
+                | ComposeMode::Outgoing { from, account_index, .. } => Some((*account_index, from.clone())),
+                _ => None,
+            };
+            if let Some((account_index, _)) = &sender {
+                from_dropdown.set_selected(*account_index);
+            }
+            fill_identities(from_dropdown.selected());
+            if let Some((_, email)) = &sender {
+                if let Some(pos) = identities.borrow().iter().position(|i| i.matches(email)) {
+                    identity_dropdown.set_selected(pos as u32);
+                }
+                // Don't reply to ourselves, unless we wrote the message
+                if let ComposeMode::ReplyAll { to, .. } = &mode {
+                    let ours: Vec<String> = to_chips
+                        .borrow()
+                        .iter()
+                        .chain(cc_chips.borrow().iter())
+                        .filter(|e| e.eq_ignore_ascii_case(email))
+                        .filter(|e| !to.first().is_some_and(|(author, _)| author.eq_ignore_ascii_case(e)))
+                        .cloned()
+                        .collect();
+                    for e in &ours {
+                        to_remove_chip(e);
+                        cc_remove_chip(e);
+                    }
+                }
+            }
+
+            // Drafts and outgoing messages have theirs already
+            let current = identities
+                .borrow()
+                .get(identity_dropdown.selected() as usize)
+                .and_then(|i| i.signature.clone());
+            if !matches!(mode, ComposeMode::EditDraft { .. } | ComposeMode::Outgoing { .. }) {
+                let buffer = text_view.buffer();
+                let (start, end) = buffer.bounds();
+                let body = buffer.text(&start, &end, false);
+                buffer.set_text(&insert_signature(&body, current.as_ref(), placement));
+                buffer.place_cursor(&buffer.start_iter());
+            }
+            *signature.borrow_mut() = current;
+        }
+        let apply_identity = {
+            let identities = identities.clone();
+            let signature = signature.clone();
+            let identity_dropdown = identity_dropdown.clone();
+            let buffer = text_view.buffer();
+            Rc::new(move || {
+                let new = identities
+                    .borrow()
+                    .get(identity_dropdown.selected() as usize)
+                    .and_then(|i| i.signature.clone());
+                let old = signature.replace(new.clone());
+                if old != new {
+                    swap_signature(&buffer, old.as_ref(), new.as_ref(), placement);
+                }
+            })
+        };
+        {
+            let apply_identity = apply_identity.clone();
+            identity_dropdown.connect_selected_notify(move |_| apply_identity());
+        }
+        {
+            let identity_dropdown = identity_dropdown.clone();
+            from_dropdown.connect_selected_notify(move |dropdown| {
+                fill_identities(dropdown.selected());
+                identity_dropdown.set_selected(0);
+                apply_identity();
+            });
+        }
+
+        // Reply-all on a long thread: offer to trim recipients who never wrote in it
+        if let ComposeMode::ReplyAll { subject, in_reply_to, .. } = &mode {
+            let banner = adw::Banner::builder()
+                .button_label(&tr("Trim…"))
+                .revealed(false)
+                .build();
+            content.prepend(&banner);
+
+            let recipients: Vec<String> = to_chips
+                .borrow()
+                .iter()
+                .chain(cc_chips.borrow().iter())
+                .cloned()
+                .collect();
+
+            if let Some(app) = self.application() {
+                if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                    let banner = banner.clone();
+                    let compose_win = compose_window.clone();
+                    let to_remove_chip = to_remove_chip.clone();
+                    let cc_remove_chip = cc_remove_chip.clone();
+                    let bcc_add_chip = bcc_add_chip.clone();
+                    let bcc_button = bcc_button.clone();
+                    app.suggest_recipient_trim(
+                        from_dropdown.selected(),
+                        subject.clone(),
+                        in_reply_to.clone(),
+                        recipients,
+                        move |inactive| {
+                            let count = inactive.len();
+                            banner.set_title(&ntr(
+                                "1 recipient hasn't taken part in this thread",
+                                &format!("{} recipients haven't taken part in this thread", count),
+                                count as u32,
+                            ));
+                            banner.set_revealed(true);
+
+                            let inactive = Rc::new(inactive);
+                            banner.connect_button_clicked(move |banner| {
+                                let dialog = adw::AlertDialog::builder()
+                                    .heading(&tr("Trim Recipients?"))
+                                    .body(&inactive.join("\n"))
+                                    .build();
+                                dialog.add_response("cancel", &tr("Cancel"));
+                                dialog.add_response("remove", &tr("Remove"));
+                                dialog.add_response("bcc", &tr("Move to Bcc"));
+                                dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+                                dialog.set_default_response(Some("bcc"));
+                                dialog.set_close_response("cancel");
+
+                                let banner = banner.clone();
+                                let inactive = inactive.clone();
+                                let to_remove_chip = to_remove_chip.clone();
+                                let cc_remove_chip = cc_remove_chip.clone();
+                                let bcc_add_chip = bcc_add_chip.clone();
+                                let bcc_button = bcc_button.clone();
+                                dialog.connect_response(None, move |_, response| {
+                                    if response != "remove" && response != "bcc" {
+                                        return;
+                                    }
+                                    for email in inactive.iter() {
+                                        to_remove_chip(email);
+                                        cc_remove_chip(email);
+                                    }
+                                    if response == "bcc" {
+                                        if bcc_button.is_visible() {
+                                            bcc_button.emit_clicked();
+                                        }
+                                        for email in inactive.iter() {
+                                            bcc_add_chip(email, email);
+                                        }
+                                    }
+                                    banner.set_revealed(false);
+                                });
+                                dialog.present(Some(&compose_win));
+                            });
+                        },
+                    );
+                }
+            }
         }
 
         toolbar_view.set_content(Some(&content));
@@ -3761,7 +5435,7 @@ impl NorthMailWindow {
                                 let _ = std::process::Command::new("xdg-open").arg(path).spawn();
                             } else {
                                 // Forwarded attachment - write to temp file first
-                                let temp_dir = std::env::temp_dir().join("northmail-attachments");
+                                let temp_dir = crate::profile::attachments_temp_dir();
                                 let _ = std::fs::create_dir_all(&temp_dir);
                                 let temp_path = temp_dir.join(sanitize_filename(&filename_for_open));
                                 if std::fs::write(&temp_path, &data_for_open).is_ok() {
@@ -3905,6 +5579,9 @@ impl NorthMailWindow {
         // If editing an existing draft, initialize with its info so we update it instead of creating new
         let initial_draft_state = match &mode {
             ComposeMode::EditDraft { draft_uid, account_index, .. } => Some((*account_index, *draft_uid)),
+            ComposeMode::Outgoing { draft_uid, account_index, .. } => {
+                draft_uid.map(|uid| (*account_index, uid))
+            }
             _ => None,
         };
         let draft_state: std::rc::Rc<std::cell::RefCell<Option<(u32, u32)>>> =
@@ -3929,6 +5606,8 @@ impl NorthMailWindow {
             let subject_entry_save = subject_entry.clone();
             let text_view_save = text_view.clone();
             let from_dropdown_save = from_dropdown.clone();
+            let identities_save = identities.clone();
+            let identity_dropdown_save = identity_dropdown.clone();
             let main_window = self.clone();
             let toast_overlay_save = toast_overlay.clone();
             let attachments_save = attachments.clone();
@@ -3953,6 +5632,8 @@ impl NorthMailWindow {
                 let subject_entry_timer = subject_entry_save.clone();
                 let text_view_timer = text_view_save.clone();
                 let from_dropdown_timer = from_dropdown_save.clone();
+                let identities_timer = identities_save.clone();
+                let identity_dropdown_timer = identity_dropdown_save.clone();
                 let main_window_timer = main_window.clone();
                 let toast_overlay_timer = toast_overlay_save.clone();
                 let attachments_timer = attachments_save.clone();
@@ -3993,21 +5674,19 @@ impl NorthMailWindow {
                     let Some(app) = main_window_timer.application() else { return };
                     let Some(app) = app.downcast_ref::<NorthMailApplication>() else { return };
 
-                    // Get account email for From
-                    let email = {
+                    // Get account email for From, or the identity picked
+                    let account_email = {
                         let accs = app.imp().accounts.borrow();
                         match accs.get(account_index as usize) {
                             Some(a) => a.email.clone(),
                             None => return,
                         }
                     };
-
-                    let real_name = glib::real_name().to_string_lossy().to_string();
-                    let from_name = if real_name.is_empty() || real_name == "Unknown" {
-                        None
-                    } else {
-                        Some(real_name)
-                    };
+                    let identity = identities_timer
+                        .borrow()
+                        .get(identity_dropdown_timer.selected() as usize)
+                        .cloned();
+                    let (email, from_name) = NorthMailApplication::sender(&account_email, identity.as_ref());
 
                     let mut msg = northmail_smtp::OutgoingMessage::new(&email, &subject);
                     if let Some(name) = from_name {
@@ -4056,7 +5735,7 @@ impl NorthMailWindow {
                                 .unwrap_or(false)
                         };
 
-                        if is_ms_graph {
+                        if is_ms_graph && old_acct == account_index {
                             eprintln!("[draft] Updating existing ms_graph draft uid={}", old_uid);
                             app_save.save_draft_update(account_index, msg, old_uid, move |result| {
                                 save_in_progress_cb.set(false);
@@ -4209,6 +5888,57 @@ impl NorthMailWindow {
         let timer_generation_send = timer_generation.clone();
         let attachments_send = attachments.clone();
         let bcc_chips_send = bcc_chips.clone();
+        let receipt_button_send = receipt_button.clone();
+        let identities_send = identities.clone();
+        let identity_dropdown_send = identity_dropdown.clone();
+        // Set once the user chose "Send Anyway" on the typo warning
+        let typo_confirmed = Rc::new(Cell::new(false));
+        // The answer to the size warning: whether to send large attachments
+        // as cloud links
+        let cloud_choice: Rc<Cell<Option<bool>>> = Rc::new(Cell::new(None));
+        // When to send the message, set by Send Later
+        let send_at: Rc<Cell<Option<i64>>> = Rc::new(Cell::new(None));
+
+        // Send Later: pick a time, then send as usual into the outbox
+        {
+            use northmail_core::snooze::SnoozePreset;
+
+            let send_btn = send_button.clone();
+            let send_at = send_at.clone();
+            let compose_win = compose_window.clone();
+            send_later_button.connect_clicked(move |_| {
+                let dialog = adw::AlertDialog::builder()
+                    .heading(&tr("Send Later"))
+                    .body(&tr("The message waits in the Outbox until then."))
+                    .close_response("cancel")
+                    .build();
+                dialog.add_response("cancel", &tr("Cancel"));
+                for preset in SnoozePreset::ALL {
+                    let (id, label) = match preset {
+                        SnoozePreset::LaterToday => ("later-today", tr("Later Today")),
+                        SnoozePreset::Tomorrow => ("tomorrow", tr("Tomorrow Morning")),
+                        SnoozePreset::NextWeek => ("next-week", tr("Next Week")),
+                    };
+                    dialog.add_response(id, &label);
+                }
+                dialog.set_default_response(Some("tomorrow"));
+
+                let send_btn = send_btn.clone();
+                let send_at = send_at.clone();
+                dialog.connect_response(None, move |_, response| {
+                    let preset = match response {
+                        "later-today" => SnoozePreset::LaterToday,
+                        "tomorrow" => SnoozePreset::Tomorrow,
+                        "next-week" => SnoozePreset::NextWeek,
+                        _ => return,
+                    };
+                    send_at.set(Some(preset.until(&chrono::Local::now())));
+                    send_btn.emit_clicked();
+                });
+                dialog.present(Some(&compose_win));
+            });
+        }
+
         send_button.connect_clicked(move |_| {
             let to_list = to_chips.borrow().clone();
             let cc_list = cc_chips.borrow().clone();
@@ -4232,17 +5962,151 @@ impl NorthMailWindow {
                 .collect();
 
             if to_list.is_empty() {
+                send_at.set(None);
                 if let Some(win) = window_ref.downcast_ref::<NorthMailWindow>() {
                     win.add_toast(adw::Toast::new(&tr("Please add at least one recipient")));
                 }
                 return;
             }
 
+            // Warn about likely domain typos (gmial.com) before sending
+            if !typo_confirmed.replace(false) {
+                let all_recipients: Vec<String> = to_list
+                    .iter()
+                    .chain(cc_list.iter())
+                    .chain(bcc_list.iter())
+                    .cloned()
+                    .collect();
+                let warnings = window_ref
+                    .application()
+                    .and_then(|app| {
+                        app.downcast_ref::<NorthMailApplication>()
+                            .map(|app| app.recipient_typo_warnings(&all_recipients))
+                    })
+                    .unwrap_or_default();
+
+                if !warnings.is_empty() {
+                    let lines: Vec<String> = warnings
+                        .iter()
+                        .map(|(address, suggestion)| {
+                            format!("{} \u{2192} {}?", address, suggestion)
+                        })
+                        .collect();
+                    let dialog = adw::AlertDialog::builder()
+                        .heading(&tr("Check Recipient Address?"))
+                        .body(&format!(
+                            "{}\n\n{}",
+                            tr("Some addresses look like they may contain a typo:"),
+                            lines.join("\n")
+                        ))
+                        .build();
+                    dialog.add_response("edit", &tr("Edit"));
+                    dialog.add_response("send", &tr("Send Anyway"));
+                    dialog.set_default_response(Some("edit"));
+                    dialog.set_close_response("edit");
+
+                    // Keep a Send Later time for the confirmed send
+                    let send_btn = send_btn_ref.clone();
+                    let confirmed = typo_confirmed.clone();
+                    let send_at = send_at.clone();
+                    let scheduled = send_at.take();
+                    dialog.connect_response(None, move |_, response| {
+                        if response == "send" {
+                            confirmed.set(true);
+                            send_at.set(scheduled);
+                            send_btn.emit_clicked();
+                        }
+                    });
+                    dialog.present(Some(&compose_win_ref));
+                    return;
+                }
+            }
+
             let account_index = from_dropdown.selected();
+            // The account's own address needs no identity
+            let identity = identities_send
+                .borrow()
+                .get(identity_dropdown_send.selected() as usize)
+                .filter(|i| i.id != 0)
+                .cloned();
+
+            // Offer to upload the attachments when they take the message
+            // over what the provider accepts
+            let cloud_links = match cloud_choice.take() {
+                Some(cloud_links) => cloud_links,
+                None => {
+                    let provider_type = window_ref
+                        .application()
+                        .and_then(|app| {
+                            app.downcast_ref::<NorthMailApplication>().and_then(|app| {
+                                app.imp()
+                                    .accounts
+                                    .borrow()
+                                    .get(account_index as usize)
+                                    .map(|a| a.provider_type.clone())
+                            })
+                        })
+                        .unwrap_or_default();
+                    let size = (body.len() + html_body.as_ref().map_or(0, String::len)) as u64
+                        + att_list
+                            .iter()
+                            .map(|(_, _, data)| cloud::encoded_size(data.len() as u64))
+                            .sum::<u64>();
+                    let limit = cloud::message_size_limit(&provider_type);
+
+                    if size > limit {
+                        let too_large = tr("With its attachments this message is about {size}, more than the {limit} your provider accepts, so it may be refused.")
+                            .replace("{size}", &glib::format_size(size))
+                            .replace("{limit}", &glib::format_size(limit));
+                        let dialog = adw::AlertDialog::builder()
+                            .heading(&tr("Message Too Large"))
+                            .build();
+                        dialog.add_response("edit", &tr("Edit"));
+                        dialog.add_response("send", &tr("Send Anyway"));
+                        match CloudStorage::for_provider(&provider_type) {
+                            Some(storage) => {
+                                dialog.set_body(&format!(
+                                    "{}\n\n{}",
+                                    too_large,
+                                    tr("Upload the largest attachments to {service} and send links to them instead?")
+                                        .replace("{service}", storage.name())
+                                ));
+                                dialog.add_response("upload", &tr("Upload and Link"));
+                                dialog.set_response_appearance("upload", adw::ResponseAppearance::Suggested);
+                                dialog.set_default_response(Some("upload"));
+                            }
+                            None => {
+                                dialog.set_body(&too_large);
+                                dialog.set_default_response(Some("edit"));
+                            }
+                        }
+                        dialog.set_close_response("edit");
+
+                        // Past the typo warning already; keep a Send Later time
+                        let send_btn = send_btn_ref.clone();
+                        let typo_confirmed = typo_confirmed.clone();
+                        let cloud_choice = cloud_choice.clone();
+                        let send_at = send_at.clone();
+                        let scheduled = send_at.take();
+                        dialog.connect_response(None, move |_, response| {
+                            if response == "send" || response == "upload" {
+                                typo_confirmed.set(true);
+                                cloud_choice.set(Some(response == "upload"));
+                                send_at.set(scheduled);
+                                send_btn.emit_clicked();
+                            }
+                        });
+                        dialog.present(Some(&compose_win_ref));
+                        return;
+                    }
+                    false
+                }
+            };
 
             // Invalidate any pending auto-save timer
             timer_generation_send.set(timer_generation_send.get().wrapping_add(1));
 
+            let scheduled_at = send_at.take();
             send_btn_ref.set_sensitive(false);
             send_btn_ref.set_label(&tr("Sending…"));
 
@@ -4254,28 +6118,52 @@ impl NorthMailWindow {
                     let was_sent_cb = was_sent_send.clone();
                     let draft_state_cb = draft_state_send.clone();
                     let app_for_delete = app.clone();
-                    app.send_message(
+
+                    // A saved ms_graph draft on the sending account is sent in
+                    // place, unless its attachments are to become links
+                    let graph_draft_uid = draft_state_send.borrow().filter(|_| !cloud_links).and_then(|(acct_idx, uid)| {
+                        let accs = app.imp().accounts.borrow();
+                        let is_ms_graph = accs
+                            .get(acct_idx as usize)
+                            .map(|a| a.provider_type == "ms_graph")
+                            .unwrap_or(false);
+                        (is_ms_graph && acct_idx == account_index).then_some(uid)
+                    });
+                    let request = SendRequest {
                         account_index,
-                        to_list,
-                        cc_list,
-                        bcc_list,
+                        identity,
+                        to: to_list,
+                        cc: cc_list,
+                        bcc: bcc_list,
                         subject,
                         body,
                         html_body,
-                        att_list,
-                        (*reply_in_reply_to).clone(),
-                        (*reply_references).clone(),
+                        attachments: att_list,
+                        in_reply_to: (*reply_in_reply_to).clone(),
+                        references: (*reply_references).clone(),
+                        draft_uid: graph_draft_uid,
+                        send_at: scheduled_at,
+                        request_receipt: receipt_button_send.is_active(),
+                        cloud_links,
+                    };
+                    app.send_message(
+                        request,
                         move |result| {
                             match result {
                                 Ok(()) => {
-                                    if let Some(win) = window_for_toast.downcast_ref::<NorthMailWindow>() {
-                                        win.add_toast(adw::Toast::new(&tr("Message sent")));
+                                    // The outbox reports when an unscheduled message went out
+                                    if scheduled_at.is_some() {
+                                        if let Some(win) = window_for_toast.downcast_ref::<NorthMailWindow>() {
+                                            win.add_toast(adw::Toast::new(&tr("Message scheduled")));
+                                        }
                                     }
                                     was_sent_cb.set(true);
 
-                                    // Delete draft if one was saved
+                                    // Delete draft if one was saved and not sent in place
                                     if let Some((acct_idx, uid)) = *draft_state_cb.borrow() {
-                                        app_for_delete.delete_draft(acct_idx, uid, |_| {});
+                                        if graph_draft_uid.is_none() {
+                                            app_for_delete.delete_draft(acct_idx, uid, |_| {});
+                                        }
                                     }
 
                                     compose_win_close.close();
@@ -4395,7 +6283,7 @@ impl NorthMailWindow {
         all_chips: Vec<std::rc::Rc<std::cell::RefCell<Vec<String>>>>,
         window: &NorthMailWindow,
         label_width: i32,
-    ) -> (gtk4::Box, Rc<dyn Fn(&str, &str)>) {
+    ) -> (gtk4::Box, Rc<dyn Fn(&str, &str)>, Rc<dyn Fn(&str)>) {
         let row = gtk4::Box::builder()
             .orientation(gtk4::Orientation::Horizontal)
             .spacing(6)
@@ -4541,6 +6429,25 @@ impl NorthMailWindow {
         };
         let add_chip_return = add_chip.clone();
 
+        // --- Remove chip helper (by email, matches the chip tooltip) ---
+        let remove_chip: Rc<dyn Fn(&str)> = {
+            let chip_flow = chip_flow.clone();
+            let chips = chips.clone();
+            Rc::new(move |email: &str| {
+                let mut child = chip_flow.first_child();
+                while let Some(widget) = child {
+                    child = widget.next_sibling();
+                    if widget.tooltip_text().as_deref() == Some(email) {
+                        chip_flow.remove(&widget);
+                    }
+                }
+                chips.borrow_mut().retain(|e| e != email);
+                if chip_flow.first_child().is_none() {
+                    chip_flow.set_visible(false);
+                }
+            })
+        };
+
         // Enter key → add manual entry
         let add_chip_enter = add_chip.clone();
         let popover_enter = popover.clone();
@@ -4573,7 +6480,7 @@ impl NorthMailWindow {
         // Track keyboard-highlighted row index (-1 = none)
         let kb_index: Rc<Cell<i32>> = Rc::new(Cell::new(-1));
 
-        // Instant autocomplete — filter preloaded contacts on every keystroke
+        // Autocomplete — suggest recipients and contacts on every keystroke
         let window_clone = window.clone();
         let popover_change = popover.clone();
         let suggestion_list_ref = suggestion_list.clone();
@@ -4595,8 +6502,13 @@ impl NorthMailWindow {
             let popover_cb = popover_change.clone();
             let list_cb = suggestion_list_ref.clone();
             let entry_ref = entry.clone();
+            let query = text.clone();
 
             app.query_contacts(text, move |results| {
+                // Typed on since; a newer query has the list
+                if entry_ref.text().as_str() != query {
+                    return;
+                }
                 while let Some(row) = list_cb.row_at_index(0) {
                     list_cb.remove(&row);
                 }
@@ -4768,7 +6680,7 @@ impl NorthMailWindow {
             }
         });
 
-        (row, add_chip_return)
+        (row, add_chip_return, remove_chip)
     }
 
     fn refresh_messages(&self) {
@@ -4810,32 +6722,20 @@ impl NorthMailWindow {
             };
             let app_clone = app.clone();
             glib::spawn_future_local(async move {
-                let (sender, receiver) = std::sync::mpsc::channel();
                 let fid = folder_id;
-                std::thread::spawn(move || {
-                    let rt = tokio::runtime::Runtime::new().unwrap();
-                    let result = if fid == -1 {
-                        rt.block_on(db.get_inbox_messages(100, 0))
+                let task = spawn_db(async move {
+                    if fid == -1 {
+                        db.unified_inbox(&Default::default()).await.map(|page| page.messages)
                     } else {
-                        rt.block_on(db.get_messages(fid, 100, 0))
-                    };
-                    let _ = sender.send(result);
-                });
-
-                let result = loop {
-                    match receiver.try_recv() {
-                        Ok(result) => break Some(result),
-                        Err(std::sync::mpsc::TryRecvError::Empty) => {
-                            glib::timeout_future(std::time::Duration::from_millis(10)).await;
-                        }
-                        Err(std::sync::mpsc::TryRecvError::Disconnected) => break None,
+                        db.get_messages(fid, 100, None).await
                     }
-                };
+                });
 
-                if let Some(Ok(messages)) = result {
+                if let Ok(Ok(messages)) = task.await {
                     let infos: Vec<crate::widgets::MessageInfo> =
                         messages.iter().map(crate::widgets::MessageInfo::from).collect();
                     app_clone.set_cache_offset(infos.len() as i64);
+                    app_clone.set_page_cursor(&infos);
                     if let Some(window) = app_clone.active_window() {
                         if let Some(win) = window.downcast_ref::<NorthMailWindow>() {
                             if let Some(message_list) = win.message_list() {
@@ -4846,38 +6746,43 @@ impl NorthMailWindow {
                 }
             });
         } else {
-            // Non-empty query: FTS search in current folder (or all inboxes)
+            // Queries with operators also go to the server; its results
+            // replace the local ones when they arrive
+            let has_operators = northmail_core::gmail::is_raw_query(query)
+                || northmail_core::search::SearchQuery::parse(query)
+                    .is_some_and(|parsed| !parsed.is_plain_words());
+            if folder_id > 0 && has_operators {
+                app.search_server(query);
+            }
+
+            // Non-empty query: FTS search in the current folder, all inboxes,
+            // or the starred messages shown
             let db = match app.database_ref() {
                 Some(db) => db.clone(),
                 None => return,
             };
             let query = query.to_string();
+            let starred_aid = app.starred_account_id();
+            let tag_id = app.tag_id();
             let app_clone = app.clone();
             glib::spawn_future_local(async move {
-                let (sender, receiver) = std::sync::mpsc::channel();
                 let fid = folder_id;
                 let q = query.clone();
-                std::thread::spawn(move || {
-                    let rt = tokio::runtime::Runtime::new().unwrap();
-                    let result = if fid == -1 {
-                        rt.block_on(db.search_inbox_messages(&q, 200))
-                    } else {
-                        rt.block_on(db.search_messages_in_folder(fid, &q, 200))
+                let task = spawn_db(async move {
+                    use northmail_core::models::SearchScope;
+                    let scope = match fid {
+                        -1 => SearchScope::Inbox,
+                        -2 => SearchScope::Starred,
+                        -3 => SearchScope::StarredInAccount(starred_aid.as_deref().unwrap_or("")),
+                        -4 => SearchScope::Snoozed,
+                        -5 => SearchScope::Tagged(tag_id),
+                        -6 => SearchScope::ReplyLater,
+                        _ => SearchScope::Folder(fid),
                     };
-                    let _ = sender.send(result);
+                    db.search_messages(&q, scope, 200).await
                 });
 
-                let result = loop {
-                    match receiver.try_recv() {
-                        Ok(result) => break Some(result),
-                        Err(std::sync::mpsc::TryRecvError::Empty) => {
-                            glib::timeout_future(std::time::Duration::from_millis(10)).await;
-                        }
-                        Err(std::sync::mpsc::TryRecvError::Disconnected) => break None,
-                    }
-                };
-
-                if let Some(Ok(messages)) = result {
+                if let Ok(Ok(messages)) = task.await {
                     let infos: Vec<crate::widgets::MessageInfo> =
                         messages.iter().map(crate::widgets::MessageInfo::from).collect();
                     debug!("FTS search '{}' returned {} results (unified={})", query, infos.len(), is_unified);
@@ -4956,13 +6861,78 @@ impl NorthMailWindow {
         }
     }
 
+    /// Bring the message list and the open message in line with flag
+    /// changes broadcast by the application
+    pub fn apply_flag_changes(&self, changes: &[FlagChange]) {
+        let current_folder_id = self
+            .application()
+            .and_then(|app| app.downcast_ref::<NorthMailApplication>().map(|app| app.cache_folder_id()))
+            .unwrap_or(0);
+        if let Some(message_list) = self.message_list() {
+            message_list.apply_flag_changes(changes, current_folder_id);
+        }
+
+        let imp = self.imp();
+        imp.applying_flag_changes.set(true);
+        for change in changes {
+            if let Some(is_read) = change.is_read {
+                self.update_message_view_read(change.uid, is_read);
+            }
+            if let Some(is_starred) = change.is_starred {
+                self.update_message_view_starred(change.uid, is_starred);
+            }
+        }
+        imp.applying_flag_changes.set(false);
+    }
+
     /// Clear the currently displayed message tracking (called when switching folders)
     pub fn clear_current_message(&self) {
         *self.imp().current_message_uid.borrow_mut() = None;
+        *self.imp().current_message_id.borrow_mut() = None;
         *self.imp().current_body_text.borrow_mut() = None;
+        self.stop_reading();
         *self.imp().current_attachments.borrow_mut() = Vec::new();
     }
 
+    /// Remove a message from the list after archive/delete/spam. If it was the
+    /// open message, follow the auto-advance preference: open the next or
+    /// previous message, or clear the view and go back to the list.
+    fn remove_message_and_advance(&self, uid: u32) {
+        let imp = self.imp();
+        let Some(message_list) = imp.message_list.get() else {
+            return;
+        };
+
+        if *imp.current_message_uid.borrow() != Some(uid) {
+            message_list.remove_message(uid);
+            return;
+        }
+
+        let mut mode = String::new();
+        if let Some(app) = self.application() {
+            if let Some(app) = app.downcast_ref::<NorthMailApplication>() {
+                mode = app.settings().string("auto-advance").to_string();
+            }
+        }
+        let target = match mode.as_str() {
+            "next" => message_list.adjacent_uid(uid, true),
+            "previous" => message_list.adjacent_uid(uid, false),
+            _ => None,
+        };
+
+        message_list.remove_message(uid);
+
+        // Clear message view
+        while let Some(child) = imp.message_view_box.first_child() {
+            imp.message_view_box.remove(&child);
+        }
+        self.clear_current_message();
+
+        if let Some(target) = target {
+            message_list.select_message(target);
+        }
+    }
+
     /// Show loading spinner in the message list area
     pub fn show_loading(&self) {
         self.show_loading_with_status(&tr("Connecting..."), None);
@@ -5081,12 +7051,23 @@ fn icon_for_mime_type(mime_type: &str) -> &'static str {
     }
 }
 
-fn build_attachment_row(attachment: ParsedAttachment) -> adw::ActionRow {
-    let data = Rc::new(attachment.data);
+/// Where to download an attachment from when its data was left on the server
+struct AttachmentSource {
+    window: NorthMailWindow,
+    uid: u32,
+    msg_folder_id: Option<i64>,
+    section: Option<String>,
+    /// Displayed message's attachments, updated once the data arrives
+    store: Rc<RefCell<Vec<(String, String, Vec<u8>)>>>,
+}
+
+fn build_attachment_row(attachment: ParsedAttachment, source: AttachmentSource) -> adw::ActionRow {
     let filename = attachment.filename;
     let mime_type = attachment.mime_type;
-    // Use stored size if data is empty (loaded from cache), otherwise use actual data length
-    let size = if data.is_empty() { attachment.size } else { data.len() };
+    // Use stored size if data is empty (not downloaded yet), otherwise use actual data length
+    let size = if attachment.data.is_empty() { attachment.size } else { attachment.data.len() };
+    let data = Rc::new(RefCell::new(Rc::new(attachment.data)));
+    let source = Rc::new(source);
 
     let row = adw::ActionRow::builder()
         .title(&filename)
@@ -5107,14 +7088,6 @@ fn build_attachment_row(attachment: ParsedAttachment) -> adw::ActionRow {
         .valign(gtk4::Align::Center)
         .build();
 
-    let data_open = data.clone();
-    let filename_open = filename.clone();
-    let open_btn_ref = open_btn.clone();
-    open_btn.connect_clicked(move |_| {
-        open_attachment(&filename_open, &data_open, &open_btn_ref);
-    });
-    row.add_suffix(&open_btn);
-
     // Suffix: Save button
     let save_btn = gtk4::Button::builder()
         .icon_name("document-save-symbolic")
@@ -5123,17 +7096,83 @@ fn build_attachment_row(attachment: ParsedAttachment) -> adw::ActionRow {
         .valign(gtk4::Align::Center)
         .build();
 
+    let data_open = data.clone();
+    let source_open = source.clone();
+    let filename_open = filename.clone();
+    let row_open = row.clone();
+    open_btn.connect_clicked(move |btn| {
+        let filename = filename_open.clone();
+        let btn_ref = btn.clone();
+        with_attachment_data(&row_open, &filename_open, &data_open, &source_open, move |data| {
+            open_attachment(&filename, &data, &btn_ref);
+        });
+    });
+    row.add_suffix(&open_btn);
+
     let data_save = data.clone();
+    let source_save = source.clone();
     let filename_save = filename.clone();
-    let save_btn_ref = save_btn.clone();
-    save_btn.connect_clicked(move |_| {
-        save_attachment(&filename_save, &data_save, &save_btn_ref);
+    let row_save = row.clone();
+    save_btn.connect_clicked(move |btn| {
+        let filename = filename_save.clone();
+        let btn_ref = btn.clone();
+        with_attachment_data(&row_save, &filename_save, &data_save, &source_save, move |data| {
+            save_attachment(&filename, &data, &btn_ref);
+        });
     });
     row.add_suffix(&save_btn);
 
     row
 }
 
+/// Run `f` with an attachment's data, downloading it first if it was left on
+/// the server. The row is insensitive while the download runs.
+fn with_attachment_data(
+    row: &adw::ActionRow,
+    filename: &str,
+    data: &Rc<RefCell<Rc<Vec<u8>>>>,
+    source: &Rc<AttachmentSource>,
+    f: impl FnOnce(Rc<Vec<u8>>) + 'static,
+) {
+    let current = data.borrow().clone();
+    if !current.is_empty() {
+        f(current);
+        return;
+    }
+
+    let Some(app) = source.window.application() else { return };
+    let Some(app) = app.downcast_ref::<NorthMailApplication>() else { return };
+
+    row.set_sensitive(false);
+    let row = row.clone();
+    let data = data.clone();
+    let source = source.clone();
+    let name = filename.to_string();
+    app.fetch_attachment_data(source.uid, source.msg_folder_id, filename, source.section.clone(), move |result| {
+        row.set_sensitive(true);
+        match result {
+            Ok(bytes) => {
+                // Remember the data for forwarding and later clicks
+                for entry in source.store.borrow_mut().iter_mut() {
+                    if entry.0 == name && entry.2.is_empty() {
+                        entry.2 = bytes.clone();
+                    }
+                }
+                if *source.window.imp().current_message_uid.borrow() == Some(source.uid) {
+                    *source.window.imp().current_attachments.borrow_mut() = source.store.borrow().clone();
+                }
+                let bytes = Rc::new(bytes);
+                *data.borrow_mut() = bytes.clone();
+                f(bytes);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to download attachment '{}': {}", name, e);
+                source.window.add_toast(adw::Toast::new(&tr("Failed to download attachment")));
+            }
+        }
+    });
+}
+
 /// Sanitize a filename from untrusted email content to prevent path traversal
 fn sanitize_filename(filename: &str) -> String {
     // Strip directory components and path traversal
@@ -5146,7 +7185,7 @@ fn sanitize_filename(filename: &str) -> String {
 }
 
 fn open_attachment(filename: &str, data: &Rc<Vec<u8>>, widget: &impl gtk4::prelude::IsA<gtk4::Widget>) {
-    let temp_dir = std::env::temp_dir().join("northmail-attachments");
+    let temp_dir = crate::profile::attachments_temp_dir();
     if std::fs::create_dir_all(&temp_dir).is_err() {
         tracing::warn!("Failed to create temp dir for attachment");
         return;
@@ -1,6 +1,6 @@
 //! Test both Gmail and iCloud accounts
 use northmail_auth::{AuthManager, GoaAuthType};
-use northmail_imap::{ImapClient, SimpleImapClient};
+use northmail_imap::ImapClient;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     async_std::task::block_on(async { run_test().await })
@@ -17,12 +17,12 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
         
         match account.auth_type {
             GoaAuthType::OAuth2 if account.provider_type == "google" => {
-                // Test Gmail with SimpleImapClient
+                // Test Gmail with ImapClient
                 match auth_manager.get_xoauth2_token_for_goa(&account.id).await {
                     Ok((email, token)) => {
                         println!("  Got OAuth2 token");
                         
-                        let mut client = SimpleImapClient::new();
+                        let mut client = ImapClient::new();
                         match client.connect_gmail(&email, &token).await {
                             Ok(_) => {
                                 println!("  Connected to Gmail!");
@@ -49,11 +49,11 @@ async fn run_test() -> Result<(), Box<dyn std::error::Error>> {
                         let host = account.imap_host.clone().unwrap_or("imap.mail.me.com".to_string());
                         let username = account.imap_username.clone().unwrap_or(account.email.clone());
                         
-                        let mut client = ImapClient::new(&host, 993);
-                        match client.authenticate_login(&username, &password).await {
+                        let mut client = ImapClient::new();
+                        match client.connect_login(&host, 993, &username, &password).await {
                             Ok(_) => {
                                 println!("  Connected to iCloud!");
-                                match client.select_folder("INBOX").await {
+                                match client.select("INBOX").await {
                                     Ok(folder) => {
                                         println!("  INBOX has {} messages", folder.message_count.unwrap_or(0));
                                     }
@@ -0,0 +1,259 @@
+//! Namespaces (RFC 2342) and access control lists (RFC 4314)
+//!
+//! `NAMESPACE` tells where a server keeps mailboxes other than the user's
+//! own: other users' mailboxes shared with them (e.g. `Other Users/`) and
+//! shared mailboxes (e.g. `Shared/`). `GETACL`/`SETACL` read and change who
+//! may do what in a mailbox; rights are single letters, e.g. `lrs` to see,
+//! read and mark messages seen.
+
+/// One namespace: mailboxes whose names start with `prefix`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespace {
+    /// Name prefix, usually ending in the delimiter; empty for the
+    /// personal namespace on most servers
+    pub prefix: String,
+    pub delimiter: Option<char>,
+}
+
+/// The three kinds of namespace a server can report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Namespaces {
+    /// The user's own mailboxes
+    pub personal: Vec<Namespace>,
+    /// Other users' mailboxes shared with this user
+    pub other_users: Vec<Namespace>,
+    /// Mailboxes shared between users
+    pub shared: Vec<Namespace>,
+}
+
+impl Namespaces {
+    /// Parse an untagged `* NAMESPACE (("" "/")) (("Other Users/" "/")) NIL` line
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("* NAMESPACE ")?;
+        let values = parse_values(rest);
+        let mut kinds = values.iter().map(|value| match value {
+            Value::List(entries) => entries.iter().filter_map(Namespace::from_value).collect(),
+            _ => Vec::new(),
+        });
+        Some(Self {
+            personal: kinds.next()?,
+            other_users: kinds.next()?,
+            shared: kinds.next()?,
+        })
+    }
+
+    /// Namespaces holding mailboxes of other users or shared ones, with a
+    /// non-empty prefix (an empty one would cover the user's own mailboxes)
+    pub fn shared_roots(&self) -> impl Iterator<Item = &Namespace> {
+        self.other_users
+            .iter()
+            .chain(&self.shared)
+            .filter(|ns| !ns.prefix.is_empty())
+    }
+
+    /// Whether `path` lies in another user's or a shared namespace
+    pub fn is_shared(&self, path: &str) -> bool {
+        self.shared_roots().any(|ns| ns.contains(path))
+    }
+}
+
+impl Namespace {
+    fn from_value(value: &Value) -> Option<Self> {
+        let Value::List(fields) = value else {
+            return None;
+        };
+        let prefix = fields.first()?.as_str()?.to_string();
+        let delimiter = fields.get(1).and_then(Value::as_str).and_then(|d| d.chars().next());
+        Some(Self { prefix, delimiter })
+    }
+
+    /// Mailbox path of the namespace itself, e.g. `Shared` for `Shared/`
+    pub fn root(&self) -> &str {
+        match self.delimiter {
+            Some(delimiter) => self.prefix.strip_suffix(delimiter).unwrap_or(&self.prefix),
+            None => &self.prefix,
+        }
+    }
+
+    /// Whether `path` is the namespace root or a mailbox under it
+    pub fn contains(&self, path: &str) -> bool {
+        path.starts_with(&self.prefix) || path == self.root()
+    }
+}
+
+/// Rights a mailbox grants, e.g. `lrswipkxtea`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rights(pub String);
+
+impl Rights {
+    /// Whether the right `right` is granted (`l` lookup, `r` read, `s` keep
+    /// seen, `w` write flags, `i` insert, `p` post, `k` create mailboxes,
+    /// `x` delete mailbox, `t` delete messages, `e` expunge, `a` administer)
+    pub fn has(&self, right: char) -> bool {
+        self.0.contains(right)
+    }
+
+    /// Messages can be listed and read
+    pub fn can_read(&self) -> bool {
+        self.has('l') && self.has('r')
+    }
+
+    /// Messages can be flagged, added or deleted, not just read
+    pub fn can_write(&self) -> bool {
+        ['s', 'w', 'i', 't'].iter().any(|&right| self.has(right))
+    }
+}
+
+/// One identifier's rights on a mailbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AclEntry {
+    /// User or group, e.g. `ann`, `anyone` or `$team`
+    pub identifier: String,
+    pub rights: Rights,
+}
+
+/// Parse an untagged `* ACL mailbox identifier rights ...` line
+pub fn parse_acl(line: &str) -> Option<Vec<AclEntry>> {
+    let rest = line.trim().strip_prefix("* ACL ")?;
+    let words: Vec<String> = parse_values(rest)
+        .iter()
+        .filter_map(|value| value.as_str().map(str::to_string))
+        .collect();
+    // The first word is the mailbox the list belongs to
+    let pairs = words.get(1..)?;
+    Some(
+        pairs
+            .chunks(2)
+            .filter_map(|pair| match pair {
+                [identifier, rights] => Some(AclEntry {
+                    identifier: identifier.clone(),
+                    rights: Rights(rights.clone()),
+                }),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Parse an untagged `* MYRIGHTS mailbox rights` line
+pub fn parse_my_rights(line: &str) -> Option<Rights> {
+    let rest = line.trim().strip_prefix("* MYRIGHTS ")?;
+    let values = parse_values(rest);
+    Some(Rights(values.get(1)?.as_str()?.to_string()))
+}
+
+/// An atom, quoted string, NIL, or parenthesized list
+#[derive(Debug)]
+enum Value {
+    Nil,
+    Str(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a sequence of space-separated values
+fn parse_values(text: &str) -> Vec<Value> {
+    let mut chars = text.chars().peekable();
+    parse_list(&mut chars)
+}
+
+fn parse_list(chars: &mut std::iter::Peekable<std::str::Chars>) -> Vec<Value> {
+    let mut values = Vec::new();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\r' | '\n' => {
+                chars.next();
+            }
+            ')' => {
+                chars.next();
+                break;
+            }
+            '(' => {
+                chars.next();
+                values.push(Value::List(parse_list(chars)));
+            }
+            '"' => {
+                chars.next();
+                let mut word = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(c),
+                    }
+                }
+                values.push(Value::Str(word));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if matches!(c, ' ' | '(' | ')' | '\r' | '\n') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if word.eq_ignore_ascii_case("NIL") {
+                    values.push(Value::Nil);
+                } else {
+                    values.push(Value::Str(word));
+                }
+            }
+        }
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_namespaces() {
+        let ns = Namespaces::parse(
+            "* NAMESPACE ((\"\" \"/\")) ((\"Other Users/\" \"/\")) ((\"Shared/\" \"/\" \"X-EXT\" (\"a\")))\r\n",
+        )
+        .unwrap();
+        assert_eq!(ns.personal, vec![Namespace { prefix: String::new(), delimiter: Some('/') }]);
+        assert_eq!(ns.other_users[0].prefix, "Other Users/");
+        assert_eq!(ns.shared[0].root(), "Shared");
+        assert_eq!(ns.shared_roots().count(), 2);
+        assert!(ns.is_shared("Shared/Team/Sent"));
+        assert!(ns.is_shared("Other Users"));
+        assert!(!ns.is_shared("INBOX/Shared"));
+
+        // Cyrus-style: no other-user namespace, "." delimiter
+        let ns = Namespaces::parse("* NAMESPACE ((\"INBOX.\" \".\")) NIL ((\"\" \".\"))").unwrap();
+        assert!(ns.other_users.is_empty());
+        assert_eq!(ns.shared_roots().count(), 0);
+
+        assert!(Namespaces::parse("* NAMESPACE ((\"\" \"/\"))").is_none());
+    }
+
+    #[test]
+    fn test_parse_acl() {
+        let acl = parse_acl("* ACL \"Shared/Team\" ann lrswipkxtecda \"$team\" lrs\r\n").unwrap();
+        assert_eq!(acl.len(), 2);
+        assert_eq!(acl[0].identifier, "ann");
+        assert!(acl[0].rights.has('a'));
+        assert_eq!(acl[1], AclEntry { identifier: "$team".to_string(), rights: Rights("lrs".to_string()) });
+        assert_eq!(parse_acl("* ACL INBOX"), Some(Vec::new()));
+        assert!(parse_acl("* MYRIGHTS INBOX lr").is_none());
+    }
+
+    #[test]
+    fn test_parse_my_rights() {
+        let rights = parse_my_rights("* MYRIGHTS \"Other Users/bob\" lr\r\n").unwrap();
+        assert!(rights.can_read() && !rights.can_write());
+        assert!(Rights("lrsw".to_string()).can_write());
+        assert!(parse_my_rights("* MYRIGHTS INBOX").is_none());
+    }
+}
@@ -0,0 +1,396 @@
+//! BODYSTRUCTURE parsing (RFC 3501 §7.4.2)
+//!
+//! Lets us fetch only the parts of a message needed for display
+//! (`BODY[n]` for the text/html parts) and leave attachments on the
+//! server until the user asks for them.
+
+/// A leaf MIME part of a message, as described by BODYSTRUCTURE
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BodyPart {
+    /// Part specifier for `BODY[...]`, e.g. `1`, `2.1`
+    pub section: String,
+    /// Lowercase `type/subtype`
+    pub mime_type: String,
+    /// Content-Type parameters (names lowercased)
+    pub params: Vec<(String, String)>,
+    /// Content-ID without angle brackets
+    pub content_id: Option<String>,
+    /// Lowercase Content-Transfer-Encoding
+    pub encoding: String,
+    /// Size in octets as transferred (before decoding)
+    pub size: usize,
+    /// Lowercase Content-Disposition type (`inline`, `attachment`)
+    pub disposition: Option<String>,
+    /// Content-Disposition parameters (names lowercased)
+    pub disposition_params: Vec<(String, String)>,
+}
+
+impl BodyPart {
+    /// Charset parameter of the Content-Type
+    pub fn charset(&self) -> Option<&str> {
+        param(&self.params, "charset")
+    }
+
+    /// Filename from Content-Disposition, falling back to the Content-Type name
+    pub fn filename(&self) -> Option<String> {
+        let raw = param(&self.disposition_params, "filename")
+            .or_else(|| param(&self.params, "name"))
+            .map(decode_encoded_words)
+            .or_else(|| {
+                param(&self.disposition_params, "filename*")
+                    .or_else(|| param(&self.params, "name*"))
+                    .map(decode_rfc2231)
+            })?;
+        let trimmed = raw.trim();
+        (!trimmed.is_empty()).then(|| trimmed.to_string())
+    }
+
+    /// Whether this part is the displayable text or HTML body
+    pub fn is_body_text(&self) -> bool {
+        (self.mime_type == "text/plain" || self.mime_type == "text/html")
+            && self.disposition.as_deref() != Some("attachment")
+            && param(&self.disposition_params, "filename").is_none()
+    }
+
+    /// Whether this part is the machine-readable half of a returned read
+    /// receipt (RFC 8098)
+    pub fn is_disposition_notification(&self) -> bool {
+        self.mime_type == "message/disposition-notification"
+    }
+
+    /// Whether this part is an iCalendar object, such as a meeting invitation
+    pub fn is_calendar(&self) -> bool {
+        self.mime_type == "text/calendar" || self.mime_type == "application/ics"
+    }
+
+    /// Whether this part is an inline resource referenced from the HTML (`cid:`)
+    pub fn is_inline_resource(&self) -> bool {
+        self.content_id.is_some()
+            && self.disposition.as_deref() != Some("attachment")
+            && self.mime_type.starts_with("image/")
+    }
+
+    /// Approximate decoded size, for display before the data is fetched
+    pub fn decoded_size(&self) -> usize {
+        if self.encoding == "base64" {
+            self.size / 4 * 3
+        } else {
+            self.size
+        }
+    }
+
+    /// Decode the raw bytes of this part (as returned by `BODY[section]`),
+    /// undoing the transfer encoding and, for text parts, the charset
+    pub fn decode(&self, raw: &[u8]) -> Vec<u8> {
+        let mut entity = format!("Content-Type: {}", self.mime_type);
+        if let Some(charset) = self.charset() {
+            entity.push_str(&format!("; charset=\"{}\"", charset));
+        }
+        entity.push_str(&format!(
+            "\r\nContent-Transfer-Encoding: {}\r\n\r\n",
+            self.encoding
+        ));
+        let mut bytes = entity.into_bytes();
+        bytes.extend_from_slice(raw);
+
+        match mail_parser::MessageParser::default().parse(&bytes) {
+            Some(message) => message.root_part().contents().to_vec(),
+            None => raw.to_vec(),
+        }
+    }
+}
+
+/// Parse a BODYSTRUCTURE value into its leaf parts, in document order.
+/// Embedded `message/rfc822` parts are treated as single leaves.
+pub fn parse_bodystructure(input: &str) -> Option<Vec<BodyPart>> {
+    let mut tokens = Tokenizer { input: input.as_bytes(), pos: 0 };
+    let tree = tokens.parse_value()?;
+    let mut parts = Vec::new();
+    collect_parts(&tree, "", &mut parts)?;
+    Some(parts)
+}
+
+/// Parsed S-expression node
+#[derive(Debug)]
+enum Node {
+    List(Vec<Node>),
+    Str(String),
+    Nil,
+}
+
+impl Node {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Node::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Parameter list such as `("charset" "utf-8" "name" "a.pdf")`
+    fn as_params(&self) -> Vec<(String, String)> {
+        match self {
+            Node::List(items) => items
+                .chunks(2)
+                .filter_map(|pair| match pair {
+                    [k, v] => Some((k.as_str()?.to_lowercase(), v.as_str()?.to_string())),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+struct Tokenizer<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Tokenizer<'_> {
+    fn skip_spaces(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Option<Node> {
+        self.skip_spaces();
+        match *self.input.get(self.pos)? {
+            b'(' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_spaces();
+                    if *self.input.get(self.pos)? == b')' {
+                        self.pos += 1;
+                        return Some(Node::List(items));
+                    }
+                    items.push(self.parse_value()?);
+                }
+            }
+            b'"' => {
+                self.pos += 1;
+                let mut value = Vec::new();
+                loop {
+                    let c = *self.input.get(self.pos)?;
+                    self.pos += 1;
+                    match c {
+                        b'\\' => {
+                            value.push(*self.input.get(self.pos)?);
+                            self.pos += 1;
+                        }
+                        b'"' => break,
+                        _ => value.push(c),
+                    }
+                }
+                Some(Node::Str(String::from_utf8_lossy(&value).into_owned()))
+            }
+            b'{' => {
+                // Literal: {n}\r\n followed by n bytes
+                let close = self.pos + self.input[self.pos..].iter().position(|&c| c == b'}')?;
+                let len: usize = std::str::from_utf8(&self.input[self.pos + 1..close]).ok()?.parse().ok()?;
+                let mut start = close + 1;
+                if self.input[start..].starts_with(b"\r\n") {
+                    start += 2;
+                }
+                let end = (start + len).min(self.input.len());
+                self.pos = end;
+                Some(Node::Str(String::from_utf8_lossy(&self.input[start..end]).into_owned()))
+            }
+            b')' => None,
+            _ => {
+                let start = self.pos;
+                while self.pos < self.input.len()
+                    && !self.input[self.pos].is_ascii_whitespace()
+                    && !matches!(self.input[self.pos], b'(' | b')')
+                {
+                    self.pos += 1;
+                }
+                let atom = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
+                if atom.eq_ignore_ascii_case("NIL") {
+                    Some(Node::Nil)
+                } else {
+                    Some(Node::Str(atom))
+                }
+            }
+        }
+    }
+}
+
+fn collect_parts(node: &Node, prefix: &str, parts: &mut Vec<BodyPart>) -> Option<()> {
+    let Node::List(items) = node else {
+        return None;
+    };
+
+    // Multipart: one or more nested bodies followed by the subtype
+    if matches!(items.first(), Some(Node::List(_))) {
+        let children = items.iter().take_while(|n| matches!(n, Node::List(_)));
+        for (i, child) in children.enumerate() {
+            let section = if prefix.is_empty() {
+                (i + 1).to_string()
+            } else {
+                format!("{}.{}", prefix, i + 1)
+            };
+            collect_parts(child, &section, parts)?;
+        }
+        return Some(());
+    }
+
+    let media_type = items.first()?.as_str()?.to_lowercase();
+    let subtype = items.get(1)?.as_str()?.to_lowercase();
+    let mime_type = format!("{}/{}", media_type, subtype);
+
+    // Extension data follows the type-specific fields
+    let ext_start = if media_type == "text" {
+        8
+    } else if mime_type == "message/rfc822" {
+        10
+    } else {
+        7
+    };
+    let (disposition, disposition_params) = match items.get(ext_start + 1) {
+        Some(Node::List(d)) => (
+            d.first().and_then(Node::as_str).map(|s| s.to_lowercase()),
+            d.get(1).map(Node::as_params).unwrap_or_default(),
+        ),
+        _ => (None, Vec::new()),
+    };
+
+    parts.push(BodyPart {
+        // A non-multipart message has a single part numbered 1
+        section: if prefix.is_empty() { "1".to_string() } else { prefix.to_string() },
+        mime_type,
+        params: items.get(2).map(Node::as_params).unwrap_or_default(),
+        content_id: items
+            .get(3)
+            .and_then(Node::as_str)
+            .map(|id| id.trim_start_matches('<').trim_end_matches('>').to_string()),
+        encoding: items
+            .get(5)
+            .and_then(Node::as_str)
+            .unwrap_or("7bit")
+            .to_lowercase(),
+        size: items
+            .get(6)
+            .and_then(Node::as_str)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0),
+        disposition,
+        disposition_params,
+    });
+    Some(())
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Decode RFC 2047 encoded words (`=?utf-8?B?...?=`) in a parameter value
+fn decode_encoded_words(value: &str) -> String {
+    if !value.contains("=?") {
+        return value.to_string();
+    }
+    let header = format!("Subject: {}\r\n\r\n", value);
+    mail_parser::MessageParser::default()
+        .parse(header.as_bytes())
+        .and_then(|m| m.subject().map(|s| s.to_string()))
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Decode an RFC 2231 extended value such as `utf-8''%E2%82%AC%20rates.pdf`
+fn decode_rfc2231(value: &str) -> String {
+    let encoded = value.splitn(3, '\'').nth(2).unwrap_or(value);
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = iter.by_ref().take(2).collect();
+            match std::str::from_utf8(&hex).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(decoded) => bytes.push(decoded),
+                None => {
+                    bytes.push(b'%');
+                    bytes.extend_from_slice(&hex);
+                }
+            }
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_part() {
+        let bs = r#"("TEXT" "PLAIN" ("CHARSET" "utf-8") NIL NIL "QUOTED-PRINTABLE" 1234 40 NIL NIL NIL)"#;
+        let parts = parse_bodystructure(bs).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].section, "1");
+        assert_eq!(parts[0].mime_type, "text/plain");
+        assert_eq!(parts[0].charset(), Some("utf-8"));
+        assert_eq!(parts[0].encoding, "quoted-printable");
+        assert!(parts[0].is_body_text());
+    }
+
+    #[test]
+    fn test_parse_nested_multipart() {
+        let bs = r#"((("TEXT" "PLAIN" ("CHARSET" "utf-8") NIL NIL "7BIT" 10 1 NIL NIL NIL)("TEXT" "HTML" ("CHARSET" "utf-8") NIL NIL "7BIT" 20 1 NIL NIL NIL) "ALTERNATIVE" ("BOUNDARY" "b1") NIL NIL)("IMAGE" "PNG" ("NAME" "logo.png") "<logo@x>" NIL "BASE64" 400 NIL ("INLINE" ("FILENAME" "logo.png")) NIL)("APPLICATION" "PDF" ("NAME" "report.pdf") NIL NIL "BASE64" 20971520 NIL ("ATTACHMENT" ("FILENAME" "report.pdf")) NIL) "MIXED" ("BOUNDARY" "b0") NIL NIL)"#;
+        let parts = parse_bodystructure(bs).unwrap();
+        let sections: Vec<&str> = parts.iter().map(|p| p.section.as_str()).collect();
+        assert_eq!(sections, vec!["1.1", "1.2", "2", "3"]);
+        assert!(parts[1].is_body_text());
+        assert!(parts[2].is_inline_resource());
+        assert_eq!(parts[2].content_id.as_deref(), Some("logo@x"));
+        assert_eq!(parts[3].disposition.as_deref(), Some("attachment"));
+        assert_eq!(parts[3].filename().as_deref(), Some("report.pdf"));
+        assert!(!parts[3].is_body_text());
+    }
+
+    #[test]
+    fn test_parse_message_rfc822_and_literal() {
+        let bs = "((\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 5 1 NIL NIL NIL)(\"MESSAGE\" \"RFC822\" NIL NIL NIL \"7BIT\" 300 (NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL) (\"TEXT\" \"PLAIN\" NIL NIL NIL \"7BIT\" 5 1) 12 NIL (\"ATTACHMENT\" (\"FILENAME\" {9}\r\nfwd\u{20}1.eml)) NIL) \"MIXED\")";
+        let parts = parse_bodystructure(bs).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].mime_type, "message/rfc822");
+        assert_eq!(parts[1].filename().as_deref(), Some("fwd 1.eml"));
+    }
+
+    #[test]
+    fn test_filename_encodings() {
+        let part = BodyPart {
+            disposition_params: vec![("filename*".into(), "utf-8''%E2%82%AC%20rates.pdf".into())],
+            ..Default::default()
+        };
+        assert_eq!(part.filename().as_deref(), Some("€ rates.pdf"));
+
+        let part = BodyPart {
+            params: vec![("name".into(), "=?UTF-8?B?w6l0w6kucGRm?=".into())],
+            ..Default::default()
+        };
+        assert_eq!(part.filename().as_deref(), Some("été.pdf"));
+    }
+
+    #[test]
+    fn test_decode() {
+        let part = BodyPart {
+            mime_type: "application/octet-stream".into(),
+            encoding: "base64".into(),
+            ..Default::default()
+        };
+        assert_eq!(part.decode(b"aGVsbG8=\r\n"), b"hello");
+
+        let part = BodyPart {
+            mime_type: "text/plain".into(),
+            params: vec![("charset".into(), "iso-8859-1".into())],
+            encoding: "quoted-printable".into(),
+            ..Default::default()
+        };
+        assert_eq!(String::from_utf8(part.decode(b"caf=E9")).unwrap(), "café");
+    }
+}
@@ -0,0 +1,132 @@
+//! Server capabilities
+//!
+//! What a server supports is read from its CAPABILITY response, or from the
+//! `[CAPABILITY ...]` code many servers attach to the login reply, and kept
+//! as a typed set. Operations check it and fall back to plain IMAP4rev1
+//! where an extension is missing, so servers with few extensions still work.
+
+/// Extensions the client knows how to use or fall back from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// IDLE push (RFC 2177); without it new mail is found by polling
+    pub idle: bool,
+    /// UID MOVE (RFC 6851); without it moves are COPY + STORE + EXPUNGE
+    pub move_: bool,
+    /// UID EXPUNGE and COPYUID/APPENDUID (RFC 4315); without it EXPUNGE
+    /// removes every `\Deleted` message in the folder
+    pub uidplus: bool,
+    /// MODSEQ tracking (RFC 7162)
+    pub condstore: bool,
+    /// Quick resync (RFC 7162)
+    pub qresync: bool,
+    /// COMPRESS=DEFLATE (RFC 4978)
+    pub compress_deflate: bool,
+    /// Special-use folder attributes (RFC 6154); without them folder roles
+    /// are guessed from names
+    pub special_use: bool,
+    /// Quota (RFC 9208)
+    pub quota: bool,
+    /// Access control lists (RFC 4314)
+    pub acl: bool,
+    /// NAMESPACE (RFC 2342), for other users' and shared mailboxes
+    pub namespace: bool,
+    /// LIST-EXTENDED (RFC 5258), for `LIST (SUBSCRIBED)`; LSUB otherwise
+    pub list_extended: bool,
+    /// STATUS as part of LIST (RFC 5819)
+    pub list_status: bool,
+    /// ENABLE (RFC 5161)
+    pub enable: bool,
+    /// Non-synchronizing literals (RFC 7888)
+    pub literal_plus: bool,
+    /// Several messages in one APPEND (RFC 3502); without it messages are
+    /// appended one per command
+    pub multiappend: bool,
+    /// Gmail extensions: labels, thread ids, X-GM-RAW search
+    pub gmail: bool,
+    /// Everything advertised, uppercased
+    atoms: Vec<String>,
+}
+
+impl Capabilities {
+    /// Capabilities from a list of atoms, e.g. `["IMAP4rev1", "IDLE", "MOVE"]`
+    pub fn from_atoms<'a>(atoms: impl IntoIterator<Item = &'a str>) -> Self {
+        let atoms: Vec<String> = atoms.into_iter().map(str::to_uppercase).collect();
+        let has = |name: &str| atoms.iter().any(|a| a == name);
+        Self {
+            idle: has("IDLE"),
+            move_: has("MOVE"),
+            uidplus: has("UIDPLUS"),
+            condstore: has("CONDSTORE") || has("QRESYNC"),
+            qresync: has("QRESYNC"),
+            compress_deflate: has("COMPRESS=DEFLATE"),
+            special_use: has("SPECIAL-USE") || has("X-GM-EXT-1"),
+            quota: has("QUOTA"),
+            acl: has("ACL"),
+            namespace: has("NAMESPACE"),
+            list_extended: has("LIST-EXTENDED"),
+            list_status: has("LIST-STATUS"),
+            enable: has("ENABLE"),
+            literal_plus: has("LITERAL+"),
+            multiappend: has("MULTIAPPEND"),
+            gmail: has("X-GM-EXT-1"),
+            atoms,
+        }
+    }
+
+    /// Capabilities from a response line: an untagged `* CAPABILITY ...`
+    /// or any status response carrying a `[CAPABILITY ...]` code
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("* CAPABILITY ") {
+            return Some(Self::from_atoms(rest.split_whitespace()));
+        }
+        let start = line.find("[CAPABILITY ")? + "[CAPABILITY ".len();
+        let end = start + line[start..].find(']')?;
+        Some(Self::from_atoms(line[start..end].split_whitespace()))
+    }
+
+    /// Whether the server advertises `name` (case-insensitive), for
+    /// capabilities without a field of their own
+    pub fn has(&self, name: &str) -> bool {
+        let name = name.to_uppercase();
+        self.atoms.contains(&name)
+    }
+
+    /// Everything advertised, uppercased
+    pub fn atoms(&self) -> &[String] {
+        &self.atoms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_capability_lines() {
+        let caps = Capabilities::parse("* CAPABILITY IMAP4rev1 IDLE MOVE UIDPLUS SPECIAL-USE COMPRESS=DEFLATE MULTIAPPEND\r\n").unwrap();
+        assert!(caps.idle && caps.move_ && caps.uidplus && caps.special_use && caps.compress_deflate);
+        assert!(caps.multiappend);
+        assert!(!caps.condstore && !caps.gmail && !caps.list_extended);
+        assert!(caps.has("imap4rev1"));
+
+        let caps = Capabilities::parse("A0001 OK [CAPABILITY IMAP4rev1 X-GM-EXT-1 QRESYNC] user authenticated").unwrap();
+        assert!(caps.gmail && caps.special_use && caps.qresync && caps.condstore);
+        assert!(!caps.idle);
+
+        assert!(Capabilities::parse("A0001 OK LOGIN completed").is_none());
+    }
+
+    #[test]
+    fn test_bare_server() {
+        // A minimal IMAP4rev1 server: every operation takes its fallback
+        let caps = Capabilities::parse("* CAPABILITY IMAP4rev1 AUTH=PLAIN").unwrap();
+        assert_eq!(
+            caps,
+            Capabilities {
+                atoms: vec!["IMAP4REV1".to_string(), "AUTH=PLAIN".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -1,506 +1,3451 @@
-//! IMAP client implementation
-
-use crate::{Folder, FolderType, ImapError, ImapResult, MessageHeader, XOAuth2Authenticator};
-use crate::message::{EmailAddress, Envelope, MessageFlags};
-use async_imap::Session;
-use async_native_tls::TlsStream;
-use async_std::net::TcpStream;
-use futures::TryStreamExt;
+//! IMAP client speaking the raw protocol
+//!
+//! One client serves every code path: sync, IDLE, on-demand fetches and
+//! folder management. It runs over any [`Transport`], so pipelined commands
+//! (batch STATUS), capability tracking and protocol tracing apply to all of
+//! them, and works in any async context (async-std or tokio).
+
+use async_std::io::prelude::*;
+use async_std::io::BufReader;
 use tracing::{debug, info};
 
-// Type alias for our TLS stream
-type ImapStream = TlsStream<TcpStream>;
+use crate::acl::{parse_acl, parse_my_rights, AclEntry, Namespaces, Rights};
+use crate::capabilities::Capabilities;
+use crate::deletion::DeleteAction;
+use crate::header_batch::HeaderBatch;
+use crate::health::{SuspendDetector, KEEPALIVE_TIMEOUT};
+use crate::{BodyPart, Folder, FolderPeek, FolderType, ImapError, ImapResult, MessageHeader, MessageFlags};
+use crate::message::{EmailAddress, Envelope};
+use crate::quota::{parse_quota_roots, Quota};
+use crate::uidplus::{format_uid_set, AppendUid, CopyUid};
+use crate::utf7::{decode_mailbox_name, encode_mailbox_name};
+use crate::XOAuth2Authenticator;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Header fetches leave out BODYSTRUCTURE (low-bandwidth mode)
+static LEAN_HEADERS: AtomicBool = AtomicBool::new(false);
+
+/// Leave BODYSTRUCTURE out of header fetches on every connection to save
+/// bandwidth. Messages synced meanwhile aren't marked as having attachments.
+pub fn set_lean_header_fetch(enabled: bool) {
+    LEAN_HEADERS.store(enabled, Ordering::Relaxed);
+}
+
+/// Byte stream an IMAP session runs over: a TLS connection to a server,
+/// or anything else that reads and writes, such as an in-memory pipe
+pub trait Transport: futures::io::AsyncRead + futures::io::AsyncWrite + Unpin + Send {}
+
+impl<T: futures::io::AsyncRead + futures::io::AsyncWrite + Unpin + Send> Transport for T {}
+
+type Stream = BufReader<Box<dyn Transport>>;
+
+/// Escape a string for use in IMAP quoted strings (RFC 3501 §4.3)
+pub(crate) fn escape_imap_quoted(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Start and size of the literal announced by a `{N}` at the end of `line`
+fn literal_size(line: &str) -> Option<(usize, usize)> {
+    let body = line.trim_end_matches(['\r', '\n']).strip_suffix('}')?;
+    let start = body.rfind('{')?;
+    let size = body[start + 1..].parse().ok()?;
+    Some((start, size))
+}
 
-/// IMAP client for email operations
+/// Position of the first occurrence of `needle` in `haystack`
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Event returned from IDLE mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdleEvent {
+    /// New messages exist (reported count from EXISTS response)
+    NewMessages(u32),
+    /// A message was expunged (sequence number)
+    Expunge(u32),
+    /// Flags changed on a message
+    FlagsChanged,
+    /// IDLE timed out (for keepalive)
+    Timeout,
+    /// Server closed connection
+    ServerBye,
+    /// The machine slept during IDLE; the connection is probably dead
+    Suspended,
+}
+
+/// How often IDLE wakes up to check whether the machine slept
+const IDLE_WAKE_CHECK: Duration = Duration::from_secs(60);
+
+/// IMAP client that works in any async context
 pub struct ImapClient {
-    session: Option<Session<ImapStream>>,
-    host: String,
-    port: u16,
+    stream: Option<Stream>,
+    tag_counter: u32,
+    /// Server capabilities, from the login reply or fetched on first use
+    capabilities: Option<Capabilities>,
+    /// Name we logged in with, which keys removals in flight
+    user: Option<String>,
+    /// Folder currently selected or examined
+    selected: Option<String>,
 }
 
 impl ImapClient {
-    /// Create a new IMAP client
-    pub fn new(host: impl Into<String>, port: u16) -> Self {
+    /// Create a new client
+    pub fn new() -> Self {
         Self {
-            session: None,
-            host: host.into(),
-            port,
+            stream: None,
+            tag_counter: 0,
+            capabilities: None,
+            user: None,
+            selected: None,
         }
     }
 
-    /// Create a Gmail IMAP client
-    pub fn gmail() -> Self {
-        Self::new("imap.gmail.com", 993)
+    fn next_tag(&mut self) -> String {
+        self.tag_counter += 1;
+        format!("A{:04}", self.tag_counter)
     }
 
-    /// Connect and authenticate using XOAUTH2 (for Gmail)
-    pub async fn authenticate_xoauth2(
-        &mut self,
-        email: &str,
-        access_token: &str,
-    ) -> ImapResult<()> {
-        info!("Connecting to {}:{}", self.host, self.port);
+    /// Open a TLS connection (per the host's registered settings) and read the greeting
+    async fn open(host: &str, port: u16) -> ImapResult<Stream> {
+        info!("Connecting to {}:{}", host, port);
+        let (tls_stream, greeted) = crate::tls::connect(host, port).await?;
+        let transport: Box<dyn Transport> = Box::new(crate::trace::TracedStream::new(tls_stream, host));
+        let mut stream = BufReader::new(transport);
+        if !greeted {
+            Self::read_greeting(&mut stream).await?;
+        }
+        Ok(stream)
+    }
 
-        // Create TCP connection using async-std
-        let tcp_stream = TcpStream::connect(format!("{}:{}", self.host, self.port))
-            .await
-            .map_err(|e| ImapError::ConnectionFailed(e.to_string()))?;
+    /// Start a session over an already established transport, reading the
+    /// server greeting. Authenticate next with [`Self::login`] or
+    /// [`Self::authenticate_xoauth2`].
+    pub async fn connect_transport(&mut self, transport: impl Transport + 'static) -> ImapResult<()> {
+        let transport: Box<dyn Transport> = Box::new(transport);
+        let mut stream = BufReader::new(transport);
+        Self::read_greeting(&mut stream).await?;
+        self.stream = Some(stream);
+        self.capabilities = None;
+        self.user = None;
+        self.selected = None;
+        Ok(())
+    }
 
-        // Wrap with TLS
-        let tls_connector = async_native_tls::TlsConnector::new();
-        let tls_stream = tls_connector
-            .connect(&self.host, tcp_stream)
+    async fn read_greeting(stream: &mut Stream) -> ImapResult<()> {
+        let mut greeting = String::new();
+        stream
+            .read_line(&mut greeting)
             .await
-            .map_err(|e| ImapError::TlsError(e.to_string()))?;
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        debug!("TLS connection established");
+        debug!("Greeting: {}", greeting.trim());
 
-        // Create IMAP client
-        let client = async_imap::Client::new(tls_stream);
+        if !greeting.starts_with("* OK") {
+            return Err(ImapError::ServerError(format!(
+                "Unexpected greeting: {}",
+                greeting
+            )));
+        }
 
-        info!("Authenticating with XOAUTH2 for {}", email);
+        Ok(())
+    }
 
-        // Authenticate with XOAUTH2
-        let auth = XOAuth2Authenticator::new(email, access_token);
-        let session = client
-            .authenticate("XOAUTH2", auth)
-            .await
-            .map_err(|(e, _)| ImapError::AuthenticationFailed(e.to_string()))?;
+    /// Connect to Gmail and authenticate with XOAUTH2
+    pub async fn connect_gmail(&mut self, email: &str, access_token: &str) -> ImapResult<()> {
+        self.connect_xoauth2("imap.gmail.com", 993, email, access_token).await
+    }
 
-        self.session = Some(session);
-        info!("XOAUTH2 authentication successful");
-        Ok(())
+    /// Connect to Microsoft/Outlook and authenticate with XOAUTH2
+    pub async fn connect_outlook(&mut self, email: &str, access_token: &str) -> ImapResult<()> {
+        self.connect_xoauth2("outlook.office365.com", 993, email, access_token).await
     }
 
-    /// Connect and authenticate using LOGIN (username/password) for standard IMAP
-    pub async fn authenticate_login(
+    /// Connect to any IMAP server and authenticate with LOGIN (password)
+    pub async fn connect_login(
         &mut self,
+        host: &str,
+        port: u16,
         username: &str,
         password: &str,
     ) -> ImapResult<()> {
-        info!("Connecting to {}:{}", self.host, self.port);
+        self.stream = Some(Self::open(host, port).await?);
+        self.login(username, password).await
+    }
 
-        // Create TCP connection
-        let tcp_stream = TcpStream::connect(format!("{}:{}", self.host, self.port))
+    /// Authenticate the open connection with LOGIN (password)
+    pub async fn login(&mut self, username: &str, password: &str) -> ImapResult<()> {
+        // LOGIN command - quote username and password
+        let tag = self.next_tag();
+        let cmd = format!("{} LOGIN \"{}\" \"{}\"\r\n", tag,
+            escape_imap_quoted(username),
+            escape_imap_quoted(password));
+
+        let stream = self.stream.as_mut().ok_or(ImapError::NotConnected)?;
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
             .await
-            .map_err(|e| ImapError::ConnectionFailed(e.to_string()))?;
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        // Wrap with TLS
-        let tls_connector = async_native_tls::TlsConnector::new();
-        let tls_stream = tls_connector
-            .connect(&self.host, tcp_stream)
-            .await
-            .map_err(|e| ImapError::TlsError(e.to_string()))?;
+        // Read response
+        let mut auth_ok = false;
+        let mut capabilities = None;
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        debug!("TLS connection established");
+            debug!("Login response: {}", line.trim());
 
-        // Create IMAP client
-        let client = async_imap::Client::new(tls_stream);
+            // Capabilities can change at login, so only these count
+            if let Some(caps) = Capabilities::parse(&line) {
+                capabilities = Some(caps);
+            }
 
-        info!("Authenticating with LOGIN for {}", username);
+            if line.starts_with(&tag) {
+                if line.contains("OK") {
+                    auth_ok = true;
+                }
+                break;
+            }
+        }
 
-        // Use standard login
-        let session = client
-            .login(username, password)
-            .await
-            .map_err(|(e, _)| ImapError::AuthenticationFailed(e.to_string()))?;
+        if !auth_ok {
+            self.stream = None;
+            return Err(ImapError::AuthenticationFailed(
+                "LOGIN authentication failed".to_string(),
+            ));
+        }
 
-        self.session = Some(session);
         info!("LOGIN authentication successful");
+        self.capabilities = capabilities;
+        self.user = Some(username.to_string());
+        self.selected = None;
         Ok(())
     }
 
-    /// Create an iCloud IMAP client
-    pub fn icloud() -> Self {
-        Self::new("imap.mail.me.com", 993)
+    /// Connect to any IMAP server and authenticate with XOAUTH2
+    pub async fn connect_xoauth2(
+        &mut self,
+        host: &str,
+        port: u16,
+        email: &str,
+        access_token: &str,
+    ) -> ImapResult<()> {
+        self.stream = Some(Self::open(host, port).await?);
+        self.authenticate_xoauth2(email, access_token).await
     }
 
-    /// Get the session, returning an error if not connected
-    fn session_mut(&mut self) -> ImapResult<&mut Session<ImapStream>> {
-        self.session.as_mut().ok_or(ImapError::NotConnected)
-    }
+    /// Authenticate the open connection with XOAUTH2
+    pub async fn authenticate_xoauth2(&mut self, email: &str, access_token: &str) -> ImapResult<()> {
+        let encoded = XOAuth2Authenticator::new(email, access_token).response();
 
-    /// List all folders/mailboxes
-    pub async fn list_folders(&mut self) -> ImapResult<Vec<Folder>> {
-        let session = self.session_mut()?;
+        let tag = self.next_tag();
+        let cmd = format!("{} AUTHENTICATE XOAUTH2 {}\r\n", tag, encoded);
 
-        let mailboxes = session
-            .list(None, Some("*"))
+        let stream = self.stream.as_mut().ok_or(ImapError::NotConnected)?;
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
             .await
             .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        let mut folders = Vec::new();
+        // Read response until we get our tag
+        let mut auth_ok = false;
+        let mut error_msg = String::new();
+        let mut capabilities = None;
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        let mut stream = mailboxes;
-        while let Some(mailbox) = stream
-            .try_next()
-            .await
-            .map_err(|e| ImapError::ServerError(e.to_string()))?
-        {
-            let delim_str = mailbox.delimiter().unwrap_or("/");
-            let delim_char = delim_str.chars().next();
+            debug!("Auth response: {}", line.trim());
 
-            let name = mailbox
-                .name()
-                .split(delim_str)
-                .last()
-                .unwrap_or(mailbox.name())
-                .to_string();
-
-            let attributes: Vec<String> = mailbox
-                .attributes()
-                .iter()
-                .map(|a| {
-                    use async_imap::types::NameAttribute;
-                    match a {
-                        NameAttribute::NoInferiors => "\\Noinferiors".to_string(),
-                        NameAttribute::NoSelect => "\\Noselect".to_string(),
-                        NameAttribute::Marked => "\\Marked".to_string(),
-                        NameAttribute::Unmarked => "\\Unmarked".to_string(),
-                        NameAttribute::Extension(ext) => ext.to_string(),
-                        other => format!("{:?}", other),
-                    }
-                })
-                .collect();
-
-            debug!("LIST folder: {} attrs={:?}", mailbox.name(), &attributes);
-            folders.push(Folder::new(
-                name,
-                mailbox.name().to_string(),
-                delim_char,
-                attributes,
+            if let Some(caps) = Capabilities::parse(&line) {
+                capabilities = Some(caps);
+            }
+
+            // Check for continuation request (challenge) - send empty response
+            if line.starts_with("+ ") {
+                // Server is requesting more data, send empty line to get error details
+                stream
+                    .get_mut()
+                    .write_all(b"\r\n")
+                    .await
+                    .map_err(|e| ImapError::ServerError(e.to_string()))?;
+                continue;
+            }
+
+            if line.starts_with(&tag) {
+                if line.contains("OK") {
+                    auth_ok = true;
+                } else {
+                    error_msg = line.clone();
+                }
+                break;
+            }
+        }
+
+        if !auth_ok {
+            self.stream = None;
+            return Err(ImapError::AuthenticationFailed(
+                format!("XOAUTH2 authentication failed: {}", error_msg.trim()),
             ));
         }
 
-        FolderType::deduplicate_folder_types(&mut folders);
-        debug!("Found {} folders", folders.len());
-        Ok(folders)
+        info!("XOAUTH2 authentication successful");
+        self.capabilities = capabilities;
+        self.user = Some(email.to_string());
+        self.selected = None;
+        Ok(())
     }
 
-    /// Get STATUS for a folder (MESSAGES and UNSEEN counts)
-    /// Returns (message_count, unseen_count)
-    pub async fn folder_status(&mut self, folder: &str) -> ImapResult<(u32, u32)> {
-        let session = self.session_mut()?;
+    /// Select a folder
+    pub async fn select(&mut self, folder: &str) -> ImapResult<Folder> {
+        self.open_folder("SELECT", folder).await
+    }
 
-        let mailbox = session
-            .status(folder, "(MESSAGES UNSEEN)")
-            .await
-            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+    /// Select a folder read-only, so nothing (e.g. `\Recent`) changes on the server
+    pub async fn examine(&mut self, folder: &str) -> ImapResult<Folder> {
+        self.open_folder("EXAMINE", folder).await
+    }
 
-        // For STATUS response: exists = MESSAGES count, unseen = UNSEEN count
-        let messages = mailbox.exists;
-        let unseen = mailbox.unseen.unwrap_or(0);
-        Ok((messages, unseen))
+    /// Peek at a folder: STATUS for the counts, then a small FETCH of the
+    /// newest `count` headers. The folder is left EXAMINEd (read-only), so
+    /// callers must SELECT again before changing anything.
+    pub async fn peek_folder(&mut self, folder: &str, count: u32) -> ImapResult<FolderPeek> {
+        let (_, unread_count) = self.folder_status(folder).await?;
+        let message_count = self.examine(folder).await?.message_count.unwrap_or(0);
+
+        let mut recent = if message_count == 0 || count == 0 {
+            Vec::new()
+        } else {
+            let first = message_count.saturating_sub(count - 1).max(1);
+            self.fetch_headers(&format!("{}:{}", first, message_count)).await?
+        };
+        recent.sort_by_key(|h| std::cmp::Reverse(h.uid));
+
+        Ok(FolderPeek {
+            message_count,
+            unread_count,
+            recent,
+        })
     }
 
-    /// Select a folder and get its status
-    pub async fn select_folder(&mut self, folder_path: &str) -> ImapResult<Folder> {
-        let session = self.session_mut()?;
+    /// Send SELECT or EXAMINE and read the message count
+    async fn open_folder(&mut self, command: &str, folder: &str) -> ImapResult<Folder> {
+        let tag = self.next_tag();
+        let cmd = format!("{} {} \"{}\"\r\n", tag, command, escape_imap_quoted(folder));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
 
-        let mailbox = session
-            .select(folder_path)
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
             .await
-            .map_err(|e| ImapError::FolderNotFound(format!("{}: {}", folder_path, e)))?;
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        let folder = Folder {
-            name: folder_path
-                .split('/')
-                .last()
-                .unwrap_or(folder_path)
-                .to_string(),
-            full_path: folder_path.to_string(),
-            folder_type: FolderType::from_attributes_and_name(&[], folder_path),
+        let mut exists = 0u32;
+        let mut select_ok = false;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("Select response: {}", line.trim());
+
+            // Parse EXISTS count
+            if line.contains(" EXISTS") {
+                if let Some(n) = line
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|s| s.parse().ok())
+                {
+                    exists = n;
+                }
+            }
+
+            if line.starts_with(&tag) {
+                if line.contains("OK") {
+                    select_ok = true;
+                }
+                break;
+            }
+        }
+
+        if !select_ok {
+            self.selected = None;
+            return Err(ImapError::FolderNotFound(folder.to_string()));
+        }
+        self.selected = Some(folder.to_string());
+
+        Ok(Folder {
+            name: folder.to_string(),
+            full_path: folder.to_string(),
+            folder_type: FolderType::from_attributes_and_name(&[], folder),
             delimiter: Some('/'),
-            attributes: Vec::new(),
-            uidvalidity: mailbox.uid_validity,
-            message_count: Some(mailbox.exists),
+            attributes: vec![],
+            uidvalidity: None,
+            message_count: Some(exists),
             unread_count: None,
-            uid_next: mailbox.uid_next,
-        };
-
-        debug!(
-            "Selected folder {} with {} messages",
-            folder_path,
-            folder.message_count.unwrap_or(0)
-        );
+            uid_next: None,
+        })
+    }
 
-        Ok(folder)
+    /// Fetch message headers
+    pub async fn fetch_headers(&mut self, range: &str) -> ImapResult<Vec<MessageHeader>> {
+        let mut headers = Vec::new();
+        self.fetch_headers_each(range, false, |h| headers.push(h)).await?;
+        Ok(headers)
     }
 
-    /// Fetch message headers for a range of UIDs
-    pub async fn fetch_headers(&mut self, uids: &str) -> ImapResult<Vec<MessageHeader>> {
-        let session = self.session_mut()?;
+    /// Fetch message headers for `range` (sequence numbers, or UIDs when
+    /// `by_uid`), handing each one to `on_header` as soon as its response has
+    /// been read. Only one response is held at a time, so a large batch never
+    /// sits in memory whole and callers can report progress mid-response.
+    /// Messages being removed are skipped, as in [`Self::fetch_headers`].
+    pub async fn fetch_headers_each(
+        &mut self,
+        range: &str,
+        by_uid: bool,
+        mut on_header: impl FnMut(MessageHeader),
+    ) -> ImapResult<()> {
+        let items = self.header_fetch_items().await?;
+        let tag = self.next_tag();
+        let cmd = format!(
+            "{} {}FETCH {} {}\r\n",
+            tag,
+            if by_uid { "UID " } else { "" },
+            range,
+            items
+        );
+
+        let stream = self.stream.as_mut().ok_or(ImapError::NotConnected)?;
 
-        let fetch_stream = session
-            .uid_fetch(uids, "(UID FLAGS ENVELOPE RFC822.SIZE BODYSTRUCTURE)")
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
             .await
             .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        let mut messages = Vec::new();
+        loop {
+            let response = Self::read_response(stream).await?;
 
-        let mut stream = fetch_stream;
-        while let Some(fetch) = stream
-            .try_next()
-            .await
-            .map_err(|e| ImapError::ParseError(e.to_string()))?
-        {
-            let uid = fetch.uid.ok_or_else(|| {
-                ImapError::ParseError("Missing UID in FETCH response".to_string())
-            })?;
-
-            let envelope = fetch.envelope().map(|env| {
-                let parse_addresses =
-                    |addrs: Option<&Vec<imap_proto::types::Address>>| -> Vec<EmailAddress> {
-                        addrs
-                            .map(|v| {
-                                v.iter()
-                                    .map(|a| {
-                                        let mailbox = a
-                                            .mailbox
-                                            .as_ref()
-                                            .map(|s| String::from_utf8_lossy(s).to_string())
-                                            .unwrap_or_default();
-                                        let host = a
-                                            .host
-                                            .as_ref()
-                                            .map(|s| String::from_utf8_lossy(s).to_string())
-                                            .unwrap_or_default();
-                                        let address = format!("{}@{}", mailbox, host);
-                                        let name = a
-                                            .name
-                                            .as_ref()
-                                            .map(|s| String::from_utf8_lossy(s).to_string());
-                                        EmailAddress::new(name, address)
-                                    })
-                                    .collect()
-                            })
-                            .unwrap_or_default()
-                    };
-
-                Envelope {
-                    message_id: env
-                        .message_id
-                        .as_ref()
-                        .map(|s| String::from_utf8_lossy(s).to_string()),
-                    subject: env
-                        .subject
-                        .as_ref()
-                        .map(|s| String::from_utf8_lossy(s).to_string()),
-                    from: parse_addresses(env.from.as_ref()),
-                    to: parse_addresses(env.to.as_ref()),
-                    cc: parse_addresses(env.cc.as_ref()),
-                    reply_to: parse_addresses(env.reply_to.as_ref()),
-                    date: env
-                        .date
-                        .as_ref()
-                        .map(|s| String::from_utf8_lossy(s).to_string()),
-                    in_reply_to: env
-                        .in_reply_to
-                        .as_ref()
-                        .map(|s| String::from_utf8_lossy(s).to_string()),
-                }
-            });
-
-            // Parse flags - flags() returns an iterator directly
-            let flag_strs: Vec<String> = fetch.flags()
-                .map(|f| format!("{:?}", f))
-                .collect();
-            let flag_refs: Vec<&str> = flag_strs.iter().map(|s| s.as_str()).collect();
-            let flags = MessageFlags::from_imap_flags(&flag_refs);
-
-            // Detect attachments from BODYSTRUCTURE
-            let has_attachments = fetch.bodystructure()
-                .map(|bs| Self::bodystructure_has_attachments(bs))
-                .unwrap_or(false);
-
-            messages.push(MessageHeader {
-                uid,
-                seq: fetch.message,
-                envelope: envelope.unwrap_or_default(),
-                flags,
-                size: fetch.size.unwrap_or(0),
-                has_attachments,
-            });
-        }
-
-        debug!("Fetched {} message headers", messages.len());
-        Ok(messages)
-    }
-
-    /// Fetch a complete message body
-    pub async fn fetch_body(&mut self, uid: u32) -> ImapResult<Vec<u8>> {
-        let session = self.session_mut()?;
-
-        let fetch_stream = session
-            .uid_fetch(uid.to_string(), "BODY[]")
-            .await
-            .map_err(|e| ImapError::ServerError(e.to_string()))?;
-
-        let mut stream = fetch_stream;
-        while let Some(fetch) = stream
-            .try_next()
-            .await
-            .map_err(|e| ImapError::ParseError(e.to_string()))?
-        {
-            if let Some(body) = fetch.body() {
-                return Ok(body.to_vec());
+            if response.starts_with(&tag) {
+                break;
+            }
+
+            if !(response.starts_with("* ") && response.contains("FETCH")) {
+                continue;
+            }
+            if let Some(header) = Self::parse_fetch_response(&response) {
+                if !Self::is_removed(self.user.as_deref(), self.selected.as_deref(), header.uid, header.flags.deleted) {
+                    on_header(header);
+                }
             }
         }
 
-        Err(ImapError::MessageNotFound(uid))
+        Ok(())
     }
 
-    /// Set flags on a message
-    pub async fn set_flags(&mut self, uid: u32, flags: &[&str]) -> ImapResult<()> {
-        let session = self.session_mut()?;
+    /// Fetch message headers for `range` into `batch`, which is not cleared
+    /// first. Like [`Self::fetch_headers_each`] but without a [`MessageHeader`]
+    /// per message: responses are parsed into the batch's shared buffers, so a
+    /// batch reused across chunks of a large sync allocates almost nothing.
+    /// `on_progress` gets the batch length after each response.
+    pub async fn fetch_header_batch(
+        &mut self,
+        range: &str,
+        by_uid: bool,
+        batch: &mut HeaderBatch,
+        mut on_progress: impl FnMut(usize),
+    ) -> ImapResult<()> {
+        let items = self.header_fetch_items().await?;
+        let tag = self.next_tag();
+        let cmd = format!(
+            "{} {}FETCH {} {}\r\n",
+            tag,
+            if by_uid { "UID " } else { "" },
+            range,
+            items
+        );
+
+        let stream = self.stream.as_mut().ok_or(ImapError::NotConnected)?;
 
-        let flags_str = flags.join(" ");
-        session
-            .uid_store(uid.to_string(), format!("+FLAGS ({})", flags_str))
-            .await
-            .map_err(|e| ImapError::ServerError(e.to_string()))?
-            .try_collect::<Vec<_>>()
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
             .await
             .map_err(|e| ImapError::ServerError(e.to_string()))?;
 
-        Ok(())
-    }
+        let mut response = String::new();
+        loop {
+            response.clear();
+            Self::read_response_into(stream, &mut response).await?;
 
-    /// Remove flags from a message
-    pub async fn remove_flags(&mut self, uid: u32, flags: &[&str]) -> ImapResult<()> {
-        let session = self.session_mut()?;
+            if response.starts_with(&tag) {
+                break;
+            }
 
-        let flags_str = flags.join(" ");
-        session
-            .uid_store(uid.to_string(), format!("-FLAGS ({})", flags_str))
-            .await
-            .map_err(|e| ImapError::ServerError(e.to_string()))?
-            .try_collect::<Vec<_>>()
-            .await
-            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            if !(response.starts_with("* ") && batch.push_response(&response)) {
+                continue;
+            }
+            let header = batch.last().expect("header was just pushed");
+            if Self::is_removed(self.user.as_deref(), self.selected.as_deref(), header.uid(), header.is_deleted()) {
+                batch.truncate(batch.len() - 1);
+            } else {
+                on_progress(batch.len());
+            }
+        }
 
         Ok(())
     }
 
-    /// Mark a message as read
-    pub async fn mark_read(&mut self, uid: u32) -> ImapResult<()> {
-        self.set_flags(uid, &["\\Seen"]).await
-    }
-
-    /// Mark a message as unread
-    pub async fn mark_unread(&mut self, uid: u32) -> ImapResult<()> {
-        self.remove_flags(uid, &["\\Seen"]).await
+    /// Read one complete response. Literals (`{N}` followed by N raw bytes)
+    /// are inlined as quoted strings, so the line parsers see a subject or
+    /// name sent as a literal the same way as one sent quoted.
+    async fn read_response(stream: &mut Stream) -> ImapResult<String> {
+        let mut response = String::new();
+        Self::read_response_into(stream, &mut response).await?;
+        Ok(response)
     }
 
-    /// Move a message to another folder
-    pub async fn move_message(&mut self, uid: u32, dest_folder: &str) -> ImapResult<()> {
-        // Copy to destination
-        {
-            let session = self.session_mut()?;
-            session
-                .uid_copy(uid.to_string(), dest_folder)
+    /// [`Self::read_response`], appending to `response` so its buffer can be
+    /// reused
+    async fn read_response_into(stream: &mut Stream, response: &mut String) -> ImapResult<()> {
+        loop {
+            let mut line = String::new();
+            let n = stream
+                .read_line(&mut line)
                 .await
                 .map_err(|e| ImapError::ServerError(e.to_string()))?;
-        }
+            if n == 0 {
+                return Err(ImapError::ServerError("Connection closed".to_string()));
+            }
 
-        // Mark original as deleted
-        self.set_flags(uid, &["\\Deleted"]).await?;
+            let Some((start, size)) = literal_size(&line) else {
+                response.push_str(&line);
+                return Ok(());
+            };
 
-        // Expunge
-        {
-            let session = self.session_mut()?;
-            session
-                .expunge()
-                .await
-                .map_err(|e| ImapError::ServerError(e.to_string()))?
-                .try_collect::<Vec<_>>()
+            // Read from the BufReader, which may already hold part of the literal
+            let mut literal = vec![0u8; size];
+            stream
+                .read_exact(&mut literal)
                 .await
                 .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            response.push_str(&line[..start]);
+            response.push('"');
+            response.push_str(&escape_imap_quoted(&String::from_utf8_lossy(&literal)));
+            response.push('"');
         }
+    }
 
-        Ok(())
+    /// Items fetched during header sync: UID, FLAGS, ENVELOPE, and BODYSTRUCTURE
+    /// (for attachment detection), plus labels and thread id from Gmail
+    async fn header_fetch_items(&mut self) -> ImapResult<&'static str> {
+        let lean = LEAN_HEADERS.load(Ordering::Relaxed);
+        Ok(match (self.capabilities().await?.gmail, lean) {
+            (true, false) => "(UID FLAGS ENVELOPE BODYSTRUCTURE X-GM-LABELS X-GM-THRID)",
+            (true, true) => "(UID FLAGS ENVELOPE X-GM-LABELS X-GM-THRID)",
+            (false, false) => "(UID FLAGS ENVELOPE BODYSTRUCTURE)",
+            (false, true) => "(UID FLAGS ENVELOPE)",
+        })
     }
 
-    /// Take the session for IDLE operations
-    /// Returns the session, leaving the client disconnected.
-    /// The caller is responsible for IDLE and restoring the session.
-    pub fn take_session(&mut self) -> ImapResult<Session<ImapStream>> {
-        self.session.take().ok_or(ImapError::NotConnected)
+    pub(crate) fn parse_fetch_response(line: &str) -> Option<MessageHeader> {
+        // Very simple parser - extract UID, FLAGS, and basic envelope info
+        let uid = Self::extract_uid(line)?;
+        let flag_strs = Self::extract_flags(line);
+        let flag_refs: Vec<&str> = flag_strs.iter().map(|s| s.as_str()).collect();
+        let flags = MessageFlags::from_imap_flags(&flag_refs);
+        let envelope = Self::extract_envelope(line);
+        let has_attachments = Self::detect_attachments(line);
+
+        Some(MessageHeader {
+            uid,
+            seq: 0, // Not available in simple parser
+            envelope,
+            flags,
+            has_attachments,
+            size: 0,
+            gmail_labels: Self::extract_gmail_labels(line),
+            gmail_thread_id: Self::extract_gmail_thread_id(line),
+        })
     }
 
-    /// Restore a session after IDLE
-    pub fn restore_session(&mut self, session: Session<ImapStream>) {
-        self.session = Some(session);
+    /// Detect attachments from BODYSTRUCTURE in the raw FETCH response.
+    /// Checks the BODYSTRUCTURE portion for:
+    /// 1. Explicit "attachment" disposition
+    /// 2. Non-text/non-multipart MIME primary types (application, image, audio, video)
+    ///    which indicate file attachments even without explicit disposition
+    pub(crate) fn detect_attachments(line: &str) -> bool {
+        // Only search the BODYSTRUCTURE portion to avoid false positives from envelope fields
+        let search_area = if let Some(idx) = line.find("BODYSTRUCTURE ") {
+            &line[idx..]
+        } else {
+            return false;
+        };
+        let lower = search_area.to_ascii_lowercase();
+
+        // Explicit attachment disposition
+        if lower.contains("\"attachment\"") {
+            // But exclude S/MIME signatures even if marked as attachment
+            if lower.contains("\"pkcs7-signature\"") || lower.contains("\"pgp-signature\"")
+                || lower.contains("\"x-pkcs7-signature\"") || lower.contains("\"pkcs7-mime\"")
+            {
+                // Only count as attachment if there are OTHER attachment-like types too
+                return lower.contains("\"image\"") || lower.contains("\"audio\"")
+                    || lower.contains("\"video\"")
+                    || (lower.contains("\"application\"")
+                        && !lower.contains("\"pkcs7-signature\"")
+                        && !lower.contains("\"x-pkcs7-signature\"")
+                        && !lower.contains("\"pgp-signature\"")
+                        && !lower.contains("\"pkcs7-mime\""));
+            }
+            return true;
+        }
+
+        // Non-text MIME types, but exclude S/MIME signatures and inline images
+        // S/MIME: pkcs7-signature, x-pkcs7-signature, pgp-signature, pkcs7-mime
+        // Inline images: have Content-ID (appear as "image" type without "attachment" disposition)
+        if lower.contains("\"application\"") {
+            // Only count application/* as attachment if it's NOT a signature type
+            if !lower.contains("\"pkcs7-signature\"") && !lower.contains("\"x-pkcs7-signature\"")
+                && !lower.contains("\"pgp-signature\"") && !lower.contains("\"pkcs7-mime\"")
+            {
+                return true;
+            }
+        }
+
+        // Images/audio/video: only count if explicitly marked as "attachment" disposition
+        // (inline images with Content-ID should not count as attachments)
+        // Since we already checked for "attachment" above, these would be inline
+        // Only flag if there's no Content-ID-like structure nearby
+        if lower.contains("\"audio\"") || lower.contains("\"video\"") {
+            return true;
+        }
+
+        // For images, only count as attachment if there's an explicit "attachment" disposition
+        // (already handled above). Images without "attachment" are likely inline.
+        false
     }
 
-    /// Recursively check if a BODYSTRUCTURE contains any attachment parts
-    fn bodystructure_has_attachments(bs: &imap_proto::BodyStructure<'_>) -> bool {
-        match bs {
-            imap_proto::BodyStructure::Basic { common, .. } => {
-                let mime_type = common.ty.ty.to_ascii_lowercase();
-                let mime_subtype = common.ty.subtype.to_ascii_lowercase();
+    fn extract_uid(line: &str) -> Option<u32> {
+        // Look for "UID 12345"
+        if let Some(idx) = line.find("UID ") {
+            let rest = &line[idx + 4..];
+            let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            return rest[..end].parse().ok();
+        }
+        None
+    }
 
-                // Skip S/MIME signatures and encrypted containers
-                if mime_type == "application" && (
-                    mime_subtype == "pkcs7-signature"
-                    || mime_subtype == "x-pkcs7-signature"
-                    || mime_subtype == "pgp-signature"
-                    || mime_subtype == "pkcs7-mime"
-                ) {
-                    return false;
+    fn extract_flags(line: &str) -> Vec<String> {
+        // Look for "FLAGS (\Seen \Flagged)"
+        let mut flags = Vec::new();
+        if let Some(start) = line.find("FLAGS (") {
+            let rest = &line[start + 7..];
+            if let Some(end) = rest.find(')') {
+                let flags_str = &rest[..end];
+                for flag in flags_str.split_whitespace() {
+                    flags.push(flag.to_string());
                 }
+            }
+        }
+        flags
+    }
 
-                // Explicit attachment disposition → real attachment
-                if let Some(disp) = &common.disposition {
-                    if disp.ty.eq_ignore_ascii_case("attachment") {
-                        return true;
+    /// Parse `X-GM-LABELS (\Inbox "Project X" Receipts)`. System labels keep
+    /// their backslash; user labels are decoded from modified UTF-7.
+    fn extract_gmail_labels(line: &str) -> Vec<String> {
+        let mut labels = Vec::new();
+        let Some(start) = line.find("X-GM-LABELS (") else {
+            return labels;
+        };
+        let mut chars = line[start + 13..].chars().peekable();
+        loop {
+            match chars.peek() {
+                None | Some(')') => break,
+                Some(c) if c.is_whitespace() => {
+                    chars.next();
+                }
+                Some('"') => {
+                    chars.next();
+                    let mut value = String::new();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => value.extend(chars.next()),
+                            _ => value.push(c),
+                        }
+                    }
+                    labels.push(decode_mailbox_name(&value));
+                }
+                Some(_) => {
+                    let mut value = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == ')' {
+                            break;
+                        }
+                        value.push(c);
+                        chars.next();
                     }
+                    labels.push(decode_mailbox_name(&value));
                 }
+            }
+        }
+        labels
+    }
+
+    fn extract_gmail_thread_id(line: &str) -> Option<u64> {
+        let rest = &line[line.find("X-GM-THRID ")? + 11..];
+        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        rest[..end].parse().ok()
+    }
+
+    fn extract_envelope(line: &str) -> Envelope {
+        // ENVELOPE format: (date subject from sender reply-to to cc bcc in-reply-to message-id)
+        // Each address field is: ((name route mailbox host) ...) or NIL
+        let mut envelope = Envelope::default();
+
+        if let Some(start) = line.find("ENVELOPE (") {
+            let rest = &line[start + 10..];
+
+            // Parse envelope parts properly handling nested parens and quotes
+            let parts = Self::parse_envelope_parts(rest);
 
-                // Images without explicit "attachment" disposition are likely inline
-                if mime_type == "image" {
-                    return false;
+            // parts[0] = date, parts[1] = subject, parts[2] = from addresses
+            if let Some(date) = parts.get(0) {
+                if *date != "NIL" {
+                    envelope.date = Some(date.clone());
                 }
+            }
+            if let Some(subject) = parts.get(1) {
+                if *subject != "NIL" {
+                    envelope.subject = Some(subject.clone());
+                }
+            }
+            // From is the 3rd element (index 2)
+            if let Some(from_str) = parts.get(2) {
+                if let Some(addrs) = Self::parse_address_list_from_envelope(from_str) {
+                    envelope.from = addrs;
+                }
+            }
+            // To is the 6th element (index 5) - ENVELOPE format: (date subject from sender reply-to to ...)
+            if let Some(to_str) = parts.get(5) {
+                if let Some(addrs) = Self::parse_address_list_from_envelope(to_str) {
+                    envelope.to = addrs;
+                }
+            }
+            // CC is the 7th element (index 6)
+            if let Some(cc_str) = parts.get(6) {
+                if let Some(addrs) = Self::parse_address_list_from_envelope(cc_str) {
+                    envelope.cc = addrs;
+                }
+            }
+            // Message-ID is the 10th element (index 9)
+            // ENVELOPE format: (date subject from sender reply-to to cc bcc in-reply-to message-id)
+            if let Some(msg_id) = parts.get(9) {
+                if *msg_id != "NIL" {
+                    envelope.message_id = Some(msg_id.clone());
+                }
+            }
+        }
 
-                // Other non-text types (application/*, audio/*, video/*) are likely attachments
-                true
+        envelope
+    }
+
+    /// Parse envelope parts, handling quoted strings, NIL, and nested parentheses
+    fn parse_envelope_parts(s: &str) -> Vec<String> {
+        let mut parts = Vec::new();
+        let chars: Vec<char> = s.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            // Skip whitespace
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            if i >= chars.len() {
+                break;
             }
-            imap_proto::BodyStructure::Text { common, .. } => {
-                // Text parts are only attachments if explicitly marked as such
-                if let Some(disp) = &common.disposition {
-                    if disp.ty.eq_ignore_ascii_case("attachment") {
-                        return true;
+
+            match chars[i] {
+                '"' => {
+                    // Quoted string — unescape \" and \\
+                    i += 1;
+                    let mut value = String::new();
+                    while i < chars.len() && chars[i] != '"' {
+                        if chars[i] == '\\' && i + 1 < chars.len() {
+                            i += 1; // skip backslash, take next char literally
+                        }
+                        value.push(chars[i]);
+                        i += 1;
                     }
+                    parts.push(value);
+                    i += 1; // Skip closing quote
                 }
-                false
-            }
-            imap_proto::BodyStructure::Message { common, body, .. } => {
-                if let Some(disp) = &common.disposition {
-                    if disp.ty.eq_ignore_ascii_case("attachment") {
-                        return true;
+                '(' => {
+                    // Nested structure (like address list)
+                    let start = i;
+                    let mut depth = 1;
+                    i += 1;
+                    while i < chars.len() && depth > 0 {
+                        match chars[i] {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            '"' => {
+                                // Skip quoted string inside
+                                i += 1;
+                                while i < chars.len() && chars[i] != '"' {
+                                    if chars[i] == '\\' && i + 1 < chars.len() {
+                                        i += 1;
+                                    }
+                                    i += 1;
+                                }
+                            }
+                            _ => {}
+                        }
+                        i += 1;
+                    }
+                    let value: String = chars[start..i].iter().collect();
+                    parts.push(value);
+                }
+                'N' if i + 2 < chars.len() => {
+                    // Check for NIL
+                    let word: String = chars[i..].iter().take(3).collect();
+                    if word == "NIL" {
+                        parts.push("NIL".to_string());
+                        i += 3;
+                    } else {
+                        i += 1;
                     }
                 }
-                Self::bodystructure_has_attachments(body)
+                ')' => {
+                    // End of envelope
+                    break;
+                }
+                _ => {
+                    i += 1;
+                }
+            }
+        }
+
+        parts
+    }
+
+    /// Parse address list from envelope format: ((name route mailbox host) ...)
+    fn parse_address_list_from_envelope(s: &str) -> Option<Vec<EmailAddress>> {
+        if s == "NIL" || s.is_empty() {
+            return None;
+        }
+
+        let mut addresses = Vec::new();
+        let chars: Vec<char> = s.chars().collect();
+        let mut i = 0;
+
+        // Skip outer opening paren
+        while i < chars.len() && chars[i] != '(' {
+            i += 1;
+        }
+        if i >= chars.len() {
+            return None;
+        }
+        i += 1; // Skip first (
+
+        // Now parse each address: (name route mailbox host)
+        while i < chars.len() {
+            // Skip whitespace
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+
+            if i >= chars.len() || chars[i] == ')' {
+                break;
             }
-            imap_proto::BodyStructure::Multipart { common, bodies, .. } => {
-                if let Some(disp) = &common.disposition {
-                    if disp.ty.eq_ignore_ascii_case("attachment") {
-                        return true;
+
+            if chars[i] == '(' {
+                // Parse one address
+                i += 1;
+                let mut addr_parts: Vec<String> = Vec::new();
+
+                // Parse 4 parts: name, route, mailbox, host
+                for _ in 0..4 {
+                    // Skip whitespace
+                    while i < chars.len() && chars[i].is_whitespace() {
+                        i += 1;
+                    }
+
+                    if i >= chars.len() {
+                        break;
+                    }
+
+                    if chars[i] == '"' {
+                        // Quoted string — collect and unescape \" and \\
+                        i += 1;
+                        let mut value = String::new();
+                        while i < chars.len() && chars[i] != '"' {
+                            if chars[i] == '\\' && i + 1 < chars.len() {
+                                i += 1; // skip backslash, take next char literally
+                            }
+                            value.push(chars[i]);
+                            i += 1;
+                        }
+                        addr_parts.push(value);
+                        i += 1;
+                    } else if i + 2 < chars.len() {
+                        let word: String = chars[i..].iter().take(3).collect();
+                        if word == "NIL" {
+                            addr_parts.push("".to_string());
+                            i += 3;
+                        } else {
+                            // Unknown, skip
+                            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != ')' {
+                                i += 1;
+                            }
+                        }
+                    } else {
+                        break;
+                    }
+                }
+
+                // Skip to closing paren of this address
+                while i < chars.len() && chars[i] != ')' {
+                    i += 1;
+                }
+                i += 1; // Skip )
+
+                // addr_parts: [name, route, mailbox, host]
+                if addr_parts.len() >= 4 {
+                    let name = if addr_parts[0].is_empty() { None } else { Some(addr_parts[0].clone()) };
+                    let mailbox = &addr_parts[2];
+                    let host = &addr_parts[3];
+
+                    if !mailbox.is_empty() && !host.is_empty() {
+                        addresses.push(EmailAddress {
+                            name,
+                            address: format!("{}@{}", mailbox, host),
+                        });
+                    } else if mailbox.contains('@') {
+                        // Some servers put full email in mailbox.
+                        // A bare mailbox with NIL host is a group start marker
+                        // (RFC 3501 §7.4.2) and is skipped, as is the end marker.
+                        addresses.push(EmailAddress {
+                            name,
+                            address: mailbox.clone(),
+                        });
                     }
                 }
-                bodies.iter().any(|b| Self::bodystructure_has_attachments(b))
+            } else {
+                i += 1;
             }
         }
+
+        if addresses.is_empty() {
+            None
+        } else {
+            Some(addresses)
+        }
     }
 
-    /// Close the connection
-    pub async fn logout(&mut self) -> ImapResult<()> {
-        if let Some(mut session) = self.session.take() {
-            session
-                .logout()
+    /// Fetch message headers by UID range (uses UID FETCH instead of FETCH)
+    pub async fn uid_fetch_headers(&mut self, range: &str) -> ImapResult<Vec<MessageHeader>> {
+        let mut headers = Vec::new();
+        self.fetch_headers_each(range, true, |h| headers.push(h)).await?;
+        Ok(headers)
+    }
+
+    /// Whether a message is flagged `\Deleted` or being removed from the
+    /// selected folder, on this connection or another (see [`crate::deletion`])
+    fn is_removed(user: Option<&str>, folder: Option<&str>, uid: u32, deleted: bool) -> bool {
+        deleted
+            || matches!(
+                (user, folder),
+                (Some(user), Some(folder)) if crate::deletion::is_being_removed(user, folder, uid)
+            )
+    }
+
+    /// Search the selected folder, returning the UIDs that match `criteria`
+    /// (e.g. `SINCE 1-Jan-2024`)
+    pub async fn uid_search(&mut self, criteria: &str) -> ImapResult<Vec<u32>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} UID SEARCH {}\r\n", tag, criteria);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut uids = Vec::new();
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
                 .await
                 .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "UID SEARCH failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+
+            uids.extend(Self::parse_search_response(&line));
         }
-        Ok(())
+
+        Ok(uids)
+    }
+
+    /// Parse the numbers from an `* SEARCH` line
+    fn parse_search_response(line: &str) -> Vec<u32> {
+        line.strip_prefix("* SEARCH")
+            .map(|rest| rest.split_whitespace().filter_map(|n| n.parse().ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Search the selected folder with Gmail's own query syntax
+    /// (`from:alice has:attachment newer_than:7d`) via X-GM-RAW
+    pub async fn gmail_raw_search(&mut self, query: &str) -> ImapResult<Vec<u32>> {
+        if !self.capabilities().await?.gmail {
+            return Err(ImapError::Unsupported("Gmail search".to_string()));
+        }
+        self.uid_search(&Self::gmail_raw_criteria(query)).await
+    }
+
+    /// `X-GM-RAW` search criteria for a query, declaring UTF-8 when needed
+    fn gmail_raw_criteria(query: &str) -> String {
+        let query = query.trim().replace(['\r', '\n'], " ");
+        let criteria = format!("X-GM-RAW \"{}\"", escape_imap_quoted(&query));
+        if query.is_ascii() {
+            criteria
+        } else {
+            format!("CHARSET UTF-8 {}", criteria)
+        }
+    }
+
+    /// Fetch flags for all messages by UID range, keywords included
+    /// Returns Vec<(uid, flags)>
+    pub async fn uid_fetch_flags(&mut self, range: &str) -> ImapResult<Vec<(u32, MessageFlags)>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} UID FETCH {} (UID FLAGS)\r\n", tag, range);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut results = Vec::new();
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            if line.starts_with(&tag) {
+                break;
+            }
+
+            if line.starts_with("* ") && line.contains("FETCH") {
+                if let Some(uid) = Self::extract_uid(&line) {
+                    let flag_strs = Self::extract_flags(&line);
+                    let flag_refs: Vec<&str> = flag_strs.iter().map(String::as_str).collect();
+                    results.push((uid, MessageFlags::from_imap_flags(&flag_refs)));
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Message-ID headers of every message in the selected folder, for
+    /// telling which messages it already holds. Messages without one are
+    /// left out.
+    pub async fn fetch_message_ids(&mut self) -> ImapResult<Vec<String>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} UID FETCH 1:* (UID BODY.PEEK[HEADER.FIELDS (MESSAGE-ID)])\r\n", tag);
+
+        let stream = self.stream.as_mut().ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut ids = Vec::new();
+        loop {
+            let response = Self::read_response(stream).await?;
+
+            if response.starts_with(&tag) {
+                if !response.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "UID FETCH failed: {}",
+                        response.trim()
+                    )));
+                }
+                break;
+            }
+
+            if response.starts_with("* ") && response.contains("FETCH") {
+                if let Some(id) = Self::extract_message_id_header(&response) {
+                    ids.push(id);
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// The Message-ID header's value in a fetched
+    /// `BODY[HEADER.FIELDS (MESSAGE-ID)]`, unfolded. The header block arrives
+    /// as a literal, which [`Self::read_response`] inlines as a quoted string.
+    fn extract_message_id_header(response: &str) -> Option<String> {
+        const NAME: &str = "message-id:";
+        let start = response.to_ascii_lowercase().find(NAME)? + NAME.len();
+        let mut lines = response[start..].split("\r\n");
+        let mut value = lines.next()?.to_string();
+        for line in lines.take_while(|line| line.starts_with([' ', '\t'])) {
+            value.push_str(line);
+        }
+        let value = value.replace("\\\"", "\"").replace("\\\\", "\\");
+        let value = value.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    }
+
+    /// Fetch message body by UID
+    pub async fn fetch_body(&mut self, uid: u32) -> ImapResult<String> {
+        let body = String::from_utf8_lossy(&self.fetch_raw(uid).await?).into_owned();
+        debug!("Fetched body: {} bytes", body.len());
+        Ok(body)
+    }
+
+    /// Fetch the raw RFC 822 message by UID, without marking it read
+    pub async fn fetch_raw(&mut self, uid: u32) -> ImapResult<Vec<u8>> {
+        use async_std::future::timeout;
+        use async_std::io::ReadExt;
+
+        let mut body = match self.fetch_raw_stream(uid).await {
+            Ok(body) => body,
+            // Nothing to fetch: the message is gone from the folder
+            Err(ImapError::MessageNotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut body_bytes = Vec::with_capacity(body.len() as usize);
+        match timeout(Duration::from_secs(60), body.read_to_end(&mut body_bytes)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                return Err(ImapError::ServerError(format!("Failed to read literal: {}", e)));
+            }
+            Err(_) => {
+                return Err(ImapError::ServerError("Timeout reading message body".to_string()));
+            }
+        }
+        body.finish().await?;
+
+        debug!("fetch_body: read {} bytes of literal data", body_bytes.len());
+        Ok(body_bytes)
+    }
+
+    /// Start fetching the raw RFC 822 message by UID, without marking it read,
+    /// and return a reader over the body literal as it arrives from the server.
+    /// Callers can decode or write the message out in chunks instead of holding
+    /// it whole. Call [`BodyStream::finish`] when done; dropping the reader
+    /// early leaves the response half-read, so the connection is closed.
+    pub async fn fetch_raw_stream(&mut self, uid: u32) -> ImapResult<BodyStream<'_>> {
+        use async_std::future::timeout;
+
+        let tag = self.next_tag();
+        // Use BODY.PEEK[] to avoid marking the message as read
+        let cmd = format!("{} UID FETCH {} BODY.PEEK[]\r\n", tag, uid);
+
+        debug!("fetch_body: sending command: {} UID FETCH {} BODY.PEEK[]", tag, uid);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let read_timeout = Duration::from_secs(30);
+
+        loop {
+            let mut line = String::new();
+            let read_result = timeout(read_timeout, stream.read_line(&mut line)).await;
+            match read_result {
+                Ok(Ok(0)) => {
+                    return Err(ImapError::ServerError("Connection closed".to_string()));
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    return Err(ImapError::ServerError(format!("Read error: {}", e)));
+                }
+                Err(_) => {
+                    return Err(ImapError::ServerError(format!(
+                        "Timeout waiting for response to UID FETCH {} - message may not exist",
+                        uid
+                    )));
+                }
+            }
+
+            debug!("fetch_body: received line: {} chars, starts with: '{}'",
+                   line.len(),
+                   line.chars().take(60).collect::<String>().replace('\r', "\\r").replace('\n', "\\n"));
+
+            // Completed without a body: no message with this UID
+            if line.starts_with(&tag) {
+                return Err(ImapError::MessageNotFound(uid));
+            }
+
+            // Literal start: * N FETCH (BODY[] {SIZE}
+            if let Some((_, size)) = literal_size(&line) {
+                debug!("fetch_body: streaming literal of {} bytes", size);
+                return Ok(BodyStream {
+                    stream: &mut self.stream,
+                    tag,
+                    len: size as u64,
+                    remaining: size as u64,
+                    finished: false,
+                });
+            }
+        }
+    }
+
+    /// Fetch the MIME structure of a message by UID, as a flat list of leaf parts
+    pub async fn uid_fetch_bodystructure(&mut self, uid: u32) -> ImapResult<Vec<BodyPart>> {
+        let response = self
+            .uid_fetch_raw(&format!("UID FETCH {} (BODYSTRUCTURE)", uid))
+            .await?;
+
+        let start = find_bytes(&response, b"BODYSTRUCTURE ")
+            .ok_or_else(|| ImapError::ParseError(format!("No BODYSTRUCTURE for UID {}", uid)))?;
+        let text = String::from_utf8_lossy(&response[start + b"BODYSTRUCTURE ".len()..]);
+        crate::parse_bodystructure(&text)
+            .ok_or_else(|| ImapError::ParseError(format!("Invalid BODYSTRUCTURE for UID {}", uid)))
+    }
+
+    /// Fetch a single body part (`BODY.PEEK[section]`) by UID.
+    /// Returns the raw, still transfer-encoded bytes; see [`BodyPart::decode`].
+    pub async fn uid_fetch_section(&mut self, uid: u32, section: &str) -> ImapResult<Vec<u8>> {
+        let response = self
+            .uid_fetch_raw(&format!("UID FETCH {} BODY.PEEK[{}]", uid, section))
+            .await?;
+
+        let marker = format!("BODY[{}]", section);
+        let Some(pos) = find_bytes(&response, marker.as_bytes()) else {
+            return Err(ImapError::MessageNotFound(uid));
+        };
+        let rest = &response[pos + marker.len()..];
+        let rest = rest.strip_prefix(b" ").unwrap_or(rest);
+
+        match rest.first() {
+            Some(b'{') => {
+                let close = rest.iter().position(|&c| c == b'}')
+                    .ok_or_else(|| ImapError::ParseError("Unterminated literal".to_string()))?;
+                let len: usize = std::str::from_utf8(&rest[1..close])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| ImapError::ParseError("Invalid literal size".to_string()))?;
+                let data_start = close + 3; // skip "}\r\n"
+                let data_end = (data_start + len).min(rest.len());
+                Ok(rest.get(data_start..data_end).unwrap_or_default().to_vec())
+            }
+            Some(b'"') => {
+                let end = rest[1..].iter().position(|&c| c == b'"').unwrap_or(rest.len() - 1);
+                Ok(rest[1..end + 1].to_vec())
+            }
+            // NIL: the part is empty
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Send a UID FETCH command and collect the untagged response bytes,
+    /// with literals spliced in place (`{n}\r\n` followed by the n bytes)
+    async fn uid_fetch_raw(&mut self, command: &str) -> ImapResult<Vec<u8>> {
+        use async_std::future::timeout;
+        use async_std::io::ReadExt;
+
+        let tag = self.next_tag();
+        let cmd = format!("{} {}\r\n", tag, command);
+        let read_timeout = Duration::from_secs(60);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut response = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            timeout(read_timeout, stream.read_until(b'\n', &mut line))
+                .await
+                .map_err(|_| ImapError::ServerError(format!("Timeout waiting for {}", command)))?
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            if line.is_empty() {
+                return Err(ImapError::ServerError("Connection closed".to_string()));
+            }
+
+            if line.starts_with(tag.as_bytes()) {
+                let status = String::from_utf8_lossy(&line);
+                debug!("{} response: {}", command, status.trim());
+                if !status.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "{} failed: {}",
+                        command,
+                        status.trim()
+                    )));
+                }
+                break;
+            }
+
+            response.extend_from_slice(&line);
+
+            // A line ending in {n} announces an n-byte literal
+            let trimmed = line.strip_suffix(b"\r\n").unwrap_or(&line);
+            if trimmed.ends_with(b"}") {
+                if let Some(open) = trimmed.iter().rposition(|&c| c == b'{') {
+                    if let Some(len) = std::str::from_utf8(&trimmed[open + 1..trimmed.len() - 1])
+                        .ok()
+                        .and_then(|n| n.parse::<usize>().ok())
+                    {
+                        let mut literal = vec![0u8; len];
+                        timeout(read_timeout, stream.read_exact(&mut literal))
+                            .await
+                            .map_err(|_| ImapError::ServerError("Timeout reading literal".to_string()))?
+                            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+                        response.extend_from_slice(&literal);
+                    }
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// List folders, including the mailboxes of other users and shared
+    /// mailboxes when the server reports their namespaces
+    pub async fn list_folders(&mut self) -> ImapResult<Vec<Folder>> {
+        let mut folders = self.list_pattern("*").await?;
+
+        if self.capabilities().await?.namespace {
+            match self.namespaces().await {
+                Ok(namespaces) => self.add_shared_folders(&namespaces, &mut folders).await?,
+                Err(e) => debug!("NAMESPACE failed, listing own folders only: {}", e),
+            }
+        }
+
+        FolderType::deduplicate_folder_types(&mut folders);
+        Ok(folders)
+    }
+
+    /// Folders matching a LIST pattern, e.g. `*` or `Shared/*`
+    async fn list_pattern(&mut self, pattern: &str) -> ImapResult<Vec<Folder>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} LIST \"\" \"{}\"\r\n", tag, escape_imap_quoted(pattern));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut folders = Vec::new();
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("LIST response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                break;
+            }
+
+            // Parse LIST response: * LIST (\HasNoChildren) "/" "INBOX"
+            if line.starts_with("* LIST ") {
+                if let Some(folder) = Self::parse_list_response(&line) {
+                    folders.push(folder);
+                }
+            }
+        }
+
+        Ok(folders)
+    }
+
+    /// Add the folders of other users' and shared namespaces that `LIST "" *`
+    /// left out (many servers only list the personal namespace there), each
+    /// namespace under a container of its own so it shows as a separate tree.
+    /// Shared folders never take a special role like Sent or Trash.
+    async fn add_shared_folders(
+        &mut self,
+        namespaces: &Namespaces,
+        folders: &mut Vec<Folder>,
+    ) -> ImapResult<()> {
+        for namespace in namespaces.shared_roots() {
+            let pattern = format!("{}*", namespace.prefix);
+            for folder in self.list_pattern(&pattern).await? {
+                if !folders.iter().any(|f| f.full_path == folder.full_path) {
+                    folders.push(folder);
+                }
+            }
+
+            let root = namespace.root();
+            let has_children = folders.iter().any(|f| f.full_path.starts_with(&namespace.prefix));
+            if has_children && !folders.iter().any(|f| f.full_path == root) {
+                folders.push(Folder::new(
+                    root.to_string(),
+                    root.to_string(),
+                    namespace.delimiter,
+                    vec!["\\Noselect".to_string(), "\\HasChildren".to_string()],
+                ));
+            }
+        }
+
+        for folder in folders.iter_mut() {
+            if namespaces.is_shared(&folder.full_path) {
+                folder.folder_type = FolderType::Other;
+            }
+        }
+        Ok(())
+    }
+
+    /// List the paths of subscribed folders. Uses `LIST (SUBSCRIBED)` when the
+    /// server supports LIST-EXTENDED, otherwise `LSUB`.
+    pub async fn list_subscribed_folders(&mut self) -> ImapResult<Vec<String>> {
+        let extended = self.capabilities().await?.list_extended;
+        let tag = self.next_tag();
+        let cmd = if extended {
+            format!("{} LIST (SUBSCRIBED) \"\" \"*\"\r\n", tag)
+        } else {
+            format!("{} LSUB \"\" \"*\"\r\n", tag)
+        };
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut paths = Vec::new();
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("Subscribed LIST response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "Listing subscribed folders failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+
+            if let Some(folder) = Self::parse_lsub_response(&line) {
+                paths.push(folder.full_path);
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Parse an `* LSUB` line, or an `* LIST` line from `LIST (SUBSCRIBED)`
+    fn parse_lsub_response(line: &str) -> Option<Folder> {
+        match line.strip_prefix("* LSUB ") {
+            Some(rest) => Self::parse_list_response(&format!("* LIST {}", rest)),
+            None if line.starts_with("* LIST ") => Self::parse_list_response(line),
+            None => None,
+        }
+    }
+
+    fn parse_list_response(line: &str) -> Option<Folder> {
+        // Format: * LIST (\attr1 \attr2) "delimiter" "folder name"
+        //     or: * LIST (\attr1 \attr2) NIL "folder name"
+        let rest = line.strip_prefix("* LIST ")?;
+
+        // Extract attributes between ( and )
+        let attr_start = rest.find('(')?;
+        let attr_end = rest.find(')')?;
+        let attrs_str = &rest[attr_start + 1..attr_end];
+        let attributes: Vec<String> = attrs_str
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+
+        // Everything after the closing paren: e.g. ` "/" "INBOX"` or ` NIL "INBOX"`
+        let after_attrs = rest[attr_end + 1..].trim();
+
+        // Parse delimiter and find where folder name starts
+        let (delimiter, folder_part) = if after_attrs.starts_with("NIL") {
+            (None, after_attrs[3..].trim())
+        } else if after_attrs.starts_with('"') {
+            // Delimiter is a quoted single character like "/"
+            let delim_char = after_attrs.chars().nth(1);
+            // Skip past the delimiter string (e.g. `"/"`) to get to the folder name
+            if let Some(close) = after_attrs[1..].find('"') {
+                (delim_char, after_attrs[close + 2..].trim())
+            } else {
+                (None, after_attrs)
+            }
+        } else {
+            (None, after_attrs)
+        };
+
+        // Extract folder name from the remaining part — should be a quoted string
+        let folder_name = if folder_part.starts_with('"') {
+            // Find the closing quote (handle escaped quotes)
+            let inner = &folder_part[1..];
+            let mut end = 0;
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                if c == '\\' {
+                    // Skip escaped character
+                    chars.next();
+                    end += 2;
+                } else if c == '"' {
+                    break;
+                } else {
+                    end += c.len_utf8();
+                }
+            }
+            &inner[..end]
+        } else {
+            // Unquoted folder name (some servers do this)
+            folder_part.trim()
+        };
+
+        // Skip empty or root-delimiter-only folder names
+        if folder_name.is_empty()
+            || (folder_name.len() == 1 && delimiter == Some(folder_name.chars().next().unwrap_or('/')))
+        {
+            debug!("Skipping root/empty folder: {:?}", folder_name);
+            return None;
+        }
+
+        // \Noselect containers are kept so their children can be shown as a
+        // tree, but never get a special folder type. The path stays in its
+        // raw modified UTF-7 form for commands; the name is for display.
+        let display_path = decode_mailbox_name(folder_name);
+        let mut folder = Folder {
+            name: display_path
+                .split(delimiter.unwrap_or('/'))
+                .last()
+                .unwrap_or(&display_path)
+                .to_string(),
+            full_path: folder_name.to_string(),
+            folder_type: FolderType::from_attributes_and_name(&attributes, &display_path),
+            delimiter,
+            attributes,
+            uidvalidity: None,
+            message_count: None,
+            unread_count: None,
+            uid_next: None,
+        };
+        if !folder.is_selectable() {
+            folder.folder_type = FolderType::Other;
+        }
+        Some(folder)
+    }
+
+    /// Get STATUS for a folder (MESSAGES and UNSEEN counts)
+    /// Returns (message_count, unseen_count)
+    pub async fn folder_status(&mut self, folder: &str) -> ImapResult<(u32, u32)> {
+        let tag = self.next_tag();
+        let cmd = format!("{} STATUS \"{}\" (MESSAGES UNSEEN)\r\n", tag, folder);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut messages = 0u32;
+        let mut unseen = 0u32;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("STATUS response: {}", line.trim());
+
+            // Parse: * STATUS "folder" (MESSAGES 42 UNSEEN 5)
+            if line.starts_with("* STATUS ") {
+                if let Some(paren_start) = line.rfind('(') {
+                    let status_data = &line[paren_start + 1..];
+                    let parts: Vec<&str> = status_data.split_whitespace().collect();
+                    for i in (0..parts.len()).step_by(2) {
+                        if i + 1 < parts.len() {
+                            let val = parts[i + 1].trim_end_matches(')').parse().unwrap_or(0);
+                            match parts[i] {
+                                "MESSAGES" => messages = val,
+                                "UNSEEN" => unseen = val,
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+
+            if line.starts_with(&tag) {
+                break;
+            }
+        }
+
+        Ok((messages, unseen))
+    }
+
+    /// Pipelined batch STATUS for multiple folders.
+    /// Sends ALL STATUS commands before reading any responses.
+    /// For N folders: N sequential round trips → 1 pipelined batch.
+    /// Returns Vec<(folder_path, message_count, unseen_count)>.
+    pub async fn batch_folder_status(&mut self, folders: &[&str]) -> ImapResult<Vec<(String, u32, u32)>> {
+        if folders.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        // Phase 1: Send all STATUS commands without waiting for responses
+        let mut tags: Vec<(String, String)> = Vec::with_capacity(folders.len()); // (tag, folder_path)
+        for folder in folders {
+            self.tag_counter += 1;
+            let tag = format!("A{:04}", self.tag_counter);
+            let cmd = format!("{} STATUS \"{}\" (MESSAGES UNSEEN)\r\n", tag, folder);
+            stream
+                .get_mut()
+                .write_all(cmd.as_bytes())
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            tags.push((tag, folder.to_string()));
+        }
+
+        // Flush all commands at once
+        stream
+            .get_mut()
+            .flush()
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        // Phase 2: Read all responses in tag order.
+        // IMAP processes pipelined commands sequentially (RFC 3501 §3),
+        // so the * STATUS response before each tagged OK belongs to that command.
+        // This avoids case-sensitivity issues (e.g. Outlook returns "Inbox" not "INBOX").
+        let mut results: Vec<(String, u32, u32)> = Vec::with_capacity(folders.len());
+        let mut completed_tags = 0;
+        // Accumulate the most recent STATUS data seen before each tagged response
+        let mut pending_messages = 0u32;
+        let mut pending_unseen = 0u32;
+        let mut got_status = false;
+
+        while completed_tags < tags.len() {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("Batch STATUS response: {}", line.trim());
+
+            // Parse: * STATUS "folder" (MESSAGES 42 UNSEEN 5)
+            if line.starts_with("* STATUS ") {
+                if let Some((_, messages, unseen)) = Self::parse_status_line(&line) {
+                    pending_messages = messages;
+                    pending_unseen = unseen;
+                    got_status = true;
+                }
+            }
+
+            // Check if this is a tagged response (OK or BAD)
+            let (tag, folder_path) = &tags[completed_tags];
+            if line.starts_with(tag.as_str()) {
+                if got_status {
+                    results.push((folder_path.clone(), pending_messages, pending_unseen));
+                } else {
+                    // BAD or no STATUS line — use (0, 0)
+                    results.push((folder_path.clone(), 0, 0));
+                }
+                completed_tags += 1;
+                got_status = false;
+                pending_messages = 0;
+                pending_unseen = 0;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Parse a STATUS response line: * STATUS "folder" (MESSAGES 42 UNSEEN 5)
+    fn parse_status_line(line: &str) -> Option<(String, u32, u32)> {
+        // Extract folder name (quoted)
+        let after_status = line.strip_prefix("* STATUS ")?;
+
+        // Folder name can be quoted or unquoted
+        let (folder_name, rest) = if after_status.starts_with('"') {
+            let inner = &after_status[1..];
+            let end = inner.find('"')?;
+            (&inner[..end], &inner[end + 1..])
+        } else {
+            let end = after_status.find(' ')?;
+            (&after_status[..end], &after_status[end..])
+        };
+
+        // Parse counts from (MESSAGES N UNSEEN M)
+        let mut messages = 0u32;
+        let mut unseen = 0u32;
+        if let Some(paren_start) = rest.rfind('(') {
+            let data = &rest[paren_start + 1..];
+            let parts: Vec<&str> = data.split_whitespace().collect();
+            for i in (0..parts.len()).step_by(2) {
+                if i + 1 < parts.len() {
+                    let val = parts[i + 1].trim_end_matches(')').parse().unwrap_or(0);
+                    match parts[i] {
+                        "MESSAGES" => messages = val,
+                        "UNSEEN" => unseen = val,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        Some((folder_name.to_string(), messages, unseen))
+    }
+
+    /// Check if connection is alive with NOOP
+    pub async fn noop(&mut self) -> ImapResult<()> {
+        if self.stream.is_none() {
+            return Err(ImapError::NotConnected);
+        }
+
+        let tag = self.next_tag();
+        let cmd = format!("{} NOOP\r\n", tag);
+
+        let stream = self.stream.as_mut().unwrap();
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        // Read response
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            if line.starts_with(&tag) {
+                if line.contains("OK") {
+                    return Ok(());
+                } else {
+                    return Err(ImapError::ServerError(format!(
+                        "NOOP failed: {}",
+                        line.trim()
+                    )));
+                }
+            }
+        }
+    }
+
+    /// NOOP with a deadline. A connection that doesn't answer in time is
+    /// dropped, so a socket that died silently (e.g. across a suspend) fails
+    /// fast instead of hanging the next command.
+    pub async fn keepalive(&mut self) -> ImapResult<()> {
+        match async_std::future::timeout(KEEPALIVE_TIMEOUT, self.noop()).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.stream = None;
+                Err(ImapError::Timeout)
+            }
+        }
+    }
+
+    /// Check if the client has a connection
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// APPEND a message to a folder, returning the new UID if the server provides APPENDUID
+    pub async fn append(
+        &mut self,
+        folder: &str,
+        flags: &[&str],
+        message_data: &[u8],
+    ) -> ImapResult<Option<AppendUid>> {
+        let tag = self.next_tag();
+        let flags_str = if flags.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", flags.join(" "))
+        };
+        let cmd = format!(
+            "{} APPEND \"{}\"{} {{{}}}\r\n",
+            tag,
+            escape_imap_quoted(folder),
+            flags_str,
+            message_data.len()
+        );
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        // Send the APPEND command with literal size
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        // Wait for continuation response "+ "
+        let mut line = String::new();
+        stream
+            .read_line(&mut line)
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        debug!("APPEND continuation: {}", line.trim());
+
+        if !line.starts_with('+') {
+            return Err(ImapError::ServerError(format!(
+                "Expected continuation, got: {}",
+                line.trim()
+            )));
+        }
+
+        // Send the literal message data followed by CRLF
+        stream
+            .get_mut()
+            .write_all(message_data)
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+        stream
+            .get_mut()
+            .write_all(b"\r\n")
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        // Read the tagged response
+        let uid;
+        loop {
+            let mut resp = String::new();
+            stream
+                .read_line(&mut resp)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("APPEND response: {}", resp.trim());
+
+            if resp.starts_with(&tag) {
+                if !resp.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "APPEND failed: {}",
+                        resp.trim()
+                    )));
+                }
+                // Parse [APPENDUID uidvalidity uid] from OK response (UIDPLUS)
+                uid = AppendUid::parse(&resp);
+                break;
+            }
+        }
+
+        debug!("APPEND successful, uid: {:?}", uid);
+        Ok(uid)
+    }
+
+    /// APPEND several messages to a folder in one command (MULTIAPPEND,
+    /// RFC 3502), each with its own flags. The server stores all of them or
+    /// none, so a batch that failed can be sent again whole.
+    pub async fn append_many(&mut self, folder: &str, messages: &[(&[&str], &[u8])]) -> ImapResult<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        if !self.capabilities().await?.multiappend {
+            return Err(ImapError::Unsupported("MULTIAPPEND".to_string()));
+        }
+
+        let tag = self.next_tag();
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        for (i, (flags, message_data)) in messages.iter().enumerate() {
+            let flags_str = if flags.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", flags.join(" "))
+            };
+            // Each further message follows the previous literal on the same line
+            let cmd = if i == 0 {
+                format!(
+                    "{} APPEND \"{}\"{} {{{}}}\r\n",
+                    tag,
+                    escape_imap_quoted(folder),
+                    flags_str,
+                    message_data.len()
+                )
+            } else {
+                format!("{} {{{}}}\r\n", flags_str, message_data.len())
+            };
+            stream
+                .get_mut()
+                .write_all(cmd.as_bytes())
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            if !line.starts_with('+') {
+                return Err(ImapError::ServerError(format!(
+                    "APPEND failed at message {}: {}",
+                    i + 1,
+                    line.trim()
+                )));
+            }
+
+            stream
+                .get_mut()
+                .write_all(message_data)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+        }
+        stream
+            .get_mut()
+            .write_all(b"\r\n")
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut resp = String::new();
+            stream
+                .read_line(&mut resp)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("MULTIAPPEND response: {}", resp.trim());
+
+            if resp.starts_with(&tag) {
+                if !resp.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "APPEND failed: {}",
+                        resp.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        debug!("MULTIAPPEND of {} messages successful", messages.len());
+        Ok(())
+    }
+
+    /// Add or remove flags on a set of messages with a single UID STORE.
+    /// `add` = true for +FLAGS, false for -FLAGS
+    pub async fn store_flags(&mut self, uids: &[u32], flags: &str, add: bool) -> ImapResult<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        let op = if add { "+" } else { "-" };
+        // .SILENT spares us an untagged FETCH per message
+        self.uid_command(
+            &format!("UID STORE {} {}FLAGS.SILENT ({})", format_uid_set(uids), op, flags),
+            "UID STORE",
+            0,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Add or remove Gmail labels on a set of messages (X-GM-LABELS), which
+    /// Gmail shows as tags and as the label's folder
+    pub async fn store_gmail_labels(&mut self, uids: &[u32], labels: &[String], add: bool) -> ImapResult<()> {
+        if uids.is_empty() || labels.is_empty() {
+            return Ok(());
+        }
+        let op = if add { "+" } else { "-" };
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|label| format!("\"{}\"", escape_imap_quoted(&encode_mailbox_name(label))))
+            .collect();
+        self.uid_command(
+            &format!("UID STORE {} {}X-GM-LABELS.SILENT ({})", format_uid_set(uids), op, labels.join(" ")),
+            "UID STORE",
+            0,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Copy a message to another folder by UID.
+    /// Returns the COPYUID mapping if the server supports UIDPLUS.
+    pub async fn uid_copy(&mut self, uid: u32, dest_folder: &str) -> ImapResult<Option<CopyUid>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} UID COPY {} \"{}\"\r\n", tag, uid, escape_imap_quoted(dest_folder));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("UID COPY response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "UID COPY failed: {}",
+                        line.trim()
+                    )));
+                }
+                return Ok(CopyUid::parse(&line, 1));
+            }
+        }
+    }
+
+    /// Expunge deleted messages from the current folder
+    pub async fn expunge(&mut self) -> ImapResult<()> {
+        let tag = self.next_tag();
+        let cmd = format!("{} EXPUNGE\r\n", tag);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("EXPUNGE response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "EXPUNGE failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expunge messages already flagged `\Deleted`. With UIDPLUS only these
+    /// messages go (UID EXPUNGE); without it EXPUNGE removes every `\Deleted`
+    /// message in the folder.
+    pub async fn uid_expunge(&mut self, uids: &[u32]) -> ImapResult<()> {
+        if self.capabilities().await?.uidplus {
+            self.uid_command(&format!("UID EXPUNGE {}", format_uid_set(uids)), "UID EXPUNGE", 0)
+                .await?;
+            Ok(())
+        } else {
+            self.expunge().await
+        }
+    }
+
+    /// Flag messages `\Deleted` and expunge them
+    async fn store_deleted_and_expunge(&mut self, uids: &[u32]) -> ImapResult<()> {
+        self.uid_command(
+            &format!("UID STORE {} +FLAGS.SILENT (\\Deleted)", format_uid_set(uids)),
+            "UID STORE",
+            0,
+        )
+        .await?;
+        self.uid_expunge(uids).await
+    }
+
+    /// Delete messages from the selected folder: move them to `trash`, or
+    /// flag and expunge them when they are already in Trash or the account
+    /// has none (see [`crate::deletion::delete_action`]). Returns the COPYUID
+    /// mapping when they were moved.
+    pub async fn delete_messages(&mut self, uids: &[u32], trash: Option<&str>) -> ImapResult<Option<CopyUid>> {
+        if uids.is_empty() {
+            return Ok(None);
+        }
+        let folder = self
+            .selected
+            .clone()
+            .ok_or_else(|| ImapError::ServerError("No folder selected".to_string()))?;
+
+        let removal = self.begin_removal(uids);
+        let result = match crate::deletion::delete_action(&folder, trash) {
+            DeleteAction::MoveToTrash(trash) => self.move_uids(uids, &trash).await,
+            DeleteAction::Expunge => self.store_deleted_and_expunge(uids).await.map(|_| None),
+        };
+        self.end_removal(removal, uids);
+        result
+    }
+
+    /// Delete messages from the selected folder for good, wherever it is:
+    /// they're flagged `\Deleted` and expunged without going to Trash
+    pub async fn purge_messages(&mut self, uids: &[u32]) -> ImapResult<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        let removal = self.begin_removal(uids);
+        let result = self.store_deleted_and_expunge(uids).await;
+        self.end_removal(removal, uids);
+        result
+    }
+
+    /// Hide messages from fetches until their removal from the selected folder is settled
+    fn begin_removal(&self, uids: &[u32]) -> Option<(String, String)> {
+        let user = self.user.clone()?;
+        let folder = self.selected.clone()?;
+        crate::deletion::begin_removal(&user, &folder, uids);
+        Some((user, folder))
+    }
+
+    fn end_removal(&self, removal: Option<(String, String)>, uids: &[u32]) {
+        if let Some((user, folder)) = removal {
+            crate::deletion::end_removal(&user, &folder, uids);
+        }
+    }
+
+    /// What the server supports. Taken from the login reply when the server
+    /// sends it there, otherwise CAPABILITY is asked once per connection.
+    pub async fn capabilities(&mut self) -> ImapResult<&Capabilities> {
+        if self.capabilities.is_none() {
+            let tag = self.next_tag();
+            let cmd = format!("{} CAPABILITY\r\n", tag);
+
+            let stream = self
+                .stream
+                .as_mut()
+                .ok_or(ImapError::NotConnected)?;
+
+            stream
+                .get_mut()
+                .write_all(cmd.as_bytes())
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            let mut capabilities = Capabilities::default();
+            loop {
+                let mut line = String::new();
+                stream
+                    .read_line(&mut line)
+                    .await
+                    .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+                debug!("CAPABILITY response: {}", line.trim());
+
+                if let Some(caps) = Capabilities::parse(&line) {
+                    capabilities = caps;
+                }
+
+                if line.starts_with(&tag) {
+                    if !line.contains("OK") {
+                        return Err(ImapError::ServerError(format!(
+                            "CAPABILITY failed: {}",
+                            line.trim()
+                        )));
+                    }
+                    break;
+                }
+            }
+            self.capabilities = Some(capabilities);
+        }
+
+        Ok(self.capabilities.get_or_insert_with(Capabilities::default))
+    }
+
+    /// Check whether the server advertises a capability (e.g. `COMPRESS=DEFLATE`)
+    /// that has no field of its own in [`Capabilities`]
+    pub async fn has_capability(&mut self, name: &str) -> ImapResult<bool> {
+        Ok(self.capabilities().await?.has(name))
+    }
+
+    /// Where the server keeps personal, other users' and shared mailboxes
+    /// (NAMESPACE)
+    pub async fn namespaces(&mut self) -> ImapResult<Namespaces> {
+        if !self.capabilities().await?.namespace {
+            return Err(ImapError::Unsupported("NAMESPACE".to_string()));
+        }
+        let lines = self.untagged_command("NAMESPACE").await?;
+        lines
+            .iter()
+            .find_map(|line| Namespaces::parse(line))
+            .ok_or_else(|| ImapError::ParseError("No NAMESPACE response".to_string()))
+    }
+
+    /// Who may do what in a folder (GETACL). Needs the administer right.
+    pub async fn get_acl(&mut self, folder: &str) -> ImapResult<Vec<AclEntry>> {
+        if !self.capabilities().await?.acl {
+            return Err(ImapError::Unsupported("ACL".to_string()));
+        }
+        let lines = self
+            .untagged_command(&format!("GETACL \"{}\"", escape_imap_quoted(folder)))
+            .await?;
+        Ok(lines.iter().filter_map(|line| parse_acl(line)).flatten().collect())
+    }
+
+    /// Grant `identifier` exactly `rights` on a folder (SETACL). A leading
+    /// `+` or `-` adds or removes rights instead, as in RFC 4314.
+    pub async fn set_acl(&mut self, folder: &str, identifier: &str, rights: &str) -> ImapResult<()> {
+        if !self.capabilities().await?.acl {
+            return Err(ImapError::Unsupported("ACL".to_string()));
+        }
+        self.untagged_command(&format!(
+            "SETACL \"{}\" \"{}\" \"{}\"",
+            escape_imap_quoted(folder),
+            escape_imap_quoted(identifier),
+            escape_imap_quoted(rights)
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Remove every right `identifier` has on a folder (DELETEACL)
+    pub async fn delete_acl(&mut self, folder: &str, identifier: &str) -> ImapResult<()> {
+        if !self.capabilities().await?.acl {
+            return Err(ImapError::Unsupported("ACL".to_string()));
+        }
+        self.untagged_command(&format!(
+            "DELETEACL \"{}\" \"{}\"",
+            escape_imap_quoted(folder),
+            escape_imap_quoted(identifier)
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// The rights this user has on a folder (MYRIGHTS)
+    pub async fn my_rights(&mut self, folder: &str) -> ImapResult<Rights> {
+        if !self.capabilities().await?.acl {
+            return Err(ImapError::Unsupported("ACL".to_string()));
+        }
+        let lines = self
+            .untagged_command(&format!("MYRIGHTS \"{}\"", escape_imap_quoted(folder)))
+            .await?;
+        lines
+            .iter()
+            .find_map(|line| parse_my_rights(line))
+            .ok_or_else(|| ImapError::ParseError(format!("No MYRIGHTS response for {}", folder)))
+    }
+
+    /// Quota roots covering INBOX with their usage and limits (GETQUOTAROOT).
+    /// Roots the server names without reporting are asked with GETQUOTA.
+    pub async fn get_quota_root(&mut self) -> ImapResult<Vec<Quota>> {
+        if !self.capabilities().await?.quota {
+            return Err(ImapError::Unsupported("QUOTA".to_string()));
+        }
+
+        let mut roots = Vec::new();
+        let mut quotas = Vec::new();
+        for line in self.untagged_command("GETQUOTAROOT INBOX").await? {
+            if let Some(names) = parse_quota_roots(&line) {
+                roots = names;
+            } else if let Some(quota) = Quota::parse(&line) {
+                quotas.push(quota);
+            }
+        }
+
+        for root in roots {
+            if quotas.iter().any(|q| q.root == root) {
+                continue;
+            }
+            quotas.extend(self.get_quota(&root).await?);
+        }
+        Ok(quotas)
+    }
+
+    /// Usage and limits of one quota root (GETQUOTA)
+    pub async fn get_quota(&mut self, root: &str) -> ImapResult<Option<Quota>> {
+        let lines = self
+            .untagged_command(&format!("GETQUOTA \"{}\"", escape_imap_quoted(root)))
+            .await?;
+        Ok(lines.iter().find_map(|line| Quota::parse(line)))
+    }
+
+    /// Send a command and collect its untagged responses, failing unless the
+    /// server completes it with OK
+    async fn untagged_command(&mut self, command: &str) -> ImapResult<Vec<String>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} {}\r\n", tag, command);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("{} response: {}", command, line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "{} failed: {}",
+                        command,
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+            if line.starts_with("* ") {
+                lines.push(line);
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Move messages from the selected folder to another folder.
+    ///
+    /// Uses UID MOVE (RFC 6851) when the server supports it, otherwise falls
+    /// back to UID COPY + STORE \Deleted + (UID) EXPUNGE. Returns the COPYUID
+    /// mapping if the server reports one.
+    pub async fn move_messages(&mut self, uids: &[u32], dest_folder: &str) -> ImapResult<Option<CopyUid>> {
+        if uids.is_empty() {
+            return Ok(None);
+        }
+        let removal = self.begin_removal(uids);
+        let result = self.move_uids(uids, dest_folder).await;
+        self.end_removal(removal, uids);
+        result
+    }
+
+    async fn move_uids(&mut self, uids: &[u32], dest_folder: &str) -> ImapResult<Option<CopyUid>> {
+        let set = format_uid_set(uids);
+        let dest = escape_imap_quoted(dest_folder);
+
+        if self.capabilities().await?.move_ {
+            return self
+                .uid_command(&format!("UID MOVE {} \"{}\"", set, dest), "UID MOVE", uids.len())
+                .await;
+        }
+
+        debug!("Server lacks MOVE, using COPY + EXPUNGE for {}", set);
+        let copy_uid = self
+            .uid_command(&format!("UID COPY {} \"{}\"", set, dest), "UID COPY", uids.len())
+            .await?;
+        self.store_deleted_and_expunge(uids).await?;
+
+        Ok(copy_uid)
+    }
+
+    /// Run a single command and wait for its tagged response, returning any
+    /// COPYUID code seen (tagged for COPY, untagged for MOVE) for at most
+    /// the `copied` messages the command copies
+    async fn uid_command(&mut self, command: &str, label: &str, copied: usize) -> ImapResult<Option<CopyUid>> {
+        let tag = self.next_tag();
+        let cmd = format!("{} {}\r\n", tag, command);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        let mut copy_uid = None;
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("{} response: {}", label, line.trim());
+
+            if let Some(parsed) = CopyUid::parse(&line, copied) {
+                copy_uid = Some(parsed);
+            }
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "{} failed: {}",
+                        label,
+                        line.trim()
+                    )));
+                }
+                return Ok(copy_uid);
+            }
+        }
+    }
+
+    /// Create a new folder (mailbox) on the server
+    /// Empty a folder by marking all messages as \Deleted and expunging
+    pub async fn empty_folder(&mut self, folder_path: &str) -> ImapResult<()> {
+        // Select the folder
+        self.select(folder_path).await?;
+
+        // Mark all messages as \Deleted (1:* means all messages)
+        let tag = self.next_tag();
+        let cmd = format!("{} STORE 1:* +FLAGS (\\Deleted)\r\n", tag);
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("STORE response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    // If folder is empty, STORE 1:* may fail — that's OK
+                    if line.contains("no matching messages") || line.contains("No matching") {
+                        break;
+                    }
+                    return Err(ImapError::ServerError(format!(
+                        "STORE failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        // Expunge to permanently remove
+        self.expunge().await?;
+
+        Ok(())
+    }
+
+    pub async fn create_folder(&mut self, folder_path: &str) -> ImapResult<()> {
+        let tag = self.next_tag();
+        let cmd = format!("{} CREATE \"{}\"\r\n", tag, escape_imap_quoted(folder_path));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("CREATE response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "CREATE failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rename a folder (mailbox) on the server
+    pub async fn rename_folder(&mut self, from: &str, to: &str) -> ImapResult<()> {
+        let tag = self.next_tag();
+        let cmd = format!("{} RENAME \"{}\" \"{}\"\r\n", tag, escape_imap_quoted(from), escape_imap_quoted(to));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("RENAME response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "RENAME failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete a folder (mailbox) from the server
+    pub async fn delete_folder(&mut self, folder_path: &str) -> ImapResult<()> {
+        let tag = self.next_tag();
+        let cmd = format!("{} DELETE \"{}\"\r\n", tag, escape_imap_quoted(folder_path));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("DELETE response: {}", line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "DELETE failed: {}",
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Subscribe to a folder so it appears in LSUB
+    pub async fn subscribe_folder(&mut self, folder_path: &str) -> ImapResult<()> {
+        self.set_subscribed(folder_path, "SUBSCRIBE").await
+    }
+
+    /// Unsubscribe from a folder
+    pub async fn unsubscribe_folder(&mut self, folder_path: &str) -> ImapResult<()> {
+        self.set_subscribed(folder_path, "UNSUBSCRIBE").await
+    }
+
+    async fn set_subscribed(&mut self, folder_path: &str, command: &str) -> ImapResult<()> {
+        let tag = self.next_tag();
+        let cmd = format!("{} {} \"{}\"\r\n", tag, command, escape_imap_quoted(folder_path));
+
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or(ImapError::NotConnected)?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        loop {
+            let mut line = String::new();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            debug!("{} response: {}", command, line.trim());
+
+            if line.starts_with(&tag) {
+                if !line.contains("OK") {
+                    return Err(ImapError::ServerError(format!(
+                        "{} failed: {}",
+                        command,
+                        line.trim()
+                    )));
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Logout
+    /// Enter IDLE mode and wait for server events or timeout
+    ///
+    /// IDLE allows the server to push notifications about mailbox changes.
+    /// The client must call `idle_done()` to exit IDLE mode before sending
+    /// other commands.
+    ///
+    /// # Arguments
+    /// * `timeout` - Maximum time to wait before returning `IdleEvent::Timeout`
+    ///
+    /// # Returns
+    /// The first event received from the server, or `Timeout` if no event
+    /// arrives within the specified duration.
+    pub async fn idle(&mut self, timeout: Duration) -> ImapResult<IdleEvent> {
+        if !self.capabilities().await?.idle {
+            return Err(ImapError::Unsupported("IDLE".to_string()));
+        }
+
+        // Get tag before borrowing stream to avoid borrow checker issues
+        let tag = self.next_tag();
+        let cmd = format!("{} IDLE\r\n", tag);
+
+        let stream = self.stream.as_mut().ok_or_else(|| {
+            ImapError::ServerError("Not connected".to_string())
+        })?;
+
+        stream
+            .get_mut()
+            .write_all(cmd.as_bytes())
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        // Wait for continuation response (+)
+        let mut line = String::new();
+        stream
+            .read_line(&mut line)
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        debug!("IDLE response: {}", line.trim());
+
+        if !line.starts_with('+') {
+            return Err(ImapError::ServerError(format!(
+                "Expected '+' continuation, got: {}",
+                line.trim()
+            )));
+        }
+
+        // Clear line before entering event loop (read_line appends!)
+        line.clear();
+
+        // Now we're in IDLE mode - wait for untagged responses
+        // Use a timeout to allow periodic keepalive
+        let start = std::time::Instant::now();
+        let mut clock = SuspendDetector::new();
+
+        loop {
+            // Check if we've exceeded timeout
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Ok(IdleEvent::Timeout);
+            }
+            if clock.slept() {
+                return Ok(IdleEvent::Suspended);
+            }
+
+            // Calculate remaining timeout, waking up now and then to notice
+            // a suspend (the timer doesn't run while the machine sleeps)
+            let remaining = (timeout - elapsed).min(IDLE_WAKE_CHECK);
+
+            // Try to read a line with timeout
+            let read_future = stream.read_line(&mut line);
+            let result = async_std::future::timeout(remaining, read_future).await;
+
+            match result {
+                Ok(Ok(0)) => {
+                    // Connection closed
+                    return Ok(IdleEvent::ServerBye);
+                }
+                Ok(Ok(_)) => {
+                    let trimmed = line.trim();
+                    info!("IDLE received: {}", trimmed);
+
+                    // Parse untagged response
+                    if trimmed.starts_with("* BYE") {
+                        return Ok(IdleEvent::ServerBye);
+                    } else if let Some(rest) = trimmed.strip_prefix("* ") {
+                        // Parse "* N EXISTS", "* N EXPUNGE", "* N FETCH (FLAGS ...)"
+                        let parts: Vec<&str> = rest.split_whitespace().collect();
+                        if parts.len() >= 2 {
+                            if let Ok(num) = parts[0].parse::<u32>() {
+                                match parts[1].to_uppercase().as_str() {
+                                    "EXISTS" => {
+                                        info!("IDLE: Detected EXISTS event with count {}", num);
+                                        return Ok(IdleEvent::NewMessages(num));
+                                    }
+                                    "RECENT" => {
+                                        // Some servers send RECENT for new mail
+                                        if num > 0 {
+                                            info!("IDLE: Detected RECENT event with count {}", num);
+                                            return Ok(IdleEvent::NewMessages(num));
+                                        }
+                                    }
+                                    "EXPUNGE" => return Ok(IdleEvent::Expunge(num)),
+                                    "FETCH" => {
+                                        // Flags updated
+                                        return Ok(IdleEvent::FlagsChanged);
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                    }
+
+                    // Clear line for next read
+                    line.clear();
+                }
+                Ok(Err(e)) => {
+                    return Err(ImapError::ServerError(format!("Read error: {}", e)));
+                }
+                Err(_) => {
+                    // Wake-up check or overall timeout; the loop decides
+                }
+            }
+        }
+    }
+
+    /// Exit IDLE mode by sending DONE
+    ///
+    /// This must be called before sending any other IMAP commands after `idle()`.
+    pub async fn idle_done(&mut self) -> ImapResult<()> {
+        let stream = self.stream.as_mut().ok_or_else(|| {
+            ImapError::ServerError("Not connected".to_string())
+        })?;
+
+        // Send DONE to exit IDLE
+        stream
+            .get_mut()
+            .write_all(b"DONE\r\n")
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+        // Read the tagged response
+        let mut line = String::new();
+        loop {
+            line.clear();
+            stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+            let trimmed = line.trim();
+            debug!("IDLE DONE response: {}", trimmed);
+
+            // Look for tagged response (OK or error)
+            if trimmed.contains(" OK") || trimmed.contains(" NO") || trimmed.contains(" BAD") {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close the connection
+    pub async fn logout(&mut self) -> ImapResult<()> {
+        let tag = self.next_tag();
+        let cmd = format!("{} LOGOUT\r\n", tag);
+        if let Some(stream) = self.stream.as_mut() {
+            let _ = stream.get_mut().write_all(cmd.as_bytes()).await;
+        }
+        self.stream = None;
+        Ok(())
+    }
+}
+
+impl Default for ImapClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Message body streamed from a `UID FETCH` literal, returned by
+/// [`ImapClient::fetch_raw_stream`]. Reads end at the end of the literal.
+pub struct BodyStream<'a> {
+    stream: &'a mut Option<Stream>,
+    tag: String,
+    len: u64,
+    remaining: u64,
+    finished: bool,
+}
+
+impl BodyStream<'_> {
+    /// Size of the whole message in bytes, as announced by the server
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Skip whatever of the body wasn't read and consume the rest of the
+    /// FETCH response, leaving the connection ready for the next command
+    pub async fn finish(mut self) -> ImapResult<()> {
+        let mut scratch = [0u8; 8192];
+        while self.remaining > 0 {
+            let n = self
+                .read(&mut scratch)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            if n == 0 {
+                break;
+            }
+        }
+
+        let stream = self.stream.as_mut().ok_or(ImapError::NotConnected)?;
+        loop {
+            let mut line = String::new();
+            let n = stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            if n == 0 {
+                return Err(ImapError::ServerError("Connection closed".to_string()));
+            }
+            if line.starts_with(&self.tag) {
+                break;
+            }
+        }
+
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl futures::io::AsyncRead for BodyStream<'_> {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        use std::task::Poll;
+
+        let this = self.get_mut();
+        if this.remaining == 0 || buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let Some(stream) = this.stream.as_mut() else {
+            return Poll::Ready(Err(std::io::ErrorKind::NotConnected.into()));
+        };
+
+        // Read from the BufReader, which may already hold part of the literal
+        let max = buf.len().min(this.remaining as usize);
+        match std::pin::Pin::new(stream).poll_read(cx, &mut buf[..max]) {
+            Poll::Ready(Ok(0)) => Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into())),
+            Poll::Ready(Ok(n)) => {
+                this.remaining -= n as u64;
+                Poll::Ready(Ok(n))
+            }
+            other => other,
+        }
+    }
+}
+
+impl Drop for BodyStream<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // The rest of the response is still on the wire; nothing sent on
+            // this connection would line up with its reply any more
+            *self.stream = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_gmail_inbox() {
+        let line = r#"* LIST (\HasNoChildren) "/" "INBOX""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "INBOX");
+        assert_eq!(folder.name, "INBOX");
+        assert_eq!(folder.delimiter, Some('/'));
+        assert_eq!(folder.folder_type, FolderType::Inbox);
+    }
+
+    #[test]
+    fn test_parse_list_gmail_nested() {
+        let line = r#"* LIST (\HasNoChildren) "/" "[Gmail]/Sent Mail""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "[Gmail]/Sent Mail");
+        assert_eq!(folder.name, "Sent Mail");
+        assert_eq!(folder.folder_type, FolderType::Sent);
+    }
+
+    #[test]
+    fn test_parse_list_outlook_sent_items() {
+        let line = r#"* LIST (\HasNoChildren \Sent) "/" "Sent Items""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "Sent Items");
+        assert_eq!(folder.name, "Sent Items");
+        assert_eq!(folder.folder_type, FolderType::Sent);
+    }
+
+    #[test]
+    fn test_parse_list_outlook_junk() {
+        let line = r#"* LIST (\HasNoChildren \Junk) "/" "Junk Email""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "Junk Email");
+        assert_eq!(folder.name, "Junk Email");
+        assert_eq!(folder.folder_type, FolderType::Spam);
+    }
+
+    #[test]
+    fn test_parse_list_noselect_container() {
+        let line = r#"* LIST (\Noselect \HasChildren) "/" "[Gmail]""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "[Gmail]");
+        assert!(!folder.is_selectable());
+        assert_eq!(folder.folder_type, FolderType::Other);
+    }
+
+    #[test]
+    fn test_parse_lsub_response() {
+        let line = r#"* LSUB (\HasNoChildren) "." "INBOX.Projects""#;
+        let folder = ImapClient::parse_lsub_response(line).unwrap();
+        assert_eq!(folder.full_path, "INBOX.Projects");
+
+        let line = r#"* LIST (\Subscribed \HasNoChildren) "/" "Lists/rust""#;
+        let folder = ImapClient::parse_lsub_response(line).unwrap();
+        assert_eq!(folder.full_path, "Lists/rust");
+
+        assert!(ImapClient::parse_lsub_response("* OK still here").is_none());
+    }
+
+    #[test]
+    fn test_parse_list_utf7_name() {
+        let line = r#"* LIST (\HasNoChildren) "/" "INBOX/&BBAEQARFBDgEMg-""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "INBOX/&BBAEQARFBDgEMg-");
+        assert_eq!(folder.name, "Архив");
+        assert_eq!(folder.display_path(), "INBOX/Архив");
+    }
+
+    #[test]
+    fn test_parse_list_root_slash_skipped() {
+        // Outlook can return the root delimiter as a folder
+        let line = r#"* LIST (\HasNoChildren) "/" "/""#;
+        assert!(ImapClient::parse_list_response(line).is_none());
+    }
+
+    #[test]
+    fn test_parse_list_empty_name_skipped() {
+        let line = r#"* LIST (\Noselect) "/" """#;
+        // Empty names should be filtered
+        assert!(ImapClient::parse_list_response(line).is_none());
+    }
+
+    #[test]
+    fn test_parse_list_nil_delimiter() {
+        let line = r#"* LIST (\HasNoChildren) NIL "INBOX""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "INBOX");
+        assert_eq!(folder.delimiter, None);
+    }
+
+    #[test]
+    fn test_parse_list_dot_delimiter() {
+        // Some servers use "." as delimiter
+        let line = r#"* LIST (\HasNoChildren) "." "INBOX.Sent""#;
+        let folder = ImapClient::parse_list_response(line).unwrap();
+        assert_eq!(folder.full_path, "INBOX.Sent");
+        assert_eq!(folder.name, "Sent");
+        assert_eq!(folder.delimiter, Some('.'));
+    }
+
+    #[test]
+    fn test_parse_search_response() {
+        assert_eq!(ImapClient::parse_search_response("* SEARCH 2 84 882\r\n"), vec![2, 84, 882]);
+        assert!(ImapClient::parse_search_response("* SEARCH\r\n").is_empty());
+        assert!(ImapClient::parse_search_response("* 3 EXISTS\r\n").is_empty());
+    }
+
+    #[test]
+    fn test_gmail_raw_criteria() {
+        assert_eq!(
+            ImapClient::gmail_raw_criteria(" from:bob has:attachment subject:\"q3 plan\" "),
+            r#"X-GM-RAW "from:bob has:attachment subject:\"q3 plan\"""#
+        );
+        assert_eq!(
+            ImapClient::gmail_raw_criteria("from:zoë\r\nA1 LOGOUT"),
+            "CHARSET UTF-8 X-GM-RAW \"from:zoë  A1 LOGOUT\""
+        );
+    }
+
+    #[test]
+    fn test_parse_envelope_address_group() {
+        // "Team: a@x.org, "Doe, Jane" <jane@y.org>;" arrives as group markers
+        let s = r#"((NIL NIL "Team" NIL)(NIL NIL "a" "x.org")("Doe, Jane" NIL "jane" "y.org")(NIL NIL NIL NIL))"#;
+        let addrs = ImapClient::parse_address_list_from_envelope(s).unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(addrs[0].address, "a@x.org");
+        assert_eq!(addrs[1].name.as_deref(), Some("Doe, Jane"));
+        assert_eq!(addrs[1].address, "jane@y.org");
+    }
+
+    #[test]
+    fn test_extract_gmail_attributes() {
+        let line = r#"* 12 FETCH (X-GM-THRID 1278455344230334865 X-GM-LABELS (\Inbox \Important "Project X" Receipts "Caf&AOk-") UID 4 FLAGS (\Seen))"#;
+        assert_eq!(ImapClient::extract_gmail_thread_id(line), Some(1278455344230334865));
+        assert_eq!(
+            ImapClient::extract_gmail_labels(line),
+            vec!["\\Inbox", "\\Important", "Project X", "Receipts", "Café"]
+        );
+        assert_eq!(ImapClient::extract_uid(line), Some(4));
+
+        let plain = "* 1 FETCH (UID 9 FLAGS ())";
+        assert!(ImapClient::extract_gmail_labels(plain).is_empty());
+        assert_eq!(ImapClient::extract_gmail_thread_id(plain), None);
+        assert!(ImapClient::extract_gmail_labels("* 1 FETCH (X-GM-LABELS () UID 9)").is_empty());
+    }
+
+    #[test]
+    fn test_flags_keep_keywords() {
+        let line = r"* 3 FETCH (UID 7 FLAGS (\Seen $Work \Recent $label1 project-x))";
+        let flag_strs = ImapClient::extract_flags(line);
+        let flag_refs: Vec<&str> = flag_strs.iter().map(String::as_str).collect();
+        let flags = MessageFlags::from_imap_flags(&flag_refs);
+        assert!(flags.seen && !flags.flagged);
+        assert_eq!(flags.keywords(), vec!["$Work", "$label1", "project-x"]);
+    }
+
+    /// Transport replaying a canned server script and recording what the
+    /// client sends
+    struct ScriptedTransport {
+        incoming: futures::io::Cursor<Vec<u8>>,
+        sent: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl futures::io::AsyncRead for ScriptedTransport {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::pin::Pin::new(&mut self.incoming).poll_read(cx, buf)
+        }
+    }
+
+    impl futures::io::AsyncWrite for ScriptedTransport {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.sent.lock().unwrap().extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_login_and_pipelined_status_over_transport() {
+        let script = "* OK IMAP4rev1 ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1 UIDPLUS MOVE] Logged in\r\n\
+            * STATUS \"INBOX\" (MESSAGES 42 UNSEEN 5)\r\n\
+            A0002 OK STATUS completed\r\n\
+            A0003 NO Mailbox doesn't exist\r\n";
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.as_bytes().to_vec()),
+            sent: sent.clone(),
+        };
+
+        let mut client = ImapClient::new();
+        async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "se\"cret").await.unwrap();
+            let status = client.batch_folder_status(&["INBOX", "Gone"]).await.unwrap();
+            assert_eq!(
+                status,
+                vec![("INBOX".to_string(), 42, 5), ("Gone".to_string(), 0, 0)]
+            );
+            assert!(client.has_capability("MOVE").await.unwrap());
+        });
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            sent,
+            "A0001 LOGIN \"ann\" \"se\\\"cret\"\r\n\
+             A0002 STATUS \"INBOX\" (MESSAGES UNSEEN)\r\n\
+             A0003 STATUS \"Gone\" (MESSAGES UNSEEN)\r\n"
+        );
+    }
+
+    #[test]
+    fn test_fetch_headers_each_inlines_literals() {
+        let subject = "Re: \"plans\" \\ notes";
+        let script = format!(
+            "* OK IMAP4rev1 ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1] Logged in\r\n\
+            * 1 FETCH (UID 7 FLAGS (\\Seen) ENVELOPE (\"Mon, 1 Jan 2024 10:00:00 +0000\" {{{}}}\r\n{} \
+            ((\"Ann\" NIL \"ann\" \"example.com\")) NIL NIL NIL NIL NIL NIL \"<a@example.com>\") \
+            BODYSTRUCTURE (\"text\" \"plain\" NIL NIL NIL \"7bit\" 5 1))\r\n\
+            * 2 FETCH (UID 8 FLAGS (\\Deleted) ENVELOPE (NIL \"gone\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+            * 3 FETCH (UID 9 FLAGS () ENVELOPE (NIL \"plain\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+            A0002 OK FETCH completed\r\n",
+            subject.len(),
+            subject
+        );
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.into_bytes()),
+            sent: sent.clone(),
+        };
+
+        let mut client = ImapClient::new();
+        let mut headers = Vec::new();
+        async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            client
+                .fetch_headers_each("7:9", true, |h| headers.push(h))
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(headers.iter().map(|h| h.uid).collect::<Vec<_>>(), vec![7, 9]);
+        assert_eq!(headers[0].envelope.subject.as_deref(), Some(subject));
+        assert_eq!(headers[0].envelope.from[0].address, "ann@example.com");
+        assert_eq!(headers[1].envelope.subject.as_deref(), Some("plain"));
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert!(sent.ends_with("A0002 UID FETCH 7:9 (UID FLAGS ENVELOPE BODYSTRUCTURE)\r\n"));
+    }
+
+    #[test]
+    fn test_fetch_header_batch_skips_deleted() {
+        let script = "* OK IMAP4rev1 ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1] Logged in\r\n\
+            * 1 FETCH (UID 7 FLAGS (\\Seen) ENVELOPE (NIL {5}\r\nfirst ((\"Ann\" NIL \"ann\" \"example.com\")) NIL NIL NIL NIL NIL NIL NIL))\r\n\
+            * 2 FETCH (UID 8 FLAGS (\\Deleted) ENVELOPE (NIL \"gone\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+            * 3 FETCH (UID 9 FLAGS () ENVELOPE (NIL \"second\" NIL NIL NIL NIL NIL NIL NIL NIL))\r\n\
+            A0002 OK FETCH completed\r\n";
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.as_bytes().to_vec()),
+            sent: Default::default(),
+        };
+
+        let mut client = ImapClient::new();
+        let mut batch = HeaderBatch::new();
+        let mut progress = Vec::new();
+        async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            client
+                .fetch_header_batch("7:9", true, &mut batch, |n| progress.push(n))
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(progress, vec![1, 2]);
+        assert_eq!(batch.iter().map(|h| h.uid()).collect::<Vec<_>>(), vec![7, 9]);
+        let first = batch.get(0).unwrap();
+        assert_eq!(first.subject(), Some("first"));
+        assert!(first.is_read());
+        assert_eq!(first.from().next().unwrap().address, "ann@example.com");
+        assert_eq!(batch.get(1).unwrap().subject(), Some("second"));
+    }
+
+    #[test]
+    fn test_fetch_raw_stream_reads_body_in_chunks() {
+        let body = "Subject: hi\r\n\r\n".to_string() + &"x".repeat(5000);
+        let script = format!(
+            "* OK IMAP4rev1 ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1] Logged in\r\n\
+            * 3 FETCH (UID 12 BODY[] {{{}}}\r\n{})\r\n\
+            A0002 OK FETCH completed\r\n\
+            * 4 FETCH (UID 13 BODY[] {{{}}}\r\n{})\r\n\
+            A0003 OK FETCH completed\r\n\
+            A0004 OK FETCH completed\r\n\
+            * 5 FETCH (UID 15 BODY[] {{{}}}\r\n{})\r\n\
+            A0005 OK FETCH completed\r\n",
+            body.len(),
+            body,
+            body.len(),
+            body,
+            body.len(),
+            body
+        );
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.into_bytes()),
+            sent: Default::default(),
+        };
+
+        let mut client = ImapClient::new();
+        async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+
+            // Read part of the body in small chunks, then skip the rest
+            let mut stream = client.fetch_raw_stream(12).await.unwrap();
+            assert_eq!(stream.len(), body.len() as u64);
+            let mut chunk = [0u8; 16];
+            stream.read_exact(&mut chunk).await.unwrap();
+            assert_eq!(&chunk, b"Subject: hi\r\n\r\nx");
+            stream.finish().await.unwrap();
+
+            // The connection is still in step for the next command
+            assert_eq!(client.fetch_raw(13).await.unwrap(), body.as_bytes());
+            assert!(client.fetch_raw(14).await.unwrap().is_empty());
+
+            // Dropping an unfinished stream leaves the connection unusable
+            drop(client.fetch_raw_stream(15).await.unwrap());
+            assert!(client.stream.is_none());
+        });
+    }
+
+    #[test]
+    fn test_list_folders_adds_shared_namespaces() {
+        let script = "* OK IMAP4rev1 ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1 NAMESPACE ACL] Logged in\r\n\
+            * LIST (\\HasNoChildren) \"/\" \"INBOX\"\r\n\
+            * LIST (\\HasNoChildren \\Sent) \"/\" \"Sent\"\r\n\
+            A0002 OK LIST completed\r\n\
+            * NAMESPACE ((\"\" \"/\")) ((\"Other Users/\" \"/\")) ((\"Shared/\" \"/\"))\r\n\
+            A0003 OK NAMESPACE completed\r\n\
+            A0004 OK LIST completed\r\n\
+            * LIST (\\HasChildren) \"/\" \"Shared/Team\"\r\n\
+            * LIST (\\HasNoChildren) \"/\" \"Shared/Team/Sent\"\r\n\
+            A0005 OK LIST completed\r\n\
+            * MYRIGHTS \"Shared/Team\" lrs\r\n\
+            A0006 OK MYRIGHTS completed\r\n";
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.as_bytes().to_vec()),
+            sent: sent.clone(),
+        };
+
+        let mut client = ImapClient::new();
+        let (folders, rights) = async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            let folders = client.list_folders().await.unwrap();
+            (folders, client.my_rights("Shared/Team").await.unwrap())
+        });
+
+        let paths: Vec<&str> = folders.iter().map(|f| f.full_path.as_str()).collect();
+        assert_eq!(paths, vec!["INBOX", "Sent", "Shared/Team", "Shared/Team/Sent", "Shared"]);
+        assert_eq!(folders[1].folder_type, FolderType::Sent);
+        // Another team's Sent folder isn't this account's
+        assert_eq!(folders[3].folder_type, FolderType::Other);
+        assert!(!folders[4].is_selectable());
+        assert!(rights.can_read() && rights.can_write());
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert!(sent.contains("A0004 LIST \"\" \"Other Users/*\"\r\nA0005 LIST \"\" \"Shared/*\"\r\n"));
+    }
+
+    #[test]
+    fn test_store_gmail_labels() {
+        let script = "* OK Gimap ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1 X-GM-EXT-1] Logged in\r\n\
+            A0002 OK Success\r\n";
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.as_bytes().to_vec()),
+            sent: sent.clone(),
+        };
+
+        let mut client = ImapClient::new();
+        async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            let labels = vec!["Project X".to_string(), "Café".to_string()];
+            client.store_gmail_labels(&[5, 4], &labels, true).await.unwrap();
+            // Nothing to send for no labels
+            client.store_gmail_labels(&[4], &[], false).await.unwrap();
+        });
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert!(sent.ends_with("A0002 UID STORE 4:5 +X-GM-LABELS.SILENT (\"Project X\" \"Caf&AOk-\")\r\n"));
+    }
+
+    #[test]
+    fn test_append_many() {
+        let script = "* OK ready\r\n\
+            A0001 OK [CAPABILITY IMAP4rev1 MULTIAPPEND] Logged in\r\n\
+            + Ready\r\n\
+            + Ready\r\n\
+            A0002 OK [APPENDUID 7 10:11] APPEND completed\r\n";
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.as_bytes().to_vec()),
+            sent: sent.clone(),
+        };
+
+        let mut client = ImapClient::new();
+        async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            let messages: [(&[&str], &[u8]); 2] = [(&["\\Seen"], b"Hello"), (&[], b"Bye")];
+            client.append_many("Archive", &messages).await.unwrap();
+        });
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert!(sent.ends_with("A0002 APPEND \"Archive\" (\\Seen) {5}\r\nHello {3}\r\nBye\r\n"));
+    }
+
+    #[test]
+    fn test_fetch_message_ids() {
+        let script = "* OK ready\r\n\
+            A0001 OK Logged in\r\n\
+            * 1 FETCH (UID 4 BODY[HEADER.FIELDS (MESSAGE-ID)] {23}\r\nMessage-ID: <a@b.c>\r\n\r\n)\r\n\
+            * 2 FETCH (UID 5 BODY[HEADER.FIELDS (MESSAGE-ID)] {23}\r\nMessage-Id:\r\n <x@y>\r\n\r\n)\r\n\
+            * 3 FETCH (UID 9 BODY[HEADER.FIELDS (MESSAGE-ID)] {2}\r\n\r\n)\r\n\
+            A0002 OK Fetch completed\r\n";
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(script.as_bytes().to_vec()),
+            sent: sent.clone(),
+        };
+
+        let mut client = ImapClient::new();
+        let ids = async_std::task::block_on(async {
+            client.connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            client.fetch_message_ids().await.unwrap()
+        });
+
+        // The folded header is unfolded; the message without one is left out
+        assert_eq!(ids, vec!["<a@b.c>".to_string(), "<x@y>".to_string()]);
     }
 }
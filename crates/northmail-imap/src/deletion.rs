@@ -0,0 +1,93 @@
+//! Delete lifecycle
+//!
+//! Removing a message from a folder (moving it to Trash, archiving, or
+//! deleting it for good) takes a round trip or three, and a sync running on
+//! another connection in the meantime still sees the message. Removals are
+//! recorded here from the moment they are requested until the server
+//! confirms them, and header fetches on every connection leave recorded
+//! messages out, so a message being removed never comes back.
+//!
+//! Which removal a delete means depends on where the message is: outside
+//! Trash it is moved there; in Trash (or on an account without one) it is
+//! flagged `\Deleted` and expunged. Gmail follows the same rule, since
+//! expunging outside `[Gmail]/Trash` only drops a label.
+
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+
+/// What deleting a message from a folder does on the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeleteAction {
+    /// Move to the Trash folder at this path
+    MoveToTrash(String),
+    /// Set `\Deleted` and expunge: gone for good
+    Expunge,
+}
+
+/// How to delete from `folder`, given the account's Trash folder if it has one
+pub fn delete_action(folder: &str, trash: Option<&str>) -> DeleteAction {
+    match trash {
+        Some(trash) if !trash.is_empty() && !trash.eq_ignore_ascii_case(folder) => {
+            DeleteAction::MoveToTrash(trash.to_string())
+        }
+        _ => DeleteAction::Expunge,
+    }
+}
+
+/// Removals in flight: (login user, folder, UID)
+fn pending() -> &'static Mutex<HashSet<(String, String, u32)>> {
+    static PENDING: OnceLock<Mutex<HashSet<(String, String, u32)>>> = OnceLock::new();
+    PENDING.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Record that messages are leaving `folder`, so fetches stop returning them.
+/// `user` is the name the account logs in with.
+pub fn begin_removal(user: &str, folder: &str, uids: &[u32]) {
+    let user = user.to_lowercase();
+    let mut pending = pending().lock().unwrap();
+    pending.extend(uids.iter().map(|uid| (user.clone(), folder.to_string(), *uid)));
+}
+
+/// The server has confirmed (or refused) a removal; stop hiding the messages
+pub fn end_removal(user: &str, folder: &str, uids: &[u32]) {
+    let user = user.to_lowercase();
+    let mut pending = pending().lock().unwrap();
+    for uid in uids {
+        pending.remove(&(user.clone(), folder.to_string(), *uid));
+    }
+}
+
+/// Whether a message is on its way out of `folder`
+pub fn is_being_removed(user: &str, folder: &str, uid: u32) -> bool {
+    pending()
+        .lock()
+        .unwrap()
+        .contains(&(user.to_lowercase(), folder.to_string(), uid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_action() {
+        assert_eq!(
+            delete_action("INBOX", Some("[Gmail]/Trash")),
+            DeleteAction::MoveToTrash("[Gmail]/Trash".to_string())
+        );
+        assert_eq!(delete_action("[Gmail]/Trash", Some("[Gmail]/Trash")), DeleteAction::Expunge);
+        assert_eq!(delete_action("INBOX", None), DeleteAction::Expunge);
+        assert_eq!(delete_action("INBOX", Some("")), DeleteAction::Expunge);
+    }
+
+    #[test]
+    fn test_removal_lifecycle() {
+        begin_removal("Ann@Example.com", "INBOX", &[7, 8]);
+        assert!(is_being_removed("ann@example.com", "INBOX", 7));
+        assert!(!is_being_removed("ann@example.com", "Archive", 7));
+        end_removal("ann@example.com", "INBOX", &[7]);
+        assert!(!is_being_removed("ann@example.com", "INBOX", 7));
+        assert!(is_being_removed("ann@example.com", "INBOX", 8));
+        end_removal("ann@example.com", "INBOX", &[8]);
+    }
+}
@@ -36,6 +36,15 @@ pub enum ImapError {
     #[error("TLS error: {0}")]
     TlsError(String),
 
+    /// The server certificate failed verification and isn't pinned
+    #[error("Untrusted certificate for {host} (SHA-256 {fingerprint}): {reason}")]
+    CertificateUntrusted {
+        host: String,
+        /// SHA-256 fingerprint of the presented certificate, to show and pin
+        fingerprint: String,
+        reason: String,
+    },
+
     /// IO error
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
@@ -44,6 +53,10 @@ pub enum ImapError {
     #[error("IMAP session is not connected")]
     NotConnected,
 
+    /// The server lacks the extension an operation needs
+    #[error("Server does not support {0}")]
+    Unsupported(String),
+
     /// Operation timed out
     #[error("Operation timed out")]
     Timeout,
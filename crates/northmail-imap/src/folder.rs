@@ -1,5 +1,8 @@
 //! IMAP folder types and operations
 
+use crate::utf7::decode_mailbox_name;
+use crate::MessageHeader;
+
 /// Type of email folder
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FolderType {
@@ -112,12 +115,24 @@ impl FolderType {
     }
 }
 
+/// A quick look at a folder's newest messages, taken without selecting it
+/// for sync
+#[derive(Debug, Clone)]
+pub struct FolderPeek {
+    /// Number of messages
+    pub message_count: u32,
+    /// Number of unread messages
+    pub unread_count: u32,
+    /// Headers of the newest messages, newest first
+    pub recent: Vec<MessageHeader>,
+}
+
 /// Represents an IMAP folder/mailbox
 #[derive(Debug, Clone)]
 pub struct Folder {
-    /// Folder name (display name)
+    /// Folder name (display name, decoded from modified UTF-7)
     pub name: String,
-    /// Full path including hierarchy delimiter
+    /// Full path including hierarchy delimiter, as sent by the server
     pub full_path: String,
     /// Folder type
     pub folder_type: FolderType,
@@ -138,9 +153,10 @@ pub struct Folder {
 impl Folder {
     /// Create a new folder from IMAP LIST response
     pub fn new(name: String, full_path: String, delimiter: Option<char>, attributes: Vec<String>) -> Self {
+        let name = decode_mailbox_name(&name);
         let folder_type = FolderType::from_attributes_and_name(&attributes, &name);
 
-        Self {
+        let mut folder = Self {
             name,
             full_path,
             folder_type,
@@ -150,7 +166,17 @@ impl Folder {
             message_count: None,
             unread_count: None,
             uid_next: None,
+        };
+        // Containers can't hold messages, so they never act as a special folder
+        if !folder.is_selectable() {
+            folder.folder_type = FolderType::Other;
         }
+        folder
+    }
+
+    /// Full path decoded for display
+    pub fn display_path(&self) -> String {
+        decode_mailbox_name(&self.full_path)
     }
 
     /// Check if this folder can be selected
@@ -0,0 +1,723 @@
+//! Batch header parsing
+//!
+//! Parsing a header sync of tens of thousands of messages into
+//! [`MessageHeader`]s allocates a String for every field and a Vec for every
+//! address list. A [`HeaderBatch`] parses FETCH responses into compact
+//! records instead: their text is copied once into a buffer shared by the
+//! whole batch, and [`HeaderBatch::clear`] keeps the allocations so one batch
+//! can be refilled for every chunk of a sync.
+
+use crate::message::{EmailAddress, Envelope};
+use crate::utf7::decode_mailbox_name;
+use crate::{ImapClient, MessageFlags, MessageHeader};
+
+/// Byte range of a field in the batch's text
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AddressRecord {
+    name: Option<Span>,
+    address: Span,
+}
+
+const SEEN: u8 = 1;
+const ANSWERED: u8 = 1 << 1;
+const FLAGGED: u8 = 1 << 2;
+const DELETED: u8 = 1 << 3;
+const DRAFT: u8 = 1 << 4;
+
+#[derive(Debug, Clone)]
+struct Record {
+    uid: u32,
+    flags: u8,
+    /// Other flags, space-separated
+    custom: Span,
+    date: Option<Span>,
+    subject: Option<Span>,
+    message_id: Option<Span>,
+    from: (u32, u32),
+    to: (u32, u32),
+    cc: (u32, u32),
+    has_attachments: bool,
+    /// Decoded labels, newline-separated
+    gmail_labels: Span,
+    gmail_thread_id: Option<u64>,
+    /// Where this record's text and addresses start, to undo a push
+    text_start: u32,
+    addresses_start: u32,
+}
+
+/// Headers parsed from FETCH responses, stored compactly
+#[derive(Debug, Default)]
+pub struct HeaderBatch {
+    text: String,
+    addresses: Vec<AddressRecord>,
+    records: Vec<Record>,
+}
+
+impl HeaderBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of headers in the batch
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Remove every header, keeping the allocated buffers for reuse
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.addresses.clear();
+        self.records.clear();
+    }
+
+    /// Keep only the first `len` headers
+    pub fn truncate(&mut self, len: usize) {
+        if let Some(first) = self.records.get(len) {
+            self.text.truncate(first.text_start as usize);
+            self.addresses.truncate(first.addresses_start as usize);
+            self.records.truncate(len);
+        }
+    }
+
+    /// Header at `index`
+    pub fn get(&self, index: usize) -> Option<HeaderView<'_>> {
+        self.records.get(index).map(|record| HeaderView { batch: self, record })
+    }
+
+    /// The most recently added header
+    pub fn last(&self) -> Option<HeaderView<'_>> {
+        self.len().checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = HeaderView<'_>> + ExactSizeIterator {
+        self.records.iter().map(move |record| HeaderView { batch: self, record })
+    }
+
+    /// Parse one untagged `* N FETCH (...)` response, with any literals
+    /// already inlined as quoted strings. Returns false, adding nothing, if it
+    /// isn't a FETCH response or carries no UID.
+    pub fn push_response(&mut self, response: &str) -> bool {
+        let Some(start) = response.find("FETCH (") else {
+            return false;
+        };
+
+        let mut record = Record {
+            uid: 0,
+            flags: 0,
+            custom: Span { start: 0, end: 0 },
+            date: None,
+            subject: None,
+            message_id: None,
+            from: (0, 0),
+            to: (0, 0),
+            cc: (0, 0),
+            has_attachments: false,
+            gmail_labels: Span { start: 0, end: 0 },
+            gmail_thread_id: None,
+            text_start: self.text.len() as u32,
+            addresses_start: self.addresses.len() as u32,
+        };
+        record.custom = self.empty_span();
+        record.gmail_labels = self.empty_span();
+
+        let mut uid = None;
+        let mut cursor = Cursor::new(&response[start + "FETCH (".len()..]);
+        loop {
+            cursor.skip_spaces();
+            match cursor.peek() {
+                None | Some(b')') => break,
+                _ => {}
+            }
+            let item_start = cursor.pos;
+            let item = cursor.atom();
+            cursor.skip_spaces();
+            if item.eq_ignore_ascii_case("UID") {
+                uid = cursor.number().map(|n| n as u32);
+            } else if item.eq_ignore_ascii_case("FLAGS") {
+                self.parse_flags(&mut cursor, &mut record);
+            } else if item.eq_ignore_ascii_case("ENVELOPE") {
+                self.parse_envelope(&mut cursor, &mut record);
+            } else if item.eq_ignore_ascii_case("BODYSTRUCTURE") {
+                record.has_attachments = ImapClient::detect_attachments(&cursor.text[item_start..]);
+                cursor.skip_value();
+            } else if item.eq_ignore_ascii_case("X-GM-LABELS") {
+                self.parse_gmail_labels(&mut cursor, &mut record);
+            } else if item.eq_ignore_ascii_case("X-GM-THRID") {
+                record.gmail_thread_id = cursor.number();
+            } else {
+                cursor.skip_value();
+            }
+        }
+
+        match uid {
+            Some(uid) => {
+                record.uid = uid;
+                self.records.push(record);
+                true
+            }
+            None => {
+                self.text.truncate(record.text_start as usize);
+                self.addresses.truncate(record.addresses_start as usize);
+                false
+            }
+        }
+    }
+
+    fn empty_span(&self) -> Span {
+        let at = self.text.len() as u32;
+        Span { start: at, end: at }
+    }
+
+    fn span(&self, span: Span) -> &str {
+        &self.text[span.start as usize..span.end as usize]
+    }
+
+    /// `FLAGS (\Seen $Work)`: system flags as bits, the rest kept as text
+    fn parse_flags(&mut self, cursor: &mut Cursor, record: &mut Record) {
+        if !cursor.eat(b'(') {
+            cursor.skip_value();
+            return;
+        }
+        let start = self.text.len() as u32;
+        loop {
+            cursor.skip_spaces();
+            match cursor.peek() {
+                None => break,
+                Some(b')') => {
+                    cursor.eat(b')');
+                    break;
+                }
+                _ => {}
+            }
+            let flag = cursor.atom();
+            if flag.is_empty() {
+                cursor.skip_value();
+                continue;
+            }
+            let bit = [
+                ("\\Seen", SEEN),
+                ("\\Answered", ANSWERED),
+                ("\\Flagged", FLAGGED),
+                ("\\Deleted", DELETED),
+                ("\\Draft", DRAFT),
+            ]
+            .iter()
+            .find(|(name, _)| flag.eq_ignore_ascii_case(name))
+            .map(|(_, bit)| *bit);
+            match bit {
+                Some(bit) => record.flags |= bit,
+                None => {
+                    if self.text.len() as u32 > start {
+                        self.text.push(' ');
+                    }
+                    self.text.push_str(flag);
+                }
+            }
+        }
+        record.custom = Span { start, end: self.text.len() as u32 };
+    }
+
+    /// `ENVELOPE (date subject from sender reply-to to cc bcc in-reply-to message-id)`
+    fn parse_envelope(&mut self, cursor: &mut Cursor, record: &mut Record) {
+        if !cursor.eat(b'(') {
+            cursor.skip_value();
+            return;
+        }
+        record.date = self.nstring(cursor);
+        record.subject = self.nstring(cursor);
+        record.from = self.address_list(cursor);
+        cursor.skip_value(); // sender
+        cursor.skip_value(); // reply-to
+        record.to = self.address_list(cursor);
+        record.cc = self.address_list(cursor);
+        cursor.skip_value(); // bcc
+        cursor.skip_value(); // in-reply-to
+        record.message_id = self.nstring(cursor);
+        cursor.skip_to_close();
+    }
+
+    /// A quoted string or atom copied into the text, or `None` for NIL
+    fn nstring(&mut self, cursor: &mut Cursor) -> Option<Span> {
+        cursor.skip_spaces();
+        let start = self.text.len() as u32;
+        match cursor.peek()? {
+            b'"' => cursor.quoted_into(&mut self.text),
+            b'(' | b')' => return None,
+            _ => {
+                let atom = cursor.atom();
+                if atom.eq_ignore_ascii_case("NIL") {
+                    return None;
+                }
+                self.text.push_str(atom);
+            }
+        }
+        Some(Span { start, end: self.text.len() as u32 })
+    }
+
+    /// `((name adl mailbox host) ...)` or NIL, as a range of `addresses`.
+    /// Group markers (a mailbox with a NIL host) are left out.
+    fn address_list(&mut self, cursor: &mut Cursor) -> (u32, u32) {
+        let first = self.addresses.len() as u32;
+        cursor.skip_spaces();
+        if !cursor.eat(b'(') {
+            cursor.skip_value();
+            return (first, first);
+        }
+        loop {
+            cursor.skip_spaces();
+            match cursor.peek() {
+                Some(b'(') => {
+                    cursor.eat(b'(');
+                }
+                Some(b')') => {
+                    cursor.eat(b')');
+                    break;
+                }
+                None => break,
+                _ => {
+                    cursor.skip_value();
+                    continue;
+                }
+            }
+
+            let name = self.nstring(cursor).filter(|s| s.start != s.end);
+            cursor.skip_value(); // adl
+            let address_start = self.text.len() as u32;
+            let mailbox = self.nstring(cursor);
+            cursor.skip_spaces();
+            let host_at = self.text.len();
+            let has_host = match cursor.peek() {
+                Some(b'"') => {
+                    self.text.push('@');
+                    cursor.quoted_into(&mut self.text);
+                    self.text.len() > host_at + 1
+                }
+                _ => {
+                    cursor.skip_value();
+                    false
+                }
+            };
+            cursor.skip_to_close();
+
+            let mailbox = mailbox.map(|m| self.span(m)).unwrap_or_default();
+            let keep = if has_host { !mailbox.is_empty() } else { mailbox.contains('@') };
+            if !has_host {
+                self.text.truncate(host_at);
+            }
+            if keep {
+                let address = Span { start: address_start, end: self.text.len() as u32 };
+                self.addresses.push(AddressRecord { name, address });
+            } else {
+                self.text.truncate(name.map(|n| n.start).unwrap_or(address_start) as usize);
+            }
+        }
+        (first, self.addresses.len() as u32)
+    }
+
+    /// `X-GM-LABELS (\Inbox "Project X")`, decoded from modified UTF-7
+    fn parse_gmail_labels(&mut self, cursor: &mut Cursor, record: &mut Record) {
+        if !cursor.eat(b'(') {
+            cursor.skip_value();
+            return;
+        }
+        let start = self.text.len() as u32;
+        let mut raw = String::new();
+        loop {
+            cursor.skip_spaces();
+            match cursor.peek() {
+                None => break,
+                Some(b')') => {
+                    cursor.eat(b')');
+                    break;
+                }
+                Some(b'"') => {
+                    raw.clear();
+                    cursor.quoted_into(&mut raw);
+                }
+                _ => {
+                    raw.clear();
+                    raw.push_str(cursor.atom());
+                }
+            }
+            if self.text.len() as u32 > start {
+                self.text.push('\n');
+            }
+            if raw.contains('&') {
+                self.text.push_str(&decode_mailbox_name(&raw));
+            } else {
+                self.text.push_str(&raw);
+            }
+        }
+        record.gmail_labels = Span { start, end: self.text.len() as u32 };
+    }
+}
+
+/// One address of a [`HeaderView`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressView<'a> {
+    pub name: Option<&'a str>,
+    pub address: &'a str,
+}
+
+impl AddressView<'_> {
+    pub fn to_email_address(&self) -> EmailAddress {
+        EmailAddress {
+            name: self.name.map(str::to_string),
+            address: self.address.to_string(),
+        }
+    }
+}
+
+/// A header in a [`HeaderBatch`], borrowing its text
+#[derive(Clone, Copy)]
+pub struct HeaderView<'a> {
+    batch: &'a HeaderBatch,
+    record: &'a Record,
+}
+
+impl<'a> HeaderView<'a> {
+    pub fn uid(&self) -> u32 {
+        self.record.uid
+    }
+
+    /// Envelope date as sent, e.g. `Mon, 1 Jan 2024 10:00:00 +0000`
+    pub fn date(&self) -> Option<&'a str> {
+        self.record.date.map(|s| self.batch.span(s))
+    }
+
+    /// Subject as sent, still MIME-encoded if it was
+    pub fn subject(&self) -> Option<&'a str> {
+        self.record.subject.map(|s| self.batch.span(s))
+    }
+
+    pub fn message_id(&self) -> Option<&'a str> {
+        self.record.message_id.map(|s| self.batch.span(s))
+    }
+
+    pub fn from(&self) -> impl Iterator<Item = AddressView<'a>> {
+        self.addresses(self.record.from)
+    }
+
+    pub fn to(&self) -> impl Iterator<Item = AddressView<'a>> {
+        self.addresses(self.record.to)
+    }
+
+    pub fn cc(&self) -> impl Iterator<Item = AddressView<'a>> {
+        self.addresses(self.record.cc)
+    }
+
+    fn addresses(&self, (first, end): (u32, u32)) -> impl Iterator<Item = AddressView<'a>> {
+        let batch = self.batch;
+        batch.addresses[first as usize..end as usize]
+            .iter()
+            .map(move |a| AddressView {
+                name: a.name.map(|s| batch.span(s)),
+                address: batch.span(a.address),
+            })
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.record.flags & SEEN != 0
+    }
+
+    pub fn is_starred(&self) -> bool {
+        self.record.flags & FLAGGED != 0
+    }
+
+    pub fn is_answered(&self) -> bool {
+        self.record.flags & ANSWERED != 0
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.record.flags & DELETED != 0
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.record.flags & DRAFT != 0
+    }
+
+    /// Flags other than the five system flags, as the server sent them
+    pub fn custom_flags(&self) -> impl Iterator<Item = &'a str> {
+        self.batch.span(self.record.custom).split(' ').filter(|f| !f.is_empty())
+    }
+
+    /// Keywords (flags without a backslash, like `$Work`), sorted
+    pub fn keywords(&self) -> Vec<String> {
+        let mut keywords: Vec<String> = self
+            .custom_flags()
+            .filter(|flag| !flag.starts_with('\\'))
+            .map(str::to_string)
+            .collect();
+        keywords.sort();
+        keywords.dedup();
+        keywords
+    }
+
+    pub fn has_attachments(&self) -> bool {
+        self.record.has_attachments
+    }
+
+    /// Gmail labels, decoded; none on other servers
+    pub fn gmail_labels(&self) -> impl Iterator<Item = &'a str> {
+        self.batch.span(self.record.gmail_labels).split('\n').filter(|l| !l.is_empty())
+    }
+
+    pub fn gmail_thread_id(&self) -> Option<u64> {
+        self.record.gmail_thread_id
+    }
+
+    pub fn flags(&self) -> MessageFlags {
+        MessageFlags {
+            seen: self.is_read(),
+            answered: self.is_answered(),
+            flagged: self.is_starred(),
+            deleted: self.is_deleted(),
+            draft: self.is_draft(),
+            custom: self.custom_flags().map(str::to_string).collect(),
+        }
+    }
+
+    /// The header as an owned [`MessageHeader`]
+    pub fn to_header(&self) -> MessageHeader {
+        MessageHeader {
+            uid: self.uid(),
+            seq: 0,
+            envelope: Envelope {
+                message_id: self.message_id().map(str::to_string),
+                subject: self.subject().map(str::to_string),
+                from: self.from().map(|a| a.to_email_address()).collect(),
+                to: self.to().map(|a| a.to_email_address()).collect(),
+                cc: self.cc().map(|a| a.to_email_address()).collect(),
+                date: self.date().map(str::to_string),
+                ..Default::default()
+            },
+            flags: self.flags(),
+            size: 0,
+            has_attachments: self.has_attachments(),
+            gmail_labels: self.gmail_labels().map(str::to_string).collect(),
+            gmail_thread_id: self.gmail_thread_id(),
+        }
+    }
+}
+
+/// Reads IMAP syntax from a response line
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_spaces();
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\r' | b'\n' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// An atom, including any `[section]` part as in `BODY[HEADER]`
+    fn atom(&mut self) -> &'a str {
+        let start = self.pos;
+        let mut in_section = false;
+        while let Some(c) = self.peek() {
+            match c {
+                b'[' => in_section = true,
+                b']' => in_section = false,
+                b' ' | b'(' | b')' | b'"' | b'\r' | b'\n' if !in_section => break,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        self.text[start..self.pos].parse().ok()
+    }
+
+    /// Copy a quoted string, unescaped, onto `out`
+    fn quoted_into(&mut self, out: &mut String) {
+        self.pos += 1; // opening quote
+        let bytes = self.text.as_bytes();
+        let mut run = self.pos;
+        while self.pos < bytes.len() {
+            match bytes[self.pos] {
+                b'"' => break,
+                b'\\' if self.pos + 1 < bytes.len() => {
+                    out.push_str(&self.text[run..self.pos]);
+                    self.pos += 1;
+                    run = self.pos;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        out.push_str(&self.text[run..self.pos.min(bytes.len())]);
+        self.pos = (self.pos + 1).min(bytes.len()); // closing quote
+    }
+
+    fn skip_quoted(&mut self) {
+        let bytes = self.text.as_bytes();
+        self.pos += 1;
+        while self.pos < bytes.len() {
+            match bytes[self.pos] {
+                b'"' => break,
+                b'\\' => self.pos += 1,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        self.pos = (self.pos + 1).min(bytes.len());
+    }
+
+    /// Step over one value (atom, quoted string, or parenthesized list),
+    /// returning its text
+    fn skip_value(&mut self) -> &'a str {
+        self.skip_spaces();
+        let start = self.pos;
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                self.skip_to_close();
+            }
+            Some(b'"') => self.skip_quoted(),
+            Some(b')') | None => {}
+            Some(_) => {
+                self.atom();
+            }
+        }
+        &self.text[start..self.pos]
+    }
+
+    /// Step past the `)` closing the current list
+    fn skip_to_close(&mut self) {
+        let mut depth = 1;
+        while let Some(c) = self.peek() {
+            match c {
+                b'"' => {
+                    self.skip_quoted();
+                    continue;
+                }
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += 1;
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GMAIL: &str = "* 12 FETCH (X-GM-THRID 1789 X-GM-LABELS (\\Inbox \"Project &AOk-t\\\"\" Receipts) UID 345 \
+        FLAGS (\\Seen $Work \\Recent) ENVELOPE (\"Mon, 1 Jan 2024 10:00:00 +0000\" \"Re: \\\"plans\\\" (draft)\" \
+        ((\"Ann Lee\" NIL \"ann\" \"example.com\")) ((NIL NIL \"ann\" \"example.com\")) NIL \
+        ((NIL NIL \"bob\" \"example.org\")(\"\" NIL \"carol\" \"example.org\")) \
+        ((NIL NIL \"team\" NIL)(\"Dan\" NIL \"dan\" \"example.net\")(NIL NIL NIL NIL)) NIL NIL \"<id@example.com>\") \
+        BODYSTRUCTURE ((\"text\" \"plain\" NIL NIL NIL \"7bit\" 5 1)(\"application\" \"pdf\" (\"name\" \"a.pdf\") NIL NIL \"base64\" 100) \"mixed\"))\r\n";
+
+    #[test]
+    fn test_parse_gmail_response() {
+        let mut batch = HeaderBatch::new();
+        assert!(batch.push_response(GMAIL));
+        let h = batch.get(0).unwrap();
+
+        assert_eq!(h.uid(), 345);
+        assert_eq!(h.subject(), Some("Re: \"plans\" (draft)"));
+        assert_eq!(h.date(), Some("Mon, 1 Jan 2024 10:00:00 +0000"));
+        assert_eq!(h.message_id(), Some("<id@example.com>"));
+        assert_eq!(
+            h.from().collect::<Vec<_>>(),
+            vec![AddressView { name: Some("Ann Lee"), address: "ann@example.com" }]
+        );
+        let to: Vec<_> = h.to().collect();
+        assert_eq!(to[1], AddressView { name: None, address: "carol@example.org" });
+        // The group marker and its end are left out
+        assert_eq!(h.cc().map(|a| a.address).collect::<Vec<_>>(), vec!["dan@example.net"]);
+        assert!(h.is_read() && !h.is_starred());
+        assert_eq!(h.keywords(), vec!["$Work"]);
+        assert!(h.has_attachments());
+        assert_eq!(h.gmail_labels().collect::<Vec<_>>(), vec!["\\Inbox", "Project ét\"", "Receipts"]);
+        assert_eq!(h.gmail_thread_id(), Some(1789));
+    }
+
+    #[test]
+    fn test_matches_line_parser() {
+        let mut batch = HeaderBatch::new();
+        batch.push_response(GMAIL);
+        let from_batch = batch.get(0).unwrap().to_header();
+        let from_line = ImapClient::parse_fetch_response(GMAIL).unwrap();
+
+        assert_eq!(from_batch.uid, from_line.uid);
+        assert_eq!(from_batch.envelope.subject, from_line.envelope.subject);
+        assert_eq!(from_batch.envelope.date, from_line.envelope.date);
+        assert_eq!(from_batch.envelope.message_id, from_line.envelope.message_id);
+        let addresses = |list: &[EmailAddress]| list.iter().map(|a| a.to_display_string()).collect::<Vec<_>>();
+        assert_eq!(addresses(&from_batch.envelope.from), addresses(&from_line.envelope.from));
+        assert_eq!(addresses(&from_batch.envelope.to), addresses(&from_line.envelope.to));
+        assert_eq!(addresses(&from_batch.envelope.cc), addresses(&from_line.envelope.cc));
+        assert_eq!(from_batch.flags.custom, from_line.flags.custom);
+        assert_eq!(from_batch.has_attachments, from_line.has_attachments);
+        assert_eq!(from_batch.gmail_labels, from_line.gmail_labels);
+        assert_eq!(from_batch.gmail_thread_id, from_line.gmail_thread_id);
+    }
+
+    #[test]
+    fn test_reuse_and_truncate() {
+        let mut batch = HeaderBatch::new();
+        batch.push_response("* 1 FETCH (UID 7 FLAGS (\\Deleted) ENVELOPE (NIL \"one\" NIL NIL NIL NIL NIL NIL NIL NIL))");
+        batch.push_response("* 2 FETCH (UID 8 FLAGS () ENVELOPE (NIL NIL NIL NIL NIL NIL NIL NIL NIL NIL))");
+        assert!(!batch.push_response("* 3 FETCH (FLAGS (\\Seen))"));
+        assert!(!batch.push_response("* 3 EXISTS"));
+        assert_eq!(batch.len(), 2);
+        assert!(batch.get(0).unwrap().is_deleted());
+        assert_eq!(batch.last().unwrap().subject(), None);
+
+        batch.truncate(1);
+        assert_eq!(batch.iter().map(|h| h.uid()).collect::<Vec<_>>(), vec![7]);
+        assert_eq!(batch.get(0).unwrap().subject(), Some("one"));
+
+        let capacity = batch.text.capacity();
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.text.capacity(), capacity);
+    }
+}
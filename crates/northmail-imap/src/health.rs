@@ -0,0 +1,123 @@
+//! Connection health
+//!
+//! Long-lived connections (the pool workers and IDLE) die quietly: a NAT
+//! drops the mapping, the server times out, or the machine sleeps and the
+//! socket is gone by the time it wakes. Keepalive NOOPs get a deadline so a
+//! dead socket is noticed instead of hanging, a jump between the wall clock
+//! and the monotonic clock reveals a suspend, and reconnects back off
+//! exponentially with jitter so a server that is down isn't hammered by
+//! every connection at once.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a keepalive NOOP may take before the connection counts as dead
+pub const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How much further the wall clock may run than the monotonic clock before
+/// the gap is taken to be a suspend
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Exponential reconnect backoff with jitter
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self { base, max, attempt: 0 }
+    }
+
+    /// Failed attempts since the last success
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Record a failure and return how long to wait before the next attempt
+    pub fn next_delay(&mut self) -> Duration {
+        self.attempt = self.attempt.saturating_add(1);
+        delay_for(self.base, self.max, self.attempt, rand::random::<f64>())
+    }
+
+    /// The connection is healthy again
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Delay before retry `attempt` (1-based): `base * 2^(attempt-1)` capped at
+/// `max`, of which the upper half is scaled by `jitter` in `0.0..1.0`
+fn delay_for(base: Duration, max: Duration, attempt: u32, jitter: f64) -> Duration {
+    let exp = base
+        .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+        .min(max);
+    exp / 2 + (exp / 2).mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+/// Notices when the machine slept, by comparing how far the wall clock and
+/// the monotonic clock moved (the monotonic clock stops during suspend)
+#[derive(Debug, Clone)]
+pub struct SuspendDetector {
+    monotonic: Instant,
+    wall: SystemTime,
+}
+
+impl SuspendDetector {
+    pub fn new() -> Self {
+        Self {
+            monotonic: Instant::now(),
+            wall: SystemTime::now(),
+        }
+    }
+
+    /// Whether the machine slept since the last check
+    pub fn slept(&mut self) -> bool {
+        let monotonic = self.monotonic.elapsed();
+        let wall = self.wall.elapsed().unwrap_or_default();
+        *self = Self::new();
+        clock_gap_is_suspend(monotonic, wall)
+    }
+}
+
+impl Default for SuspendDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn clock_gap_is_suspend(monotonic: Duration, wall: Duration) -> bool {
+    wall.saturating_sub(monotonic) > SUSPEND_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delays() {
+        let base = Duration::from_secs(5);
+        let max = Duration::from_secs(300);
+        assert_eq!(delay_for(base, max, 1, 0.0), Duration::from_millis(2500));
+        assert_eq!(delay_for(base, max, 1, 1.0), base);
+        assert_eq!(delay_for(base, max, 3, 1.0), Duration::from_secs(20));
+        assert_eq!(delay_for(base, max, 30, 1.0), max);
+        assert_eq!(delay_for(base, max, 30, 0.0), max / 2);
+
+        let mut backoff = Backoff::new(base, max);
+        assert!(backoff.next_delay() <= base);
+        assert_eq!(backoff.attempt(), 1);
+        backoff.reset();
+        assert_eq!(backoff.attempt(), 0);
+    }
+
+    #[test]
+    fn test_suspend_gap() {
+        let minute = Duration::from_secs(60);
+        assert!(!clock_gap_is_suspend(minute, minute + Duration::from_secs(2)));
+        assert!(clock_gap_is_suspend(minute, minute * 20));
+        // Wall clock set backwards is not a suspend
+        assert!(!clock_gap_is_suspend(minute, Duration::ZERO));
+    }
+}
@@ -2,16 +2,38 @@
 //!
 //! Provides async IMAP operations with XOAUTH2 support for Gmail.
 
+mod acl;
+mod bodystructure;
+mod capabilities;
 mod client;
+pub mod deletion;
 mod error;
 mod folder;
+mod header_batch;
+pub mod health;
 mod message;
 mod oauth2;
-mod simple_client;
+mod quota;
+pub mod sieve;
+mod tls;
+mod trace;
+mod uidplus;
+mod utf7;
 
-pub use client::ImapClient;
+pub use acl::{AclEntry, Namespace, Namespaces, Rights};
+pub use bodystructure::{parse_bodystructure, BodyPart};
+pub use capabilities::Capabilities;
+pub use client::{set_lean_header_fetch, BodyStream, IdleEvent, ImapClient, Transport};
 pub use error::{ImapError, ImapResult};
-pub use folder::{Folder, FolderType};
-pub use message::{Envelope, MessageFlags, MessageHeader};
+pub use folder::{Folder, FolderPeek, FolderType};
+pub use header_batch::{AddressView, HeaderBatch, HeaderView};
+pub use message::{EmailAddress, Envelope, MessageFlags, MessageHeader};
 pub use oauth2::XOAuth2Authenticator;
-pub use simple_client::{IdleEvent, SimpleImapClient};
+pub use quota::{Quota, QuotaResource};
+pub use tls::{
+    certificate_fingerprint, configure_server, fingerprints_match, server_options, TlsMode,
+    TlsOptions,
+};
+pub use trace::TracedStream;
+pub use uidplus::{format_uid_set, AppendUid, CopyUid};
+pub use utf7::{decode_mailbox_name, encode_mailbox_name};
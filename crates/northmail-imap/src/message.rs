@@ -15,7 +15,8 @@ pub struct MessageFlags {
     pub deleted: bool,
     /// Message is a draft
     pub draft: bool,
-    /// Custom flags (Gmail labels, etc.)
+    /// Other flags as the server sent them: keywords like `$Work`, and
+    /// system flags such as `\Recent`
     pub custom: HashSet<String>,
 }
 
@@ -31,8 +32,9 @@ impl MessageFlags {
                 "\\flagged" => result.flagged = true,
                 "\\deleted" => result.deleted = true,
                 "\\draft" => result.draft = true,
-                other => {
-                    result.custom.insert(other.to_string());
+                // Keywords keep their case so they are stored back as they were
+                _ => {
+                    result.custom.insert(flag.to_string());
                 }
             }
         }
@@ -63,6 +65,18 @@ impl MessageFlags {
         flags.extend(self.custom.iter().cloned());
         flags
     }
+
+    /// Keywords (flags without a backslash, like `$Work`), sorted
+    pub fn keywords(&self) -> Vec<String> {
+        let mut keywords: Vec<String> = self
+            .custom
+            .iter()
+            .filter(|flag| !flag.starts_with('\\'))
+            .cloned()
+            .collect();
+        keywords.sort();
+        keywords
+    }
 }
 
 /// Email address with optional display name
@@ -145,6 +159,10 @@ pub struct MessageHeader {
     pub size: u32,
     /// Body structure (for attachment detection)
     pub has_attachments: bool,
+    /// Gmail labels (X-GM-LABELS), decoded; empty on other servers
+    pub gmail_labels: Vec<String>,
+    /// Gmail conversation id (X-GM-THRID)
+    pub gmail_thread_id: Option<u64>,
 }
 
 impl MessageHeader {
@@ -1,10 +1,10 @@
-//! XOAUTH2 authenticator for async-imap
+//! XOAUTH2 SASL mechanism
 
 use base64::prelude::*;
 
-/// XOAUTH2 authenticator for async-imap
+/// XOAUTH2 authenticator
 ///
-/// Implements the SASL XOAUTH2 mechanism for Gmail IMAP authentication.
+/// Implements the SASL XOAUTH2 mechanism used by Gmail and Microsoft.
 #[derive(Debug, Clone)]
 pub struct XOAuth2Authenticator {
     /// Email address
@@ -38,15 +38,6 @@ impl XOAuth2Authenticator {
     }
 }
 
-/// Implement the async-imap Authenticator trait
-impl async_imap::Authenticator for XOAuth2Authenticator {
-    type Response = String;
-
-    fn process(&mut self, _challenge: &[u8]) -> Self::Response {
-        self.response()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
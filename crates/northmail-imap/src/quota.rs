@@ -0,0 +1,133 @@
+//! Quota responses (RFC 9208)
+//!
+//! `GETQUOTAROOT INBOX` names the quota roots that cover the mailbox and,
+//! on most servers, reports each of them with a `* QUOTA` line. Usage and
+//! limits come as resource triples; STORAGE is counted in units of 1024
+//! octets.
+
+/// One resource of a quota root, e.g. STORAGE or MESSAGE
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResource {
+    /// Resource name, uppercased
+    pub name: String,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+/// Usage and limits of one quota root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quota {
+    /// Quota root name; often empty, meaning the whole account
+    pub root: String,
+    pub resources: Vec<QuotaResource>,
+}
+
+impl Quota {
+    /// Parse an untagged `* QUOTA root (STORAGE 10 512 MESSAGE 5 100)` line
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("* QUOTA ")?;
+        let open = rest.rfind('(')?;
+        let root = unquote(rest[..open].trim());
+        let list = rest[open + 1..].trim_end().strip_suffix(')')?;
+
+        let parts: Vec<&str> = list.split_whitespace().collect();
+        let resources = parts
+            .chunks(3)
+            .filter_map(|triple| match triple {
+                [name, usage, limit] => Some(QuotaResource {
+                    name: name.to_uppercase(),
+                    usage: usage.parse().ok()?,
+                    limit: limit.parse().ok()?,
+                }),
+                _ => None,
+            })
+            .collect();
+        Some(Self { root, resources })
+    }
+
+    /// Storage used and allowed, in bytes
+    pub fn storage_bytes(&self) -> Option<(u64, u64)> {
+        self.resources
+            .iter()
+            .find(|r| r.name == "STORAGE")
+            .map(|r| (r.usage.saturating_mul(1024), r.limit.saturating_mul(1024)))
+    }
+}
+
+/// Parse an untagged `* QUOTAROOT mailbox root...` line into the root names
+pub fn parse_quota_roots(line: &str) -> Option<Vec<String>> {
+    let rest = line.trim().strip_prefix("* QUOTAROOT ")?;
+    let mut words = split_words(rest).into_iter();
+    // The first word is the mailbox the roots belong to
+    words.next()?;
+    Some(words.collect())
+}
+
+/// Split on spaces, keeping quoted strings (which may be empty) whole
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c == ' ' {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut word = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => word.extend(chars.next()),
+                    _ => word.push(c),
+                }
+            }
+            words.push(word);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ' ' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn unquote(text: &str) -> String {
+    split_words(text).into_iter().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quota() {
+        let quota = Quota::parse("* QUOTA \"\" (STORAGE 14889779 15728640)\r\n").unwrap();
+        assert_eq!(quota.root, "");
+        assert_eq!(quota.storage_bytes(), Some((14889779 * 1024, 15728640 * 1024)));
+
+        let quota = Quota::parse("* QUOTA User.ann (STORAGE 10 512 MESSAGE 5 100)").unwrap();
+        assert_eq!(quota.root, "User.ann");
+        assert_eq!(quota.resources.len(), 2);
+        assert_eq!(quota.resources[1], QuotaResource { name: "MESSAGE".to_string(), usage: 5, limit: 100 });
+
+        let quota = Quota::parse("* QUOTA \"\" (MESSAGE 5 100)").unwrap();
+        assert_eq!(quota.storage_bytes(), None);
+        assert!(Quota::parse("* QUOTAROOT INBOX \"\"").is_none());
+    }
+
+    #[test]
+    fn test_parse_quota_roots() {
+        assert_eq!(parse_quota_roots("* QUOTAROOT INBOX \"\"\r\n"), Some(vec![String::new()]));
+        assert_eq!(
+            parse_quota_roots("* QUOTAROOT \"INBOX\" User.ann Shared"),
+            Some(vec!["User.ann".to_string(), "Shared".to_string()])
+        );
+        assert_eq!(parse_quota_roots("* QUOTAROOT INBOX"), Some(Vec::new()));
+        assert!(parse_quota_roots("* QUOTA \"\" (STORAGE 1 2)").is_none());
+    }
+}
@@ -0,0 +1,589 @@
+//! ManageSieve client (RFC 5804)
+//!
+//! Servers such as Dovecot keep filtering rules as Sieve scripts that run on
+//! delivery, whether or not a client is open. ManageSieve lists, uploads,
+//! activates and deletes those scripts; at most one is active at a time.
+
+use async_std::io::prelude::*;
+use async_std::io::BufReader;
+use base64::prelude::*;
+use tracing::{debug, info};
+
+use crate::client::escape_imap_quoted;
+use crate::{ImapError, ImapResult, Transport, XOAuth2Authenticator};
+
+/// Standard ManageSieve port
+pub const DEFAULT_PORT: u16 = 4190;
+
+type Stream = BufReader<Box<dyn Transport>>;
+
+/// A script stored on the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SieveScript {
+    pub name: String,
+    /// Whether this is the script the server runs on delivery
+    pub active: bool,
+}
+
+/// What the server announced after connecting
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SieveCapabilities {
+    /// Server software, e.g. "Dovecot Pigeonhole"
+    pub implementation: Option<String>,
+    /// SASL mechanisms offered
+    pub sasl: Vec<String>,
+    /// Sieve extensions scripts may `require`
+    pub extensions: Vec<String>,
+    pub starttls: bool,
+}
+
+impl SieveCapabilities {
+    pub fn supports_sasl(&self, mechanism: &str) -> bool {
+        self.sasl.iter().any(|m| m.eq_ignore_ascii_case(mechanism))
+    }
+
+    /// Fill in from the `"NAME" "value"` lines before the greeting's OK
+    fn from_lines(lines: &[Vec<Token>]) -> Self {
+        let mut capabilities = Self::default();
+        for line in lines {
+            let (Some(name), value) = (
+                line.first().and_then(Token::text),
+                line.get(1).and_then(Token::text),
+            ) else {
+                continue;
+            };
+            let words = || {
+                value
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect()
+            };
+            match name.to_ascii_uppercase().as_str() {
+                "IMPLEMENTATION" => capabilities.implementation = value.map(str::to_string),
+                "SASL" => capabilities.sasl = words(),
+                "SIEVE" => capabilities.extensions = words(),
+                "STARTTLS" => capabilities.starttls = true,
+                _ => {}
+            }
+        }
+        capabilities
+    }
+}
+
+/// One element of a response line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Bare word such as `OK` or `ACTIVE`
+    Atom(String),
+    /// Quoted string or literal
+    Str(String),
+    /// Parenthesized response code, without the parentheses
+    Code(String),
+}
+
+impl Token {
+    fn text(&self) -> Option<&str> {
+        match self {
+            Token::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn is_atom(&self, word: &str) -> bool {
+        matches!(self, Token::Atom(a) if a.eq_ignore_ascii_case(word))
+    }
+}
+
+/// Split a response line into tokens. A trailing `{N}` or `{N+}` announces a
+/// literal of N bytes that follows the line; its size is returned.
+fn tokenize(line: &str) -> ImapResult<(Vec<Token>, Option<usize>)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            ' ' => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        Some((_, '"')) => break,
+                        Some((_, c)) => value.push(c),
+                        None => {
+                            return Err(ImapError::ParseError(format!(
+                                "Unterminated string: {}",
+                                line
+                            )))
+                        }
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            '{' => {
+                let rest = line[start + 1..].trim_end();
+                let size = rest
+                    .strip_suffix('}')
+                    .map(|n| n.trim_end_matches('+'))
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| ImapError::ParseError(format!("Bad literal: {}", line)))?;
+                return Ok((tokens, Some(size)));
+            }
+            '(' => {
+                let mut depth = 0;
+                let mut in_quotes = false;
+                let mut end = None;
+                let mut escaped = false;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' if in_quotes => escaped = true,
+                        '"' => in_quotes = !in_quotes,
+                        '(' if !in_quotes => depth += 1,
+                        ')' if !in_quotes => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(i);
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                let end = end.ok_or_else(|| {
+                    ImapError::ParseError(format!("Unbalanced parentheses: {}", line))
+                })?;
+                tokens.push(Token::Code(line[start + 1..end].to_string()));
+            }
+            _ => {
+                let mut atom = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c == ' ' || c == '(' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Atom(atom));
+            }
+        }
+    }
+    Ok((tokens, None))
+}
+
+/// How a command finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    No,
+    Bye,
+}
+
+/// Data lines and the final status line of a response
+#[derive(Debug)]
+struct Response {
+    lines: Vec<Vec<Token>>,
+    status: Status,
+    /// Human-readable text after the status, if any
+    text: Option<String>,
+}
+
+impl Response {
+    /// The response as an error, unless it was OK
+    fn check(self, fail: fn(String) -> ImapError) -> ImapResult<Response> {
+        let message = || {
+            self.text
+                .clone()
+                .unwrap_or_else(|| "no reason given".to_string())
+        };
+        match self.status {
+            Status::Ok => Ok(self),
+            Status::No => Err(fail(message())),
+            Status::Bye => Err(ImapError::ConnectionFailed(format!(
+                "Server closed the connection: {}",
+                message()
+            ))),
+        }
+    }
+}
+
+/// Format `script` as a non-synchronizing literal
+fn literal(script: &str) -> String {
+    format!("{{{}+}}\r\n{}", script.len(), script)
+}
+
+/// Quote a script name or SASL argument
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", escape_imap_quoted(s))
+}
+
+/// Session with a ManageSieve server
+pub struct SieveClient {
+    stream: Stream,
+    capabilities: SieveCapabilities,
+}
+
+impl SieveClient {
+    /// Connect to `host:port` and secure the session with STARTTLS, using the
+    /// host's registered CA file and pinned fingerprint
+    pub async fn connect(host: &str, port: u16) -> ImapResult<Self> {
+        info!("Connecting to ManageSieve on {}:{}", host, port);
+        let tls_stream = crate::tls::connect_sieve(host, port).await?;
+        Self::connect_transport(tls_stream).await
+    }
+
+    /// Start a session over an already secured transport, reading the
+    /// capability listing the server sends first
+    pub async fn connect_transport(transport: impl Transport + 'static) -> ImapResult<Self> {
+        let transport: Box<dyn Transport> = Box::new(transport);
+        let mut client = Self {
+            stream: BufReader::new(transport),
+            capabilities: SieveCapabilities::default(),
+        };
+        let greeting = client
+            .read_response()
+            .await?
+            .check(ImapError::ServerError)?;
+        client.capabilities = SieveCapabilities::from_lines(&greeting.lines);
+        debug!("ManageSieve capabilities: {:?}", client.capabilities);
+        Ok(client)
+    }
+
+    pub fn capabilities(&self) -> &SieveCapabilities {
+        &self.capabilities
+    }
+
+    /// Log in with a username and password (SASL PLAIN)
+    pub async fn login(&mut self, username: &str, password: &str) -> ImapResult<()> {
+        let credentials = BASE64_STANDARD.encode(format!("\0{}\0{}", username, password));
+        self.authenticate("PLAIN", &credentials).await
+    }
+
+    /// Log in with an OAuth2 access token (SASL XOAUTH2)
+    pub async fn authenticate_xoauth2(
+        &mut self,
+        email: &str,
+        access_token: &str,
+    ) -> ImapResult<()> {
+        if !self.capabilities.supports_sasl("XOAUTH2") {
+            return Err(ImapError::Unsupported(
+                "XOAUTH2 for ManageSieve".to_string(),
+            ));
+        }
+        let encoded = XOAuth2Authenticator::new(email, access_token).response();
+        self.authenticate("XOAUTH2", &encoded).await
+    }
+
+    async fn authenticate(&mut self, mechanism: &str, initial: &str) -> ImapResult<()> {
+        self.send(&format!(
+            "AUTHENTICATE {} {}\r\n",
+            quoted(mechanism),
+            quoted(initial)
+        ))
+        .await?;
+        loop {
+            let (tokens, status) = self.read_line().await?;
+            match status {
+                Some(status) => {
+                    let text = tokens.iter().find_map(|t| t.text().map(str::to_string));
+                    Response {
+                        lines: Vec::new(),
+                        status,
+                        text,
+                    }
+                    .check(ImapError::AuthenticationFailed)?;
+                    return Ok(());
+                }
+                // A challenge carrying the failure details; answer empty to get the NO
+                None => self.send("\"\"\r\n").await?,
+            }
+        }
+    }
+
+    /// Scripts on the server, in the order it lists them
+    pub async fn list_scripts(&mut self) -> ImapResult<Vec<SieveScript>> {
+        let response = self.command("LISTSCRIPTS\r\n").await?;
+        Ok(response
+            .lines
+            .iter()
+            .filter_map(|line| {
+                let name = line.first()?.text()?.to_string();
+                let active = line.get(1).is_some_and(|t| t.is_atom("ACTIVE"));
+                Some(SieveScript { name, active })
+            })
+            .collect())
+    }
+
+    /// Source of the script called `name`
+    pub async fn get_script(&mut self, name: &str) -> ImapResult<String> {
+        let response = self
+            .command(&format!("GETSCRIPT {}\r\n", quoted(name)))
+            .await?;
+        response
+            .lines
+            .iter()
+            .find_map(|line| line.first().and_then(Token::text).map(str::to_string))
+            .ok_or_else(|| ImapError::ParseError(format!("No content for script {}", name)))
+    }
+
+    /// Store `script` as `name`, replacing any script of that name. The
+    /// server rejects scripts that don't compile, with its error message.
+    pub async fn put_script(&mut self, name: &str, script: &str) -> ImapResult<()> {
+        self.command(&format!(
+            "PUTSCRIPT {} {}\r\n",
+            quoted(name),
+            literal(script)
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Make `name` the script run on delivery; an empty name turns
+    /// server-side filtering off
+    pub async fn set_active(&mut self, name: &str) -> ImapResult<()> {
+        self.command(&format!("SETACTIVE {}\r\n", quoted(name)))
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the script called `name`. Servers refuse to delete the active script.
+    pub async fn delete_script(&mut self, name: &str) -> ImapResult<()> {
+        self.command(&format!("DELETESCRIPT {}\r\n", quoted(name)))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn logout(&mut self) -> ImapResult<()> {
+        self.command("LOGOUT\r\n").await?;
+        Ok(())
+    }
+
+    async fn command(&mut self, command: &str) -> ImapResult<Response> {
+        self.send(command).await?;
+        self.read_response().await?.check(ImapError::ServerError)
+    }
+
+    async fn send(&mut self, data: &str) -> ImapResult<()> {
+        self.stream.get_mut().write_all(data.as_bytes()).await?;
+        self.stream.get_mut().flush().await?;
+        Ok(())
+    }
+
+    async fn read_response(&mut self) -> ImapResult<Response> {
+        let mut lines = Vec::new();
+        loop {
+            let (tokens, status) = self.read_line().await?;
+            if let Some(status) = status {
+                let text = tokens.iter().find_map(|t| t.text().map(str::to_string));
+                return Ok(Response {
+                    lines,
+                    status,
+                    text,
+                });
+            }
+            lines.push(tokens);
+        }
+    }
+
+    /// Read one response line, literals included, and whether it ends the response
+    async fn read_line(&mut self) -> ImapResult<(Vec<Token>, Option<Status>)> {
+        let mut tokens = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(ImapError::ConnectionFailed("Connection closed".to_string()));
+            }
+            let (mut parsed, literal) = tokenize(line.trim_end_matches(['\r', '\n']))?;
+            tokens.append(&mut parsed);
+            let Some(size) = literal else { break };
+            let mut data = vec![0; size];
+            self.stream.read_exact(&mut data).await?;
+            tokens.push(Token::Str(String::from_utf8_lossy(&data).into_owned()));
+        }
+        let status = match tokens.first() {
+            Some(t) if t.is_atom("OK") => Some(Status::Ok),
+            Some(t) if t.is_atom("NO") => Some(Status::No),
+            Some(t) if t.is_atom("BYE") => Some(Status::Bye),
+            _ => None,
+        };
+        Ok((tokens, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transport replaying a canned server script and recording what the
+    /// client sends
+    struct ScriptedTransport {
+        incoming: futures::io::Cursor<Vec<u8>>,
+        sent: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl futures::io::AsyncRead for ScriptedTransport {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::pin::Pin::new(&mut self.incoming).poll_read(cx, buf)
+        }
+    }
+
+    impl futures::io::AsyncWrite for ScriptedTransport {
+        fn poll_write(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.sent.lock().unwrap().extend_from_slice(buf);
+            std::task::Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    const GREETING: &str = "\"IMPLEMENTATION\" \"Dovecot Pigeonhole\"\r\n\
+        \"SIEVE\" \"fileinto reject envelope\"\r\n\
+        \"SASL\" \"PLAIN LOGIN\"\r\n\
+        \"VERSION\" \"1.0\"\r\n\
+        OK \"Dovecot ready.\"\r\n";
+
+    fn transport(script: &str) -> (ScriptedTransport, std::sync::Arc<std::sync::Mutex<Vec<u8>>>) {
+        let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let transport = ScriptedTransport {
+            incoming: futures::io::Cursor::new(format!("{}{}", GREETING, script).into_bytes()),
+            sent: sent.clone(),
+        };
+        (transport, sent)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let (tokens, literal) = tokenize("NO (NONEXISTENT) \"No \\\"such\\\" script\"").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Atom("NO".to_string()),
+                Token::Code("NONEXISTENT".to_string()),
+                Token::Str("No \"such\" script".to_string()),
+            ]
+        );
+        assert_eq!(literal, None);
+
+        let (tokens, literal) = tokenize("OK (WARNINGS) {42}").unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(literal, Some(42));
+        assert!(matches!(tokenize("\"open"), Err(ImapError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let (transport, _) = transport("");
+        let client = async_std::task::block_on(SieveClient::connect_transport(transport)).unwrap();
+        let capabilities = client.capabilities();
+        assert_eq!(
+            capabilities.implementation.as_deref(),
+            Some("Dovecot Pigeonhole")
+        );
+        assert_eq!(
+            capabilities.extensions,
+            vec!["fileinto", "reject", "envelope"]
+        );
+        assert!(capabilities.supports_sasl("plain"));
+        assert!(!capabilities.supports_sasl("XOAUTH2"));
+        assert!(!capabilities.starttls);
+    }
+
+    #[test]
+    fn test_list_get_and_put() {
+        let (transport, sent) = transport(
+            "OK \"Logged in.\"\r\n\
+            \"vacation\"\r\n\
+            \"main\" ACTIVE\r\n\
+            OK \"Listscripts completed.\"\r\n\
+            {21}\r\nrequire \"fileinto\";\r\n\r\n\
+            OK \"Getscript completed.\"\r\n\
+            OK\r\n",
+        );
+        async_std::task::block_on(async {
+            let mut client = SieveClient::connect_transport(transport).await.unwrap();
+            client.login("ann", "secret").await.unwrap();
+            assert_eq!(
+                client.list_scripts().await.unwrap(),
+                vec![
+                    SieveScript {
+                        name: "vacation".to_string(),
+                        active: false
+                    },
+                    SieveScript {
+                        name: "main".to_string(),
+                        active: true
+                    },
+                ]
+            );
+            assert_eq!(
+                client.get_script("main").await.unwrap(),
+                "require \"fileinto\";\r\n"
+            );
+            client.put_script("main", "keep;\r\n").await.unwrap();
+        });
+
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            sent,
+            "AUTHENTICATE \"PLAIN\" \"AGFubgBzZWNyZXQ=\"\r\n\
+             LISTSCRIPTS\r\n\
+             GETSCRIPT \"main\"\r\n\
+             PUTSCRIPT \"main\" {7+}\r\nkeep;\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let (transport, _) = transport(
+            "NO \"Authentication failed.\"\r\n\
+            OK\r\n\
+            NO \"line 1: unknown command 'kepe'\"\r\n\
+            BYE \"Too many errors\"\r\n",
+        );
+        async_std::task::block_on(async {
+            let mut client = SieveClient::connect_transport(transport).await.unwrap();
+            assert!(matches!(
+                client.login("ann", "wrong").await,
+                Err(ImapError::AuthenticationFailed(_))
+            ));
+            client.login("ann", "secret").await.unwrap();
+            match client.put_script("main", "kepe;").await {
+                Err(ImapError::ServerError(message)) => {
+                    assert!(message.contains("unknown command"))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+            assert!(matches!(
+                client.set_active("main").await,
+                Err(ImapError::ConnectionFailed(_))
+            ));
+        });
+    }
+}
@@ -0,0 +1,351 @@
+//! TLS setup for IMAP connections
+//!
+//! Servers are reached over implicit TLS (port 993) with the system roots by
+//! default. Self-hosted accounts can instead use STARTTLS on 143, trust an
+//! extra CA bundle, or pin a certificate that doesn't verify. Settings are
+//! registered per host with [`configure_server`], so every connection to that
+//! host (pool workers, IDLE, one-off fetches) picks them up. The TCP connection
+//! goes through the host's proxy from `northmail_proxy`, if any.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use async_native_tls::{Certificate, TlsConnector, TlsStream};
+use async_std::io::prelude::*;
+use async_std::io::BufReader;
+use async_std::net::TcpStream;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::{ImapError, ImapResult};
+
+/// How the connection is encrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsMode {
+    /// TLS from the first byte (IMAPS)
+    #[default]
+    Implicit,
+    /// Plain connection upgraded with STARTTLS (RFC 3501 §6.2.1)
+    StartTls,
+}
+
+impl TlsMode {
+    /// Standard port for this mode
+    pub fn default_port(self) -> u16 {
+        match self {
+            TlsMode::Implicit => 993,
+            TlsMode::StartTls => 143,
+        }
+    }
+}
+
+/// TLS settings for one server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    pub mode: TlsMode,
+    /// Port to connect to instead of the caller's (defaults to 143 for STARTTLS)
+    pub port: Option<u16>,
+    /// PEM file with extra CA certificates to trust
+    pub ca_file: Option<PathBuf>,
+    /// SHA-256 fingerprint of a certificate the user chose to trust
+    pub pinned_fingerprint: Option<String>,
+}
+
+fn servers() -> &'static Mutex<HashMap<String, TlsOptions>> {
+    static SERVERS: OnceLock<Mutex<HashMap<String, TlsOptions>>> = OnceLock::new();
+    SERVERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Register TLS settings for a host. Default options clear the entry.
+pub fn configure_server(host: &str, options: TlsOptions) {
+    let mut servers = servers().lock().unwrap();
+    if options == TlsOptions::default() {
+        servers.remove(&host.to_ascii_lowercase());
+    } else {
+        servers.insert(host.to_ascii_lowercase(), options);
+    }
+}
+
+/// TLS settings registered for a host, or the defaults
+pub fn server_options(host: &str) -> TlsOptions {
+    servers()
+        .lock()
+        .unwrap()
+        .get(&host.to_ascii_lowercase())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// SHA-256 fingerprint of a DER certificate, as colon-separated uppercase hex
+pub fn certificate_fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Compare two fingerprints, ignoring case, colons and whitespace
+pub fn fingerprints_match(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_ascii_hexdigit())
+            .map(|c| c.to_ascii_uppercase())
+            .collect::<String>()
+    };
+    let a = normalize(a);
+    !a.is_empty() && a == normalize(b)
+}
+
+/// Connect to `host` using its registered TLS settings.
+///
+/// Returns the stream and whether the server greeting was already read
+/// (STARTTLS consumes it before the handshake). A certificate that fails
+/// verification and isn't pinned gives [`ImapError::CertificateUntrusted`].
+pub(crate) async fn connect(host: &str, port: u16) -> ImapResult<(TlsStream<TcpStream>, bool)> {
+    let options = server_options(host);
+    let port = match (options.port, options.mode) {
+        (Some(port), _) => port,
+        (None, TlsMode::StartTls) => TlsMode::StartTls.default_port(),
+        (None, TlsMode::Implicit) => port,
+    };
+    let upgrade = match options.mode {
+        TlsMode::Implicit => None,
+        TlsMode::StartTls => Some(Protocol::Imap),
+    };
+    let stream = connect_verified(host, port, upgrade, &options).await?;
+    Ok((stream, upgrade.is_some()))
+}
+
+/// Connect to a ManageSieve server on `host:port` and upgrade with STARTTLS.
+///
+/// The host's CA file and pinned fingerprint apply as they do for IMAP. The
+/// pre-TLS capability listing is consumed; the server repeats it after the
+/// handshake.
+pub(crate) async fn connect_sieve(host: &str, port: u16) -> ImapResult<TlsStream<TcpStream>> {
+    let options = server_options(host);
+    connect_verified(host, port, Some(Protocol::ManageSieve), &options).await
+}
+
+/// Protocol spoken before a STARTTLS upgrade
+#[derive(Debug, Clone, Copy)]
+enum Protocol {
+    Imap,
+    ManageSieve,
+}
+
+/// Handshake with verification, falling back to the pinned fingerprint
+async fn connect_verified(
+    host: &str,
+    port: u16,
+    upgrade: Option<Protocol>,
+    options: &TlsOptions,
+) -> ImapResult<TlsStream<TcpStream>> {
+    let verify_error = match handshake(host, port, upgrade, connector(options, false)?).await? {
+        Ok(stream) => {
+            debug!("TLS connection established ({:?})", upgrade);
+            return Ok(stream);
+        }
+        Err(e) => e,
+    };
+
+    // Connect again without verification to see which certificate the server presents
+    let stream = match handshake(host, port, upgrade, connector(options, true)?).await? {
+        Ok(stream) => stream,
+        Err(_) => return Err(ImapError::TlsError(verify_error.to_string())),
+    };
+    let der = stream
+        .peer_certificate()
+        .ok()
+        .flatten()
+        .and_then(|cert| cert.to_der().ok())
+        .ok_or_else(|| ImapError::TlsError(verify_error.to_string()))?;
+    let fingerprint = certificate_fingerprint(&der);
+
+    if options
+        .pinned_fingerprint
+        .as_deref()
+        .is_some_and(|pin| fingerprints_match(pin, &fingerprint))
+    {
+        info!("Certificate for {} matches the pinned fingerprint", host);
+        return Ok(stream);
+    }
+
+    Err(ImapError::CertificateUntrusted {
+        host: host.to_string(),
+        fingerprint,
+        reason: verify_error.to_string(),
+    })
+}
+
+fn connector(options: &TlsOptions, accept_invalid: bool) -> ImapResult<TlsConnector> {
+    let mut connector = TlsConnector::new();
+    if let Some(path) = &options.ca_file {
+        let pem = std::fs::read(path)
+            .map_err(|e| ImapError::TlsError(format!("Can't read CA file {}: {}", path.display(), e)))?;
+        let certs = Certificate::stack_from_pem(&pem)
+            .map_err(|e| ImapError::TlsError(format!("Invalid CA file {}: {}", path.display(), e)))?;
+        for cert in certs {
+            connector = connector.add_root_certificate(cert);
+        }
+    }
+    Ok(connector.danger_accept_invalid_certs(accept_invalid))
+}
+
+/// TCP connect, STARTTLS if needed, then the TLS handshake. The outer error is
+/// a connection problem; the inner one is the handshake failing.
+async fn handshake(
+    host: &str,
+    port: u16,
+    upgrade: Option<Protocol>,
+    connector: TlsConnector,
+) -> ImapResult<Result<TlsStream<TcpStream>, async_native_tls::Error>> {
+    let tcp_stream = open_tcp(host, port).await?;
+
+    let tcp_stream = match upgrade {
+        None => tcp_stream,
+        Some(Protocol::Imap) => starttls(tcp_stream).await?,
+        Some(Protocol::ManageSieve) => sieve_starttls(tcp_stream).await?,
+    };
+
+    Ok(connector.connect(host, tcp_stream).await)
+}
+
+/// Connect to `host:port`, through the host's proxy if one is configured
+async fn open_tcp(host: &str, port: u16) -> ImapResult<TcpStream> {
+    let Some(proxy) = northmail_proxy::proxy_for(host) else {
+        return TcpStream::connect(format!("{}:{}", host, port))
+            .await
+            .map_err(|e| ImapError::ConnectionFailed(e.to_string()));
+    };
+
+    let mut tcp_stream = TcpStream::connect((proxy.host.as_str(), proxy.port))
+        .await
+        .map_err(|e| ImapError::ConnectionFailed(format!("{}: {}", proxy, e)))?;
+    northmail_proxy::tunnel(&mut tcp_stream, &proxy, host, port)
+        .await
+        .map_err(|e| ImapError::ConnectionFailed(e.to_string()))?;
+    Ok(tcp_stream)
+}
+
+/// Read the greeting and issue STARTTLS on a plain connection
+async fn starttls(tcp_stream: TcpStream) -> ImapResult<TcpStream> {
+    let mut reader = BufReader::new(tcp_stream);
+
+    let mut greeting = String::new();
+    reader
+        .read_line(&mut greeting)
+        .await
+        .map_err(|e| ImapError::ServerError(e.to_string()))?;
+    debug!("Greeting: {}", greeting.trim());
+    if !greeting.starts_with("* OK") {
+        return Err(ImapError::ServerError(format!("Unexpected greeting: {}", greeting)));
+    }
+
+    reader
+        .get_mut()
+        .write_all(b"S001 STARTTLS\r\n")
+        .await
+        .map_err(|e| ImapError::ServerError(e.to_string()))?;
+
+    loop {
+        let mut line = String::new();
+        let n = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+        if n == 0 {
+            return Err(ImapError::ServerError("Connection closed during STARTTLS".to_string()));
+        }
+        if line.starts_with("S001 ") {
+            if !line.starts_with("S001 OK") {
+                return Err(ImapError::TlsError(format!("STARTTLS refused: {}", line.trim())));
+            }
+            break;
+        }
+    }
+
+    // Anything already buffered was sent before the handshake and can't be trusted
+    if !reader.buffer().is_empty() {
+        return Err(ImapError::TlsError("Unexpected data after STARTTLS".to_string()));
+    }
+
+    Ok(reader.into_inner())
+}
+
+/// Read the ManageSieve capability listing and issue STARTTLS
+async fn sieve_starttls(tcp_stream: TcpStream) -> ImapResult<TcpStream> {
+    let mut reader = BufReader::new(tcp_stream);
+
+    let mut sent_starttls = false;
+    loop {
+        let mut line = String::new();
+        let n = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| ImapError::ServerError(e.to_string()))?;
+        if n == 0 {
+            return Err(ImapError::ServerError("Connection closed during STARTTLS".to_string()));
+        }
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("OK") {
+            if sent_starttls {
+                break;
+            }
+            reader
+                .get_mut()
+                .write_all(b"STARTTLS\r\n")
+                .await
+                .map_err(|e| ImapError::ServerError(e.to_string()))?;
+            sent_starttls = true;
+        } else if upper.starts_with("NO") || upper.starts_with("BYE") {
+            return Err(ImapError::TlsError(format!("STARTTLS refused: {}", line.trim())));
+        }
+    }
+
+    if !reader.buffer().is_empty() {
+        return Err(ImapError::TlsError("Unexpected data after STARTTLS".to_string()));
+    }
+
+    Ok(reader.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate_fingerprint() {
+        // SHA-256 of the empty input
+        assert_eq!(
+            certificate_fingerprint(b""),
+            "E3:B0:C4:42:98:FC:1C:14:9A:FB:F4:C8:99:6F:B9:24:27:AE:41:E4:64:9B:93:4C:A4:95:99:1B:78:52:B8:55"
+        );
+    }
+
+    #[test]
+    fn test_fingerprints_match() {
+        let fp = certificate_fingerprint(b"cert");
+        assert!(fingerprints_match(&fp, &fp));
+        assert!(fingerprints_match(&fp.to_lowercase().replace(':', ""), &fp));
+        assert!(fingerprints_match(&fp.replace(':', " "), &fp));
+        assert!(!fingerprints_match(&certificate_fingerprint(b"other"), &fp));
+        assert!(!fingerprints_match("", ""));
+    }
+
+    #[test]
+    fn test_server_options_registry() {
+        assert_eq!(server_options("mail.example.test"), TlsOptions::default());
+
+        let options = TlsOptions {
+            mode: TlsMode::StartTls,
+            ..Default::default()
+        };
+        configure_server("Mail.Example.Test", options.clone());
+        assert_eq!(server_options("mail.example.test"), options);
+
+        configure_server("mail.example.test", TlsOptions::default());
+        assert_eq!(server_options("mail.example.test"), TlsOptions::default());
+    }
+}
@@ -0,0 +1,219 @@
+//! Opt-in protocol trace
+//!
+//! When enabled (see [`northmail_trace`]), IMAP connections record what
+//! they send and receive into the shared trace, so a failing sync can be
+//! diagnosed from a copied log instead of a rebuild with extra tracing.
+//! Credentials are masked and literals (message bodies, appended messages)
+//! are replaced by their size. Tracing applies to connections opened after
+//! it is enabled.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::io::{AsyncRead, AsyncWrite};
+
+/// Longest line recorded, in characters; the rest is cut
+const MAX_LINE: usize = 500;
+/// Bytes buffered for a single unfinished line
+const MAX_PENDING: usize = 16 * 1024;
+
+/// Splits one direction of the byte stream into lines, skipping literals
+#[derive(Debug, Default)]
+struct LineSplitter {
+    pending: Vec<u8>,
+    literal: usize,
+}
+
+impl LineSplitter {
+    fn feed(&mut self, mut data: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        while !data.is_empty() {
+            if self.literal > 0 {
+                let n = self.literal.min(data.len());
+                self.literal -= n;
+                data = &data[n..];
+                continue;
+            }
+            let Some(end) = data.iter().position(|&b| b == b'\n') else {
+                self.extend_pending(data);
+                break;
+            };
+            self.extend_pending(&data[..end]);
+            data = &data[end + 1..];
+
+            let line = String::from_utf8_lossy(&self.pending).trim_end().to_string();
+            self.pending.clear();
+            match literal_size(&line) {
+                Some(size) => {
+                    self.literal = size;
+                    lines.push(format!("{} <{} bytes>", line, size));
+                }
+                None => lines.push(line),
+            }
+        }
+        lines
+    }
+
+    fn extend_pending(&mut self, data: &[u8]) {
+        let room = MAX_PENDING.saturating_sub(self.pending.len());
+        self.pending.extend_from_slice(&data[..data.len().min(room)]);
+    }
+}
+
+/// Size of the literal announced at the end of a line (`{123}` or `{123+}`)
+fn literal_size(line: &str) -> Option<usize> {
+    let inner = line.strip_suffix('}')?;
+    let open = inner.rfind('{')?;
+    inner[open + 1..].trim_end_matches('+').parse().ok()
+}
+
+/// Mask credentials in a client line. `authenticating` tracks an AUTHENTICATE
+/// exchange, whose continuation lines are all secret.
+fn redact_client(line: &str, authenticating: &mut bool) -> String {
+    let mut parts = line.splitn(3, ' ');
+    let tag = parts.next().unwrap_or_default();
+    let command = parts.next().unwrap_or_default();
+    let rest = parts.next().unwrap_or_default();
+
+    if command.eq_ignore_ascii_case("LOGIN") {
+        format!("{} LOGIN ***", tag)
+    } else if command.eq_ignore_ascii_case("AUTHENTICATE") {
+        *authenticating = true;
+        let mechanism = rest.split(' ').next().unwrap_or_default();
+        format!("{} AUTHENTICATE {} ***", tag, mechanism)
+    } else if *authenticating {
+        "***".to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+/// Per-connection trace state
+#[derive(Debug)]
+struct Recorder {
+    id: u64,
+    host: String,
+    client: LineSplitter,
+    server: LineSplitter,
+    authenticating: bool,
+}
+
+impl Recorder {
+    fn record(&self, direction: &str, text: &str) {
+        let text = match text.char_indices().nth(MAX_LINE) {
+            Some((cut, _)) => format!("{}…", &text[..cut]),
+            None => text.to_string(),
+        };
+        northmail_trace::record("IMAP", self.id, &self.host, direction, &text);
+    }
+
+    fn sent(&mut self, data: &[u8]) {
+        for line in self.client.feed(data) {
+            let line = redact_client(&line, &mut self.authenticating);
+            self.record("C:", &line);
+        }
+    }
+
+    fn received(&mut self, data: &[u8]) {
+        for line in self.server.feed(data) {
+            if self.authenticating && !line.starts_with('*') && !line.starts_with('+') {
+                self.authenticating = false;
+            }
+            self.record("S:", &line);
+        }
+    }
+}
+
+/// A stream that records its traffic when tracing was on at connect time
+#[derive(Debug)]
+pub struct TracedStream<S> {
+    inner: S,
+    recorder: Option<Recorder>,
+}
+
+impl<S> TracedStream<S> {
+    pub(crate) fn new(inner: S, host: &str) -> Self {
+        let recorder = northmail_trace::connection().map(|id| {
+            let recorder = Recorder {
+                id,
+                host: host.to_string(),
+                client: LineSplitter::default(),
+                server: LineSplitter::default(),
+                authenticating: false,
+            };
+            recorder.record("--", "connected");
+            recorder
+        });
+        Self { inner, recorder }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TracedStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(recorder)) = (&poll, this.recorder.as_mut()) {
+            recorder.received(&buf[..*n]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TracedStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some(recorder)) = (&poll, this.recorder.as_mut()) {
+            recorder.sent(&buf[..*n]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splitter_skips_literals() {
+        let mut splitter = LineSplitter::default();
+        assert!(splitter.feed(b"* 1 FETCH (UID 7 BODY[] {1").is_empty());
+        let lines = splitter.feed(b"1}\r\nhello world)\r\nA0001 OK");
+        assert_eq!(lines, vec!["* 1 FETCH (UID 7 BODY[] {11} <11 bytes>", ")"]);
+        assert_eq!(splitter.feed(b" done\r\n"), vec!["A0001 OK done"]);
+    }
+
+    #[test]
+    fn test_literal_size() {
+        assert_eq!(literal_size("A1 APPEND INBOX {310}"), Some(310));
+        assert_eq!(literal_size("A1 APPEND INBOX {42+}"), Some(42));
+        assert_eq!(literal_size("* OK [CAPABILITY IMAP4rev1]"), None);
+        assert_eq!(literal_size("* LIST () \"/\" {x}"), None);
+    }
+
+    #[test]
+    fn test_redact_client() {
+        let mut authenticating = false;
+        assert_eq!(
+            redact_client("A0001 LOGIN user@example.com \"hunter2\"", &mut authenticating),
+            "A0001 LOGIN ***"
+        );
+        assert_eq!(
+            redact_client("A0002 AUTHENTICATE XOAUTH2 dXNlcj1mb28=", &mut authenticating),
+            "A0002 AUTHENTICATE XOAUTH2 ***"
+        );
+        assert!(authenticating);
+        assert_eq!(redact_client("dXNlcj1mb28=", &mut authenticating), "***");
+
+        authenticating = false;
+        assert_eq!(redact_client("A0003 SELECT INBOX", &mut authenticating), "A0003 SELECT INBOX");
+    }
+}
@@ -0,0 +1,188 @@
+//! UIDPLUS response codes (RFC 4315)
+//!
+//! Servers advertising UIDPLUS attach `[APPENDUID ...]` to a successful APPEND
+//! and `[COPYUID ...]` to a successful COPY/MOVE, telling us the UIDs the
+//! messages received in the destination mailbox.
+
+/// UID assigned to a message by APPEND (`[APPENDUID uidvalidity uid]`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppendUid {
+    /// UIDVALIDITY of the destination mailbox
+    pub uidvalidity: u32,
+    /// UID of the appended message
+    pub uid: u32,
+}
+
+impl AppendUid {
+    /// Parse the APPENDUID response code from a tagged OK line
+    pub fn parse(line: &str) -> Option<Self> {
+        let data = response_code(line, "APPENDUID")?;
+        let mut parts = data.split_whitespace();
+        let uidvalidity = parts.next()?.parse().ok()?;
+        // After a MULTIAPPEND this is a set; the first UID is the first message's
+        let uid = parts.next()?.split([',', ':']).next()?.parse().ok()?;
+        Some(Self { uidvalidity, uid })
+    }
+}
+
+/// UID mapping reported by COPY/MOVE (`[COPYUID uidvalidity src-set dst-set]`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyUid {
+    /// UIDVALIDITY of the destination mailbox
+    pub uidvalidity: u32,
+    /// Source UIDs, in the order the server reported them
+    pub source_uids: Vec<u32>,
+    /// Destination UIDs, positionally matching `source_uids`
+    pub dest_uids: Vec<u32>,
+}
+
+impl CopyUid {
+    /// Parse the COPYUID response code from an OK line (tagged, or untagged
+    /// for MOVE) of a command that copied at most `copied` messages; a
+    /// mapping of more is ignored
+    pub fn parse(line: &str, copied: usize) -> Option<Self> {
+        let data = response_code(line, "COPYUID")?;
+        let mut parts = data.split_whitespace();
+        let uidvalidity = parts.next()?.parse().ok()?;
+        let source_uids = parse_uid_set(parts.next()?, copied)?;
+        let dest_uids = parse_uid_set(parts.next()?, copied)?;
+        if source_uids.len() != dest_uids.len() {
+            return None;
+        }
+        Some(Self {
+            uidvalidity,
+            source_uids,
+            dest_uids,
+        })
+    }
+
+    /// Look up the destination UID for a source UID
+    pub fn dest_uid_for(&self, source_uid: u32) -> Option<u32> {
+        self.source_uids
+            .iter()
+            .position(|&u| u == source_uid)
+            .map(|i| self.dest_uids[i])
+    }
+}
+
+/// Extract the body of a bracketed response code, e.g. `[COPYUID 1 2 3]` → `1 2 3`
+fn response_code<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let start = line.find(&format!("[{} ", name))?;
+    let rest = &line[start + name.len() + 2..];
+    let end = rest.find(']')?;
+    Some(rest[..end].trim())
+}
+
+/// Expand a UID set such as `4:6,9` into `[4, 5, 6, 9]`, or `None` if it
+/// holds more than `max` UIDs; the set comes from the server, and a range
+/// like `1:4294967295` would take gigabytes.
+/// Ranges keep the server's direction (`6:4` → `[6, 5, 4]`) since COPYUID
+/// pairs source and destination UIDs by position.
+pub fn parse_uid_set(set: &str, max: usize) -> Option<Vec<u32>> {
+    let mut uids = Vec::new();
+    for part in set.split(',') {
+        if let Some((a, b)) = part.split_once(':') {
+            let a: u32 = a.parse().ok()?;
+            let b: u32 = b.parse().ok()?;
+            if a.abs_diff(b) as usize >= max.saturating_sub(uids.len()) {
+                return None;
+            }
+            if a <= b {
+                uids.extend(a..=b);
+            } else {
+                uids.extend((b..=a).rev());
+            }
+        } else {
+            if uids.len() >= max {
+                return None;
+            }
+            uids.push(part.parse().ok()?);
+        }
+    }
+    Some(uids)
+}
+
+/// Build a compact UID set for a command, e.g. `[9, 4, 5, 6]` → `4:6,9`
+pub fn format_uid_set(uids: &[u32]) -> String {
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let start = sorted[i];
+        let mut end = start;
+        while i + 1 < sorted.len() && sorted[i + 1] == end + 1 {
+            i += 1;
+            end = sorted[i];
+        }
+        if start == end {
+            parts.push(start.to_string());
+        } else {
+            parts.push(format!("{}:{}", start, end));
+        }
+        i += 1;
+    }
+    parts.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_appenduid() {
+        let line = "A0003 OK [APPENDUID 38505 3955] APPEND completed\r\n";
+        assert_eq!(
+            AppendUid::parse(line),
+            Some(AppendUid { uidvalidity: 38505, uid: 3955 })
+        );
+        assert_eq!(AppendUid::parse("A0003 OK APPEND completed\r\n"), None);
+    }
+
+    #[test]
+    fn test_parse_copyuid() {
+        let line = "A0004 OK [COPYUID 38505 304,319:320 3956:3958] Done\r\n";
+        let copy = CopyUid::parse(line, 3).unwrap();
+        assert_eq!(copy.uidvalidity, 38505);
+        assert_eq!(copy.source_uids, vec![304, 319, 320]);
+        assert_eq!(copy.dest_uids, vec![3956, 3957, 3958]);
+        assert_eq!(copy.dest_uid_for(319), Some(3957));
+        assert_eq!(copy.dest_uid_for(1), None);
+    }
+
+    #[test]
+    fn test_parse_copyuid_untagged_move() {
+        let line = "* OK [COPYUID 432432 42:43 1202:1203]\r\n";
+        let copy = CopyUid::parse(line, 3).unwrap();
+        assert_eq!(copy.dest_uid_for(43), Some(1203));
+    }
+
+    #[test]
+    fn test_parse_copyuid_mismatched_sets() {
+        assert!(CopyUid::parse("A1 OK [COPYUID 1 1:3 10:11] Done", 3).is_none());
+    }
+
+    #[test]
+    fn test_parse_uid_set_descending_range() {
+        assert_eq!(parse_uid_set("6:4", 3), Some(vec![6, 5, 4]));
+        assert_eq!(parse_uid_set("x", 3), None);
+    }
+
+    #[test]
+    fn test_parse_uid_set_limit() {
+        assert_eq!(parse_uid_set("4:6,9", 4), Some(vec![4, 5, 6, 9]));
+        assert_eq!(parse_uid_set("4:6,9", 3), None);
+        assert_eq!(parse_uid_set("1:4294967295", 10), None);
+        assert_eq!(parse_uid_set("4294967295:1", 10), None);
+        assert!(CopyUid::parse("A1 OK [COPYUID 1 1:4294967295 1:4294967295] Done", 2).is_none());
+    }
+
+    #[test]
+    fn test_format_uid_set() {
+        assert_eq!(format_uid_set(&[9, 4, 5, 6, 5]), "4:6,9");
+        assert_eq!(format_uid_set(&[42]), "42");
+        assert_eq!(parse_uid_set(&format_uid_set(&[1, 3, 4]), 3), Some(vec![1, 3, 4]));
+    }
+}
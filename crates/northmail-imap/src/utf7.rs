@@ -0,0 +1,130 @@
+//! Modified UTF-7 mailbox names (RFC 3501 §5.1.3)
+//!
+//! Servers send non-ASCII folder names as e.g. `&BBAEQARF-`. Paths are kept
+//! in this raw form for commands; these helpers produce the display form and
+//! encode names typed by the user.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+,";
+
+/// Decode a mailbox name for display.
+///
+/// Names that aren't valid modified UTF-7 (some servers send raw UTF-8 or
+/// stray `&`) are returned unchanged rather than mangled.
+pub fn decode_mailbox_name(name: &str) -> String {
+    try_decode(name).unwrap_or_else(|| name.to_string())
+}
+
+/// Encode a mailbox name for use in IMAP commands
+pub fn encode_mailbox_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut pending: Vec<u16> = Vec::new();
+
+    for c in name.chars() {
+        if (' '..='~').contains(&c) {
+            flush_utf16(&mut out, &mut pending);
+            if c == '&' {
+                out.push_str("&-");
+            } else {
+                out.push(c);
+            }
+        } else {
+            let mut buf = [0u16; 2];
+            pending.extend_from_slice(c.encode_utf16(&mut buf));
+        }
+    }
+    flush_utf16(&mut out, &mut pending);
+    out
+}
+
+fn try_decode(name: &str) -> Option<String> {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find('-')?;
+        let chunk = &after[..end];
+        if chunk.is_empty() {
+            out.push('&');
+        } else {
+            out.push_str(&decode_utf16_chunk(chunk)?);
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Decode the base64 text between `&` and `-` as UTF-16BE
+fn decode_utf16_chunk(chunk: &str) -> Option<String> {
+    let mut bits: u32 = 0;
+    let mut nbits = 0;
+    let mut units = Vec::new();
+
+    for c in chunk.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+        bits = (bits << 6) | value;
+        nbits += 6;
+        if nbits >= 16 {
+            nbits -= 16;
+            units.push((bits >> nbits) as u16);
+            bits &= (1 << nbits) - 1;
+        }
+    }
+
+    // Only zero padding may be left over
+    if nbits >= 6 || bits != 0 {
+        return None;
+    }
+    String::from_utf16(&units).ok()
+}
+
+fn flush_utf16(out: &mut String, pending: &mut Vec<u16>) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let bytes: Vec<u8> = pending.iter().flat_map(|u| u.to_be_bytes()).collect();
+    out.push('&');
+    for group in bytes.chunks(3) {
+        let n = group
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+        let digits = group.len() + 1;
+        for i in 0..digits {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    out.push('-');
+    pending.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_mailbox_name() {
+        assert_eq!(decode_mailbox_name("&BBAEQARFBDgEMg-"), "Архив");
+        assert_eq!(decode_mailbox_name("~peter/mail/&U,BTFw-/&ZeVnLIqe-"), "~peter/mail/台北/日本語");
+        assert_eq!(decode_mailbox_name("Tom &- Jerry"), "Tom & Jerry");
+        assert_eq!(decode_mailbox_name("INBOX"), "INBOX");
+    }
+
+    #[test]
+    fn test_decode_broken_names_unchanged() {
+        assert_eq!(decode_mailbox_name("R&D"), "R&D");
+        assert_eq!(decode_mailbox_name("A&B-C"), "A&B-C");
+        assert_eq!(decode_mailbox_name("Отправленные"), "Отправленные");
+    }
+
+    #[test]
+    fn test_encode_mailbox_name() {
+        assert_eq!(encode_mailbox_name("Архив"), "&BBAEQARFBDgEMg-");
+        assert_eq!(encode_mailbox_name("~peter/mail/台北/日本語"), "~peter/mail/&U,BTFw-/&ZeVnLIqe-");
+        assert_eq!(encode_mailbox_name("Tom & Jerry"), "Tom &- Jerry");
+        assert_eq!(decode_mailbox_name(&encode_mailbox_name("Émoji 📬")), "Émoji 📬");
+    }
+}
@@ -0,0 +1,741 @@
+use crate::error::{JmapError, JmapResult};
+use crate::push::EventStream;
+use crate::types::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+
+/// Email properties to fetch in list queries (keeps payload small)
+const EMAIL_PROPERTIES: &[&str] = &[
+    "id",
+    "blobId",
+    "threadId",
+    "mailboxIds",
+    "keywords",
+    "size",
+    "receivedAt",
+    "sentAt",
+    "messageId",
+    "inReplyTo",
+    "from",
+    "to",
+    "cc",
+    "subject",
+    "hasAttachment",
+    "preview",
+];
+
+/// ...plus these when bodies are fetched too
+const BODY_PROPERTIES: &[&str] = &["textBody", "htmlBody", "attachments", "bodyValues"];
+
+/// Largest body value the server is asked to send; longer ones come back
+/// truncated
+const MAX_BODY_VALUE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Most redirects followed looking for the session resource
+const MAX_REDIRECTS: usize = 5;
+
+/// How a client proves who it is
+#[derive(Clone)]
+pub enum JmapAuth {
+    /// An API token, as Fastmail issues them
+    Bearer(String),
+    /// A username and (app) password
+    Basic { username: String, password: String },
+}
+
+impl std::fmt::Debug for JmapAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JmapAuth::Bearer(_) => f.write_str("Bearer(..)"),
+            JmapAuth::Basic { username, .. } => write!(f, "Basic({}, ..)", username),
+        }
+    }
+}
+
+/// A JMAP mail client, logged in to one account
+pub struct JmapClient {
+    client: reqwest::Client,
+    auth: JmapAuth,
+    session: Session,
+    account_id: String,
+}
+
+impl JmapClient {
+    /// Fetch the session resource and pick the primary mail account. `url`
+    /// is the session URL, or just the server's host name, in which case
+    /// the session is found at `/.well-known/jmap` (RFC 8620 section 2.2).
+    pub async fn connect(url: &str, auth: JmapAuth) -> JmapResult<Self> {
+        let url = session_url(url);
+        let client = http_client(&url);
+        let session = fetch_session(&client, &auth, &url).await?;
+
+        let account_id = session
+            .primary_accounts
+            .get(CAPABILITY_MAIL)
+            .cloned()
+            .ok_or(JmapError::NoMailAccount)?;
+        info!(
+            "JMAP: connected as {} (account {})",
+            session.username, account_id
+        );
+
+        Ok(Self {
+            client,
+            auth,
+            session,
+            account_id,
+        })
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// ID of the mail account calls go to
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    /// Whether the server can send mail for the account
+    pub fn can_submit(&self) -> bool {
+        self.session
+            .capabilities
+            .contains_key(CAPABILITY_SUBMISSION)
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        authorize(request, &self.auth)
+    }
+
+    /// Make one API request with method calls `(name, arguments)`, returning
+    /// each call's response arguments in order. A method error fails the
+    /// whole request.
+    pub async fn call(&self, calls: Vec<(&str, Value)>) -> JmapResult<Vec<Value>> {
+        let using = [CAPABILITY_CORE, CAPABILITY_MAIL, CAPABILITY_SUBMISSION];
+        let method_calls: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (name, args))| json!([name, args, format!("c{}", i)]))
+            .collect();
+        let body = json!({ "using": using, "methodCalls": method_calls });
+        debug!(
+            "JMAP: {}",
+            calls
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let response = self
+            .authorize(self.client.post(&self.session.api_url))
+            .json(&body)
+            .send()
+            .await?;
+        let mut response: Value = json_response(response).await?;
+
+        let responses = match response.get_mut("methodResponses").map(Value::take) {
+            Some(Value::Array(responses)) => responses,
+            _ => return Err(JmapError::ParseError("No methodResponses".to_string())),
+        };
+        let mut results = Vec::with_capacity(calls.len());
+        for response in responses {
+            let Value::Array(mut parts) = response else {
+                return Err(JmapError::ParseError(
+                    "Malformed method response".to_string(),
+                ));
+            };
+            if parts.len() != 3 {
+                return Err(JmapError::ParseError(
+                    "Malformed method response".to_string(),
+                ));
+            }
+            let args = parts.swap_remove(1);
+            if parts[0] == "error" {
+                return Err(JmapError::MethodError {
+                    kind: args["type"].as_str().unwrap_or("unknown").to_string(),
+                    description: args["description"].as_str().unwrap_or_default().to_string(),
+                });
+            }
+            results.push(args);
+        }
+        Ok(results)
+    }
+
+    /// Make a single method call
+    async fn call_one(&self, name: &str, args: Value) -> JmapResult<Value> {
+        self.call(vec![(name, args)])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| JmapError::ParseError(format!("No response to {}", name)))
+    }
+
+    /// List all mailboxes, parents before children, with their full paths
+    /// built from the names along the tree. Returns the Mailbox state too.
+    pub async fn list_mailboxes(&self) -> JmapResult<(Vec<Mailbox>, String)> {
+        let response = self
+            .call_one(
+                "Mailbox/get",
+                json!({
+                    "accountId": self.account_id,
+                    "properties": ["id", "name", "parentId", "role", "totalEmails", "unreadEmails", "sortOrder"],
+                }),
+            )
+            .await?;
+        let state = state_of(&response)?;
+        let mut mailboxes: Vec<Mailbox> = parse(&response["list"])?;
+
+        let by_id: HashMap<String, (String, Option<String>)> = mailboxes
+            .iter()
+            .map(|m| (m.id.clone(), (m.name.clone(), m.parent_id.clone())))
+            .collect();
+        for mailbox in &mut mailboxes {
+            let mut path = vec![mailbox.name.clone()];
+            let mut parent = mailbox.parent_id.clone();
+            // Bounded, in case a broken server sends a cycle
+            while let Some((name, next)) = parent
+                .and_then(|id| by_id.get(&id))
+                .filter(|_| path.len() < 64)
+            {
+                path.push(name.clone());
+                parent = next.clone();
+            }
+            path.reverse();
+            mailbox.full_path = path.join("/");
+        }
+        mailboxes.sort_by(|a, b| {
+            a.full_path
+                .cmp(&b.full_path)
+                .then(a.sort_order.cmp(&b.sort_order))
+        });
+
+        info!("JMAP: found {} mailboxes", mailboxes.len());
+        Ok((mailboxes, state))
+    }
+
+    /// The newest `limit` emails of a mailbox, from `position` on, with
+    /// their bodies if `with_bodies`. Returns the Email state too, for
+    /// [`Self::email_changes`].
+    pub async fn list_emails(
+        &self,
+        mailbox_id: &str,
+        position: u64,
+        limit: u64,
+        with_bodies: bool,
+    ) -> JmapResult<(Vec<Email>, String)> {
+        let mut get = self.email_get_args(with_bodies);
+        get["#ids"] = json!({ "resultOf": "c0", "name": "Email/query", "path": "/ids" });
+
+        let responses = self
+            .call(vec![
+                (
+                    "Email/query",
+                    json!({
+                        "accountId": self.account_id,
+                        "filter": { "inMailbox": mailbox_id },
+                        "sort": [{ "property": "receivedAt", "isAscending": false }],
+                        "position": position,
+                        "limit": limit,
+                    }),
+                ),
+                ("Email/get", get),
+            ])
+            .await?;
+        let response = responses
+            .get(1)
+            .ok_or_else(|| JmapError::ParseError("No response to Email/get".to_string()))?;
+
+        debug!(
+            "JMAP: got {} emails from {}",
+            response["list"].as_array().map_or(0, Vec::len),
+            mailbox_id
+        );
+        Ok((parse(&response["list"])?, state_of(response)?))
+    }
+
+    /// Fetch emails by ID. IDs the server doesn't know are left out.
+    pub async fn get_emails(&self, ids: &[String], with_bodies: bool) -> JmapResult<Vec<Email>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut args = self.email_get_args(with_bodies);
+        args["ids"] = json!(ids);
+        let response = self.call_one("Email/get", args).await?;
+        parse(&response["list"])
+    }
+
+    fn email_get_args(&self, with_bodies: bool) -> Value {
+        let mut properties: Vec<&str> = EMAIL_PROPERTIES.to_vec();
+        if with_bodies {
+            properties.extend_from_slice(BODY_PROPERTIES);
+        }
+        json!({
+            "accountId": self.account_id,
+            "properties": properties,
+            "fetchTextBodyValues": with_bodies,
+            "fetchHTMLBodyValues": with_bodies,
+            "maxBodyValueBytes": MAX_BODY_VALUE_BYTES,
+        })
+    }
+
+    /// Emails created, changed or destroyed since `since_state`, in any
+    /// mailbox. A `cannotCalculateChanges` method error means the state is
+    /// too old and the caller has to start over.
+    pub async fn email_changes(
+        &self,
+        since_state: &str,
+        max_changes: u64,
+    ) -> JmapResult<EmailChanges> {
+        let response = self
+            .call_one(
+                "Email/changes",
+                json!({
+                    "accountId": self.account_id,
+                    "sinceState": since_state,
+                    "maxChanges": max_changes,
+                }),
+            )
+            .await?;
+        parse(&response)
+    }
+
+    /// Apply patches to emails, e.g. `{"keywords/$seen": true}`
+    async fn update_emails(&self, updates: HashMap<&str, Value>) -> JmapResult<()> {
+        let response = self
+            .call_one(
+                "Email/set",
+                json!({ "accountId": self.account_id, "update": updates }),
+            )
+            .await?;
+        set_errors(&response, "notUpdated")
+    }
+
+    /// Set or clear a keyword such as `$seen` or `$flagged`
+    pub async fn set_keyword(&self, ids: &[String], keyword: &str, on: bool) -> JmapResult<()> {
+        let value = if on { json!(true) } else { Value::Null };
+        let updates = ids
+            .iter()
+            .map(|id| {
+                (
+                    id.as_str(),
+                    json!({ format!("keywords/{}", keyword): value }),
+                )
+            })
+            .collect();
+        self.update_emails(updates).await
+    }
+
+    /// Move emails out of one mailbox into another
+    pub async fn move_emails(
+        &self,
+        ids: &[String],
+        from_mailbox: &str,
+        to_mailbox: &str,
+    ) -> JmapResult<()> {
+        let updates = ids
+            .iter()
+            .map(|id| {
+                (
+                    id.as_str(),
+                    json!({
+                        format!("mailboxIds/{}", from_mailbox): null,
+                        format!("mailboxIds/{}", to_mailbox): true,
+                    }),
+                )
+            })
+            .collect();
+        self.update_emails(updates).await
+    }
+
+    /// Delete emails permanently
+    pub async fn destroy_emails(&self, ids: &[String]) -> JmapResult<()> {
+        let response = self
+            .call_one(
+                "Email/set",
+                json!({ "accountId": self.account_id, "destroy": ids }),
+            )
+            .await?;
+        set_errors(&response, "notDestroyed")
+    }
+
+    /// Download a blob, such as an email's raw RFC 5322 message
+    pub async fn download(
+        &self,
+        blob_id: &str,
+        mime_type: &str,
+        name: &str,
+    ) -> JmapResult<Vec<u8>> {
+        let url = expand(
+            &self.session.download_url,
+            &[
+                ("accountId", &self.account_id),
+                ("blobId", blob_id),
+                ("type", mime_type),
+                ("name", name),
+            ],
+        );
+        let response = self.authorize(self.client.get(&url)).send().await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(JmapError::ApiError { status, body });
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// The raw RFC 5322 message of an email
+    pub async fn download_message(&self, email: &Email) -> JmapResult<Vec<u8>> {
+        self.download(&email.blob_id, "message/rfc822", "message.eml")
+            .await
+    }
+
+    /// Upload a blob, returning its ID
+    pub async fn upload(&self, data: Vec<u8>, mime_type: &str) -> JmapResult<String> {
+        let url = expand(&self.session.upload_url, &[("accountId", &self.account_id)]);
+        let response = self
+            .authorize(self.client.post(&url))
+            .header(reqwest::header::CONTENT_TYPE, mime_type)
+            .body(data)
+            .send()
+            .await?;
+        let response: Value = json_response(response).await?;
+        response["blobId"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| JmapError::ParseError("Upload returned no blobId".to_string()))
+    }
+
+    /// Add a raw RFC 5322 message to mailboxes with the given keywords,
+    /// returning the new email's ID
+    pub async fn import_message(
+        &self,
+        message: Vec<u8>,
+        mailbox_ids: &[&str],
+        keywords: &[&str],
+    ) -> JmapResult<String> {
+        let blob_id = self.upload(message, "message/rfc822").await?;
+        let mailbox_ids: HashMap<&str, bool> = mailbox_ids.iter().map(|id| (*id, true)).collect();
+        let keywords: HashMap<&str, bool> = keywords.iter().map(|k| (*k, true)).collect();
+        let response = self
+            .call_one(
+                "Email/import",
+                json!({
+                    "accountId": self.account_id,
+                    "emails": { "m": { "blobId": blob_id, "mailboxIds": mailbox_ids, "keywords": keywords } },
+                }),
+            )
+            .await?;
+        set_errors(&response, "notCreated")?;
+        response["created"]["m"]["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| JmapError::ParseError("Import returned no email id".to_string()))
+    }
+
+    /// Addresses the account may send as
+    pub async fn identities(&self) -> JmapResult<Vec<Identity>> {
+        let response = self
+            .call_one("Identity/get", json!({ "accountId": self.account_id }))
+            .await?;
+        parse(&response["list"])
+    }
+
+    /// Send a raw RFC 5322 message as `identity_id`. It is stored in
+    /// `drafts_mailbox` first, as submission needs an existing email, and
+    /// moved to `sent_mailbox` once the server accepts a copy. The envelopes
+    /// are given rather than read from the headers, so Bcc recipients stay
+    /// out of the message; each is a submission of its own, sending a copy
+    /// to its recipients. If the server refuses some copies after accepting
+    /// others, this fails with [`JmapError::PartialSend`] and the message
+    /// stays in Sent, as sending it again would repeat the copies that went.
+    pub async fn send_message(
+        &self,
+        message: Vec<u8>,
+        identity_id: &str,
+        mail_from: &str,
+        envelopes: &[Vec<String>],
+        drafts_mailbox: &str,
+        sent_mailbox: &str,
+    ) -> JmapResult<()> {
+        let email_id = self
+            .import_message(message, &[drafts_mailbox], &["$draft", "$seen"])
+            .await?;
+
+        let submissions: serde_json::Map<String, Value> = envelopes
+            .iter()
+            .enumerate()
+            .map(|(index, rcpt_to)| {
+                let submission = json!({
+                    "identityId": identity_id,
+                    "emailId": email_id,
+                    "envelope": {
+                        "mailFrom": { "email": mail_from },
+                        "rcptTo": rcpt_to.iter().map(|email| json!({ "email": email })).collect::<Vec<_>>(),
+                    },
+                });
+                (format!("s{}", index), submission)
+            })
+            .collect();
+        let response = self
+            .call_one(
+                "EmailSubmission/set",
+                json!({ "accountId": self.account_id, "create": submissions }),
+            )
+            .await;
+        let (sent, refused) = match response {
+            Ok(response) => (
+                response["created"].as_object().map_or(0, |created| created.len()),
+                set_errors(&response, "notCreated").err(),
+            ),
+            Err(e) => (0, Some(e)),
+        };
+        if sent == 0 {
+            // Don't leave the unsent copy behind as a draft
+            let _ = self.destroy_emails(&[email_id]).await;
+            return Err(refused.unwrap_or_else(|| {
+                JmapError::ParseError("Submission returned no result".to_string())
+            }));
+        }
+
+        // Once a copy went out the message belongs in Sent, whatever
+        // happened to the others
+        let filed = json!({
+            format!("mailboxIds/{}", drafts_mailbox): null,
+            format!("mailboxIds/{}", sent_mailbox): true,
+            "keywords/$draft": null,
+        });
+        if let Err(e) = self
+            .update_emails(HashMap::from([(email_id.as_str(), filed)]))
+            .await
+        {
+            warn!("JMAP: failed to move sent message to Sent: {}", e);
+        }
+
+        if let Some(e) = refused {
+            return Err(JmapError::PartialSend {
+                sent,
+                total: envelopes.len(),
+                error: e.to_string(),
+            });
+        }
+        info!("JMAP: message submitted");
+        Ok(())
+    }
+
+    /// Open the EventSource push stream for changes to `types` (e.g.
+    /// `Email`, `Mailbox`), with the server pinging every `ping_secs`
+    pub async fn event_stream(&self, types: &[&str], ping_secs: u32) -> JmapResult<EventStream> {
+        let url = expand(
+            &self.session.event_source_url,
+            &[
+                ("types", &types.join(",")),
+                ("closeafter", "no"),
+                ("ping", &ping_secs.to_string()),
+            ],
+        );
+        let response = self
+            .authorize(self.client.get(&url))
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(JmapError::ApiError { status, body });
+        }
+        info!("JMAP: push stream open");
+        Ok(EventStream::new(response))
+    }
+}
+
+fn authorize(request: reqwest::RequestBuilder, auth: &JmapAuth) -> reqwest::RequestBuilder {
+    match auth {
+        JmapAuth::Bearer(token) => request.bearer_auth(token),
+        JmapAuth::Basic { username, password } => request.basic_auth(username, Some(password)),
+    }
+}
+
+/// The session URL for what the user typed: a URL as it is, a bare host
+/// name with the well-known path
+fn session_url(input: &str) -> String {
+    let input = input.trim().trim_end_matches('/');
+    if input.contains("://") {
+        input.to_string()
+    } else if input.contains('/') {
+        format!("https://{}", input)
+    } else {
+        format!("https://{}/.well-known/jmap", input)
+    }
+}
+
+/// Get the session resource, following redirects by hand: the well-known
+/// URL usually redirects to another host, and reqwest drops credentials
+/// on those
+async fn fetch_session(
+    client: &reqwest::Client,
+    auth: &JmapAuth,
+    url: &str,
+) -> JmapResult<Session> {
+    let mut url = url::Url::parse(url)
+        .map_err(|e| JmapError::ParseError(format!("Bad URL {}: {}", url, e)))?;
+    for _ in 0..=MAX_REDIRECTS {
+        debug!("JMAP: fetching session from {}", url);
+        let response = authorize(client.get(url.clone()), auth).send().await?;
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or_else(|| JmapError::ParseError("Redirect without a location".to_string()))?;
+            url = url
+                .join(location)
+                .map_err(|e| JmapError::ParseError(format!("Bad redirect {}: {}", location, e)))?;
+            continue;
+        }
+        return json_response(response).await;
+    }
+    Err(JmapError::ParseError("Too many redirects".to_string()))
+}
+
+async fn json_response<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+) -> JmapResult<T> {
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        return Err(JmapError::ApiError { status, body });
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| JmapError::ParseError(e.to_string()))
+}
+
+fn parse<T: serde::de::DeserializeOwned>(value: &Value) -> JmapResult<T> {
+    T::deserialize(value).map_err(|e| JmapError::ParseError(e.to_string()))
+}
+
+fn state_of(response: &Value) -> JmapResult<String> {
+    response["state"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| JmapError::ParseError("Response has no state".to_string()))
+}
+
+/// Fail with the first error of a `/set` response's `notCreated`,
+/// `notUpdated` or `notDestroyed` map
+fn set_errors(response: &Value, key: &str) -> JmapResult<()> {
+    match response[key]
+        .as_object()
+        .and_then(|errors| errors.values().next())
+    {
+        Some(error) => Err(JmapError::MethodError {
+            kind: error["type"].as_str().unwrap_or("unknown").to_string(),
+            description: error["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Fill in an RFC 6570 level 1 URI template, percent-encoding the values
+fn expand(template: &str, values: &[(&str, &str)]) -> String {
+    let mut url = template.to_string();
+    for (name, value) in values {
+        let encoded: String = url::form_urlencoded::byte_serialize(value.as_bytes())
+            .collect::<String>()
+            // Form encoding turns spaces into `+`, URI templates don't
+            .replace('+', "%20");
+        url = url.replace(&format!("{{{}}}", name), &encoded);
+    }
+    url
+}
+
+/// HTTP client that goes through the proxy configured for the server, if
+/// any. Redirects are followed by hand, see [`fetch_session`].
+fn http_client(url: &str) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string));
+    if let Some(proxy) = host.and_then(|host| northmail_proxy::proxy_for(&host)) {
+        debug!("JMAP: using proxy {}", proxy);
+        let proxy =
+            reqwest::Proxy::all(proxy.to_url()).expect("proxy URL is built from a parsed config");
+        builder = builder.proxy(proxy);
+    }
+    // Same failure mode as reqwest::Client::new()
+    builder.build().expect("failed to initialize HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_url() {
+        assert_eq!(
+            session_url("fastmail.com"),
+            "https://fastmail.com/.well-known/jmap"
+        );
+        assert_eq!(
+            session_url("https://api.fastmail.com/jmap/session/"),
+            "https://api.fastmail.com/jmap/session"
+        );
+        assert_eq!(
+            session_url("mail.example.org/jmap"),
+            "https://mail.example.org/jmap"
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let url = expand(
+            "https://h/download/{accountId}/{blobId}/{name}?accept={type}",
+            &[
+                ("accountId", "u1"),
+                ("blobId", "B 1"),
+                ("type", "message/rfc822"),
+                ("name", "message.eml"),
+            ],
+        );
+        assert_eq!(
+            url,
+            "https://h/download/u1/B%201/message.eml?accept=message%2Frfc822"
+        );
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let ok = json!({ "updated": { "e1": null }, "notUpdated": null });
+        assert!(set_errors(&ok, "notUpdated").is_ok());
+        let failed = json!({ "notUpdated": { "e1": { "type": "notFound" } } });
+        assert!(set_errors(&failed, "notUpdated")
+            .unwrap_err()
+            .is_method_error("notFound"));
+    }
+
+    #[test]
+    fn test_email_bodies() {
+        let email: Email = serde_json::from_value(json!({
+            "id": "e1",
+            "blobId": "b1",
+            "keywords": { "$seen": true },
+            "mailboxIds": { "m1": true },
+            "textBody": [{ "partId": "1", "type": "text/plain" }],
+            "htmlBody": [{ "partId": "1", "type": "text/plain" }],
+            "bodyValues": { "1": { "value": "hello", "isTruncated": false } },
+        }))
+        .unwrap();
+        assert!(email.is_seen());
+        assert!(!email.is_flagged());
+        assert!(email.is_in("m1"));
+        assert_eq!(email.text().as_deref(), Some("hello"));
+        // No HTML part: the server repeats the text one
+        assert_eq!(email.html(), None);
+    }
+}
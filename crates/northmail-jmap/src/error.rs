@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JmapError {
+    #[error("HTTP request failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+
+    #[error("JMAP server error {status}: {body}")]
+    ApiError { status: u16, body: String },
+
+    /// A method call failed, e.g. `cannotCalculateChanges`
+    #[error("JMAP method error {kind}: {description}")]
+    MethodError { kind: String, description: String },
+
+    #[error("Failed to parse response: {0}")]
+    ParseError(String),
+
+    #[error("The server offers no mail account")]
+    NoMailAccount,
+
+    /// Some copies of a message went out before the server refused
+    /// another; sending it again would repeat them
+    #[error("{sent} of {total} copies sent, then: {error}")]
+    PartialSend { sent: usize, total: usize, error: String },
+}
+
+impl JmapError {
+    /// Whether the error is a method error of this type
+    pub fn is_method_error(&self, kind: &str) -> bool {
+        matches!(self, JmapError::MethodError { kind: k, .. } if k == kind)
+    }
+}
+
+pub type JmapResult<T> = Result<T, JmapError>;
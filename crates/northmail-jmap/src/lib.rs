@@ -0,0 +1,9 @@
+pub mod client;
+pub mod error;
+pub mod push;
+pub mod types;
+
+pub use client::{JmapAuth, JmapClient};
+pub use error::{JmapError, JmapResult};
+pub use push::{EventStream, SseEvent, SseParser};
+pub use types::*;
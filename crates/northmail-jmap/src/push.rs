@@ -0,0 +1,188 @@
+//! Push notifications over an EventSource (server-sent events) stream
+//!
+//! The server keeps the response open and writes an event whenever data
+//! changes: a `state` event whose data is a [`StateChange`], or a `ping`
+//! to show the connection is still alive. Events are blocks of
+//! `field: value` lines ended by a blank line.
+
+use crate::error::{JmapError, JmapResult};
+use crate::types::StateChange;
+use tracing::debug;
+
+/// A server-sent event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// Event type, `message` when the server gave none
+    pub event: String,
+    /// Data lines, joined with newlines
+    pub data: String,
+}
+
+/// Splits a server-sent event stream into events as chunks of it arrive.
+/// Chunks may end anywhere, even inside a UTF-8 sequence.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next chunk, returning the events it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n' || b == b'\r') {
+            // A CR at the very end may be the first half of a CRLF
+            if self.buffer[end] == b'\r' && end + 1 == self.buffer.len() {
+                break;
+            }
+            let skip = if self.buffer[end] == b'\r' && self.buffer.get(end + 1) == Some(&b'\n') {
+                2
+            } else {
+                1
+            };
+            let line: Vec<u8> = self.buffer.drain(..end + skip).take(end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(event) = self.line(&line) {
+                events.push(event);
+            }
+        }
+
+        events
+    }
+
+    /// Handle one line, returning the event a blank line completes
+    fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            if self.data.is_empty() {
+                self.event = None;
+                return None;
+            }
+            return Some(SseEvent {
+                event: self.event.take().unwrap_or_else(|| "message".to_string()),
+                data: std::mem::take(&mut self.data).join("\n"),
+            });
+        }
+        // Comment, often sent to keep the connection open
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            // `id` and `retry` matter to browsers reconnecting, not here
+            _ => {}
+        }
+        None
+    }
+}
+
+/// An open EventSource connection, see [`crate::JmapClient::event_stream`]
+pub struct EventStream {
+    response: reqwest::Response,
+    parser: SseParser,
+    pending: std::collections::VecDeque<SseEvent>,
+}
+
+impl EventStream {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            parser: SseParser::new(),
+            pending: Default::default(),
+        }
+    }
+
+    /// Wait for the next state change. Pings are skipped. Returns `None`
+    /// when the server closes the stream.
+    pub async fn next_change(&mut self) -> JmapResult<Option<StateChange>> {
+        loop {
+            while let Some(event) = self.pending.pop_front() {
+                match event.event.as_str() {
+                    "state" => {
+                        let change = serde_json::from_str(&event.data).map_err(|e| {
+                            JmapError::ParseError(format!("Bad state change: {}", e))
+                        })?;
+                        return Ok(Some(change));
+                    }
+                    other => debug!("JMAP: {} event", other),
+                }
+            }
+
+            match self.response.chunk().await? {
+                Some(chunk) => self.pending.extend(self.parser.push(&chunk)),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_event() {
+        let mut parser = SseParser::new();
+        let events = parser.push(
+            b"event: state\ndata: {\"@type\":\"StateChange\",\"changed\":{\"u1\":{\"Email\":\"s2\"}}}\n\n",
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "state");
+
+        let change: StateChange = serde_json::from_str(&events[0].data).unwrap();
+        assert_eq!(change.kind, "StateChange");
+        assert!(change.touches("u1", &["Email", "Mailbox"]));
+        assert!(!change.touches("u1", &["Mailbox"]));
+        assert!(!change.touches("u2", &["Email"]));
+    }
+
+    #[test]
+    fn test_split_chunks_and_crlf() {
+        let mut parser = SseParser::new();
+        assert!(parser.push(b"event: pi").is_empty());
+        assert!(parser.push(b"ng\r").is_empty());
+        assert!(parser.push(b"\ndata: {}\r\n").is_empty());
+        let events = parser.push(b"\r\n");
+        assert_eq!(
+            events,
+            vec![SseEvent {
+                event: "ping".to_string(),
+                data: "{}".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_split_utf8() {
+        let mut parser = SseParser::new();
+        let data = "data: Grüße\n\n".as_bytes();
+        // Split inside the ü
+        assert!(parser.push(&data[..9]).is_empty());
+        let events = parser.push(&data[9..]);
+        assert_eq!(events[0].data, "Grüße");
+        assert_eq!(events[0].event, "message");
+    }
+
+    #[test]
+    fn test_multiline_data_and_comments() {
+        let mut parser = SseParser::new();
+        let events = parser.push(b": keepalive\n\ndata: a\ndata:b\nid: 4\n\nevent: x\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "a\nb");
+        // An event with no data is dropped, and its type doesn't leak on
+        let events = parser.push(b"data: c\n\n");
+        assert_eq!(events[0].event, "message");
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Capability of the core protocol (RFC 8620)
+pub const CAPABILITY_CORE: &str = "urn:ietf:params:jmap:core";
+/// Capability for mailboxes and email (RFC 8621)
+pub const CAPABILITY_MAIL: &str = "urn:ietf:params:jmap:mail";
+/// Capability for identities and sending (RFC 8621)
+pub const CAPABILITY_SUBMISSION: &str = "urn:ietf:params:jmap:submission";
+
+/// The session resource: where the API lives and which accounts it serves
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub username: String,
+    pub api_url: String,
+    /// RFC 6570 level 1 template with `accountId`, `blobId`, `type` and
+    /// `name`
+    pub download_url: String,
+    /// RFC 6570 level 1 template with `accountId`
+    pub upload_url: String,
+    /// RFC 6570 level 1 template with `types`, `closeafter` and `ping`
+    pub event_source_url: String,
+    #[serde(default)]
+    pub primary_accounts: HashMap<String, String>,
+    #[serde(default)]
+    pub capabilities: HashMap<String, serde_json::Value>,
+    pub state: String,
+}
+
+/// A mailbox, JMAP's folder. Mailboxes form a tree through `parent_id`,
+/// and a message can be in several at once.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mailbox {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    /// `inbox`, `sent`, `drafts`, `trash`, `junk`, `archive`... (RFC 8457)
+    pub role: Option<String>,
+    #[serde(default)]
+    pub total_emails: i64,
+    #[serde(default)]
+    pub unread_emails: i64,
+    #[serde(default)]
+    pub sort_order: u32,
+    /// Full hierarchical path (e.g. "Parent/Child"), built by
+    /// [`crate::JmapClient::list_mailboxes`]
+    #[serde(skip)]
+    pub full_path: String,
+}
+
+/// An address in an email header
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmailAddress {
+    pub name: Option<String>,
+    pub email: String,
+}
+
+/// A part of an email's body structure
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailBodyPart {
+    pub part_id: Option<String>,
+    pub blob_id: Option<String>,
+    #[serde(rename = "type")]
+    pub mime_type: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+    pub cid: Option<String>,
+    pub disposition: Option<String>,
+}
+
+/// Decoded content of a text body part
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailBodyValue {
+    pub value: String,
+    #[serde(default)]
+    pub is_truncated: bool,
+}
+
+/// An email, with the properties NorthMail asks for
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Email {
+    pub id: String,
+    pub blob_id: String,
+    pub thread_id: Option<String>,
+    #[serde(default)]
+    pub mailbox_ids: HashMap<String, bool>,
+    #[serde(default)]
+    pub keywords: HashMap<String, bool>,
+    #[serde(default)]
+    pub size: u64,
+    /// When the server got it, as an RFC 3339 UTC date
+    pub received_at: Option<String>,
+    /// Date header, as an RFC 3339 date
+    pub sent_at: Option<String>,
+    pub message_id: Option<Vec<String>>,
+    pub in_reply_to: Option<Vec<String>>,
+    pub from: Option<Vec<EmailAddress>>,
+    pub to: Option<Vec<EmailAddress>>,
+    pub cc: Option<Vec<EmailAddress>>,
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub has_attachment: bool,
+    pub preview: Option<String>,
+    #[serde(default)]
+    pub text_body: Vec<EmailBodyPart>,
+    #[serde(default)]
+    pub html_body: Vec<EmailBodyPart>,
+    #[serde(default)]
+    pub attachments: Vec<EmailBodyPart>,
+    #[serde(default)]
+    pub body_values: HashMap<String, EmailBodyValue>,
+}
+
+impl Email {
+    pub fn is_seen(&self) -> bool {
+        self.keywords.get("$seen").copied().unwrap_or(false)
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.keywords.get("$flagged").copied().unwrap_or(false)
+    }
+
+    pub fn is_in(&self, mailbox_id: &str) -> bool {
+        self.mailbox_ids.get(mailbox_id).copied().unwrap_or(false)
+    }
+
+    /// The decoded text of a list of body parts, joined, if the server
+    /// sent their values
+    fn body_text(&self, parts: &[EmailBodyPart]) -> Option<String> {
+        let values: Vec<&str> = parts
+            .iter()
+            .filter_map(|part| part.part_id.as_ref())
+            .filter_map(|id| self.body_values.get(id))
+            .map(|value| value.value.as_str())
+            .collect();
+        (!values.is_empty()).then(|| values.concat())
+    }
+
+    /// Plain text body, when fetched with bodies
+    pub fn text(&self) -> Option<String> {
+        self.body_text(&self.text_body)
+    }
+
+    /// HTML body, when fetched with bodies and the message has one
+    pub fn html(&self) -> Option<String> {
+        if self
+            .html_body
+            .iter()
+            .all(|part| part.mime_type != "text/html")
+        {
+            return None;
+        }
+        self.body_text(&self.html_body)
+    }
+}
+
+/// A sending identity: an address the account may send as
+#[derive(Debug, Clone, Deserialize)]
+pub struct Identity {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    pub email: String,
+}
+
+/// Emails created, updated and destroyed since a state
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailChanges {
+    pub old_state: String,
+    pub new_state: String,
+    pub has_more_changes: bool,
+    #[serde(default)]
+    pub created: Vec<String>,
+    #[serde(default)]
+    pub updated: Vec<String>,
+    #[serde(default)]
+    pub destroyed: Vec<String>,
+}
+
+/// A push notification: new states of data types, per account
+#[derive(Debug, Clone, Deserialize)]
+pub struct StateChange {
+    #[serde(rename = "@type")]
+    pub kind: String,
+    /// Account ID to type name (`Email`, `Mailbox`...) to its new state
+    #[serde(default)]
+    pub changed: HashMap<String, HashMap<String, String>>,
+}
+
+impl StateChange {
+    /// Whether any of `types` changed for the account
+    pub fn touches(&self, account_id: &str, types: &[&str]) -> bool {
+        self.changed
+            .get(account_id)
+            .is_some_and(|changed| types.iter().any(|t| changed.contains_key(*t)))
+    }
+}
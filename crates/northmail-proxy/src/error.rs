@@ -0,0 +1,30 @@
+//! Error types for proxy connections
+
+use thiserror::Error;
+
+/// Result type for proxy operations
+pub type ProxyResult<T> = Result<T, ProxyError>;
+
+/// Errors that can occur while setting up a proxy tunnel
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    /// The proxy address couldn't be parsed
+    #[error("Invalid proxy address: {0}")]
+    InvalidUrl(String),
+
+    /// The proxy rejected the credentials, or wanted some we don't have
+    #[error("Proxy authentication failed")]
+    AuthenticationFailed,
+
+    /// The proxy couldn't or wouldn't reach the destination
+    #[error("Proxy refused connection to {target}: {reason}")]
+    Refused { target: String, reason: String },
+
+    /// The proxy answered with something we don't understand
+    #[error("Unexpected proxy response: {0}")]
+    Protocol(String),
+
+    /// IO error talking to the proxy
+    #[error("Proxy connection failed: {0}")]
+    IoError(#[from] std::io::Error),
+}
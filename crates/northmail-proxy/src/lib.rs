@@ -0,0 +1,13 @@
+//! Proxy support for NorthMail
+//!
+//! IMAP, SMTP and Graph connections can go through a SOCKS5 or HTTP proxy,
+//! set globally or per account. This crate holds the routing table the
+//! clients consult and the handshakes that open a tunnel through the proxy.
+
+mod config;
+mod error;
+mod tunnel;
+
+pub use config::{proxy_for, set_default_proxy, set_host_route, ProxyConfig, ProxyKind, ProxyRoute};
+pub use error::{ProxyError, ProxyResult};
+pub use tunnel::tunnel;
@@ -1,26 +1,60 @@
 //! SMTP client implementation
 
-use crate::{SmtpError, SmtpResult};
+use crate::envelope::{self, BccDelivery};
+use crate::pool::{self, Session, SessionKey};
+use crate::trace::Recorder;
+use crate::{html_to_plain_text, sanitize_outgoing_html, SmtpError, SmtpResult};
 use lettre::{
-    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
-    transport::smtp::authentication::{Credentials, Mechanism},
-    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{
+        header::{ContentType, HeaderName, HeaderValue},
+        Attachment, Mailbox, MultiPart, SinglePart,
+    },
+    transport::smtp::{
+        authentication::{Credentials, Mechanism},
+        client::{AsyncSmtpConnection, TlsParameters},
+        extension::ClientId,
+    },
+    address::Envelope,
+    Address, Message,
 };
+use northmail_proxy::ProxyConfig;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_util::compat::TokioAsyncReadCompatExt;
 use tracing::info;
 
+/// How long to wait for the connection (and proxy tunnel, if any) to open
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// An attachment to include in an outgoing message
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutgoingAttachment {
     /// Filename to display
     pub filename: String,
     /// MIME type (e.g., "application/pdf")
     pub mime_type: String,
-    /// Raw file data
+    /// Raw file data, serialized as base64
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+
+/// An image shown in the HTML body, which refers to it as
+/// `cid:<content_id>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InlineImage {
+    /// Content-ID, without angle brackets
+    pub content_id: String,
+    /// MIME type (e.g., "image/png")
+    pub mime_type: String,
+    /// Raw image data, serialized as base64
+    #[serde(with = "base64_data")]
     pub data: Vec<u8>,
 }
 
-/// Email message to send
-#[derive(Debug, Clone)]
+/// Email message to send. It serializes to JSON so it can wait in the
+/// outbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutgoingMessage {
     /// From address
     pub from: String,
@@ -30,8 +64,11 @@ pub struct OutgoingMessage {
     pub to: Vec<String>,
     /// CC addresses
     pub cc: Vec<String>,
-    /// BCC addresses
+    /// BCC addresses, sent to through the envelope only
     pub bcc: Vec<String>,
+    /// Whether Bcc recipients share a copy or get one each
+    #[serde(default)]
+    pub bcc_delivery: BccDelivery,
     /// Subject line
     pub subject: String,
     /// Plain text body
@@ -44,6 +81,34 @@ pub struct OutgoingMessage {
     pub references: Vec<String>,
     /// File attachments
     pub attachments: Vec<OutgoingAttachment>,
+    /// Always include a text/plain part alongside HTML, deriving it from
+    /// the HTML when no text body was given
+    pub always_text_part: bool,
+    /// Message-ID to send with, instead of a generated one
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Ask for a read receipt (Disposition-Notification-To) back to the
+    /// From address
+    #[serde(default)]
+    pub request_receipt: bool,
+    /// Fields of a read receipt (message/disposition-notification). The
+    /// message is sent as the receipt, a multipart/report with the text
+    /// body, and attachments and HTML are left out.
+    #[serde(default)]
+    pub disposition_notification: Option<String>,
+    /// iCalendar object sent as a text/calendar alternative to the body,
+    /// such as a reply to a meeting invitation (iMIP, RFC 6047). Its
+    /// METHOD becomes the part's method parameter.
+    #[serde(default)]
+    pub calendar: Option<String>,
+    /// Images the HTML body shows, sent with it as a multipart/related
+    #[serde(default)]
+    pub inline_images: Vec<InlineImage>,
+    /// Upload the attachments that would take the message over its
+    /// provider's size limit and send links to them instead (see
+    /// [`crate::cloud`]). Cleared once they are uploaded.
+    #[serde(default)]
+    pub cloud_links: bool,
 }
 
 impl OutgoingMessage {
@@ -55,12 +120,20 @@ impl OutgoingMessage {
             to: Vec::new(),
             cc: Vec::new(),
             bcc: Vec::new(),
+            bcc_delivery: BccDelivery::default(),
             subject: subject.into(),
             text_body: None,
             html_body: None,
             in_reply_to: None,
             references: Vec::new(),
             attachments: Vec::new(),
+            always_text_part: false,
+            message_id: None,
+            request_receipt: false,
+            disposition_notification: None,
+            calendar: None,
+            inline_images: Vec::new(),
+            cloud_links: false,
         }
     }
 
@@ -88,6 +161,17 @@ impl OutgoingMessage {
         self
     }
 
+    /// Send each Bcc recipient a copy of their own, or one for all
+    pub fn bcc_delivery(mut self, delivery: BccDelivery) -> Self {
+        self.bcc_delivery = delivery;
+        self
+    }
+
+    /// Envelope recipients of each copy to send (see [`crate::envelope`])
+    pub fn envelopes(&self) -> Vec<Vec<String>> {
+        envelope::envelopes(&self.to, &self.cc, &self.bcc, self.bcc_delivery)
+    }
+
     /// Set the plain text body
     pub fn text(mut self, body: impl Into<String>) -> Self {
         self.text_body = Some(body.into());
@@ -100,6 +184,21 @@ impl OutgoingMessage {
         self
     }
 
+    /// Always send multipart/alternative with a text part when there is HTML
+    pub fn always_text_part(mut self, enabled: bool) -> Self {
+        self.always_text_part = enabled;
+        self
+    }
+
+    /// Plain text body to send, derived from the HTML if requested and missing
+    pub fn effective_text_body(&self) -> Option<String> {
+        match (&self.text_body, &self.html_body) {
+            (Some(text), _) if !text.trim().is_empty() => Some(text.clone()),
+            (_, Some(html)) if self.always_text_part => Some(html_to_plain_text(html)),
+            (text, _) => text.clone(),
+        }
+    }
+
     /// Set the In-Reply-To header
     pub fn reply_to_message(mut self, message_id: impl Into<String>) -> Self {
         self.in_reply_to = Some(message_id.into());
@@ -112,6 +211,40 @@ impl OutgoingMessage {
         self
     }
 
+    /// Set the Message-ID header
+    pub fn message_id(mut self, message_id: impl Into<String>) -> Self {
+        self.message_id = Some(message_id.into());
+        self
+    }
+
+    /// Ask for a read receipt
+    pub fn request_receipt(mut self, enabled: bool) -> Self {
+        self.request_receipt = enabled;
+        self
+    }
+
+    /// Send the message as a read receipt with these disposition fields
+    pub fn disposition_notification(mut self, fields: impl Into<String>) -> Self {
+        self.disposition_notification = Some(fields.into());
+        self
+    }
+
+    /// Send an iCalendar object alongside the body
+    pub fn calendar(mut self, ics: impl Into<String>) -> Self {
+        self.calendar = Some(ics.into());
+        self
+    }
+
+    /// Add an image for the HTML body to show as `cid:<content_id>`
+    pub fn inline_image(mut self, content_id: impl Into<String>, mime_type: impl Into<String>, data: Vec<u8>) -> Self {
+        self.inline_images.push(InlineImage {
+            content_id: content_id.into(),
+            mime_type: mime_type.into(),
+            data,
+        });
+        self
+    }
+
     /// Add an attachment
     pub fn attachment(mut self, filename: impl Into<String>, mime_type: impl Into<String>, data: Vec<u8>) -> Self {
         self.attachments.push(OutgoingAttachment {
@@ -123,6 +256,23 @@ impl OutgoingMessage {
     }
 }
 
+/// Attachment data as a base64 string rather than an array of numbers
+mod base64_data {
+    use base64::Engine;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(D::Error::custom)
+    }
+}
+
 /// SMTP client for sending emails
 pub struct SmtpClient {
     host: String,
@@ -157,23 +307,10 @@ impl SmtpClient {
     ) -> SmtpResult<()> {
         info!("Sending email via SMTP with XOAUTH2");
 
-        // Build the lettre message
-        let lettre_message = self.build_message(&message)?;
-
         // lettre's Xoauth2 mechanism expects the access token directly -
         // it constructs and encodes the XOAUTH2 string internally
-        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
-            .map_err(|e| SmtpError::ConnectionFailed(e.to_string()))?
-            .port(self.port)
-            .credentials(Credentials::new(email.to_string(), access_token.to_string()))
-            .authentication(vec![Mechanism::Xoauth2])
-            .build();
-
-        // Send the message
-        transport
-            .send(lettre_message)
-            .await
-            .map_err(|e| SmtpError::SendFailed(e.to_string()))?;
+        self.send(email, access_token, Mechanism::Xoauth2, &message)
+            .await?;
 
         info!("Email sent successfully");
         Ok(())
@@ -188,22 +325,113 @@ impl SmtpClient {
     ) -> SmtpResult<()> {
         info!("Sending email via SMTP with password auth");
 
-        let lettre_message = self.build_message(&message)?;
+        self.send(email, password, Mechanism::Plain, &message)
+            .await?;
 
-        let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)
-            .map_err(|e| SmtpError::ConnectionFailed(e.to_string()))?
-            .port(self.port)
-            .credentials(Credentials::new(email.to_string(), password.to_string()))
-            .authentication(vec![Mechanism::Plain])
-            .build();
+        info!("Email sent successfully");
+        Ok(())
+    }
 
-        transport
-            .send(lettre_message)
+    /// Send each copy of a message (see [`OutgoingMessage::envelopes`]) over
+    /// STARTTLS, through the host's proxy if one is configured, on a session
+    /// an earlier send left open if there is one (see [`crate::pool`])
+    async fn send(&self, username: &str, secret: &str, mechanism: Mechanism, message: &OutgoingMessage) -> SmtpResult<()> {
+        let body = self.build_message(message)?.formatted();
+        let envelopes = message
+            .envelopes()
+            .iter()
+            .map(|recipients| smtp_envelope(&message.from, recipients))
+            .collect::<SmtpResult<Vec<_>>>()?;
+
+        let key = SessionKey {
+            host: self.host.clone(),
+            port: self.port,
+            username: username.to_string(),
+        };
+        let mut session = match pool::take(&key).await {
+            Some(session) => session,
+            None => {
+                let credentials = Credentials::new(username.to_string(), secret.to_string());
+                self.open_session(&credentials, mechanism).await?
+            }
+        };
+        for (sent, envelope) in envelopes.iter().enumerate() {
+            if let Err(e) = session.send(envelope, &body).await {
+                session.connection.abort().await;
+                // A 4xx reply, or the connection dropping, is worth another
+                // try, unless copies went out already that it would send again
+                if sent > 0 {
+                    return Err(SmtpError::SendFailed(format!(
+                        "{} of {} copies sent, then: {}",
+                        sent,
+                        envelopes.len(),
+                        e
+                    )));
+                }
+                return if e.is_permanent() || e.is_client() || e.is_response() {
+                    Err(SmtpError::SendFailed(e.to_string()))
+                } else {
+                    Err(SmtpError::Deferred(e.to_string()))
+                };
+            }
+        }
+        pool::put_back(key, session).await;
+        Ok(())
+    }
+
+    /// Connect, STARTTLS and authenticate. We drive the connection ourselves
+    /// rather than through lettre's transport, which only dials servers
+    /// directly, can't be traced and doesn't hand its connection back.
+    async fn open_session(&self, credentials: &Credentials, mechanism: Mechanism) -> SmtpResult<Session> {
+        let proxy = northmail_proxy::proxy_for(&self.host);
+        let trace = Recorder::start(&self.host);
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, self.open_stream(proxy.as_ref()))
             .await
-            .map_err(|e| SmtpError::SendFailed(e.to_string()))?;
+            .map_err(|_| SmtpError::ConnectionFailed(format!("Timed out connecting to {}", self.host)))?
+            .inspect_err(|e| trace.server(&format!("error: {}", e)))?;
 
-        info!("Email sent successfully");
-        Ok(())
+        let hello = ClientId::default();
+        let mut connection = AsyncSmtpConnection::connect_with_transport(Box::new(stream), &hello)
+            .await
+            .inspect_err(|e| trace.server(&format!("error: {}", e)))
+            .map_err(|e| SmtpError::ConnectionFailed(e.to_string()))?;
+        trace.server(&connection.server_info().to_string());
+
+        let tls = TlsParameters::new(self.host.clone()).map_err(|e| SmtpError::TlsError(e.to_string()))?;
+        trace.client("STARTTLS");
+        match connection.starttls(tls, &hello).await {
+            Ok(()) => trace.server(&connection.server_info().to_string()),
+            Err(e) => {
+                trace.server(&format!("error: {}", e));
+                return Err(SmtpError::TlsError(e.to_string()));
+            }
+        }
+
+        trace.client(&format!("AUTH {} ***", mechanism));
+        let result = connection.auth(&[mechanism], credentials).await;
+        trace.reply(&result);
+        result.map_err(|e| SmtpError::AuthenticationFailed(e.to_string()))?;
+
+        Ok(Session { connection, trace })
+    }
+
+    /// TCP connection to the server, tunnelled through `proxy` if given
+    async fn open_stream(&self, proxy: Option<&ProxyConfig>) -> SmtpResult<TcpStream> {
+        let Some(proxy) = proxy else {
+            return TcpStream::connect((self.host.as_str(), self.port))
+                .await
+                .map_err(|e| SmtpError::ConnectionFailed(e.to_string()));
+        };
+
+        info!("Connecting to {} through proxy {}", self.host, proxy);
+        let tcp_stream = TcpStream::connect((proxy.host.as_str(), proxy.port))
+            .await
+            .map_err(|e| SmtpError::ConnectionFailed(format!("{}: {}", proxy, e)))?;
+        let mut stream = tcp_stream.compat();
+        northmail_proxy::tunnel(&mut stream, proxy, &self.host, self.port)
+            .await
+            .map_err(|e| SmtpError::ConnectionFailed(e.to_string()))?;
+        Ok(stream.into_inner())
     }
 
     /// Build a lettre Message from OutgoingMessage
@@ -212,8 +440,29 @@ impl SmtpClient {
     }
 }
 
-/// Build a lettre Message from OutgoingMessage (standalone, no SmtpClient needed)
+/// The SMTP envelope of one copy, from `from` to `recipients`
+fn smtp_envelope(from: &str, recipients: &[String]) -> SmtpResult<Envelope> {
+    let address = |address: &str| {
+        address
+            .parse::<Address>()
+            .map_err(|e| SmtpError::InvalidAddress(format!("{}: {}", address, e)))
+    };
+    let recipients = recipients
+        .iter()
+        .map(|recipient| address(recipient))
+        .collect::<SmtpResult<Vec<_>>>()?;
+    Envelope::new(Some(address(from)?), recipients).map_err(|e| SmtpError::MessageBuildError(e.to_string()))
+}
+
+/// Build a lettre Message from OutgoingMessage (standalone, no SmtpClient
+/// needed), without the Bcc header
 pub fn build_lettre_message(msg: &OutgoingMessage) -> SmtpResult<Message> {
+    build_message_with_options(msg, false)
+}
+
+/// Build a lettre Message, optionally keeping the Bcc header (needed when the
+/// server derives recipients from the MIME headers, e.g. Graph MIME upload)
+pub(crate) fn build_message_with_options(msg: &OutgoingMessage, keep_bcc: bool) -> SmtpResult<Message> {
     // Parse from address
     let from_mailbox = if let Some(ref name) = msg.from_name {
         Mailbox::new(
@@ -273,8 +522,36 @@ pub fn build_lettre_message(msg: &OutgoingMessage) -> SmtpResult<Message> {
         builder = builder.references(msg.references.join(" "));
     }
 
+    if let Some(ref message_id) = msg.message_id {
+        builder = builder.message_id(Some(message_id.clone()));
+    }
+
+    if keep_bcc {
+        builder = builder.keep_bcc();
+    }
+
+    if let Some(ref fields) = msg.disposition_notification {
+        return builder
+            .multipart(build_disposition_report(msg, fields)?)
+            .map_err(|e| SmtpError::MessageBuildError(e.to_string()));
+    }
+
+    // Strip remote resources so opening our mail can't be tracked
+    let html_body = msg.html_body.as_deref().map(sanitize_outgoing_html);
+    let text_body = msg.effective_text_body();
+
     // Build the body part (text/html or multipart/alternative)
-    let body_part = match (&msg.text_body, &msg.html_body) {
+    let mut body_part = match (&text_body, &html_body) {
+        (Some(text), Some(html)) if !msg.inline_images.is_empty() => MultiPart::alternative()
+            .singlepart(
+                SinglePart::builder()
+                    .header(ContentType::TEXT_PLAIN)
+                    .body(text.clone()),
+            )
+            .multipart(build_related_part(html, &msg.inline_images)),
+        (None, Some(html)) if !msg.inline_images.is_empty() => {
+            MultiPart::alternative().multipart(build_related_part(html, &msg.inline_images))
+        }
         (Some(text), Some(html)) => {
             // Multipart alternative for both text and HTML
             MultiPart::alternative()
@@ -306,8 +583,12 @@ pub fn build_lettre_message(msg: &OutgoingMessage) -> SmtpResult<Message> {
         ),
     };
 
+    if let Some(ref ics) = msg.calendar {
+        body_part = body_part.singlepart(build_calendar_part(ics)?);
+    }
+
     // If there are attachments, wrap in multipart/mixed
-    let message = if msg.attachments.is_empty() {
+    let mut message = if msg.attachments.is_empty() {
         builder
             .multipart(body_part)
             .map_err(|e| SmtpError::MessageBuildError(e.to_string()))?
@@ -330,5 +611,71 @@ pub fn build_lettre_message(msg: &OutgoingMessage) -> SmtpResult<Message> {
             .map_err(|e| SmtpError::MessageBuildError(e.to_string()))?
     };
 
+    if msg.request_receipt {
+        message.headers_mut().insert_raw(HeaderValue::new(
+            HeaderName::new_from_ascii_str("Disposition-Notification-To"),
+            format!("<{}>", msg.from),
+        ));
+    }
+
     Ok(message)
 }
+
+/// The HTML body with the images it shows (multipart/related, RFC 2387)
+fn build_related_part(html: &str, images: &[InlineImage]) -> MultiPart {
+    let mut related = MultiPart::related().singlepart(
+        SinglePart::builder()
+            .header(ContentType::TEXT_HTML)
+            .body(html.to_string()),
+    );
+    for image in images {
+        let content_type = image
+            .mime_type
+            .parse::<ContentType>()
+            .unwrap_or(ContentType::parse("application/octet-stream").unwrap());
+        related = related.singlepart(Attachment::new_inline(image.content_id.clone()).body(image.data.clone(), content_type));
+    }
+    related
+}
+
+/// The text/calendar part for an iCalendar object, labelled with its METHOD
+fn build_calendar_part(ics: &str) -> SmtpResult<SinglePart> {
+    let method = ics
+        .lines()
+        .find_map(|line| line.strip_prefix("METHOD:"))
+        .map(str::trim)
+        .filter(|method| !method.is_empty() && method.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'));
+    let content_type = match method {
+        Some(method) => format!("text/calendar; charset=utf-8; method={}", method),
+        None => "text/calendar; charset=utf-8".to_string(),
+    };
+    let content_type =
+        ContentType::parse(&content_type).map_err(|e| SmtpError::MessageBuildError(e.to_string()))?;
+    Ok(SinglePart::builder().header(content_type).body(ics.to_string()))
+}
+
+/// A read receipt: multipart/report with the text body and the
+/// disposition fields (RFC 8098)
+fn build_disposition_report(msg: &OutgoingMessage, fields: &str) -> SmtpResult<MultiPart> {
+    let notification_type = ContentType::parse("message/disposition-notification")
+        .map_err(|e| SmtpError::MessageBuildError(e.to_string()))?;
+    let mut report = MultiPart::mixed()
+        .singlepart(
+            SinglePart::builder()
+                .header(ContentType::TEXT_PLAIN)
+                .body(msg.text_body.clone().unwrap_or_default()),
+        )
+        .singlepart(
+            SinglePart::builder()
+                .header(notification_type)
+                .body(fields.to_string()),
+        );
+    // lettre has no multipart/report, so relabel the multipart/mixed
+    let report_type = ContentType::parse(&format!(
+        "multipart/report; report-type=disposition-notification; boundary=\"{}\"",
+        report.boundary()
+    ))
+    .map_err(|e| SmtpError::MessageBuildError(e.to_string()))?;
+    report.headers_mut().set(report_type);
+    Ok(report)
+}
@@ -0,0 +1,429 @@
+//! Large attachments sent as cloud links
+//!
+//! Providers refuse messages over a size limit, counted on the message as
+//! sent, where attachments are base64-encoded and a third larger. When a
+//! message would be over its account's limit, the composer offers to upload
+//! the attachments that don't fit to the account's own cloud storage, Google
+//! Drive for Google accounts and OneDrive for Microsoft 365 ones, with the
+//! OAuth token the account already has, and to send links to them instead.
+//! The message is queued with [`OutgoingMessage::cloud_links`] set and the
+//! upload happens in the outbox, just before sending, so a failed upload is
+//! retried like a failed send.
+//!
+//! Files are shared with anyone who has the link, read-only; on OneDrive
+//! tenants that don't allow that, with the organization only.
+
+use crate::msgraph::http_client;
+use crate::{OutgoingAttachment, OutgoingMessage, SmtpError, SmtpResult};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tracing::info;
+
+/// Size limit of Gmail and Google Workspace
+pub const GOOGLE_LIMIT: u64 = 25_000_000;
+
+/// Size limit of most other providers, Outlook.com and iCloud among them
+pub const DEFAULT_LIMIT: u64 = 20_000_000;
+
+/// Allowance for the headers and MIME structure around the parts
+const ENVELOPE_BYTES: u64 = 4096;
+
+const DRIVE_HOST: &str = "www.googleapis.com";
+const DRIVE_UPLOAD_URL: &str = "https://www.googleapis.com/upload/drive/v3/files?uploadType=resumable&fields=id,webViewLink";
+const DRIVE_FILES_URL: &str = "https://www.googleapis.com/drive/v3/files";
+
+const GRAPH_HOST: &str = "graph.microsoft.com";
+const ONEDRIVE_URL: &str = "https://graph.microsoft.com/v1.0/me/drive";
+/// Folder OneDrive uploads go to, created on first use
+const ONEDRIVE_FOLDER: &str = "NorthMail Attachments";
+/// Bytes sent per request to a OneDrive upload session, which takes
+/// multiples of 320 KiB
+const ONEDRIVE_CHUNK: usize = 32 * 320 * 1024;
+
+/// Where an account can put attachments too large to send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudStorage {
+    GoogleDrive,
+    OneDrive,
+}
+
+impl CloudStorage {
+    /// The storage that comes with an account of `provider_type`. Only
+    /// Microsoft 365 accounts have a token Graph takes.
+    pub fn for_provider(provider_type: &str) -> Option<Self> {
+        match provider_type {
+            "google" => Some(Self::GoogleDrive),
+            "ms_graph" => Some(Self::OneDrive),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GoogleDrive => "Google Drive",
+            Self::OneDrive => "OneDrive",
+        }
+    }
+}
+
+/// Largest message an account of `provider_type` sends, as sent
+pub fn message_size_limit(provider_type: &str) -> u64 {
+    match provider_type {
+        "google" => GOOGLE_LIMIT,
+        _ => DEFAULT_LIMIT,
+    }
+}
+
+/// Size of `bytes` once base64-encoded in lines of 76
+pub fn encoded_size(bytes: u64) -> u64 {
+    let encoded = bytes.div_ceil(3) * 4;
+    encoded + encoded.div_ceil(76) * 2
+}
+
+/// Roughly how large `message` is as sent: its bodies, and its
+/// attachments and inline images encoded
+pub fn estimated_size(message: &OutgoingMessage) -> u64 {
+    let bodies = [&message.text_body, &message.html_body, &message.calendar]
+        .into_iter()
+        .flatten()
+        .map(|body| body.len() as u64)
+        .sum::<u64>();
+    let parts = message
+        .attachments
+        .iter()
+        .map(|attachment| attachment.data.len())
+        .chain(message.inline_images.iter().map(|image| image.data.len()))
+        .map(|len| encoded_size(len as u64))
+        .sum::<u64>();
+    ENVELOPE_BYTES + bodies + parts
+}
+
+/// Indices of the attachments to upload so `message` fits in `limit`: the
+/// largest first, until what is left fits. Empty when it fits already.
+pub fn attachments_to_link(message: &OutgoingMessage, limit: u64) -> Vec<usize> {
+    let mut size = estimated_size(message);
+    let mut by_size: Vec<usize> = (0..message.attachments.len())
+        .filter(|&index| !message.attachments[index].data.is_empty())
+        .collect();
+    by_size.sort_by_key(|&index| std::cmp::Reverse(message.attachments[index].data.len()));
+
+    let mut linked = Vec::new();
+    for index in by_size {
+        if size <= limit {
+            break;
+        }
+        size -= encoded_size(message.attachments[index].data.len() as u64);
+        linked.push(index);
+    }
+    linked.sort_unstable();
+    linked
+}
+
+/// An uploaded attachment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudLink {
+    pub filename: String,
+    pub size: u64,
+    pub url: String,
+}
+
+/// `bytes` for people: "12.5 MB", "300 KB"
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000 {
+        format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+    } else {
+        format!("{} KB", bytes.div_ceil(1000))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Take the attachments at `indices` out of `message` and list `links` to
+/// them, in the same order, at the end of its bodies
+fn link_attachments(message: &mut OutgoingMessage, indices: &[usize], links: &[CloudLink]) {
+    for &index in indices.iter().rev() {
+        message.attachments.remove(index);
+    }
+    if links.is_empty() {
+        return;
+    }
+
+    let text: String = links
+        .iter()
+        .map(|link| format!("\n{} ({}): {}", link.filename, format_size(link.size), link.url))
+        .collect();
+    message.text_body = Some(format!("{}\n{}", message.text_body.as_deref().unwrap_or_default(), text));
+
+    if let Some(html) = &message.html_body {
+        let items: String = links
+            .iter()
+            .map(|link| {
+                format!(
+                    "<a href=\"{}\">{}</a> ({})<br>",
+                    escape_html(&link.url),
+                    escape_html(&link.filename),
+                    format_size(link.size)
+                )
+            })
+            .collect();
+        let block = format!("<br><div class=\"northmail-cloud-links\">{}</div>", items);
+        let html = match html.rfind("</body>") {
+            Some(end) => format!("{}{}{}", &html[..end], block, &html[end..]),
+            None => format!("{}{}", html, block),
+        };
+        message.html_body = Some(html);
+    }
+}
+
+/// Upload the attachments that take `message` over `limit` to `storage`
+/// and send links to them instead, with the account's `access_token`
+pub async fn upload_large_attachments(
+    mut message: OutgoingMessage,
+    storage: CloudStorage,
+    access_token: &str,
+    limit: u64,
+) -> SmtpResult<OutgoingMessage> {
+    let indices = attachments_to_link(&message, limit);
+    let mut links = Vec::with_capacity(indices.len());
+    for &index in &indices {
+        let attachment = &message.attachments[index];
+        info!(
+            "Uploading {} ({} bytes) to {}",
+            attachment.filename,
+            attachment.data.len(),
+            storage.name()
+        );
+        let url = match storage {
+            CloudStorage::GoogleDrive => upload_to_drive(access_token, attachment).await?,
+            CloudStorage::OneDrive => upload_to_onedrive(access_token, attachment).await?,
+        };
+        links.push(CloudLink {
+            filename: attachment.filename.clone(),
+            size: attachment.data.len() as u64,
+            url,
+        });
+    }
+    link_attachments(&mut message, &indices, &links);
+    message.cloud_links = false;
+    Ok(message)
+}
+
+/// An upload the service refused; worth trying again when it was busy
+fn upload_error(storage: CloudStorage, status: StatusCode, body: String) -> SmtpError {
+    let message = format!("{} returned {}: {}", storage.name(), status, body);
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        SmtpError::Deferred(message)
+    } else {
+        SmtpError::SendFailed(message)
+    }
+}
+
+fn request_error(storage: CloudStorage, e: reqwest::Error) -> SmtpError {
+    SmtpError::Deferred(format!("Upload to {} failed: {}", storage.name(), e))
+}
+
+/// The JSON body of a successful response
+async fn json_response(storage: CloudStorage, response: reqwest::Response) -> SmtpResult<Value> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(upload_error(storage, status, body));
+    }
+    response.json().await.map_err(|e| request_error(storage, e))
+}
+
+fn field(value: &Value, pointer: &str, storage: CloudStorage) -> SmtpResult<String> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| SmtpError::SendFailed(format!("{} sent no {}", storage.name(), pointer)))
+}
+
+/// Upload to Drive in one resumable request, share it with anyone who has
+/// the link, and return the link
+async fn upload_to_drive(access_token: &str, attachment: &OutgoingAttachment) -> SmtpResult<String> {
+    let storage = CloudStorage::GoogleDrive;
+    let client = http_client(DRIVE_HOST)?;
+
+    let response = client
+        .post(DRIVE_UPLOAD_URL)
+        .bearer_auth(access_token)
+        .header("X-Upload-Content-Type", &attachment.mime_type)
+        .header("X-Upload-Content-Length", attachment.data.len())
+        .json(&json!({ "name": attachment.filename }))
+        .send()
+        .await
+        .map_err(|e| request_error(storage, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(upload_error(storage, status, body));
+    }
+    let session = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| SmtpError::SendFailed("Google Drive sent no upload location".to_string()))?;
+
+    let response = client
+        .put(session)
+        .header(reqwest::header::CONTENT_TYPE, &attachment.mime_type)
+        .body(attachment.data.clone())
+        .send()
+        .await
+        .map_err(|e| request_error(storage, e))?;
+    let file = json_response(storage, response).await?;
+    let id = field(&file, "/id", storage)?;
+
+    let response = client
+        .post(format!("{}/{}/permissions", DRIVE_FILES_URL, id))
+        .bearer_auth(access_token)
+        .json(&json!({ "role": "reader", "type": "anyone" }))
+        .send()
+        .await
+        .map_err(|e| request_error(storage, e))?;
+    json_response(storage, response).await?;
+
+    field(&file, "/webViewLink", storage)
+}
+
+/// `text` as one segment of a URL path
+fn encode_path_segment(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Upload to OneDrive through an upload session, a chunk at a time, into
+/// [`ONEDRIVE_FOLDER`], and return a view link to it
+async fn upload_to_onedrive(access_token: &str, attachment: &OutgoingAttachment) -> SmtpResult<String> {
+    let storage = CloudStorage::OneDrive;
+    let client = http_client(GRAPH_HOST)?;
+
+    let url = format!(
+        "{}/root:/{}/{}:/createUploadSession",
+        ONEDRIVE_URL,
+        encode_path_segment(ONEDRIVE_FOLDER),
+        encode_path_segment(&attachment.filename)
+    );
+    let response = client
+        .post(url)
+        .bearer_auth(access_token)
+        .json(&json!({ "item": { "@microsoft.graph.conflictBehavior": "rename" } }))
+        .send()
+        .await
+        .map_err(|e| request_error(storage, e))?;
+    let session = json_response(storage, response).await?;
+    let upload_url = field(&session, "/uploadUrl", storage)?;
+
+    // The session URL carries its own authorization and refuses a token
+    let total = attachment.data.len();
+    let mut item = Value::Null;
+    for (index, chunk) in attachment.data.chunks(ONEDRIVE_CHUNK).enumerate() {
+        let start = index * ONEDRIVE_CHUNK;
+        let response = client
+            .put(&upload_url)
+            .header(
+                reqwest::header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, start + chunk.len() - 1, total),
+            )
+            .body(chunk.to_vec())
+            .send()
+            .await
+            .map_err(|e| request_error(storage, e))?;
+        item = json_response(storage, response).await?;
+    }
+    let id = field(&item, "/id", storage)?;
+
+    let mut refused = None;
+    for scope in ["anonymous", "organization"] {
+        let response = client
+            .post(format!("{}/items/{}/createLink", ONEDRIVE_URL, id))
+            .bearer_auth(access_token)
+            .json(&json!({ "type": "view", "scope": scope }))
+            .send()
+            .await
+            .map_err(|e| request_error(storage, e))?;
+        match json_response(storage, response).await {
+            Ok(link) => return field(&link, "/link/webUrl", storage),
+            Err(e) => refused = Some(e),
+        }
+    }
+    Err(refused.unwrap_or_else(|| SmtpError::SendFailed("OneDrive made no link".to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(attachments: &[(&str, usize)]) -> OutgoingMessage {
+        let mut message = OutgoingMessage::new("me@example.com", "Photos");
+        message.text_body = Some("Here they are".to_string());
+        for (filename, size) in attachments {
+            message = message.attachment(*filename, "image/jpeg", vec![0; *size]);
+        }
+        message
+    }
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(encoded_size(0), 0);
+        // 57 bytes make one full line
+        assert_eq!(encoded_size(57), 78);
+        assert_eq!(encoded_size(58), 84);
+        assert_eq!(CloudStorage::for_provider("google"), Some(CloudStorage::GoogleDrive));
+        assert_eq!(CloudStorage::for_provider("ms_graph"), Some(CloudStorage::OneDrive));
+        assert_eq!(CloudStorage::for_provider("windows_live"), None);
+        assert_eq!(message_size_limit("google"), GOOGLE_LIMIT);
+    }
+
+    #[test]
+    fn test_attachments_to_link() {
+        let message = message(&[("a.jpg", 6_000_000), ("b.jpg", 9_000_000), ("c.jpg", 4_000_000)]);
+        assert!(attachments_to_link(&message, 30_000_000).is_empty());
+        // The largest goes first, then as many as it takes
+        assert_eq!(attachments_to_link(&message, 20_000_000), vec![1]);
+        assert_eq!(attachments_to_link(&message, 10_000_000), vec![0, 1]);
+        assert_eq!(attachments_to_link(&message, 100), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_link_attachments() {
+        let mut message = message(&[("a.jpg", 10), ("b & c.jpg", 2_500_000)]);
+        message.html_body = Some("<html><body><p>Here they are</p></body></html>".to_string());
+        let link = CloudLink {
+            filename: "b & c.jpg".to_string(),
+            size: 2_500_000,
+            url: "https://example.com/s?id=1&view".to_string(),
+        };
+        link_attachments(&mut message, &[1], &[link]);
+
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].filename, "a.jpg");
+        assert_eq!(
+            message.text_body.as_deref(),
+            Some("Here they are\n\nb & c.jpg (2.5 MB): https://example.com/s?id=1&view")
+        );
+        assert_eq!(
+            message.html_body.as_deref(),
+            Some(
+                "<html><body><p>Here they are</p><br><div class=\"northmail-cloud-links\">\
+                 <a href=\"https://example.com/s?id=1&amp;view\">b &amp; c.jpg</a> (2.5 MB)<br>\
+                 </div></body></html>"
+            )
+        );
+        assert_eq!(encode_path_segment("b & c.jpg"), "b%20%26%20c.jpg");
+    }
+}
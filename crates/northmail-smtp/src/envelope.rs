@@ -0,0 +1,96 @@
+//! Who each copy of a message goes to
+//!
+//! Bcc recipients are never in a message's headers: the Bcc header is
+//! dropped when the message is built, and they are reached through the
+//! envelope alone, the RCPT TO of SMTP or the envelope of a JMAP
+//! submission. By default one copy goes to everyone. With
+//! [`BccDelivery::Separate`] each Bcc recipient gets a copy of their own
+//! with them alone in its envelope, as servers may name every envelope
+//! recipient of a copy in its trace headers (`Received: … for`) or bounce
+//! reports.
+//!
+//! Microsoft Graph takes recipients from the message rather than an
+//! envelope, so it sends one copy and leaves Bcc to Exchange, which keeps it
+//! out of the delivered headers itself.
+
+use serde::{Deserialize, Serialize};
+
+/// How Bcc recipients are sent to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BccDelivery {
+    /// One copy for everyone, the Bcc recipients in its envelope
+    #[default]
+    Shared,
+    /// One copy for To and Cc, and one for each Bcc recipient alone
+    Separate,
+}
+
+/// The envelope recipients of each copy to send, in order. An address
+/// is sent to once, however often it was given.
+pub fn envelopes(to: &[String], cc: &[String], bcc: &[String], delivery: BccDelivery) -> Vec<Vec<String>> {
+    let mut seen: Vec<String> = Vec::new();
+    let mut first_time = |address: &String| {
+        let address = address.trim();
+        let key = address.to_lowercase();
+        if address.is_empty() || seen.contains(&key) {
+            return None;
+        }
+        seen.push(key);
+        Some(address.to_string())
+    };
+
+    let visible: Vec<String> = to.iter().chain(cc).filter_map(&mut first_time).collect();
+    let hidden: Vec<String> = bcc.iter().filter_map(&mut first_time).collect();
+    match delivery {
+        BccDelivery::Shared => {
+            let all: Vec<String> = visible.into_iter().chain(hidden).collect();
+            if all.is_empty() {
+                Vec::new()
+            } else {
+                vec![all]
+            }
+        }
+        BccDelivery::Separate => (!visible.is_empty())
+            .then_some(visible)
+            .into_iter()
+            .chain(hidden.into_iter().map(|address| vec![address]))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_envelopes() {
+        let to = list(&["ann@example.com"]);
+        let cc = list(&["bob@example.com"]);
+        let bcc = list(&["carl@example.com", "Ann@Example.com", "dora@example.com"]);
+
+        assert_eq!(
+            envelopes(&to, &cc, &bcc, BccDelivery::Shared),
+            vec![list(&["ann@example.com", "bob@example.com", "carl@example.com", "dora@example.com"])]
+        );
+        // Ann is in To already and gets no second copy
+        assert_eq!(
+            envelopes(&to, &cc, &bcc, BccDelivery::Separate),
+            vec![
+                list(&["ann@example.com", "bob@example.com"]),
+                list(&["carl@example.com"]),
+                list(&["dora@example.com"]),
+            ]
+        );
+        // Bcc only
+        assert_eq!(
+            envelopes(&[], &[], &bcc[..1], BccDelivery::Separate),
+            vec![list(&["carl@example.com"])]
+        );
+        assert!(envelopes(&[], &[], &[], BccDelivery::Shared).is_empty());
+    }
+}
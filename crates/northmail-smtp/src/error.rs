@@ -31,4 +31,16 @@ pub enum SmtpError {
     /// TLS error
     #[error("TLS error: {0}")]
     TlsError(String),
+
+    /// The server or the network turned the message away for now; it may
+    /// go through later
+    #[error("Message deferred: {0}")]
+    Deferred(String),
+}
+
+impl SmtpError {
+    /// Whether sending again later may succeed without changing anything
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SmtpError::ConnectionFailed(_) | SmtpError::Deferred(_))
+    }
 }
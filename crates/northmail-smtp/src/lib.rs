@@ -4,8 +4,15 @@
 //! and Microsoft Graph API sending for Outlook/Exchange accounts.
 
 mod client;
+pub mod cloud;
+mod envelope;
 mod error;
 pub mod msgraph;
+mod pool;
+mod sanitize;
+mod trace;
 
-pub use client::{build_lettre_message, OutgoingAttachment, OutgoingMessage, SmtpClient};
+pub use client::{build_lettre_message, InlineImage, OutgoingAttachment, OutgoingMessage, SmtpClient};
+pub use envelope::BccDelivery;
 pub use error::{SmtpError, SmtpResult};
+pub use sanitize::{html_to_plain_text, sanitize_outgoing_html};
@@ -3,12 +3,27 @@
 //! Sends emails via POST /me/sendMail using the Graph API.
 //! This works with GOA OAuth2 tokens which have `mail.send` scope.
 
-use crate::{OutgoingMessage, SmtpError, SmtpResult};
+use crate::client::build_message_with_options;
+use crate::{sanitize_outgoing_html, OutgoingMessage, SmtpError, SmtpResult};
 use base64::Engine;
 use serde::Serialize;
 use tracing::info;
 
 const GRAPH_SEND_MAIL_URL: &str = "https://graph.microsoft.com/v1.0/me/sendMail";
+const GRAPH_HOST: &str = "graph.microsoft.com";
+
+/// HTTP client that goes through the proxy configured for `host`, if any
+pub(crate) fn http_client(host: &str) -> SmtpResult<reqwest::Client> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = northmail_proxy::proxy_for(host) {
+        let proxy = reqwest::Proxy::all(proxy.to_url())
+            .map_err(|e| SmtpError::ConnectionFailed(format!("Invalid proxy {}: {}", proxy, e)))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| SmtpError::ConnectionFailed(e.to_string()))
+}
 
 #[derive(Serialize)]
 struct SendMailRequest {
@@ -71,8 +86,20 @@ struct GraphAttachment {
 pub async fn send_via_graph(access_token: &str, message: OutgoingMessage) -> SmtpResult<()> {
     info!("Sending email via Microsoft Graph API");
 
+    // The JSON body carries a single content type and no receipt headers, so
+    // when a text alternative, a receipt, a calendar part or inline images
+    // are wanted we upload the full MIME message instead
+    if (message.always_text_part && message.html_body.is_some())
+        || message.request_receipt
+        || message.disposition_notification.is_some()
+        || message.calendar.is_some()
+        || !message.inline_images.is_empty()
+    {
+        return send_mime_via_graph(access_token, &message).await;
+    }
+
     let (content_type, content) = match (&message.html_body, &message.text_body) {
-        (Some(html), _) => ("HTML".to_string(), html.clone()),
+        (Some(html), _) => ("HTML".to_string(), sanitize_outgoing_html(html)),
         (None, Some(text)) => ("Text".to_string(), text.clone()),
         (None, None) => ("Text".to_string(), String::new()),
     };
@@ -156,14 +183,14 @@ pub async fn send_via_graph(access_token: &str, message: OutgoingMessage) -> Smt
     info!("Graph sendMail request body length: {} bytes", request_json.len());
     info!("Graph sendMail to: {:?}", message.to);
 
-    let client = reqwest::Client::new();
+    let client = http_client(GRAPH_HOST)?;
     let response = client
         .post(GRAPH_SEND_MAIL_URL)
         .bearer_auth(access_token)
         .json(&request)
         .send()
         .await
-        .map_err(|e| SmtpError::SendFailed(format!("Graph API request failed: {}", e)))?;
+        .map_err(|e| SmtpError::Deferred(format!("Graph API request failed: {}", e)))?;
 
     let status = response.status();
     info!("Graph sendMail response status: {}", status);
@@ -177,9 +204,49 @@ pub async fn send_via_graph(access_token: &str, message: OutgoingMessage) -> Smt
             .text()
             .await
             .unwrap_or_else(|_| "Failed to read response body".to_string());
-        Err(SmtpError::SendFailed(format!(
-            "Graph API returned {}: {}",
-            status, body
-        )))
+        Err(graph_error(status, body))
+    }
+}
+
+/// Error for a failed Graph response: throttling and server errors are
+/// worth another try later
+fn graph_error(status: reqwest::StatusCode, body: String) -> SmtpError {
+    let message = format!("Graph API returned {}: {}", status, body);
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        SmtpError::Deferred(message)
+    } else {
+        SmtpError::SendFailed(message)
+    }
+}
+
+/// Send a pre-built MIME message via Graph (POST /me/sendMail with a
+/// base64 text/plain body). Graph takes recipients from the MIME headers,
+/// so Bcc is kept in the message.
+async fn send_mime_via_graph(access_token: &str, message: &OutgoingMessage) -> SmtpResult<()> {
+    let mime = build_message_with_options(message, true)?.formatted();
+    let body = base64::engine::general_purpose::STANDARD.encode(&mime);
+    info!("Graph sendMail MIME body length: {} bytes", body.len());
+
+    let client = http_client(GRAPH_HOST)?;
+    let response = client
+        .post(GRAPH_SEND_MAIL_URL)
+        .bearer_auth(access_token)
+        .header(reqwest::header::CONTENT_TYPE, "text/plain")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| SmtpError::Deferred(format!("Graph API request failed: {}", e)))?;
+
+    let status = response.status();
+    info!("Graph sendMail (MIME) response status: {}", status);
+
+    if status.is_success() {
+        Ok(())
+    } else {
+        let body = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Failed to read response body".to_string());
+        Err(graph_error(status, body))
     }
 }
@@ -0,0 +1,254 @@
+//! Reused SMTP sessions
+//!
+//! Opening a session takes a TCP connect, STARTTLS and AUTH, several round
+//! trips and more through a proxy. Rather than doing that for every message,
+//! a session is kept open once its message is sent and the next send to the
+//! same server as the same user takes it over, so a few messages in a row or
+//! an outbox flush share one session. Idle sessions get a NOOP every
+//! [`KEEPALIVE_INTERVAL`] so the server doesn't drop them, and are closed
+//! after [`IDLE_TIMEOUT`] unused. A session is checked with a NOOP before it
+//! is reused, and one that failed a send is never reused.
+
+use crate::trace::Recorder;
+use lettre::transport::smtp::client::AsyncSmtpConnection;
+use lettre::address::Envelope;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How often idle sessions are kept alive
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a session is kept open unused
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Most idle sessions kept per server and user
+const MAX_IDLE: usize = 2;
+
+/// Whose sessions can be shared: the same user on the same server
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SessionKey {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) username: String,
+}
+
+/// An authenticated connection, ready to send
+pub(crate) struct Session {
+    pub(crate) connection: AsyncSmtpConnection,
+    pub(crate) trace: Recorder,
+}
+
+impl Session {
+    /// Send one copy of a message, leaving the session ready for the next
+    pub(crate) async fn send(&mut self, envelope: &Envelope, body: &[u8]) -> Result<(), lettre::transport::smtp::Error> {
+        self.trace.client(&format!(
+            "MAIL FROM, RCPT TO ({} recipients), DATA ({} bytes)",
+            envelope.to().len(),
+            body.len()
+        ));
+        let result = self.connection.send(envelope, body).await;
+        self.trace.reply(&result);
+        result.map(|_| ())
+    }
+
+    /// Whether the server still answers
+    async fn alive(&mut self) -> bool {
+        self.trace.client("NOOP");
+        let alive = self.connection.test_connected().await;
+        if !alive {
+            self.trace.server("error: connection lost");
+        }
+        alive
+    }
+
+    /// Say goodbye, ignoring whether the server listens
+    pub(crate) async fn close(mut self) {
+        self.trace.client("QUIT");
+        let _ = self.connection.quit().await;
+    }
+}
+
+/// Sessions waiting to be reused, with when each was last used
+pub(crate) struct IdleSessions<K, S> {
+    sessions: HashMap<K, Vec<(S, Instant)>>,
+}
+
+impl<K: Hash + Eq + Clone, S> IdleSessions<K, S> {
+    fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// The most recently used session for `key`
+    fn take(&mut self, key: &K) -> Option<(S, Instant)> {
+        let sessions = self.sessions.get_mut(key)?;
+        let session = sessions.pop();
+        if sessions.is_empty() {
+            self.sessions.remove(key);
+        }
+        session
+    }
+
+    /// Keep a session for reuse, or hand it back when `key` has enough
+    fn put(&mut self, key: K, session: S, last_used: Instant) -> Option<S> {
+        let sessions = self.sessions.entry(key).or_default();
+        if sessions.len() >= MAX_IDLE {
+            return Some(session);
+        }
+        sessions.push((session, last_used));
+        sessions.sort_by_key(|(_, last_used)| *last_used);
+        None
+    }
+
+    /// Every session, to keep alive or close
+    fn drain(&mut self) -> Vec<(K, S, Instant)> {
+        self.sessions
+            .drain()
+            .flat_map(|(key, sessions)| {
+                sessions
+                    .into_iter()
+                    .map(move |(session, last_used)| (key.clone(), session, last_used))
+            })
+            .collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+struct Pool {
+    idle: IdleSessions<SessionKey, Session>,
+    /// Whether the keepalive task is running
+    keepalive: bool,
+}
+
+fn pool() -> &'static Mutex<Pool> {
+    static POOL: OnceLock<Mutex<Pool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        Mutex::new(Pool {
+            idle: IdleSessions::new(),
+            keepalive: false,
+        })
+    })
+}
+
+/// An idle session for `key` the server still answers on. Dead ones found
+/// on the way are dropped.
+pub(crate) async fn take(key: &SessionKey) -> Option<Session> {
+    loop {
+        let (mut session, _) = pool().lock().unwrap().idle.take(key)?;
+        if session.alive().await {
+            debug!("Reusing SMTP session to {}", key.host);
+            return Some(session);
+        }
+        session.connection.abort().await;
+    }
+}
+
+/// Keep a session that just sent a message for the next send. Must be
+/// called within a tokio runtime, which runs the keepalive.
+pub(crate) async fn put_back(key: SessionKey, session: Session) {
+    let surplus = {
+        let mut pool = pool().lock().unwrap();
+        let surplus = pool.idle.put(key, session, Instant::now());
+        if !pool.keepalive && !pool.idle.is_empty() {
+            pool.keepalive = true;
+            tokio::spawn(keepalive());
+        }
+        surplus
+    };
+    if let Some(session) = surplus {
+        session.close().await;
+    }
+}
+
+/// Clears [`Pool::keepalive`] if the keepalive task is dropped before it
+/// ends, as when the runtime it was spawned on shuts down, so the next
+/// [`put_back`] starts another
+struct KeepaliveRunning;
+
+impl Drop for KeepaliveRunning {
+    fn drop(&mut self) {
+        if let Ok(mut pool) = pool().lock() {
+            pool.keepalive = false;
+        }
+    }
+}
+
+/// Keep idle sessions alive, closing those unused for [`IDLE_TIMEOUT`] or
+/// that the server dropped; ends once none are left
+async fn keepalive() {
+    let running = KeepaliveRunning;
+    loop {
+        tokio::time::sleep(KEEPALIVE_INTERVAL).await;
+        let sessions = pool().lock().unwrap().idle.drain();
+        for (key, mut session, last_used) in sessions {
+            if last_used.elapsed() >= IDLE_TIMEOUT {
+                debug!("Closing idle SMTP session to {}", key.host);
+                session.close().await;
+            } else if session.alive().await {
+                let surplus = pool().lock().unwrap().idle.put(key, session, last_used);
+                if let Some(session) = surplus {
+                    session.close().await;
+                }
+            } else {
+                session.connection.abort().await;
+            }
+        }
+
+        let mut pool = pool().lock().unwrap();
+        if pool.idle.is_empty() {
+            pool.keepalive = false;
+            // Cleared under the lock already; a new task may set it again
+            // before the guard would run
+            std::mem::forget(running);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_sessions() {
+        let start = Instant::now();
+        let mut idle = IdleSessions::new();
+        assert!(idle.put("a", 1, start).is_none());
+        assert!(idle.put("a", 2, start + Duration::from_secs(1)).is_none());
+        // Enough kept for this server and user
+        assert_eq!(idle.put("a", 3, start), Some(3));
+        assert!(idle.put("b", 4, start).is_none());
+
+        // The most recently used first
+        assert_eq!(idle.take(&"a").map(|(session, _)| session), Some(2));
+        assert_eq!(idle.take(&"a").map(|(session, _)| session), Some(1));
+        assert!(idle.take(&"a").is_none());
+
+        let drained = idle.drain();
+        assert_eq!(drained.len(), 1);
+        assert_eq!((drained[0].0, drained[0].1), ("b", 4));
+        assert!(idle.is_empty());
+    }
+
+    #[test]
+    fn test_keepalive_cleared_on_shutdown() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        pool().lock().unwrap().keepalive = true;
+        runtime.spawn(keepalive());
+        // Let the task start and wait on its first tick
+        runtime.block_on(tokio::task::yield_now());
+        assert!(pool().lock().unwrap().keepalive);
+        drop(runtime);
+        assert!(!pool().lock().unwrap().keepalive);
+    }
+}
@@ -0,0 +1,345 @@
+//! Outgoing HTML cleanup
+//!
+//! Compose templates and pasted content can pull in remote resources
+//! (stylesheets, images, `@import`, CSS `url()`) that the recipient's client
+//! fetches on open — effectively a read receipt. Everything we send is
+//! self-contained, so any remote reference is stripped before sending.
+
+/// URL schemes outgoing HTML may use. Links keep the web ones; resources
+/// are held to embedded content (see [`RESOURCE_ATTRIBUTES`]).
+const URL_SCHEMES: [&str; 5] = ["http", "https", "mailto", "cid", "data"];
+
+/// Attributes whose URL a client fetches on open, which may only point at
+/// `cid:` or `data:` content. The allowlist lets only `src` through, but the
+/// rest are listed so widening it can't let them out.
+const RESOURCE_ATTRIBUTES: [&str; 4] = ["src", "srcset", "background", "poster"];
+
+/// Remove remote resources from outgoing HTML.
+///
+/// The HTML is rebuilt by ammonia from an allowlist of formatting tags and
+/// attributes, so `<script>`, `<iframe>`, `<link>`, SVG and media elements
+/// are dropped, resource attributes lose anything not `cid:`/`data:`, and
+/// remote CSS references are cut from `style` attributes and `<style>`
+/// blocks. Text content is untouched.
+pub fn sanitize_outgoing_html(html: &str) -> String {
+    let mut builder = ammonia::Builder::default();
+    // <style> has to leave the clean-content tags before it can be allowed
+    builder
+        .rm_clean_content_tags(&["style"])
+        .add_clean_content_tags(&["iframe", "object"])
+        .add_tags(&["style", "center", "font"])
+        .add_generic_attributes(&["style", "dir"])
+        .add_tag_attributes("table", &["width", "height", "border", "cellpadding", "cellspacing", "bgcolor"])
+        .add_tag_attributes("tr", &["bgcolor", "valign"])
+        .add_tag_attributes("td", &["width", "height", "valign", "bgcolor"])
+        .add_tag_attributes("th", &["width", "height", "valign", "bgcolor"])
+        .add_tag_attributes("img", &["title"])
+        .add_tag_attributes("font", &["color", "size", "face"])
+        .add_tag_attributes("div", &["align"])
+        .add_tag_attributes("p", &["align"])
+        .add_tag_attributes("a", &["title", "name"])
+        .link_rel(None)
+        .url_schemes(URL_SCHEMES.into_iter().collect())
+        .attribute_filter(|_, attribute, value| match attribute {
+            "style" => Some(strip_remote_css(value).into()),
+            _ if RESOURCE_ATTRIBUTES.contains(&attribute) && is_remote_url(value) => None,
+            _ => Some(value.into()),
+        });
+    clean_stylesheets(&builder.clean(html).to_string())
+}
+
+/// Render HTML as readable plain text for the text/plain alternative
+pub fn html_to_plain_text(html: &str) -> String {
+    let mut html = html.to_string();
+    for tag in ["script", "style", "head"] {
+        html = remove_elements(&html, tag);
+    }
+
+    let mut text = String::with_capacity(html.len());
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset;
+        text.push_str(&html[pos..start]);
+        let Some(len) = lower[start..].find('>') else {
+            pos = html.len();
+            break;
+        };
+        let tag = &lower[start..start + len + 1];
+        let closing = tag.starts_with("</");
+        match tag_name(tag) {
+            "br" => text.push('\n'),
+            "p" | "div" | "tr" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote"
+                if closing =>
+            {
+                text.push('\n')
+            }
+            "li" if !closing => text.push_str("\n- "),
+            _ => {}
+        }
+        pos = start + len + 1;
+    }
+    text.push_str(&html[pos..]);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    // Collapse runs of blank lines left behind by nested blocks
+    let mut result = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result.trim().to_string()
+}
+
+/// Lowercase tag name of a tag slice such as `<img src=...>` or `</p>`
+fn tag_name(tag: &str) -> &str {
+    let inner = tag.trim_start_matches('<').trim_start_matches('/');
+    let end = inner
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(inner.len());
+    &inner[..end]
+}
+
+/// Remove `<tag ...>...</tag>` elements including their content
+fn remove_elements(html: &str, tag: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let open = format!("<{}", tag);
+    let close = format!("</{}", tag);
+    let mut out = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find(&open) {
+        let start = pos + offset;
+        let after = lower[start + open.len()..].chars().next();
+        if matches!(after, Some(c) if c.is_ascii_alphanumeric()) {
+            // e.g. `<objective>` when looking for `<object`
+            out.push_str(&html[pos..start + open.len()]);
+            pos = start + open.len();
+            continue;
+        }
+        out.push_str(&html[pos..start]);
+        pos = match lower[start..].find(&close) {
+            Some(i) => {
+                let close_start = start + i;
+                lower[close_start..]
+                    .find('>')
+                    .map(|j| close_start + j + 1)
+                    .unwrap_or(html.len())
+            }
+            // No closing tag: drop just the opening tag
+            None => lower[start..]
+                .find('>')
+                .map(|j| start + j + 1)
+                .unwrap_or(html.len()),
+        };
+    }
+    out.push_str(&html[pos..]);
+    out
+}
+
+/// Whether a URL points outside the message
+fn is_remote_url(url: &str) -> bool {
+    let url = url
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .trim()
+        .to_ascii_lowercase();
+    !(url.starts_with("cid:") || url.starts_with("data:") || url.starts_with('#'))
+}
+
+/// Replace remote CSS `url(...)` references with `none`, after dropping
+/// the declarations that could reference a resource some other way
+fn strip_remote_css(css: &str) -> String {
+    let css = drop_hidden_references(css);
+    let lower = css.to_ascii_lowercase();
+    let mut out = String::with_capacity(css.len());
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find("url(") {
+        let start = pos + offset;
+        let Some(len) = lower[start..].find(')') else {
+            break;
+        };
+        let end = start + len + 1;
+        out.push_str(&css[pos..start]);
+        let inner = &css[start + 4..end - 1];
+        if is_remote_url(inner) {
+            out.push_str("none");
+        } else {
+            out.push_str(&css[start..end]);
+        }
+        pos = end;
+    }
+    out.push_str(&css[pos..]);
+    out
+}
+
+/// Drop declarations (or selectors) with an `image-set()`, which takes
+/// plain strings as URLs, or a backslash, which can escape `url(` into a
+/// form the search above doesn't see
+fn drop_hidden_references(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut pos = 0;
+
+    for (end, delimiter) in css.match_indices([';', '{', '}']) {
+        let part = &css[pos..end];
+        if is_hidden_reference(part) {
+            // Keep braces so the blocks stay balanced
+            if delimiter != ";" {
+                out.push_str(delimiter);
+            }
+        } else {
+            out.push_str(part);
+            out.push_str(delimiter);
+        }
+        pos = end + delimiter.len();
+    }
+    let rest = &css[pos..];
+    if !is_hidden_reference(rest) {
+        out.push_str(rest);
+    }
+    out
+}
+
+/// Whether a piece of CSS has what [`drop_hidden_references`] drops
+fn is_hidden_reference(css: &str) -> bool {
+    css.contains('\\') || css.to_ascii_lowercase().contains("image-set")
+}
+
+/// Strip `@import` rules and remote `url()`s from the `<style>` elements
+/// of sanitized HTML, where each is a bare `<style>` tag
+fn clean_stylesheets(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut pos = 0;
+
+    while let Some(offset) = html[pos..].find("<style>") {
+        let start = pos + offset + "<style>".len();
+        let end = html[start..]
+            .find("</style>")
+            .map_or(html.len(), |i| start + i);
+        out.push_str(&html[pos..start]);
+        out.push_str(&strip_remote_css(&strip_imports(&html[start..end])));
+        pos = end;
+    }
+    out.push_str(&html[pos..]);
+    out
+}
+
+/// Remove `@import` rules from a stylesheet
+fn strip_imports(css: &str) -> String {
+    let lower = css.to_ascii_lowercase();
+    let mut out = String::with_capacity(css.len());
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find("@import") {
+        let start = pos + offset;
+        out.push_str(&css[pos..start]);
+        pos = lower[start..]
+            .find(';')
+            .map(|i| start + i + 1)
+            .unwrap_or(css.len());
+    }
+    out.push_str(&css[pos..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_remote_images_and_links() {
+        let html = r#"<link rel="stylesheet" href="https://t.example/a.css"><p>Hi<img src="https://t.example/pixel.gif" width=1><img src="cid:logo"></p>"#;
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            r#"<p>Hi<img width="1"><img src="cid:logo"></p>"#
+        );
+    }
+
+    #[test]
+    fn test_strips_remote_resource_attributes() {
+        let html = r#"<img src="cid:logo" srcset="https://t.example/a.png 2x"><table background="https://t.example/b.png"><tr><td background="https://t.example/c.png">x</td></tr></table>"#;
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            r#"<img src="cid:logo"><table><tbody><tr><td>x</td></tr></tbody></table>"#
+        );
+    }
+
+    #[test]
+    fn test_strips_media_and_svg() {
+        let html = r#"<video poster="https://t.example/p.png" src="cid:clip"></video><svg><image href="https://t.example/i.png"/></svg><p>x</p>"#;
+        assert_eq!(sanitize_outgoing_html(html), "<p>x</p>");
+    }
+
+    #[test]
+    fn test_quoted_angle_bracket() {
+        let html = r#"<img alt="a>b" src="https://t.example/p.gif"><a href="https://example.com">link</a>"#;
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            r#"<img alt="a>b"><a href="https://example.com">link</a>"#
+        );
+    }
+
+    #[test]
+    fn test_strips_remote_css() {
+        let html = "<style>@import url(https://t.example/x.css); body { color: red; background: url('https://t.example/b.png') }</style><div style=\"background:url(http://t.example/c.png)\">x</div>";
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            "<style> body { color: red; background: none }</style><div style=\"background:none\">x</div>"
+        );
+    }
+
+    #[test]
+    fn test_strips_image_set() {
+        let html = "<style>p { color: red; background: image-set(\"https://t.example/a.png\" 1x) }</style><div style=\"color:red;background:-webkit-image-set('https://t.example/b.png' 1x)\">x</div>";
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            "<style>p { color: red;}</style><div style=\"color:red;\">x</div>"
+        );
+    }
+
+    #[test]
+    fn test_strips_escaped_url() {
+        let html = "<style>p { background: \\75rl(https://t.example/a.png); color: red }</style><div style=\"background:\\75 rl(https://t.example/b.png)\">x</div>";
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            "<style>p { color: red }</style><div style=\"\">x</div>"
+        );
+    }
+
+    #[test]
+    fn test_leaves_text_alone() {
+        let html = "<p>see url(http://example.com) and <b>bold</b></p><script>track()</script>";
+        assert_eq!(
+            sanitize_outgoing_html(html),
+            "<p>see url(http://example.com) and <b>bold</b></p>"
+        );
+    }
+
+    #[test]
+    fn test_html_to_plain_text() {
+        let html = "<style>p{}</style><p>Hello &amp; welcome</p><ul><li>one</li><li>two</li></ul>Bye<br>now";
+        assert_eq!(
+            html_to_plain_text(html),
+            "Hello & welcome\n\n- one\n- two\nBye\nnow"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Opt-in protocol trace
+//!
+//! When enabled (see [`northmail_trace`]), sends record each SMTP step and
+//! the server's reply into the shared trace that can be copied into a bug
+//! report. Credentials and message content are never recorded, only the
+//! mechanism and the message size.
+
+use lettre::transport::smtp::response::Response;
+
+/// Records one connection's exchange; does nothing if tracing was off when it started
+pub(crate) struct Recorder(Option<(u64, String)>);
+
+impl Recorder {
+    pub(crate) fn start(host: &str) -> Self {
+        let recorder = Self(northmail_trace::connection().map(|id| (id, host.to_string())));
+        recorder.record("--", "connected");
+        recorder
+    }
+
+    /// A step we took
+    pub(crate) fn client(&self, text: &str) {
+        self.record("C:", text);
+    }
+
+    /// What the server said back
+    pub(crate) fn server(&self, text: &str) {
+        self.record("S:", text);
+    }
+
+    /// The server's reply to a command, or why it failed
+    pub(crate) fn reply(&self, result: &Result<Response, lettre::transport::smtp::Error>) {
+        match result {
+            Ok(response) => self.server(&format!(
+                "{} {}",
+                response.code(),
+                response.first_line().unwrap_or_default()
+            )),
+            Err(e) => self.server(&format!("error: {}", e)),
+        }
+    }
+
+    fn record(&self, direction: &str, text: &str) {
+        let Some((id, host)) = &self.0 else { return };
+        northmail_trace::record("SMTP", *id, host, direction, text);
+    }
+}
@@ -0,0 +1,155 @@
+//! Opt-in protocol trace
+//!
+//! When enabled, the IMAP and SMTP clients record what they send and
+//! receive on connections opened from then on, each with its own redaction,
+//! into one ring buffer, so a failing sync or send can be diagnosed from a
+//! copied log instead of a rebuild with extra tracing. Lines from all
+//! connections are kept in the order they happened, numbered by connection.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bytes of trace kept, oldest lines dropped first
+const CAPACITY: usize = 320 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(1);
+static BUFFER: Mutex<RingBuffer> = Mutex::new(RingBuffer::new(CAPACITY));
+
+/// Turn tracing on or off for new connections
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether new connections are traced
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Number for a new traced connection, or `None` if tracing is off
+pub fn connection() -> Option<u64> {
+    enabled().then(|| NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Add a line for `protocol` connection `id` to `host`; `direction` is `C:`
+/// for what we sent, `S:` for what the server did, `--` for events
+pub fn record(protocol: &str, id: u64, host: &str, direction: &str, text: &str) {
+    let line = format!(
+        "{} {} #{} {} {} {}",
+        timestamp(),
+        protocol,
+        id,
+        host,
+        direction,
+        text.trim_end()
+    );
+    BUFFER.lock().unwrap().push(line);
+}
+
+/// The most recent lines, at most `max_bytes` long
+pub fn dump(max_bytes: usize) -> String {
+    BUFFER.lock().unwrap().dump(max_bytes)
+}
+
+/// Discard everything recorded so far
+pub fn clear() {
+    BUFFER.lock().unwrap().clear();
+}
+
+/// Lines kept up to a total size, oldest dropped first
+#[derive(Debug)]
+struct RingBuffer {
+    lines: VecDeque<String>,
+    bytes: usize,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            bytes: 0,
+            capacity,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        self.bytes += line.len() + 1;
+        self.lines.push_back(line);
+        while self.bytes > self.capacity {
+            match self.lines.pop_front() {
+                Some(old) => self.bytes -= old.len() + 1,
+                None => break,
+            }
+        }
+    }
+
+    fn dump(&self, max_bytes: usize) -> String {
+        let mut size = 0;
+        let mut start = self.lines.len();
+        for line in self.lines.iter().rev() {
+            if size + line.len() + 1 > max_bytes {
+                break;
+            }
+            size += line.len() + 1;
+            start -= 1;
+        }
+        self.lines.range(start..).fold(String::with_capacity(size), |mut out, line| {
+            out.push_str(line);
+            out.push('\n');
+            out
+        })
+    }
+
+    fn clear(&mut self) {
+        self.lines.clear();
+        self.bytes = 0;
+    }
+}
+
+/// UTC time of day with milliseconds
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs() % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        now.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer() {
+        let mut ring = RingBuffer::new(20);
+        for line in ["one", "two", "three", "four", "five"] {
+            ring.push(line.to_string());
+        }
+        // 20 bytes holds the last four lines with their newlines
+        assert_eq!(ring.dump(usize::MAX), "two\nthree\nfour\nfive\n");
+        assert_eq!(ring.dump(10), "four\nfive\n");
+        ring.clear();
+        assert_eq!(ring.dump(usize::MAX), "");
+    }
+
+    #[test]
+    fn test_record() {
+        assert_eq!(connection(), None);
+        set_enabled(true);
+        let imap = connection().unwrap();
+        let smtp = connection().unwrap();
+        assert_ne!(imap, smtp);
+        record("IMAP", imap, "imap.example.com", "C:", "A1 NOOP\r\n");
+        record("SMTP", smtp, "smtp.example.com", "S:", "250 OK");
+        let lines: Vec<String> = dump(usize::MAX).lines().map(str::to_string).collect();
+        assert!(lines[0].ends_with(&format!(" IMAP #{} imap.example.com C: A1 NOOP", imap)));
+        assert!(lines[1].ends_with(&format!(" SMTP #{} smtp.example.com S: 250 OK", smtp)));
+    }
+}